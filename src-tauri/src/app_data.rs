@@ -0,0 +1,331 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Backup and restore of everything the user configured: the backend stores
+//! (tags, bookmarks, saved connections, ...) and the frontend user data
+//! (settings, workspaces, home banner media). Secrets stay in the OS keychain
+//! and are never part of a backup.
+
+use crate::json_store;
+use crate::utils::{normalize_path, to_os_path};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+use zip::write::SimpleFileOptions;
+
+const BACKUP_FORMAT: &str = "sigma-file-manager-backup";
+const BACKUP_FORMAT_VERSION: u32 = 1;
+const MANIFEST_NAME: &str = "manifest.json";
+const BACKUP_EXTENSION: &str = "sfmbackup";
+
+// Folders of the app data directory that make up a backup
+const BACKUP_DIRS: [&str; 2] = ["stores", "user-data"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format: String,
+    pub format_version: u32,
+    pub app_version: String,
+    pub created_at: u64,
+    pub entries: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportResult {
+    pub path: String,
+    pub entry_count: u32,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub manifest: BackupManifest,
+    pub restored_count: u32,
+    /// Export of the data that was replaced, taken right before the import
+    pub previous_data_backup: Option<String>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// Temporary and quarantined store files are left out
+fn is_backup_file(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    !name.ends_with(".tmp") && !name.ends_with(".corrupt")
+}
+
+fn backup_file_name() -> String {
+    let timestamp = now_ms() / 1000;
+    format!(
+        "sigma-file-manager-backup-{}.{}",
+        timestamp, BACKUP_EXTENSION
+    )
+}
+
+fn write_archive(
+    data_dir: &Path,
+    archive_path: &Path,
+    app_version: &str,
+) -> Result<ExportResult, String> {
+    let mut files: Vec<(String, PathBuf)> = Vec::new();
+
+    for dir_name in BACKUP_DIRS {
+        let dir = data_dir.join(dir_name);
+
+        if !dir.is_dir() {
+            continue;
+        }
+
+        for entry in WalkDir::new(&dir).follow_links(false) {
+            let entry = entry.map_err(|error| error.to_string())?;
+
+            if !entry.file_type().is_file() || !is_backup_file(entry.path()) {
+                continue;
+            }
+
+            let relative_path = entry
+                .path()
+                .strip_prefix(data_dir)
+                .map_err(|error| error.to_string())?;
+            files.push((
+                normalize_path(&relative_path.to_string_lossy()),
+                entry.path().to_path_buf(),
+            ));
+        }
+    }
+
+    let manifest = BackupManifest {
+        format: BACKUP_FORMAT.to_string(),
+        format_version: BACKUP_FORMAT_VERSION,
+        app_version: app_version.to_string(),
+        created_at: now_ms(),
+        entries: files.iter().map(|(name, _)| name.clone()).collect(),
+    };
+
+    if let Some(parent) = archive_path.parent() {
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+
+    let temp_path = archive_path.with_extension("tmp");
+    let archive_file = File::create(&temp_path).map_err(|error| error.to_string())?;
+    let mut writer = zip::ZipWriter::new(archive_file);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let write_result = (|| -> Result<u64, String> {
+        let manifest_json =
+            serde_json::to_vec_pretty(&manifest).map_err(|error| error.to_string())?;
+        writer
+            .start_file(MANIFEST_NAME, options)
+            .map_err(|error| error.to_string())?;
+        writer
+            .write_all(&manifest_json)
+            .map_err(|error| error.to_string())?;
+
+        let mut total_bytes = 0;
+
+        for (name, path) in &files {
+            let mut source = File::open(path).map_err(|error| error.to_string())?;
+            writer
+                .start_file(name.as_str(), options)
+                .map_err(|error| error.to_string())?;
+            total_bytes +=
+                std::io::copy(&mut source, &mut writer).map_err(|error| error.to_string())?;
+        }
+
+        writer.finish().map_err(|error| error.to_string())?;
+        Ok(total_bytes)
+    })();
+
+    let total_bytes = match write_result {
+        Ok(total_bytes) => total_bytes,
+        Err(error) => {
+            let _ = fs::remove_file(&temp_path);
+            return Err(error);
+        }
+    };
+
+    fs::rename(&temp_path, archive_path).map_err(|error| error.to_string())?;
+
+    Ok(ExportResult {
+        path: normalize_path(&archive_path.to_string_lossy()),
+        entry_count: files.len() as u32,
+        total_bytes,
+    })
+}
+
+fn read_manifest<R: Read + std::io::Seek>(
+    archive: &mut zip::ZipArchive<R>,
+) -> Result<BackupManifest, String> {
+    let mut manifest_file = archive
+        .by_name(MANIFEST_NAME)
+        .map_err(|_| "Not a Sigma File Manager backup: manifest is missing".to_string())?;
+    let mut manifest_json = String::new();
+    manifest_file
+        .read_to_string(&mut manifest_json)
+        .map_err(|error| error.to_string())?;
+
+    let manifest: BackupManifest =
+        serde_json::from_str(&manifest_json).map_err(|error| error.to_string())?;
+
+    if manifest.format != BACKUP_FORMAT {
+        return Err("Not a Sigma File Manager backup".to_string());
+    }
+
+    if manifest.format_version > BACKUP_FORMAT_VERSION {
+        return Err(format!(
+            "Backup was created by a newer version of the app ({})",
+            manifest.app_version
+        ));
+    }
+
+    Ok(manifest)
+}
+
+// Only files inside the backup folders are restored, so a crafted archive
+// can't write anywhere else in the app data directory or outside of it
+fn restore_target(data_dir: &Path, entry_path: &Path) -> Option<PathBuf> {
+    let first_component = entry_path.components().next()?;
+    let is_backup_dir = BACKUP_DIRS
+        .iter()
+        .any(|dir_name| first_component.as_os_str() == *dir_name);
+
+    is_backup_dir.then(|| data_dir.join(entry_path))
+}
+
+fn restore_archive(
+    data_dir: &Path,
+    archive_path: &Path,
+    app_version: &str,
+) -> Result<ImportResult, String> {
+    let archive_file = File::open(archive_path).map_err(|error| error.to_string())?;
+    let mut archive = zip::ZipArchive::new(archive_file).map_err(|error| error.to_string())?;
+    let manifest = read_manifest(&mut archive)?;
+
+    // Extract everything first so a damaged archive leaves current data intact
+    let staging_dir = data_dir.join("import-staging");
+    let _ = fs::remove_dir_all(&staging_dir);
+    let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();
+
+    let extract_result = (|| -> Result<(), String> {
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(|error| error.to_string())?;
+
+            if entry.is_dir() {
+                continue;
+            }
+
+            let Some(entry_path) = entry.enclosed_name() else {
+                continue;
+            };
+            let Some(target_path) = restore_target(data_dir, &entry_path) else {
+                continue;
+            };
+
+            let staged_path = staging_dir.join(&entry_path);
+            if let Some(parent) = staged_path.parent() {
+                fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+            }
+
+            let mut staged_file = File::create(&staged_path).map_err(|error| error.to_string())?;
+            std::io::copy(&mut entry, &mut staged_file).map_err(|error| error.to_string())?;
+            staged.push((staged_path, target_path));
+        }
+
+        Ok(())
+    })();
+
+    if let Err(error) = extract_result {
+        let _ = fs::remove_dir_all(&staging_dir);
+        return Err(error);
+    }
+
+    let previous_data_backup = write_archive(
+        data_dir,
+        &data_dir.join("backups").join(backup_file_name()),
+        app_version,
+    )
+    .map(|result| result.path)
+    .map_err(|error| tracing::warn!("Failed to back up data before import: {}", error))
+    .ok();
+
+    let mut restored_count = 0;
+
+    for (staged_path, target_path) in &staged {
+        if let Some(parent) = target_path.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+
+        if fs::rename(staged_path, target_path).is_err() {
+            fs::copy(staged_path, target_path).map_err(|error| error.to_string())?;
+        }
+
+        restored_count += 1;
+    }
+
+    let _ = fs::remove_dir_all(&staging_dir);
+
+    Ok(ImportResult {
+        manifest,
+        restored_count,
+        previous_data_backup,
+    })
+}
+
+/// Packages settings, workspaces, tags, bookmarks, saved connections and the
+/// other stores into a single archive. `destination` is either the archive
+/// path or a directory to create a timestamped archive in.
+#[tauri::command]
+pub async fn export_app_data(
+    app: tauri::AppHandle,
+    destination: String,
+) -> Result<ExportResult, String> {
+    let data_dir = json_store::data_dir()?;
+    let app_version = app.package_info().version.to_string();
+
+    tokio::task::spawn_blocking(move || {
+        let destination = to_os_path(&destination);
+        let archive_path = if destination.is_dir() {
+            destination.join(backup_file_name())
+        } else {
+            destination
+        };
+
+        write_archive(&data_dir, &archive_path, &app_version)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Restores an archive made by `export_app_data`, replacing the current
+/// data. The current data is exported to `<app data>/backups` first. The app
+/// restarts afterwards, since stores are held in memory while it runs.
+#[tauri::command]
+pub async fn import_app_data(
+    app: tauri::AppHandle,
+    archive: String,
+) -> Result<ImportResult, String> {
+    let data_dir = json_store::data_dir()?;
+    let app_version = app.package_info().version.to_string();
+
+    let result = tokio::task::spawn_blocking(move || {
+        restore_archive(&data_dir, &to_os_path(&archive), &app_version)
+    })
+    .await
+    .map_err(|error| error.to_string())??;
+
+    app.request_restart();
+
+    Ok(result)
+}
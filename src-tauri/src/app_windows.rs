@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Coordination between app windows. Backend stores are shared by all
+//! windows; each change is announced with a `store-changed` event so other
+//! windows can reload, while progress of long-running jobs only goes to the
+//! window that started them.
+
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, EventTarget, Manager};
+
+pub const STORE_CHANGED_EVENT: &str = "store-changed";
+
+static APP_HANDLE: OnceCell<AppHandle> = OnceCell::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreChangedEvent {
+    /// Store name: "bookmarks", "tags", "history", "network_shares"
+    pub store: String,
+    /// Label of the window that made the change, so it can skip reloading
+    pub origin_window: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppWindowInfo {
+    pub label: String,
+    pub title: String,
+    pub is_focused: bool,
+    pub is_visible: bool,
+}
+
+pub fn init(app: &AppHandle) {
+    let _ = APP_HANDLE.set(app.clone());
+}
+
+/// Tells every window that a shared store changed
+pub fn notify_store_changed(store: &str, origin_window: Option<&str>) {
+    let Some(app) = APP_HANDLE.get() else {
+        return;
+    };
+
+    let payload = StoreChangedEvent {
+        store: store.to_string(),
+        origin_window: origin_window.map(str::to_string),
+    };
+
+    if let Err(error) = app.emit(STORE_CHANGED_EVENT, payload) {
+        tracing::warn!("Failed to emit {}: {}", STORE_CHANGED_EVENT, error);
+    }
+}
+
+/// Sends an event to the window that owns a job. Falls back to all windows
+/// when the owner is unknown or was closed in the meantime.
+pub fn emit_to_owner<S: Serialize + Clone>(
+    app: &AppHandle,
+    owner_window: Option<&str>,
+    event: &str,
+    payload: S,
+) {
+    let result = match owner_window.filter(|label| app.get_webview_window(label).is_some()) {
+        Some(label) => app.emit_to(EventTarget::webview_window(label), event, payload),
+        None => app.emit(event, payload),
+    };
+
+    if let Err(error) = result {
+        tracing::warn!("Failed to emit {}: {}", event, error);
+    }
+}
+
+#[tauri::command]
+pub fn list_app_windows(app: AppHandle) -> Vec<AppWindowInfo> {
+    app.webview_windows()
+        .into_values()
+        .map(|window| AppWindowInfo {
+            label: window.label().to_string(),
+            title: window.title().unwrap_or_default(),
+            is_focused: window.is_focused().unwrap_or(false),
+            is_visible: window.is_visible().unwrap_or(false),
+        })
+        .collect()
+}
@@ -0,0 +1,314 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use crate::file_operations::FileOperationResult;
+use crate::utils::normalize_path;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A single transformation applied to each name, in recipe order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RenameStep {
+    /// Transliterates any script to ASCII ("Привет" becomes "Privet")
+    Transliterate,
+    /// ASCII, lowercase, words joined by `separator` (default "-")
+    Slugify {
+        separator: Option<String>,
+    },
+    StripDiacritics,
+    /// Collapses whitespace runs into single spaces and trims the ends
+    NormalizeWhitespace,
+    Lowercase,
+    Uppercase,
+    Replace {
+        find: String,
+        replace: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenamePreview {
+    pub source_path: String,
+    pub new_name: String,
+    pub new_path: String,
+    pub is_unchanged: bool,
+    pub error: Option<String>,
+}
+
+fn slugify(value: &str, separator: &str) -> String {
+    let ascii = deunicode::deunicode(value).to_lowercase();
+    let mut slug = String::new();
+    let mut pending_separator = false;
+
+    for character in ascii.chars() {
+        if character.is_ascii_alphanumeric() {
+            if pending_separator && !slug.is_empty() {
+                slug.push_str(separator);
+            }
+            slug.push(character);
+            pending_separator = false;
+        } else {
+            pending_separator = true;
+        }
+    }
+
+    slug
+}
+
+fn apply_step(value: &str, step: &RenameStep) -> String {
+    match step {
+        RenameStep::Transliterate => deunicode::deunicode(value),
+        RenameStep::Slugify { separator } => slugify(value, separator.as_deref().unwrap_or("-")),
+        RenameStep::StripDiacritics => crate::text::strip_diacritics(value),
+        RenameStep::NormalizeWhitespace => {
+            value.split_whitespace().collect::<Vec<&str>>().join(" ")
+        }
+        RenameStep::Lowercase => value.to_lowercase(),
+        RenameStep::Uppercase => value.to_uppercase(),
+        RenameStep::Replace { find, replace } => {
+            if find.is_empty() {
+                value.to_string()
+            } else {
+                value.replace(find.as_str(), replace)
+            }
+        }
+    }
+}
+
+fn apply_steps(value: &str, steps: &[RenameStep]) -> String {
+    steps
+        .iter()
+        .fold(crate::text::normalize_name(value), |current, step| {
+            apply_step(&current, step)
+        })
+}
+
+// Applies the recipe to the stem only, unless `include_extension` is set
+fn build_new_name(path: &Path, steps: &[RenameStep], include_extension: bool) -> Option<String> {
+    let file_name = path.file_name()?.to_string_lossy().to_string();
+
+    if include_extension || path.is_dir() {
+        return Some(apply_steps(&file_name, steps));
+    }
+
+    match file_name.rfind('.') {
+        Some(dot_index) if dot_index > 0 => Some(format!(
+            "{}{}",
+            apply_steps(&file_name[..dot_index], steps),
+            &file_name[dot_index..]
+        )),
+        _ => Some(apply_steps(&file_name, steps)),
+    }
+}
+
+fn validate_new_name(new_name: &str) -> Result<(), String> {
+    if new_name.trim().is_empty() || new_name == "." || new_name == ".." {
+        return Err("Resulting name is empty".to_string());
+    }
+
+    if new_name.contains('/') || new_name.contains('\\') {
+        return Err("Resulting name contains path separators".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+pub(crate) fn is_same_file(first: &Path, second: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (fs::metadata(first), fs::metadata(second)) {
+        (Ok(first_metadata), Ok(second_metadata)) => {
+            first_metadata.dev() == second_metadata.dev()
+                && first_metadata.ino() == second_metadata.ino()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn is_same_file(first: &Path, second: &Path) -> bool {
+    match (fs::canonicalize(first), fs::canonicalize(second)) {
+        (Ok(first_path), Ok(second_path)) => {
+            first_path.to_string_lossy().to_lowercase()
+                == second_path.to_string_lossy().to_lowercase()
+        }
+        _ => false,
+    }
+}
+
+fn build_previews(
+    paths: &[String],
+    steps: &[RenameStep],
+    include_extension: bool,
+) -> Vec<RenamePreview> {
+    let mut previews: Vec<RenamePreview> = paths
+        .iter()
+        .map(|path_str| {
+            let source = Path::new(path_str);
+            let source_normalized = normalize_path(path_str);
+
+            let mut preview = RenamePreview {
+                source_path: source_normalized.clone(),
+                new_name: String::new(),
+                new_path: source_normalized.clone(),
+                is_unchanged: true,
+                error: None,
+            };
+
+            match (
+                build_new_name(source, steps, include_extension),
+                source.parent(),
+            ) {
+                (Some(new_name), Some(parent)) if source.exists() => {
+                    preview.new_path = normalize_path(&parent.join(&new_name).to_string_lossy());
+                    preview.is_unchanged = preview.new_path == source_normalized;
+                    preview.new_name = new_name;
+                }
+                _ => preview.error = Some(format!("Path does not exist: {}", path_str)),
+            }
+
+            preview
+        })
+        .collect();
+
+    // Targets occupied by batch items are free only when those items move away
+    let moving_sources: HashSet<String> = previews
+        .iter()
+        .filter(|preview| preview.error.is_none() && !preview.is_unchanged)
+        .map(|preview| preview.source_path.clone())
+        .collect();
+    let mut claimed_paths: HashSet<String> = HashSet::new();
+
+    for preview in previews.iter_mut() {
+        if preview.error.is_some() || preview.is_unchanged {
+            continue;
+        }
+
+        let source = Path::new(&preview.source_path);
+        let new_path = Path::new(&preview.new_path);
+
+        if let Err(error) = validate_new_name(&preview.new_name) {
+            preview.error = Some(error);
+        } else if !claimed_paths.insert(preview.new_path.to_lowercase()) {
+            preview.error = Some(format!(
+                "Another item in the batch is also renamed to '{}'",
+                preview.new_name
+            ));
+        } else if new_path.exists()
+            && !moving_sources.contains(&preview.new_path)
+            && !is_same_file(source, new_path)
+        {
+            preview.error = Some(format!(
+                "A file or folder with the name '{}' already exists",
+                preview.new_name
+            ));
+        }
+    }
+
+    previews
+}
+
+/// Shows what `batch_rename` would do without touching any files
+#[tauri::command]
+pub fn preview_batch_rename(
+    paths: Vec<String>,
+    steps: Vec<RenameStep>,
+    include_extension: Option<bool>,
+) -> Vec<RenamePreview> {
+    build_previews(&paths, &steps, include_extension.unwrap_or(false))
+}
+
+/// Renames all items with the recipe. Nothing is renamed when any item
+/// would conflict. Items are moved through temporary names first so
+/// swaps within the batch and case-only changes work.
+#[tauri::command]
+pub fn batch_rename(
+    paths: Vec<String>,
+    steps: Vec<RenameStep>,
+    include_extension: Option<bool>,
+) -> FileOperationResult {
+    let previews = build_previews(&paths, &steps, include_extension.unwrap_or(false));
+
+    if let Some(error) = previews.iter().find_map(|preview| preview.error.clone()) {
+        return FileOperationResult {
+            success: false,
+            error: Some(error),
+            copied_count: Some(0),
+            failed_count: Some(
+                previews
+                    .iter()
+                    .filter(|preview| preview.error.is_some())
+                    .count() as u32,
+            ),
+            skipped_count: Some(0),
+            ..Default::default()
+        };
+    }
+
+    let pending: Vec<&RenamePreview> = previews
+        .iter()
+        .filter(|preview| !preview.is_unchanged)
+        .collect();
+    let mut staged: Vec<(PathBuf, &RenamePreview)> = Vec::new();
+    let mut failed_count: u32 = 0;
+    let mut last_error: Option<String> = None;
+
+    for (index, preview) in pending.iter().enumerate() {
+        let source = Path::new(&preview.source_path);
+        let temp_path = match source.parent() {
+            Some(parent) => parent.join(format!(".sigma-rename-{}-{}", std::process::id(), index)),
+            None => continue,
+        };
+
+        match fs::rename(source, &temp_path) {
+            Ok(()) => staged.push((temp_path, *preview)),
+            Err(error) => {
+                failed_count += 1;
+                last_error = Some(format!("{}: {}", preview.source_path, error));
+            }
+        }
+    }
+
+    let mut renamed_count: u32 = 0;
+
+    for (temp_path, preview) in staged {
+        let new_path = Path::new(&preview.new_path);
+
+        // Never let the rename silently replace an item that appeared meanwhile
+        let result = if new_path.exists() {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "destination already exists",
+            ))
+        } else {
+            fs::rename(&temp_path, new_path)
+        };
+
+        match result {
+            Ok(()) => {
+                crate::tags::handle_path_moved(Path::new(&preview.source_path), new_path);
+                renamed_count += 1;
+            }
+            Err(error) => {
+                // Put the item back under its original name
+                let _ = fs::rename(&temp_path, &preview.source_path);
+                failed_count += 1;
+                last_error = Some(format!("{}: {}", preview.source_path, error));
+            }
+        }
+    }
+
+    FileOperationResult {
+        error: last_error,
+        ..FileOperationResult::counts(
+            renamed_count,
+            failed_count,
+            (previews.len() - pending.len()) as u32,
+        )
+    }
+}
@@ -0,0 +1,246 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use crate::app_windows::notify_store_changed;
+use crate::json_store;
+use crate::utils::normalize_path;
+use crate::volume_paths::{MountedVolumes, VolumePath};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use sysinfo::{Disk, Disks};
+
+const STORE_NAME: &str = "bookmarks";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    /// Label of the removable volume the bookmark lives on
+    #[serde(default)]
+    pub volume_label: Option<String>,
+    /// Path relative to that volume's mount point
+    #[serde(default)]
+    pub relative_path: Option<String>,
+    /// Where the bookmark is on its volume, wherever that is mounted. Older
+    /// bookmarks only have the volume label.
+    #[serde(default)]
+    pub volume_path: Option<VolumePath>,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookmarkInfo {
+    #[serde(flatten)]
+    pub bookmark: Bookmark,
+    pub exists: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BookmarkStore {
+    #[serde(default)]
+    bookmarks: Vec<Bookmark>,
+}
+
+static BOOKMARK_STORE: Lazy<Mutex<BookmarkStore>> =
+    Lazy::new(|| Mutex::new(json_store::load(STORE_NAME)));
+
+fn volume_label(disk: &Disk) -> Option<String> {
+    // Linux device names (/dev/sdb1) aren't stable, the mount directory
+    // created by udisks is named after the volume label instead
+    #[cfg(target_os = "linux")]
+    let label = disk
+        .mount_point()
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string());
+
+    #[cfg(not(target_os = "linux"))]
+    let label = Some(disk.name().to_string_lossy().to_string());
+
+    label.filter(|label| !label.is_empty())
+}
+
+// Finds the removable volume containing `path` and returns its label and
+// the path relative to its mount point
+fn removable_location(disks: &Disks, path: &Path) -> Option<(String, String)> {
+    let disk = disks
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())?;
+
+    if !disk.is_removable() {
+        return None;
+    }
+
+    let relative_path = path.strip_prefix(disk.mount_point()).ok()?;
+    Some((
+        volume_label(disk)?,
+        normalize_path(&relative_path.to_string_lossy()),
+    ))
+}
+
+// Looks for the bookmark's volume under a different mount point.
+// Returns the new path when the target exists there.
+fn re_resolve(volumes: &MountedVolumes, disks: &Disks, bookmark: &Bookmark) -> Option<String> {
+    if let Some(volume_path) = &bookmark.volume_path {
+        return volumes
+            .resolve(volume_path)
+            .map(|path| normalize_path(&path.to_string_lossy()));
+    }
+
+    let label = bookmark.volume_label.as_ref()?;
+    let relative_path = bookmark.relative_path.as_ref()?;
+
+    disks
+        .iter()
+        .filter(|disk| volume_label(disk).as_ref() == Some(label))
+        .map(|disk| disk.mount_point().join(relative_path))
+        .find(|candidate: &PathBuf| candidate.exists())
+        .map(|candidate| normalize_path(&candidate.to_string_lossy()))
+}
+
+fn to_info(bookmark: &Bookmark) -> BookmarkInfo {
+    BookmarkInfo {
+        exists: Path::new(&bookmark.path).exists(),
+        bookmark: bookmark.clone(),
+    }
+}
+
+/// Adds a bookmark. Adding an already bookmarked path returns the existing one.
+#[tauri::command]
+pub fn add_bookmark(
+    window: tauri::WebviewWindow,
+    path: String,
+    name: Option<String>,
+) -> Result<Bookmark, String> {
+    let target = Path::new(&path);
+
+    if !target.exists() {
+        return Err(format!("Path does not exist: {}", path));
+    }
+
+    let normalized_path = normalize_path(&path);
+    let mut store = BOOKMARK_STORE.lock().map_err(|error| error.to_string())?;
+
+    if let Some(existing) = store
+        .bookmarks
+        .iter()
+        .find(|bookmark| bookmark.path == normalized_path)
+    {
+        return Ok(existing.clone());
+    }
+
+    let disks = Disks::new_with_refreshed_list();
+    let (volume_label, relative_path) = match removable_location(&disks, target) {
+        Some((label, relative_path)) => (Some(label), Some(relative_path)),
+        None => (None, None),
+    };
+
+    let name = name
+        .filter(|name| !name.trim().is_empty())
+        .or_else(|| {
+            target
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| normalized_path.clone());
+
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0);
+
+    let bookmark = Bookmark {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        path: normalized_path,
+        volume_label,
+        relative_path,
+        volume_path: MountedVolumes::list().locate(target),
+        created_at,
+    };
+
+    store.bookmarks.push(bookmark.clone());
+    json_store::save(STORE_NAME, &*store)?;
+    notify_store_changed(STORE_NAME, Some(window.label()));
+
+    Ok(bookmark)
+}
+
+#[tauri::command]
+pub fn remove_bookmark(window: tauri::WebviewWindow, id: String) -> Result<(), String> {
+    let mut store = BOOKMARK_STORE.lock().map_err(|error| error.to_string())?;
+    let count_before = store.bookmarks.len();
+
+    store.bookmarks.retain(|bookmark| bookmark.id != id);
+
+    if store.bookmarks.len() == count_before {
+        return Err(format!("Bookmark not found: {}", id));
+    }
+
+    json_store::save(STORE_NAME, &*store)?;
+    notify_store_changed(STORE_NAME, Some(window.label()));
+    Ok(())
+}
+
+/// Lists bookmarks, re-resolving ones on volumes that were remounted at a
+/// different drive letter or mount point
+#[tauri::command]
+pub fn list_bookmarks() -> Result<Vec<BookmarkInfo>, String> {
+    let mut store = BOOKMARK_STORE.lock().map_err(|error| error.to_string())?;
+    let has_missing_relocatable = store.bookmarks.iter().any(|bookmark| {
+        (bookmark.volume_path.is_some() || bookmark.volume_label.is_some())
+            && !Path::new(&bookmark.path).exists()
+    });
+
+    if has_missing_relocatable {
+        let volumes = MountedVolumes::list();
+        let disks = Disks::new_with_refreshed_list();
+        let mut is_changed = false;
+
+        for bookmark in store.bookmarks.iter_mut() {
+            if Path::new(&bookmark.path).exists() {
+                continue;
+            }
+
+            if let Some(new_path) = re_resolve(&volumes, &disks, bookmark) {
+                bookmark.path = new_path;
+                is_changed = true;
+            }
+        }
+
+        if is_changed {
+            json_store::save(STORE_NAME, &*store)?;
+        }
+    }
+
+    Ok(store.bookmarks.iter().map(to_info).collect())
+}
+
+/// Reorders bookmarks to match `ids`. Bookmarks missing from `ids` keep
+/// their relative order after the listed ones.
+#[tauri::command]
+pub fn reorder_bookmarks(
+    window: tauri::WebviewWindow,
+    ids: Vec<String>,
+) -> Result<Vec<BookmarkInfo>, String> {
+    let mut store = BOOKMARK_STORE.lock().map_err(|error| error.to_string())?;
+
+    let position = |id: &str| {
+        ids.iter()
+            .position(|ordered_id| ordered_id == id)
+            .unwrap_or(usize::MAX)
+    };
+    store
+        .bookmarks
+        .sort_by_key(|bookmark| position(&bookmark.id));
+
+    json_store::save(STORE_NAME, &*store)?;
+    notify_store_changed(STORE_NAME, Some(window.label()));
+
+    Ok(store.bookmarks.iter().map(to_info).collect())
+}
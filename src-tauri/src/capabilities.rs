@@ -0,0 +1,180 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Optional external tools some features run, and whether this system has
+//! them. The UI uses this to hide or explain those features up front rather
+//! than failing when they are clicked.
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+/// By tool name, such as `ffmpeg`
+pub type Capabilities = BTreeMap<&'static str, Capability>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Capability {
+    pub available: bool,
+    /// Where the tool was found
+    pub path: Option<String>,
+    /// Why the tool isn't available, when it isn't
+    pub reason: Option<String>,
+}
+
+struct Tool {
+    name: &'static str,
+    /// Every group is needed, any program of a group will do
+    programs: &'static [&'static [&'static str]],
+    is_supported: bool,
+}
+
+const TOOLS: &[Tool] = &[
+    Tool {
+        name: "udisks2",
+        programs: &[&["udisksctl"]],
+        is_supported: cfg!(target_os = "linux"),
+    },
+    Tool {
+        name: "sshfs",
+        programs: &[&["sshfs"]],
+        is_supported: cfg!(unix),
+    },
+    Tool {
+        name: "smbclient",
+        programs: &[&["smbclient"]],
+        is_supported: cfg!(unix),
+    },
+    Tool {
+        name: "ffmpeg",
+        programs: &[&["ffmpeg"], &["ffprobe"]],
+        is_supported: true,
+    },
+    Tool {
+        name: "7z",
+        programs: &[&["7z", "7zz", "7za"]],
+        is_supported: true,
+    },
+    Tool {
+        name: "smartctl",
+        programs: &[&["smartctl"]],
+        is_supported: true,
+    },
+];
+
+/// The result of the latest probe
+static CAPABILITIES: Lazy<Mutex<Option<Capabilities>>> = Lazy::new(|| Mutex::new(None));
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+fn executable_names(program: &str) -> Vec<String> {
+    if !cfg!(windows) {
+        return vec![program.to_string()];
+    }
+
+    let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".EXE;.CMD;.BAT".to_string());
+    extensions
+        .split(';')
+        .filter(|extension| !extension.is_empty())
+        .map(|extension| format!("{}{}", program, extension.to_lowercase()))
+        .collect()
+}
+
+// Only PATH is searched, as that is where the features look for the tools
+// when they run them
+fn find_program(program: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    let names = executable_names(program);
+
+    std::env::split_paths(&paths)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| is_executable(candidate))
+}
+
+fn probe_tool(tool: &Tool) -> Capability {
+    if !tool.is_supported {
+        return Capability {
+            available: false,
+            path: None,
+            reason: Some("Not available on this platform".to_string()),
+        };
+    }
+
+    let mut path = None;
+    for group in tool.programs {
+        let found = group.iter().find_map(|program| find_program(program));
+        let Some(found) = found else {
+            return Capability {
+                available: false,
+                path: None,
+                reason: Some(format!("{} was not found in PATH", group.join(" or "))),
+            };
+        };
+        path.get_or_insert(found);
+    }
+
+    Capability {
+        available: true,
+        path: path.map(|path| crate::utils::normalize_path(&path.to_string_lossy())),
+        reason: None,
+    }
+}
+
+fn probe() -> Capabilities {
+    let capabilities: Capabilities = TOOLS
+        .iter()
+        .map(|tool| (tool.name, probe_tool(tool)))
+        .collect();
+
+    if let Ok(mut cached) = CAPABILITIES.lock() {
+        *cached = Some(capabilities.clone());
+    }
+    capabilities
+}
+
+/// Probes in the background at startup, so the first `get_capabilities`
+/// call finds the result ready
+pub fn start() {
+    let spawn_result = thread::Builder::new()
+        .name("capabilities".to_string())
+        .spawn(|| {
+            probe();
+        });
+
+    if let Err(error) = spawn_result {
+        tracing::error!("Failed to start the capabilities probe: {}", error);
+    }
+}
+
+/// Which optional external tools this system has. The result of the probe
+/// at startup is reused unless `refresh` is set, as after installing a tool.
+#[tauri::command]
+pub async fn get_capabilities(refresh: Option<bool>) -> Result<Capabilities, String> {
+    if !refresh.unwrap_or(false) {
+        let cached = CAPABILITIES
+            .lock()
+            .map_err(|error| error.to_string())?
+            .clone();
+        if let Some(capabilities) = cached {
+            return Ok(capabilities);
+        }
+    }
+
+    tokio::task::spawn_blocking(probe)
+        .await
+        .map_err(|error| error.to_string())
+}
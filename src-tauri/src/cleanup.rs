@@ -0,0 +1,857 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Storage cleanup scanners. Found items are handed to the frontend with
+//! ids, and only items found by a scan can be deleted through them, each
+//! checked again right before it is deleted.
+
+use crate::dir_compare::exclusion_matcher;
+use crate::requests::{self, ActiveRequest, ProgressEmitter, Throttle};
+use crate::skipped_dirs::SkippedDirs;
+use crate::utils::normalize_path;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+use walkdir::WalkDir;
+
+const FIND_EMPTY_EVENT: &str = "find-empty-progress";
+const FIND_LARGE_FILES_EVENT: &str = "find-large-files-progress";
+const FIND_BROKEN_LINKS_EVENT: &str = "find-broken-links-progress";
+const BATCH_SIZE: usize = 256;
+const DEFAULT_LARGE_FILES_LIMIT: usize = 100;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Items of the latest scan by id. A new scan replaces them.
+static FOUND_ITEMS: Lazy<Mutex<HashMap<String, FoundItem>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyKind {
+    Directories,
+    Files,
+    All,
+}
+
+impl EmptyKind {
+    fn includes_dirs(self) -> bool {
+        self != EmptyKind::Files
+    }
+
+    fn includes_files(self) -> bool {
+        self != EmptyKind::Directories
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FindEmptyOptions {
+    /// Patterns in `.gitignore` syntax, relative to each root, of entries
+    /// to leave out. Left out entries still count as content of their folder.
+    pub exclude: Vec<String>,
+    /// How deep below each root to look, unlimited by default
+    pub max_depth: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FoundReason {
+    Empty,
+    BrokenSymlink,
+    /// A Windows shell link (`.lnk`) whose target is gone
+    BrokenShellLink,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FoundItem {
+    pub id: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub reason: FoundReason,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FindEmptyProgress {
+    pub request_id: String,
+    pub items: Vec<FoundItem>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FindEmptyResult {
+    /// Zero-byte files and the topmost empty folders. Folders holding only
+    /// empty folders are empty too and are reported as one item.
+    pub items: Vec<FoundItem>,
+    pub skipped_dirs: SkippedDirs,
+    pub errors: Vec<String>,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeleteFoundResult {
+    pub deleted_paths: Vec<String>,
+    /// Items that aren't empty or broken anymore and were left alone
+    pub changed_paths: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+fn found_item(path: &Path, is_dir: bool, reason: FoundReason) -> FoundItem {
+    FoundItem {
+        id: uuid::Uuid::new_v4().to_string(),
+        path: normalize_path(&path.to_string_lossy()),
+        is_dir,
+        reason,
+    }
+}
+
+fn is_dir_without_entries(path: &Path) -> bool {
+    fs::read_dir(path)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(false)
+}
+
+/// Whether `path` holds nothing but empty folders
+fn is_empty_tree(path: &Path) -> bool {
+    WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .all(|entry| entry.is_ok_and(|entry| entry.file_type().is_dir()))
+}
+
+struct EmptyScan<'a> {
+    kind: EmptyKind,
+    cancel_token: &'a AtomicBool,
+    on_batch: &'a mut dyn FnMut(&[FoundItem]),
+    result: FindEmptyResult,
+    pending_batch: Vec<FoundItem>,
+}
+
+impl EmptyScan<'_> {
+    fn report(&mut self, item: FoundItem) {
+        self.pending_batch.push(item);
+        if self.pending_batch.len() >= BATCH_SIZE {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.pending_batch.is_empty() {
+            return;
+        }
+        (self.on_batch)(&self.pending_batch);
+        self.result.items.append(&mut self.pending_batch);
+    }
+
+    fn scan_root(&mut self, root: &Path, options: &FindEmptyOptions) -> Result<(), String> {
+        let exclude = exclusion_matcher(root, &options.exclude)?;
+        // Folders known to hold something, filled in as their content is
+        // walked, which comes before the folder itself
+        let non_empty_dirs: RefCell<HashSet<PathBuf>> = RefCell::new(HashSet::new());
+        // Empty folders by parent, reported once the parent turns out to
+        // hold something, otherwise covered by the parent
+        let mut empty_children: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        let max_depth = options.max_depth.unwrap_or(usize::MAX);
+
+        let mark_parent = |path: &Path| {
+            if let Some(parent) = path.parent() {
+                non_empty_dirs.borrow_mut().insert(parent.to_path_buf());
+            }
+        };
+
+        let walker = WalkDir::new(root)
+            .follow_links(false)
+            .contents_first(true)
+            .max_depth(max_depth)
+            .into_iter()
+            .filter_entry(|entry| {
+                let is_excluded = entry.depth() > 0
+                    && exclude
+                        .matched(entry.path(), entry.file_type().is_dir())
+                        .is_ignore();
+                if is_excluded {
+                    mark_parent(entry.path());
+                }
+                !is_excluded
+            });
+
+        for entry in walker {
+            if self.cancel_token.load(Ordering::Relaxed) {
+                self.result.cancelled = true;
+                return Ok(());
+            }
+
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    if let Some(path) = error.path() {
+                        non_empty_dirs.borrow_mut().insert(path.to_path_buf());
+                        mark_parent(path);
+                    }
+                    if !self.result.skipped_dirs.record(&error) {
+                        self.result.errors.push(error.to_string());
+                    }
+                    continue;
+                }
+            };
+            let path = entry.path();
+
+            if !entry.file_type().is_dir() {
+                mark_parent(path);
+                let is_zero_byte = entry.file_type().is_file()
+                    && entry.metadata().is_ok_and(|metadata| metadata.len() == 0);
+                if is_zero_byte && self.kind.includes_files() {
+                    self.report(found_item(path, false, FoundReason::Empty));
+                }
+                continue;
+            }
+
+            let children = empty_children.remove(path).unwrap_or_default();
+            // The content of folders at the depth limit isn't walked
+            let is_empty = !non_empty_dirs.borrow_mut().remove(path)
+                && (entry.depth() < max_depth || is_dir_without_entries(path));
+
+            if is_empty && entry.depth() > 0 {
+                if let Some(parent) = path.parent() {
+                    empty_children
+                        .entry(parent.to_path_buf())
+                        .or_default()
+                        .push(path.to_path_buf());
+                }
+                continue;
+            }
+
+            mark_parent(path);
+            if self.kind.includes_dirs() {
+                for child in children {
+                    self.report(found_item(&child, true, FoundReason::Empty));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn find_empty_items(
+    roots: &[String],
+    kind: EmptyKind,
+    options: &FindEmptyOptions,
+    cancel_token: &AtomicBool,
+    on_batch: &mut dyn FnMut(&[FoundItem]),
+) -> FindEmptyResult {
+    let mut scan = EmptyScan {
+        kind,
+        cancel_token,
+        on_batch,
+        result: FindEmptyResult::default(),
+        pending_batch: Vec::new(),
+    };
+
+    for root in roots {
+        let root = Path::new(root);
+        if !root.is_dir() {
+            scan.result
+                .errors
+                .push(format!("Not a directory: {}", root.display()));
+            continue;
+        }
+        if let Err(error) = scan.scan_root(root, options) {
+            scan.result.errors.push(error);
+        }
+        if scan.result.cancelled {
+            break;
+        }
+    }
+
+    scan.flush();
+    scan.result
+}
+
+/// Finds zero-byte files and empty folders below `roots`. With a
+/// `request_id`, items are also emitted in batches as `find-empty-progress`
+/// events while the scan runs, and it can be stopped with
+/// `cancel_cleanup_scan`.
+#[tauri::command]
+pub async fn find_empty(
+    window: tauri::WebviewWindow,
+    roots: Vec<String>,
+    kind: EmptyKind,
+    options: Option<FindEmptyOptions>,
+    request_id: Option<String>,
+) -> Result<FindEmptyResult, String> {
+    let app = window.app_handle().clone();
+    let options = options.unwrap_or_default();
+    let active_request = ActiveRequest::register(request_id.as_deref());
+    let cancel_token = active_request.cancel_token();
+
+    let owner_window = window.label().to_string();
+    let result = tokio::task::spawn_blocking(move || {
+        let progress = ProgressEmitter::new(
+            &app,
+            Some(&owner_window),
+            FIND_EMPTY_EVENT,
+            request_id.as_deref(),
+        );
+        find_empty_items(&roots, kind, &options, &cancel_token, &mut |items| {
+            progress.emit(true, |request_id| FindEmptyProgress {
+                request_id: request_id.to_string(),
+                items: items.to_vec(),
+            });
+        })
+    })
+    .await
+    .map_err(|error| error.to_string())?;
+
+    remember_found(result.items.iter());
+
+    Ok(result)
+}
+
+/// Stops a `find_empty`, `find_large_files` or `find_broken_links` scan
+#[tauri::command]
+pub fn cancel_cleanup_scan(request_id: String) {
+    requests::cancel(&request_id);
+}
+
+fn remember_found<'a>(items: impl Iterator<Item = &'a FoundItem>) {
+    if let Ok(mut found_items) = FOUND_ITEMS.lock() {
+        *found_items = items.map(|item| (item.id.clone(), item.clone())).collect();
+    }
+}
+
+fn delete_found_item(item: &FoundItem, use_trash: bool) -> Result<(), String> {
+    let path = crate::utils::to_os_path(&item.path);
+
+    if use_trash {
+        return crate::trash_bin::trash_path(&path);
+    }
+    if item.reason != FoundReason::Empty {
+        // Symlinks to folders are removed as folders on Windows
+        return fs::remove_file(&path)
+            .or_else(|_| fs::remove_dir(&path))
+            .map_err(|error| error.to_string());
+    }
+    if !item.is_dir {
+        return fs::remove_file(&path).map_err(|error| error.to_string());
+    }
+
+    // Folder by folder, so anything created in the meantime stops it
+    for entry in WalkDir::new(&path).contents_first(true) {
+        let entry = entry.map_err(|error| error.to_string())?;
+        fs::remove_dir(entry.path()).map_err(|error| format!("{}: {}", item.path, error))?;
+    }
+    Ok(())
+}
+
+/// Deletes items found by the latest scan. Items that gained content or
+/// whose link target came back since are left alone and listed in
+/// `changed_paths`.
+#[tauri::command]
+pub async fn delete_found(ids: Vec<String>, use_trash: bool) -> Result<DeleteFoundResult, String> {
+    let items: Vec<FoundItem> = {
+        let mut found_items = FOUND_ITEMS.lock().map_err(|error| error.to_string())?;
+        ids.iter().filter_map(|id| found_items.remove(id)).collect()
+    };
+
+    tokio::task::spawn_blocking(move || {
+        let mut result = DeleteFoundResult::default();
+
+        for item in items {
+            let path = crate::utils::to_os_path(&item.path);
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+            let is_unchanged = match item.reason {
+                FoundReason::Empty if item.is_dir => metadata.is_dir() && is_empty_tree(&path),
+                FoundReason::Empty => metadata.is_file() && metadata.len() == 0,
+                FoundReason::BrokenSymlink | FoundReason::BrokenShellLink => {
+                    missing_link_target(&path, item.reason).is_some()
+                }
+            };
+
+            if !is_unchanged {
+                result.changed_paths.push(item.path);
+                continue;
+            }
+
+            match delete_found_item(&item, use_trash) {
+                Ok(()) => {
+                    crate::tags::handle_path_removed(&path);
+                    result.deleted_paths.push(item.path);
+                }
+                Err(error) => result.errors.push(error),
+            }
+        }
+
+        result
+    })
+    .await
+    .map_err(|error| error.to_string())
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FindLargeFilesOptions {
+    /// Patterns in `.gitignore` syntax, relative to each root, of entries
+    /// to leave out
+    pub exclude: Vec<String>,
+    /// How many of the largest files to return
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LargeFile {
+    pub path: String,
+    pub size: u64,
+    pub modified_time: u64,
+    /// `None` where the file system doesn't keep access times. Volumes
+    /// mounted with `noatime` or `relatime` keep them loosely.
+    pub accessed_time: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FindLargeFilesProgress {
+    pub request_id: String,
+    /// The largest files found so far, largest first
+    pub files: Vec<LargeFile>,
+    pub scanned_count: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FindLargeFilesResult {
+    /// Largest first
+    pub files: Vec<LargeFile>,
+    pub scanned_count: u64,
+    pub skipped_dirs: SkippedDirs,
+    pub errors: Vec<String>,
+    pub cancelled: bool,
+}
+
+fn to_millis(time: std::io::Result<SystemTime>) -> Option<u64> {
+    time.ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|duration| duration.as_millis() as u64)
+}
+
+/// The largest files seen so far, shared by the walker threads
+struct LargestFiles {
+    limit: usize,
+    heap: BinaryHeap<Reverse<(u64, String, u64, Option<u64>)>>,
+}
+
+impl LargestFiles {
+    fn offer(&mut self, file: LargeFile) {
+        // Files that wouldn't make the list are turned away before their
+        // path is stored
+        let is_full = self.heap.len() >= self.limit;
+        if is_full
+            && self
+                .heap
+                .peek()
+                .is_some_and(|Reverse(smallest)| smallest.0 >= file.size)
+        {
+            return;
+        }
+
+        self.heap.push(Reverse((
+            file.size,
+            file.path,
+            file.modified_time,
+            file.accessed_time,
+        )));
+        if self.heap.len() > self.limit {
+            self.heap.pop();
+        }
+    }
+
+    fn sorted(&self) -> Vec<LargeFile> {
+        let mut files: Vec<LargeFile> = self
+            .heap
+            .iter()
+            .map(
+                |Reverse((size, path, modified_time, accessed_time))| LargeFile {
+                    path: path.clone(),
+                    size: *size,
+                    modified_time: *modified_time,
+                    accessed_time: *accessed_time,
+                },
+            )
+            .collect();
+        files.sort_by(|first, second| second.size.cmp(&first.size));
+        files
+    }
+}
+
+struct LargeFilesScan<'a> {
+    min_size: u64,
+    older_than: Option<u64>,
+    cancel_token: &'a AtomicBool,
+    largest: Mutex<LargestFiles>,
+    scanned_count: AtomicU64,
+    skipped_dirs: Mutex<SkippedDirs>,
+    errors: Mutex<Vec<String>>,
+    progress_throttle: Throttle,
+    on_progress: &'a (dyn Fn(Vec<LargeFile>, u64) + Sync),
+}
+
+impl LargeFilesScan<'_> {
+    fn visit(&self, entry: Result<ignore::DirEntry, ignore::Error>) -> ignore::WalkState {
+        if self.cancel_token.load(Ordering::Relaxed) {
+            return ignore::WalkState::Quit;
+        }
+
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                let is_skipped = self
+                    .skipped_dirs
+                    .lock()
+                    .is_ok_and(|mut skipped_dirs| skipped_dirs.record_ignore_error(&error));
+                if !is_skipped {
+                    if let Ok(mut errors) = self.errors.lock() {
+                        errors.push(error.to_string());
+                    }
+                }
+                return ignore::WalkState::Continue;
+            }
+        };
+
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            return ignore::WalkState::Continue;
+        }
+        self.scanned_count.fetch_add(1, Ordering::Relaxed);
+
+        let Ok(metadata) = entry.metadata() else {
+            return ignore::WalkState::Continue;
+        };
+        if metadata.len() < self.min_size {
+            return ignore::WalkState::Continue;
+        }
+
+        let modified_time = to_millis(metadata.modified()).unwrap_or(0);
+        let accessed_time = to_millis(metadata.accessed());
+        // Last used is whichever is later, access times may not be updated
+        let last_used = accessed_time.unwrap_or(0).max(modified_time);
+        if self
+            .older_than
+            .is_some_and(|older_than| last_used >= older_than)
+        {
+            return ignore::WalkState::Continue;
+        }
+
+        if let Ok(mut largest) = self.largest.lock() {
+            largest.offer(LargeFile {
+                path: normalize_path(&entry.path().to_string_lossy()),
+                size: metadata.len(),
+                modified_time,
+                accessed_time,
+            });
+        }
+        self.emit_progress(false);
+
+        ignore::WalkState::Continue
+    }
+
+    fn emit_progress(&self, force: bool) {
+        if !self.progress_throttle.ready(force) {
+            return;
+        }
+
+        if let Ok(largest) = self.largest.lock() {
+            (self.on_progress)(largest.sorted(), self.scanned_count.load(Ordering::Relaxed));
+        }
+    }
+
+    fn scan_root(&self, root: &Path, exclude: &[String]) -> Result<(), String> {
+        let exclude = Arc::new(exclusion_matcher(root, exclude)?);
+
+        ignore::WalkBuilder::new(root)
+            .standard_filters(false)
+            .follow_links(false)
+            .filter_entry(move |entry| {
+                let is_dir = entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_dir());
+                !exclude.matched(entry.path(), is_dir).is_ignore()
+            })
+            .build_parallel()
+            .run(|| Box::new(|entry| self.visit(entry)));
+
+        Ok(())
+    }
+}
+
+fn find_large_files_blocking(
+    roots: &[String],
+    min_size: u64,
+    older_than: Option<u64>,
+    options: &FindLargeFilesOptions,
+    cancel_token: &AtomicBool,
+    on_progress: &(dyn Fn(Vec<LargeFile>, u64) + Sync),
+) -> FindLargeFilesResult {
+    let scan = LargeFilesScan {
+        min_size,
+        older_than,
+        cancel_token,
+        largest: Mutex::new(LargestFiles {
+            limit: options.limit.unwrap_or(DEFAULT_LARGE_FILES_LIMIT).max(1),
+            heap: BinaryHeap::new(),
+        }),
+        scanned_count: AtomicU64::new(0),
+        skipped_dirs: Mutex::new(SkippedDirs::default()),
+        errors: Mutex::new(Vec::new()),
+        progress_throttle: Throttle::new(PROGRESS_INTERVAL),
+        on_progress,
+    };
+
+    for root in roots {
+        let root = Path::new(root);
+        let result = if root.is_dir() {
+            scan.scan_root(root, &options.exclude)
+        } else {
+            Err(format!("Not a directory: {}", root.display()))
+        };
+
+        if let (Err(error), Ok(mut errors)) = (result, scan.errors.lock()) {
+            errors.push(error);
+        }
+        if cancel_token.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+
+    scan.emit_progress(true);
+
+    FindLargeFilesResult {
+        files: scan
+            .largest
+            .lock()
+            .map(|largest| largest.sorted())
+            .unwrap_or_default(),
+        scanned_count: scan.scanned_count.load(Ordering::Relaxed),
+        skipped_dirs: scan.skipped_dirs.into_inner().unwrap_or_default(),
+        errors: scan.errors.into_inner().unwrap_or_default(),
+        cancelled: cancel_token.load(Ordering::Relaxed),
+    }
+}
+
+/// Finds the largest files of at least `min_size` bytes below `roots`, in
+/// parallel. With `older_than`, in milliseconds since the Unix epoch, only
+/// files last used before then are counted. With a `request_id`, the
+/// largest files so far are emitted as `find-large-files-progress` events
+/// while the scan runs, and it can be stopped with `cancel_cleanup_scan`.
+#[tauri::command]
+pub async fn find_large_files(
+    window: tauri::WebviewWindow,
+    roots: Vec<String>,
+    min_size: u64,
+    older_than: Option<u64>,
+    options: Option<FindLargeFilesOptions>,
+    request_id: Option<String>,
+) -> Result<FindLargeFilesResult, String> {
+    let app = window.app_handle().clone();
+    let options = options.unwrap_or_default();
+    let active_request = ActiveRequest::register(request_id.as_deref());
+    let cancel_token = active_request.cancel_token();
+
+    let owner_window = window.label().to_string();
+    tokio::task::spawn_blocking(move || {
+        let progress = ProgressEmitter::new(
+            &app,
+            Some(&owner_window),
+            FIND_LARGE_FILES_EVENT,
+            request_id.as_deref(),
+        );
+        // Already throttled by the scan
+        let on_progress = |files: Vec<LargeFile>, scanned_count: u64| {
+            progress.emit(true, |request_id| FindLargeFilesProgress {
+                request_id: request_id.to_string(),
+                files,
+                scanned_count,
+            });
+        };
+
+        find_large_files_blocking(
+            &roots,
+            min_size,
+            older_than,
+            &options,
+            &cancel_token,
+            &on_progress,
+        )
+    })
+    .await
+    .map_err(|error| error.to_string())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrokenLink {
+    #[serde(flatten)]
+    pub item: FoundItem,
+    /// Where the link points
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FindBrokenLinksProgress {
+    pub request_id: String,
+    pub links: Vec<BrokenLink>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FindBrokenLinksResult {
+    pub links: Vec<BrokenLink>,
+    pub skipped_dirs: SkippedDirs,
+    pub errors: Vec<String>,
+    pub cancelled: bool,
+}
+
+// Targets on network shares or on drives that aren't there right now, like
+// an unplugged USB drive, can't be told apart from deleted ones
+fn is_on_present_drive(target: &Path) -> bool {
+    let is_drive = matches!(
+        target.components().next(),
+        Some(Component::Prefix(prefix))
+            if matches!(prefix.kind(), Prefix::Disk(_) | Prefix::VerbatimDisk(_))
+    );
+    is_drive && target.ancestors().last().is_some_and(|root| root.exists())
+}
+
+fn is_not_found(result: std::io::Result<fs::Metadata>) -> bool {
+    result.is_err_and(|error| error.kind() == ErrorKind::NotFound)
+}
+
+/// The target of the link at `path` when that target is gone
+fn missing_link_target(path: &Path, reason: FoundReason) -> Option<PathBuf> {
+    match reason {
+        FoundReason::BrokenSymlink => {
+            let target = fs::read_link(path).ok()?;
+            // Relative targets are resolved by following the link itself
+            is_not_found(fs::metadata(path)).then_some(target)
+        }
+        FoundReason::BrokenShellLink => {
+            let target = crate::shell_links::read_target(path).ok()??;
+            let is_missing = is_on_present_drive(&target) && is_not_found(fs::metadata(&target));
+            is_missing.then_some(target)
+        }
+        FoundReason::Empty => None,
+    }
+}
+
+// Shell links are only checked on Windows, where their targets live
+fn is_shell_link(path: &Path) -> bool {
+    cfg!(windows)
+        && path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("lnk"))
+}
+
+fn find_broken_links_blocking(
+    root: &Path,
+    cancel_token: &AtomicBool,
+    on_batch: &mut dyn FnMut(&[BrokenLink]),
+) -> FindBrokenLinksResult {
+    let mut result = FindBrokenLinksResult::default();
+    let mut pending_batch: Vec<BrokenLink> = Vec::new();
+
+    if !root.is_dir() {
+        result
+            .errors
+            .push(format!("Not a directory: {}", root.display()));
+        return result;
+    }
+
+    for entry in WalkDir::new(root).follow_links(false) {
+        if cancel_token.load(Ordering::Relaxed) {
+            result.cancelled = true;
+            break;
+        }
+
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                if !result.skipped_dirs.record(&error) {
+                    result.errors.push(error.to_string());
+                }
+                continue;
+            }
+        };
+
+        let reason = if entry.file_type().is_symlink() {
+            FoundReason::BrokenSymlink
+        } else if entry.file_type().is_file() && is_shell_link(entry.path()) {
+            FoundReason::BrokenShellLink
+        } else {
+            continue;
+        };
+
+        if let Some(target) = missing_link_target(entry.path(), reason) {
+            pending_batch.push(BrokenLink {
+                item: found_item(entry.path(), false, reason),
+                target: normalize_path(&target.to_string_lossy()),
+            });
+        }
+        if pending_batch.len() >= BATCH_SIZE {
+            on_batch(&pending_batch);
+            result.links.append(&mut pending_batch);
+        }
+    }
+
+    if !pending_batch.is_empty() {
+        on_batch(&pending_batch);
+        result.links.append(&mut pending_batch);
+    }
+    result
+}
+
+/// Finds symlinks below `root` whose target is gone, and on Windows shell
+/// links (`.lnk` files) whose target is gone. With a `request_id`, links
+/// are also emitted in batches as `find-broken-links-progress` events while
+/// the scan runs, and it can be stopped with `cancel_cleanup_scan`. Found
+/// links can be removed with `delete_found`.
+#[tauri::command]
+pub async fn find_broken_links(
+    window: tauri::WebviewWindow,
+    root: String,
+    request_id: Option<String>,
+) -> Result<FindBrokenLinksResult, String> {
+    let app = window.app_handle().clone();
+    let active_request = ActiveRequest::register(request_id.as_deref());
+    let cancel_token = active_request.cancel_token();
+
+    let owner_window = window.label().to_string();
+    let result = tokio::task::spawn_blocking(move || {
+        let progress = ProgressEmitter::new(
+            &app,
+            Some(&owner_window),
+            FIND_BROKEN_LINKS_EVENT,
+            request_id.as_deref(),
+        );
+        let root = crate::utils::to_os_path(&root);
+        find_broken_links_blocking(&root, &cancel_token, &mut |links| {
+            progress.emit(true, |request_id| FindBrokenLinksProgress {
+                request_id: request_id.to_string(),
+                links: links.to_vec(),
+            });
+        })
+    })
+    .await
+    .map_err(|error| error.to_string())?;
+
+    remember_found(result.links.iter().map(|link| &link.item));
+
+    Ok(result)
+}
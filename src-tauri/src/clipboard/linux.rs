@@ -0,0 +1,154 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::ClipboardOperation;
+use gtk::gdk;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use tauri::Url;
+
+// Nautilus, Nemo, Caja and Thunar
+const GNOME_COPIED_FILES_TARGET: &str = "x-special/gnome-copied-files";
+// Dolphin marks cut files with an extra target next to the URI list
+const KDE_CUT_SELECTION_TARGET: &str = "application/x-kde-cutselection";
+const URI_LIST_TARGET: &str = "text/uri-list";
+const TEXT_TARGET: &str = "UTF8_STRING";
+
+// GTK owns the clipboard, so it may only be touched from the main thread
+fn run_on_main_thread<T, F>(app: &tauri::AppHandle, task: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+
+    app.run_on_main_thread(move || {
+        let _ = sender.send(task());
+    })
+    .map_err(|error| error.to_string())?;
+
+    receiver.recv().map_err(|error| error.to_string())
+}
+
+fn path_to_uri(path: &Path) -> Option<String> {
+    Url::from_file_path(path).ok().map(|url| url.to_string())
+}
+
+fn parse_uris<'a>(lines: impl Iterator<Item = &'a str>) -> Vec<PathBuf> {
+    lines
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| Url::parse(line).ok())
+        .filter_map(|url| url.to_file_path().ok())
+        .collect()
+}
+
+pub fn set_files(
+    app: &tauri::AppHandle,
+    paths: Vec<PathBuf>,
+    operation: ClipboardOperation,
+) -> Result<(), String> {
+    let uris: Vec<String> = paths
+        .iter()
+        .map(PathBuf::as_path)
+        .filter_map(path_to_uri)
+        .collect();
+    let plain_text = paths
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let operation_name = match operation {
+        ClipboardOperation::Copy => "copy",
+        ClipboardOperation::Cut => "cut",
+    };
+    let gnome_data = format!("{}\n{}", operation_name, uris.join("\n"));
+    let uri_list_data = format!("{}\r\n", uris.join("\r\n"));
+    let kde_cut_data = if operation == ClipboardOperation::Cut {
+        "1"
+    } else {
+        "0"
+    };
+
+    let is_set = run_on_main_thread(app, move || {
+        let targets = [
+            gtk::TargetEntry::new(GNOME_COPIED_FILES_TARGET, gtk::TargetFlags::empty(), 0),
+            gtk::TargetEntry::new(URI_LIST_TARGET, gtk::TargetFlags::empty(), 1),
+            gtk::TargetEntry::new(KDE_CUT_SELECTION_TARGET, gtk::TargetFlags::empty(), 2),
+            gtk::TargetEntry::new(TEXT_TARGET, gtk::TargetFlags::empty(), 3),
+        ];
+
+        let clipboard = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+        let is_set = clipboard.set_with_data(&targets, move |_, selection_data, info| {
+            let data = match info {
+                0 => gnome_data.as_bytes(),
+                1 => uri_list_data.as_bytes(),
+                2 => kde_cut_data.as_bytes(),
+                _ => plain_text.as_bytes(),
+            };
+            selection_data.set(&selection_data.target(), 8, data);
+        });
+
+        // Keep the files on the clipboard after Sigma exits
+        clipboard.set_can_store(&[]);
+        is_set
+    })?;
+
+    if !is_set {
+        return Err("Failed to take ownership of the clipboard".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn get_files(
+    app: &tauri::AppHandle,
+) -> Result<Option<(Vec<PathBuf>, ClipboardOperation)>, String> {
+    run_on_main_thread(app, || {
+        let clipboard = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+        let read_target = |target: &str| {
+            clipboard
+                .wait_for_contents(&gdk::Atom::intern(target))
+                .map(|selection_data| String::from_utf8_lossy(&selection_data.data()).to_string())
+        };
+
+        // First line is "copy" or "cut", the rest are file URIs
+        if let Some(gnome_data) = read_target(GNOME_COPIED_FILES_TARGET) {
+            let mut lines = gnome_data.lines();
+            let operation = match lines.next().map(str::trim) {
+                Some("cut") => ClipboardOperation::Cut,
+                _ => ClipboardOperation::Copy,
+            };
+            let paths = parse_uris(lines);
+
+            if !paths.is_empty() {
+                return Some((paths, operation));
+            }
+        }
+
+        let paths = parse_uris(read_target(URI_LIST_TARGET)?.lines());
+        if paths.is_empty() {
+            return None;
+        }
+
+        let operation = match read_target(KDE_CUT_SELECTION_TARGET)
+            .as_deref()
+            .map(str::trim)
+        {
+            Some("1") => ClipboardOperation::Cut,
+            _ => ClipboardOperation::Copy,
+        };
+
+        Some((paths, operation))
+    })
+}
+
+pub fn get_text(app: &tauri::AppHandle) -> Result<Option<String>, String> {
+    run_on_main_thread(app, || {
+        gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD)
+            .wait_for_text()
+            .map(|text| text.to_string())
+    })
+}
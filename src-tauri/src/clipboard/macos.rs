@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::ClipboardOperation;
+use objc2::rc::Retained;
+use objc2::runtime::AnyObject;
+use objc2::{class, msg_send};
+use objc2_foundation::{NSArray, NSString, NSURL};
+use once_cell::sync::Lazy;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Uniform type identifiers of file URLs and text on the pasteboard
+const FILE_URL_TYPE: &str = "public.file-url";
+const TEXT_TYPE: &str = "public.utf8-plain-text";
+
+// Finder has no "cut" for files, it pastes copied files as a move with
+// Option held. Remember which pasteboard change was a cut made by Sigma.
+static CUT_CHANGE_COUNT: Lazy<Mutex<Option<isize>>> = Lazy::new(|| Mutex::new(None));
+
+fn general_pasteboard() -> Retained<AnyObject> {
+    unsafe { msg_send![class!(NSPasteboard), generalPasteboard] }
+}
+
+pub fn set_files(
+    _app: &tauri::AppHandle,
+    paths: Vec<PathBuf>,
+    operation: ClipboardOperation,
+) -> Result<(), String> {
+    let urls: Vec<Retained<NSURL>> = paths
+        .iter()
+        .map(|path| NSURL::fileURLWithPath(&NSString::from_str(&path.to_string_lossy())))
+        .collect();
+    let url_array = NSArray::from_retained_slice(&urls);
+    let pasteboard = general_pasteboard();
+
+    let change_count: isize = unsafe {
+        let _: isize = msg_send![&*pasteboard, clearContents];
+        let is_written: bool = msg_send![&*pasteboard, writeObjects: &*url_array];
+
+        if !is_written {
+            return Err("Failed to write files to the pasteboard".to_string());
+        }
+
+        msg_send![&*pasteboard, changeCount]
+    };
+
+    if let Ok(mut cut_change_count) = CUT_CHANGE_COUNT.lock() {
+        *cut_change_count = (operation == ClipboardOperation::Cut).then_some(change_count);
+    }
+
+    Ok(())
+}
+
+pub fn get_files(
+    _app: &tauri::AppHandle,
+) -> Result<Option<(Vec<PathBuf>, ClipboardOperation)>, String> {
+    let pasteboard = general_pasteboard();
+    let file_url_type = NSString::from_str(FILE_URL_TYPE);
+
+    let (items, change_count): (Option<Retained<NSArray<AnyObject>>>, isize) = unsafe {
+        (
+            msg_send![&*pasteboard, pasteboardItems],
+            msg_send![&*pasteboard, changeCount],
+        )
+    };
+
+    let paths: Vec<PathBuf> = items
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    let url_string: Option<Retained<NSString>> =
+                        unsafe { msg_send![&*item, stringForType: &*file_url_type] };
+                    let url = unsafe { NSURL::URLWithString(&url_string?) }?;
+                    url.path().map(|path| PathBuf::from(path.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if paths.is_empty() {
+        return Ok(None);
+    }
+
+    let is_cut = CUT_CHANGE_COUNT
+        .lock()
+        .map(|cut_change_count| *cut_change_count == Some(change_count))
+        .unwrap_or(false);
+
+    let operation = if is_cut {
+        ClipboardOperation::Cut
+    } else {
+        ClipboardOperation::Copy
+    };
+
+    Ok(Some((paths, operation)))
+}
+
+pub fn get_text(_app: &tauri::AppHandle) -> Result<Option<String>, String> {
+    let pasteboard = general_pasteboard();
+    let text_type = NSString::from_str(TEXT_TYPE);
+    let text: Option<Retained<NSString>> =
+        unsafe { msg_send![&*pasteboard, stringForType: &*text_type] };
+
+    Ok(text.map(|text| text.to_string()))
+}
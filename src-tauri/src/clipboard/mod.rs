@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Files on the system clipboard, in the native formats so copy/paste
+//! interoperates with Explorer, Finder, Nautilus and Dolphin.
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(target_os = "linux")]
+use linux as platform;
+#[cfg(target_os = "macos")]
+use macos as platform;
+#[cfg(windows)]
+use windows as platform;
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+mod platform {
+    use super::ClipboardOperation;
+    use std::path::PathBuf;
+
+    pub fn set_files(
+        _app: &tauri::AppHandle,
+        _paths: Vec<PathBuf>,
+        _operation: ClipboardOperation,
+    ) -> Result<(), String> {
+        Err("The system clipboard is not supported on this platform".to_string())
+    }
+
+    pub fn get_files(
+        _app: &tauri::AppHandle,
+    ) -> Result<Option<(Vec<PathBuf>, ClipboardOperation)>, String> {
+        Ok(None)
+    }
+
+    pub fn get_text(_app: &tauri::AppHandle) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+}
+
+pub use platform::get_text;
+
+use crate::utils::normalize_path;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardOperation {
+    Copy,
+    Cut,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipboardFiles {
+    pub paths: Vec<String>,
+    pub operation: ClipboardOperation,
+}
+
+/// Puts files on the system clipboard, marked as copied or cut
+#[tauri::command]
+pub async fn clipboard_set_files(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    operation: ClipboardOperation,
+) -> Result<(), String> {
+    if paths.is_empty() {
+        return Err("No paths to put on the clipboard".to_string());
+    }
+
+    let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+
+    tokio::task::spawn_blocking(move || platform::set_files(&app, paths, operation))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Reads files from the system clipboard, whichever app put them there.
+/// Returns `None` when the clipboard holds no files.
+#[tauri::command]
+pub async fn clipboard_get_files(app: tauri::AppHandle) -> Result<Option<ClipboardFiles>, String> {
+    let files = tokio::task::spawn_blocking(move || platform::get_files(&app))
+        .await
+        .map_err(|error| error.to_string())??;
+
+    Ok(files.map(|(paths, operation)| ClipboardFiles {
+        paths: paths
+            .iter()
+            .map(|path| normalize_path(&path.to_string_lossy()))
+            .collect(),
+        operation,
+    }))
+}
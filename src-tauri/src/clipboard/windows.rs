@@ -0,0 +1,201 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::ClipboardOperation;
+use std::ffi::OsString;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::PathBuf;
+use windows_sys::Win32::Foundation::GlobalFree;
+use windows_sys::Win32::System::DataExchange::{
+    CloseClipboard, EmptyClipboard, GetClipboardData, IsClipboardFormatAvailable, OpenClipboard,
+    RegisterClipboardFormatW, SetClipboardData,
+};
+use windows_sys::Win32::System::Memory::{
+    GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE, GMEM_ZEROINIT,
+};
+use windows_sys::Win32::UI::Shell::{DragQueryFileW, DROPFILES};
+
+const CF_UNICODETEXT: u32 = 13;
+const CF_HDROP: u32 = 15;
+const DROPEFFECT_COPY: u32 = 1;
+const DROPEFFECT_MOVE: u32 = 2;
+const OPEN_ATTEMPTS: u32 = 10;
+
+// Closes the clipboard when dropped, it stays locked for every other app
+// while open
+struct OpenedClipboard;
+
+impl OpenedClipboard {
+    fn open() -> Result<Self, String> {
+        // Another app may hold the clipboard for a moment
+        for _ in 0..OPEN_ATTEMPTS {
+            if unsafe { OpenClipboard(std::ptr::null_mut()) } != 0 {
+                return Ok(OpenedClipboard);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+
+        Err("The clipboard is in use by another application".to_string())
+    }
+}
+
+impl Drop for OpenedClipboard {
+    fn drop(&mut self) {
+        unsafe {
+            CloseClipboard();
+        }
+    }
+}
+
+fn drop_effect_format() -> u32 {
+    let name: Vec<u16> = "Preferred DropEffect"
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    unsafe { RegisterClipboardFormatW(name.as_ptr()) }
+}
+
+unsafe fn set_clipboard_bytes(format: u32, bytes: &[u8]) -> Result<(), String> {
+    let global = GlobalAlloc(GMEM_MOVEABLE | GMEM_ZEROINIT, bytes.len());
+    if global.is_null() {
+        return Err("Failed to allocate clipboard memory".to_string());
+    }
+
+    let pointer = GlobalLock(global) as *mut u8;
+    if pointer.is_null() {
+        GlobalFree(global);
+        return Err("Failed to lock clipboard memory".to_string());
+    }
+
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), pointer, bytes.len());
+    GlobalUnlock(global);
+
+    // The clipboard owns the memory only once SetClipboardData succeeds
+    if SetClipboardData(format, global).is_null() {
+        GlobalFree(global);
+        return Err("Failed to set clipboard data".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn set_files(
+    _app: &tauri::AppHandle,
+    paths: Vec<PathBuf>,
+    operation: ClipboardOperation,
+) -> Result<(), String> {
+    // CF_HDROP is a DROPFILES header followed by double-null terminated
+    // wide paths
+    let mut file_list: Vec<u16> = Vec::new();
+    for path in &paths {
+        file_list.extend(path.as_os_str().encode_wide());
+        file_list.push(0);
+    }
+    file_list.push(0);
+
+    let header = DROPFILES {
+        pFiles: std::mem::size_of::<DROPFILES>() as u32,
+        pt: unsafe { std::mem::zeroed() },
+        fNC: 0,
+        fWide: 1,
+    };
+    let mut drop_files_bytes: Vec<u8> = unsafe {
+        std::slice::from_raw_parts(
+            &header as *const DROPFILES as *const u8,
+            std::mem::size_of::<DROPFILES>(),
+        )
+    }
+    .to_vec();
+    drop_files_bytes.extend(file_list.iter().flat_map(|unit| unit.to_le_bytes()));
+
+    let drop_effect = match operation {
+        ClipboardOperation::Copy => DROPEFFECT_COPY,
+        ClipboardOperation::Cut => DROPEFFECT_MOVE,
+    };
+
+    let _clipboard = OpenedClipboard::open()?;
+
+    unsafe {
+        if EmptyClipboard() == 0 {
+            return Err("Failed to clear the clipboard".to_string());
+        }
+
+        set_clipboard_bytes(CF_HDROP, &drop_files_bytes)?;
+        set_clipboard_bytes(drop_effect_format(), &drop_effect.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+pub fn get_files(
+    _app: &tauri::AppHandle,
+) -> Result<Option<(Vec<PathBuf>, ClipboardOperation)>, String> {
+    let _clipboard = OpenedClipboard::open()?;
+
+    unsafe {
+        if IsClipboardFormatAvailable(CF_HDROP) == 0 {
+            return Ok(None);
+        }
+
+        let drop_handle = GetClipboardData(CF_HDROP);
+        if drop_handle.is_null() {
+            return Ok(None);
+        }
+
+        let file_count = DragQueryFileW(drop_handle, u32::MAX, std::ptr::null_mut(), 0);
+        let mut paths: Vec<PathBuf> = Vec::with_capacity(file_count as usize);
+
+        for index in 0..file_count {
+            let length = DragQueryFileW(drop_handle, index, std::ptr::null_mut(), 0) as usize;
+            let mut buffer: Vec<u16> = vec![0; length + 1];
+            DragQueryFileW(drop_handle, index, buffer.as_mut_ptr(), buffer.len() as u32);
+            paths.push(PathBuf::from(OsString::from_wide(&buffer[..length])));
+        }
+
+        let mut operation = ClipboardOperation::Copy;
+        let effect_handle = GetClipboardData(drop_effect_format());
+
+        if !effect_handle.is_null() {
+            let pointer = GlobalLock(effect_handle) as *const u32;
+            if !pointer.is_null() {
+                if std::ptr::read_unaligned(pointer) & DROPEFFECT_MOVE != 0 {
+                    operation = ClipboardOperation::Cut;
+                }
+                GlobalUnlock(effect_handle);
+            }
+        }
+
+        Ok(Some((paths, operation)))
+    }
+}
+
+pub fn get_text(_app: &tauri::AppHandle) -> Result<Option<String>, String> {
+    let _clipboard = OpenedClipboard::open()?;
+
+    unsafe {
+        if IsClipboardFormatAvailable(CF_UNICODETEXT) == 0 {
+            return Ok(None);
+        }
+
+        let text_handle = GetClipboardData(CF_UNICODETEXT);
+        if text_handle.is_null() {
+            return Ok(None);
+        }
+
+        let pointer = GlobalLock(text_handle) as *const u16;
+        if pointer.is_null() {
+            return Ok(None);
+        }
+
+        // NUL terminated UTF-16
+        let mut length = 0;
+        while *pointer.add(length) != 0 {
+            length += 1;
+        }
+        let text = String::from_utf16_lossy(std::slice::from_raw_parts(pointer, length));
+        GlobalUnlock(text_handle);
+
+        Ok(Some(text))
+    }
+}
@@ -0,0 +1,391 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use crate::utils::normalize_path;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+// Generated and minified files past this size would skew line counts
+const MAX_FILE_SIZE: u64 = 8 * 1024 * 1024;
+const BINARY_SNIFF_SIZE: usize = 8 * 1024;
+
+struct Language {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    line_comment: &'static [&'static str],
+}
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "TypeScript",
+        extensions: &["ts", "tsx", "mts", "cts"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "JavaScript",
+        extensions: &["js", "jsx", "mjs", "cjs"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "Vue",
+        extensions: &["vue"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "Svelte",
+        extensions: &["svelte"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "Python",
+        extensions: &["py", "pyw", "pyi"],
+        line_comment: &["#"],
+    },
+    Language {
+        name: "Ruby",
+        extensions: &["rb"],
+        line_comment: &["#"],
+    },
+    Language {
+        name: "Go",
+        extensions: &["go"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "Java",
+        extensions: &["java"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "Kotlin",
+        extensions: &["kt", "kts"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "Swift",
+        extensions: &["swift"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "C",
+        extensions: &["c", "h"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "C++",
+        extensions: &["cpp", "cc", "cxx", "hpp", "hh", "hxx"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "C#",
+        extensions: &["cs"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "PHP",
+        extensions: &["php"],
+        line_comment: &["//", "#"],
+    },
+    Language {
+        name: "Shell",
+        extensions: &["sh", "bash", "zsh", "fish"],
+        line_comment: &["#"],
+    },
+    Language {
+        name: "PowerShell",
+        extensions: &["ps1", "psm1"],
+        line_comment: &["#"],
+    },
+    Language {
+        name: "Perl",
+        extensions: &["pl", "pm"],
+        line_comment: &["#"],
+    },
+    Language {
+        name: "Lua",
+        extensions: &["lua"],
+        line_comment: &["--"],
+    },
+    Language {
+        name: "Dart",
+        extensions: &["dart"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "Scala",
+        extensions: &["scala"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "Haskell",
+        extensions: &["hs"],
+        line_comment: &["--"],
+    },
+    Language {
+        name: "Elixir",
+        extensions: &["ex", "exs"],
+        line_comment: &["#"],
+    },
+    Language {
+        name: "Zig",
+        extensions: &["zig"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "SQL",
+        extensions: &["sql"],
+        line_comment: &["--"],
+    },
+    Language {
+        name: "HTML",
+        extensions: &["html", "htm"],
+        line_comment: &[],
+    },
+    Language {
+        name: "CSS",
+        extensions: &["css"],
+        line_comment: &[],
+    },
+    Language {
+        name: "SCSS",
+        extensions: &["scss", "sass"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "Less",
+        extensions: &["less"],
+        line_comment: &["//"],
+    },
+    Language {
+        name: "JSON",
+        extensions: &["json", "jsonc"],
+        line_comment: &[],
+    },
+    Language {
+        name: "YAML",
+        extensions: &["yaml", "yml"],
+        line_comment: &["#"],
+    },
+    Language {
+        name: "TOML",
+        extensions: &["toml"],
+        line_comment: &["#"],
+    },
+    Language {
+        name: "XML",
+        extensions: &["xml", "xsd", "svg"],
+        line_comment: &[],
+    },
+    Language {
+        name: "Markdown",
+        extensions: &["md", "markdown"],
+        line_comment: &[],
+    },
+];
+
+// Interpreters named in shebang lines of extensionless scripts
+const SHEBANG_LANGUAGES: &[(&str, &str)] = &[
+    ("python", "Python"),
+    ("node", "JavaScript"),
+    ("deno", "TypeScript"),
+    ("ruby", "Ruby"),
+    ("perl", "Perl"),
+    ("bash", "Shell"),
+    ("zsh", "Shell"),
+    ("fish", "Shell"),
+    ("sh", "Shell"),
+    ("lua", "Lua"),
+    ("pwsh", "PowerShell"),
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LanguageStats {
+    pub language: String,
+    pub file_count: u64,
+    pub bytes: u64,
+    pub lines: u64,
+    pub code: u64,
+    pub comments: u64,
+    pub blanks: u64,
+}
+
+impl LanguageStats {
+    fn add(&mut self, other: &LanguageStats) {
+        self.file_count += other.file_count;
+        self.bytes += other.bytes;
+        self.lines += other.lines;
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CodeStats {
+    pub path: String,
+    /// Sorted by lines of code, largest first
+    pub languages: Vec<LanguageStats>,
+    pub total: LanguageStats,
+    /// Files not recognized as source code, binary or too large
+    pub skipped_count: u64,
+}
+
+fn language_by_extension(path: &Path) -> Option<&'static Language> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|language| language.extensions.contains(&extension.as_str()))
+}
+
+fn language_by_name(name: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|language| language.name == name)
+}
+
+// "#!/usr/bin/env python3" and "#!/bin/bash" both name the interpreter in
+// the last path component, possibly followed by a version
+fn language_by_shebang(first_line: &str) -> Option<&'static Language> {
+    let command = first_line.strip_prefix("#!")?.trim();
+    let mut words = command.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-'))?;
+    }
+
+    let interpreter = interpreter
+        .trim_end_matches(|character: char| character.is_ascii_digit() || character == '.');
+
+    SHEBANG_LANGUAGES
+        .iter()
+        .find(|(name, _)| *name == interpreter)
+        .and_then(|(_, language_name)| language_by_name(language_name))
+}
+
+fn count_file(path: &Path, size: u64) -> Option<(&'static str, LanguageStats)> {
+    if size > MAX_FILE_SIZE {
+        return None;
+    }
+
+    let mut file = File::open(path).ok()?;
+    let mut head = vec![0u8; BINARY_SNIFF_SIZE];
+    let head_length = file.read(&mut head).ok()?;
+    head.truncate(head_length);
+
+    if head.contains(&0) {
+        return None;
+    }
+
+    let language = match language_by_extension(path) {
+        Some(language) => language,
+        None if path.extension().is_none() => {
+            let first_line = head.split(|byte| *byte == b'\n').next()?;
+            language_by_shebang(&String::from_utf8_lossy(first_line))?
+        }
+        None => return None,
+    };
+
+    let mut stats = LanguageStats {
+        language: language.name.to_string(),
+        file_count: 1,
+        bytes: size,
+        ..Default::default()
+    };
+
+    let reader = BufReader::new(head.as_slice().chain(file));
+
+    for line in reader.split(b'\n') {
+        let line = line.ok()?;
+        let line = String::from_utf8_lossy(&line);
+        let trimmed = line.trim();
+        stats.lines += 1;
+
+        if trimmed.is_empty() {
+            stats.blanks += 1;
+        } else if language
+            .line_comment
+            .iter()
+            .any(|prefix| trimmed.starts_with(prefix))
+        {
+            stats.comments += 1;
+        } else {
+            stats.code += 1;
+        }
+    }
+
+    Some((language.name, stats))
+}
+
+fn analyze_blocking(root: &Path) -> Result<CodeStats, String> {
+    if !root.is_dir() {
+        return Err(format!("Path is not a directory: {}", root.display()));
+    }
+
+    // Honors .gitignore, .ignore and global git excludes, and skips hidden
+    // folders such as .git
+    let files: Vec<(std::path::PathBuf, u64)> = ignore::WalkBuilder::new(root)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+        })
+        .map(|entry| {
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            (entry.into_path(), size)
+        })
+        .collect();
+
+    let counted: Vec<Option<(&'static str, LanguageStats)>> = files
+        .par_iter()
+        .map(|(path, size)| count_file(path, *size))
+        .collect();
+
+    let mut by_language: HashMap<&'static str, LanguageStats> = HashMap::new();
+    let mut stats = CodeStats {
+        path: normalize_path(&root.to_string_lossy()),
+        ..Default::default()
+    };
+
+    for result in counted {
+        match result {
+            Some((language, file_stats)) => {
+                by_language
+                    .entry(language)
+                    .or_insert_with(|| LanguageStats {
+                        language: language.to_string(),
+                        ..Default::default()
+                    })
+                    .add(&file_stats);
+                stats.total.add(&file_stats);
+            }
+            None => stats.skipped_count += 1,
+        }
+    }
+
+    stats.languages = by_language.into_values().collect();
+    stats
+        .languages
+        .sort_by(|first, second| second.code.cmp(&first.code));
+
+    Ok(stats)
+}
+
+/// Counts files and lines per language in a project folder, respecting
+/// .gitignore rules
+#[tauri::command]
+pub async fn analyze_code_dir(path: String) -> Result<CodeStats, String> {
+    tokio::task::spawn_blocking(move || analyze_blocking(&crate::utils::to_os_path(&path)))
+        .await
+        .map_err(|error| error.to_string())?
+}
@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Secrets kept in the OS keychain: Windows Credential Manager, macOS
+//! Keychain or the Secret Service on Linux. Stores on disk only hold the
+//! `credential_ref` that names an entry.
+
+use serde::{Deserialize, Serialize};
+
+const KEYRING_SERVICE: &str = "com.sigma-file-manager.app";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareCredentials {
+    pub username: Option<String>,
+    pub password: String,
+}
+
+fn entry(credential_ref: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, credential_ref).map_err(|error| error.to_string())
+}
+
+pub(crate) fn save(credential_ref: &str, credentials: &ShareCredentials) -> Result<(), String> {
+    let secret = serde_json::to_string(credentials).map_err(|error| error.to_string())?;
+    entry(credential_ref)?
+        .set_password(&secret)
+        .map_err(|error| error.to_string())
+}
+
+pub(crate) fn load(credential_ref: &str) -> Result<Option<ShareCredentials>, String> {
+    match entry(credential_ref)?.get_password() {
+        Ok(secret) => serde_json::from_str(&secret)
+            .map(Some)
+            .map_err(|error| error.to_string()),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+pub(crate) fn delete(credential_ref: &str) -> Result<(), String> {
+    match entry(credential_ref)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Stores a username and password in the OS keychain and returns the
+/// reference to link with a saved share. Passing an existing
+/// `credential_ref` replaces its credentials.
+#[tauri::command]
+pub async fn save_share_credentials(
+    credential_ref: Option<String>,
+    username: Option<String>,
+    password: String,
+) -> Result<String, String> {
+    let credential_ref = credential_ref.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    tokio::task::spawn_blocking(move || {
+        save(&credential_ref, &ShareCredentials { username, password })?;
+        Ok(credential_ref)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Returns `None` when the keychain has no entry for `credential_ref`
+#[tauri::command]
+pub async fn load_share_credentials(
+    credential_ref: String,
+) -> Result<Option<ShareCredentials>, String> {
+    tokio::task::spawn_blocking(move || load(&credential_ref))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub async fn delete_share_credentials(credential_ref: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || delete(&credential_ref))
+        .await
+        .map_err(|error| error.to_string())?
+}
@@ -0,0 +1,415 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Structured comparison of two directory trees, the basis of the two-pane
+//! comparison view and of sync previews. Both trees are walked at the same
+//! time and files present on both sides are compared in parallel, with
+//! results streamed to the window in batches as they are found. A folder
+//! present on one side only is reported once, not file by file.
+
+use crate::mirror::MTIME_TOLERANCE_MS;
+use crate::requests::{self, ActiveRequest, ProgressEmitter};
+use crate::utils::normalize_path;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+const PROGRESS_EVENT: &str = "dir-compare-progress";
+const BATCH_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CompareOptions {
+    /// Treats files of equal size as different when their modification
+    /// times differ by more than FAT's 2 second precision
+    pub compare_mtime: bool,
+    /// Compares the content of files that look the same otherwise
+    pub compare_content: bool,
+    /// Patterns in `.gitignore` syntax, relative to each side's root, of
+    /// entries to leave out
+    pub exclude: Vec<String>,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            compare_mtime: true,
+            compare_content: false,
+            exclude: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    OnlyInLeft,
+    OnlyInRight,
+    Different,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffReason {
+    /// A file on one side and a folder on the other
+    Type,
+    Size,
+    Mtime,
+    Content,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TreeEntry {
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirDiffEntry {
+    pub relative_path: String,
+    pub kind: DiffKind,
+    /// Set for `Different` entries
+    pub reason: Option<DiffReason>,
+    pub is_dir: bool,
+    pub left_size: Option<u64>,
+    pub right_size: Option<u64>,
+    pub left_modified: Option<u64>,
+    pub right_modified: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirCompareProgress {
+    pub request_id: String,
+    pub entries: Vec<DirDiffEntry>,
+    pub compared_count: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DirComparison {
+    pub left: String,
+    pub right: String,
+    pub entries: Vec<DirDiffEntry>,
+    pub identical_count: u64,
+    pub errors: Vec<String>,
+    pub cancelled: bool,
+}
+
+fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub fn exclusion_matcher(root: &Path, patterns: &[String]) -> Result<Gitignore, String> {
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in patterns {
+        builder
+            .add_line(None, pattern)
+            .map_err(|error| format!("Invalid exclusion pattern {}: {}", pattern, error))?;
+    }
+    builder.build().map_err(|error| error.to_string())
+}
+
+/// Everything below `root` by normalized relative path, leaving out what
+/// `exclude` matches
+pub fn collect_tree(
+    root: &Path,
+    exclude: &Gitignore,
+) -> (BTreeMap<String, TreeEntry>, Vec<String>) {
+    let mut entries = BTreeMap::new();
+    let mut errors = Vec::new();
+
+    let walker = WalkDir::new(root)
+        .follow_links(false)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| {
+            !exclude
+                .matched(entry.path(), entry.file_type().is_dir())
+                .is_ignore()
+        });
+
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                errors.push(error.to_string());
+                continue;
+            }
+        };
+
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                errors.push(format!("{}: {}", entry.path().display(), error));
+                continue;
+            }
+        };
+
+        let relative_path = match entry.path().strip_prefix(root) {
+            Ok(relative_path) => normalize_path(&relative_path.to_string_lossy()),
+            Err(_) => continue,
+        };
+
+        entries.insert(
+            relative_path,
+            TreeEntry {
+                is_dir: metadata.is_dir(),
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                modified_ms: metadata.modified().map(to_millis).unwrap_or(0),
+            },
+        );
+    }
+
+    (entries, errors)
+}
+
+/// Whether a folder above `relative_path` is in `dirs`
+pub fn has_ancestor_in(relative_path: &str, dirs: &HashSet<String>) -> bool {
+    let mut path = relative_path;
+    while let Some((parent, _)) = path.rsplit_once('/') {
+        if dirs.contains(parent) {
+            return true;
+        }
+        path = parent;
+    }
+    false
+}
+
+fn one_sided_entry(relative_path: &str, entry: &TreeEntry, kind: DiffKind) -> DirDiffEntry {
+    let size = (!entry.is_dir).then_some(entry.size);
+    let modified = Some(entry.modified_ms);
+    let is_left = kind == DiffKind::OnlyInLeft;
+
+    DirDiffEntry {
+        relative_path: relative_path.to_string(),
+        kind,
+        reason: None,
+        is_dir: entry.is_dir,
+        left_size: if is_left { size } else { None },
+        right_size: if is_left { None } else { size },
+        left_modified: if is_left { modified } else { None },
+        right_modified: if is_left { None } else { modified },
+    }
+}
+
+fn metadata_difference(
+    left: &TreeEntry,
+    right: &TreeEntry,
+    options: &CompareOptions,
+) -> Option<DiffReason> {
+    if left.is_dir != right.is_dir {
+        return Some(DiffReason::Type);
+    }
+    if left.is_dir {
+        return None;
+    }
+    if left.size != right.size {
+        return Some(DiffReason::Size);
+    }
+    if options.compare_mtime && left.modified_ms.abs_diff(right.modified_ms) > MTIME_TOLERANCE_MS {
+        return Some(DiffReason::Mtime);
+    }
+    None
+}
+
+/// Compares two trees, handing each batch of differences to `on_batch` as
+/// it is found. Stops early when `cancel_token` is set.
+pub fn compare_trees(
+    left: &Path,
+    right: &Path,
+    options: &CompareOptions,
+    cancel_token: &AtomicBool,
+    mut on_batch: impl FnMut(&[DirDiffEntry], u64),
+) -> Result<DirComparison, String> {
+    if !left.is_dir() {
+        return Err(format!("Not a directory: {}", left.display()));
+    }
+    if !right.is_dir() {
+        return Err(format!("Not a directory: {}", right.display()));
+    }
+
+    let left_exclude = exclusion_matcher(left, &options.exclude)?;
+    let right_exclude = exclusion_matcher(right, &options.exclude)?;
+    let ((left_tree, left_errors), (right_tree, right_errors)) = rayon::join(
+        || collect_tree(left, &left_exclude),
+        || collect_tree(right, &right_exclude),
+    );
+
+    let mut comparison = DirComparison {
+        left: normalize_path(&left.to_string_lossy()),
+        right: normalize_path(&right.to_string_lossy()),
+        errors: [left_errors, right_errors].concat(),
+        ..Default::default()
+    };
+
+    // Folders reported as a whole, including folders that are files on the
+    // other side, whose contents are left out
+    let mut reported_dirs = HashSet::new();
+    let mut one_sided = Vec::new();
+    let mut common = Vec::new();
+
+    for (relative_path, left_entry) in &left_tree {
+        if has_ancestor_in(relative_path, &reported_dirs) {
+            continue;
+        }
+        match right_tree.get(relative_path) {
+            Some(right_entry) => {
+                if left_entry.is_dir && !right_entry.is_dir {
+                    reported_dirs.insert(relative_path.clone());
+                }
+                common.push((relative_path, left_entry, right_entry));
+            }
+            None => {
+                if left_entry.is_dir {
+                    reported_dirs.insert(relative_path.clone());
+                }
+                one_sided.push(one_sided_entry(
+                    relative_path,
+                    left_entry,
+                    DiffKind::OnlyInLeft,
+                ));
+            }
+        }
+    }
+
+    reported_dirs.clear();
+    for (relative_path, right_entry) in &right_tree {
+        if has_ancestor_in(relative_path, &reported_dirs) {
+            continue;
+        }
+        if let Some(left_entry) = left_tree.get(relative_path) {
+            if right_entry.is_dir && !left_entry.is_dir {
+                reported_dirs.insert(relative_path.clone());
+            }
+            continue;
+        }
+        if right_entry.is_dir {
+            reported_dirs.insert(relative_path.clone());
+        }
+        one_sided.push(one_sided_entry(
+            relative_path,
+            right_entry,
+            DiffKind::OnlyInRight,
+        ));
+    }
+
+    let mut compared_count = one_sided.len() as u64;
+    for batch in one_sided.chunks(BATCH_SIZE) {
+        on_batch(batch, compared_count);
+    }
+    comparison.entries = one_sided;
+
+    for batch in common.chunks(BATCH_SIZE) {
+        if cancel_token.load(Ordering::Relaxed) {
+            comparison.cancelled = true;
+            break;
+        }
+
+        let results: Vec<Result<Option<DirDiffEntry>, String>> = batch
+            .par_iter()
+            .map(|(relative_path, left_entry, right_entry)| {
+                let mut reason = metadata_difference(left_entry, right_entry, options);
+
+                if reason.is_none() && options.compare_content && !left_entry.is_dir {
+                    let is_equal = crate::file_compare::files_equal(
+                        &left.join(relative_path.as_str()),
+                        &right.join(relative_path.as_str()),
+                    )
+                    .map_err(|error| format!("{}: {}", relative_path, error))?;
+                    if !is_equal {
+                        reason = Some(DiffReason::Content);
+                    }
+                }
+
+                Ok(reason.map(|reason| DirDiffEntry {
+                    relative_path: (*relative_path).clone(),
+                    kind: DiffKind::Different,
+                    reason: Some(reason),
+                    is_dir: left_entry.is_dir && right_entry.is_dir,
+                    left_size: (!left_entry.is_dir).then_some(left_entry.size),
+                    right_size: (!right_entry.is_dir).then_some(right_entry.size),
+                    left_modified: Some(left_entry.modified_ms),
+                    right_modified: Some(right_entry.modified_ms),
+                }))
+            })
+            .collect();
+
+        compared_count += batch.len() as u64;
+        let mut differences = Vec::new();
+
+        for result in results {
+            match result {
+                Ok(Some(entry)) => differences.push(entry),
+                Ok(None) => comparison.identical_count += 1,
+                Err(error) => comparison.errors.push(error),
+            }
+        }
+
+        if !differences.is_empty() {
+            on_batch(&differences, compared_count);
+        }
+        comparison.entries.extend(differences);
+    }
+
+    comparison
+        .entries
+        .sort_by(|first, second| first.relative_path.cmp(&second.relative_path));
+
+    Ok(comparison)
+}
+
+/// Compares two folders: entries only on the left, only on the right, and
+/// on both sides but different by type, size, modification time or, with
+/// `compare_content`, content. With a `request_id`, differences are also
+/// emitted as `dir-compare-progress` events while the comparison runs.
+#[tauri::command]
+pub async fn compare_dirs(
+    app: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    left: String,
+    right: String,
+    options: Option<CompareOptions>,
+    request_id: Option<String>,
+) -> Result<DirComparison, String> {
+    let options = options.unwrap_or_default();
+    let active_request = ActiveRequest::register(request_id.as_deref());
+    let cancel_token = active_request.cancel_token();
+    let owner_window = window.label().to_string();
+
+    tokio::task::spawn_blocking(move || {
+        let progress = ProgressEmitter::new(
+            &app,
+            Some(&owner_window),
+            PROGRESS_EVENT,
+            request_id.as_deref(),
+        );
+        compare_trees(
+            Path::new(&left),
+            Path::new(&right),
+            &options,
+            &cancel_token,
+            |entries, compared_count| {
+                progress.emit(true, |request_id| DirCompareProgress {
+                    request_id: request_id.to_string(),
+                    entries: entries.to_vec(),
+                    compared_count,
+                });
+            },
+        )
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub fn cancel_compare_dirs(request_id: String) {
+    requests::cancel(&request_id);
+}
@@ -2,10 +2,16 @@
 // License: GNU GPLv3 or later. See the license file in the project root for more information.
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
-use crate::utils::normalize_path;
+use crate::faults::FaultPoint;
+use crate::git::{GitFileStatus, GitRepoSummary};
+use crate::git_ignore::{ignored_children, GitIgnoreMode};
+use crate::messages;
+use crate::utils::{normalize_path, to_os_path};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::UNIX_EPOCH;
 use sysinfo::Disks;
 
@@ -15,6 +21,8 @@ pub struct DirEntry {
     pub ext: Option<String>,
     pub path: String,
     pub size: u64,
+    /// Left `None` when listing without `include_item_counts`, see
+    /// `get_item_counts`
     pub item_count: Option<u32>,
     pub modified_time: u64,
     pub accessed_time: u64,
@@ -23,7 +31,34 @@ pub struct DirEntry {
     pub is_file: bool,
     pub is_dir: bool,
     pub is_symlink: bool,
+    /// A `.lnk` file on Windows or a `.desktop` launcher on Linux, which
+    /// `resolve_shortcut` reads the target of
+    #[serde(default)]
+    pub is_shortcut: bool,
     pub is_hidden: bool,
+    /// Read-only by the attribute on Windows, by the owner's write bit
+    /// elsewhere
+    #[serde(default)]
+    pub is_readonly: bool,
+    /// Space the file takes on disk, set when it is less than `size`
+    /// because the file is sparse or compressed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allocated_size: Option<u64>,
+    pub hard_link_count: Option<u64>,
+    pub file_id: Option<String>,
+    pub mode: Option<u32>,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub tags: Option<Vec<crate::tags::FileTag>>,
+    /// Matched by git ignore rules, set when listing with `GitIgnoreMode::Mark`
+    #[serde(default)]
+    pub git_ignored: bool,
+    /// Set when listing with `include_git_status` inside a repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_status: Option<GitFileStatus>,
+    /// Total size of a folder, when the search index keeps it up to date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indexed_size: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,6 +68,8 @@ pub struct DirContents {
     pub total_count: usize,
     pub dir_count: usize,
     pub file_count: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_repository: Option<GitRepoSummary>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -58,9 +95,22 @@ pub struct MountableDevice {
     pub device_path: String,
     pub file_system: String,
     pub size: u64,
+    /// LUKS or BitLocker container
+    pub is_encrypted: bool,
+    /// Needs `unlock_device` before it can be mounted
+    pub is_locked: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct EjectResult {
+    pub ejected: bool,
+    /// Files on the drive are still open, which blocks ejecting it
+    pub files_in_use: bool,
+    /// What the system reported when it refused, such as the blocking process
+    pub details: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkShareParams {
     pub protocol: String,
     pub host: String,
@@ -71,7 +121,7 @@ pub struct NetworkShareParams {
     pub mount_name: String,
 }
 
-fn is_hidden(path: &Path) -> bool {
+pub(crate) fn is_hidden(path: &Path) -> bool {
     #[cfg(windows)]
     {
         use std::os::windows::fs::MetadataExt;
@@ -91,13 +141,84 @@ fn is_hidden(path: &Path) -> bool {
     }
 }
 
+// Returns (hard link count, "volume:file index") so entries sharing storage
+// can be grouped by the frontend.
+#[cfg(unix)]
+fn get_link_info(_path: &Path, metadata: &fs::Metadata) -> (Option<u64>, Option<String>) {
+    use std::os::unix::fs::MetadataExt;
+    (
+        Some(metadata.nlink()),
+        Some(format!("{}:{}", metadata.dev(), metadata.ino())),
+    )
+}
+
+#[cfg(windows)]
+fn get_link_info(path: &Path, metadata: &fs::Metadata) -> (Option<u64>, Option<String>) {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION,
+    };
+
+    // Directories can't be hard linked, skip the extra handle open for them
+    if !metadata.is_file() {
+        return (None, None);
+    }
+
+    let file = match fs::OpenOptions::new().access_mode(0).open(path) {
+        Ok(file) => file,
+        Err(_) => return (None, None),
+    };
+
+    let mut info = BY_HANDLE_FILE_INFORMATION::default();
+    let got_info =
+        unsafe { GetFileInformationByHandle(HANDLE(file.as_raw_handle()), &mut info).is_ok() };
+
+    if !got_info {
+        return (None, None);
+    }
+
+    let file_index = ((info.nFileIndexHigh as u64) << 32) | info.nFileIndexLow as u64;
+    (
+        Some(info.nNumberOfLinks as u64),
+        Some(format!("{}:{}", info.dwVolumeSerialNumber, file_index)),
+    )
+}
+
+#[cfg(not(any(unix, windows)))]
+fn get_link_info(_path: &Path, _metadata: &fs::Metadata) -> (Option<u64>, Option<String>) {
+    (None, None)
+}
+
+// Returns (permission bits, owner name, group name) on Unix.
+#[cfg(unix)]
+pub(crate) fn get_ownership_info(
+    metadata: &fs::Metadata,
+) -> (Option<u32>, Option<String>, Option<String>) {
+    use crate::permissions::unix::{group_name, user_name};
+    use std::os::unix::fs::MetadataExt;
+    (
+        Some(metadata.mode() & 0o7777),
+        Some(user_name(metadata.uid())),
+        Some(group_name(metadata.gid())),
+    )
+}
+
+#[cfg(not(unix))]
+pub(crate) fn get_ownership_info(
+    _metadata: &fs::Metadata,
+) -> (Option<u32>, Option<String>, Option<String>) {
+    (None, None, None)
+}
+
 fn get_extension(path: &Path) -> Option<String> {
     path.extension()
         .and_then(|ext| ext.to_str())
         .map(|ext| ext.to_lowercase())
 }
 
-fn get_mime_type(extension: &Option<String>) -> Option<String> {
+pub(crate) fn get_mime_type(extension: &Option<String>) -> Option<String> {
     extension.as_ref().map(|ext| {
         match ext.as_str() {
             "txt" | "text" => "text/plain",
@@ -116,6 +237,10 @@ fn get_mime_type(extension: &Option<String>) -> Option<String> {
             "jpg" | "jpeg" => "image/jpeg",
             "gif" => "image/gif",
             "webp" => "image/webp",
+            "heic" => "image/heic",
+            "heif" => "image/heif",
+            "avif" => "image/avif",
+            "jxl" => "image/jxl",
             "svg" => "image/svg+xml",
             "ico" => "image/x-icon",
             "mp3" => "audio/mpeg",
@@ -154,7 +279,11 @@ fn get_mime_type(extension: &Option<String>) -> Option<String> {
     })
 }
 
-fn read_entry(path: &Path) -> Option<DirEntry> {
+fn count_items(path: &Path) -> Option<u32> {
+    fs::read_dir(path).ok().map(|entries| entries.count() as u32)
+}
+
+fn read_entry(path: &Path, include_item_count: bool) -> Option<DirEntry> {
     let metadata = match fs::metadata(path) {
         Ok(meta) => meta,
         Err(_) => return None,
@@ -194,10 +323,8 @@ fn read_entry(path: &Path) -> Option<DirEntry> {
 
     let size = if is_file { metadata.len() } else { 0 };
 
-    let item_count = if is_dir {
-        fs::read_dir(path)
-            .ok()
-            .map(|entries| entries.count() as u32)
+    let item_count = if is_dir && include_item_count {
+        count_items(path)
     } else {
         None
     };
@@ -208,6 +335,9 @@ fn read_entry(path: &Path) -> Option<DirEntry> {
         None
     };
 
+    let (hard_link_count, file_id) = get_link_info(path, &metadata);
+    let (mode, owner, group) = get_ownership_info(&metadata);
+
     Some(DirEntry {
         name,
         ext: extension,
@@ -221,13 +351,49 @@ fn read_entry(path: &Path) -> Option<DirEntry> {
         is_file,
         is_dir,
         is_symlink,
+        is_shortcut: is_file && crate::shortcuts::is_shortcut(path),
         is_hidden: is_hidden(path),
+        is_readonly: crate::file_attributes::is_readonly(&metadata),
+        allocated_size: crate::sparse_files::allocated_size(path, &metadata),
+        hard_link_count,
+        file_id,
+        mode,
+        owner,
+        group,
+        tags: None,
+        git_ignored: false,
+        git_status: None,
+        indexed_size: None,
     })
 }
 
+/// Lists the folder at `path`. Entries are read in parallel, which hides
+/// the round trip of each one on network shares. Counting the items of
+/// every subfolder takes another read per folder, so the counts are only
+/// included with `include_item_counts`; otherwise `get_item_counts` fills
+/// them in for the rows on screen.
 #[tauri::command]
-pub fn read_dir(path: String) -> Result<DirContents, String> {
-    let directory = Path::new(&path);
+pub async fn read_dir(
+    path: String,
+    git_ignore: Option<GitIgnoreMode>,
+    include_git_status: Option<bool>,
+    include_item_counts: Option<bool>,
+) -> Result<DirContents, String> {
+    tokio::task::spawn_blocking(move || {
+        read_dir_blocking(path, git_ignore, include_git_status, include_item_counts)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+pub fn read_dir_blocking(
+    path: String,
+    git_ignore: Option<GitIgnoreMode>,
+    include_git_status: Option<bool>,
+    include_item_counts: Option<bool>,
+) -> Result<DirContents, String> {
+    let _timer = crate::metrics::time_command("read_dir");
+    let directory = &to_os_path(&path);
 
     if !directory.exists() {
         return Err(format!("Path does not exist: {}", path));
@@ -237,29 +403,56 @@ pub fn read_dir(path: String) -> Result<DirContents, String> {
         return Err(format!("Path is not a directory: {}", path));
     }
 
+    crate::faults::check(FaultPoint::ReadDir, directory).map_err(|error| error.to_string())?;
     let read_result = fs::read_dir(directory).map_err(|error| error.to_string())?;
+    let git_ignore = git_ignore.unwrap_or_default();
+    let ignored_paths = match git_ignore {
+        GitIgnoreMode::Off => Default::default(),
+        GitIgnoreMode::Mark | GitIgnoreMode::Exclude => ignored_children(directory),
+    };
 
-    let mut entries: Vec<DirEntry> = Vec::new();
-    let mut dir_count = 0;
-    let mut file_count = 0;
-
-    for entry_result in read_result {
-        if let Ok(entry) = entry_result {
-            if let Some(dir_entry) = read_entry(&entry.path()) {
-                if dir_entry.is_dir {
-                    dir_count += 1;
-                } else if dir_entry.is_file {
-                    file_count += 1;
-                }
-                entries.push(dir_entry);
+    let include_item_counts = include_item_counts.unwrap_or(false);
+
+    let paths: Vec<(PathBuf, bool)> = read_result
+        .filter_map(|entry_result| entry_result.ok())
+        .map(|entry| {
+            let entry_path = entry.path();
+            let git_ignored = ignored_paths.contains(&entry_path);
+            (entry_path, git_ignored)
+        })
+        .filter(|(_, git_ignored)| !(*git_ignored && git_ignore == GitIgnoreMode::Exclude))
+        .collect();
+
+    let mut entries: Vec<DirEntry> = paths
+        .par_iter()
+        .filter_map(|(entry_path, git_ignored)| {
+            let mut dir_entry = read_entry(entry_path, include_item_counts)?;
+            dir_entry.git_ignored = *git_ignored;
+            Some(dir_entry)
+        })
+        .collect();
+
+    let dir_count = entries.iter().filter(|entry| entry.is_dir).count();
+    let file_count = entries.iter().filter(|entry| entry.is_file).count();
+
+    crate::tags::attach_tags(&mut entries);
+    crate::folder_sizes::attach_sizes(&mut entries);
+
+    let git_repository = if include_git_status.unwrap_or(false) {
+        crate::git::dir_status(directory).map(|(summary, statuses)| {
+            for entry in &mut entries {
+                entry.git_status = statuses.get(&entry.name).copied();
             }
-        }
-    }
+            summary
+        })
+    } else {
+        None
+    };
 
     entries.sort_by(|first, second| match (first.is_dir, second.is_dir) {
         (true, false) => std::cmp::Ordering::Less,
         (false, true) => std::cmp::Ordering::Greater,
-        _ => first.name.to_lowercase().cmp(&second.name.to_lowercase()),
+        _ => crate::text::compare_names(&first.name, &second.name),
     });
 
     Ok(DirContents {
@@ -268,9 +461,28 @@ pub fn read_dir(path: String) -> Result<DirContents, String> {
         total_count: dir_count + file_count,
         dir_count,
         file_count,
+        git_repository,
     })
 }
 
+/// Counts the items inside each of the folders at `paths`, such as those
+/// of a listing read without `include_item_counts` that are on screen.
+/// Folders that can't be read are left out.
+#[tauri::command]
+pub async fn get_item_counts(paths: Vec<String>) -> Result<HashMap<String, u32>, String> {
+    tokio::task::spawn_blocking(move || {
+        paths
+            .into_par_iter()
+            .filter_map(|path| {
+                let count = count_items(&to_os_path(&path))?;
+                Some((path, count))
+            })
+            .collect()
+    })
+    .await
+    .map_err(|error| error.to_string())
+}
+
 // ---------------------------------------------------------------------------
 // Linux: mount filtering and display names
 // ---------------------------------------------------------------------------
@@ -716,6 +928,32 @@ fn get_partition_fs_type(device_name: &str) -> Option<String> {
     }
 }
 
+#[cfg(target_os = "linux")]
+fn is_encrypted_file_system(fs_type: &str) -> bool {
+    // lsblk's names for LUKS and BitLocker signatures
+    matches!(fs_type, "crypto_LUKS" | "BitLocker")
+}
+
+/// The dm-crypt device an encrypted partition is unlocked as, if it is open
+#[cfg(target_os = "linux")]
+fn linux_cleartext_device(device_path: &str) -> Option<String> {
+    let name = fs::canonicalize(device_path)
+        .ok()?
+        .file_name()?
+        .to_string_lossy()
+        .to_string();
+
+    if !get_partition_fs_type(&name).is_some_and(|fs_type| is_encrypted_file_system(&fs_type)) {
+        return None;
+    }
+
+    fs::read_dir(Path::new("/sys/class/block").join(&name).join("holders"))
+        .ok()?
+        .flatten()
+        .map(|entry| format!("/dev/{}", entry.file_name().to_string_lossy()))
+        .next()
+}
+
 // ---------------------------------------------------------------------------
 // Mountable device discovery
 // ---------------------------------------------------------------------------
@@ -727,12 +965,47 @@ pub fn get_mountable_devices() -> Result<Vec<MountableDevice>, String> {
         return Ok(linux_get_mountable_devices());
     }
 
-    #[cfg(not(target_os = "linux"))]
+    // Windows mounts every volume it can read, so only locked ones are left
+    #[cfg(windows)]
+    {
+        return Ok(windows_locked_volumes());
+    }
+
+    #[cfg(target_os = "macos")]
     {
         Ok(Vec::new())
     }
 }
 
+/// BitLocker volumes waiting for a passphrase. The shell's protection
+/// property is readable without admin rights, unlike Get-BitLockerVolume,
+/// and is 6 for locked volumes.
+#[cfg(windows)]
+fn windows_locked_volumes() -> Vec<MountableDevice> {
+    let script = "$shell = New-Object -ComObject Shell.Application; \
+        Get-Volume | Where-Object DriveLetter | ForEach-Object { \
+        $drive = \"$($_.DriveLetter):\"; \
+        $protection = $shell.NameSpace(17).ParseName($drive).ExtendedProperty(\
+        'System.Volume.BitLockerProtection'); \
+        if ($protection -eq 6) { \"$drive`t$($_.Size)\" } }";
+
+    windows_powershell(script)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (drive, size) = line.trim().split_once('\t')?;
+            Some(MountableDevice {
+                name: drive.to_string(),
+                device_path: drive.to_string(),
+                file_system: "BitLocker".to_string(),
+                size: size.trim().parse().unwrap_or(0),
+                is_encrypted: true,
+                is_locked: true,
+            })
+        })
+        .collect()
+}
+
 #[cfg(target_os = "linux")]
 fn linux_get_mountable_devices() -> Vec<MountableDevice> {
     let mounted_devices: std::collections::HashSet<String> = fs::read_to_string("/proc/mounts")
@@ -813,6 +1086,17 @@ fn linux_get_mountable_devices() -> Vec<MountableDevice> {
                 continue;
             }
 
+            let is_encrypted = fs_type.as_deref().is_some_and(is_encrypted_file_system);
+            let cleartext_device = linux_cleartext_device(&dev_path);
+            if let Some(cleartext_device) = &cleartext_device {
+                let canonical_cleartext = fs::canonicalize(cleartext_device)
+                    .map(|resolved| resolved.to_string_lossy().to_string())
+                    .unwrap_or_else(|_| cleartext_device.clone());
+                if mounted_devices.contains(&canonical_cleartext) {
+                    continue;
+                }
+            }
+
             let size_sectors: u64 = fs::read_to_string(
                 sys_block
                     .join(&block_name)
@@ -833,6 +1117,8 @@ fn linux_get_mountable_devices() -> Vec<MountableDevice> {
                 device_path: dev_path,
                 file_system: fs_type.unwrap_or_default(),
                 size: size_sectors * 512,
+                is_encrypted,
+                is_locked: is_encrypted && cleartext_device.is_none(),
             });
         }
     }
@@ -848,6 +1134,19 @@ fn linux_get_mountable_devices() -> Vec<MountableDevice> {
 pub fn mount_drive(device_path: String) -> Result<String, String> {
     #[cfg(target_os = "linux")]
     {
+        // Unlocked containers are mounted through their decrypted device
+        let device_path = match linux_cleartext_device(&device_path) {
+            Some(cleartext_device) => cleartext_device,
+            None if get_partition_fs_type(device_path.trim_start_matches("/dev/"))
+                .is_some_and(|fs_type| is_encrypted_file_system(&fs_type)) =>
+            {
+                return Err(messages::MOUNT_DEVICE_LOCKED
+                    .with("device", &device_path)
+                    .into());
+            }
+            None => device_path,
+        };
+
         if let Ok(output) = std::process::Command::new("udisksctl")
             .args(["mount", "-b", &device_path, "--no-user-interaction"])
             .output()
@@ -872,10 +1171,9 @@ pub fn mount_drive(device_path: String) -> Result<String, String> {
             }
         }
 
-        Err(format!(
-            "Could not mount {}. Install udisks2 for automatic mounting.",
-            device_path
-        ))
+        Err(messages::MOUNT_INSTALL_UDISKS
+            .with("device", &device_path)
+            .into())
     }
 
     #[cfg(target_os = "macos")]
@@ -883,7 +1181,11 @@ pub fn mount_drive(device_path: String) -> Result<String, String> {
         let output = std::process::Command::new("diskutil")
             .args(["mount", &device_path])
             .output()
-            .map_err(|mount_error| format!("Failed to run diskutil: {}", mount_error))?;
+            .map_err(|mount_error| {
+                messages::MOUNT_COMMAND_FAILED
+                    .with("command", "diskutil")
+                    .with("error", mount_error)
+            })?;
 
         if output.status.success() {
             let stdout = String::from_utf8_lossy(&output.stdout).to_string();
@@ -902,7 +1204,7 @@ pub fn mount_drive(device_path: String) -> Result<String, String> {
     #[cfg(windows)]
     {
         let _ = device_path;
-        Err("Mount not supported on Windows - drives are auto-mounted".to_string())
+        Err(messages::MOUNT_NOT_SUPPORTED_WINDOWS.text().into())
     }
 }
 
@@ -923,7 +1225,11 @@ pub fn unmount_drive(device_path: String, mount_point: String) -> Result<(), Str
         let output = std::process::Command::new("diskutil")
             .args(["unmount", target])
             .output()
-            .map_err(|unmount_error| format!("Failed to run diskutil: {}", unmount_error))?;
+            .map_err(|unmount_error| {
+                messages::MOUNT_COMMAND_FAILED
+                    .with("command", "diskutil")
+                    .with("error", unmount_error)
+            })?;
 
         if output.status.success() {
             Ok(())
@@ -936,7 +1242,7 @@ pub fn unmount_drive(device_path: String, mount_point: String) -> Result<(), Str
     #[cfg(windows)]
     {
         let _ = (device_path, mount_point);
-        Err("Unmount not supported on Windows - use system tray eject".to_string())
+        Err(messages::UNMOUNT_NOT_SUPPORTED_WINDOWS.text().into())
     }
 }
 
@@ -975,10 +1281,804 @@ fn linux_unmount(device_path: &str, mount_point: &str) -> Result<(), String> {
         }
     }
 
-    Err(format!(
-        "Could not unmount. Install udisks2 or use 'umount {}'.",
-        mount_point
-    ))
+    Err(messages::UNMOUNT_INSTALL_UDISKS
+        .with("mountPoint", mount_point)
+        .into())
+}
+
+/// Unlocks a LUKS or BitLocker container and mounts the decrypted volume.
+/// Returns the mount point, or an empty string when it isn't known.
+#[tauri::command]
+pub fn unlock_device(device_path: String, passphrase: String) -> Result<String, String> {
+    #[cfg(target_os = "linux")]
+    {
+        // Already unlocked, e.g. by the desktop's own prompt
+        let cleartext_device = match linux_cleartext_device(&device_path) {
+            Some(cleartext_device) => cleartext_device,
+            None => {
+                // The key file is read as is, so the passphrase goes without a newline
+                let stdout = run_with_stdin(
+                    "udisksctl",
+                    &[
+                        "unlock",
+                        "-b",
+                        &device_path,
+                        "--key-file",
+                        "/dev/stdin",
+                        "--no-user-interaction",
+                    ],
+                    &passphrase,
+                )?;
+                // "Unlocked /dev/sdb1 as /dev/dm-0."
+                stdout
+                    .split(" as ")
+                    .nth(1)
+                    .map(|segment| segment.trim().trim_end_matches('.').to_string())
+                    .ok_or_else(|| format!("Unexpected udisksctl output: {}", stdout.trim()))?
+            }
+        };
+
+        return mount_drive(cleartext_device);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // Mounts the volume once it is unlocked
+        run_with_stdin(
+            "diskutil",
+            &["apfs", "unlockVolume", &device_path, "-stdinpassphrase"],
+            &format!("{}\n", passphrase),
+        )?;
+
+        Ok(String::new())
+    }
+
+    #[cfg(windows)]
+    {
+        let drive_letter = device_path
+            .chars()
+            .next()
+            .filter(|letter| letter.is_ascii_alphabetic() && device_path[1..].starts_with(':'))
+            .ok_or_else(|| format!("Not a drive letter: {}", device_path))?;
+
+        // Read from stdin so the passphrase stays out of the process list.
+        // Unlocked BitLocker volumes keep their drive letter.
+        let script = format!(
+            "$passphrase = ConvertTo-SecureString ([Console]::In.ReadLine()) -AsPlainText -Force; \
+             Unlock-BitLocker -MountPoint '{}:' -Password $passphrase | Out-Null",
+            drive_letter
+        );
+        run_with_stdin(
+            "powershell",
+            &["-NoProfile", "-NonInteractive", "-Command", &script],
+            &format!("{}\n", passphrase),
+        )?;
+
+        Ok(normalize_path(&format!("{}:\\", drive_letter)))
+    }
+}
+
+// Runs a tool that reads a secret from stdin and returns its stdout
+fn run_with_stdin(program: &str, args: &[&str], input: &str) -> Result<String, String> {
+    use std::io::Write;
+
+    let mut child = std::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|spawn_error| {
+            messages::MOUNT_COMMAND_FAILED
+                .with("command", program)
+                .with("error", spawn_error)
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|error| error.to_string())?;
+    }
+
+    let output = child.wait_with_output().map_err(|error| error.to_string())?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        Err(stderr.trim().to_string())
+    }
+}
+
+/// Unmounts the drive and then powers it off or ejects the media, so it
+/// can be unplugged safely. Open files are reported in the result rather
+/// than as an error, so the UI can ask the user to close them.
+#[tauri::command]
+pub fn eject_drive(device_path: String, mount_point: String) -> Result<EjectResult, String> {
+    #[cfg(target_os = "linux")]
+    {
+        return linux_eject(&device_path, &mount_point);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let target = if device_path.is_empty() {
+            &mount_point
+        } else {
+            &device_path
+        };
+        let output = std::process::Command::new("diskutil")
+            .args(["eject", target])
+            .output()
+            .map_err(|eject_error| {
+                messages::MOUNT_COMMAND_FAILED
+                    .with("command", "diskutil")
+                    .with("error", eject_error)
+            })?;
+
+        if output.status.success() {
+            return Ok(EjectResult::ejected());
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        EjectResult::refused(stderr)
+    }
+
+    #[cfg(windows)]
+    {
+        return windows_eject(&device_path, &mount_point);
+    }
+}
+
+impl EjectResult {
+    fn ejected() -> Self {
+        EjectResult {
+            ejected: true,
+            files_in_use: false,
+            details: None,
+        }
+    }
+
+    // Tools only explain a refusal in text, so a busy drive is recognized
+    // by wording; any other failure stays an error
+    #[cfg(unix)]
+    fn refused(details: String) -> Result<Self, String> {
+        let lowercase = details.to_lowercase();
+        let is_busy = ["busy", "in use", "dissented"]
+            .iter()
+            .any(|phrase| lowercase.contains(phrase));
+
+        if !is_busy {
+            return Err(details);
+        }
+
+        Ok(EjectResult {
+            ejected: false,
+            files_in_use: true,
+            details: Some(details),
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn linux_eject(device_path: &str, mount_point: &str) -> Result<EjectResult, String> {
+    if !mount_point.is_empty() {
+        if let Err(unmount_error) = linux_unmount(device_path, mount_point) {
+            return EjectResult::refused(unmount_error);
+        }
+    }
+
+    if !device_path.starts_with("/dev/") {
+        return Ok(EjectResult::ejected());
+    }
+
+    let output = std::process::Command::new("udisksctl")
+        .args(["power-off", "-b", device_path, "--no-user-interaction"])
+        .output()
+        .map_err(|power_off_error| {
+            messages::MOUNT_COMMAND_FAILED
+                .with("command", "udisksctl")
+                .with("error", power_off_error)
+        })?;
+
+    if output.status.success() {
+        return Ok(EjectResult::ejected());
+    }
+
+    // Another partition of the same disk may still be mounted and in use
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    EjectResult::refused(stderr)
+}
+
+/// Locks and dismounts the volume, then ejects the media. The lock is
+/// refused while any handle on the volume is open.
+#[cfg(windows)]
+fn windows_eject(device_path: &str, mount_point: &str) -> Result<EjectResult, String> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, GetLastError, ERROR_ACCESS_DENIED, GENERIC_READ, GENERIC_WRITE,
+        INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{
+        CreateFileW, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+    };
+    use windows_sys::Win32::System::Ioctl::{
+        FSCTL_DISMOUNT_VOLUME, FSCTL_LOCK_VOLUME, IOCTL_STORAGE_EJECT_MEDIA,
+        IOCTL_STORAGE_MEDIA_REMOVAL,
+    };
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    const LOCK_ATTEMPTS: u32 = 5;
+
+    let drive_letter = [mount_point, device_path]
+        .iter()
+        .find_map(|path| {
+            let mut chars = path.chars();
+            match (chars.next(), chars.next()) {
+                (Some(letter), Some(':')) if letter.is_ascii_alphabetic() => Some(letter),
+                _ => None,
+            }
+        })
+        .ok_or_else(|| format!("Not a drive letter: {}", mount_point))?;
+
+    let volume_path: Vec<u16> = std::ffi::OsStr::new(&format!("\\\\.\\{}:", drive_letter))
+        .encode_wide()
+        .chain([0])
+        .collect();
+
+    let handle = unsafe {
+        CreateFileW(
+            volume_path.as_ptr(),
+            GENERIC_READ | GENERIC_WRITE,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            0,
+            std::ptr::null_mut(),
+        )
+    };
+
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+
+    let control = |code: u32, input: &[u8]| -> Result<(), u32> {
+        let mut returned = 0u32;
+        let succeeded = unsafe {
+            DeviceIoControl(
+                handle,
+                code,
+                input.as_ptr() as _,
+                input.len() as u32,
+                std::ptr::null_mut(),
+                0,
+                &mut returned,
+                std::ptr::null_mut(),
+            )
+        };
+
+        if succeeded == 0 {
+            Err(unsafe { GetLastError() })
+        } else {
+            Ok(())
+        }
+    };
+
+    let os_error = |code: u32| std::io::Error::from_raw_os_error(code as i32).to_string();
+
+    let result = (|| {
+        // Explorer and indexers let go of the volume shortly after being
+        // asked, so a refused lock is retried before giving up
+        let mut lock_result = control(FSCTL_LOCK_VOLUME, &[]);
+        for _ in 1..LOCK_ATTEMPTS {
+            if lock_result.is_ok() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            lock_result = control(FSCTL_LOCK_VOLUME, &[]);
+        }
+
+        match lock_result {
+            Ok(()) => {}
+            Err(ERROR_ACCESS_DENIED) => {
+                return Ok(EjectResult {
+                    ejected: false,
+                    files_in_use: true,
+                    details: None,
+                })
+            }
+            Err(code) => return Err(os_error(code)),
+        }
+
+        control(FSCTL_DISMOUNT_VOLUME, &[]).map_err(os_error)?;
+        // A single byte PREVENT_MEDIA_REMOVAL set to false
+        control(IOCTL_STORAGE_MEDIA_REMOVAL, &[0]).map_err(os_error)?;
+        control(IOCTL_STORAGE_EJECT_MEDIA, &[]).map_err(os_error)?;
+
+        Ok(EjectResult::ejected())
+    })();
+
+    unsafe {
+        CloseHandle(handle);
+    }
+
+    result
+}
+
+// ---------------------------------------------------------------------------
+// Volume labels
+// ---------------------------------------------------------------------------
+
+/// Renames a volume. `device_or_mount` is a device path or a mount point;
+/// an empty `new_label` clears the label.
+#[tauri::command]
+pub fn set_volume_label(device_or_mount: String, new_label: String) -> Result<(), String> {
+    let new_label = new_label.trim();
+
+    #[cfg(target_os = "linux")]
+    {
+        return linux_set_volume_label(&device_or_mount, new_label);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let output = std::process::Command::new("diskutil")
+            .args(["rename", &device_or_mount, new_label])
+            .output()
+            .map_err(|rename_error| {
+                messages::MOUNT_COMMAND_FAILED
+                    .with("command", "diskutil")
+                    .with("error", rename_error)
+            })?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            Err(stderr.trim().to_string())
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Storage::FileSystem::SetVolumeLabelW;
+
+        let drive_letter = device_or_mount
+            .chars()
+            .next()
+            .filter(|letter| letter.is_ascii_alphabetic() && device_or_mount[1..].starts_with(':'))
+            .ok_or_else(|| format!("Not a drive letter: {}", device_or_mount))?;
+
+        let root: Vec<u16> = std::ffi::OsStr::new(&format!("{}:\\", drive_letter))
+            .encode_wide()
+            .chain([0])
+            .collect();
+        let label: Vec<u16> = std::ffi::OsStr::new(new_label)
+            .encode_wide()
+            .chain([0])
+            .collect();
+
+        // A null label deletes it
+        let label_pointer = if new_label.is_empty() {
+            std::ptr::null()
+        } else {
+            label.as_ptr()
+        };
+
+        if unsafe { SetVolumeLabelW(root.as_ptr(), label_pointer) } == 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+
+        Ok(())
+    }
+}
+
+// Mount points are resolved to the device mounted there
+#[cfg(target_os = "linux")]
+fn linux_block_device(device_or_mount: &str) -> Option<String> {
+    if device_or_mount.starts_with("/dev/") {
+        return Some(device_or_mount.to_string());
+    }
+
+    let mount_point = device_or_mount.trim_end_matches('/');
+    let mount_point = if mount_point.is_empty() { "/" } else { mount_point };
+
+    fs::read_to_string("/proc/mounts")
+        .ok()?
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some((fields.next()?, fields.next()?))
+        })
+        // Spaces in mount points are escaped as \040
+        .filter(|(_, mounted_at)| mounted_at.replace("\\040", " ") == mount_point)
+        .map(|(device, _)| device.to_string())
+        .last()
+}
+
+/// Goes through udisks, which picks the labelling tool for the file
+/// system and handles authorization, and falls back to running the tool
+/// directly, which works when the user has access to the device.
+#[cfg(target_os = "linux")]
+fn linux_set_volume_label(device_or_mount: &str, new_label: &str) -> Result<(), String> {
+    let device_path = linux_block_device(device_or_mount)
+        .filter(|device| device.starts_with("/dev/"))
+        .ok_or_else(|| format!("No block device found for {}", device_or_mount))?;
+    let device_name = device_path.trim_start_matches("/dev/").to_string();
+
+    // udisks names objects after the kernel device, with '-' escaped
+    let object_path = format!(
+        "/org/freedesktop/UDisks2/block_devices/{}",
+        fs::canonicalize(&device_path)
+            .ok()
+            .and_then(|resolved| {
+                resolved
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+            })
+            .unwrap_or_else(|| device_name.clone())
+            .replace('-', "_2d")
+    );
+
+    if let Ok(output) = std::process::Command::new("gdbus")
+        .args([
+            "call",
+            "--system",
+            "--dest",
+            "org.freedesktop.UDisks2",
+            "--object-path",
+            &object_path,
+            "--method",
+            "org.freedesktop.UDisks2.Filesystem.SetLabel",
+            new_label,
+            "{}",
+        ])
+        .output()
+    {
+        if output.status.success() {
+            return Ok(());
+        }
+    }
+
+    let fs_type = get_partition_fs_type(&device_name)
+        .ok_or_else(|| format!("No file system found on {}", device_path))?;
+
+    let mut command = match fs_type.as_str() {
+        "ext2" | "ext3" | "ext4" => {
+            let mut command = std::process::Command::new("e2label");
+            command.args([device_path.as_str(), new_label]);
+            command
+        }
+        "vfat" => {
+            let mut command = std::process::Command::new("fatlabel");
+            command.arg(&device_path);
+            if new_label.is_empty() {
+                command.arg("--reset");
+            } else {
+                command.arg(new_label);
+            }
+            command
+        }
+        "exfat" => {
+            let mut command = std::process::Command::new("exfatlabel");
+            command.args([device_path.as_str(), new_label]);
+            command
+        }
+        "ntfs" => {
+            let mut command = std::process::Command::new("ntfslabel");
+            command.args([device_path.as_str(), new_label]);
+            command
+        }
+        "btrfs" => {
+            let mut command = std::process::Command::new("btrfs");
+            command.args(["filesystem", "label", device_path.as_str(), new_label]);
+            command
+        }
+        other => return Err(format!("Renaming {} volumes is not supported", other)),
+    };
+
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command.output().map_err(|label_error| {
+        messages::MOUNT_COMMAND_FAILED
+            .with("command", &program)
+            .with("error", label_error)
+    })?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        Err(stderr.trim().to_string())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Disk images
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MountedImage {
+    /// Loop device, disk node or virtual drive the image is attached as
+    pub device_path: String,
+    /// One per mounted file system; partitioned images can have several
+    pub mount_points: Vec<String>,
+}
+
+/// Attaches an ISO or raw disk image read-only and mounts the file systems
+/// on it, so it can be browsed like a drive
+#[tauri::command]
+pub fn mount_image(path: String) -> Result<MountedImage, String> {
+    let image_path = to_os_path(&path);
+
+    if !image_path.is_file() {
+        return Err(messages::SOURCE_NOT_FILE.with("path", &path).into());
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        return linux_mount_image(&image_path);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let output = std::process::Command::new("hdiutil")
+            .args(["attach", "-readonly", "-plist"])
+            .arg(&image_path)
+            .output()
+            .map_err(|attach_error| {
+                messages::MOUNT_COMMAND_FAILED
+                    .with("command", "hdiutil")
+                    .with("error", attach_error)
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            return Err(stderr.trim().to_string());
+        }
+
+        let attached = plist::Value::from_reader(std::io::Cursor::new(output.stdout))
+            .map_err(|error| error.to_string())?;
+        let entities = attached
+            .as_dictionary()
+            .and_then(|dictionary| dictionary.get("system-entities"))
+            .and_then(plist::Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let entity_field = |entity: &plist::Value, key: &str| {
+            entity
+                .as_dictionary()
+                .and_then(|dictionary| dictionary.get(key))
+                .and_then(plist::Value::as_string)
+                .map(str::to_string)
+        };
+
+        // The whole disk comes first, followed by its partitions
+        Ok(MountedImage {
+            device_path: entities
+                .first()
+                .and_then(|entity| entity_field(entity, "dev-entry"))
+                .unwrap_or_default(),
+            mount_points: entities
+                .iter()
+                .filter_map(|entity| entity_field(entity, "mount-point"))
+                .collect(),
+        })
+    }
+
+    #[cfg(windows)]
+    {
+        // Prints the virtual drive, then the letter of each volume on it
+        let script = format!(
+            "$image = Mount-DiskImage -ImagePath '{}' -Access ReadOnly -PassThru; \
+             $image.DevicePath; ($image | Get-Volume).DriveLetter",
+            image_path.to_string_lossy().replace('\'', "''")
+        );
+        let stdout = windows_powershell(&script)?;
+        let mut lines = stdout.lines().map(str::trim).filter(|line| !line.is_empty());
+
+        Ok(MountedImage {
+            device_path: lines.next().unwrap_or_default().to_string(),
+            mount_points: lines
+                .map(|letter| normalize_path(&format!("{}:\\", letter)))
+                .collect(),
+        })
+    }
+}
+
+/// Unmounts a mounted image and detaches it, taking every file system on
+/// the image down with the one at `mount_point`
+#[tauri::command]
+pub fn unmount_image(mount_point: String) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    {
+        return linux_unmount_image(&mount_point);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let output = std::process::Command::new("hdiutil")
+            .args(["detach", &mount_point])
+            .output()
+            .map_err(|detach_error| {
+                messages::MOUNT_COMMAND_FAILED
+                    .with("command", "hdiutil")
+                    .with("error", detach_error)
+            })?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            Err(stderr.trim().to_string())
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        let drive_letter = mount_point
+            .chars()
+            .next()
+            .filter(|letter| letter.is_ascii_alphabetic() && mount_point[1..].starts_with(':'))
+            .ok_or_else(|| format!("Not a drive letter: {}", mount_point))?;
+
+        windows_powershell(&format!(
+            "Get-Volume -DriveLetter {} | Get-DiskImage | Dismount-DiskImage | Out-Null",
+            drive_letter
+        ))
+        .map(|_| ())
+    }
+}
+
+#[cfg(windows)]
+fn windows_powershell(script: &str) -> Result<String, String> {
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .output()
+        .map_err(|powershell_error| {
+            messages::MOUNT_COMMAND_FAILED
+                .with("command", "powershell")
+                .with("error", powershell_error)
+        })?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        Err(stderr.trim().to_string())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn udisksctl(args: &[&str]) -> Result<String, String> {
+    let output = std::process::Command::new("udisksctl")
+        .args(args)
+        .arg("--no-user-interaction")
+        .output()
+        .map_err(|udisks_error| {
+            messages::MOUNT_COMMAND_FAILED
+                .with("command", "udisksctl")
+                .with("error", udisks_error)
+        })?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        Err(stderr.trim().to_string())
+    }
+}
+
+/// Sets up a loop device through udisks, which needs no root, and mounts
+/// the device itself or its partitions. Desktops may automount them first,
+/// in which case the existing mount points are reported.
+#[cfg(target_os = "linux")]
+fn linux_mount_image(image_path: &Path) -> Result<MountedImage, String> {
+    let stdout = udisksctl(&["loop-setup", "--read-only", "-f", &image_path.to_string_lossy()])?;
+    // "Mapped file /path/image.iso as /dev/loop0."
+    let loop_device = stdout
+        .split(" as ")
+        .nth(1)
+        .map(|segment| segment.trim().trim_end_matches('.').to_string())
+        .ok_or_else(|| format!("Unexpected udisksctl output: {}", stdout.trim()))?;
+    let loop_name = loop_device.trim_start_matches("/dev/").to_string();
+
+    let mut partitions: Vec<String> = fs::read_dir(Path::new("/sys/class/block").join(&loop_name))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| name.starts_with(&format!("{}p", loop_name)))
+                .map(|name| format!("/dev/{}", name))
+                .collect()
+        })
+        .unwrap_or_default();
+    partitions.sort();
+
+    let devices = if partitions.is_empty() {
+        vec![loop_device.clone()]
+    } else {
+        partitions
+    };
+
+    let mut mount_points = Vec::new();
+    for device in &devices {
+        let already_mounted = linux_loop_mounts(device)
+            .into_iter()
+            .find(|(mounted_device, _)| mounted_device == device);
+
+        if let Some((_, mount_point)) = already_mounted {
+            mount_points.push(mount_point);
+            continue;
+        }
+
+        // Partitions without a file system, such as boot loaders, don't mount
+        if let Ok(stdout) = udisksctl(&["mount", "-b", device]) {
+            if let Some(mount_point) = stdout.split(" at ").nth(1) {
+                mount_points.push(mount_point.trim().trim_end_matches('.').to_string());
+            }
+        }
+    }
+
+    if mount_points.is_empty() {
+        let _ = udisksctl(&["loop-delete", "-b", &loop_device]);
+        return Err(format!(
+            "No mountable file system found in {}",
+            image_path.to_string_lossy()
+        ));
+    }
+
+    Ok(MountedImage {
+        device_path: loop_device,
+        mount_points,
+    })
+}
+
+/// Mounted devices whose name starts with `device`, so a loop device
+/// matches its partitions too
+#[cfg(target_os = "linux")]
+fn linux_loop_mounts(device: &str) -> Vec<(String, String)> {
+    fs::read_to_string("/proc/mounts")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some((fields.next()?, fields.next()?))
+        })
+        .filter(|(mounted_device, _)| mounted_device.starts_with(device))
+        .map(|(mounted_device, mount_point)| {
+            (mounted_device.to_string(), mount_point.replace("\\040", " "))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn linux_unmount_image(mount_point: &str) -> Result<(), String> {
+    let device = linux_block_device(mount_point)
+        .filter(|device| device.starts_with("/dev/loop"))
+        .ok_or_else(|| format!("No disk image is mounted at {}", mount_point))?;
+
+    // /dev/loop0p1 belongs to /dev/loop0
+    let loop_device = match device.rsplit_once('p') {
+        Some((base, number))
+            if base.len() > "/dev/loop".len() && number.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            base.to_string()
+        }
+        _ => device,
+    };
+
+    for (mounted_device, _) in linux_loop_mounts(&format!("{}p", loop_device)) {
+        udisksctl(&["unmount", "-b", &mounted_device])?;
+    }
+    if linux_loop_mounts(&loop_device)
+        .iter()
+        .any(|(mounted_device, _)| *mounted_device == loop_device)
+    {
+        udisksctl(&["unmount", "-b", &loop_device])?;
+    }
+
+    udisksctl(&["loop-delete", "-b", &loop_device]).map(|_| ())
 }
 
 // ---------------------------------------------------------------------------
@@ -1008,13 +2108,15 @@ pub fn mount_network_share(params: NetworkShareParams) -> Result<String, String>
         let mount_point = format!("{}/{}", mount_base, params.mount_name);
 
         fs::create_dir_all(&mount_point)
-            .map_err(|dir_error| format!("Failed to create mount point: {}", dir_error))?;
+            .map_err(|dir_error| messages::MOUNT_POINT_CREATE_FAILED.with("error", dir_error))?;
 
         let result = match params.protocol.as_str() {
             "sshfs" => mount_sshfs(&params, &mount_point),
             "nfs" => mount_nfs(&params, &mount_point),
             "smb" => mount_smb(&params, &mount_point),
-            unknown => Err(format!("Unknown protocol: {}", unknown)),
+            unknown => Err(messages::MOUNT_UNKNOWN_PROTOCOL
+                .with("protocol", unknown)
+                .into()),
         };
 
         if result.is_err() {
@@ -1043,7 +2145,11 @@ fn mount_network_share_windows(params: &NetworkShareParams) -> Result<String, St
             let output = std::process::Command::new("net")
                 .args(&args)
                 .output()
-                .map_err(|run_error| format!("Failed to run 'net use': {}", run_error))?;
+                .map_err(|run_error| {
+                    messages::MOUNT_COMMAND_FAILED
+                        .with("command", "net use")
+                        .with("error", run_error)
+                })?;
 
             if output.status.success() {
                 let stdout = String::from_utf8_lossy(&output.stdout).to_string();
@@ -1056,16 +2162,17 @@ fn mount_network_share_windows(params: &NetworkShareParams) -> Result<String, St
                 Ok(drive_letter)
             } else {
                 let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                Err(format!("net use failed: {}", stderr.trim()))
+                Err(messages::MOUNT_FAILED
+                    .with("protocol", "SMB")
+                    .with("error", stderr.trim())
+                    .into())
             }
         }
-        "sshfs" => {
-            Err("SSHFS on Windows requires WinFSP and sshfs-win. Install from https://github.com/winfsp/sshfs-win".to_string())
-        }
-        "nfs" => {
-            Err("NFS on Windows requires 'Services for NFS' Windows feature to be enabled".to_string())
-        }
-        unknown => Err(format!("Unknown protocol: {}", unknown)),
+        "sshfs" => Err(messages::MOUNT_SSHFS_WINDOWS.text().into()),
+        "nfs" => Err(messages::MOUNT_NFS_WINDOWS.text().into()),
+        unknown => Err(messages::MOUNT_UNKNOWN_PROTOCOL
+            .with("protocol", unknown)
+            .into()),
     }
 }
 
@@ -1098,27 +2205,32 @@ fn mount_sshfs(params: &NetworkShareParams, mount_point: &str) -> Result<(), Str
             .stderr(std::process::Stdio::piped())
             .spawn()
             .map_err(|spawn_error| {
-                format!("Failed to run sshfs: {}. Is sshfs installed?", spawn_error)
+                messages::MOUNT_SSHFS_NOT_INSTALLED.with("error", spawn_error)
             })?;
 
         if let Some(ref mut stdin) = child.stdin {
             let _ = stdin.write_all(password.as_bytes());
         }
 
-        child
-            .wait_with_output()
-            .map_err(|wait_error| format!("sshfs process error: {}", wait_error))?
-    } else {
-        command.output().map_err(|run_error| {
-            format!("Failed to run sshfs: {}. Is sshfs installed?", run_error)
+        child.wait_with_output().map_err(|wait_error| {
+            messages::MOUNT_FAILED
+                .with("protocol", "SSHFS")
+                .with("error", wait_error)
         })?
+    } else {
+        command
+            .output()
+            .map_err(|run_error| messages::MOUNT_SSHFS_NOT_INSTALLED.with("error", run_error))?
     };
 
     if output.status.success() {
         Ok(())
     } else {
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        Err(format!("sshfs failed: {}", stderr.trim()))
+        Err(messages::MOUNT_FAILED
+            .with("protocol", "SSHFS")
+            .with("error", stderr.trim())
+            .into())
     }
 }
 
@@ -1133,13 +2245,20 @@ fn mount_nfs(params: &NetworkShareParams, mount_point: &str) -> Result<(), Strin
                 .args(["-t", "nfs", &source, mount_point])
                 .output()
         })
-        .map_err(|run_error| format!("Failed to run mount: {}", run_error))?;
+        .map_err(|run_error| {
+            messages::MOUNT_COMMAND_FAILED
+                .with("command", "mount")
+                .with("error", run_error)
+        })?;
 
     if output.status.success() {
         Ok(())
     } else {
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        Err(format!("NFS mount failed: {}", stderr.trim()))
+        Err(messages::MOUNT_FAILED
+            .with("protocol", "NFS")
+            .with("error", stderr.trim())
+            .into())
     }
 }
 
@@ -1157,14 +2276,21 @@ fn mount_smb(params: &NetworkShareParams, mount_point: &str) -> Result<(), Strin
         let output = std::process::Command::new("mount")
             .args(["-t", "smbfs", &mount_source, mount_point])
             .output()
-            .map_err(|run_error| format!("Failed to run mount: {}", run_error))?;
+            .map_err(|run_error| {
+                messages::MOUNT_COMMAND_FAILED
+                    .with("command", "mount")
+                    .with("error", run_error)
+            })?;
 
         if output.status.success() {
             return Ok(());
         }
 
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        return Err(format!("SMB mount failed: {}", stderr.trim()));
+        return Err(messages::MOUNT_FAILED
+            .with("protocol", "SMB")
+            .with("error", stderr.trim())
+            .into());
     }
 
     #[cfg(not(target_os = "macos"))]
@@ -1199,13 +2325,20 @@ fn mount_smb(params: &NetworkShareParams, mount_point: &str) -> Result<(), Strin
         let output = std::process::Command::new("mount")
             .args(&mount_args)
             .output()
-            .map_err(|run_error| format!("Failed to run mount: {}", run_error))?;
+            .map_err(|run_error| {
+                messages::MOUNT_COMMAND_FAILED
+                    .with("command", "mount")
+                    .with("error", run_error)
+            })?;
 
         if output.status.success() {
             Ok(())
         } else {
             let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-            Err(format!("SMB mount failed: {}", stderr.trim()))
+            Err(messages::MOUNT_FAILED
+                .with("protocol", "SMB")
+                .with("error", stderr.trim())
+                .into())
         }
     }
 }
@@ -1216,7 +2349,7 @@ fn mount_smb(params: &NetworkShareParams, mount_point: &str) -> Result<(), Strin
 
 #[tauri::command]
 pub fn get_parent_dir(path: String) -> Option<String> {
-    Path::new(&path)
+    to_os_path(&path)
         .parent()
         .and_then(|parent| parent.to_str())
         .map(|path_str| normalize_path(path_str))
@@ -1224,5 +2357,5 @@ pub fn get_parent_dir(path: String) -> Option<String> {
 
 #[tauri::command]
 pub fn path_exists(path: String) -> bool {
-    Path::new(&path).exists()
+    to_os_path(&path).exists()
 }
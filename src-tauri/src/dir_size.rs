@@ -13,6 +13,7 @@ use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use walkdir::WalkDir;
+use crate::skipped_dirs::SkippedDirs;
 use crate::utils::normalize_path;
 
 const CACHE_SIZE: usize = 2000;
@@ -28,6 +29,70 @@ pub enum SizeStatus {
     Cancelled,
 }
 
+/// Groups of derived folders that can be left out of size calculations, so
+/// a project's own size isn't dominated by dependencies and build artifacts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExclusionPreset {
+    VcsMetadata,
+    Dependencies,
+    BuildOutputs,
+}
+
+impl ExclusionPreset {
+    fn dir_names(self) -> &'static [&'static str] {
+        match self {
+            ExclusionPreset::VcsMetadata => &[".git", ".hg", ".svn", ".bzr", ".jj", "_darcs"],
+            ExclusionPreset::Dependencies => &[
+                "node_modules",
+                "bower_components",
+                "jspm_packages",
+                ".pnpm-store",
+                "vendor",
+                ".venv",
+                "venv",
+                "__pypackages__",
+                "Pods",
+            ],
+            ExclusionPreset::BuildOutputs => &[
+                "target",
+                "build",
+                "dist",
+                "out",
+                "obj",
+                ".next",
+                ".nuxt",
+                ".output",
+                ".svelte-kit",
+                ".gradle",
+                ".tox",
+                "__pycache__",
+                ".pytest_cache",
+            ],
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ExclusionPreset::VcsMetadata => "vcs_metadata",
+            ExclusionPreset::Dependencies => "dependencies",
+            ExclusionPreset::BuildOutputs => "build_outputs",
+        }
+    }
+}
+
+/// Whether a walked entry is a folder excluded by one of the presets
+pub(crate) fn is_excluded(entry: &walkdir::DirEntry, presets: &[ExclusionPreset]) -> bool {
+    if presets.is_empty() || !entry.file_type().is_dir() {
+        return false;
+    }
+
+    let name = entry.file_name().to_string_lossy();
+    presets
+        .iter()
+        .any(|preset| preset.dir_names().contains(&name.as_ref()))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirSizeResult {
     pub path: String,
@@ -36,6 +101,9 @@ pub struct DirSizeResult {
     pub file_count: u64,
     pub dir_count: u64,
     pub error: Option<String>,
+    /// Folders left out of the totals because they couldn't be entered
+    #[serde(default)]
+    pub skipped_dirs: SkippedDirs,
 }
 
 #[derive(Debug, Clone)]
@@ -44,6 +112,7 @@ struct CacheEntry {
     file_count: u64,
     dir_count: u64,
     status: SizeStatus,
+    skipped_dirs: SkippedDirs,
     calculated_at: u64,
     dir_mtime: u64,
 }
@@ -114,10 +183,29 @@ fn get_current_timestamp() -> u64 {
         .unwrap_or(0)
 }
 
-fn get_cached_size(path: &str) -> Option<CacheEntry> {
+// Sizes computed with exclusions are cached separately from full sizes
+fn cache_key(path: &str, presets: &[ExclusionPreset]) -> String {
     let normalized = normalize_path(path);
+
+    if presets.is_empty() {
+        return normalized;
+    }
+
+    let mut preset_names: Vec<&str> = presets.iter().map(|preset| preset.as_str()).collect();
+    preset_names.sort_unstable();
+    preset_names.dedup();
+    format!("{}|{}", normalized, preset_names.join(","))
+}
+
+fn get_cached_size(path: &str, presets: &[ExclusionPreset]) -> Option<CacheEntry> {
+    let entry = lookup_cached_size(path, presets);
+    crate::metrics::record_cache_lookup("dir_size", entry.is_some());
+    entry
+}
+
+fn lookup_cached_size(path: &str, presets: &[ExclusionPreset]) -> Option<CacheEntry> {
     let mut cache = SIZE_CACHE.lock().ok()?;
-    let entry = cache.get(&normalized)?;
+    let entry = cache.get(&cache_key(path, presets))?;
 
     let now = get_current_timestamp();
     if now - entry.calculated_at > CACHE_TTL_SECONDS {
@@ -132,16 +220,33 @@ fn get_cached_size(path: &str) -> Option<CacheEntry> {
     Some(entry.clone())
 }
 
-fn set_cached_size(path: &str, entry: CacheEntry) {
-    let normalized = normalize_path(path);
+// Sizes kept by the search index are always full sizes
+fn indexed_size(path: &str, presets: &[ExclusionPreset]) -> Option<DirSizeResult> {
+    if !presets.is_empty() {
+        return None;
+    }
+
+    crate::folder_sizes::lookup(path).map(|totals| DirSizeResult {
+        path: normalize_path(path),
+        size: totals.size,
+        status: SizeStatus::Complete,
+        file_count: totals.file_count,
+        dir_count: totals.dir_count,
+        error: None,
+        skipped_dirs: SkippedDirs::default(),
+    })
+}
+
+fn set_cached_size(path: &str, presets: &[ExclusionPreset], entry: CacheEntry) {
     if let Ok(mut cache) = SIZE_CACHE.lock() {
-        cache.put(normalized, entry);
+        cache.put(cache_key(path, presets), entry);
     }
 }
 
 fn calculate_dir_size_with_timeout(
     path: &Path,
     timeout: Duration,
+    presets: &[ExclusionPreset],
 ) -> DirSizeResult {
     let path_str = normalize_path(&path.to_string_lossy());
 
@@ -153,6 +258,7 @@ fn calculate_dir_size_with_timeout(
             file_count: 0,
             dir_count: 0,
             error: Some("Path does not exist".to_string()),
+            skipped_dirs: SkippedDirs::default(),
         };
     }
 
@@ -164,10 +270,12 @@ fn calculate_dir_size_with_timeout(
             file_count: 0,
             dir_count: 0,
             error: Some("Path is not a directory".to_string()),
+            skipped_dirs: SkippedDirs::default(),
         };
     }
 
     let start_time = Instant::now();
+    let mut skipped_dirs = SkippedDirs::default();
     let cancelled = Arc::new(AtomicBool::new(false));
     let total_size = Arc::new(AtomicU64::new(0));
     let file_count = Arc::new(AtomicU64::new(0));
@@ -176,7 +284,14 @@ fn calculate_dir_size_with_timeout(
     let entries: Vec<_> = WalkDir::new(path)
         .min_depth(1)
         .into_iter()
-        .filter_map(|entry| entry.ok())
+        .filter_entry(|entry| !is_excluded(entry, presets))
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(error) => {
+                skipped_dirs.record(&error);
+                None
+            }
+        })
         .take_while(|_| {
             if start_time.elapsed() > timeout {
                 cancelled.store(true, Ordering::SeqCst);
@@ -215,11 +330,13 @@ fn calculate_dir_size_with_timeout(
         let dir_mtime = get_dir_mtime(path);
         set_cached_size(
             &path_str,
+            presets,
             CacheEntry {
                 size: final_size,
                 file_count: final_file_count,
                 dir_count: final_dir_count,
                 status: status.clone(),
+                skipped_dirs: skipped_dirs.clone(),
                 calculated_at: get_current_timestamp(),
                 dir_mtime,
             },
@@ -233,6 +350,7 @@ fn calculate_dir_size_with_timeout(
         file_count: final_file_count,
         dir_count: final_dir_count,
         error: None,
+        skipped_dirs,
     }
 }
 
@@ -240,6 +358,7 @@ fn calculate_dir_size_no_timeout(
     path: &Path,
     cancel_token: Arc<AtomicBool>,
     progress: CalculationProgress,
+    presets: &[ExclusionPreset],
 ) -> DirSizeResult {
     let path_str = normalize_path(&path.to_string_lossy());
 
@@ -251,6 +370,7 @@ fn calculate_dir_size_no_timeout(
             file_count: 0,
             dir_count: 0,
             error: Some("Path does not exist".to_string()),
+            skipped_dirs: SkippedDirs::default(),
         };
     }
 
@@ -262,6 +382,7 @@ fn calculate_dir_size_no_timeout(
             file_count: 0,
             dir_count: 0,
             error: Some("Path is not a directory".to_string()),
+            skipped_dirs: SkippedDirs::default(),
         };
     }
 
@@ -278,11 +399,20 @@ fn calculate_dir_size_no_timeout(
     let file_count_clone = file_count.clone();
     let dir_count_clone = dir_count.clone();
 
+    let mut skipped_dirs = SkippedDirs::default();
+
     // Process entries one by one, updating progress as we go
     for entry in WalkDir::new(path)
         .min_depth(1)
         .into_iter()
-        .filter_map(|entry| entry.ok())
+        .filter_entry(|entry| !is_excluded(entry, presets))
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(error) => {
+                skipped_dirs.record(&error);
+                None
+            }
+        })
     {
         // Check cancellation
         if cancel_token_clone.load(Ordering::SeqCst) {
@@ -309,6 +439,7 @@ fn calculate_dir_size_no_timeout(
             file_count: file_count.load(Ordering::SeqCst),
             dir_count: dir_count.load(Ordering::SeqCst),
             error: None,
+            skipped_dirs,
         };
     }
 
@@ -319,11 +450,13 @@ fn calculate_dir_size_no_timeout(
     let dir_mtime = get_dir_mtime(path);
     set_cached_size(
         &path_str,
+        presets,
         CacheEntry {
             size: final_size,
             file_count: final_file_count,
             dir_count: final_dir_count,
             status: SizeStatus::Complete,
+            skipped_dirs: skipped_dirs.clone(),
             calculated_at: get_current_timestamp(),
             dir_mtime,
         },
@@ -336,11 +469,22 @@ fn calculate_dir_size_no_timeout(
         file_count: final_file_count,
         dir_count: final_dir_count,
         error: None,
+        skipped_dirs,
     }
 }
 
 #[tauri::command]
-pub async fn get_dir_size(path: String, timeout_ms: Option<u64>) -> DirSizeResult {
+pub async fn get_dir_size(
+    path: String,
+    timeout_ms: Option<u64>,
+    exclude: Option<Vec<ExclusionPreset>>,
+) -> DirSizeResult {
+    let _timer = crate::metrics::time_command("get_dir_size");
+    let presets = exclude.unwrap_or_default();
+
+    if let Some(result) = indexed_size(&path, &presets) {
+        return result;
+    }
     let path_clone = path.clone();
     let (cancel_token, progress) = register_calculation(&path);
 
@@ -348,8 +492,10 @@ pub async fn get_dir_size(path: String, timeout_ms: Option<u64>) -> DirSizeResul
         let dir_path = Path::new(&path_clone);
 
         match timeout_ms {
-            Some(ms) => calculate_dir_size_with_timeout(dir_path, Duration::from_millis(ms)),
-            None => calculate_dir_size_no_timeout(dir_path, cancel_token, progress),
+            Some(ms) => {
+                calculate_dir_size_with_timeout(dir_path, Duration::from_millis(ms), &presets)
+            }
+            None => calculate_dir_size_no_timeout(dir_path, cancel_token, progress, &presets),
         }
     })
     .await
@@ -360,6 +506,7 @@ pub async fn get_dir_size(path: String, timeout_ms: Option<u64>) -> DirSizeResul
         file_count: 0,
         dir_count: 0,
         error: Some("Task failed".to_string()),
+        skipped_dirs: SkippedDirs::default(),
     });
 
     unregister_calculation(&path);
@@ -380,6 +527,7 @@ pub fn get_dir_size_progress(path: String) -> Option<DirSizeResult> {
                 file_count: progress.file_count.load(Ordering::SeqCst),
                 dir_count: progress.dir_count.load(Ordering::SeqCst),
                 error: None,
+                skipped_dirs: SkippedDirs::default(),
             });
         }
     }
@@ -401,6 +549,7 @@ pub fn get_active_calculations() -> Vec<DirSizeResult> {
                 file_count: progress.file_count.load(Ordering::SeqCst),
                 dir_count: progress.dir_count.load(Ordering::SeqCst),
                 error: None,
+                skipped_dirs: SkippedDirs::default(),
             });
         }
     }
@@ -425,7 +574,11 @@ pub async fn get_dir_sizes_batch(
     paths: Vec<String>,
     timeout_ms: Option<u64>,
     use_cache: Option<bool>,
+    exclude: Option<Vec<ExclusionPreset>>,
 ) -> Vec<DirSizeResult> {
+    let _timer = crate::metrics::time_command("get_dir_sizes_batch");
+    let presets = exclude.unwrap_or_default();
+
     tokio::task::spawn_blocking(move || {
         let timeout = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS));
         let should_use_cache = use_cache.unwrap_or(true);
@@ -433,8 +586,12 @@ pub async fn get_dir_sizes_batch(
         paths
             .par_iter()
             .map(|path| {
+                if let Some(result) = indexed_size(path, &presets) {
+                    return result;
+                }
+
                 if should_use_cache {
-                    if let Some(cached) = get_cached_size(path) {
+                    if let Some(cached) = get_cached_size(path, &presets) {
                         return DirSizeResult {
                             path: normalize_path(path),
                             size: cached.size,
@@ -442,11 +599,12 @@ pub async fn get_dir_sizes_batch(
                             file_count: cached.file_count,
                             dir_count: cached.dir_count,
                             error: None,
+                            skipped_dirs: cached.skipped_dirs,
                         };
                     }
                 }
 
-                calculate_dir_size_with_timeout(Path::new(path), timeout)
+                calculate_dir_size_with_timeout(Path::new(path), timeout, &presets)
             })
             .collect()
     })
@@ -456,6 +614,11 @@ pub async fn get_dir_sizes_batch(
 
 #[tauri::command]
 pub fn invalidate_dir_size_cache(paths: Vec<String>) {
+    let folders: Vec<std::path::PathBuf> = paths.iter().map(std::path::PathBuf::from).collect();
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::folder_sizes::refresh(folders.iter().map(std::path::PathBuf::as_path))
+    });
+
     if let Ok(mut cache) = SIZE_CACHE.lock() {
         for path in paths {
             let normalized = normalize_path(&path);
@@ -469,7 +632,10 @@ pub fn invalidate_dir_size_cache(paths: Vec<String>) {
 
             let keys_to_remove: Vec<String> = cache
                 .iter()
-                .filter(|(key, _)| key.starts_with(&path_with_slash))
+                .filter(|(key, _)| {
+                    key.starts_with(&path_with_slash)
+                        || key.starts_with(&format!("{}|", normalized))
+                })
                 .map(|(key, _)| key.clone())
                 .collect();
 
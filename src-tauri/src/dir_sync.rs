@@ -0,0 +1,717 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Folder synchronization built on `dir_compare`. Mirror makes the
+//! destination an exact copy of the source, update copies what is new or
+//! newer in the source and deletes nothing, and two-way carries changes
+//! across in both directions. Two-way remembers the state of each pair
+//! after its last sync, which tells a file changed on one side from one
+//! changed on both; the latter is reported as a conflict and left alone.
+//! A dry run returns the plan without touching anything.
+
+use crate::dir_compare::{
+    collect_tree, compare_trees, exclusion_matcher, has_ancestor_in, CompareOptions, DiffKind,
+    DiffReason, DirDiffEntry,
+};
+use crate::file_operations::remove_dir_or_file;
+use crate::job_reports::{ItemAction, JobKind, JobRecorder, JobReportItem, SkipReason};
+use crate::json_store;
+use crate::mirror::MTIME_TOLERANCE_MS;
+use crate::requests::{self, ActiveRequest, ProgressEmitter};
+use crate::utils::{normalize_path, to_os_path};
+use ignore::gitignore::Gitignore;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::Manager;
+use walkdir::WalkDir;
+
+const STORE_NAME: &str = "dir-sync-state";
+const PROGRESS_EVENT: &str = "dir-sync-progress";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+    Mirror,
+    Update,
+    TwoWay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncActionKind {
+    CopyToDestination,
+    CopyToSource,
+    DeleteFromDestination,
+    DeleteFromSource,
+    /// Changed on both sides since the last sync, left as it is
+    Conflict,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncAction {
+    /// Empty when the action covers the whole folder
+    pub relative_path: String,
+    pub kind: SyncActionKind,
+    pub is_dir: bool,
+    /// Bytes copied by the action
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncProgress {
+    pub request_id: String,
+    pub completed_count: u64,
+    pub total_count: u64,
+    pub copied_bytes: u64,
+    pub total_bytes: u64,
+    pub current_path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub source: String,
+    pub destination: String,
+    pub mode: SyncMode,
+    pub dry_run: bool,
+    pub actions: Vec<SyncAction>,
+    pub bytes_to_copy: u64,
+    pub completed_count: u64,
+    pub errors: Vec<String>,
+    pub cancelled: bool,
+    /// Id of the run's report, see `job_reports`. Unset for dry runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct SyncedEntry {
+    size: u64,
+    modified_ms: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncStore {
+    /// Entries of each pair as of its last two-way sync, by the pair's
+    /// source and destination joined with a newline
+    #[serde(default)]
+    pairs: HashMap<String, BTreeMap<String, SyncedEntry>>,
+}
+
+static SYNC_STORE: Lazy<Mutex<SyncStore>> = Lazy::new(|| Mutex::new(json_store::load(STORE_NAME)));
+
+fn pair_key(source: &str, destination: &str) -> String {
+    format!("{}\n{}", source, destination)
+}
+
+fn is_newer(first: Option<u64>, second: Option<u64>) -> bool {
+    first.unwrap_or(0) > second.unwrap_or(0) + MTIME_TOLERANCE_MS
+}
+
+// Folders count as unchanged while they exist; their contents are checked
+// on their own
+fn changed_since(
+    synced: Option<&SyncedEntry>,
+    is_dir: bool,
+    size: Option<u64>,
+    modified: Option<u64>,
+) -> bool {
+    match synced {
+        None => true,
+        Some(_) if is_dir => false,
+        Some(synced) => {
+            size != Some(synced.size)
+                || modified.unwrap_or(0).abs_diff(synced.modified_ms) > MTIME_TOLERANCE_MS
+        }
+    }
+}
+
+/// Whether anything in a folder present on one side only was added or
+/// changed since the last sync, which rules out deleting it
+fn tree_changed_since(
+    root: &Path,
+    relative_path: &str,
+    synced_entries: &BTreeMap<String, SyncedEntry>,
+) -> bool {
+    WalkDir::new(root.join(relative_path))
+        .follow_links(false)
+        .min_depth(1)
+        .into_iter()
+        .any(|entry| {
+            let Ok(entry) = entry else {
+                return true;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                return true;
+            };
+            let Ok(below_root) = entry.path().strip_prefix(root) else {
+                return true;
+            };
+            let entry_path = normalize_path(&below_root.to_string_lossy());
+
+            changed_since(
+                synced_entries.get(&entry_path),
+                metadata.is_dir(),
+                Some(metadata.len()),
+                metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|duration| duration.as_millis() as u64),
+            )
+        })
+}
+
+fn tree_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn plan_action(
+    entry: &DirDiffEntry,
+    mode: SyncMode,
+    source: &Path,
+    destination: &Path,
+    synced_entries: &BTreeMap<String, SyncedEntry>,
+) -> Option<SyncActionKind> {
+    let synced = synced_entries.get(&entry.relative_path);
+
+    match (mode, entry.kind) {
+        (SyncMode::Mirror, DiffKind::OnlyInLeft | DiffKind::Different) => {
+            Some(SyncActionKind::CopyToDestination)
+        }
+        (SyncMode::Mirror, DiffKind::OnlyInRight) => Some(SyncActionKind::DeleteFromDestination),
+        (SyncMode::Update, DiffKind::OnlyInLeft) => Some(SyncActionKind::CopyToDestination),
+        (SyncMode::Update, DiffKind::OnlyInRight) => None,
+        (SyncMode::Update, DiffKind::Different) => {
+            let is_skipped = entry.reason == Some(DiffReason::Type)
+                || is_newer(entry.right_modified, entry.left_modified);
+            (!is_skipped).then_some(SyncActionKind::CopyToDestination)
+        }
+        (SyncMode::TwoWay, DiffKind::OnlyInLeft) => {
+            if synced.is_none() {
+                return Some(SyncActionKind::CopyToDestination);
+            }
+            // Synced before, so it was deleted from the destination since
+            let is_changed = if entry.is_dir {
+                tree_changed_since(source, &entry.relative_path, synced_entries)
+            } else {
+                changed_since(synced, false, entry.left_size, entry.left_modified)
+            };
+            Some(if is_changed {
+                SyncActionKind::Conflict
+            } else {
+                SyncActionKind::DeleteFromSource
+            })
+        }
+        (SyncMode::TwoWay, DiffKind::OnlyInRight) => {
+            if synced.is_none() {
+                return Some(SyncActionKind::CopyToSource);
+            }
+            let is_changed = if entry.is_dir {
+                tree_changed_since(destination, &entry.relative_path, synced_entries)
+            } else {
+                changed_since(synced, false, entry.right_size, entry.right_modified)
+            };
+            Some(if is_changed {
+                SyncActionKind::Conflict
+            } else {
+                SyncActionKind::DeleteFromDestination
+            })
+        }
+        (SyncMode::TwoWay, DiffKind::Different) => {
+            if entry.reason == Some(DiffReason::Type) {
+                return Some(SyncActionKind::Conflict);
+            }
+            let left_changed = changed_since(synced, false, entry.left_size, entry.left_modified);
+            let right_changed =
+                changed_since(synced, false, entry.right_size, entry.right_modified);
+
+            match (left_changed, right_changed) {
+                (true, false) => Some(SyncActionKind::CopyToDestination),
+                (false, true) => Some(SyncActionKind::CopyToSource),
+                (true, true) => Some(SyncActionKind::Conflict),
+                (false, false) => None,
+            }
+        }
+    }
+}
+
+// Copies a file along with its modification time, which later syncs
+// compare
+fn copy_file(from: &Path, to: &Path) -> std::io::Result<u64> {
+    let modified = fs::metadata(from)?.modified()?;
+    let size = fs::copy(from, to)?;
+    fs::File::options()
+        .write(true)
+        .open(to)?
+        .set_modified(modified)?;
+    Ok(size)
+}
+
+/// Recreates the symlink `from` at `to`, pointing at the same target.
+/// Following it instead would copy what it points to, or fail for folders.
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    let target = fs::read_link(from)?;
+    if fs::symlink_metadata(to).is_ok() {
+        fs::remove_file(to).or_else(|_| fs::remove_dir(to))?;
+    }
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&target, to)
+    }
+
+    // Needs Developer Mode or the symlink privilege
+    #[cfg(windows)]
+    {
+        if from.is_dir() {
+            std::os::windows::fs::symlink_dir(&target, to)
+        } else {
+            std::os::windows::fs::symlink_file(&target, to)
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = target;
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Symlinks are not supported on this platform",
+        ))
+    }
+}
+
+struct SyncRun<'a> {
+    progress: ProgressEmitter<'a>,
+    cancel_token: &'a AtomicBool,
+    completed_count: u64,
+    total_count: u64,
+    copied_bytes: u64,
+    total_bytes: u64,
+}
+
+impl SyncRun<'_> {
+    fn emit_progress(&self, current_path: &str, force: bool) {
+        self.progress.emit(force, |request_id| SyncProgress {
+            request_id: request_id.to_string(),
+            completed_count: self.completed_count,
+            total_count: self.total_count,
+            copied_bytes: self.copied_bytes,
+            total_bytes: self.total_bytes,
+            current_path: current_path.to_string(),
+        });
+    }
+
+    /// Copies a file or folder over whatever is in its place, leaving out
+    /// excluded entries of folders. Symlinks are recreated as symlinks.
+    fn copy_entry(&mut self, from: &Path, to: &Path, exclude: &Gitignore) -> Result<(), String> {
+        let from_metadata = fs::symlink_metadata(from).map_err(|error| error.to_string())?;
+        let is_dir = from_metadata.is_dir();
+
+        if let Ok(metadata) = fs::symlink_metadata(to) {
+            if metadata.is_dir() != is_dir {
+                remove_dir_or_file(to)?;
+            }
+        }
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+
+        if from_metadata.is_symlink() {
+            return copy_symlink(from, to).map_err(|error| error.to_string());
+        }
+        if !is_dir {
+            self.copied_bytes += copy_file(from, to).map_err(|error| error.to_string())?;
+            return Ok(());
+        }
+
+        let walker = WalkDir::new(from)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|entry| {
+                !exclude
+                    .matched(entry.path(), entry.file_type().is_dir())
+                    .is_ignore()
+            });
+
+        for entry in walker {
+            if self.cancel_token.load(Ordering::Relaxed) {
+                return Err("Cancelled".to_string());
+            }
+
+            let entry = entry.map_err(|error| error.to_string())?;
+            let target = match entry.path().strip_prefix(from) {
+                Ok(below_from) if !below_from.as_os_str().is_empty() => to.join(below_from),
+                _ => to.to_path_buf(),
+            };
+
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(&target).map_err(|error| error.to_string())?;
+            } else if entry.file_type().is_symlink() {
+                copy_symlink(entry.path(), &target).map_err(|error| error.to_string())?;
+            } else {
+                self.copied_bytes +=
+                    copy_file(entry.path(), &target).map_err(|error| error.to_string())?;
+                self.emit_progress(&normalize_path(&entry.path().to_string_lossy()), false);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn resolve(root: &Path, relative_path: &str) -> PathBuf {
+    if relative_path.is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(relative_path)
+    }
+}
+
+fn plan_sync(
+    source: &Path,
+    destination: &Path,
+    mode: SyncMode,
+    options: &CompareOptions,
+    cancel_token: &AtomicBool,
+    synced_entries: &BTreeMap<String, SyncedEntry>,
+) -> Result<(Vec<SyncAction>, Vec<String>, bool), String> {
+    // A destination that doesn't exist yet is copied as a whole
+    if !destination.exists() {
+        return Ok((
+            vec![SyncAction {
+                relative_path: String::new(),
+                kind: SyncActionKind::CopyToDestination,
+                is_dir: true,
+                size: tree_size(source),
+            }],
+            Vec::new(),
+            false,
+        ));
+    }
+
+    let comparison = compare_trees(source, destination, options, cancel_token, |_, _| {})?;
+
+    let actions = comparison
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let kind = plan_action(entry, mode, source, destination, synced_entries)?;
+            // Folders have no size in the comparison
+            let left_is_dir = entry.kind != DiffKind::OnlyInRight && entry.left_size.is_none();
+            let right_is_dir = entry.kind != DiffKind::OnlyInLeft && entry.right_size.is_none();
+
+            let size = match kind {
+                SyncActionKind::CopyToDestination if left_is_dir => {
+                    tree_size(&source.join(&entry.relative_path))
+                }
+                SyncActionKind::CopyToSource if right_is_dir => {
+                    tree_size(&destination.join(&entry.relative_path))
+                }
+                SyncActionKind::CopyToDestination => entry.left_size.unwrap_or(0),
+                SyncActionKind::CopyToSource => entry.right_size.unwrap_or(0),
+                _ => 0,
+            };
+            let is_dir = match kind {
+                SyncActionKind::CopyToDestination | SyncActionKind::DeleteFromSource => left_is_dir,
+                SyncActionKind::CopyToSource | SyncActionKind::DeleteFromDestination => {
+                    right_is_dir
+                }
+                SyncActionKind::Conflict => left_is_dir || right_is_dir,
+            };
+
+            Some(SyncAction {
+                relative_path: entry.relative_path.clone(),
+                kind,
+                is_dir,
+                size,
+            })
+        })
+        .collect();
+
+    Ok((actions, comparison.errors, comparison.cancelled))
+}
+
+/// `path` with symlinks resolved, also when its last components don't
+/// exist yet
+fn resolved_root(path: &Path) -> PathBuf {
+    let mut missing_names = Vec::new();
+    let mut existing = path;
+
+    loop {
+        if let Ok(canonical) = fs::canonicalize(existing) {
+            return missing_names
+                .iter()
+                .rev()
+                .fold(canonical, |resolved, name| resolved.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing_names.push(name);
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+fn sync_blocking(
+    run: &mut SyncRun,
+    source: &Path,
+    destination: &Path,
+    mode: SyncMode,
+    dry_run: bool,
+    exclude: Vec<String>,
+) -> Result<SyncReport, String> {
+    if !source.is_dir() {
+        return Err(format!("Not a directory: {}", source.display()));
+    }
+
+    // Copying into its own tree would keep nesting the copy, and mirroring
+    // would delete from the tree being read
+    let source_root = resolved_root(source);
+    let destination_root = resolved_root(destination);
+    if source_root.starts_with(&destination_root) || destination_root.starts_with(&source_root) {
+        return Err(format!(
+            "{} and {} overlap, a folder can't be synced with its own contents",
+            source.display(),
+            destination.display()
+        ));
+    }
+
+    let source_key = normalize_path(&source.to_string_lossy());
+    let destination_key = normalize_path(&destination.to_string_lossy());
+    let synced_entries = match mode {
+        SyncMode::TwoWay => SYNC_STORE
+            .lock()
+            .map_err(|error| error.to_string())?
+            .pairs
+            .get(&pair_key(&source_key, &destination_key))
+            .cloned()
+            .unwrap_or_default(),
+        _ => BTreeMap::new(),
+    };
+
+    let options = CompareOptions {
+        exclude,
+        ..Default::default()
+    };
+    let (actions, errors, cancelled) = plan_sync(
+        source,
+        destination,
+        mode,
+        &options,
+        run.cancel_token,
+        &synced_entries,
+    )?;
+
+    let mut report = SyncReport {
+        source: source_key.clone(),
+        destination: destination_key.clone(),
+        mode,
+        dry_run,
+        bytes_to_copy: actions.iter().map(|action| action.size).sum(),
+        actions,
+        completed_count: 0,
+        errors,
+        cancelled,
+        job_id: None,
+    };
+
+    if dry_run || cancelled {
+        return Ok(report);
+    }
+
+    let source_exclude = exclusion_matcher(source, &options.exclude)?;
+    let destination_exclude = exclusion_matcher(destination, &options.exclude)?;
+    run.total_count = report.actions.len() as u64;
+    run.total_bytes = report.bytes_to_copy;
+
+    // Paths whose state the next two-way sync must not take as synced
+    let mut unsynced_paths: HashSet<String> = HashSet::new();
+    let mut job = JobRecorder::new(JobKind::Sync);
+
+    // Copies go first, so an interrupted mirror never deleted something
+    // without copying what replaces it
+    let mut ordered_actions: Vec<&SyncAction> = report.actions.iter().collect();
+    ordered_actions.sort_by_key(|action| {
+        matches!(
+            action.kind,
+            SyncActionKind::DeleteFromSource | SyncActionKind::DeleteFromDestination
+        )
+    });
+
+    for action in ordered_actions {
+        if run.cancel_token.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            break;
+        }
+
+        let source_path = resolve(source, &action.relative_path);
+        let destination_path = resolve(destination, &action.relative_path);
+        let action_started_at = Instant::now();
+        run.emit_progress(&action.relative_path, false);
+
+        let result = match action.kind {
+            SyncActionKind::CopyToDestination => {
+                run.copy_entry(&source_path, &destination_path, &source_exclude)
+            }
+            SyncActionKind::CopyToSource => {
+                run.copy_entry(&destination_path, &source_path, &destination_exclude)
+            }
+            SyncActionKind::DeleteFromDestination => remove_dir_or_file(&destination_path),
+            SyncActionKind::DeleteFromSource => remove_dir_or_file(&source_path),
+            SyncActionKind::Conflict => {
+                unsynced_paths.insert(action.relative_path.clone());
+                job.record(JobReportItem::skipped(&source_path, SkipReason::Conflict));
+                continue;
+            }
+        };
+
+        let (from, to) = match action.kind {
+            SyncActionKind::CopyToSource | SyncActionKind::DeleteFromSource => {
+                (&destination_path, &source_path)
+            }
+            _ => (&source_path, &destination_path),
+        };
+        let item = match &result {
+            Ok(()) if action.kind == SyncActionKind::DeleteFromDestination => {
+                JobReportItem::new(&destination_path, ItemAction::Deleted)
+            }
+            Ok(()) if action.kind == SyncActionKind::DeleteFromSource => {
+                JobReportItem::new(&source_path, ItemAction::Deleted)
+            }
+            Ok(()) => JobReportItem::new(from, ItemAction::Copied)
+                .destination(to)
+                .bytes(action.size),
+            Err(error) => JobReportItem::failed(from, error.clone()).destination(to),
+        };
+        job.record(item.started_at(action_started_at));
+
+        match result {
+            Ok(()) => report.completed_count += 1,
+            Err(error) => {
+                unsynced_paths.insert(action.relative_path.clone());
+                report
+                    .errors
+                    .push(format!("{}: {}", action.relative_path, error));
+            }
+        }
+        run.completed_count = report.completed_count;
+    }
+
+    run.emit_progress("", true);
+    job.set_cancelled(report.cancelled);
+    report.job_id = job.finish();
+
+    if mode == SyncMode::TwoWay && !report.cancelled {
+        let (source_tree, _) = collect_tree(source, &source_exclude);
+        let synced_entries: BTreeMap<String, SyncedEntry> = source_tree
+            .into_iter()
+            .filter(|(relative_path, _)| {
+                !unsynced_paths.contains(relative_path)
+                    && !has_ancestor_in(relative_path, &unsynced_paths)
+            })
+            .map(|(relative_path, entry)| {
+                (
+                    relative_path,
+                    SyncedEntry {
+                        size: entry.size,
+                        modified_ms: entry.modified_ms,
+                    },
+                )
+            })
+            .collect();
+
+        let mut store = SYNC_STORE.lock().map_err(|error| error.to_string())?;
+        store
+            .pairs
+            .insert(pair_key(&source_key, &destination_key), synced_entries);
+        json_store::save(STORE_NAME, &*store)?;
+    }
+
+    Ok(report)
+}
+
+/// Runs a sync without progress events, for scheduled tasks
+pub fn run_sync(
+    app: &tauri::AppHandle,
+    source: &Path,
+    destination: &Path,
+    mode: SyncMode,
+    exclude: Vec<String>,
+) -> Result<SyncReport, String> {
+    let cancel_token = AtomicBool::new(false);
+    let mut run = SyncRun {
+        progress: ProgressEmitter::new(app, None, PROGRESS_EVENT, None),
+        cancel_token: &cancel_token,
+        completed_count: 0,
+        total_count: 0,
+        copied_bytes: 0,
+        total_bytes: 0,
+    };
+    sync_blocking(&mut run, source, destination, mode, false, exclude)
+}
+
+/// Synchronizes `destination` with `source`. With `dry_run` only the plan
+/// is returned, for the user to confirm before running it for real.
+/// `exclude` takes `.gitignore` style patterns. Copies and deletions are
+/// reported through `dir-sync-progress` events when a `request_id` is given.
+#[tauri::command]
+pub async fn sync_dirs(
+    window: tauri::WebviewWindow,
+    source: String,
+    destination: String,
+    mode: SyncMode,
+    dry_run: bool,
+    exclude: Option<Vec<String>>,
+    request_id: Option<String>,
+) -> Result<SyncReport, String> {
+    let active_request = ActiveRequest::register(request_id.as_deref());
+    let cancel_token = active_request.cancel_token();
+    let app = window.app_handle().clone();
+    let owner_window = window.label().to_string();
+
+    tokio::task::spawn_blocking(move || {
+        let mut run = SyncRun {
+            progress: ProgressEmitter::new(
+                &app,
+                Some(&owner_window),
+                PROGRESS_EVENT,
+                request_id.as_deref(),
+            ),
+            cancel_token: &cancel_token,
+            completed_count: 0,
+            total_count: 0,
+            copied_bytes: 0,
+            total_bytes: 0,
+        };
+        sync_blocking(
+            &mut run,
+            &to_os_path(&source),
+            &to_os_path(&destination),
+            mode,
+            dry_run,
+            exclude.unwrap_or_default(),
+        )
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub fn cancel_sync_dirs(request_id: String) {
+    requests::cancel(&request_id);
+}
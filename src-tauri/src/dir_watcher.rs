@@ -4,7 +4,7 @@
 
 use notify::{event::ModifyKind, Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -67,7 +67,9 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
         let watcher_result = RecommendedWatcher::new(
             move |res: Result<notify::Event, notify::Error>| {
                 if let Ok(event) = res {
-                    let _ = tx.send(event);
+                    if tx.send(event).is_ok() {
+                        crate::metrics::watcher_event_queued();
+                    }
                 }
             },
             Config::default().with_poll_interval(Duration::from_secs(1)),
@@ -76,21 +78,23 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
         let mut watcher = match watcher_result {
             Ok(watcher) => watcher,
             Err(err) => {
-                log::error!("Failed to create watcher for {}: {}", path_for_thread, err);
+                tracing::error!("Failed to create watcher for {}: {}", path_for_thread, err);
                 return;
             }
         };
 
         if let Err(err) = watcher.watch(&watch_path, RecursiveMode::NonRecursive) {
-            log::error!("Failed to watch {}: {}", path_for_thread, err);
+            tracing::error!("Failed to watch {}: {}", path_for_thread, err);
             return;
         }
 
-        log::info!("Started watching directory: {}", path_for_thread);
+        tracing::info!("Started watching directory: {}", path_for_thread);
 
         let debounce_duration = Duration::from_millis(300);
         let mut last_emit_time: Option<Instant> = None;
         let mut pending_emit = false;
+        let mut last_change_time: Option<Instant> = None;
+        let mut changed_file_paths: HashSet<PathBuf> = HashSet::new();
 
         loop {
             {
@@ -98,18 +102,31 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
                     .lock()
                     .unwrap_or_else(|err| err.into_inner());
                 if *should_stop {
-                    log::info!("Stopping watcher for: {}", path_for_thread);
+                    tracing::info!("Stopping watcher for: {}", path_for_thread);
                     break;
                 }
             }
 
             match rx.recv_timeout(Duration::from_millis(100)) {
                 Ok(event) => {
+                    crate::metrics::watcher_event_processed();
+
                     if !is_relevant_event(&event.kind) {
                         continue;
                     }
 
+                    // An injected failure drops the event, like an overflowed queue
+                    let is_dropped = event.paths.first().is_some_and(|path| {
+                        crate::faults::check(crate::faults::FaultPoint::WatchEvent, path).is_err()
+                    });
+
+                    if is_dropped {
+                        continue;
+                    }
+
                     let now = Instant::now();
+                    changed_file_paths.extend(event.paths.iter().cloned());
+                    last_change_time = Some(now);
                     let should_emit = match last_emit_time {
                         Some(last_time) => now.duration_since(last_time) >= debounce_duration,
                         None => true,
@@ -130,7 +147,7 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
                         });
 
                         if let Err(err) = app_handle.emit("dir-change", payload) {
-                            log::error!("Failed to emit dir-change event: {}", err);
+                            tracing::error!("Failed to emit dir-change event: {}", err);
                         }
 
                         last_emit_time = Some(now);
@@ -140,6 +157,18 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
                     }
                 }
                 Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                    // Thumbnails are only invalidated once writes have settled,
+                    // so a file being saved isn't regenerated on every chunk
+                    let changes_settled = last_change_time
+                        .map(|time| Instant::now().duration_since(time) >= debounce_duration)
+                        .unwrap_or(false);
+
+                    if changes_settled && !changed_file_paths.is_empty() {
+                        let changed_paths: Vec<PathBuf> = changed_file_paths.drain().collect();
+                        crate::thumbnails::invalidate_thumbnails(&app_handle, &changed_paths);
+                        crate::folder_sizes::apply_changes(&changed_paths);
+                    }
+
                     if pending_emit {
                         if let Some(last_time) = last_emit_time {
                             if Instant::now().duration_since(last_time) >= debounce_duration {
@@ -150,7 +179,7 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
                                 });
 
                                 if let Err(err) = app_handle.emit("dir-change", payload) {
-                                    log::error!("Failed to emit dir-change event: {}", err);
+                                    tracing::error!("Failed to emit dir-change event: {}", err);
                                 }
 
                                 last_emit_time = Some(Instant::now());
@@ -160,12 +189,22 @@ pub async fn watch_directory(app: AppHandle, path: String) -> Result<(), String>
                     }
                 }
                 Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
-                    log::info!("Watcher channel disconnected for: {}", path_for_thread);
+                    tracing::info!("Watcher channel disconnected for: {}", path_for_thread);
                     break;
                 }
             }
         }
 
+        // Events still queued when the watcher stops are dropped unprocessed
+        drop(watcher);
+        for _ in rx.try_iter() {
+            crate::metrics::watcher_event_processed();
+        }
+
+        let changed_paths: Vec<PathBuf> = changed_file_paths.drain().collect();
+        crate::folder_sizes::apply_changes(&changed_paths);
+        crate::folder_sizes::flush();
+
         if let Ok(mut watchers) = ACTIVE_WATCHERS.lock() {
             watchers.remove(&path_for_thread);
         }
@@ -189,7 +228,7 @@ pub async fn unwatch_directory(path: String) -> Result<(), String> {
             .lock()
             .unwrap_or_else(|err| err.into_inner());
         *should_stop = true;
-        log::info!("Signaled watcher to stop for: {}", normalized_path);
+        tracing::info!("Signaled watcher to stop for: {}", normalized_path);
     }
 
     Ok(())
@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! lsblk reads partition tables and file system signatures through udev,
+//! which works without root. Partition numbers and offsets come from sysfs.
+
+use super::{gpt_flags, mbr_flags, DiskInfo, PartitionInfo};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+const LSBLK_COLUMNS: &str = "NAME,PATH,TYPE,SIZE,FSTYPE,LABEL,UUID,PARTUUID,PARTLABEL,PARTTYPE,\
+PARTFLAGS,PTTYPE,MOUNTPOINT,RM,MODEL,SERIAL,TRAN";
+const SECTOR_SIZE: u64 = 512;
+
+fn string_field(device: &Value, name: &str) -> Option<String> {
+    device
+        .get(name)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+// Older lsblk prints numbers and flags as strings
+fn number_field(device: &Value, name: &str) -> Option<u64> {
+    match device.get(name)? {
+        Value::Number(number) => number.as_u64(),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn flag_field(device: &Value, name: &str) -> bool {
+    match device.get(name) {
+        Some(Value::Bool(flag)) => *flag,
+        Some(Value::String(text)) => text.trim() == "1",
+        Some(Value::Number(number)) => number.as_u64() == Some(1),
+        _ => false,
+    }
+}
+
+fn parse_hex(text: &str) -> Option<u64> {
+    u64::from_str_radix(text.trim_start_matches("0x"), 16).ok()
+}
+
+fn sysfs_number(name: &str, attribute: &str) -> Option<u64> {
+    fs::read_to_string(Path::new("/sys/class/block").join(name).join(attribute))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+fn partition_from(device: &Value, partition_table: Option<&str>) -> PartitionInfo {
+    let name = string_field(device, "name").unwrap_or_default();
+    let partition_type = string_field(device, "parttype");
+    let attributes = string_field(device, "partflags")
+        .and_then(|flags| parse_hex(&flags))
+        .unwrap_or(0);
+
+    let flags = match (partition_table, partition_type.as_deref()) {
+        (Some("gpt"), Some(type_guid)) => gpt_flags(type_guid, attributes),
+        (Some("dos"), Some(type_byte)) => mbr_flags(
+            parse_hex(type_byte).unwrap_or(0) as u8,
+            attributes & 0x80 != 0,
+        ),
+        _ => Vec::new(),
+    };
+
+    PartitionInfo {
+        device_path: string_field(device, "path").unwrap_or_else(|| format!("/dev/{}", name)),
+        number: sysfs_number(&name, "partition").map(|number| number as u32),
+        offset: sysfs_number(&name, "start").map(|start| start * SECTOR_SIZE),
+        size: number_field(device, "size").unwrap_or(0),
+        partition_type,
+        partition_label: string_field(device, "partlabel"),
+        partition_uuid: string_field(device, "partuuid"),
+        file_system: string_field(device, "fstype"),
+        label: string_field(device, "label"),
+        uuid: string_field(device, "uuid"),
+        mount_point: string_field(device, "mountpoint"),
+        flags,
+    }
+}
+
+pub fn list_disks() -> Result<Vec<DiskInfo>, String> {
+    let output = std::process::Command::new("lsblk")
+        .args(["--json", "--bytes", "--output", LSBLK_COLUMNS])
+        .output()
+        .map_err(|error| format!("Failed to run lsblk: {}", error))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(format!("lsblk failed: {}", stderr.trim()));
+    }
+
+    let listing: Value =
+        serde_json::from_slice(&output.stdout).map_err(|error| error.to_string())?;
+    let devices = listing
+        .get("blockdevices")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    let disks = devices
+        .iter()
+        .filter(|device| string_field(device, "type").as_deref() == Some("disk"))
+        .map(|device| {
+            // lsblk calls MBR "dos"
+            let partition_table = string_field(device, "pttype");
+            let children = device
+                .get("children")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+
+            let mut partitions: Vec<PartitionInfo> = children
+                .iter()
+                .filter(|child| string_field(child, "type").as_deref() == Some("part"))
+                .map(|child| partition_from(child, partition_table.as_deref()))
+                .collect();
+
+            // A file system written straight to the disk, as on some USB sticks
+            if partitions.is_empty() && string_field(device, "fstype").is_some() {
+                let mut whole_disk = partition_from(device, None);
+                whole_disk.offset = Some(0);
+                partitions.push(whole_disk);
+            }
+
+            DiskInfo {
+                device_path: string_field(device, "path").unwrap_or_else(|| {
+                    format!("/dev/{}", string_field(device, "name").unwrap_or_default())
+                }),
+                model: string_field(device, "model"),
+                serial_number: string_field(device, "serial"),
+                size: number_field(device, "size").unwrap_or(0),
+                partition_table: partition_table.map(|table| match table.as_str() {
+                    "dos" => "mbr".to_string(),
+                    _ => table,
+                }),
+                is_removable: flag_field(device, "rm"),
+                transport: string_field(device, "tran"),
+                partitions,
+            }
+        })
+        .collect();
+
+    Ok(disks)
+}
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! diskutil reports what IOKit knows about each disk as a property list:
+//! `list` gives the partition layout and `info` the details of one disk or
+//! partition. APFS containers show up as synthesized disks, which are left
+//! out in favour of the physical store they live on.
+
+use super::{DiskInfo, PartitionInfo};
+use plist::{Dictionary, Value};
+
+fn diskutil_plist(args: &[&str]) -> Result<Dictionary, String> {
+    let output = std::process::Command::new("diskutil")
+        .args(args)
+        .arg("-plist")
+        .output()
+        .map_err(|error| format!("Failed to run diskutil: {}", error))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(format!("diskutil failed: {}", stderr.trim()));
+    }
+
+    Value::from_reader(std::io::Cursor::new(output.stdout))
+        .map_err(|error| error.to_string())?
+        .into_dictionary()
+        .ok_or_else(|| "Unexpected diskutil output".to_string())
+}
+
+fn diskutil_info(identifier: &str) -> Dictionary {
+    diskutil_plist(&["info", identifier]).unwrap_or_default()
+}
+
+fn string_value(dictionary: &Dictionary, key: &str) -> Option<String> {
+    dictionary
+        .get(key)
+        .and_then(Value::as_string)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+fn number_value(dictionary: &Dictionary, key: &str) -> Option<u64> {
+    dictionary.get(key).and_then(Value::as_unsigned_integer)
+}
+
+fn bool_value(dictionary: &Dictionary, key: &str) -> bool {
+    dictionary
+        .get(key)
+        .and_then(Value::as_boolean)
+        .unwrap_or(false)
+}
+
+fn partition_from(entry: &Dictionary) -> PartitionInfo {
+    let identifier = string_value(entry, "DeviceIdentifier").unwrap_or_default();
+    let info = diskutil_info(&identifier);
+    let content = string_value(entry, "Content");
+
+    let mut flags = Vec::new();
+    if content.as_deref() == Some("EFI") {
+        flags.push("esp".to_string());
+    }
+    if !bool_value(&info, "WritableMedia") && info.contains_key("WritableMedia") {
+        flags.push("read-only".to_string());
+    }
+
+    PartitionInfo {
+        device_path: format!("/dev/{}", identifier),
+        number: identifier
+            .rsplit_once('s')
+            .and_then(|(_, number)| number.parse().ok()),
+        offset: number_value(&info, "PartitionMapPartitionOffset"),
+        size: number_value(entry, "Size").unwrap_or(0),
+        partition_type: content,
+        partition_label: None,
+        partition_uuid: string_value(entry, "DiskUUID"),
+        // Reported for formatted partitions whether mounted or not
+        file_system: string_value(&info, "FilesystemType"),
+        label: string_value(entry, "VolumeName").or_else(|| string_value(&info, "VolumeName")),
+        uuid: string_value(entry, "VolumeUUID").or_else(|| string_value(&info, "VolumeUUID")),
+        mount_point: string_value(entry, "MountPoint"),
+        flags,
+    }
+}
+
+pub fn list_disks() -> Result<Vec<DiskInfo>, String> {
+    let listing = diskutil_plist(&["list", "physical"])?;
+    let disks = listing
+        .get("AllDisksAndPartitions")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    Ok(disks
+        .iter()
+        .filter_map(Value::as_dictionary)
+        .map(|disk| {
+            let identifier = string_value(disk, "DeviceIdentifier").unwrap_or_default();
+            let info = diskutil_info(&identifier);
+            let scheme = string_value(disk, "Content");
+
+            let mut partitions: Vec<PartitionInfo> = disk
+                .get("Partitions")
+                .and_then(Value::as_array)
+                .map(|entries| {
+                    entries
+                        .iter()
+                        .filter_map(Value::as_dictionary)
+                        .map(partition_from)
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            // A file system written straight to the disk, as on some USB sticks
+            if partitions.is_empty() && string_value(&info, "FilesystemType").is_some() {
+                let mut whole_disk = partition_from(disk);
+                whole_disk.number = None;
+                whole_disk.offset = Some(0);
+                partitions.push(whole_disk);
+            }
+
+            DiskInfo {
+                device_path: format!("/dev/{}", identifier),
+                model: string_value(&info, "MediaName"),
+                serial_number: None,
+                size: number_value(disk, "Size").unwrap_or(0),
+                partition_table: match scheme.as_deref() {
+                    Some("GUID_partition_scheme") => Some("gpt".to_string()),
+                    Some("FDisk_partition_scheme") => Some("mbr".to_string()),
+                    Some("Apple_partition_scheme") => Some("apm".to_string()),
+                    _ => None,
+                },
+                is_removable: bool_value(&info, "Removable")
+                    || bool_value(&info, "RemovableMedia")
+                    || bool_value(&info, "Ejectable"),
+                transport: string_value(&info, "BusProtocol").map(|bus| bus.to_lowercase()),
+                partitions,
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Physical disks with their partition tables, including partitions that
+//! aren't mounted or formatted, for the Drives page. Built on lsblk on
+//! Linux, diskutil on macOS and the disk and volume IOCTLs on Windows.
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "linux")]
+use self::linux as platform;
+#[cfg(target_os = "macos")]
+use self::macos as platform;
+#[cfg(target_os = "windows")]
+use self::windows as platform;
+
+use serde::{Deserialize, Serialize};
+
+const EFI_SYSTEM_PARTITION_GUID: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+const MICROSOFT_RESERVED_GUID: &str = "e3c9e316-0b5c-4db8-817d-f92df00215ae";
+const MBR_EFI_SYSTEM_TYPE: u8 = 0xef;
+const MBR_EXTENDED_TYPES: [u8; 3] = [0x05, 0x0f, 0x85];
+
+// GPT attribute bits
+const GPT_REQUIRED: u64 = 1;
+const GPT_LEGACY_BIOS_BOOTABLE: u64 = 1 << 2;
+const GPT_READ_ONLY: u64 = 1 << 60;
+const GPT_HIDDEN: u64 = 1 << 62;
+const GPT_NO_AUTOMOUNT: u64 = 1 << 63;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskInfo {
+    /// `/dev/sda`, `/dev/disk2` or `\\.\PhysicalDrive0`
+    pub device_path: String,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    pub size: u64,
+    /// "gpt", "mbr" or "apm" for old Mac disks, `None` for disks without a
+    /// partition table
+    pub partition_table: Option<String>,
+    pub is_removable: bool,
+    /// Bus the disk is attached by, such as "usb", "sata" or "nvme"
+    pub transport: Option<String>,
+    pub partitions: Vec<PartitionInfo>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PartitionInfo {
+    pub device_path: String,
+    pub number: Option<u32>,
+    /// Byte offset from the start of the disk
+    pub offset: Option<u64>,
+    pub size: u64,
+    /// Type GUID on GPT disks, type byte such as "0x07" on MBR disks, or
+    /// the platform's content name
+    pub partition_type: Option<String>,
+    /// Partition name from the GPT entry
+    pub partition_label: Option<String>,
+    pub partition_uuid: Option<String>,
+    /// `None` for unformatted partitions
+    pub file_system: Option<String>,
+    pub label: Option<String>,
+    /// File system UUID or serial number
+    pub uuid: Option<String>,
+    pub mount_point: Option<String>,
+    /// "esp", "boot", "hidden", "read-only", "required", "no-automount",
+    /// "reserved" or "extended"
+    pub flags: Vec<String>,
+}
+
+pub(crate) fn gpt_flags(type_guid: &str, attributes: u64) -> Vec<String> {
+    let type_guid = type_guid.to_lowercase();
+    let mut flags = Vec::new();
+
+    if type_guid == EFI_SYSTEM_PARTITION_GUID {
+        flags.push("esp");
+    }
+    if type_guid == MICROSOFT_RESERVED_GUID {
+        flags.push("reserved");
+    }
+    if attributes & GPT_LEGACY_BIOS_BOOTABLE != 0 {
+        flags.push("boot");
+    }
+    if attributes & GPT_REQUIRED != 0 {
+        flags.push("required");
+    }
+    if attributes & GPT_READ_ONLY != 0 {
+        flags.push("read-only");
+    }
+    if attributes & GPT_HIDDEN != 0 {
+        flags.push("hidden");
+    }
+    if attributes & GPT_NO_AUTOMOUNT != 0 {
+        flags.push("no-automount");
+    }
+
+    flags.into_iter().map(str::to_string).collect()
+}
+
+pub(crate) fn mbr_flags(partition_type: u8, is_bootable: bool) -> Vec<String> {
+    let mut flags = Vec::new();
+
+    if partition_type == MBR_EFI_SYSTEM_TYPE {
+        flags.push("esp");
+    }
+    if MBR_EXTENDED_TYPES.contains(&partition_type) {
+        flags.push("extended");
+    }
+    if is_bootable {
+        flags.push("boot");
+    }
+
+    flags.into_iter().map(str::to_string).collect()
+}
+
+/// File system UUIDs, or serial numbers on Windows, of the mounted
+/// partitions, with where they are mounted
+pub(crate) fn mounted_volume_ids() -> Vec<(String, String)> {
+    platform::list_disks()
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|disk| disk.partitions)
+        .filter_map(|partition| Some((partition.uuid?, partition.mount_point?)))
+        .collect()
+}
+
+/// Lists physical disks with every partition on them, mounted or not
+#[tauri::command]
+pub async fn get_disk_layout() -> Result<Vec<DiskInfo>, String> {
+    tokio::task::spawn_blocking(|| {
+        let mut disks = platform::list_disks()?;
+
+        for disk in disks.iter_mut() {
+            disk.partitions
+                .sort_by_key(|partition| (partition.offset, partition.number));
+        }
+        disks.sort_by(|a, b| a.device_path.cmp(&b.device_path));
+
+        Ok(disks)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
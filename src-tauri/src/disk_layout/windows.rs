@@ -0,0 +1,458 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Disks are read through `\\.\PhysicalDriveN` and their partition tables
+//! with `IOCTL_DISK_GET_DRIVE_LAYOUT_EX`. Volumes are matched to partitions
+//! by the disk extents they occupy, which gives each partition its file
+//! system, label and mount point. All of these queries need no access
+//! rights on the device, so they work without elevation.
+
+use super::{gpt_flags, mbr_flags, DiskInfo, PartitionInfo};
+use std::collections::HashMap;
+use std::os::windows::ffi::OsStrExt;
+use windows_sys::core::GUID;
+use windows_sys::Win32::Foundation::{
+    CloseHandle, GetLastError, ERROR_INSUFFICIENT_BUFFER, ERROR_MORE_DATA, HANDLE,
+    INVALID_HANDLE_VALUE,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, FindFirstVolumeW, FindNextVolumeW, FindVolumeClose, GetVolumeInformationW,
+    GetVolumePathNamesForVolumeNameW, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+};
+use windows_sys::Win32::System::Ioctl::{
+    DISK_EXTENT, DISK_GEOMETRY_EX, DRIVE_LAYOUT_INFORMATION_EX, IOCTL_DISK_GET_DRIVE_GEOMETRY_EX,
+    IOCTL_DISK_GET_DRIVE_LAYOUT_EX, IOCTL_STORAGE_QUERY_PROPERTY,
+    IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS, PARTITION_INFORMATION_EX, PARTITION_STYLE_GPT,
+    PARTITION_STYLE_MBR, VOLUME_DISK_EXTENTS,
+};
+use windows_sys::Win32::System::IO::DeviceIoControl;
+
+// Disk numbers can have gaps after devices are removed
+const MAX_PHYSICAL_DRIVES: u32 = 64;
+const MAX_QUERY_BUFFER_SIZE: usize = 1024 * 1024;
+const VOLUME_NAME_LENGTH: usize = 260;
+
+struct Handle(HANDLE);
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+/// IOCTL output, kept in 8-byte words so the structures it holds are aligned
+struct QueryBuffer {
+    words: Vec<u64>,
+    length: usize,
+}
+
+impl QueryBuffer {
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.words.as_ptr() as *const u8, self.length) }
+    }
+
+    fn as_ptr<T>(&self) -> *const T {
+        self.words.as_ptr() as *const T
+    }
+}
+
+struct VolumeDetails {
+    file_system: Option<String>,
+    label: Option<String>,
+    serial_number: Option<String>,
+    mount_point: Option<String>,
+}
+
+fn wide(text: &str) -> Vec<u16> {
+    std::ffi::OsStr::new(text)
+        .encode_wide()
+        .chain([0])
+        .collect()
+}
+
+fn from_wide(buffer: &[u16]) -> String {
+    let length = buffer
+        .iter()
+        .position(|char| *char == 0)
+        .unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..length])
+}
+
+fn non_empty(text: String) -> Option<String> {
+    let trimmed = text.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+fn format_guid(guid: &GUID) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        guid.data1,
+        guid.data2,
+        guid.data3,
+        guid.data4[0],
+        guid.data4[1],
+        guid.data4[2],
+        guid.data4[3],
+        guid.data4[4],
+        guid.data4[5],
+        guid.data4[6],
+        guid.data4[7]
+    )
+}
+
+fn open_device(path: &str) -> Option<Handle> {
+    let path = wide(path);
+    let handle = unsafe {
+        CreateFileW(
+            path.as_ptr(),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            0,
+            std::ptr::null_mut(),
+        )
+    };
+
+    (handle != INVALID_HANDLE_VALUE).then_some(Handle(handle))
+}
+
+fn query(device: &Handle, code: u32, input: &[u8], initial_size: usize) -> Option<QueryBuffer> {
+    let mut size = initial_size;
+
+    loop {
+        let mut words = vec![0u64; size.div_ceil(8)];
+        let mut returned = 0u32;
+        let succeeded = unsafe {
+            DeviceIoControl(
+                device.0,
+                code,
+                input.as_ptr() as _,
+                input.len() as u32,
+                words.as_mut_ptr() as _,
+                (words.len() * 8) as u32,
+                &mut returned,
+                std::ptr::null_mut(),
+            )
+        };
+
+        if succeeded != 0 {
+            return Some(QueryBuffer {
+                words,
+                length: returned as usize,
+            });
+        }
+
+        let error = unsafe { GetLastError() };
+        if (error != ERROR_INSUFFICIENT_BUFFER && error != ERROR_MORE_DATA)
+            || size >= MAX_QUERY_BUFFER_SIZE
+        {
+            return None;
+        }
+
+        size *= 2;
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let field = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(field.try_into().ok()?))
+}
+
+fn read_c_string(bytes: &[u8], offset: u32) -> Option<String> {
+    if offset == 0 {
+        return None;
+    }
+
+    let text = bytes.get(offset as usize..)?;
+    let length = text
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(text.len());
+    non_empty(String::from_utf8_lossy(&text[..length]).to_string())
+}
+
+fn bus_name(bus_type: u32) -> Option<&'static str> {
+    Some(match bus_type {
+        1 => "scsi",
+        2 => "atapi",
+        3 => "ata",
+        4 => "1394",
+        6 => "fibre",
+        7 => "usb",
+        8 => "raid",
+        9 => "iscsi",
+        10 => "sas",
+        11 => "sata",
+        12 => "sd",
+        13 => "mmc",
+        14 | 15 => "virtual",
+        17 => "nvme",
+        _ => return None,
+    })
+}
+
+struct DeviceDescriptor {
+    model: Option<String>,
+    serial_number: Option<String>,
+    is_removable: bool,
+    transport: Option<&'static str>,
+}
+
+// STORAGE_DEVICE_DESCRIPTOR is read by offset, its strings follow the
+// fixed part at the offsets it lists
+fn device_descriptor(device: &Handle) -> Option<DeviceDescriptor> {
+    // STORAGE_PROPERTY_QUERY for StorageDeviceProperty, PropertyStandardQuery
+    let property_query = [0u8; 12];
+    let buffer = query(device, IOCTL_STORAGE_QUERY_PROPERTY, &property_query, 1024)?;
+    let bytes = buffer.as_bytes();
+
+    let vendor = read_c_string(bytes, read_u32(bytes, 12)?);
+    let product = read_c_string(bytes, read_u32(bytes, 16)?);
+    let model = match (vendor, product) {
+        (Some(vendor), Some(product)) => Some(format!("{} {}", vendor, product)),
+        (vendor, product) => product.or(vendor),
+    };
+
+    Some(DeviceDescriptor {
+        model,
+        serial_number: read_c_string(bytes, read_u32(bytes, 24)?),
+        is_removable: bytes.get(10).copied().unwrap_or(0) != 0,
+        transport: bus_name(read_u32(bytes, 28)?),
+    })
+}
+
+fn volume_details(volume_name: &str) -> VolumeDetails {
+    let root = wide(volume_name);
+    let mut label = [0u16; VOLUME_NAME_LENGTH + 1];
+    let mut file_system = [0u16; VOLUME_NAME_LENGTH + 1];
+    let mut serial_number = 0u32;
+    let mut max_component_length = 0u32;
+    let mut file_system_flags = 0u32;
+
+    // Fails for unformatted and unrecognized partitions
+    let has_file_system = unsafe {
+        GetVolumeInformationW(
+            root.as_ptr(),
+            label.as_mut_ptr(),
+            label.len() as u32,
+            &mut serial_number,
+            &mut max_component_length,
+            &mut file_system_flags,
+            file_system.as_mut_ptr(),
+            file_system.len() as u32,
+        )
+    } != 0;
+
+    let mut path_names = [0u16; VOLUME_NAME_LENGTH * 4];
+    let mut returned = 0u32;
+    let has_path = unsafe {
+        GetVolumePathNamesForVolumeNameW(
+            root.as_ptr(),
+            path_names.as_mut_ptr(),
+            path_names.len() as u32,
+            &mut returned,
+        )
+    } != 0;
+
+    VolumeDetails {
+        file_system: has_file_system
+            .then(|| non_empty(from_wide(&file_system)))
+            .flatten(),
+        label: has_file_system
+            .then(|| non_empty(from_wide(&label)))
+            .flatten(),
+        serial_number: has_file_system
+            .then(|| format!("{:04X}-{:04X}", serial_number >> 16, serial_number & 0xffff)),
+        // The first of possibly several mount points
+        mount_point: has_path
+            .then(|| non_empty(from_wide(&path_names)))
+            .flatten(),
+    }
+}
+
+/// Volumes keyed by the disk number and byte offset of their first extent
+fn list_volumes() -> HashMap<(u32, i64), VolumeDetails> {
+    let mut volumes = HashMap::new();
+    let mut name = [0u16; VOLUME_NAME_LENGTH];
+
+    let search = unsafe { FindFirstVolumeW(name.as_mut_ptr(), name.len() as u32) };
+    if search == INVALID_HANDLE_VALUE {
+        return volumes;
+    }
+
+    loop {
+        // Names end with a backslash, which must be dropped to open the volume
+        let volume_name = from_wide(&name);
+
+        if let Some(device) = open_device(volume_name.trim_end_matches('\\')) {
+            if let Some(buffer) = query(
+                &device,
+                IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS,
+                &[],
+                std::mem::size_of::<VOLUME_DISK_EXTENTS>() + 4 * std::mem::size_of::<DISK_EXTENT>(),
+            ) {
+                let extents = buffer.as_ptr::<VOLUME_DISK_EXTENTS>();
+                let first_extent =
+                    unsafe { ((*extents).NumberOfDiskExtents > 0).then(|| (*extents).Extents[0]) };
+
+                if let Some(extent) = first_extent {
+                    volumes.insert(
+                        (extent.DiskNumber, extent.StartingOffset),
+                        volume_details(&volume_name),
+                    );
+                }
+            }
+        }
+
+        if unsafe { FindNextVolumeW(search, name.as_mut_ptr(), name.len() as u32) } == 0 {
+            break;
+        }
+    }
+
+    unsafe {
+        FindVolumeClose(search);
+    }
+
+    volumes
+}
+
+fn partition_from(
+    disk_number: u32,
+    entry: &PARTITION_INFORMATION_EX,
+    is_gpt: bool,
+    volumes: &mut HashMap<(u32, i64), VolumeDetails>,
+) -> PartitionInfo {
+    let volume = volumes.remove(&(disk_number, entry.StartingOffset));
+
+    let (partition_type, partition_label, partition_uuid, flags) = unsafe {
+        if is_gpt {
+            let gpt = &entry.Anonymous.Gpt;
+            let type_guid = format_guid(&gpt.PartitionType);
+            let flags = gpt_flags(&type_guid, gpt.Attributes as u64);
+            (
+                Some(type_guid),
+                non_empty(from_wide(&gpt.Name)),
+                Some(format_guid(&gpt.PartitionId)),
+                flags,
+            )
+        } else {
+            let mbr = &entry.Anonymous.Mbr;
+            (
+                Some(format!("0x{:02x}", mbr.PartitionType)),
+                None,
+                None,
+                mbr_flags(mbr.PartitionType, mbr.BootIndicator as u8 != 0),
+            )
+        }
+    };
+
+    let volume = volume.unwrap_or(VolumeDetails {
+        file_system: None,
+        label: None,
+        serial_number: None,
+        mount_point: None,
+    });
+
+    PartitionInfo {
+        device_path: format!(
+            "\\\\?\\GLOBALROOT\\Device\\Harddisk{}\\Partition{}",
+            disk_number, entry.PartitionNumber
+        ),
+        number: Some(entry.PartitionNumber),
+        offset: Some(entry.StartingOffset as u64),
+        size: entry.PartitionLength as u64,
+        partition_type,
+        partition_label,
+        partition_uuid,
+        file_system: volume.file_system,
+        label: volume.label,
+        uuid: volume.serial_number,
+        mount_point: volume.mount_point,
+        flags,
+    }
+}
+
+pub fn list_disks() -> Result<Vec<DiskInfo>, String> {
+    let mut volumes = list_volumes();
+    let mut disks = Vec::new();
+
+    for disk_number in 0..MAX_PHYSICAL_DRIVES {
+        let device_path = format!("\\\\.\\PhysicalDrive{}", disk_number);
+        let Some(device) = open_device(&device_path) else {
+            continue;
+        };
+
+        let size = query(
+            &device,
+            IOCTL_DISK_GET_DRIVE_GEOMETRY_EX,
+            &[],
+            std::mem::size_of::<DISK_GEOMETRY_EX>() + 64,
+        )
+        .map(|buffer| unsafe { (*buffer.as_ptr::<DISK_GEOMETRY_EX>()).DiskSize as u64 })
+        .unwrap_or(0);
+
+        let descriptor = device_descriptor(&device);
+        let mut partition_table = None;
+        let mut partitions = Vec::new();
+
+        if let Some(buffer) = query(
+            &device,
+            IOCTL_DISK_GET_DRIVE_LAYOUT_EX,
+            &[],
+            std::mem::size_of::<DRIVE_LAYOUT_INFORMATION_EX>()
+                + 16 * std::mem::size_of::<PARTITION_INFORMATION_EX>(),
+        ) {
+            let layout = buffer.as_ptr::<DRIVE_LAYOUT_INFORMATION_EX>();
+            let (style, entries) = unsafe {
+                let first_entry =
+                    std::ptr::addr_of!((*layout).PartitionEntry) as *const PARTITION_INFORMATION_EX;
+                (
+                    (*layout).PartitionStyle,
+                    std::slice::from_raw_parts(first_entry, (*layout).PartitionCount as usize),
+                )
+            };
+
+            let is_gpt = style == PARTITION_STYLE_GPT as _;
+            partition_table = if is_gpt {
+                Some("gpt".to_string())
+            } else if style == PARTITION_STYLE_MBR as _ {
+                Some("mbr".to_string())
+            } else {
+                None
+            };
+
+            // MBR layouts list all four primary slots, used or not
+            partitions = entries
+                .iter()
+                .filter(|entry| entry.PartitionLength > 0 && entry.PartitionNumber > 0)
+                .map(|entry| partition_from(disk_number, entry, is_gpt, &mut volumes))
+                .collect();
+        }
+
+        disks.push(DiskInfo {
+            device_path,
+            model: descriptor
+                .as_ref()
+                .and_then(|descriptor| descriptor.model.clone()),
+            serial_number: descriptor
+                .as_ref()
+                .and_then(|descriptor| descriptor.serial_number.clone()),
+            size,
+            partition_table,
+            is_removable: descriptor
+                .as_ref()
+                .map(|descriptor| descriptor.is_removable)
+                .unwrap_or(false),
+            transport: descriptor
+                .as_ref()
+                .and_then(|descriptor| descriptor.transport.map(str::to_string)),
+            partitions,
+        });
+    }
+
+    Ok(disks)
+}
@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Kernel uevents for block devices, read from the same netlink group udev
+//! listens on, plus the mount table, which changes when a desktop automounts
+//! a drive some time after the device appears.
+
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+const KERNEL_UEVENT_GROUP: u32 = 1;
+const UEVENT_BUFFER_SIZE: usize = 8192;
+
+fn uevent_socket() -> io::Result<OwnedFd> {
+    unsafe {
+        let socket = libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_KOBJECT_UEVENT,
+        );
+
+        if socket < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let socket = OwnedFd::from_raw_fd(socket);
+        let mut address: libc::sockaddr_nl = std::mem::zeroed();
+        address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        address.nl_groups = KERNEL_UEVENT_GROUP;
+
+        let bound = libc::bind(
+            socket.as_raw_fd(),
+            &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        );
+
+        if bound < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(socket)
+    }
+}
+
+fn is_block_event(message: &[u8]) -> bool {
+    message
+        .split(|byte| *byte == 0)
+        .any(|field| field == b"SUBSYSTEM=block")
+}
+
+pub fn watch(sender: Sender<()>) -> Result<(), String> {
+    let socket = uevent_socket().map_err(|error| error.to_string())?;
+    // Polling the mount table reports a priority event on every change
+    let mount_table = File::open("/proc/self/mountinfo").map_err(|error| error.to_string())?;
+
+    thread::Builder::new()
+        .name("drive-monitor-uevents".to_string())
+        .spawn(move || {
+            let mut buffer = vec![0u8; UEVENT_BUFFER_SIZE];
+
+            loop {
+                let mut poll_fds = [
+                    libc::pollfd {
+                        fd: socket.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    },
+                    libc::pollfd {
+                        fd: mount_table.as_raw_fd(),
+                        events: libc::POLLPRI,
+                        revents: 0,
+                    },
+                ];
+
+                let ready = unsafe { libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as _, -1) };
+
+                if ready < 0 {
+                    if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    break;
+                }
+
+                let mut is_relevant = poll_fds[1].revents & (libc::POLLPRI | libc::POLLERR) != 0;
+
+                if poll_fds[0].revents & libc::POLLIN != 0 {
+                    let received = unsafe {
+                        libc::recv(
+                            socket.as_raw_fd(),
+                            buffer.as_mut_ptr() as *mut libc::c_void,
+                            buffer.len(),
+                            0,
+                        )
+                    };
+
+                    if received > 0 {
+                        is_relevant |= is_block_event(&buffer[..received as usize]);
+                    }
+                }
+
+                if is_relevant && sender.send(()).is_err() {
+                    break;
+                }
+            }
+        })
+        .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
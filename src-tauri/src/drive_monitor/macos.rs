@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! DiskArbitration callbacks, run on a dedicated run loop. Description
+//! changes cover mounts, unmounts and renames of disks that stay attached.
+
+use core_foundation::array::CFArrayRef;
+use core_foundation::base::{kCFAllocatorDefault, CFAllocatorRef, TCFType};
+use core_foundation::dictionary::CFDictionaryRef;
+use core_foundation::runloop::{kCFRunLoopDefaultMode, CFRunLoop, CFRunLoopRef};
+use core_foundation::string::CFStringRef;
+use std::ffi::c_void;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+type DASessionRef = *mut c_void;
+type DADiskRef = *mut c_void;
+type DiskCallback = extern "C" fn(disk: DADiskRef, context: *mut c_void);
+type DiskChangedCallback = extern "C" fn(disk: DADiskRef, keys: CFArrayRef, context: *mut c_void);
+
+#[link(name = "DiskArbitration", kind = "framework")]
+extern "C" {
+    fn DASessionCreate(allocator: CFAllocatorRef) -> DASessionRef;
+    fn DASessionScheduleWithRunLoop(
+        session: DASessionRef,
+        run_loop: CFRunLoopRef,
+        run_loop_mode: CFStringRef,
+    );
+    fn DARegisterDiskAppearedCallback(
+        session: DASessionRef,
+        match_description: CFDictionaryRef,
+        callback: DiskCallback,
+        context: *mut c_void,
+    );
+    fn DARegisterDiskDisappearedCallback(
+        session: DASessionRef,
+        match_description: CFDictionaryRef,
+        callback: DiskCallback,
+        context: *mut c_void,
+    );
+    fn DARegisterDiskDescriptionChangedCallback(
+        session: DASessionRef,
+        match_description: CFDictionaryRef,
+        watch: CFArrayRef,
+        callback: DiskChangedCallback,
+        context: *mut c_void,
+    );
+}
+
+fn notify(context: *mut c_void) {
+    // The sender is leaked for the lifetime of the run loop below
+    let sender = unsafe { &*(context as *const Sender<()>) };
+    let _ = sender.send(());
+}
+
+extern "C" fn disk_event(_disk: DADiskRef, context: *mut c_void) {
+    notify(context);
+}
+
+extern "C" fn disk_changed(_disk: DADiskRef, _keys: CFArrayRef, context: *mut c_void) {
+    notify(context);
+}
+
+pub fn watch(sender: Sender<()>) -> Result<(), String> {
+    thread::Builder::new()
+        .name("drive-monitor-diskarbitration".to_string())
+        .spawn(move || unsafe {
+            let session = DASessionCreate(kCFAllocatorDefault);
+
+            if session.is_null() {
+                tracing::warn!("Drive notifications are unavailable: no DiskArbitration session");
+                return;
+            }
+
+            let context = Box::into_raw(Box::new(sender)) as *mut c_void;
+
+            DARegisterDiskAppearedCallback(session, std::ptr::null(), disk_event, context);
+            DARegisterDiskDisappearedCallback(session, std::ptr::null(), disk_event, context);
+            DARegisterDiskDescriptionChangedCallback(
+                session,
+                std::ptr::null(),
+                std::ptr::null(),
+                disk_changed,
+                context,
+            );
+            DASessionScheduleWithRunLoop(
+                session,
+                CFRunLoop::get_current().as_concrete_TypeRef(),
+                kCFRunLoopDefaultMode,
+            );
+
+            CFRunLoop::run_current();
+        })
+        .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
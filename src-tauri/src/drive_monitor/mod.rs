@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Pushes drive changes to the frontend as they happen. Each platform's
+//! device notifications (uevents and mount table changes on Linux,
+//! `WM_DEVICECHANGE` on Windows, DiskArbitration on macOS) trigger a fresh
+//! `get_system_drives` listing, which is compared with the previous one to
+//! emit `drive-added`, `drive-removed` and `drive-changed`.
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "linux")]
+use self::linux as platform;
+#[cfg(target_os = "macos")]
+use self::macos as platform;
+#[cfg(target_os = "windows")]
+use self::windows as platform;
+
+use crate::dir_reader::{get_system_drives, DriveInfo};
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+pub const DRIVE_ADDED_EVENT: &str = "drive-added";
+pub const DRIVE_REMOVED_EVENT: &str = "drive-removed";
+pub const DRIVE_CHANGED_EVENT: &str = "drive-changed";
+
+/// Mounting finishes a moment after the device shows up, and one plug-in
+/// produces a burst of notifications
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+/// Also catches what notifications miss, like free space changing
+const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+fn snapshot() -> BTreeMap<String, DriveInfo> {
+    get_system_drives()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|drive| (drive.path.clone(), drive))
+        .collect()
+}
+
+// Free space moves constantly, so only whole percents count as a change
+fn has_changed(previous: &DriveInfo, current: &DriveInfo) -> bool {
+    previous.name != current.name
+        || previous.mount_point != current.mount_point
+        || previous.file_system != current.file_system
+        || previous.total_space != current.total_space
+        || previous.percent_used != current.percent_used
+        || previous.is_read_only != current.is_read_only
+        || previous.is_mounted != current.is_mounted
+}
+
+fn emit(app: &AppHandle, event: &str, drive: &DriveInfo) {
+    tracing::debug!(path = %drive.path, "{}", event);
+
+    if let Err(error) = app.emit(event, drive) {
+        tracing::warn!("Failed to emit {}: {}", event, error);
+    }
+}
+
+fn run(app: AppHandle, notifications: Receiver<()>) {
+    let mut known = snapshot();
+    let mut has_watcher = true;
+
+    loop {
+        if has_watcher {
+            match notifications.recv_timeout(RESCAN_INTERVAL) {
+                Ok(()) => {
+                    thread::sleep(SETTLE_DELAY);
+                    while notifications.try_recv().is_ok() {}
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    tracing::warn!("Drive notifications stopped, falling back to rescans");
+                    has_watcher = false;
+                }
+            }
+        } else {
+            thread::sleep(RESCAN_INTERVAL);
+        }
+
+        let current = snapshot();
+
+        for (path, drive) in &current {
+            match known.get(path) {
+                None => emit(&app, DRIVE_ADDED_EVENT, drive),
+                Some(previous) if has_changed(previous, drive) => {
+                    emit(&app, DRIVE_CHANGED_EVENT, drive)
+                }
+                Some(_) => {}
+            }
+        }
+
+        for (path, drive) in &known {
+            if !current.contains_key(path) {
+                emit(&app, DRIVE_REMOVED_EVENT, drive);
+            }
+        }
+
+        known = current;
+    }
+}
+
+pub fn start(app: AppHandle) {
+    let (sender, receiver) = mpsc::channel();
+
+    if let Err(error) = platform::watch(sender) {
+        tracing::warn!("Drive notifications are unavailable: {}", error);
+    }
+
+    let spawn_result = thread::Builder::new()
+        .name("drive-monitor".to_string())
+        .spawn(move || run(app, receiver));
+
+    if let Err(error) = spawn_result {
+        tracing::error!("Failed to start the drive monitor: {}", error);
+    }
+}
@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! `WM_DEVICECHANGE` is broadcast to top-level windows only, so a hidden
+//! one is created on its own thread to receive it.
+
+use once_cell::sync::OnceCell;
+use std::sync::mpsc::Sender;
+use std::thread;
+use windows::core::w;
+use windows::Win32::Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW,
+    TranslateMessage, DBT_DEVICEARRIVAL, DBT_DEVICEREMOVECOMPLETE, DBT_DEVNODES_CHANGED, HMENU,
+    MSG, WINDOW_EX_STYLE, WINDOW_STYLE, WM_DEVICECHANGE, WNDCLASSW,
+};
+
+static NOTIFICATIONS: OnceCell<Sender<()>> = OnceCell::new();
+
+unsafe extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if message == WM_DEVICECHANGE
+        && matches!(
+            wparam.0 as u32,
+            DBT_DEVICEARRIVAL | DBT_DEVICEREMOVECOMPLETE | DBT_DEVNODES_CHANGED
+        )
+    {
+        if let Some(sender) = NOTIFICATIONS.get() {
+            let _ = sender.send(());
+        }
+    }
+
+    DefWindowProcW(window, message, wparam, lparam)
+}
+
+fn run_message_loop() -> Result<(), String> {
+    unsafe {
+        let class_name = w!("SigmaFileManagerDriveMonitor");
+        let window_class = WNDCLASSW {
+            lpfnWndProc: Some(window_proc),
+            lpszClassName: class_name,
+            ..Default::default()
+        };
+
+        if RegisterClassW(&window_class) == 0 {
+            return Err(windows::core::Error::from_win32().message().to_string());
+        }
+
+        // Never shown; a message-only window would miss the broadcasts
+        CreateWindowExW(
+            WINDOW_EX_STYLE::default(),
+            class_name,
+            w!(""),
+            WINDOW_STYLE::default(),
+            0,
+            0,
+            0,
+            0,
+            HWND::default(),
+            HMENU::default(),
+            HINSTANCE::default(),
+            None,
+        )
+        .map_err(|error| error.message().to_string())?;
+
+        let mut message = MSG::default();
+
+        while GetMessageW(&mut message, HWND::default(), 0, 0).as_bool() {
+            let _ = TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn watch(sender: Sender<()>) -> Result<(), String> {
+    NOTIFICATIONS
+        .set(sender)
+        .map_err(|_| "The drive monitor is already running".to_string())?;
+
+    thread::Builder::new()
+        .name("drive-monitor-messages".to_string())
+        .spawn(|| {
+            if let Err(error) = run_message_loop() {
+                tracing::warn!("Drive notifications are unavailable: {}", error);
+            }
+        })
+        .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
@@ -0,0 +1,474 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Encrypting files with age, to a passphrase or to an age public key,
+//! before they go somewhere less trusted such as a cloud folder. Each file
+//! is written next to the original with an `.age` extension.
+//!
+//! Recipients that aren't age keys, like an email address or a key ID, are
+//! handed to GnuPG when it is installed, as are `.gpg` files to decrypt.
+
+use crate::file_operations::get_unique_destination_path;
+use crate::requests::{self, ActiveRequest, ProgressEmitter};
+use crate::utils::normalize_path;
+use age::secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::Manager;
+
+const PROGRESS_EVENT: &str = "file-encryption-progress";
+const AGE_EXTENSION: &str = "age";
+const GPG_EXTENSION: &str = "gpg";
+const GPG_EXTENSIONS: [&str; 3] = ["gpg", "pgp", "asc"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EncryptionKey {
+    Passphrase {
+        passphrase: String,
+    },
+    /// An age public key (`age1...`), or a GnuPG user ID
+    Recipient {
+        recipient: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptionProgress {
+    pub request_id: String,
+    pub processed_bytes: u64,
+    pub total_bytes: u64,
+    pub current_path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptionResult {
+    /// Paths of the written files
+    pub outputs: Vec<String>,
+    pub errors: Vec<String>,
+    pub cancelled: bool,
+}
+
+fn gpg_command() -> Command {
+    let mut command = Command::new("gpg");
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+}
+
+fn is_gpg_available() -> bool {
+    gpg_command()
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn run_gpg(arguments: &[&std::ffi::OsStr], passphrase: Option<&str>) -> Result<(), String> {
+    let mut child = gpg_command()
+        .args(arguments)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Failed to run GnuPG: {}", error))?;
+
+    if let (Some(passphrase), Some(mut stdin)) = (passphrase, child.stdin.take()) {
+        stdin
+            .write_all(passphrase.as_bytes())
+            .map_err(|error| error.to_string())?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|error| error.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
+
+fn has_gpg_extension(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        GPG_EXTENSIONS
+            .iter()
+            .any(|gpg_extension| extension.eq_ignore_ascii_case(gpg_extension))
+    })
+}
+
+fn output_path(path: &Path, extension: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let parent = path.parent().unwrap_or(Path::new(""));
+    get_unique_destination_path(parent, &format!("{}.{}", name, extension))
+}
+
+fn decrypted_path(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    let name = match path.file_stem() {
+        Some(stem) if path.extension().is_some() => stem.to_string_lossy().to_string(),
+        _ => format!(
+            "{}.decrypted",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ),
+    };
+    get_unique_destination_path(parent, &name)
+}
+
+struct EncryptionRun<'a> {
+    progress: ProgressEmitter<'a>,
+    cancel_token: &'a AtomicBool,
+    processed_bytes: u64,
+    total_bytes: u64,
+}
+
+impl EncryptionRun<'_> {
+    fn emit_progress(&self, current_path: &Path, force: bool) {
+        self.progress.emit(force, |request_id| EncryptionProgress {
+            request_id: request_id.to_string(),
+            processed_bytes: self.processed_bytes,
+            total_bytes: self.total_bytes,
+            current_path: normalize_path(&current_path.to_string_lossy()),
+        });
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_token.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts bytes read from the input for progress, and stops the stream
+/// when the job is cancelled
+struct ProgressReader<'a, 'b, R> {
+    inner: R,
+    run: &'a mut EncryptionRun<'b>,
+    path: &'a Path,
+}
+
+impl<R: Read> Read for ProgressReader<'_, '_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.run.is_cancelled() {
+            // Not `Interrupted`, which `io::copy` retries
+            return Err(io::Error::other("Cancelled"));
+        }
+
+        let read_count = self.inner.read(buffer)?;
+        self.run.processed_bytes += read_count as u64;
+        self.run.emit_progress(self.path, false);
+        Ok(read_count)
+    }
+}
+
+/// Writes `output` with `write`, removing what was written when it fails
+fn write_output(
+    output: &Path,
+    write: impl FnOnce(fs::File) -> Result<(), String>,
+) -> Result<(), String> {
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(output)
+        .map_err(|error| error.to_string())?;
+
+    write(file).inspect_err(|_| {
+        let _ = fs::remove_file(output);
+    })
+}
+
+fn encrypt_with_age(
+    run: &mut EncryptionRun,
+    path: &Path,
+    encryptor: age::Encryptor,
+) -> Result<PathBuf, String> {
+    let input = fs::File::open(path).map_err(|error| error.to_string())?;
+    let output = output_path(path, AGE_EXTENSION);
+
+    write_output(&output, |file| {
+        let mut writer = encryptor
+            .wrap_output(io::BufWriter::new(file))
+            .map_err(|error| error.to_string())?;
+        let mut reader = ProgressReader {
+            inner: io::BufReader::new(input),
+            run,
+            path,
+        };
+
+        io::copy(&mut reader, &mut writer).map_err(|error| error.to_string())?;
+        writer
+            .finish()
+            .and_then(|mut file| file.flush())
+            .map_err(|error| error.to_string())
+    })?;
+    Ok(output)
+}
+
+fn encrypt_with_gpg(
+    run: &mut EncryptionRun,
+    path: &Path,
+    recipient: &str,
+) -> Result<PathBuf, String> {
+    let output = output_path(path, GPG_EXTENSION);
+    let size = fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+
+    run_gpg(
+        &[
+            "--batch".as_ref(),
+            "--yes".as_ref(),
+            "--encrypt".as_ref(),
+            "--recipient".as_ref(),
+            recipient.as_ref(),
+            "--output".as_ref(),
+            output.as_os_str(),
+            path.as_os_str(),
+        ],
+        None,
+    )
+    .inspect_err(|_| {
+        let _ = fs::remove_file(&output);
+    })?;
+
+    run.processed_bytes += size;
+    Ok(output)
+}
+
+fn age_recipient(recipient: &str) -> Option<age::x25519::Recipient> {
+    age::x25519::Recipient::from_str(recipient.trim()).ok()
+}
+
+fn age_encryptor(key: &EncryptionKey) -> Result<age::Encryptor, String> {
+    match key {
+        EncryptionKey::Passphrase { passphrase } => Ok(age::Encryptor::with_user_passphrase(
+            SecretString::from(passphrase.clone()),
+        )),
+        EncryptionKey::Recipient { recipient } => {
+            let recipient = age_recipient(recipient)
+                .ok_or_else(|| format!("{} is not an age public key", recipient))?;
+            age::Encryptor::with_recipients(std::iter::once(&recipient as &dyn age::Recipient))
+                .map_err(|error| error.to_string())
+        }
+    }
+}
+
+fn encrypt_blocking(
+    run: &mut EncryptionRun,
+    paths: &[PathBuf],
+    key: &EncryptionKey,
+) -> Result<EncryptionResult, String> {
+    let use_gpg = match key {
+        EncryptionKey::Recipient { recipient } if age_recipient(recipient).is_none() => {
+            if !is_gpg_available() {
+                return Err(format!(
+                    "{} is not an age public key, and GnuPG is not installed",
+                    recipient
+                ));
+            }
+            true
+        }
+        _ => false,
+    };
+
+    run.total_bytes = paths
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+
+    let mut outputs = Vec::new();
+    let mut errors = Vec::new();
+
+    for path in paths {
+        if run.is_cancelled() {
+            break;
+        }
+        if path.is_dir() {
+            errors.push(format!(
+                "{}: Folders can't be encrypted, compress them to an archive first",
+                path.display()
+            ));
+            continue;
+        }
+
+        // An age encryptor is consumed by the file it encrypts
+        let result = match key {
+            EncryptionKey::Recipient { recipient } if use_gpg => {
+                encrypt_with_gpg(run, path, recipient.trim())
+            }
+            _ => age_encryptor(key).and_then(|encryptor| encrypt_with_age(run, path, encryptor)),
+        };
+
+        match result {
+            Ok(output) => outputs.push(normalize_path(&output.to_string_lossy())),
+            Err(_) if run.is_cancelled() => {}
+            Err(error) => errors.push(format!("{}: {}", path.display(), error)),
+        }
+        run.emit_progress(path, true);
+    }
+
+    Ok(EncryptionResult {
+        outputs,
+        errors,
+        cancelled: run.is_cancelled(),
+    })
+}
+
+fn decrypt_with_age(
+    run: &mut EncryptionRun,
+    path: &Path,
+    passphrase: &str,
+) -> Result<PathBuf, String> {
+    let input = fs::File::open(path).map_err(|error| error.to_string())?;
+    let reader = ProgressReader {
+        inner: io::BufReader::new(input),
+        run,
+        path,
+    };
+
+    let decryptor = age::Decryptor::new(reader).map_err(|error| error.to_string())?;
+    if !decryptor.is_scrypt() {
+        return Err("The file was encrypted to a key, not a passphrase".to_string());
+    }
+
+    let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_string()));
+    let mut stream = decryptor
+        .decrypt(std::iter::once(&identity as &dyn age::Identity))
+        .map_err(|error| match error {
+            age::DecryptError::DecryptionFailed | age::DecryptError::NoMatchingKeys => {
+                "Wrong passphrase".to_string()
+            }
+            error => error.to_string(),
+        })?;
+
+    // Nothing is written until the passphrase checks out
+    let output = decrypted_path(path);
+    write_output(&output, |file| {
+        let mut writer = io::BufWriter::new(file);
+        io::copy(&mut stream, &mut writer)
+            .and_then(|_| writer.flush())
+            .map_err(|error| error.to_string())
+    })?;
+    Ok(output)
+}
+
+fn decrypt_with_gpg(path: &Path, passphrase: &str) -> Result<PathBuf, String> {
+    if !is_gpg_available() {
+        return Err("Decrypting GnuPG files requires GnuPG to be installed".to_string());
+    }
+
+    let output = decrypted_path(path);
+    run_gpg(
+        &[
+            "--batch".as_ref(),
+            "--yes".as_ref(),
+            "--pinentry-mode".as_ref(),
+            "loopback".as_ref(),
+            "--passphrase-fd".as_ref(),
+            "0".as_ref(),
+            "--decrypt".as_ref(),
+            "--output".as_ref(),
+            output.as_os_str(),
+            path.as_os_str(),
+        ],
+        Some(passphrase),
+    )
+    .inspect_err(|_| {
+        let _ = fs::remove_file(&output);
+    })?;
+    Ok(output)
+}
+
+/// Encrypts each file to `<name>.age`, or `<name>.gpg` for GnuPG
+/// recipients, next to the original. Originals are left in place.
+#[tauri::command]
+pub async fn encrypt_files(
+    window: tauri::WebviewWindow,
+    paths: Vec<String>,
+    key: EncryptionKey,
+    request_id: Option<String>,
+) -> Result<EncryptionResult, String> {
+    let active_request = ActiveRequest::register(request_id.as_deref());
+    let cancel_token = active_request.cancel_token();
+    let app = window.app_handle().clone();
+    let owner_window = window.label().to_string();
+
+    tokio::task::spawn_blocking(move || {
+        let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+        let mut run = EncryptionRun {
+            progress: ProgressEmitter::new(
+                &app,
+                Some(&owner_window),
+                PROGRESS_EVENT,
+                request_id.as_deref(),
+            ),
+            cancel_token: &cancel_token,
+            processed_bytes: 0,
+            total_bytes: 0,
+        };
+        encrypt_blocking(&mut run, &paths, &key)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Decrypts an `.age` or GnuPG file with a passphrase and returns the path
+/// of the decrypted file, written next to it
+#[tauri::command]
+pub async fn decrypt_file(
+    window: tauri::WebviewWindow,
+    path: String,
+    passphrase: String,
+    request_id: Option<String>,
+) -> Result<String, String> {
+    let active_request = ActiveRequest::register(request_id.as_deref());
+    let cancel_token = active_request.cancel_token();
+    let app = window.app_handle().clone();
+    let owner_window = window.label().to_string();
+
+    tokio::task::spawn_blocking(move || {
+        let path = PathBuf::from(&path);
+        let mut run = EncryptionRun {
+            progress: ProgressEmitter::new(
+                &app,
+                Some(&owner_window),
+                PROGRESS_EVENT,
+                request_id.as_deref(),
+            ),
+            cancel_token: &cancel_token,
+            processed_bytes: 0,
+            total_bytes: fs::metadata(&path)
+                .map(|metadata| metadata.len())
+                .unwrap_or(0),
+        };
+
+        let output = if has_gpg_extension(&path) {
+            decrypt_with_gpg(&path, &passphrase)
+        } else {
+            decrypt_with_age(&mut run, &path, &passphrase)
+        }?;
+
+        run.processed_bytes = run.total_bytes;
+        run.emit_progress(&path, true);
+        Ok(normalize_path(&output.to_string_lossy()))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub fn cancel_file_encryption(request_id: String) {
+    requests::cancel(&request_id);
+}
@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Fault injection for integration tests. With the `test-fixtures` feature,
+//! a test can make a chosen operation slow or failing for paths under a
+//! prefix, a set number of times or until cleared. Without the feature
+//! `check` does nothing and compiles away.
+
+use std::io;
+use std::path::Path;
+
+/// Places in the backend where faults can be injected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultPoint {
+    /// Listing a directory in `read_dir`
+    ReadDir,
+    /// Copying a single file in the copy engine
+    CopyFile,
+    /// Handling a change event in the directory watcher
+    WatchEvent,
+    /// Visiting an entry during a global search scan
+    SearchScan,
+}
+
+#[cfg(feature = "test-fixtures")]
+pub use injection::{clear_faults, inject_fault, Fault};
+
+#[cfg(feature = "test-fixtures")]
+mod injection {
+    use super::FaultPoint;
+    use once_cell::sync::Lazy;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[derive(Debug, Clone)]
+    pub enum Fault {
+        /// Stalls the operation, like a slow network drive
+        Delay(Duration),
+        PermissionDenied,
+        Error(io::ErrorKind),
+    }
+
+    struct Rule {
+        point: FaultPoint,
+        path_prefix: PathBuf,
+        fault: Fault,
+        remaining: Option<u32>,
+    }
+
+    static RULES: Lazy<Mutex<Vec<Rule>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+    /// Applies `fault` to `point` for paths under `path_prefix`, `times`
+    /// times or, with `None`, until `clear_faults`
+    pub fn inject_fault(
+        point: FaultPoint,
+        path_prefix: impl Into<PathBuf>,
+        fault: Fault,
+        times: Option<u32>,
+    ) {
+        RULES
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .push(Rule {
+                point,
+                path_prefix: path_prefix.into(),
+                fault,
+                remaining: times,
+            });
+    }
+
+    pub fn clear_faults() {
+        RULES
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .clear();
+    }
+
+    pub(super) fn triggered(point: FaultPoint, path: &Path) -> Vec<Fault> {
+        let mut rules = RULES.lock().unwrap_or_else(|error| error.into_inner());
+        let mut faults = Vec::new();
+
+        for rule in rules.iter_mut() {
+            if rule.point != point
+                || !path.starts_with(&rule.path_prefix)
+                || rule.remaining == Some(0)
+            {
+                continue;
+            }
+
+            if let Some(remaining) = rule.remaining.as_mut() {
+                *remaining -= 1;
+            }
+
+            faults.push(rule.fault.clone());
+        }
+
+        faults
+    }
+
+    pub(super) fn apply(faults: Vec<Fault>) -> io::Result<()> {
+        for fault in faults {
+            match fault {
+                Fault::Delay(duration) => std::thread::sleep(duration),
+                Fault::PermissionDenied => {
+                    return Err(io::Error::from(io::ErrorKind::PermissionDenied))
+                }
+                Fault::Error(kind) => return Err(io::Error::from(kind)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the faults injected for an operation on `path`
+#[cfg(feature = "test-fixtures")]
+pub(crate) fn check(point: FaultPoint, path: &Path) -> io::Result<()> {
+    // Faults are applied after the lock is released, as delays would
+    // otherwise stall every other check
+    injection::apply(injection::triggered(point, path))
+}
+
+#[cfg(not(feature = "test-fixtures"))]
+#[inline(always)]
+pub(crate) fn check(_point: FaultPoint, _path: &Path) -> io::Result<()> {
+    Ok(())
+}
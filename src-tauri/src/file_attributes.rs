@@ -0,0 +1,324 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Hidden, read-only, system and immutable attributes, each kept the way
+//! its platform keeps it. Windows has `FILE_ATTRIBUTE_*` bits, macOS file
+//! flags, and Linux hides items by a leading dot and keeps the immutable
+//! flag as an inode flag, as `chattr +i` does.
+
+use crate::utils::{normalize_path, to_os_path};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Changes to make, attributes left `None` are kept as they are
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct AttributeChanges {
+    pub hidden: Option<bool>,
+    pub readonly: Option<bool>,
+    /// Windows only
+    pub system: Option<bool>,
+    /// Linux and macOS only. Immutable items can't be changed, renamed or
+    /// deleted, even by their owner.
+    pub immutable: Option<bool>,
+}
+
+fn permission_error(path: &Path, attribute: &str, error: io::Error) -> String {
+    if error.kind() == io::ErrorKind::PermissionDenied {
+        return format!(
+            "Changing the {} attribute of {} needs administrator rights",
+            attribute,
+            path.display()
+        );
+    }
+    format!("{}: {}", path.display(), error)
+}
+
+// The other bits, like directory or reparse point, are the system's
+#[cfg(windows)]
+const SETTABLE_ATTRIBUTES: u32 = {
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_ATTRIBUTE_ARCHIVE, FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_NOT_CONTENT_INDEXED,
+        FILE_ATTRIBUTE_OFFLINE, FILE_ATTRIBUTE_READONLY, FILE_ATTRIBUTE_SYSTEM,
+        FILE_ATTRIBUTE_TEMPORARY,
+    };
+    FILE_ATTRIBUTE_ARCHIVE
+        | FILE_ATTRIBUTE_HIDDEN
+        | FILE_ATTRIBUTE_NOT_CONTENT_INDEXED
+        | FILE_ATTRIBUTE_OFFLINE
+        | FILE_ATTRIBUTE_READONLY
+        | FILE_ATTRIBUTE_SYSTEM
+        | FILE_ATTRIBUTE_TEMPORARY
+};
+
+#[cfg(windows)]
+fn write_windows_attributes(path: &Path, attributes: u32) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{SetFileAttributesW, FILE_ATTRIBUTE_NORMAL};
+
+    let attributes = if attributes == 0 {
+        FILE_ATTRIBUTE_NORMAL
+    } else {
+        attributes
+    };
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    if unsafe { SetFileAttributesW(wide_path.as_ptr(), attributes) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_windows_attributes(path: &Path, changes: &AttributeChanges) -> io::Result<()> {
+    use std::os::windows::fs::MetadataExt;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_READONLY, FILE_ATTRIBUTE_SYSTEM,
+    };
+
+    let mut attributes = fs::symlink_metadata(path)?.file_attributes() & SETTABLE_ATTRIBUTES;
+    for (flag, value) in [
+        (FILE_ATTRIBUTE_HIDDEN, changes.hidden),
+        (FILE_ATTRIBUTE_READONLY, changes.readonly),
+        (FILE_ATTRIBUTE_SYSTEM, changes.system),
+    ] {
+        match value {
+            Some(true) => attributes |= flag,
+            Some(false) => attributes &= !flag,
+            None => {}
+        }
+    }
+    write_windows_attributes(path, attributes)
+}
+
+/// Sets or clears the `FS_IMMUTABLE_FL` inode flag, which needs the
+/// `CAP_LINUX_IMMUTABLE` capability
+#[cfg(target_os = "linux")]
+fn set_immutable(path: &Path, immutable: bool) -> io::Result<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    const FS_IMMUTABLE_FL: libc::c_int = 0x10;
+
+    // Read-only and non-blocking opens work for folders and FIFOs too
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)?;
+
+    let mut flags: libc::c_int = 0;
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if immutable {
+        flags |= FS_IMMUTABLE_FL;
+    } else {
+        flags &= !FS_IMMUTABLE_FL;
+    }
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_SETFLAGS, &flags) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn set_file_flag(path: &Path, flag: u32, value: bool) -> io::Result<()> {
+    use std::os::macos::fs::MetadataExt;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut flags = fs::symlink_metadata(path)?.st_flags();
+    if value {
+        flags |= flag;
+    } else {
+        flags &= !flag;
+    }
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Path contains a null byte"))?;
+    if unsafe { libc::chflags(c_path.as_ptr(), flags as _) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn set_immutable(path: &Path, immutable: bool) -> io::Result<()> {
+    set_file_flag(path, libc::UF_IMMUTABLE as u32, immutable)
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+fn set_immutable(_path: &Path, _immutable: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "The immutable attribute is not supported on this platform",
+    ))
+}
+
+#[cfg(target_os = "macos")]
+fn set_hidden(path: &Path, hidden: bool) -> Result<PathBuf, String> {
+    set_file_flag(path, libc::UF_HIDDEN as u32, hidden)
+        .map_err(|error| permission_error(path, "hidden", error))?;
+    Ok(path.to_path_buf())
+}
+
+/// Read-only clears the write bits, writable gives the owner write access
+#[cfg(unix)]
+fn set_unix_readonly(path: &Path, readonly: bool) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)?.permissions().mode();
+    let mode = if readonly {
+        mode & !0o222
+    } else {
+        mode | 0o200
+    };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+/// Hidden items are those named with a leading dot, so hiding renames
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_hidden(path: &Path, hidden: bool) -> Result<PathBuf, String> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("Cannot hide {}", path.display()))?;
+
+    if name.starts_with('.') == hidden {
+        return Ok(path.to_path_buf());
+    }
+
+    let new_name = if hidden {
+        format!(".{}", name)
+    } else {
+        name.trim_start_matches('.').to_string()
+    };
+    if new_name.is_empty() {
+        return Err(format!("Cannot unhide {}", path.display()));
+    }
+
+    let new_path = path.with_file_name(&new_name);
+    if new_path.symlink_metadata().is_ok() {
+        return Err(crate::messages::NAME_ALREADY_EXISTS
+            .with("name", &new_name)
+            .into());
+    }
+    fs::rename(path, &new_path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    crate::tags::handle_path_moved(path, &new_path);
+
+    Ok(new_path)
+}
+
+fn apply_changes(path: &Path, changes: &AttributeChanges) -> Result<PathBuf, String> {
+    #[cfg(windows)]
+    {
+        if changes.immutable.is_some() {
+            return Err("The immutable attribute isn't available on Windows".to_string());
+        }
+        set_windows_attributes(path, changes)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        Ok(path.to_path_buf())
+    }
+
+    #[cfg(unix)]
+    {
+        if changes.system.is_some() {
+            return Err("The system attribute only exists on Windows".to_string());
+        }
+
+        // Immutable items can't be changed otherwise, so that flag is
+        // cleared first and set last
+        if changes.immutable == Some(false) {
+            set_immutable(path, false)
+                .map_err(|error| permission_error(path, "immutable", error))?;
+        }
+
+        if let Some(readonly) = changes.readonly {
+            set_unix_readonly(path, readonly)
+                .map_err(|error| permission_error(path, "read-only", error))?;
+        }
+
+        let path = match changes.hidden {
+            Some(hidden) => set_hidden(path, hidden)?,
+            None => path.to_path_buf(),
+        };
+
+        if changes.immutable == Some(true) {
+            set_immutable(&path, true)
+                .map_err(|error| permission_error(&path, "immutable", error))?;
+        }
+        Ok(path)
+    }
+
+    #[cfg(not(any(windows, unix)))]
+    {
+        let _ = changes;
+        Err(format!(
+            "Changing attributes of {} is not supported on this platform",
+            path.display()
+        ))
+    }
+}
+
+/// Sets or clears the hidden, read-only, system and immutable attributes
+/// of `path`. Returns the path of the item afterwards, which changes when
+/// hiding renames it on Linux.
+#[tauri::command]
+pub async fn set_attributes(path: String, attributes: AttributeChanges) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let path = to_os_path(&path);
+        if path.symlink_metadata().is_err() {
+            return Err(crate::messages::SOURCE_NOT_FOUND
+                .with("path", path.to_string_lossy())
+                .into());
+        }
+
+        let new_path = apply_changes(&path, &attributes)?;
+        Ok(normalize_path(&new_path.to_string_lossy()))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Gives `path` the attribute bits of `reference` on Windows and its user
+/// file flags on macOS, such as hidden. Linux keeps neither.
+#[cfg(windows)]
+pub fn copy_attributes(path: &Path, reference: &fs::Metadata) -> io::Result<()> {
+    use std::os::windows::fs::MetadataExt;
+    write_windows_attributes(path, reference.file_attributes() & SETTABLE_ATTRIBUTES)
+}
+
+#[cfg(target_os = "macos")]
+pub fn copy_attributes(path: &Path, reference: &fs::Metadata) -> io::Result<()> {
+    use std::os::macos::fs::MetadataExt;
+    use std::os::unix::ffi::OsStrExt;
+
+    // The system flags above these need root
+    let flags = reference.st_flags() & libc::UF_SETTABLE as u32;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Path contains a null byte"))?;
+    if unsafe { libc::chflags(c_path.as_ptr(), flags as _) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn copy_attributes(_path: &Path, _reference: &fs::Metadata) -> io::Result<()> {
+    Ok(())
+}
+
+/// Whether `metadata` is of a read-only item, by the attribute on Windows
+/// and by the owner's write bit elsewhere
+pub fn is_readonly(metadata: &fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o200 == 0
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.permissions().readonly()
+    }
+}
@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Tiered file comparison: size, then a few sampled blocks, then the full
+//! content. Each tier exits on the first difference, so differing files are
+//! usually told apart without reading them completely. Differing text files
+//! can also be diffed.
+
+use crate::utils::normalize_path;
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const SAMPLE_BLOCK_SIZE: u64 = 64 * 1024;
+const SAMPLE_BLOCK_COUNT: u64 = 8;
+const FULL_COMPARE_BUFFER_SIZE: usize = 1024 * 1024;
+/// Larger files aren't diffed, the diff would be too long to read anyway
+const MAX_DIFF_FILE_SIZE: u64 = 2 * 1024 * 1024;
+const DIFF_CONTEXT_LINES: usize = 3;
+
+/// Offsets of the sampled blocks: the start, the end and evenly spaced
+/// blocks in between, where edits and truncated copies usually show up
+fn sample_offsets(size: u64) -> Vec<u64> {
+    if size <= SAMPLE_BLOCK_SIZE * SAMPLE_BLOCK_COUNT {
+        return vec![0];
+    }
+
+    let last_offset = size - SAMPLE_BLOCK_SIZE;
+    (0..SAMPLE_BLOCK_COUNT)
+        .map(|index| last_offset / (SAMPLE_BLOCK_COUNT - 1) * index)
+        .chain(std::iter::once(last_offset))
+        .collect()
+}
+
+// Fills the buffer unless the end of the file comes first
+fn read_full(file: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        let read_bytes = file.read(&mut buffer[filled..])?;
+        if read_bytes == 0 {
+            break;
+        }
+        filled += read_bytes;
+    }
+
+    Ok(filled)
+}
+
+fn read_block(file: &mut File, offset: u64, buffer: &mut [u8]) -> io::Result<usize> {
+    file.seek(SeekFrom::Start(offset))?;
+    read_full(file, buffer)
+}
+
+/// Whether two files have identical content. Compares sizes, then sampled
+/// blocks, then streams both files side by side, stopping at the first
+/// differing block.
+pub fn files_equal(first: &Path, second: &Path) -> io::Result<bool> {
+    let mut first_file = File::open(first)?;
+    let mut second_file = File::open(second)?;
+    let size = first_file.metadata()?.len();
+
+    if size != second_file.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let mut first_buffer = vec![0u8; FULL_COMPARE_BUFFER_SIZE];
+    let mut second_buffer = vec![0u8; FULL_COMPARE_BUFFER_SIZE];
+
+    if size > SAMPLE_BLOCK_SIZE * SAMPLE_BLOCK_COUNT {
+        let block_size = SAMPLE_BLOCK_SIZE as usize;
+
+        for offset in sample_offsets(size) {
+            let first_read = read_block(&mut first_file, offset, &mut first_buffer[..block_size])?;
+            let second_read =
+                read_block(&mut second_file, offset, &mut second_buffer[..block_size])?;
+
+            if first_buffer[..first_read] != second_buffer[..second_read] {
+                return Ok(false);
+            }
+        }
+
+        first_file.seek(SeekFrom::Start(0))?;
+        second_file.seek(SeekFrom::Start(0))?;
+    }
+
+    loop {
+        let first_read = read_full(&mut first_file, &mut first_buffer)?;
+        let second_read = read_full(&mut second_file, &mut second_buffer)?;
+
+        if first_buffer[..first_read] != second_buffer[..second_read] {
+            return Ok(false);
+        }
+
+        if first_read == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextDiffPayload {
+    /// The diff in unified format, with `DIFF_CONTEXT_LINES` lines of context
+    pub unified: String,
+    pub added_lines: usize,
+    pub removed_lines: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileComparison {
+    pub first_size: u64,
+    pub second_size: u64,
+    pub is_identical: bool,
+    /// Set for differing text files that are small enough to diff
+    pub diff: Option<TextDiffPayload>,
+}
+
+// Text is valid UTF-8 without NUL bytes, which text files never contain
+fn read_text(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn diff_text(first: &Path, second: &Path) -> Option<TextDiffPayload> {
+    let first_text = read_text(first)?;
+    let second_text = read_text(second)?;
+    let diff = TextDiff::from_lines(&first_text, &second_text);
+
+    let (added_lines, removed_lines) =
+        diff.iter_all_changes()
+            .fold((0, 0), |(added, removed), change| match change.tag() {
+                ChangeTag::Insert => (added + 1, removed),
+                ChangeTag::Delete => (added, removed + 1),
+                ChangeTag::Equal => (added, removed),
+            });
+    let unified = diff
+        .unified_diff()
+        .context_radius(DIFF_CONTEXT_LINES)
+        .header(
+            &normalize_path(&first.to_string_lossy()),
+            &normalize_path(&second.to_string_lossy()),
+        )
+        .to_string();
+
+    Some(TextDiffPayload {
+        unified,
+        added_lines,
+        removed_lines,
+    })
+}
+
+/// Whether two files are identical and, for differing text files, how they
+/// differ. Files of different sizes are told apart without being read.
+#[tauri::command]
+pub async fn compare_files(first: String, second: String) -> Result<FileComparison, String> {
+    tokio::task::spawn_blocking(move || {
+        let first = Path::new(&first);
+        let second = Path::new(&second);
+
+        for path in [first, second] {
+            if !path.is_file() {
+                return Err(format!("Path is not a file: {}", path.display()));
+            }
+        }
+
+        let is_identical = files_equal(first, second).map_err(|error| error.to_string())?;
+        let first_size = first.metadata().map_err(|error| error.to_string())?.len();
+        let second_size = second.metadata().map_err(|error| error.to_string())?.len();
+
+        let is_diffable = first_size <= MAX_DIFF_FILE_SIZE && second_size <= MAX_DIFF_FILE_SIZE;
+        let diff = if !is_identical && is_diffable {
+            diff_text(first, second)
+        } else {
+            None
+        };
+
+        Ok(FileComparison {
+            first_size,
+            second_size,
+            is_identical,
+            diff,
+        })
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
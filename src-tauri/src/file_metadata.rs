@@ -0,0 +1,332 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use crate::file_operations::FileOperationResult;
+use crate::utils::to_os_path;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, FileTimes, Metadata};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeKind {
+    Times,
+    Permissions,
+    /// Unix owner and group. Giving an item to another user needs root.
+    Ownership,
+    /// Extended attributes, which hold the resource forks and Finder info
+    /// on macOS and are alternate data streams on Windows
+    Xattrs,
+    /// Windows attribute bits and macOS file flags, such as hidden
+    Attributes,
+}
+
+impl AttributeKind {
+    fn label(self) -> &'static str {
+        match self {
+            AttributeKind::Times => "times",
+            AttributeKind::Permissions => "permissions",
+            AttributeKind::Ownership => "ownership",
+            AttributeKind::Xattrs => "extended attributes",
+            AttributeKind::Attributes => "attributes",
+        }
+    }
+}
+
+// Directories can only be opened for attribute changes with backup semantics
+#[cfg(windows)]
+fn open_for_attributes(path: &Path) -> std::io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_WRITE_ATTRIBUTES: u32 = 0x0100;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+
+    fs::OpenOptions::new()
+        .access_mode(FILE_WRITE_ATTRIBUTES)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)
+}
+
+#[cfg(not(windows))]
+fn open_for_attributes(path: &Path) -> std::io::Result<File> {
+    File::open(path)
+}
+
+/// Times to set, in milliseconds since the Unix epoch. Times left `None`
+/// are kept, or with `reference` taken from that item.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TimesToSet {
+    pub modified: Option<u64>,
+    pub accessed: Option<u64>,
+    /// Only settable on Windows and macOS
+    pub created: Option<u64>,
+    pub reference: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Times {
+    accessed: Option<SystemTime>,
+    modified: Option<SystemTime>,
+    created: Option<SystemTime>,
+}
+
+impl Times {
+    fn of(metadata: &Metadata) -> Self {
+        Self {
+            accessed: metadata.accessed().ok(),
+            modified: metadata.modified().ok(),
+            created: metadata.created().ok(),
+        }
+    }
+
+    fn apply(&self, path: &Path) -> std::io::Result<()> {
+        let mut times = FileTimes::new();
+
+        if let Some(accessed) = self.accessed {
+            times = times.set_accessed(accessed);
+        }
+
+        if let Some(modified) = self.modified {
+            times = times.set_modified(modified);
+        }
+
+        #[cfg(windows)]
+        if let Some(created) = self.created {
+            use std::os::windows::fs::FileTimesExt;
+            times = times.set_created(created);
+        }
+
+        #[cfg(target_os = "macos")]
+        if let Some(created) = self.created {
+            use std::os::macos::fs::FileTimesExt;
+            times = times.set_created(created);
+        }
+
+        open_for_attributes(path)?.set_times(times)
+    }
+}
+
+/// Applies access, modification and (where supported) creation times
+pub(crate) fn apply_times(path: &Path, reference: &Metadata) -> std::io::Result<()> {
+    Times::of(reference).apply(path)
+}
+
+#[cfg(unix)]
+fn apply_ownership(path: &Path, reference: &Metadata) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let result = std::os::unix::fs::chown(path, Some(reference.uid()), Some(reference.gid()));
+    // Without root the group can still be kept when the user is in it
+    if result.is_err() {
+        let _ = std::os::unix::fs::chown(path, None, Some(reference.gid()));
+    }
+    result
+}
+
+#[cfg(not(unix))]
+fn apply_ownership(_path: &Path, _reference: &Metadata) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Ownership can only be copied on Linux and macOS",
+    ))
+}
+
+/// Applies each of the `which` attributes of `reference` onto `target`,
+/// going on past the ones that fail, which are returned with their error
+pub(crate) fn preserve_attributes(
+    reference: &Path,
+    reference_metadata: &Metadata,
+    target: &Path,
+    which: &[AttributeKind],
+) -> Vec<(AttributeKind, std::io::Error)> {
+    // Extended attributes first and permissions and flags last, a read-only
+    // mode or an immutable flag would otherwise block the other writes.
+    // Changing the owner clears setuid bits, so it comes before the mode.
+    let order = [
+        AttributeKind::Xattrs,
+        AttributeKind::Ownership,
+        AttributeKind::Times,
+        AttributeKind::Permissions,
+        AttributeKind::Attributes,
+    ];
+
+    let mut failures = Vec::new();
+    for kind in order.into_iter().filter(|kind| which.contains(kind)) {
+        let result = match kind {
+            AttributeKind::Xattrs => crate::xattrs::copy_all(reference, target).map(|_| ()),
+            AttributeKind::Ownership => apply_ownership(target, reference_metadata),
+            AttributeKind::Times => apply_times(target, reference_metadata),
+            AttributeKind::Permissions => {
+                fs::set_permissions(target, reference_metadata.permissions())
+            }
+            AttributeKind::Attributes => {
+                crate::file_attributes::copy_attributes(target, reference_metadata)
+            }
+        };
+        if let Err(error) = result {
+            failures.push((kind, error));
+        }
+    }
+    failures
+}
+
+fn apply_attributes(
+    reference: &Path,
+    reference_metadata: &Metadata,
+    target: &Path,
+    which: &[AttributeKind],
+) -> Result<(), String> {
+    match preserve_attributes(reference, reference_metadata, target, which)
+        .into_iter()
+        .next()
+    {
+        Some((kind, error)) => Err(format!("Failed to copy {}: {}", kind.label(), error)),
+        None => Ok(()),
+    }
+}
+
+/// Applies timestamps, permissions, ownership, extended attributes and/or
+/// attributes of `reference` onto each of `targets`
+#[tauri::command]
+pub fn copy_attributes(
+    reference: String,
+    targets: Vec<String>,
+    which: Vec<AttributeKind>,
+) -> FileOperationResult {
+    let reference_path = to_os_path(&reference);
+
+    let reference_metadata = match fs::metadata(&reference_path) {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            return FileOperationResult {
+                success: false,
+                error: Some(format!(
+                    "Failed to read reference '{}': {}",
+                    reference, error
+                )),
+                copied_count: Some(0),
+                failed_count: Some(targets.len() as u32),
+                skipped_count: Some(0),
+                ..Default::default()
+            };
+        }
+    };
+
+    let mut applied_count: u32 = 0;
+    let mut failed_count: u32 = 0;
+    let mut skipped_count: u32 = 0;
+    let mut last_error: Option<String> = None;
+
+    for target in &targets {
+        let target_path = to_os_path(target);
+
+        if target_path == reference_path {
+            skipped_count += 1;
+            continue;
+        }
+
+        if !target_path.exists() {
+            failed_count += 1;
+            last_error = Some(format!("Path does not exist: {}", target));
+            continue;
+        }
+
+        match apply_attributes(&reference_path, &reference_metadata, &target_path, &which) {
+            Ok(()) => applied_count += 1,
+            Err(error) => {
+                failed_count += 1;
+                last_error = Some(format!("{}: {}", target, error));
+            }
+        }
+    }
+
+    FileOperationResult {
+        error: last_error,
+        ..FileOperationResult::counts(applied_count, failed_count, skipped_count)
+    }
+}
+
+fn resolve_times(times: &TimesToSet) -> Result<Times, String> {
+    let mut resolved = match &times.reference {
+        Some(reference) => {
+            let metadata = fs::metadata(to_os_path(reference))
+                .map_err(|error| format!("Failed to read reference '{}': {}", reference, error))?;
+            Times::of(&metadata)
+        }
+        None => Times::default(),
+    };
+
+    let from_millis = |millis: u64| UNIX_EPOCH + Duration::from_millis(millis);
+    if let Some(accessed) = times.accessed {
+        resolved.accessed = Some(from_millis(accessed));
+    }
+    if let Some(modified) = times.modified {
+        resolved.modified = Some(from_millis(modified));
+    }
+    if let Some(created) = times.created {
+        if cfg!(not(any(windows, target_os = "macos"))) {
+            return Err("Creation times can't be set on this platform".to_string());
+        }
+        resolved.created = Some(from_millis(created));
+    }
+
+    Ok(resolved)
+}
+
+/// Sets the modification, access and creation times of `paths`, and with
+/// `recursive` of everything inside them, such as to restore times a
+/// transfer lost. Symlinks inside folders are skipped, setting their times
+/// would change their targets.
+#[tauri::command]
+pub async fn set_file_times(
+    paths: Vec<String>,
+    times: TimesToSet,
+    recursive: bool,
+) -> Result<FileOperationResult, String> {
+    let resolved = resolve_times(&times)?;
+
+    tokio::task::spawn_blocking(move || {
+        let mut applied_count: u32 = 0;
+        let mut failed_count: u32 = 0;
+        let mut last_error: Option<String> = None;
+        let max_depth = if recursive { usize::MAX } else { 0 };
+
+        for path in &paths {
+            for entry in walkdir::WalkDir::new(to_os_path(path))
+                .follow_links(false)
+                .max_depth(max_depth)
+            {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(error) => {
+                        failed_count += 1;
+                        last_error = Some(error.to_string());
+                        continue;
+                    }
+                };
+
+                if entry.depth() > 0 && entry.path_is_symlink() {
+                    continue;
+                }
+
+                match resolved.apply(entry.path()) {
+                    Ok(()) => applied_count += 1,
+                    Err(error) => {
+                        failed_count += 1;
+                        last_error = Some(format!("{}: {}", entry.path().display(), error));
+                    }
+                }
+            }
+        }
+
+        FileOperationResult {
+            error: last_error,
+            ..FileOperationResult::counts(applied_count, failed_count, 0)
+        }
+    })
+    .await
+    .map_err(|error| error.to_string())
+}
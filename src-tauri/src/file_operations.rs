@@ -1,599 +0,0 @@
-// SPDX-License-Identifier: GPL-3.0-or-later
-// License: GNU GPLv3 or later. See the license file in the project root for more information.
-// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
-
-use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::Path;
-use crate::utils::normalize_path;
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct FileOperationResult {
-    pub success: bool,
-    pub error: Option<String>,
-    pub copied_count: Option<u32>,
-    pub failed_count: Option<u32>,
-    pub skipped_count: Option<u32>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ConflictItem {
-    pub source_path: String,
-    pub source_name: String,
-    pub source_is_dir: bool,
-    pub source_size: Option<u64>,
-    pub destination_path: String,
-    pub destination_is_dir: bool,
-    pub destination_size: Option<u64>,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum ConflictResolution {
-    Replace,
-    Skip,
-    AutoRename,
-}
-
-impl ConflictResolution {
-    fn from_str(value: &str) -> Self {
-        match value {
-            "replace" => ConflictResolution::Replace,
-            "skip" => ConflictResolution::Skip,
-            "auto-rename" => ConflictResolution::AutoRename,
-            _ => ConflictResolution::AutoRename,
-        }
-    }
-}
-
-fn copy_dir_recursive(source: &Path, destination: &Path) -> Result<(), String> {
-    if !destination.exists() {
-        fs::create_dir_all(destination).map_err(|error| error.to_string())?;
-    }
-
-    for entry in fs::read_dir(source).map_err(|error| error.to_string())? {
-        let entry = entry.map_err(|error| error.to_string())?;
-        let source_path = entry.path();
-        let file_name = source_path.file_name().ok_or("Invalid file name")?;
-        let dest_path = destination.join(file_name);
-
-        if source_path.is_dir() {
-            copy_dir_recursive(&source_path, &dest_path)?;
-        } else {
-            fs::copy(&source_path, &dest_path).map_err(|error| error.to_string())?;
-        }
-    }
-
-    Ok(())
-}
-
-fn get_unique_destination_path(destination: &Path, name: &str) -> std::path::PathBuf {
-    let mut dest_path = destination.join(name);
-    let mut counter = 1;
-
-    while dest_path.exists() {
-        let path = Path::new(name);
-        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or(name);
-        let extension = path.extension().and_then(|ext| ext.to_str());
-
-        let new_name = if let Some(ext) = extension {
-            format!("{} ({}).{}", stem, counter, ext)
-        } else {
-            format!("{} ({})", stem, counter)
-        };
-
-        dest_path = destination.join(&new_name);
-        counter += 1;
-    }
-
-    dest_path
-}
-
-#[tauri::command]
-pub fn check_conflicts(source_paths: Vec<String>, destination_path: String) -> Vec<ConflictItem> {
-    let destination = Path::new(&destination_path);
-    let mut conflicts = Vec::new();
-
-    if !destination.exists() || !destination.is_dir() {
-        return conflicts;
-    }
-
-    for source_path_str in &source_paths {
-        let source = Path::new(source_path_str);
-
-        if !source.exists() {
-            continue;
-        }
-
-        let source_parent = source.parent().map(|parent| normalize_path(&parent.to_string_lossy()));
-        let dest_normalized = normalize_path(&destination.to_string_lossy());
-        let is_same_directory = source_parent
-            .map(|parent| parent == dest_normalized)
-            .unwrap_or(false);
-
-        if is_same_directory {
-            continue;
-        }
-
-        let file_name = match source.file_name() {
-            Some(name) => name.to_string_lossy().to_string(),
-            None => continue,
-        };
-
-        let dest_item_path = destination.join(&file_name);
-
-        if dest_item_path.exists() {
-            let source_size = if source.is_file() {
-                fs::metadata(source).ok().map(|metadata| metadata.len())
-            } else {
-                None
-            };
-
-            let destination_size = if dest_item_path.is_file() {
-                fs::metadata(&dest_item_path).ok().map(|metadata| metadata.len())
-            } else {
-                None
-            };
-
-            conflicts.push(ConflictItem {
-                source_path: source_path_str.clone(),
-                source_name: file_name,
-                source_is_dir: source.is_dir(),
-                source_size,
-                destination_path: dest_item_path.to_string_lossy().to_string(),
-                destination_is_dir: dest_item_path.is_dir(),
-                destination_size,
-            });
-        }
-    }
-
-    conflicts
-}
-
-fn remove_dir_or_file(path: &Path) -> Result<(), String> {
-    if path.is_dir() {
-        fs::remove_dir_all(path).map_err(|error| error.to_string())
-    } else {
-        fs::remove_file(path).map_err(|error| error.to_string())
-    }
-}
-
-#[tauri::command]
-pub fn copy_items(source_paths: Vec<String>, destination_path: String, conflict_resolution: Option<String>) -> FileOperationResult {
-    let destination = Path::new(&destination_path);
-    let resolution = conflict_resolution
-        .map(|value| ConflictResolution::from_str(&value))
-        .unwrap_or(ConflictResolution::AutoRename);
-
-    if !destination.exists() {
-        return FileOperationResult {
-            success: false,
-            error: Some(format!("Destination path does not exist: {}", destination_path)),
-            copied_count: None,
-            failed_count: None,
-            skipped_count: None,
-        };
-    }
-
-    if !destination.is_dir() {
-        return FileOperationResult {
-            success: false,
-            error: Some(format!("Destination is not a directory: {}", destination_path)),
-            copied_count: None,
-            failed_count: None,
-            skipped_count: None,
-        };
-    }
-
-    let mut copied_count: u32 = 0;
-    let mut failed_count: u32 = 0;
-    let mut skipped_count: u32 = 0;
-    let mut last_error: Option<String> = None;
-
-    for source_path_str in &source_paths {
-        let source = Path::new(source_path_str);
-
-        if !source.exists() {
-            failed_count += 1;
-            last_error = Some(format!("Source path does not exist: {}", source_path_str));
-            continue;
-        }
-
-        let source_parent = source.parent().map(|parent| normalize_path(&parent.to_string_lossy()));
-        let dest_normalized = normalize_path(&destination.to_string_lossy());
-        let is_same_directory = source_parent
-            .map(|parent| parent == dest_normalized)
-            .unwrap_or(false);
-
-        let file_name = match source.file_name() {
-            Some(name) => name.to_string_lossy().to_string(),
-            None => {
-                failed_count += 1;
-                last_error = Some(format!("Invalid source path: {}", source_path_str));
-                continue;
-            }
-        };
-
-        let dest_path = if is_same_directory {
-            get_unique_destination_path(destination, &file_name)
-        } else {
-            let initial_dest = destination.join(&file_name);
-            if initial_dest.exists() {
-                match resolution {
-                    ConflictResolution::Skip => {
-                        skipped_count += 1;
-                        continue;
-                    }
-                    ConflictResolution::Replace => {
-                        if let Err(error) = remove_dir_or_file(&initial_dest) {
-                            failed_count += 1;
-                            last_error = Some(error);
-                            continue;
-                        }
-                        initial_dest
-                    }
-                    ConflictResolution::AutoRename => {
-                        get_unique_destination_path(destination, &file_name)
-                    }
-                }
-            } else {
-                initial_dest
-            }
-        };
-
-        let result = if source.is_dir() {
-            copy_dir_recursive(source, &dest_path)
-        } else {
-            fs::copy(source, &dest_path)
-                .map(|_| ())
-                .map_err(|error| error.to_string())
-        };
-
-        match result {
-            Ok(()) => copied_count += 1,
-            Err(error) => {
-                failed_count += 1;
-                last_error = Some(error);
-            }
-        }
-    }
-
-    FileOperationResult {
-        success: failed_count == 0,
-        error: last_error,
-        copied_count: Some(copied_count),
-        failed_count: Some(failed_count),
-        skipped_count: Some(skipped_count),
-    }
-}
-
-#[tauri::command]
-pub fn move_items(source_paths: Vec<String>, destination_path: String, conflict_resolution: Option<String>) -> FileOperationResult {
-    let destination = Path::new(&destination_path);
-    let resolution = conflict_resolution
-        .map(|value| ConflictResolution::from_str(&value))
-        .unwrap_or(ConflictResolution::Skip);
-
-    if !destination.exists() {
-        return FileOperationResult {
-            success: false,
-            error: Some(format!("Destination path does not exist: {}", destination_path)),
-            copied_count: None,
-            failed_count: None,
-            skipped_count: None,
-        };
-    }
-
-    if !destination.is_dir() {
-        return FileOperationResult {
-            success: false,
-            error: Some(format!("Destination is not a directory: {}", destination_path)),
-            copied_count: None,
-            failed_count: None,
-            skipped_count: None,
-        };
-    }
-
-    let mut moved_count: u32 = 0;
-    let mut failed_count: u32 = 0;
-    let mut skipped_count: u32 = 0;
-    let mut last_error: Option<String> = None;
-
-    for source_path_str in &source_paths {
-        let source = Path::new(source_path_str);
-
-        if !source.exists() {
-            failed_count += 1;
-            last_error = Some(format!("Source path does not exist: {}", source_path_str));
-            continue;
-        }
-
-        let source_parent = source.parent().map(|parent| normalize_path(&parent.to_string_lossy()));
-        let dest_normalized = normalize_path(&destination.to_string_lossy());
-        let is_same_directory = source_parent
-            .map(|parent| parent == dest_normalized)
-            .unwrap_or(false);
-
-        if is_same_directory {
-            continue;
-        }
-
-        let file_name = match source.file_name() {
-            Some(name) => name.to_string_lossy().to_string(),
-            None => {
-                failed_count += 1;
-                last_error = Some(format!("Invalid source path: {}", source_path_str));
-                continue;
-            }
-        };
-
-        let dest_path = destination.join(&file_name);
-
-        let final_dest_path = if dest_path.exists() {
-            match resolution {
-                ConflictResolution::Skip => {
-                    skipped_count += 1;
-                    continue;
-                }
-                ConflictResolution::Replace => {
-                    if let Err(error) = remove_dir_or_file(&dest_path) {
-                        failed_count += 1;
-                        last_error = Some(error);
-                        continue;
-                    }
-                    dest_path
-                }
-                ConflictResolution::AutoRename => {
-                    get_unique_destination_path(destination, &file_name)
-                }
-            }
-        } else {
-            dest_path
-        };
-
-        let result = fs::rename(source, &final_dest_path);
-
-        match result {
-            Ok(()) => moved_count += 1,
-            Err(error) => {
-                if error.raw_os_error() == Some(17) || error.raw_os_error() == Some(18) {
-                    let copy_result = if source.is_dir() {
-                        copy_dir_recursive(source, &final_dest_path)
-                    } else {
-                        fs::copy(source, &final_dest_path)
-                            .map(|_| ())
-                            .map_err(|copy_error| copy_error.to_string())
-                    };
-
-                    match copy_result {
-                        Ok(()) => {
-                            let _ = remove_dir_or_file(source);
-                            moved_count += 1;
-                        }
-                        Err(copy_error) => {
-                            failed_count += 1;
-                            last_error = Some(copy_error);
-                        }
-                    }
-                } else {
-                    failed_count += 1;
-                    last_error = Some(error.to_string());
-                }
-            }
-        }
-    }
-
-    FileOperationResult {
-        success: failed_count == 0,
-        error: last_error,
-        copied_count: Some(moved_count),
-        failed_count: Some(failed_count),
-        skipped_count: Some(skipped_count),
-    }
-}
-
-#[tauri::command]
-pub fn rename_item(source_path: String, new_name: String) -> FileOperationResult {
-    let source = Path::new(&source_path);
-
-    if !source.exists() {
-        return FileOperationResult {
-            success: false,
-            error: Some(format!("Source path does not exist: {}", source_path)),
-            copied_count: None,
-            failed_count: None,
-            skipped_count: None,
-        };
-    }
-
-    let parent = match source.parent() {
-        Some(parent) => parent,
-        None => {
-            return FileOperationResult {
-                success: false,
-                error: Some("Cannot determine parent directory".to_string()),
-                copied_count: None,
-                failed_count: None,
-                skipped_count: None,
-            };
-        }
-    };
-
-    let dest_path = parent.join(&new_name);
-
-    if dest_path.exists() {
-        return FileOperationResult {
-            success: false,
-            error: Some(format!("A file or folder with the name '{}' already exists", new_name)),
-            copied_count: None,
-            failed_count: None,
-            skipped_count: None,
-        };
-    }
-
-    match fs::rename(source, &dest_path) {
-        Ok(()) => FileOperationResult {
-            success: true,
-            error: None,
-            copied_count: Some(1),
-            failed_count: Some(0),
-            skipped_count: Some(0),
-        },
-        Err(error) => FileOperationResult {
-            success: false,
-            error: Some(error.to_string()),
-            copied_count: None,
-            failed_count: Some(1),
-            skipped_count: None,
-        },
-    }
-}
-
-#[tauri::command]
-pub fn delete_items(paths: Vec<String>, use_trash: bool) -> FileOperationResult {
-    let mut deleted_count: u32 = 0;
-    let mut failed_count: u32 = 0;
-    let mut last_error: Option<String> = None;
-
-    for path_str in &paths {
-        let path = Path::new(path_str);
-
-        if !path.exists() {
-            failed_count += 1;
-            last_error = Some(format!("Path does not exist: {}", path_str));
-            continue;
-        }
-
-        let result = if use_trash {
-            trash::delete(path).map_err(|error| error.to_string())
-        } else if path.is_dir() {
-            fs::remove_dir_all(path).map_err(|error| error.to_string())
-        } else {
-            fs::remove_file(path).map_err(|error| error.to_string())
-        };
-
-        match result {
-            Ok(()) => deleted_count += 1,
-            Err(error) => {
-                failed_count += 1;
-                last_error = Some(error);
-            }
-        }
-    }
-
-    FileOperationResult {
-        success: failed_count == 0,
-        error: last_error,
-        copied_count: Some(deleted_count),
-        failed_count: Some(failed_count),
-        skipped_count: Some(0),
-    }
-}
-
-#[tauri::command]
-pub fn ensure_directory(directory_path: String) -> FileOperationResult {
-    let directory = Path::new(&directory_path);
-
-    match fs::create_dir_all(directory) {
-        Ok(()) => FileOperationResult {
-            success: true,
-            error: None,
-            copied_count: Some(1),
-            failed_count: Some(0),
-            skipped_count: Some(0),
-        },
-        Err(error) => FileOperationResult {
-            success: false,
-            error: Some(error.to_string()),
-            copied_count: None,
-            failed_count: Some(1),
-            skipped_count: None,
-        },
-    }
-}
-
-#[tauri::command]
-pub fn create_item(directory_path: String, name: String, is_directory: bool) -> FileOperationResult {
-    let trimmed_name = name.trim();
-
-    if trimmed_name.is_empty() {
-        return FileOperationResult {
-            success: false,
-            error: Some("Name cannot be empty".to_string()),
-            copied_count: None,
-            failed_count: None,
-            skipped_count: None,
-        };
-    }
-
-    if trimmed_name.contains('/') || trimmed_name.contains('\\') {
-        return FileOperationResult {
-            success: false,
-            error: Some("Name contains invalid path separators".to_string()),
-            copied_count: None,
-            failed_count: None,
-            skipped_count: None,
-        };
-    }
-
-    let directory = Path::new(&directory_path);
-
-    if !directory.exists() {
-        return FileOperationResult {
-            success: false,
-            error: Some(format!("Directory does not exist: {}", directory_path)),
-            copied_count: None,
-            failed_count: None,
-            skipped_count: None,
-        };
-    }
-
-    if !directory.is_dir() {
-        return FileOperationResult {
-            success: false,
-            error: Some(format!("Path is not a directory: {}", directory_path)),
-            copied_count: None,
-            failed_count: None,
-            skipped_count: None,
-        };
-    }
-
-    let dest_path = directory.join(trimmed_name);
-
-    if dest_path.exists() {
-        return FileOperationResult {
-            success: false,
-            error: Some(format!("Path already exists: {}", dest_path.display())),
-            copied_count: None,
-            failed_count: None,
-            skipped_count: None,
-        };
-    }
-
-    let result = if is_directory {
-        fs::create_dir(&dest_path).map_err(|error| error.to_string())
-    } else {
-        fs::OpenOptions::new()
-            .write(true)
-            .create_new(true)
-            .open(&dest_path)
-            .map(|_| ())
-            .map_err(|error| error.to_string())
-    };
-
-    match result {
-        Ok(()) => FileOperationResult {
-            success: true,
-            error: None,
-            copied_count: Some(1),
-            failed_count: Some(0),
-            skipped_count: Some(0),
-        },
-        Err(error) => FileOperationResult {
-            success: false,
-            error: Some(error),
-            copied_count: None,
-            failed_count: Some(1),
-            skipped_count: None,
-        },
-    }
-}
@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Copy-on-write clones. A clone shares the blocks of its source until
+//! either of them changes, so it is instant and takes no extra space. Only
+//! possible within one volume of a file system that supports it: Btrfs and
+//! XFS through `FICLONE`, APFS through `clonefile` and ReFS through
+//! `FSCTL_DUPLICATE_EXTENTS_TO_FILE`.
+
+use std::io;
+use std::path::Path;
+
+#[cfg(not(target_os = "macos"))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Cloning is not supported for this copy",
+    )
+}
+
+/// Whether the folder `path` is on a file system with `FICLONE`. XFS
+/// volumes made without reflink support still fail the clone itself.
+#[cfg(target_os = "linux")]
+fn supports_clones(path: &Path) -> io::Result<bool> {
+    use std::os::unix::ffi::OsStrExt;
+
+    const BTRFS_SUPER_MAGIC: i64 = 0x9123_683e;
+    const XFS_SUPER_MAGIC: i64 = 0x5846_5342;
+    const BCACHEFS_SUPER_MAGIC: i64 = 0xca45_1a4e;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Path contains a null byte"))?;
+    let mut stats: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stats) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(matches!(
+        stats.f_type as i64,
+        BTRFS_SUPER_MAGIC | XFS_SUPER_MAGIC | BCACHEFS_SUPER_MAGIC
+    ))
+}
+
+/// Clones `source` to `destination`, which must not exist yet. Fails
+/// without leaving a destination behind where a clone isn't possible, for
+/// the caller to copy normally instead.
+#[cfg(target_os = "linux")]
+pub fn clone_file(source: &Path, destination: &Path) -> io::Result<()> {
+    use std::fs::{self, File, OpenOptions};
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+
+    let parent = destination.parent().ok_or_else(unsupported)?;
+    let source_metadata = fs::metadata(source)?;
+    if source_metadata.dev() != fs::metadata(parent)?.dev() || !supports_clones(parent)? {
+        return Err(unsupported());
+    }
+
+    let source_file = File::open(source)?;
+    let destination_file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(destination)?;
+
+    let result = if unsafe {
+        libc::ioctl(
+            destination_file.as_raw_fd(),
+            libc::FICLONE,
+            source_file.as_raw_fd(),
+        )
+    } != 0
+    {
+        Err(io::Error::last_os_error())
+    } else {
+        destination_file.set_permissions(source_metadata.permissions())
+    };
+
+    if result.is_err() {
+        drop(destination_file);
+        let _ = fs::remove_file(destination);
+    }
+    result
+}
+
+/// `clonefile` also copies the permissions, times and extended attributes
+#[cfg(target_os = "macos")]
+pub fn clone_file(source: &Path, destination: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let to_c_path = |path: &Path| {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Path contains a null byte"))
+    };
+    let c_source = to_c_path(source)?;
+    let c_destination = to_c_path(destination)?;
+
+    if unsafe { libc::clonefile(c_source.as_ptr(), c_destination.as_ptr(), 0) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn to_wide(path: &Path) -> Vec<u16> {
+    use std::os::windows::ffi::OsStrExt;
+    path.as_os_str().encode_wide().chain(Some(0)).collect()
+}
+
+/// The root of the volume `path` is on, such as `C:\`
+#[cfg(windows)]
+fn volume_root(path: &Path) -> io::Result<Vec<u16>> {
+    use windows_sys::Win32::Storage::FileSystem::GetVolumePathNameW;
+
+    let mut root = vec![0u16; 1024];
+    if unsafe { GetVolumePathNameW(to_wide(path).as_ptr(), root.as_mut_ptr(), root.len() as u32) }
+        == 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    let length = root
+        .iter()
+        .position(|unit| *unit == 0)
+        .unwrap_or(root.len());
+    root.truncate(length + 1);
+    Ok(root)
+}
+
+/// Whether the volume at `root` is ReFS, the Windows file system with
+/// block cloning, and its cluster size
+#[cfg(windows)]
+fn refs_cluster_size(root: &[u16]) -> io::Result<Option<u64>> {
+    use windows_sys::Win32::Storage::FileSystem::{GetDiskFreeSpaceW, GetVolumeInformationW};
+
+    let mut file_system_name = [0u16; 32];
+    if unsafe {
+        GetVolumeInformationW(
+            root.as_ptr(),
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            file_system_name.as_mut_ptr(),
+            file_system_name.len() as u32,
+        )
+    } == 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    let name_length = file_system_name
+        .iter()
+        .position(|unit| *unit == 0)
+        .unwrap_or(file_system_name.len());
+    if String::from_utf16_lossy(&file_system_name[..name_length]) != "ReFS" {
+        return Ok(None);
+    }
+
+    let mut sectors_per_cluster = 0u32;
+    let mut bytes_per_sector = 0u32;
+    let mut free_clusters = 0u32;
+    let mut total_clusters = 0u32;
+    if unsafe {
+        GetDiskFreeSpaceW(
+            root.as_ptr(),
+            &mut sectors_per_cluster,
+            &mut bytes_per_sector,
+            &mut free_clusters,
+            &mut total_clusters,
+        )
+    } == 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(sectors_per_cluster as u64 * bytes_per_sector as u64))
+}
+
+#[cfg(windows)]
+fn file_control(file: &std::fs::File, code: u32, input: &[u8]) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    let mut returned = 0u32;
+    let succeeded = unsafe {
+        DeviceIoControl(
+            file.as_raw_handle() as _,
+            code,
+            input.as_ptr() as _,
+            input.len() as u32,
+            std::ptr::null_mut(),
+            0,
+            &mut returned,
+            std::ptr::null_mut(),
+        )
+    };
+
+    if succeeded == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Clones the extents of `source` into a new file of the same size. Each
+/// call takes a whole number of clusters, the last one may reach past the
+/// end of the file.
+#[cfg(windows)]
+fn duplicate_extents(
+    source: &std::fs::File,
+    destination: &std::fs::File,
+    length: u64,
+    cluster_size: u64,
+) -> io::Result<()> {
+    use std::os::windows::fs::MetadataExt;
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::FILE_ATTRIBUTE_SPARSE_FILE;
+    use windows_sys::Win32::System::Ioctl::{
+        DUPLICATE_EXTENTS_DATA, FSCTL_DUPLICATE_EXTENTS_TO_FILE, FSCTL_SET_SPARSE,
+    };
+
+    /// Cloned per call, a multiple of every cluster size
+    const CHUNK_SIZE: u64 = 1024 * 1024 * 1024;
+
+    // Sparse sources can only be cloned into sparse files
+    if source.metadata()?.file_attributes() & FILE_ATTRIBUTE_SPARSE_FILE != 0 {
+        file_control(destination, FSCTL_SET_SPARSE, &[])?;
+    }
+    destination.set_len(length)?;
+
+    let mut offset = 0u64;
+    while offset < length {
+        let byte_count = (length - offset).min(CHUNK_SIZE);
+        let data = DUPLICATE_EXTENTS_DATA {
+            FileHandle: source.as_raw_handle() as _,
+            SourceFileOffset: offset as i64,
+            TargetFileOffset: offset as i64,
+            ByteCount: byte_count
+                .div_ceil(cluster_size)
+                .saturating_mul(cluster_size) as i64,
+        };
+        let input = unsafe {
+            std::slice::from_raw_parts(
+                &data as *const DUPLICATE_EXTENTS_DATA as *const u8,
+                std::mem::size_of::<DUPLICATE_EXTENTS_DATA>(),
+            )
+        };
+        file_control(destination, FSCTL_DUPLICATE_EXTENTS_TO_FILE, input)?;
+        offset += byte_count;
+    }
+
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn clone_file(source: &Path, destination: &Path) -> io::Result<()> {
+    use std::fs::{self, File, OpenOptions};
+
+    let parent = destination.parent().ok_or_else(unsupported)?;
+    let root = volume_root(parent)?;
+    if volume_root(source)? != root {
+        return Err(unsupported());
+    }
+    let cluster_size = refs_cluster_size(&root)?.ok_or_else(unsupported)?;
+
+    let source_file = File::open(source)?;
+    let source_metadata = source_file.metadata()?;
+    let destination_file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(destination)?;
+
+    let result = duplicate_extents(
+        &source_file,
+        &destination_file,
+        source_metadata.len(),
+        cluster_size,
+    )
+    .and_then(|()| destination_file.set_permissions(source_metadata.permissions()));
+
+    if result.is_err() {
+        drop(destination_file);
+        let _ = fs::remove_file(destination);
+    }
+    result
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn clone_file(_source: &Path, _destination: &Path) -> io::Result<()> {
+    Err(unsupported())
+}
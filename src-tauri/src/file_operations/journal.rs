@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Journal of moves across volumes, which copy an item and then delete the
+//! source. Each item is recorded before its copy starts and dropped once
+//! the source is gone, so entries still present after a crash are moves
+//! that were cut short. Source files are never modified by a move, so a
+//! file still in the source is always the intact original.
+
+use super::{remove_dir_or_file, verify};
+use crate::json_store;
+use crate::utils::normalize_path;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const STORE_NAME: &str = "transfer-journal";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    id: String,
+    source_path: String,
+    destination_path: String,
+    started_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TransferJournal {
+    #[serde(default)]
+    moves: Vec<JournalEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryFileState {
+    /// Not copied yet
+    SourceOnly,
+    /// Copied, and the source was already deleted
+    DestinationOnly,
+    Identical,
+    /// The copy was cut off partway
+    Different,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryFile {
+    /// Path below the moved item, empty when the item is a single file
+    pub relative_path: String,
+    pub state: RecoveryFileState,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InterruptedMove {
+    pub id: String,
+    pub source_path: String,
+    pub destination_path: String,
+    pub started_at: u64,
+    pub files: Vec<RecoveryFile>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryAction {
+    /// Copies what is missing or damaged, then deletes the source
+    Complete,
+    /// Brings files whose source was already deleted back, then deletes
+    /// the copy
+    RollBack,
+    /// Forgets the move and leaves the files as they are
+    Dismiss,
+}
+
+static JOURNAL: Lazy<Mutex<TransferJournal>> =
+    Lazy::new(|| Mutex::new(json_store::load(STORE_NAME)));
+
+/// Moves running in this session, which aren't interrupted
+static ACTIVE_MOVES: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Records a move before its copy starts and returns its id. Replaces
+/// entries of earlier, interrupted attempts at the same move.
+pub fn begin_move(source: &Path, destination: &Path) -> String {
+    let id = uuid::Uuid::new_v4().to_string();
+    let source_path = normalize_path(&source.to_string_lossy());
+    let destination_path = normalize_path(&destination.to_string_lossy());
+
+    if let Ok(mut active_moves) = ACTIVE_MOVES.lock() {
+        active_moves.insert(id.clone());
+    }
+
+    if let Ok(mut journal) = JOURNAL.lock() {
+        journal.moves.retain(|entry| {
+            entry.source_path != source_path || entry.destination_path != destination_path
+        });
+        journal.moves.push(JournalEntry {
+            id: id.clone(),
+            source_path,
+            destination_path,
+            started_at: now_ms(),
+        });
+        let _ = json_store::save(STORE_NAME, &*journal);
+    }
+
+    id
+}
+
+/// Drops a move from the journal once nothing is left to recover
+pub fn end_move(id: &str) {
+    if let Ok(mut journal) = JOURNAL.lock() {
+        journal.moves.retain(|entry| entry.id != id);
+        let _ = json_store::save(STORE_NAME, &*journal);
+    }
+
+    if let Ok(mut active_moves) = ACTIVE_MOVES.lock() {
+        active_moves.remove(id);
+    }
+}
+
+/// Leaves a move in the journal for `get_interrupted_moves` to report
+pub fn abandon_move(id: &str) {
+    if let Ok(mut active_moves) = ACTIVE_MOVES.lock() {
+        active_moves.remove(id);
+    }
+}
+
+// A single file is keyed by the empty path
+fn resolve(root: &Path, relative_path: &str) -> PathBuf {
+    if relative_path.is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(relative_path)
+    }
+}
+
+/// Files below an item by relative path, with their sizes
+fn item_files(root: &Path) -> BTreeMap<String, u64> {
+    if root.is_file() {
+        let size = fs::metadata(root)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        return BTreeMap::from([(String::new(), size)]);
+    }
+
+    walkdir::WalkDir::new(root)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir())
+        .filter_map(|entry| {
+            let relative_path = entry.path().strip_prefix(root).ok()?;
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            Some((normalize_path(&relative_path.to_string_lossy()), size))
+        })
+        .collect()
+}
+
+fn inspect(entry: &JournalEntry) -> InterruptedMove {
+    let source = Path::new(&entry.source_path);
+    let destination = Path::new(&entry.destination_path);
+    let source_files = item_files(source);
+    let mut destination_files = item_files(destination);
+
+    let mut files: Vec<RecoveryFile> = source_files
+        .into_iter()
+        .map(|(relative_path, size)| {
+            let state = match destination_files.remove(&relative_path) {
+                None => RecoveryFileState::SourceOnly,
+                Some(_) => {
+                    let is_identical = crate::file_compare::files_equal(
+                        &resolve(source, &relative_path),
+                        &resolve(destination, &relative_path),
+                    )
+                    .unwrap_or(false);
+
+                    if is_identical {
+                        RecoveryFileState::Identical
+                    } else {
+                        RecoveryFileState::Different
+                    }
+                }
+            };
+
+            RecoveryFile {
+                relative_path,
+                state,
+                size,
+            }
+        })
+        .collect();
+
+    files.extend(
+        destination_files
+            .into_iter()
+            .map(|(relative_path, size)| RecoveryFile {
+                relative_path,
+                state: RecoveryFileState::DestinationOnly,
+                size,
+            }),
+    );
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+
+    InterruptedMove {
+        id: entry.id.clone(),
+        source_path: entry.source_path.clone(),
+        destination_path: entry.destination_path.clone(),
+        started_at: entry.started_at,
+        files,
+    }
+}
+
+/// Moves left unfinished by an earlier session, with where each of their
+/// files is now
+pub fn interrupted_moves() -> Result<Vec<InterruptedMove>, String> {
+    let active_moves = ACTIVE_MOVES
+        .lock()
+        .map_err(|error| error.to_string())?
+        .clone();
+    let entries: Vec<JournalEntry> = JOURNAL
+        .lock()
+        .map_err(|error| error.to_string())?
+        .moves
+        .iter()
+        .filter(|entry| !active_moves.contains(&entry.id))
+        .cloned()
+        .collect();
+
+    Ok(entries.iter().map(inspect).collect())
+}
+
+fn copy_verified(from: &Path, to: &Path) -> Result<(), String> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+
+    fs::copy(from, to).map_err(|error| error.to_string())?;
+    verify::verify_copy(from, to)
+}
+
+pub fn resolve_move(id: &str, action: RecoveryAction) -> Result<(), String> {
+    let entry = JOURNAL
+        .lock()
+        .map_err(|error| error.to_string())?
+        .moves
+        .iter()
+        .find(|entry| entry.id == id)
+        .cloned()
+        .ok_or_else(|| format!("No interrupted move with id {}", id))?;
+    let source = Path::new(&entry.source_path);
+    let destination = Path::new(&entry.destination_path);
+
+    match action {
+        RecoveryAction::Complete => {
+            for file in inspect(&entry).files {
+                if matches!(
+                    file.state,
+                    RecoveryFileState::SourceOnly | RecoveryFileState::Different
+                ) {
+                    copy_verified(
+                        &resolve(source, &file.relative_path),
+                        &resolve(destination, &file.relative_path),
+                    )?;
+                }
+            }
+
+            if source.exists() {
+                remove_dir_or_file(source)?;
+            }
+            crate::tags::handle_path_moved(source, destination);
+        }
+        RecoveryAction::RollBack => {
+            for file in inspect(&entry).files {
+                if file.state == RecoveryFileState::DestinationOnly {
+                    copy_verified(
+                        &resolve(destination, &file.relative_path),
+                        &resolve(source, &file.relative_path),
+                    )?;
+                }
+            }
+
+            if destination.exists() {
+                remove_dir_or_file(destination)?;
+            }
+        }
+        RecoveryAction::Dismiss => {}
+    }
+
+    end_move(id);
+    Ok(())
+}
@@ -0,0 +1,1321 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+mod clone;
+mod journal;
+mod pipeline;
+mod plan;
+mod preserve;
+mod resume;
+mod retry;
+mod salvage;
+mod verify;
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+use crate::job_reports::{self, ItemAction, JobKind, JobRecorder, JobReportItem, SkipReason};
+use crate::messages;
+use crate::utils::{normalize_path, to_os_path};
+use crate::vfs;
+
+pub use journal::{InterruptedMove, RecoveryAction};
+pub use pipeline::CopyOptions;
+pub use plan::{OperationPlan, PreflightResult};
+pub use preserve::MetadataIssue;
+pub use resume::{TransferKind, TransferTask};
+pub use retry::{Retrier, RetryPolicy};
+pub use salvage::{SalvageOptions, SalvageResult};
+pub use verify::VerificationReport;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FileOperationResult {
+    pub success: bool,
+    pub error: Option<String>,
+    pub copied_count: Option<u32>,
+    pub failed_count: Option<u32>,
+    pub skipped_count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<OperationPlan>,
+    /// Items that hit transient IO errors and needed more than one attempt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retried_paths: Option<Vec<String>>,
+    /// Id of the job's report, see `job_reports`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// Set when the copies were checked against their sources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<VerificationReport>,
+    /// Metadata that `CopyOptions::preserve` asked for but the destination
+    /// didn't take
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_issues: Option<Vec<MetadataIssue>>,
+}
+
+impl FileOperationResult {
+    /// An operation that failed before touching any item
+    pub fn failure(error: String) -> Self {
+        FileOperationResult {
+            success: false,
+            error: Some(error),
+            ..Default::default()
+        }
+    }
+
+    /// An operation over several items, successful when none of them failed
+    pub fn counts(copied_count: u32, failed_count: u32, skipped_count: u32) -> Self {
+        FileOperationResult {
+            success: failed_count == 0,
+            copied_count: Some(copied_count),
+            failed_count: Some(failed_count),
+            skipped_count: Some(skipped_count),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConflictItem {
+    pub source_path: String,
+    pub source_name: String,
+    pub source_is_dir: bool,
+    pub source_size: Option<u64>,
+    pub destination_path: String,
+    pub destination_is_dir: bool,
+    pub destination_size: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateItemsResult {
+    pub success: bool,
+    pub error: Option<String>,
+    pub new_paths: Vec<String>,
+    pub failed_count: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConflictResolution {
+    Replace,
+    Skip,
+    AutoRename,
+}
+
+impl ConflictResolution {
+    fn from_str(value: &str) -> Self {
+        match value {
+            "replace" => ConflictResolution::Replace,
+            "skip" => ConflictResolution::Skip,
+            "auto-rename" => ConflictResolution::AutoRename,
+            _ => ConflictResolution::AutoRename,
+        }
+    }
+}
+
+// Copies a file or directory tree, retrying transient errors per file.
+// Files already complete at the destination are kept, see `resume`.
+pub(crate) fn copy_item(
+    source: &Path,
+    destination: &Path,
+    retrier: &mut Retrier,
+    options: &CopyOptions,
+) -> Result<(), String> {
+    if source.is_dir() {
+        pipeline::copy_dir(source, destination, retrier, options)
+    } else {
+        pipeline::copy_file(source, destination, retrier, options)
+    }
+}
+
+pub(crate) fn get_unique_destination_path(destination: &Path, name: &str) -> std::path::PathBuf {
+    unique_destination_path_with(destination, name, |path| path.exists())
+}
+
+pub(crate) fn unique_destination_path_with<F>(
+    destination: &Path,
+    name: &str,
+    mut is_taken: F,
+) -> std::path::PathBuf
+where
+    F: FnMut(&Path) -> bool,
+{
+    let mut dest_path = destination.join(name);
+    let mut counter = 1;
+
+    while is_taken(&dest_path) {
+        let path = Path::new(name);
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or(name);
+        let extension = path.extension().and_then(|ext| ext.to_str());
+
+        let new_name = if let Some(ext) = extension {
+            format!("{} ({}).{}", stem, counter, ext)
+        } else {
+            format!("{} ({})", stem, counter)
+        };
+
+        dest_path = destination.join(&new_name);
+        counter += 1;
+    }
+
+    dest_path
+}
+
+#[tauri::command]
+pub fn check_conflicts(source_paths: Vec<String>, destination_path: String) -> Vec<ConflictItem> {
+    let destination = &to_os_path(&destination_path);
+    let mut conflicts = Vec::new();
+
+    if !destination.exists() || !destination.is_dir() {
+        return conflicts;
+    }
+
+    for source_path_str in &source_paths {
+        let source = &to_os_path(source_path_str);
+
+        if !source.exists() {
+            continue;
+        }
+
+        let source_parent = source.parent().map(|parent| normalize_path(&parent.to_string_lossy()));
+        let dest_normalized = normalize_path(&destination.to_string_lossy());
+        let is_same_directory = source_parent
+            .map(|parent| parent == dest_normalized)
+            .unwrap_or(false);
+
+        if is_same_directory {
+            continue;
+        }
+
+        let file_name = match source.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => continue,
+        };
+
+        let dest_item_path = destination.join(&file_name);
+
+        if dest_item_path.exists() {
+            let source_size = if source.is_file() {
+                fs::metadata(source).ok().map(|metadata| metadata.len())
+            } else {
+                None
+            };
+
+            let destination_size = if dest_item_path.is_file() {
+                fs::metadata(&dest_item_path).ok().map(|metadata| metadata.len())
+            } else {
+                None
+            };
+
+            conflicts.push(ConflictItem {
+                source_path: source_path_str.clone(),
+                source_name: file_name,
+                source_is_dir: source.is_dir(),
+                source_size,
+                destination_path: dest_item_path.to_string_lossy().to_string(),
+                destination_is_dir: dest_item_path.is_dir(),
+                destination_size,
+            });
+        }
+    }
+
+    conflicts
+}
+
+pub(crate) fn remove_dir_or_file(path: &Path) -> Result<(), String> {
+    if path.is_dir() {
+        fs::remove_dir_all(path).map_err(|error| error.to_string())
+    } else {
+        fs::remove_file(path).map_err(|error| error.to_string())
+    }
+}
+
+// Refuses to start a transfer whose output won't fit on the destination
+// volume, instead of failing hours into it
+fn refuse_if_out_of_space(
+    source_paths: &[String],
+    destination: &Path,
+    resolution: &ConflictResolution,
+    is_move: bool,
+) -> Option<FileOperationResult> {
+    let plan = plan::plan_transfer(source_paths, destination, resolution, is_move);
+
+    if plan.has_enough_space == Some(false) {
+        Some(plan.into_result())
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PreflightOperation {
+    Copy {
+        source_paths: Vec<String>,
+        destination_path: String,
+        conflict_resolution: Option<String>,
+    },
+    Move {
+        source_paths: Vec<String>,
+        destination_path: String,
+        conflict_resolution: Option<String>,
+    },
+    /// Any other operation with a known output size, e.g. extracting an archive
+    Write {
+        destination_path: String,
+        required_bytes: u64,
+    },
+}
+
+/// Checks whether an operation's output fits on the destination volume
+/// before it starts
+#[tauri::command]
+pub async fn preflight_operation(operation: PreflightOperation) -> Result<PreflightResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let (source_paths, destination_path, conflict_resolution, is_move) = match operation {
+            PreflightOperation::Copy {
+                source_paths,
+                destination_path,
+                conflict_resolution,
+            } => (source_paths, destination_path, conflict_resolution, false),
+            PreflightOperation::Move {
+                source_paths,
+                destination_path,
+                conflict_resolution,
+            } => (source_paths, destination_path, conflict_resolution, true),
+            PreflightOperation::Write {
+                destination_path,
+                required_bytes,
+            } => {
+                return Ok(PreflightResult::new(
+                    &to_os_path(&destination_path),
+                    required_bytes,
+                ))
+            }
+        };
+
+        let destination = &to_os_path(&destination_path);
+        // Same defaults as copy_items and move_items
+        let resolution = conflict_resolution
+            .map(|value| ConflictResolution::from_str(&value))
+            .unwrap_or(if is_move {
+                ConflictResolution::Skip
+            } else {
+                ConflictResolution::AutoRename
+            });
+
+        if !destination.is_dir() {
+            return Err(messages::DESTINATION_NOT_DIRECTORY.with("path", destination_path).into());
+        }
+
+        let plan = plan::plan_transfer(&source_paths, destination, &resolution, is_move);
+        Ok(PreflightResult::new(destination, plan.required_bytes))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+// Archives, devices and servers go through their virtual file system, which
+// has no previews, verification or resuming
+fn transfer_virtual(
+    source_paths: &[String],
+    destination_path: &str,
+    resolution: &ConflictResolution,
+    is_move: bool,
+    dry_run: Option<bool>,
+) -> Option<FileOperationResult> {
+    if vfs::is_local(destination_path) && source_paths.iter().all(|path| vfs::is_local(path)) {
+        return None;
+    }
+
+    if dry_run.unwrap_or(false) {
+        return Some(FileOperationResult::failure(vfs::unsupported("Previewing")));
+    }
+
+    Some(
+        vfs::transfer(source_paths, destination_path, is_move, resolution)
+            .unwrap_or_else(FileOperationResult::failure),
+    )
+}
+
+/// Copies items into `destination_path`. With `verify`, every copied file
+/// is hashed against its source afterwards, and items whose copy differs
+/// count as failed and are listed in the result's `verification` report.
+/// Metadata listed in `options.preserve` that the destination can't keep
+/// is listed in `metadata_issues` without failing the item. Copies that
+/// don't finish can be continued with `resume_operation`. Items in
+/// archives, on portable devices or on servers, or a destination there, are
+/// copied through `vfs` instead.
+#[tauri::command]
+pub fn copy_items(
+    source_paths: Vec<String>,
+    destination_path: String,
+    conflict_resolution: Option<String>,
+    dry_run: Option<bool>,
+    retry_policy: Option<RetryPolicy>,
+    verify: Option<bool>,
+    options: Option<CopyOptions>,
+) -> FileOperationResult {
+    run_copy(
+        source_paths,
+        destination_path,
+        conflict_resolution,
+        dry_run,
+        retry_policy,
+        verify,
+        options,
+        None,
+    )
+}
+
+// Runs a new copy task, or the rest of `resumed_task`
+#[allow(clippy::too_many_arguments)]
+fn run_copy(
+    source_paths: Vec<String>,
+    destination_path: String,
+    conflict_resolution: Option<String>,
+    dry_run: Option<bool>,
+    retry_policy: Option<RetryPolicy>,
+    verify: Option<bool>,
+    options: Option<CopyOptions>,
+    resumed_task: Option<String>,
+) -> FileOperationResult {
+    let _timer = crate::metrics::time_command("copy_items");
+    let destination = &to_os_path(&destination_path);
+    let resolution = conflict_resolution
+        .as_deref()
+        .map(ConflictResolution::from_str)
+        .unwrap_or(ConflictResolution::AutoRename);
+
+    if let Some(result) =
+        transfer_virtual(&source_paths, &destination_path, &resolution, false, dry_run)
+    {
+        return result;
+    }
+
+    if dry_run.unwrap_or(false) {
+        return plan::plan_transfer(&source_paths, destination, &resolution, false).into_result();
+    }
+
+    if !destination.exists() {
+        return FileOperationResult::failure(
+            messages::DESTINATION_NOT_FOUND.with("path", destination_path).into(),
+        );
+    }
+
+    if !destination.is_dir() {
+        return FileOperationResult::failure(
+            messages::DESTINATION_NOT_DIRECTORY.with("path", destination_path).into(),
+        );
+    }
+
+    // Resumed tasks have part of their output on the destination already
+    if resumed_task.is_none() {
+        if let Some(result) =
+            refuse_if_out_of_space(&source_paths, destination, &resolution, false)
+        {
+            return result;
+        }
+    }
+
+    let options = options.unwrap_or_default();
+    let task_id = resumed_task.unwrap_or_else(|| {
+        resume::begin(
+            TransferKind::Copy,
+            &source_paths,
+            &destination_path,
+            conflict_resolution.clone(),
+            verify,
+            &options,
+        )
+    });
+    let started_at = Instant::now();
+    let mut retrier = Retrier::new(retry_policy.unwrap_or_default());
+    let mut copied_count: u32 = 0;
+    let mut failed_count: u32 = 0;
+    let mut skipped_count: u32 = 0;
+    let mut last_error: Option<String> = None;
+    let mut job = JobRecorder::new(JobKind::Copy);
+    let mut verification = verify.unwrap_or(false).then(VerificationReport::default);
+
+    for source_path_str in &source_paths {
+        let source = &to_os_path(source_path_str);
+        let item_started_at = Instant::now();
+
+        if !source.exists() {
+            failed_count += 1;
+            let error: String = messages::SOURCE_NOT_FOUND.with("path", source_path_str).into();
+            job.record(JobReportItem::failed(source, error.clone()));
+            last_error = Some(error);
+            // Resuming can't bring it back
+            resume::complete_item(&task_id, source);
+            continue;
+        }
+
+        let source_parent = source.parent().map(|parent| normalize_path(&parent.to_string_lossy()));
+        let dest_normalized = normalize_path(&destination.to_string_lossy());
+        let is_same_directory = source_parent
+            .map(|parent| parent == dest_normalized)
+            .unwrap_or(false);
+
+        let file_name = match source.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => {
+                failed_count += 1;
+                let error: String = messages::SOURCE_INVALID.with("path", source_path_str).into();
+                job.record(JobReportItem::failed(source, error.clone()));
+                last_error = Some(error);
+                resume::complete_item(&task_id, source);
+                continue;
+            }
+        };
+
+        let dest_path = if let Some(resumed_dest) = resume::item_destination(&task_id, source) {
+            resumed_dest
+        } else if is_same_directory {
+            get_unique_destination_path(destination, &file_name)
+        } else {
+            let initial_dest = destination.join(&file_name);
+            if initial_dest.exists() {
+                match resolution {
+                    ConflictResolution::Skip => {
+                        skipped_count += 1;
+                        job.record(JobReportItem::skipped(source, SkipReason::AlreadyExists));
+                        resume::complete_item(&task_id, source);
+                        continue;
+                    }
+                    ConflictResolution::Replace => {
+                        if let Err(error) = remove_dir_or_file(&initial_dest) {
+                            failed_count += 1;
+                            job.record(JobReportItem::failed(source, error.clone()));
+                            last_error = Some(error);
+                            continue;
+                        }
+                        initial_dest
+                    }
+                    ConflictResolution::AutoRename => {
+                        get_unique_destination_path(destination, &file_name)
+                    }
+                }
+            } else {
+                initial_dest
+            }
+        };
+
+        resume::set_item_destination(&task_id, source, &dest_path);
+        let copy_result = copy_item(source, &dest_path, &mut retrier, &options);
+        // Every file and folder copied, and the one the copy failed at
+        job.record_all(options.copied_items.take());
+        // Resuming only continues unfinished copies, it can't fix wrong ones
+        if copy_result.is_ok() {
+            resume::complete_item(&task_id, source);
+        }
+        let result = copy_result.and_then(|()| match &mut verification {
+            Some(report) if !report.check_copy(source, &dest_path) => {
+                let error: String = messages::COPY_MISMATCH.with("path", source_path_str).into();
+                job.record(
+                    JobReportItem::failed(source, error.clone())
+                        .destination(&dest_path)
+                        .started_at(item_started_at),
+                );
+                Err(error)
+            }
+            _ => Ok(()),
+        });
+
+        match result {
+            Ok(()) => copied_count += 1,
+            Err(error) => {
+                failed_count += 1;
+                last_error = Some(error);
+            }
+        }
+    }
+
+    resume::end(&task_id);
+    if copied_count > 0 {
+        crate::history::record_destination(destination, &source_paths);
+    }
+
+    crate::metrics::record_job(
+        "copy",
+        copied_count as u64,
+        failed_count as u64,
+        started_at.elapsed(),
+    );
+
+    FileOperationResult {
+        error: last_error,
+        retried_paths: retrier.into_retried_paths(),
+        job_id: job.finish(),
+        verification,
+        metadata_issues: options.metadata_issues.take(),
+        ..FileOperationResult::counts(copied_count, failed_count, skipped_count)
+    }
+}
+
+/// Moves items into `destination_path`. Moves to other volumes that don't
+/// finish can be continued with `resume_operation`. Like `copy_items`, moves
+/// outside local disks go through `vfs`.
+#[tauri::command]
+pub fn move_items(
+    source_paths: Vec<String>,
+    destination_path: String,
+    conflict_resolution: Option<String>,
+    dry_run: Option<bool>,
+    retry_policy: Option<RetryPolicy>,
+    verify_checksums: Option<bool>,
+    options: Option<CopyOptions>,
+) -> FileOperationResult {
+    run_move(
+        source_paths,
+        destination_path,
+        conflict_resolution,
+        dry_run,
+        retry_policy,
+        verify_checksums,
+        options,
+        None,
+    )
+}
+
+// Runs a new move task, or the rest of `resumed_task`
+#[allow(clippy::too_many_arguments)]
+fn run_move(
+    source_paths: Vec<String>,
+    destination_path: String,
+    conflict_resolution: Option<String>,
+    dry_run: Option<bool>,
+    retry_policy: Option<RetryPolicy>,
+    verify_checksums: Option<bool>,
+    options: Option<CopyOptions>,
+    resumed_task: Option<String>,
+) -> FileOperationResult {
+    let _timer = crate::metrics::time_command("move_items");
+    let destination = &to_os_path(&destination_path);
+    let resolution = conflict_resolution
+        .as_deref()
+        .map(ConflictResolution::from_str)
+        .unwrap_or(ConflictResolution::Skip);
+
+    if let Some(result) =
+        transfer_virtual(&source_paths, &destination_path, &resolution, true, dry_run)
+    {
+        return result;
+    }
+
+    if dry_run.unwrap_or(false) {
+        return plan::plan_transfer(&source_paths, destination, &resolution, true).into_result();
+    }
+
+    if !destination.exists() {
+        return FileOperationResult::failure(
+            messages::DESTINATION_NOT_FOUND.with("path", destination_path).into(),
+        );
+    }
+
+    if !destination.is_dir() {
+        return FileOperationResult::failure(
+            messages::DESTINATION_NOT_DIRECTORY.with("path", destination_path).into(),
+        );
+    }
+
+    if resumed_task.is_none() {
+        if let Some(result) =
+            refuse_if_out_of_space(&source_paths, destination, &resolution, true)
+        {
+            return result;
+        }
+    }
+
+    let options = options.unwrap_or_default();
+    let task_id = resumed_task.unwrap_or_else(|| {
+        resume::begin(
+            TransferKind::Move,
+            &source_paths,
+            &destination_path,
+            conflict_resolution.clone(),
+            verify_checksums,
+            &options,
+        )
+    });
+    let started_at = Instant::now();
+    let mut retrier = Retrier::new(retry_policy.unwrap_or_default());
+    // Applies to items that can't be renamed and are copied, then deleted
+    let verify_checksums =
+        verify_checksums.unwrap_or_else(|| verify::verifies_by_default(destination));
+    let mut moved_count: u32 = 0;
+    let mut failed_count: u32 = 0;
+    let mut skipped_count: u32 = 0;
+    let mut last_error: Option<String> = None;
+    let mut job = JobRecorder::new(JobKind::Move);
+
+    for source_path_str in &source_paths {
+        let source = &to_os_path(source_path_str);
+        let item_started_at = Instant::now();
+
+        if !source.exists() {
+            failed_count += 1;
+            let error: String = messages::SOURCE_NOT_FOUND.with("path", source_path_str).into();
+            job.record(JobReportItem::failed(source, error.clone()));
+            last_error = Some(error);
+            // Also the case when an earlier run moved it but stopped before
+            // recording that
+            resume::complete_item(&task_id, source);
+            continue;
+        }
+
+        let source_parent = source.parent().map(|parent| normalize_path(&parent.to_string_lossy()));
+        let dest_normalized = normalize_path(&destination.to_string_lossy());
+        let is_same_directory = source_parent
+            .map(|parent| parent == dest_normalized)
+            .unwrap_or(false);
+
+        if is_same_directory {
+            job.record(JobReportItem::skipped(source, SkipReason::SameFolder));
+            resume::complete_item(&task_id, source);
+            continue;
+        }
+
+        let file_name = match source.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => {
+                failed_count += 1;
+                let error: String = messages::SOURCE_INVALID.with("path", source_path_str).into();
+                job.record(JobReportItem::failed(source, error.clone()));
+                last_error = Some(error);
+                resume::complete_item(&task_id, source);
+                continue;
+            }
+        };
+
+        let dest_path = destination.join(&file_name);
+        let resumed_dest = resume::item_destination(&task_id, source);
+
+        let final_dest_path = if let Some(resumed_dest) = resumed_dest {
+            resumed_dest
+        } else if dest_path.exists() {
+            match resolution {
+                ConflictResolution::Skip => {
+                    skipped_count += 1;
+                    job.record(JobReportItem::skipped(source, SkipReason::AlreadyExists));
+                    resume::complete_item(&task_id, source);
+                    continue;
+                }
+                ConflictResolution::Replace => {
+                    if let Err(error) = remove_dir_or_file(&dest_path) {
+                        failed_count += 1;
+                        job.record(JobReportItem::failed(source, error.clone()));
+                        last_error = Some(error);
+                        continue;
+                    }
+                    dest_path
+                }
+                ConflictResolution::AutoRename => {
+                    get_unique_destination_path(destination, &file_name)
+                }
+            }
+        } else {
+            dest_path
+        };
+
+        resume::set_item_destination(&task_id, source, &final_dest_path);
+        let result = fs::rename(source, &final_dest_path);
+        let moved_item =
+            JobReportItem::new(source, ItemAction::Moved).destination(&final_dest_path);
+
+        match result {
+            Ok(()) => {
+                resume::complete_item(&task_id, source);
+                crate::tags::handle_path_moved(source, &final_dest_path);
+                moved_count += 1;
+                job.record(
+                    moved_item
+                        .bytes(job_reports::item_bytes(&final_dest_path))
+                        .started_at(item_started_at),
+                );
+            }
+            Err(error) => {
+                if error.raw_os_error() == Some(17) || error.raw_os_error() == Some(18) {
+                    let journal_id = journal::begin_move(source, &final_dest_path);
+                    let copy_result = copy_item(source, &final_dest_path, &mut retrier, &options);
+                    let copied_items = options.copied_items.take();
+                    let copy_result = copy_result.and_then(|()| {
+                        if !verify_checksums {
+                            return Ok(());
+                        }
+                        // The source is untouched, so a bad copy is removed
+                        verify::verify_copy(source, &final_dest_path).inspect_err(|_| {
+                            let _ = remove_dir_or_file(&final_dest_path);
+                        })
+                    });
+
+                    match copy_result {
+                        Ok(()) => {
+                            // A source that couldn't be fully deleted is left
+                            // for recovery
+                            match remove_dir_or_file(source) {
+                                Ok(()) => journal::end_move(&journal_id),
+                                Err(_) => journal::abandon_move(&journal_id),
+                            }
+                            resume::complete_item(&task_id, source);
+                            crate::tags::handle_path_moved(source, &final_dest_path);
+                            moved_count += 1;
+                            job.record_all(copied_items.into_iter().map(|item| JobReportItem {
+                                action: ItemAction::Moved,
+                                ..item
+                            }));
+                        }
+                        Err(copy_error) => {
+                            journal::end_move(&journal_id);
+                            failed_count += 1;
+                            job.record(
+                                JobReportItem::failed(source, copy_error.clone())
+                                    .destination(&final_dest_path)
+                                    .started_at(item_started_at),
+                            );
+                            last_error = Some(copy_error);
+                        }
+                    }
+                } else {
+                    failed_count += 1;
+                    job.record(
+                        JobReportItem::failed(source, error.to_string())
+                            .destination(&final_dest_path)
+                            .started_at(item_started_at),
+                    );
+                    last_error = Some(error.to_string());
+                }
+            }
+        }
+    }
+
+    resume::end(&task_id);
+    if moved_count > 0 {
+        crate::history::record_destination(destination, &source_paths);
+    }
+
+    crate::metrics::record_job(
+        "move",
+        moved_count as u64,
+        failed_count as u64,
+        started_at.elapsed(),
+    );
+
+    FileOperationResult {
+        error: last_error,
+        retried_paths: retrier.into_retried_paths(),
+        job_id: job.finish(),
+        metadata_issues: options.metadata_issues.take(),
+        ..FileOperationResult::counts(moved_count, failed_count, skipped_count)
+    }
+}
+
+/// Copies items off damaged media, skipping unreadable sectors instead of
+/// failing. Reports the recovered and missing byte ranges of every file.
+#[tauri::command]
+pub async fn salvage_copy(
+    source_paths: Vec<String>,
+    destination_path: String,
+    options: Option<SalvageOptions>,
+) -> Result<SalvageResult, String> {
+    let options = options.unwrap_or_default();
+
+    tokio::task::spawn_blocking(move || {
+        let destination = &to_os_path(&destination_path);
+
+        if !destination.is_dir() {
+            return Err(messages::DESTINATION_NOT_DIRECTORY.with("path", destination_path).into());
+        }
+
+        let mut result = SalvageResult::default();
+
+        for source_path_str in &source_paths {
+            let source = &to_os_path(source_path_str);
+
+            let file_name = match source.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => {
+                    result.failed_count += 1;
+                    result.error =
+                        Some(messages::SOURCE_INVALID.with("path", source_path_str).into());
+                    continue;
+                }
+            };
+
+            let dest_path = get_unique_destination_path(destination, &file_name);
+            salvage::salvage_item(source, &dest_path, &options, &mut result);
+        }
+
+        result.success = result.failed_count == 0;
+        Ok(result)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Moves across volumes that a crash or power loss cut short, with the
+/// state of each of their files in the source and the destination
+#[tauri::command]
+pub async fn get_interrupted_moves() -> Result<Vec<InterruptedMove>, String> {
+    tokio::task::spawn_blocking(journal::interrupted_moves)
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Completes, rolls back or dismisses an interrupted move
+#[tauri::command]
+pub async fn resolve_interrupted_move(id: String, action: RecoveryAction) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || journal::resolve_move(&id, action))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Copies and moves that stopped before all their items were done, from
+/// earlier sessions or from runs that failed partway
+#[tauri::command]
+pub async fn get_resumable_operations() -> Result<Vec<TransferTask>, String> {
+    tokio::task::spawn_blocking(resume::resumable_tasks)
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Continues a copy or move with the items it hasn't finished. Files that
+/// were completed are kept and large ones continue from where they stopped.
+#[tauri::command]
+pub async fn resume_operation(task_id: String) -> Result<FileOperationResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let task = resume::take_for_resume(&task_id)?;
+        let source_paths: Vec<String> = task
+            .items
+            .into_iter()
+            .filter(|item| !item.is_done)
+            .map(|item| item.source_path)
+            .collect();
+
+        let run = match task.kind {
+            TransferKind::Copy => run_copy,
+            TransferKind::Move => run_move,
+        };
+        Ok(run(
+            source_paths,
+            task.destination_path,
+            task.conflict_resolution,
+            None,
+            None,
+            task.verify,
+            Some(task.options),
+            Some(task_id),
+        ))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Forgets an unfinished copy or move and deletes its partial copies
+#[tauri::command]
+pub async fn discard_operation(task_id: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || resume::discard(&task_id))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Renames through a temporary name, for names that differ from the current
+/// one only in case. Case-insensitive file systems see both as the same
+/// name, and some of them ignore a direct rename between them. On failure
+/// the item is put back under its original name.
+fn rename_case_only(source: &Path, dest_path: &Path) -> std::io::Result<()> {
+    // Renames replace what is at the target, such as an item an earlier
+    // failed rollback left behind, so the name is new every time
+    let temp_path = source.with_file_name(format!(".sigma-rename-{}", uuid::Uuid::new_v4()));
+    if fs::symlink_metadata(&temp_path).is_ok() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists", temp_path.display()),
+        ));
+    }
+    fs::rename(source, &temp_path)?;
+
+    if let Err(error) = fs::rename(&temp_path, dest_path) {
+        if let Err(rollback_error) = fs::rename(&temp_path, source) {
+            return Err(std::io::Error::new(
+                error.kind(),
+                format!(
+                    "{}. The item was left at {}: {}",
+                    error,
+                    temp_path.display(),
+                    rollback_error
+                ),
+            ));
+        }
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+/// Renames an item within its folder. Case-only renames, like `readme.md`
+/// to `README.md`, work on case-insensitive file systems too.
+#[tauri::command]
+pub fn rename_item(source_path: String, new_name: String) -> FileOperationResult {
+    let source = &to_os_path(&source_path);
+
+    if !source.exists() {
+        return FileOperationResult::failure(
+            messages::SOURCE_NOT_FOUND.with("path", source_path).into(),
+        );
+    }
+
+    let parent = match source.parent() {
+        Some(parent) => parent,
+        None => {
+            return FileOperationResult::failure(messages::NO_PARENT_DIRECTORY.text().into());
+        }
+    };
+
+    let dest_path = parent.join(&new_name);
+    let is_case_only = source.file_name().is_some_and(|name| {
+        let name = name.to_string_lossy();
+        name != new_name && name.to_lowercase() == new_name.to_lowercase()
+    }) && crate::batch_rename::is_same_file(source, &dest_path);
+
+    if dest_path.exists() && !is_case_only {
+        return FileOperationResult::failure(
+            messages::NAME_ALREADY_EXISTS.with("name", &new_name).into(),
+        );
+    }
+
+    let rename_result = if is_case_only {
+        rename_case_only(source, &dest_path)
+    } else {
+        fs::rename(source, &dest_path)
+    };
+
+    match rename_result {
+        Ok(()) => {
+            crate::tags::handle_path_moved(source, &dest_path);
+            FileOperationResult::counts(1, 0, 0)
+        }
+        Err(error) => FileOperationResult {
+            success: false,
+            error: Some(error.to_string()),
+            failed_count: Some(1),
+            ..Default::default()
+        },
+    }
+}
+
+/// Deletes `paths`, or with `view_id` and `selection_id` the view's
+/// selection, refusing if it changed since the id was handed out
+#[tauri::command]
+pub fn delete_items(
+    window: tauri::WebviewWindow,
+    paths: Vec<String>,
+    use_trash: bool,
+    dry_run: Option<bool>,
+    view_id: Option<String>,
+    selection_id: Option<u64>,
+) -> FileOperationResult {
+    let _timer = crate::metrics::time_command("delete_items");
+
+    let paths = match (view_id, selection_id) {
+        (Some(view_id), Some(selection_id)) => {
+            match crate::view_context::selected_paths(window.label(), &view_id, selection_id) {
+                Ok(selected_paths) => selected_paths,
+                Err(error) => {
+                    return FileOperationResult::failure(error)
+                }
+            }
+        }
+        _ => paths,
+    };
+
+    if dry_run.unwrap_or(false) {
+        return plan::plan_delete(&paths, use_trash).into_result();
+    }
+
+    let started_at = Instant::now();
+
+    let mut deleted_count: u32 = 0;
+    let mut failed_count: u32 = 0;
+    let mut last_error: Option<String> = None;
+    let mut job = JobRecorder::new(if use_trash { JobKind::Trash } else { JobKind::Delete });
+
+    for path_str in &paths {
+        // Archives, devices and servers go through their virtual file system
+        if !vfs::is_local(path_str) {
+            let result = if use_trash {
+                Err(vfs::unsupported("Moving to the trash"))
+            } else {
+                vfs::delete(path_str)
+            };
+
+            match result {
+                Ok(()) => deleted_count += 1,
+                Err(error) => {
+                    failed_count += 1;
+                    last_error = Some(format!("{}: {}", path_str, error));
+                }
+            }
+            continue;
+        }
+
+        let path = &to_os_path(path_str);
+        let item_started_at = Instant::now();
+
+        if !path.exists() {
+            failed_count += 1;
+            let error: String = messages::PATH_NOT_FOUND.with("path", path_str).into();
+            job.record(JobReportItem::failed(path, error.clone()));
+            last_error = Some(error);
+            continue;
+        }
+
+        // Measured first, there is nothing left to measure afterwards
+        let bytes = job_reports::item_bytes(path);
+        let result = if use_trash {
+            crate::trash_bin::trash_path(path)
+        } else if path.is_dir() {
+            fs::remove_dir_all(path).map_err(|error| error.to_string())
+        } else {
+            fs::remove_file(path).map_err(|error| error.to_string())
+        };
+
+        match result {
+            Ok(()) => {
+                crate::tags::handle_path_removed(path);
+                deleted_count += 1;
+                let action = if use_trash { ItemAction::Trashed } else { ItemAction::Deleted };
+                job.record(
+                    JobReportItem::new(path, action)
+                        .bytes(bytes)
+                        .started_at(item_started_at),
+                );
+            }
+            Err(error) => {
+                failed_count += 1;
+                job.record(JobReportItem::failed(path, error.clone()).started_at(item_started_at));
+                last_error = Some(error);
+            }
+        }
+    }
+
+    crate::metrics::record_job(
+        if use_trash { "trash" } else { "delete" },
+        deleted_count as u64,
+        failed_count as u64,
+        started_at.elapsed(),
+    );
+
+    FileOperationResult {
+        error: last_error,
+        job_id: job.finish(),
+        ..FileOperationResult::counts(deleted_count, failed_count, 0)
+    }
+}
+
+// Name of the nth copy following the platform file manager's convention
+fn duplicate_name(stem: &str, extension: &str, index: u32) -> String {
+    #[cfg(target_os = "macos")]
+    let suffix = if index == 1 {
+        " copy".to_string()
+    } else {
+        format!(" copy {}", index)
+    };
+
+    #[cfg(windows)]
+    let suffix = if index == 1 {
+        " - Copy".to_string()
+    } else {
+        format!(" - Copy ({})", index)
+    };
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let suffix = if index == 1 {
+        " (Copy)".to_string()
+    } else {
+        format!(" (Copy {})", index)
+    };
+
+    format!("{}{}{}", stem, suffix, extension)
+}
+
+fn get_duplicate_path(source: &Path) -> Option<std::path::PathBuf> {
+    let parent = source.parent()?;
+    let file_name = source.file_name()?.to_string_lossy().to_string();
+
+    // Folders and dotfiles keep their whole name as the stem
+    let (stem, extension) = match (source.is_dir(), file_name.rfind('.')) {
+        (false, Some(dot_index)) if dot_index > 0 => {
+            (file_name[..dot_index].to_string(), file_name[dot_index..].to_string())
+        }
+        _ => (file_name.clone(), String::new()),
+    };
+
+    (1..)
+        .map(|index| parent.join(duplicate_name(&stem, &extension, index)))
+        .find(|candidate| !candidate.exists())
+}
+
+/// Copies each item next to itself and returns the paths of the copies
+#[tauri::command]
+pub fn duplicate_items(paths: Vec<String>) -> DuplicateItemsResult {
+    let mut new_paths: Vec<String> = Vec::new();
+    let mut retrier = Retrier::default();
+    let mut failed_count: u32 = 0;
+    let mut last_error: Option<String> = None;
+
+    for path_str in &paths {
+        let source = &to_os_path(path_str);
+
+        if !source.exists() {
+            failed_count += 1;
+            last_error = Some(messages::PATH_NOT_FOUND.with("path", path_str).into());
+            continue;
+        }
+
+        let dest_path = match get_duplicate_path(source) {
+            Some(dest_path) => dest_path,
+            None => {
+                failed_count += 1;
+                last_error = Some(messages::SOURCE_INVALID.with("path", path_str).into());
+                continue;
+            }
+        };
+
+        let result = copy_item(source, &dest_path, &mut retrier, &CopyOptions::default());
+
+        match result {
+            Ok(()) => new_paths.push(normalize_path(&dest_path.to_string_lossy())),
+            Err(error) => {
+                failed_count += 1;
+                last_error = Some(error);
+            }
+        }
+    }
+
+    DuplicateItemsResult {
+        success: failed_count == 0,
+        error: last_error,
+        new_paths,
+        failed_count,
+    }
+}
+
+#[tauri::command]
+pub fn create_hardlink(source_path: String, destination_path: String) -> FileOperationResult {
+    let source = &to_os_path(&source_path);
+    let destination = &to_os_path(&destination_path);
+
+    if !source.is_file() {
+        return FileOperationResult::failure(
+            messages::SOURCE_NOT_FILE.with("path", source_path).into(),
+        );
+    }
+
+    if destination.exists() {
+        return FileOperationResult::failure(
+            messages::PATH_ALREADY_EXISTS.with("path", destination_path).into(),
+        );
+    }
+
+    match fs::hard_link(source, destination) {
+        Ok(()) => FileOperationResult::counts(1, 0, 0),
+        Err(error) => FileOperationResult {
+            success: false,
+            error: Some(error.to_string()),
+            failed_count: Some(1),
+            ..Default::default()
+        },
+    }
+}
+
+#[tauri::command]
+pub fn ensure_directory(directory_path: String) -> FileOperationResult {
+    let directory = &to_os_path(&directory_path);
+
+    match fs::create_dir_all(directory) {
+        Ok(()) => FileOperationResult::counts(1, 0, 0),
+        Err(error) => FileOperationResult {
+            success: false,
+            error: Some(error.to_string()),
+            failed_count: Some(1),
+            ..Default::default()
+        },
+    }
+}
+
+/// Creates an empty file or folder. With a `view_id`, `directory_path` may
+/// be relative to the view's current directory, or empty for the directory
+/// itself.
+#[tauri::command]
+pub fn create_item(
+    window: tauri::WebviewWindow,
+    directory_path: String,
+    name: String,
+    is_directory: bool,
+    view_id: Option<String>,
+) -> FileOperationResult {
+    let directory_path = match crate::view_context::resolve_path(
+        window.label(),
+        view_id.as_deref(),
+        &directory_path,
+    ) {
+        Ok(directory_path) => directory_path,
+        Err(error) => {
+            return FileOperationResult::failure(error)
+        }
+    };
+    let trimmed_name = name.trim();
+
+    if trimmed_name.is_empty() {
+        return FileOperationResult::failure(messages::NAME_EMPTY.text().into());
+    }
+
+    if trimmed_name.contains('/') || trimmed_name.contains('\\') {
+        return FileOperationResult::failure(messages::NAME_HAS_SEPARATORS.text().into());
+    }
+
+    let directory = &to_os_path(&directory_path);
+
+    if !directory.exists() {
+        return FileOperationResult::failure(
+            messages::DIRECTORY_NOT_FOUND.with("path", directory_path).into(),
+        );
+    }
+
+    if !directory.is_dir() {
+        return FileOperationResult::failure(
+            messages::PATH_NOT_DIRECTORY.with("path", directory_path).into(),
+        );
+    }
+
+    let dest_path = directory.join(trimmed_name);
+
+    if dest_path.exists() {
+        return FileOperationResult::failure(
+            messages::PATH_ALREADY_EXISTS.with("path", dest_path.display()).into(),
+        );
+    }
+
+    let result = if is_directory {
+        fs::create_dir(&dest_path).map_err(|error| error.to_string())
+    } else {
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&dest_path)
+            .map(|_| ())
+            .map_err(|error| error.to_string())
+    };
+
+    match result {
+        Ok(()) => FileOperationResult::counts(1, 0, 0),
+        Err(error) => FileOperationResult {
+            success: false,
+            error: Some(error),
+            failed_count: Some(1),
+            ..Default::default()
+        },
+    }
+}
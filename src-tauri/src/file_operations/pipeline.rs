@@ -0,0 +1,328 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Folder copies as a pipeline. One thread walks the source, creates the
+//! folders and copies large files one at a time, and a pool of workers
+//! copies the small files it queues. Small files are bound by the latency
+//! of opening, creating and closing each of them, which overlaps when many
+//! are in flight. Large files are bound by throughput, and copying several
+//! at once would only make drives seek between them.
+
+use super::preserve::{self, MetadataIssues};
+use super::resume;
+use super::retry::Retrier;
+use crate::faults::{self, FaultPoint};
+use crate::file_metadata::AttributeKind;
+use crate::job_reports::{ItemAction, ItemLog, JobReportItem};
+use crate::utils::volume_space;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Files up to this size go to the worker pool
+const SMALL_FILE_MAX_SIZE: u64 = 1024 * 1024;
+const QUEUED_FILES_PER_WORKER: usize = 64;
+const MAX_WORKERS: usize = 64;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CopyOptions {
+    /// Threads copying small files, chosen by the kind of the source and
+    /// destination drives when not set. 1 copies every file in order.
+    pub workers: Option<usize>,
+    /// Metadata to give the copies of files and folders. Copies always
+    /// keep the permissions, the rest is lost when not listed.
+    pub preserve: Vec<AttributeKind>,
+    /// What couldn't be preserved
+    #[serde(skip)]
+    pub(crate) metadata_issues: Arc<MetadataIssues>,
+    /// Every file and folder copied or failed, with the size and time of
+    /// each file, for the job report
+    #[serde(skip)]
+    pub(crate) copied_items: Arc<ItemLog>,
+}
+
+struct QueuedFile {
+    source: PathBuf,
+    destination: PathBuf,
+}
+
+/// Workers suited to the drive `path` is on. The `copy_workers` test in
+/// `tests` times these against fixed counts.
+fn workers_for_drive(path: &Path) -> usize {
+    let cores = std::thread::available_parallelism().map_or(4, |cores| cores.get());
+
+    match volume_space(path) {
+        // Many requests in flight hide the round trip of each one
+        Some(volume) if volume.is_network => 16,
+        // Spinning disks seek between files written at the same time
+        Some(volume) if volume.is_solid_state == Some(false) => 2,
+        // USB sticks and memory cards handle little concurrency
+        Some(volume) if volume.is_removable => 2,
+        Some(volume) if volume.is_solid_state == Some(true) => cores.clamp(4, 16),
+        _ => 4,
+    }
+}
+
+/// Workers for a copy, by whichever of the two drives handles fewer
+fn default_workers(source: &Path, destination: &Path) -> usize {
+    let destination_drive = destination.parent().unwrap_or(destination);
+    workers_for_drive(source).min(workers_for_drive(destination_drive))
+}
+
+/// Copies a file with the metadata `options` preserves
+pub fn copy_file(
+    source: &Path,
+    destination: &Path,
+    retrier: &mut Retrier,
+    options: &CopyOptions,
+) -> Result<(), String> {
+    let started_at = Instant::now();
+    let result = retrier.run(source, || {
+        faults::check(FaultPoint::CopyFile, source)?;
+        resume::copy_file(source, destination)
+    });
+
+    let bytes = match result {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            let error = error.to_string();
+            options.copied_items.record(
+                JobReportItem::failed(source, error.clone())
+                    .destination(destination)
+                    .started_at(started_at),
+            );
+            return Err(error);
+        }
+    };
+
+    preserve::apply(
+        source,
+        destination,
+        &options.preserve,
+        &options.metadata_issues,
+    );
+    options.copied_items.record(
+        JobReportItem::new(source, ItemAction::Copied)
+            .destination(destination)
+            .bytes(bytes)
+            .started_at(started_at),
+    );
+    Ok(())
+}
+
+/// Creates the copy of the folder `source` and lists what it holds
+fn create_folder(
+    source: &Path,
+    destination: &Path,
+    retrier: &mut Retrier,
+) -> Result<Vec<fs::DirEntry>, String> {
+    if !destination.exists() {
+        retrier
+            .run(destination, || fs::create_dir_all(destination))
+            .map_err(|error| error.to_string())?;
+    }
+
+    retrier
+        .run(source, || {
+            fs::read_dir(source)?.collect::<std::io::Result<Vec<_>>>()
+        })
+        .map_err(|error| error.to_string())
+}
+
+/// Copies the folder tree at `source`, handing small files to `queue` when
+/// there is one, and lists the folders in `folders` for their metadata.
+/// Stops early once `failed` is set. Files and folders are recorded in
+/// `options.copied_items`, the workers record the files they copy.
+fn walk(
+    source: &Path,
+    destination: &Path,
+    retrier: &mut Retrier,
+    options: &CopyOptions,
+    queue: Option<&SyncSender<QueuedFile>>,
+    folders: &mut Vec<(PathBuf, PathBuf)>,
+    failed: &AtomicBool,
+) -> Result<(), String> {
+    let entries = match create_folder(source, destination, retrier) {
+        Ok(entries) => entries,
+        Err(error) => {
+            options
+                .copied_items
+                .record(JobReportItem::failed(source, error.clone()).destination(destination));
+            return Err(error);
+        }
+    };
+    options
+        .copied_items
+        .record(JobReportItem::new(source, ItemAction::Copied).destination(destination));
+    if !options.preserve.is_empty() {
+        folders.push((source.to_path_buf(), destination.to_path_buf()));
+    }
+
+    for entry in entries {
+        if failed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let source_path = entry.path();
+        let Some(file_name) = source_path.file_name() else {
+            let error = "Invalid file name".to_string();
+            options
+                .copied_items
+                .record(JobReportItem::failed(&source_path, error.clone()));
+            return Err(error);
+        };
+        let dest_path = destination.join(file_name);
+
+        if source_path.is_dir() {
+            walk(
+                &source_path,
+                &dest_path,
+                retrier,
+                options,
+                queue,
+                folders,
+                failed,
+            )?;
+            continue;
+        }
+
+        let is_small = entry
+            .metadata()
+            .is_ok_and(|metadata| metadata.len() <= SMALL_FILE_MAX_SIZE);
+        match queue {
+            Some(queue) if is_small => queue
+                .send(QueuedFile {
+                    source: source_path,
+                    destination: dest_path,
+                })
+                .map_err(|error| error.to_string())?,
+            _ => copy_file(&source_path, &dest_path, retrier, options)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Gives the copied folders their metadata, the deepest first
+fn preserve_folders(folders: Vec<(PathBuf, PathBuf)>, options: &CopyOptions) {
+    for (source, destination) in folders.into_iter().rev() {
+        preserve::apply(
+            &source,
+            &destination,
+            &options.preserve,
+            &options.metadata_issues,
+        );
+    }
+}
+
+/// Copies a folder tree, with `options.workers` threads for small files or
+/// the count suited to the drives involved. Stops at the first file that
+/// fails.
+pub fn copy_dir(
+    source: &Path,
+    destination: &Path,
+    retrier: &mut Retrier,
+    options: &CopyOptions,
+) -> Result<(), String> {
+    let workers = options
+        .workers
+        .unwrap_or_else(|| default_workers(source, destination))
+        .min(MAX_WORKERS);
+    let failed = AtomicBool::new(false);
+    let mut folders = Vec::new();
+
+    if workers <= 1 {
+        let result = walk(
+            source,
+            destination,
+            retrier,
+            options,
+            None,
+            &mut folders,
+            &failed,
+        );
+        preserve_folders(folders, options);
+        return result;
+    }
+
+    let (sender, receiver) = mpsc::sync_channel::<QueuedFile>(workers * QUEUED_FILES_PER_WORKER);
+    let receiver = Mutex::new(receiver);
+    let first_error: Mutex<Option<String>> = Mutex::new(None);
+
+    let record_error = |error: String| {
+        failed.store(true, Ordering::Relaxed);
+        if let Ok(mut first_error) = first_error.lock() {
+            first_error.get_or_insert(error);
+        }
+    };
+
+    let worker_retriers: Vec<Retrier> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                let mut worker_retrier = retrier.fork();
+                let receiver = &receiver;
+                let failed = &failed;
+                let record_error = &record_error;
+
+                scope.spawn(move || {
+                    loop {
+                        let next_file = match receiver.lock() {
+                            Ok(receiver) => receiver.recv(),
+                            Err(_) => break,
+                        };
+                        let Ok(file) = next_file else {
+                            break;
+                        };
+                        // Drains the queue without copying after a failure
+                        if failed.load(Ordering::Relaxed) {
+                            continue;
+                        }
+                        if let Err(error) = copy_file(
+                            &file.source,
+                            &file.destination,
+                            &mut worker_retrier,
+                            options,
+                        ) {
+                            record_error(error);
+                        }
+                    }
+                    worker_retrier
+                })
+            })
+            .collect();
+
+        if let Err(error) = walk(
+            source,
+            destination,
+            retrier,
+            options,
+            Some(&sender),
+            &mut folders,
+            &failed,
+        ) {
+            record_error(error);
+        }
+        drop(sender);
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    });
+
+    for worker_retrier in worker_retriers {
+        retrier.absorb(worker_retrier);
+    }
+    preserve_folders(folders, options);
+
+    match first_error.into_inner().ok().flatten() {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,367 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::{unique_destination_path_with, ConflictResolution, FileOperationResult};
+use crate::messages;
+use crate::utils::{normalize_path, to_os_path, volume_space};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedAction {
+    /// One of "copy", "move", "replace", "rename", "skip", "delete", "trash", "error"
+    pub action: String,
+    pub source_path: String,
+    pub destination_path: Option<String>,
+    pub is_dir: bool,
+    pub size: u64,
+    pub file_count: u64,
+    pub has_conflict: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationPlan {
+    pub operation: String,
+    pub actions: Vec<PlannedAction>,
+    pub item_count: u32,
+    pub file_count: u64,
+    pub total_bytes: u64,
+    pub conflict_count: u32,
+    pub skipped_count: u32,
+    pub error_count: u32,
+    pub required_bytes: u64,
+    pub available_bytes: Option<u64>,
+    pub has_enough_space: Option<bool>,
+    #[serde(default)]
+    pub destination_volume: Option<String>,
+}
+
+/// Whether an operation's output fits on its destination volume
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightResult {
+    /// `None` when the free space of the destination can't be determined
+    pub fits: Option<bool>,
+    pub required_bytes: u64,
+    pub available_bytes: Option<u64>,
+    pub shortfall_bytes: u64,
+    pub destination_volume: Option<String>,
+    pub destination_mount_point: Option<String>,
+    pub error: Option<String>,
+}
+
+impl PreflightResult {
+    pub fn new(destination: &Path, required_bytes: u64) -> Self {
+        let volume = volume_space(destination);
+        let available_bytes = volume.as_ref().map(|volume| volume.available_bytes);
+        let shortfall_bytes = available_bytes
+            .map(|available_bytes| required_bytes.saturating_sub(available_bytes))
+            .unwrap_or(0);
+        let destination_volume = volume
+            .as_ref()
+            .map(|volume| volume.name.clone())
+            .filter(|name| !name.is_empty());
+
+        PreflightResult {
+            fits: available_bytes.map(|available_bytes| required_bytes <= available_bytes),
+            required_bytes,
+            available_bytes,
+            shortfall_bytes,
+            error: (shortfall_bytes > 0).then(|| {
+                insufficient_space_message(
+                    required_bytes,
+                    available_bytes.unwrap_or(0),
+                    destination_volume.as_deref(),
+                )
+            }),
+            destination_volume,
+            destination_mount_point: volume.map(|volume| volume.mount_point),
+        }
+    }
+}
+
+fn insufficient_space_message(
+    required_bytes: u64,
+    available_bytes: u64,
+    volume_name: Option<&str>,
+) -> String {
+    let message = match volume_name {
+        Some(volume_name) => messages::INSUFFICIENT_SPACE.with("volume", volume_name),
+        None => messages::INSUFFICIENT_SPACE_DESTINATION.text(),
+    };
+
+    message
+        .with("required", required_bytes)
+        .with("available", available_bytes)
+        .into()
+}
+
+impl OperationPlan {
+    fn new(operation: &str) -> Self {
+        OperationPlan {
+            operation: operation.to_string(),
+            actions: Vec::new(),
+            item_count: 0,
+            file_count: 0,
+            total_bytes: 0,
+            conflict_count: 0,
+            skipped_count: 0,
+            error_count: 0,
+            required_bytes: 0,
+            available_bytes: None,
+            has_enough_space: None,
+            destination_volume: None,
+        }
+    }
+
+    fn push(&mut self, action: PlannedAction) {
+        if action.has_conflict {
+            self.conflict_count += 1;
+        }
+
+        match action.action.as_str() {
+            "skip" => self.skipped_count += 1,
+            "error" => self.error_count += 1,
+            _ => {
+                self.item_count += 1;
+                self.file_count += action.file_count;
+                self.total_bytes += action.size;
+            }
+        }
+
+        self.actions.push(action);
+    }
+
+    fn error(&mut self, source_path: &str, message: String) {
+        self.push(PlannedAction {
+            action: "error".to_string(),
+            source_path: source_path.to_string(),
+            destination_path: None,
+            is_dir: false,
+            size: 0,
+            file_count: 0,
+            has_conflict: false,
+            error: Some(message),
+        });
+    }
+
+    /// Summarizes the plan in the shape the executing command would return
+    pub fn into_result(self) -> FileOperationResult {
+        let mut last_error = self
+            .actions
+            .iter()
+            .rev()
+            .find_map(|action| action.error.clone());
+
+        if self.has_enough_space == Some(false) {
+            last_error = Some(insufficient_space_message(
+                self.required_bytes,
+                self.available_bytes.unwrap_or(0),
+                self.destination_volume.as_deref(),
+            ));
+        }
+
+        FileOperationResult {
+            success: last_error.is_none(),
+            error: last_error,
+            copied_count: Some(self.item_count),
+            failed_count: Some(self.error_count),
+            skipped_count: Some(self.skipped_count),
+            plan: Some(self),
+            ..Default::default()
+        }
+    }
+}
+
+// Total size and file count of a file or directory tree, without following symlinks
+fn measure(path: &Path) -> (u64, u64) {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return (0, 0),
+    };
+
+    if !metadata.is_dir() {
+        return (metadata.len(), 1);
+    }
+
+    walkdir::WalkDir::new(path)
+        .follow_links(false)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir())
+        .fold((0, 0), |(bytes, files), entry| {
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            (bytes + size, files + 1)
+        })
+}
+
+#[cfg(unix)]
+fn is_same_volume(first: &Path, second: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (fs::metadata(first), fs::metadata(second)) {
+        (Ok(first_metadata), Ok(second_metadata)) => first_metadata.dev() == second_metadata.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(windows)]
+fn is_same_volume(first: &Path, second: &Path) -> bool {
+    let volume_of = |path: &Path| {
+        path.components()
+            .next()
+            .map(|component| component.as_os_str().to_string_lossy().to_lowercase())
+    };
+
+    volume_of(first).is_some() && volume_of(first) == volume_of(second)
+}
+
+/// Plans a copy or move the same way `copy_items` / `move_items` execute it
+pub fn plan_transfer(
+    source_paths: &[String],
+    destination: &Path,
+    resolution: &ConflictResolution,
+    is_move: bool,
+) -> OperationPlan {
+    let mut plan = OperationPlan::new(if is_move { "move" } else { "copy" });
+
+    if !destination.is_dir() {
+        plan.error(
+            &destination.to_string_lossy(),
+            messages::DESTINATION_NOT_DIRECTORY.with("path", destination.display()).into(),
+        );
+        return plan;
+    }
+
+    let dest_normalized = normalize_path(&destination.to_string_lossy());
+    // Destinations claimed by earlier items in the same batch
+    let mut claimed_paths: HashSet<PathBuf> = HashSet::new();
+    let is_taken = |path: &Path, claimed_paths: &HashSet<PathBuf>| {
+        path.exists() || claimed_paths.contains(path)
+    };
+
+    for source_path_str in source_paths {
+        let source = &to_os_path(source_path_str);
+
+        if !source.exists() {
+            plan.error(
+                source_path_str,
+                messages::SOURCE_NOT_FOUND.with("path", source_path_str).into(),
+            );
+            continue;
+        }
+
+        let file_name = match source.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => {
+                plan.error(
+                    source_path_str,
+                    messages::SOURCE_INVALID.with("path", source_path_str).into(),
+                );
+                continue;
+            }
+        };
+
+        let is_same_directory = source
+            .parent()
+            .map(|parent| normalize_path(&parent.to_string_lossy()) == dest_normalized)
+            .unwrap_or(false);
+
+        // Moving into the same directory is a no-op
+        if is_move && is_same_directory {
+            continue;
+        }
+
+        let initial_dest = destination.join(&file_name);
+        let has_conflict = !is_same_directory && is_taken(&initial_dest, &claimed_paths);
+
+        let (action, dest_path) = if is_same_directory {
+            let unique_path = unique_destination_path_with(destination, &file_name, |path| {
+                is_taken(path, &claimed_paths)
+            });
+            ("copy", Some(unique_path))
+        } else if has_conflict {
+            match resolution {
+                ConflictResolution::Skip => ("skip", None),
+                ConflictResolution::Replace => ("replace", Some(initial_dest)),
+                ConflictResolution::AutoRename => {
+                    let unique_path = unique_destination_path_with(destination, &file_name, |path| {
+                        is_taken(path, &claimed_paths)
+                    });
+                    ("rename", Some(unique_path))
+                }
+            }
+        } else if is_move {
+            ("move", Some(initial_dest))
+        } else {
+            ("copy", Some(initial_dest))
+        };
+
+        let (size, file_count) = measure(source);
+
+        if let Some(dest_path) = &dest_path {
+            claimed_paths.insert(dest_path.clone());
+
+            if !is_move || !is_same_volume(source, destination) {
+                // Replaced items free their space as the new ones are written
+                let replaced_size = if action == "replace" {
+                    measure(dest_path).0
+                } else {
+                    0
+                };
+                plan.required_bytes += size.saturating_sub(replaced_size);
+            }
+        }
+
+        plan.push(PlannedAction {
+            action: action.to_string(),
+            source_path: source_path_str.clone(),
+            destination_path: dest_path.map(|path| path.to_string_lossy().to_string()),
+            is_dir: source.is_dir(),
+            size,
+            file_count,
+            has_conflict,
+            error: None,
+        });
+    }
+
+    let preflight = PreflightResult::new(destination, plan.required_bytes);
+    plan.available_bytes = preflight.available_bytes;
+    plan.has_enough_space = preflight.fits;
+    plan.destination_volume = preflight.destination_volume;
+
+    plan
+}
+
+/// Plans a deletion the same way `delete_items` executes it
+pub fn plan_delete(paths: &[String], use_trash: bool) -> OperationPlan {
+    let mut plan = OperationPlan::new(if use_trash { "trash" } else { "delete" });
+
+    for path_str in paths {
+        let path = &to_os_path(path_str);
+
+        if !path.exists() {
+            plan.error(path_str, messages::PATH_NOT_FOUND.with("path", path_str).into());
+            continue;
+        }
+
+        let (size, file_count) = measure(path);
+
+        plan.push(PlannedAction {
+            action: plan.operation.clone(),
+            source_path: path_str.clone(),
+            destination_path: None,
+            is_dir: path.is_dir(),
+            size,
+            file_count,
+            has_conflict: false,
+            error: None,
+        });
+    }
+
+    plan
+}
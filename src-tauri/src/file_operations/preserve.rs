@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Metadata kept on copies besides the contents, chosen by kind with
+//! `CopyOptions::preserve`. What the destination can't take, such as Unix
+//! owners without root or extended attributes on a FAT drive, doesn't fail
+//! the copy and is reported in the result instead.
+
+use crate::file_metadata::{preserve_attributes, AttributeKind};
+use crate::utils::normalize_path;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataIssue {
+    pub kind: AttributeKind,
+    pub error: String,
+    /// Items it happened for, `path` is the first of them
+    pub count: u32,
+    pub path: String,
+}
+
+/// Issues of one copy, grouped by kind and error, so a folder whose files
+/// all lose their extended attributes is a single entry
+#[derive(Debug, Default)]
+pub struct MetadataIssues(Mutex<Vec<MetadataIssue>>);
+
+impl MetadataIssues {
+    fn record(&self, kind: AttributeKind, path: &Path, error: String) {
+        let Ok(mut issues) = self.0.lock() else {
+            return;
+        };
+        match issues
+            .iter_mut()
+            .find(|issue| issue.kind == kind && issue.error == error)
+        {
+            Some(issue) => issue.count += 1,
+            None => issues.push(MetadataIssue {
+                kind,
+                error,
+                count: 1,
+                path: normalize_path(&path.to_string_lossy()),
+            }),
+        }
+    }
+
+    /// The issues recorded so far, `None` when there were none
+    pub fn take(&self) -> Option<Vec<MetadataIssue>> {
+        let issues = std::mem::take(&mut *self.0.lock().ok()?);
+        (!issues.is_empty()).then_some(issues)
+    }
+}
+
+/// Gives the copy at `destination` the `which` metadata of `source`.
+/// Folders should get theirs once everything inside them is copied, as
+/// adding to a folder changes its times.
+pub fn apply(source: &Path, destination: &Path, which: &[AttributeKind], issues: &MetadataIssues) {
+    if which.is_empty() {
+        return;
+    }
+
+    let metadata = match fs::metadata(source) {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            for kind in which {
+                issues.record(*kind, destination, error.to_string());
+            }
+            return;
+        }
+    };
+
+    for (kind, error) in preserve_attributes(source, &metadata, destination, which) {
+        issues.record(kind, destination, error.to_string());
+    }
+}
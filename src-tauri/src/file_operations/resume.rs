@@ -0,0 +1,333 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Copies and moves that can pick up where they stopped. Each task is
+//! stored with the destination of every item it started, and files are
+//! written to a `.part` file next to their destination that is renamed
+//! into place once complete. After a crash or a dropped network share,
+//! finished files are skipped and large ones continue from the length of
+//! their `.part` file.
+
+use super::{clone, remove_dir_or_file, CopyOptions};
+use crate::json_store;
+use crate::utils::normalize_path;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const STORE_NAME: &str = "transfer-tasks";
+const PART_EXTENSION: &str = "part";
+/// Smaller files are copied again from the start, which is faster than
+/// checking what a `.part` file holds
+const RESUMABLE_MIN_SIZE: u64 = 16 * 1024 * 1024;
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferKind {
+    Copy,
+    Move,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskItem {
+    pub source_path: String,
+    /// Set once the item's destination was chosen, so a resumed task
+    /// continues the same copy instead of resolving the conflict again
+    pub destination_path: Option<String>,
+    pub is_done: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferTask {
+    pub id: String,
+    pub kind: TransferKind,
+    pub destination_path: String,
+    pub conflict_resolution: Option<String>,
+    pub verify: Option<bool>,
+    #[serde(default)]
+    pub options: CopyOptions,
+    pub items: Vec<TaskItem>,
+    pub started_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TaskStore {
+    #[serde(default)]
+    tasks: Vec<TransferTask>,
+}
+
+static TASKS: Lazy<Mutex<TaskStore>> = Lazy::new(|| Mutex::new(json_store::load(STORE_NAME)));
+
+/// Tasks running in this session, which can't be resumed
+static ACTIVE_TASKS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn update_task(id: &str, update: impl FnOnce(&mut TransferTask)) {
+    if let Ok(mut store) = TASKS.lock() {
+        if let Some(task) = store.tasks.iter_mut().find(|task| task.id == id) {
+            update(task);
+            let _ = json_store::save(STORE_NAME, &*store);
+        }
+    }
+}
+
+fn update_item(id: &str, source: &Path, update: impl FnOnce(&mut TaskItem)) {
+    let source_path = normalize_path(&source.to_string_lossy());
+    update_task(id, |task| {
+        if let Some(item) = task
+            .items
+            .iter_mut()
+            .find(|item| item.source_path == source_path)
+        {
+            update(item);
+        }
+    });
+}
+
+/// Records a task before its first item starts and returns its id
+pub fn begin(
+    kind: TransferKind,
+    source_paths: &[String],
+    destination_path: &str,
+    conflict_resolution: Option<String>,
+    verify: Option<bool>,
+    options: &CopyOptions,
+) -> String {
+    let id = uuid::Uuid::new_v4().to_string();
+    mark_active(&id);
+
+    if let Ok(mut store) = TASKS.lock() {
+        store.tasks.push(TransferTask {
+            id: id.clone(),
+            kind,
+            destination_path: normalize_path(destination_path),
+            conflict_resolution,
+            verify,
+            options: options.clone(),
+            items: source_paths
+                .iter()
+                .map(|source_path| TaskItem {
+                    source_path: normalize_path(source_path),
+                    destination_path: None,
+                    is_done: false,
+                })
+                .collect(),
+            started_at: now_ms(),
+        });
+        let _ = json_store::save(STORE_NAME, &*store);
+    }
+
+    id
+}
+
+fn mark_active(id: &str) {
+    if let Ok(mut active_tasks) = ACTIVE_TASKS.lock() {
+        active_tasks.insert(id.to_string());
+    }
+}
+
+/// The destination an earlier run of the task chose for `source`
+pub fn item_destination(id: &str, source: &Path) -> Option<PathBuf> {
+    let source_path = normalize_path(&source.to_string_lossy());
+    let store = TASKS.lock().ok()?;
+    let task = store.tasks.iter().find(|task| task.id == id)?;
+    let item = task
+        .items
+        .iter()
+        .find(|item| item.source_path == source_path)?;
+    item.destination_path
+        .as_deref()
+        .map(crate::utils::to_os_path)
+}
+
+pub fn set_item_destination(id: &str, source: &Path, destination: &Path) {
+    let destination_path = normalize_path(&destination.to_string_lossy());
+    update_item(id, source, |item| {
+        item.destination_path = Some(destination_path)
+    });
+}
+
+/// Marks an item as copied, moved or skipped, which resuming leaves out
+pub fn complete_item(id: &str, source: &Path) {
+    update_item(id, source, |item| item.is_done = true);
+}
+
+/// Ends a run of the task. Tasks with items left stay stored for
+/// `resume_operation`, finished ones are dropped.
+pub fn end(id: &str) {
+    if let Ok(mut store) = TASKS.lock() {
+        let is_finished = store
+            .tasks
+            .iter()
+            .find(|task| task.id == id)
+            .is_some_and(|task| task.items.iter().all(|item| item.is_done));
+        if is_finished {
+            store.tasks.retain(|task| task.id != id);
+            let _ = json_store::save(STORE_NAME, &*store);
+        }
+    }
+
+    if let Ok(mut active_tasks) = ACTIVE_TASKS.lock() {
+        active_tasks.remove(id);
+    }
+}
+
+/// Tasks with items left that aren't running
+pub fn resumable_tasks() -> Result<Vec<TransferTask>, String> {
+    let active_tasks = ACTIVE_TASKS
+        .lock()
+        .map_err(|error| error.to_string())?
+        .clone();
+    Ok(TASKS
+        .lock()
+        .map_err(|error| error.to_string())?
+        .tasks
+        .iter()
+        .filter(|task| !active_tasks.contains(&task.id))
+        .cloned()
+        .collect())
+}
+
+/// Claims a stored task for a new run
+pub fn take_for_resume(id: &str) -> Result<TransferTask, String> {
+    let task = resumable_tasks()?
+        .into_iter()
+        .find(|task| task.id == id)
+        .ok_or_else(|| format!("No resumable operation with id {}", id))?;
+    mark_active(id);
+    Ok(task)
+}
+
+/// Forgets a task, deleting the partial copies of its unfinished items.
+/// The sources of moves are only deleted once their copy is complete, so
+/// nothing is lost.
+pub fn discard(id: &str) -> Result<(), String> {
+    let task = take_for_resume(id)?;
+
+    for item in task.items.iter().filter(|item| !item.is_done) {
+        let Some(destination_path) = &item.destination_path else {
+            continue;
+        };
+        let destination = crate::utils::to_os_path(destination_path);
+        let part = part_path(&destination);
+        if part.exists() {
+            let _ = fs::remove_file(&part);
+        }
+        if destination.exists() && Path::new(&item.source_path).exists() {
+            remove_dir_or_file(&destination)?;
+        }
+    }
+
+    if let Ok(mut store) = TASKS.lock() {
+        store.tasks.retain(|task| task.id != id);
+        let _ = json_store::save(STORE_NAME, &*store);
+    }
+    if let Ok(mut active_tasks) = ACTIVE_TASKS.lock() {
+        active_tasks.remove(id);
+    }
+    Ok(())
+}
+
+fn part_path(destination: &Path) -> PathBuf {
+    let mut name = destination.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(PART_EXTENSION);
+    destination.with_file_name(name)
+}
+
+/// Whether the `offset` bytes already in `part` end with the same bytes as
+/// `source` at that point. Catches sources changed since the earlier run
+/// and writes that didn't reach the disk before a crash.
+fn part_tail_matches(source: &mut File, part: &mut File, offset: u64) -> io::Result<bool> {
+    let tail_length = offset.min(COPY_CHUNK_SIZE as u64);
+    let tail_start = offset - tail_length;
+    let mut source_tail = vec![0u8; tail_length as usize];
+    let mut part_tail = vec![0u8; tail_length as usize];
+
+    source.seek(SeekFrom::Start(tail_start))?;
+    source.read_exact(&mut source_tail)?;
+    part.seek(SeekFrom::Start(tail_start))?;
+    part.read_exact(&mut part_tail)?;
+
+    Ok(source_tail == part_tail)
+}
+
+fn copy_from_offset(source: &Path, part: &Path, source_length: u64) -> io::Result<()> {
+    let mut reader = File::open(source)?;
+    let mut writer = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(part)?;
+
+    let mut offset = writer.metadata()?.len();
+    if offset > source_length || !part_tail_matches(&mut reader, &mut writer, offset)? {
+        offset = 0;
+    }
+    writer.set_len(offset)?;
+    reader.seek(SeekFrom::Start(offset))?;
+    writer.seek(SeekFrom::Start(offset))?;
+
+    let mut buffer = vec![0u8; COPY_CHUNK_SIZE];
+    loop {
+        let read_bytes = reader.read(&mut buffer)?;
+        if read_bytes == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read_bytes])?;
+    }
+
+    writer.sync_all()?;
+    fs::set_permissions(part, fs::metadata(source)?.permissions())
+}
+
+/// Copies a file through its `.part` file, as a clone where the volume
+/// supports it and with its holes when it is sparse. A destination that
+/// already has the source's size was completed by an earlier run and is
+/// kept. Returns the size of the file.
+pub fn copy_file(source: &Path, destination: &Path) -> io::Result<u64> {
+    let source_length = fs::metadata(source)?.len();
+    if fs::metadata(destination)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.len() == source_length)
+    {
+        return Ok(source_length);
+    }
+
+    let part = part_path(destination);
+    if clone::clone_file(source, &part).is_ok() {
+        fs::rename(&part, destination)?;
+        return Ok(source_length);
+    }
+    // Sparse files are copied again from the start, their holes aren't in
+    // the length of a `.part` file
+    if crate::sparse_files::copy_if_sparse(source, &part)? {
+        fs::rename(&part, destination)?;
+        return Ok(source_length);
+    }
+
+    if source_length < RESUMABLE_MIN_SIZE {
+        if let Err(error) = fs::copy(source, &part) {
+            let _ = fs::remove_file(&part);
+            return Err(error);
+        }
+    } else {
+        copy_from_offset(source, &part, source_length)?;
+    }
+
+    fs::rename(&part, destination)?;
+    Ok(source_length)
+}
@@ -0,0 +1,146 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// How often and how patiently transient IO errors are retried
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts per file, including the first one. 1 disables retries.
+    pub max_attempts: u32,
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_delay_ms: 250,
+            max_delay_ms: 5000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Doubles after every failed attempt, up to max_delay_ms
+    fn delay(&self, attempt: u32) -> Duration {
+        let delay_ms = self
+            .initial_delay_ms
+            .saturating_mul(1u64 << attempt.saturating_sub(1).min(16));
+        Duration::from_millis(delay_ms.min(self.max_delay_ms))
+    }
+}
+
+// OS error codes of failures that tend to go away on their own: dropped
+// network connections, devices that are briefly not ready, read errors on
+// flaky media
+#[cfg(windows)]
+const TRANSIENT_OS_ERRORS: &[i32] = &[
+    21,   // ERROR_NOT_READY
+    23,   // ERROR_CRC
+    31,   // ERROR_GEN_FAILURE
+    53,   // ERROR_BAD_NETPATH
+    59,   // ERROR_UNEXP_NET_ERR
+    64,   // ERROR_NETNAME_DELETED
+    121,  // ERROR_SEM_TIMEOUT
+    1117, // ERROR_IO_DEVICE
+    1231, // ERROR_NETWORK_UNREACHABLE
+];
+
+#[cfg(unix)]
+const TRANSIENT_OS_ERRORS: &[i32] = &[
+    libc::EIO,
+    libc::EAGAIN,
+    libc::EBUSY,
+    libc::ETIMEDOUT,
+    libc::ESTALE,
+    libc::EHOSTDOWN,
+    libc::EHOSTUNREACH,
+    libc::ENETDOWN,
+    libc::ENETRESET,
+    libc::ENETUNREACH,
+];
+
+#[cfg(not(any(windows, unix)))]
+const TRANSIENT_OS_ERRORS: &[i32] = &[];
+
+pub fn is_transient(error: &io::Error) -> bool {
+    if matches!(
+        error.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+    ) {
+        return true;
+    }
+
+    error
+        .raw_os_error()
+        .is_some_and(|code| TRANSIENT_OS_ERRORS.contains(&code))
+}
+
+/// Runs IO operations under a retry policy and remembers which paths needed
+/// more than one attempt
+#[derive(Debug, Default)]
+pub struct Retrier {
+    policy: RetryPolicy,
+    retried_paths: Vec<String>,
+}
+
+impl Retrier {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Retrier {
+            policy,
+            retried_paths: Vec::new(),
+        }
+    }
+
+    pub fn run<T, F>(&mut self, path: &Path, mut operation: F) -> io::Result<T>
+    where
+        F: FnMut() -> io::Result<T>,
+    {
+        let mut attempt: u32 = 1;
+
+        loop {
+            match operation() {
+                Ok(value) => return Ok(value),
+                Err(error) if attempt < self.policy.max_attempts && is_transient(&error) => {
+                    if attempt == 1 {
+                        self.retried_paths
+                            .push(crate::utils::normalize_path(&path.to_string_lossy()));
+                    }
+
+                    std::thread::sleep(self.policy.delay(attempt));
+                    attempt += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// A retrier with the same policy, for another thread
+    pub fn fork(&self) -> Self {
+        Retrier::new(self.policy.clone())
+    }
+
+    /// Takes over the retried paths of a forked retrier
+    pub fn absorb(&mut self, other: Retrier) {
+        self.retried_paths.extend(other.retried_paths);
+    }
+
+    /// Paths that failed at least once, `None` when everything worked first time
+    pub fn into_retried_paths(self) -> Option<Vec<String>> {
+        (!self.retried_paths.is_empty()).then_some(self.retried_paths)
+    }
+}
@@ -0,0 +1,246 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Salvage copy: a lightweight ddrescue for failing drives. Files are read in
+//! blocks; a block that can't be read is retried sector by sector, and
+//! sectors that stay unreadable are left zero-filled in the copy and reported
+//! as missing ranges.
+
+use crate::utils::normalize_path;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const DEFAULT_BLOCK_SIZE: usize = 1024 * 1024;
+const SECTOR_SIZE: usize = 4096;
+const DEFAULT_SECTOR_ATTEMPTS: u32 = 3;
+const SECTOR_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SalvageOptions {
+    pub block_size: usize,
+    /// Reads per unreadable sector before it's given up on
+    pub sector_attempts: u32,
+}
+
+impl Default for SalvageOptions {
+    fn default() -> Self {
+        SalvageOptions {
+            block_size: DEFAULT_BLOCK_SIZE,
+            sector_attempts: DEFAULT_SECTOR_ATTEMPTS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SalvagedFile {
+    pub source_path: String,
+    pub destination_path: String,
+    pub size: u64,
+    pub recovered_bytes: u64,
+    pub missing_bytes: u64,
+    /// Ranges that couldn't be read and are zero-filled in the copy
+    pub missing_ranges: Vec<ByteRange>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SalvageResult {
+    pub success: bool,
+    pub error: Option<String>,
+    pub files: Vec<SalvagedFile>,
+    pub complete_count: u32,
+    pub partial_count: u32,
+    pub failed_count: u32,
+}
+
+fn read_at(file: &mut File, offset: u64, buffer: &mut [u8]) -> io::Result<usize> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read_bytes) => filled += read_bytes,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(filled)
+}
+
+fn push_missing(missing_ranges: &mut Vec<ByteRange>, start: u64, end: u64) {
+    // Adjacent bad sectors are reported as one range
+    if let Some(last_range) = missing_ranges.last_mut() {
+        if last_range.end == start {
+            last_range.end = end;
+            return;
+        }
+    }
+
+    missing_ranges.push(ByteRange { start, end });
+}
+
+fn salvage_file(source: &Path, destination: &Path, options: &SalvageOptions) -> SalvagedFile {
+    let mut report = SalvagedFile {
+        source_path: normalize_path(&source.to_string_lossy()),
+        destination_path: normalize_path(&destination.to_string_lossy()),
+        size: 0,
+        recovered_bytes: 0,
+        missing_bytes: 0,
+        missing_ranges: Vec::new(),
+        error: None,
+    };
+
+    let result = (|| -> io::Result<()> {
+        let mut source_file = File::open(source)?;
+        let size = source_file.metadata()?.len();
+        report.size = size;
+
+        let mut destination_file = File::create(destination)?;
+        // Unreadable ranges are simply never written and stay zero-filled
+        destination_file.set_len(size)?;
+
+        let block_size = options.block_size.max(SECTOR_SIZE);
+        let mut buffer = vec![0u8; block_size];
+        let mut offset: u64 = 0;
+
+        while offset < size {
+            let length = block_size.min((size - offset) as usize);
+
+            if let Ok(read_bytes) = read_at(&mut source_file, offset, &mut buffer[..length]) {
+                destination_file.seek(SeekFrom::Start(offset))?;
+                destination_file.write_all(&buffer[..read_bytes])?;
+                report.recovered_bytes += read_bytes as u64;
+
+                // The file got shorter while reading
+                if read_bytes < length {
+                    push_missing(&mut report.missing_ranges, offset + read_bytes as u64, size);
+                    break;
+                }
+
+                offset += length as u64;
+                continue;
+            }
+
+            // Retry the failed block sector by sector so one bad sector
+            // doesn't cost the whole block
+            let block_end = offset + length as u64;
+            let mut sector_offset = offset;
+
+            while sector_offset < block_end {
+                let sector_length = SECTOR_SIZE.min((block_end - sector_offset) as usize);
+                let sector_buffer = &mut buffer[..sector_length];
+                let mut read_bytes = None;
+
+                for attempt in 0..options.sector_attempts.max(1) {
+                    if attempt > 0 {
+                        std::thread::sleep(SECTOR_RETRY_DELAY);
+                    }
+
+                    if let Ok(bytes) = read_at(&mut source_file, sector_offset, sector_buffer) {
+                        read_bytes = Some(bytes);
+                        break;
+                    }
+                }
+
+                match read_bytes {
+                    Some(bytes) => {
+                        destination_file.seek(SeekFrom::Start(sector_offset))?;
+                        destination_file.write_all(&sector_buffer[..bytes])?;
+                        report.recovered_bytes += bytes as u64;
+                    }
+                    None => push_missing(
+                        &mut report.missing_ranges,
+                        sector_offset,
+                        sector_offset + sector_length as u64,
+                    ),
+                }
+
+                sector_offset += sector_length as u64;
+            }
+
+            offset = block_end;
+        }
+
+        destination_file.sync_all()
+    })();
+
+    report.missing_bytes = report
+        .missing_ranges
+        .iter()
+        .map(|range| range.end - range.start)
+        .sum();
+
+    if let Err(error) = result {
+        report.error = Some(error.to_string());
+    }
+
+    report
+}
+
+/// Salvages a file or a whole directory tree into `destination`
+pub fn salvage_item(
+    source: &Path,
+    destination: &Path,
+    options: &SalvageOptions,
+    result: &mut SalvageResult,
+) {
+    if !source.is_dir() {
+        let file_report = salvage_file(source, destination, options);
+
+        if file_report.error.is_some() {
+            result.failed_count += 1;
+            result.error = file_report.error.clone();
+        } else if file_report.missing_bytes > 0 {
+            result.partial_count += 1;
+        } else {
+            result.complete_count += 1;
+        }
+
+        result.files.push(file_report);
+        return;
+    }
+
+    if let Err(error) = fs::create_dir_all(destination) {
+        result.failed_count += 1;
+        result.error = Some(format!("{}: {}", destination.display(), error));
+        return;
+    }
+
+    // Unlistable directories are skipped, the rest of the tree may still be readable
+    let entries = match fs::read_dir(source) {
+        Ok(entries) => entries,
+        Err(error) => {
+            result.failed_count += 1;
+            result.error = Some(format!("{}: {}", source.display(), error));
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let entry_path = entry.path();
+
+        if entry_path.is_symlink() {
+            continue;
+        }
+
+        salvage_item(
+            &entry_path,
+            &destination.join(entry.file_name()),
+            options,
+            result,
+        );
+    }
+}
@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Checksum verification of copies. A move across volumes is a copy
+//! followed by a delete, so the copy is hashed against its source before
+//! the source goes away. Copies are verified on request, with a report of
+//! every file that doesn't match.
+
+use crate::messages;
+use crate::utils::{normalize_path, volume_space};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+const HASH_BUFFER_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationMismatch {
+    pub source: String,
+    pub destination: String,
+    /// Why the file couldn't be hashed, when it wasn't a differing hash
+    pub error: Option<String>,
+}
+
+/// What verifying the copies of a job found
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerificationReport {
+    /// Files whose copy matched their source
+    pub verified_count: u32,
+    pub verified_bytes: u64,
+    pub mismatches: Vec<VerificationMismatch>,
+}
+
+pub fn file_hash(path: &Path) -> io::Result<blake3::Hash> {
+    let mut file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+
+    loop {
+        let read_bytes = file.read(&mut buffer)?;
+        if read_bytes == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read_bytes]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+fn verify_file(source: &Path, destination: &Path) -> Result<(), String> {
+    let source_hash = file_hash(source).map_err(|error| error.to_string())?;
+    let destination_hash = file_hash(destination).map_err(|error| error.to_string())?;
+
+    if source_hash != destination_hash {
+        return Err(messages::CHECKSUM_MISMATCH
+            .with("path", source.to_string_lossy())
+            .into());
+    }
+
+    Ok(())
+}
+
+/// Checks that every file under `source` has an identical copy at the same
+/// place under `destination`
+pub fn verify_copy(source: &Path, destination: &Path) -> Result<(), String> {
+    if !source.is_dir() {
+        return verify_file(source, destination);
+    }
+
+    for entry in walkdir::WalkDir::new(source) {
+        let entry = entry.map_err(|error| error.to_string())?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+
+        let relative_path = entry
+            .path()
+            .strip_prefix(source)
+            .map_err(|error| error.to_string())?;
+        verify_file(entry.path(), &destination.join(relative_path))?;
+    }
+
+    Ok(())
+}
+
+impl VerificationReport {
+    fn check_file(&mut self, source: &Path, destination: &Path) -> bool {
+        let hashes = file_hash(source).and_then(|source_hash| {
+            file_hash(destination).map(|destination_hash| (source_hash, destination_hash))
+        });
+
+        let error = match hashes {
+            Ok((source_hash, destination_hash)) if source_hash == destination_hash => {
+                self.verified_count += 1;
+                self.verified_bytes += destination.metadata().map_or(0, |metadata| metadata.len());
+                return true;
+            }
+            Ok(_) => None,
+            Err(error) => Some(error.to_string()),
+        };
+
+        self.mismatches.push(VerificationMismatch {
+            source: normalize_path(&source.to_string_lossy()),
+            destination: normalize_path(&destination.to_string_lossy()),
+            error,
+        });
+        false
+    }
+
+    /// Hashes every file under `source` against its copy under
+    /// `destination`, recording the ones that differ instead of stopping at
+    /// the first. Returns whether all of them matched.
+    pub fn check_copy(&mut self, source: &Path, destination: &Path) -> bool {
+        if !source.is_dir() {
+            return self.check_file(source, destination);
+        }
+
+        let mut all_match = true;
+        for entry in walkdir::WalkDir::new(source) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    let path = error.path().unwrap_or(source).to_path_buf();
+                    self.mismatches.push(VerificationMismatch {
+                        source: normalize_path(&path.to_string_lossy()),
+                        destination: normalize_path(&destination.to_string_lossy()),
+                        error: Some(error.to_string()),
+                    });
+                    all_match = false;
+                    continue;
+                }
+            };
+            if entry.file_type().is_dir() {
+                continue;
+            }
+
+            let Ok(relative_path) = entry.path().strip_prefix(source) else {
+                continue;
+            };
+            all_match &= self.check_file(entry.path(), &destination.join(relative_path));
+        }
+
+        all_match
+    }
+}
+
+/// Whether moves to `destination` should be verified when no choice was
+/// made: removable drives can be pulled and network shares can drop
+/// mid-copy. Volumes that can't be identified are verified too.
+pub fn verifies_by_default(destination: &Path) -> bool {
+    volume_space(destination)
+        .map(|volume| volume.is_removable || volume.is_network)
+        .unwrap_or(true)
+}
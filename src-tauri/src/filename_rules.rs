@@ -0,0 +1,510 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Names that are illegal or troublesome on Windows and FAT/exFAT volumes,
+//! typically created on Linux/macOS and later copied to a USB stick, and
+//! checking new names against the rules of the file system they go to.
+
+use crate::file_operations::FileOperationResult;
+use crate::utils::{normalize_path, to_os_path, to_verbatim_path};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const WINDOWS_FORBIDDEN_CHARACTERS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+const MAX_NAME_UTF16_LENGTH: usize = 255;
+const MAX_NAME_BYTES: usize = 255;
+/// Explorer and most Windows apps can't open longer paths
+const MAX_WINDOWS_PATH_LENGTH: usize = 260;
+const MAX_UNIX_PATH_BYTES: usize = 4096;
+const MAX_APPLE_PATH_BYTES: usize = 1024;
+/// How long the file system found for a destination folder is reused.
+/// Dialogs validate on every keystroke, and finding it lists all mounts.
+const TARGET_FS_CACHE_TTL: Duration = Duration::from_secs(30);
+
+static TARGET_FS_CACHE: Lazy<Mutex<HashMap<PathBuf, (TargetFs, Instant)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// File system families, by the naming rules they share
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetFs {
+    /// NTFS and SMB shares, and any volume on Windows
+    Ntfs,
+    /// FAT32 and exFAT, as on USB sticks and memory cards
+    Fat,
+    /// APFS and HFS+
+    Apple,
+    /// ext4, Btrfs, XFS and the other Unix file systems
+    Unix,
+}
+
+impl TargetFs {
+    fn from_name(file_system: &str) -> Self {
+        let target_fs = match file_system.to_lowercase().as_str() {
+            "vfat" | "fat" | "fat12" | "fat16" | "fat32" | "msdos" | "exfat" => TargetFs::Fat,
+            // fuseblk is almost always NTFS through ntfs-3g
+            "ntfs" | "ntfs3" | "fuseblk" | "cifs" | "smbfs" | "smb3" => TargetFs::Ntfs,
+            "apfs" | "hfs" | "hfsplus" => TargetFs::Apple,
+            _ => TargetFs::Unix,
+        };
+
+        // Windows applies its own rules on top of any file system
+        if cfg!(windows) && target_fs != TargetFs::Fat {
+            return TargetFs::Ntfs;
+        }
+        target_fs
+    }
+
+    fn of_host() -> Self {
+        if cfg!(windows) {
+            TargetFs::Ntfs
+        } else if cfg!(target_os = "macos") {
+            TargetFs::Apple
+        } else {
+            TargetFs::Unix
+        }
+    }
+
+    fn is_windows_like(self) -> bool {
+        matches!(self, TargetFs::Ntfs | TargetFs::Fat)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameViolationKind {
+    Empty,
+    /// "." or ".."
+    DotName,
+    ReservedName,
+    ForbiddenCharacter,
+    TrailingDotOrSpace,
+    TooLong,
+    /// The name is fine, but the path it makes in the destination isn't
+    PathTooLong,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameViolation {
+    pub kind: NameViolationKind,
+    /// The forbidden characters found, or the limit that was exceeded
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilenameValidation {
+    pub is_valid: bool,
+    pub target_fs: TargetFs,
+    pub violations: Vec<NameViolation>,
+    /// A valid name close to the given one, `None` when that one is valid
+    pub suggested_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProblematicName {
+    pub path: String,
+    pub name: String,
+    pub is_dir: bool,
+    /// "reserved_name", "forbidden_character", "trailing_dot_or_space", "too_long"
+    pub issues: Vec<String>,
+    pub suggested_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameFix {
+    pub path: String,
+    pub new_name: String,
+}
+
+// "NUL.txt" and "com1.tar.gz" are reserved too, only the part before the
+// first dot counts
+fn is_reserved_name(name: &str) -> bool {
+    let base_name = name.split('.').next().unwrap_or(name).trim_end();
+    WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(base_name))
+}
+
+fn is_forbidden_character(character: char) -> bool {
+    WINDOWS_FORBIDDEN_CHARACTERS.contains(&character) || (character as u32) < 0x20
+}
+
+/// Lists the ways `name` is invalid on Windows and FAT volumes
+pub fn windows_name_issues(name: &str) -> Vec<String> {
+    let mut issues: Vec<String> = Vec::new();
+
+    if is_reserved_name(name) {
+        issues.push("reserved_name".to_string());
+    }
+
+    if name.chars().any(is_forbidden_character) {
+        issues.push("forbidden_character".to_string());
+    }
+
+    if name.ends_with('.') || name.ends_with(' ') {
+        issues.push("trailing_dot_or_space".to_string());
+    }
+
+    if name.encode_utf16().count() > MAX_NAME_UTF16_LENGTH {
+        issues.push("too_long".to_string());
+    }
+
+    issues
+}
+
+/// Shortens `name` from the end of its stem until `fits`, keeping the
+/// extension
+fn truncate_keeping_extension(name: &mut String, fits: impl Fn(&str) -> bool) {
+    while !fits(name) && !name.is_empty() {
+        let cut_index = match name.rfind('.') {
+            Some(dot_index) if dot_index > 0 => dot_index,
+            _ => name.len(),
+        };
+        let remove_at = name[..cut_index]
+            .char_indices()
+            .last()
+            .map(|(index, _)| index)
+            .unwrap_or(0);
+        name.remove(remove_at);
+    }
+}
+
+fn fits_utf16_limit(name: &str) -> bool {
+    name.encode_utf16().count() <= MAX_NAME_UTF16_LENGTH
+}
+
+fn fits_byte_limit(name: &str) -> bool {
+    name.len() <= MAX_NAME_BYTES
+}
+
+/// Turns `name` into one that is valid on Windows, keeping it recognizable
+pub fn sanitize_windows_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|character| {
+            if is_forbidden_character(character) {
+                '_'
+            } else {
+                character
+            }
+        })
+        .collect();
+
+    sanitized = sanitized.trim_end_matches(['.', ' ']).to_string();
+
+    if sanitized.is_empty() {
+        sanitized = "_".to_string();
+    }
+
+    if is_reserved_name(&sanitized) {
+        sanitized = match sanitized.find('.') {
+            Some(dot_index) => format!("{}_{}", &sanitized[..dot_index], &sanitized[dot_index..]),
+            None => format!("{}_", sanitized),
+        };
+    }
+
+    truncate_keeping_extension(&mut sanitized, fits_utf16_limit);
+    sanitized
+}
+
+fn forbidden_characters(name: &str, target_fs: TargetFs) -> Vec<char> {
+    let mut found: Vec<char> = Vec::new();
+    for character in name.chars() {
+        let is_forbidden = match target_fs {
+            TargetFs::Ntfs | TargetFs::Fat => is_forbidden_character(character),
+            // Finder shows colons as slashes, and HFS+ stores them so
+            TargetFs::Apple => matches!(character, '/' | ':' | '\0'),
+            TargetFs::Unix => matches!(character, '/' | '\0'),
+        };
+        if is_forbidden && !found.contains(&character) {
+            found.push(character);
+        }
+    }
+    found
+}
+
+fn violation(kind: NameViolationKind, detail: Option<String>) -> NameViolation {
+    NameViolation { kind, detail }
+}
+
+fn name_violations(name: &str, target_fs: TargetFs) -> Vec<NameViolation> {
+    if name.is_empty() {
+        return vec![violation(NameViolationKind::Empty, None)];
+    }
+    if name == "." || name == ".." {
+        return vec![violation(NameViolationKind::DotName, None)];
+    }
+
+    let mut violations: Vec<NameViolation> = Vec::new();
+
+    if target_fs.is_windows_like() && is_reserved_name(name) {
+        violations.push(violation(NameViolationKind::ReservedName, None));
+    }
+
+    let forbidden = forbidden_characters(name, target_fs);
+    if !forbidden.is_empty() {
+        let detail = forbidden
+            .iter()
+            .map(|character| character.escape_debug().to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+        violations.push(violation(
+            NameViolationKind::ForbiddenCharacter,
+            Some(detail),
+        ));
+    }
+
+    if target_fs.is_windows_like() && (name.ends_with('.') || name.ends_with(' ')) {
+        violations.push(violation(NameViolationKind::TrailingDotOrSpace, None));
+    }
+
+    let fits = match target_fs {
+        TargetFs::Unix => fits_byte_limit(name),
+        _ => fits_utf16_limit(name),
+    };
+    if !fits {
+        violations.push(violation(
+            NameViolationKind::TooLong,
+            Some(MAX_NAME_BYTES.to_string()),
+        ));
+    }
+
+    violations
+}
+
+/// The full path length limit of `target_fs`, and whether `path` is over it
+fn path_length_violation(path: &Path, target_fs: TargetFs) -> Option<NameViolation> {
+    let path = path.to_string_lossy();
+    let (length, limit) = match target_fs {
+        TargetFs::Ntfs | TargetFs::Fat => (path.encode_utf16().count(), MAX_WINDOWS_PATH_LENGTH),
+        TargetFs::Apple => (path.len(), MAX_APPLE_PATH_BYTES),
+        TargetFs::Unix => (path.len(), MAX_UNIX_PATH_BYTES),
+    };
+
+    (length > limit).then(|| violation(NameViolationKind::PathTooLong, Some(limit.to_string())))
+}
+
+fn sanitize_name(name: &str, target_fs: TargetFs) -> String {
+    if target_fs.is_windows_like() {
+        return sanitize_windows_name(name);
+    }
+
+    let forbidden = forbidden_characters(name, target_fs);
+    let mut sanitized: String = name
+        .chars()
+        .map(|character| {
+            if forbidden.contains(&character) {
+                '_'
+            } else {
+                character
+            }
+        })
+        .collect();
+
+    if sanitized.is_empty() || sanitized == "." || sanitized == ".." {
+        sanitized = "_".to_string();
+    }
+    match target_fs {
+        TargetFs::Unix => truncate_keeping_extension(&mut sanitized, fits_byte_limit),
+        _ => truncate_keeping_extension(&mut sanitized, fits_utf16_limit),
+    }
+    sanitized
+}
+
+/// The file system family of the volume `destination` is on, remembered
+/// per folder for a while
+fn destination_target_fs(destination: &Path) -> Option<TargetFs> {
+    if let Ok(cache) = TARGET_FS_CACHE.lock() {
+        if let Some((target_fs, found_at)) = cache.get(destination) {
+            if found_at.elapsed() < TARGET_FS_CACHE_TTL {
+                return Some(*target_fs);
+            }
+        }
+    }
+
+    let volume = crate::utils::volume_space(destination)?;
+    let target_fs = TargetFs::from_name(&volume.file_system);
+
+    if let Ok(mut cache) = TARGET_FS_CACHE.lock() {
+        cache.retain(|_, (_, found_at)| found_at.elapsed() < TARGET_FS_CACHE_TTL);
+        cache.insert(destination.to_path_buf(), (target_fs, Instant::now()));
+    }
+    Some(target_fs)
+}
+
+/// Checks a new name against the rules of the file system it goes to, for
+/// rename and new item dialogs. That file system is `target_fs`, or else
+/// the one `destination`, the folder the item goes in, is on. With a
+/// `destination`, the length of the whole path is checked too.
+#[tauri::command]
+pub async fn validate_filename(
+    name: String,
+    target_fs: Option<TargetFs>,
+    destination: Option<String>,
+) -> Result<FilenameValidation, String> {
+    tokio::task::spawn_blocking(move || {
+        let destination = destination.map(|destination| to_os_path(&destination));
+        let target_fs = target_fs
+            .or_else(|| destination_target_fs(destination.as_deref()?))
+            .unwrap_or_else(TargetFs::of_host);
+
+        let mut violations = name_violations(&name, target_fs);
+        if let Some(destination) = &destination {
+            let path = destination.join(&name);
+            violations.extend(path_length_violation(&path, target_fs));
+        }
+
+        // A shorter name can't fix a path that is too long on its own
+        let is_name_invalid = violations
+            .iter()
+            .any(|violation| violation.kind != NameViolationKind::PathTooLong);
+        let suggested_name = is_name_invalid.then(|| sanitize_name(&name, target_fs));
+
+        FilenameValidation {
+            is_valid: violations.is_empty(),
+            target_fs,
+            violations,
+            suggested_name,
+        }
+    })
+    .await
+    .map_err(|error| error.to_string())
+}
+
+fn unique_name(parent: &Path, name: &str, claimed: &HashSet<PathBuf>) -> String {
+    let is_taken = |candidate: &str| {
+        let candidate_path = parent.join(candidate);
+        candidate_path.exists() || claimed.contains(&candidate_path)
+    };
+
+    if !is_taken(name) {
+        return name.to_string();
+    }
+
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot_index) if dot_index > 0 => (&name[..dot_index], &name[dot_index..]),
+        _ => (name, ""),
+    };
+
+    (1..)
+        .map(|counter| format!("{} ({}){}", stem, counter, extension))
+        .find(|candidate| !is_taken(candidate))
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Finds names in a tree that are illegal or problematic on Windows/FAT,
+/// each with a suggested replacement
+#[tauri::command]
+pub async fn scan_problematic_names(path: String) -> Result<Vec<ProblematicName>, String> {
+    tokio::task::spawn_blocking(move || {
+        let root = to_verbatim_path(&path);
+
+        if !root.exists() {
+            return Err(format!("Path does not exist: {}", path));
+        }
+
+        let mut results: Vec<ProblematicName> = Vec::new();
+        let mut claimed: HashSet<PathBuf> = HashSet::new();
+
+        for entry in walkdir::WalkDir::new(&root)
+            .follow_links(false)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+        {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let issues = windows_name_issues(&name);
+
+            if issues.is_empty() {
+                continue;
+            }
+
+            let parent = entry.path().parent().unwrap_or(&root);
+            let suggested_name = unique_name(parent, &sanitize_windows_name(&name), &claimed);
+            claimed.insert(parent.join(&suggested_name));
+
+            results.push(ProblematicName {
+                path: normalize_path(&entry.path().to_string_lossy()),
+                name,
+                is_dir: entry.file_type().is_dir(),
+                issues,
+                suggested_name,
+            });
+        }
+
+        Ok(results)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Renames items to the (possibly user-edited) names from
+/// `scan_problematic_names`. Deepest items are renamed first so renaming a
+/// folder doesn't invalidate the paths of items inside it.
+#[tauri::command]
+pub fn fix_problematic_names(fixes: Vec<NameFix>) -> FileOperationResult {
+    let mut fixes = fixes;
+    fixes.sort_by_key(|fix| std::cmp::Reverse(fix.path.matches('/').count()));
+
+    let mut renamed_count: u32 = 0;
+    let mut failed_count: u32 = 0;
+    let mut last_error: Option<String> = None;
+
+    for fix in &fixes {
+        let issues = windows_name_issues(&fix.new_name);
+
+        if !issues.is_empty() {
+            failed_count += 1;
+            last_error = Some(format!(
+                "'{}' is still not a valid name ({})",
+                fix.new_name,
+                issues.join(", ")
+            ));
+            continue;
+        }
+
+        let source = to_verbatim_path(&fix.path);
+        let destination = match source.parent() {
+            Some(parent) => parent.join(&fix.new_name),
+            None => {
+                failed_count += 1;
+                last_error = Some(format!("Invalid path: {}", fix.path));
+                continue;
+            }
+        };
+
+        if destination.exists() {
+            failed_count += 1;
+            last_error = Some(format!(
+                "A file or folder with the name '{}' already exists",
+                fix.new_name
+            ));
+            continue;
+        }
+
+        match std::fs::rename(&source, &destination) {
+            Ok(()) => {
+                crate::tags::handle_path_moved(Path::new(&fix.path), &destination);
+                renamed_count += 1;
+            }
+            Err(error) => {
+                failed_count += 1;
+                last_error = Some(format!("{}: {}", fix.path, error));
+            }
+        }
+    }
+
+    FileOperationResult {
+        error: last_error,
+        ..FileOperationResult::counts(renamed_count, failed_count, 0)
+    }
+}
@@ -0,0 +1,432 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Folder sizes kept up to date by the global search index. Scans add up
+//! the sizes of the files they walk for every folder on the way, and the
+//! totals are then kept current from directory watcher events, so listings
+//! of indexed locations show folder sizes without walking anything.
+//!
+//! Only folders whose whole subtree was walked are kept: a folder below the
+//! scan depth, with an ignored or unreadable folder inside, or containing
+//! such a folder has no known size and is calculated on demand instead.
+
+use crate::dir_reader::DirEntry;
+use crate::utils::normalize_path;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+const STORE_FILE_NAME: &str = "folder-sizes.json";
+/// Watcher updates are saved at most this often, scans save right away
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FolderTotals {
+    pub size: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+}
+
+impl FolderTotals {
+    /// Replaces `old` with `new` in totals that include it
+    fn replace(&mut self, old: &FolderTotals, new: &FolderTotals) {
+        self.size = (self.size + new.size).saturating_sub(old.size);
+        self.file_count = (self.file_count + new.file_count).saturating_sub(old.file_count);
+        self.dir_count = (self.dir_count + new.dir_count).saturating_sub(old.dir_count);
+    }
+}
+
+/// Totals of every fully known folder, by normalized path
+static FOLDER_SIZES: Lazy<RwLock<BTreeMap<String, FolderTotals>>> =
+    Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+#[derive(Default)]
+struct StoreState {
+    path: Option<PathBuf>,
+    last_saved: Option<Instant>,
+    has_unsaved_changes: bool,
+}
+
+static STORE_STATE: Lazy<Mutex<StoreState>> = Lazy::new(|| Mutex::new(StoreState::default()));
+
+fn key(path: &Path) -> String {
+    normalize_path(&path.to_string_lossy())
+}
+
+/// Prefix of the keys of everything inside `folder`
+pub(crate) fn child_prefix(folder: &str) -> String {
+    if folder.ends_with('/') {
+        folder.to_string()
+    } else {
+        format!("{}/", folder)
+    }
+}
+
+/// Adds up the totals of the folders below `root` while it is walked
+pub struct FolderSizesBuilder {
+    root: String,
+    totals: HashMap<String, FolderTotals>,
+    incomplete: HashSet<String>,
+}
+
+impl FolderSizesBuilder {
+    pub fn new(root: &Path) -> Self {
+        let root = key(root);
+        let mut totals = HashMap::new();
+        totals.insert(root.clone(), FolderTotals::default());
+
+        FolderSizesBuilder {
+            root,
+            totals,
+            incomplete: HashSet::new(),
+        }
+    }
+
+    // Ancestors up to and including the root
+    fn ancestors(&self, path: &Path) -> Vec<String> {
+        path.ancestors()
+            .skip(1)
+            .map(key)
+            .take_while(|ancestor| ancestor.len() >= self.root.len())
+            .collect()
+    }
+
+    pub fn add_entry(&mut self, path: &Path, metadata: &fs::Metadata) {
+        let is_dir = metadata.is_dir();
+        if is_dir {
+            self.totals.entry(key(path)).or_default();
+        } else if !metadata.is_file() {
+            return;
+        }
+
+        for ancestor in self.ancestors(path) {
+            let totals = self.totals.entry(ancestor).or_default();
+            if is_dir {
+                totals.dir_count += 1;
+            } else {
+                totals.size += metadata.len();
+                totals.file_count += 1;
+            }
+        }
+    }
+
+    /// Marks a folder whose contents weren't walked, which leaves it and
+    /// every folder containing it without a known size
+    pub fn mark_incomplete(&mut self, path: &Path) {
+        self.incomplete.insert(key(path));
+    }
+
+    fn finish(self) -> (String, HashMap<String, FolderTotals>) {
+        let FolderSizesBuilder {
+            root,
+            mut totals,
+            incomplete,
+        } = self;
+
+        let mut unknown: HashSet<String> = HashSet::new();
+        for path in incomplete {
+            for ancestor in Path::new(&path).ancestors().map(key) {
+                if ancestor.len() < root.len() || !unknown.insert(ancestor) {
+                    break;
+                }
+            }
+        }
+
+        totals.retain(|path, _| !unknown.contains(path));
+        (root, totals)
+    }
+}
+
+/// Walks a folder that appeared since the scan
+fn walk_folder(path: &Path) -> HashMap<String, FolderTotals> {
+    let mut builder = FolderSizesBuilder::new(path);
+
+    for entry in WalkDir::new(path).min_depth(1) {
+        match entry {
+            Ok(entry) => {
+                if let Ok(metadata) = entry.metadata() {
+                    builder.add_entry(entry.path(), &metadata);
+                }
+            }
+            Err(error) => builder.mark_incomplete(error.path().unwrap_or(path)),
+        }
+    }
+
+    builder.finish().1
+}
+
+fn save(force: bool) {
+    let Ok(mut store_state) = STORE_STATE.lock() else {
+        return;
+    };
+    let Some(path) = store_state.path.clone() else {
+        return;
+    };
+
+    store_state.has_unsaved_changes = true;
+    let is_due = store_state
+        .last_saved
+        .is_none_or(|last_saved| last_saved.elapsed() >= SAVE_INTERVAL);
+    if !force && !is_due {
+        return;
+    }
+
+    let json = match FOLDER_SIZES.read() {
+        Ok(folder_sizes) => serde_json::to_string(&*folder_sizes),
+        Err(_) => return,
+    };
+    let result = json
+        .map_err(|error| error.to_string())
+        .and_then(|json| fs::write(&path, json).map_err(|error| error.to_string()));
+
+    match result {
+        Ok(()) => {
+            store_state.last_saved = Some(Instant::now());
+            store_state.has_unsaved_changes = false;
+        }
+        Err(error) => tracing::warn!("Failed to save folder sizes: {}", error),
+    }
+}
+
+/// Saves watcher updates still waiting for the save interval
+pub fn flush() {
+    let has_unsaved_changes = STORE_STATE
+        .lock()
+        .map(|store_state| store_state.has_unsaved_changes)
+        .unwrap_or(false);
+
+    if has_unsaved_changes {
+        save(true);
+    }
+}
+
+/// Loads the sizes saved with the index in `index_base_dir`, or forgets
+/// them when the index itself isn't usable
+pub fn load(index_base_dir: &Path, is_index_valid: bool) {
+    let path = index_base_dir.join(STORE_FILE_NAME);
+    let folder_sizes: BTreeMap<String, FolderTotals> = if is_index_valid {
+        fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    } else {
+        let _ = fs::remove_file(&path);
+        BTreeMap::new()
+    };
+
+    if let Ok(mut sizes) = FOLDER_SIZES.write() {
+        *sizes = folder_sizes;
+    }
+    if let Ok(mut store_state) = STORE_STATE.lock() {
+        store_state.path = Some(path);
+    }
+}
+
+/// Replaces all sizes with those of a full scan
+pub fn replace_all(builders: Vec<FolderSizesBuilder>) {
+    if let Ok(mut folder_sizes) = FOLDER_SIZES.write() {
+        folder_sizes.clear();
+        for builder in builders {
+            folder_sizes.extend(builder.finish().1);
+        }
+    }
+    save(true);
+}
+
+// Drops a folder and the folders containing it, whose totals no longer add up
+fn forget_with_ancestors(folder_sizes: &mut BTreeMap<String, FolderTotals>, path: &Path) {
+    for ancestor in path.ancestors().map(key) {
+        if folder_sizes.remove(&ancestor).is_none() {
+            break;
+        }
+    }
+}
+
+fn remove_subtree(folder_sizes: &mut BTreeMap<String, FolderTotals>, folder: &str) {
+    let prefix = child_prefix(folder);
+    let inner_keys: Vec<String> = folder_sizes
+        .range(prefix.clone()..)
+        .take_while(|(path, _)| path.starts_with(&prefix))
+        .map(|(path, _)| path.clone())
+        .collect();
+
+    for path in inner_keys {
+        folder_sizes.remove(&path);
+    }
+    folder_sizes.remove(folder);
+}
+
+/// Carries a folder's change from `old` to `new` totals over to the folders
+/// containing it
+fn update_ancestors(
+    folder_sizes: &mut BTreeMap<String, FolderTotals>,
+    path: &Path,
+    old: &FolderTotals,
+    new: &FolderTotals,
+) {
+    // Known folders only ever contain known folders, so the first unknown
+    // ancestor ends the chain
+    for ancestor in path.ancestors().skip(1).map(key) {
+        match folder_sizes.get_mut(&ancestor) {
+            Some(totals) => totals.replace(old, new),
+            None => break,
+        }
+    }
+}
+
+/// Replaces the sizes below the root of a partial scan
+pub fn replace_subtree(builder: FolderSizesBuilder) {
+    let (root, totals) = builder.finish();
+    let root_path = PathBuf::from(&root);
+
+    if let Ok(mut folder_sizes) = FOLDER_SIZES.write() {
+        let old_root_totals = folder_sizes.get(&root).copied();
+        remove_subtree(&mut folder_sizes, &root);
+
+        match (old_root_totals, totals.get(&root).copied()) {
+            (Some(old), Some(new)) => update_ancestors(&mut folder_sizes, &root_path, &old, &new),
+            // A parent folder that contains the root can't have a known
+            // size if the root doesn't
+            _ => forget_with_ancestors(&mut folder_sizes, &root_path),
+        }
+        folder_sizes.extend(totals);
+    }
+    save(true);
+}
+
+/// The size of a folder, if it is known to be up to date
+pub fn lookup(path: &str) -> Option<FolderTotals> {
+    FOLDER_SIZES
+        .read()
+        .ok()?
+        .get(&normalize_path(path))
+        .copied()
+}
+
+/// The largest known folders inside `scope`, largest first
+pub fn largest_in(scope: &str, limit: usize) -> Vec<(String, FolderTotals)> {
+    let Ok(folder_sizes) = FOLDER_SIZES.read() else {
+        return Vec::new();
+    };
+    let prefix = child_prefix(&normalize_path(scope));
+
+    let mut folders: Vec<(String, FolderTotals)> = folder_sizes
+        .range(prefix.clone()..)
+        .take_while(|(path, _)| path.starts_with(&prefix))
+        .map(|(path, totals)| (path.clone(), *totals))
+        .collect();
+    folders.sort_unstable_by(|first, second| second.1.size.cmp(&first.1.size));
+    folders.truncate(limit);
+    folders
+}
+
+/// Sets `indexed_size` of listed folders with a known size
+pub fn attach_sizes(entries: &mut [DirEntry]) {
+    let Ok(folder_sizes) = FOLDER_SIZES.read() else {
+        return;
+    };
+    if folder_sizes.is_empty() {
+        return;
+    }
+
+    for entry in entries.iter_mut().filter(|entry| entry.is_dir) {
+        entry.indexed_size = folder_sizes.get(&entry.path).map(|totals| totals.size);
+    }
+}
+
+/// Adds up a folder's direct contents, taking the totals of known
+/// subfolders as they are and walking new ones. `None` when part of the
+/// folder can't be read.
+fn recount(
+    folder_sizes: &mut BTreeMap<String, FolderTotals>,
+    folder: &Path,
+) -> Option<FolderTotals> {
+    let mut totals = FolderTotals::default();
+    let mut child_keys: HashSet<String> = HashSet::new();
+
+    for entry in fs::read_dir(folder).ok()? {
+        let entry = entry.ok()?;
+        let metadata = fs::symlink_metadata(entry.path()).ok()?;
+
+        if metadata.is_file() {
+            totals.size += metadata.len();
+            totals.file_count += 1;
+        } else if metadata.is_dir() {
+            let child_key = key(&entry.path());
+            let child_totals = match folder_sizes.get(&child_key) {
+                Some(child_totals) => *child_totals,
+                None => {
+                    let walked = walk_folder(&entry.path());
+                    let child_totals = walked.get(&child_key).copied()?;
+                    folder_sizes.extend(walked);
+                    child_totals
+                }
+            };
+
+            totals.size += child_totals.size;
+            totals.file_count += child_totals.file_count;
+            totals.dir_count += child_totals.dir_count + 1;
+            child_keys.insert(child_key);
+        }
+    }
+
+    // Subfolders that are gone
+    let prefix = child_prefix(&key(folder));
+    let removed_children: Vec<String> = folder_sizes
+        .range(prefix.clone()..)
+        .take_while(|(path, _)| path.starts_with(&prefix))
+        .map(|(path, _)| path)
+        .filter(|path| !path[prefix.len()..].contains('/') && !child_keys.contains(*path))
+        .cloned()
+        .collect();
+    for child in removed_children {
+        remove_subtree(folder_sizes, &child);
+    }
+
+    Some(totals)
+}
+
+/// Brings the sizes up to date after changes to the given paths, reported
+/// by a directory watcher
+pub fn apply_changes(changed_paths: &[PathBuf]) {
+    let folders: HashSet<&Path> = changed_paths
+        .iter()
+        .filter_map(|path| path.parent())
+        .collect();
+    refresh(folders);
+}
+
+/// Recounts known folders whose contents changed
+pub fn refresh<'a>(folders: impl IntoIterator<Item = &'a Path>) {
+    let mut is_changed = false;
+    if let Ok(mut folder_sizes) = FOLDER_SIZES.write() {
+        for folder in folders {
+            let Some(old) = folder_sizes.get(&key(folder)).copied() else {
+                continue;
+            };
+
+            match recount(&mut folder_sizes, folder) {
+                Some(new) if new == old => {}
+                Some(new) => {
+                    folder_sizes.insert(key(folder), new);
+                    update_ancestors(&mut folder_sizes, folder, &old, &new);
+                    is_changed = true;
+                }
+                None => {
+                    forget_with_ancestors(&mut folder_sizes, folder);
+                    is_changed = true;
+                }
+            }
+        }
+    }
+
+    if is_changed {
+        save(false);
+    }
+}
@@ -0,0 +1,298 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! GPS locations of photos for the map view. Locations read from EXIF are
+//! kept in a store keyed by path, size and modification time, so a library
+//! is only read in full the first time and later scans just list it.
+
+use crate::json_store;
+use crate::utils::{civil_to_unix_millis, normalize_path};
+use exif::{In, Tag, Value};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+const STORE_NAME: &str = "photo-locations";
+const DEFAULT_CLUSTER_DISTANCE_KM: f64 = 1.0;
+const KM_PER_DEGREE: f64 = 111.32;
+
+// Formats that carry EXIF in a container the reader understands
+const PHOTO_EXTENSIONS: [&str; 9] = [
+    "jpg", "jpeg", "tif", "tiff", "heic", "heif", "png", "webp", "dng",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeotaggedPhoto {
+    pub path: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// When the photo was taken, from the camera clock
+    pub taken_time: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhotoCluster {
+    /// Center of the photos in the cluster
+    pub latitude: f64,
+    pub longitude: f64,
+    pub count: usize,
+    /// Oldest first
+    pub photos: Vec<GeotaggedPhoto>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Location {
+    latitude: f64,
+    longitude: f64,
+    taken_time: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LocationEntry {
+    size: u64,
+    modified_time: u64,
+    /// `None` for photos without GPS data, so they aren't read again
+    #[serde(default)]
+    location: Option<Location>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LocationStore {
+    #[serde(default)]
+    files: HashMap<String, LocationEntry>,
+}
+
+static LOCATION_STORE: Lazy<Mutex<LocationStore>> =
+    Lazy::new(|| Mutex::new(json_store::load(STORE_NAME)));
+
+pub(crate) fn is_photo(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| PHOTO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn is_same_or_descendant(path: &str, ancestor: &str) -> bool {
+    path == ancestor
+        || path
+            .strip_prefix(ancestor.trim_end_matches('/'))
+            .map(|rest| rest.starts_with('/'))
+            .unwrap_or(false)
+}
+
+pub(crate) fn ascii_value(exif: &exif::Exif, tag: Tag) -> Option<Vec<u8>> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values.first().cloned(),
+        _ => None,
+    }
+}
+
+fn coordinate(exif: &exif::Exif, tag: Tag, reference_tag: Tag, negative: u8) -> Option<f64> {
+    let Value::Rational(parts) = &exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+
+    if parts.len() < 3 {
+        return None;
+    }
+
+    let degrees = parts[0].to_f64() + parts[1].to_f64() / 60.0 + parts[2].to_f64() / 3600.0;
+
+    if !degrees.is_finite() {
+        return None;
+    }
+
+    let reference = ascii_value(exif, reference_tag).unwrap_or_default();
+
+    Some(if reference.first() == Some(&negative) {
+        -degrees
+    } else {
+        degrees
+    })
+}
+
+fn read_location(path: &Path) -> Option<Location> {
+    let file = File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    let latitude = coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S')?;
+    let longitude = coordinate(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W')?;
+
+    // Cameras without a fix often write zeros instead of leaving GPS out
+    if (latitude == 0.0 && longitude == 0.0) || latitude.abs() > 90.0 || longitude.abs() > 180.0 {
+        return None;
+    }
+
+    // Camera clocks have no time zone, the local time is read as UTC
+    let taken_time = ascii_value(&exif, Tag::DateTimeOriginal)
+        .and_then(|value| exif::DateTime::from_ascii(&value).ok())
+        .map(|date_time| {
+            civil_to_unix_millis(
+                date_time.year as i64,
+                date_time.month as i64,
+                date_time.day as i64,
+                date_time.hour as i64,
+                date_time.minute as i64,
+                date_time.second as i64,
+            )
+        });
+
+    Some(Location {
+        latitude,
+        longitude,
+        taken_time,
+    })
+}
+
+fn file_version(path: &Path) -> Option<(u64, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified_time = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0);
+
+    Some((metadata.len(), modified_time))
+}
+
+/// Groups photos into grid cells about `distance_km` wide
+fn cluster_photos(photos: Vec<GeotaggedPhoto>, distance_km: f64) -> Vec<PhotoCluster> {
+    let step = distance_km / KM_PER_DEGREE;
+    let mut cells: HashMap<(i64, i64), Vec<GeotaggedPhoto>> = HashMap::new();
+
+    for photo in photos {
+        // A degree of longitude shrinks toward the poles
+        let longitude_scale = photo.latitude.to_radians().cos().max(0.01);
+        let cell = (
+            (photo.latitude / step).floor() as i64,
+            (photo.longitude * longitude_scale / step).floor() as i64,
+        );
+        cells.entry(cell).or_default().push(photo);
+    }
+
+    let mut clusters: Vec<PhotoCluster> = cells
+        .into_values()
+        .map(|mut photos| {
+            let count = photos.len();
+            let latitude = photos.iter().map(|photo| photo.latitude).sum::<f64>() / count as f64;
+            let longitude = photos.iter().map(|photo| photo.longitude).sum::<f64>() / count as f64;
+            photos.sort_by(|a, b| {
+                a.taken_time
+                    .cmp(&b.taken_time)
+                    .then_with(|| a.path.cmp(&b.path))
+            });
+
+            PhotoCluster {
+                latitude,
+                longitude,
+                count,
+                photos,
+            }
+        })
+        .collect();
+
+    clusters.sort_by(|a, b| b.count.cmp(&a.count));
+    clusters
+}
+
+/// Returns the photos with GPS coordinates under the `scope` folders,
+/// clustered by location for the map view.
+#[tauri::command]
+pub async fn get_geotagged_photos(
+    scope: Vec<String>,
+    cluster_distance_km: Option<f64>,
+) -> Result<Vec<PhotoCluster>, String> {
+    let _timer = crate::metrics::time_command("get_geotagged_photos");
+    let distance_km = cluster_distance_km
+        .filter(|distance| distance.is_finite() && *distance > 0.0)
+        .unwrap_or(DEFAULT_CLUSTER_DISTANCE_KM);
+
+    tokio::task::spawn_blocking(move || {
+        let roots: Vec<String> = scope.iter().map(|root| normalize_path(root)).collect();
+
+        let found: HashMap<String, (PathBuf, u64, u64)> = scope
+            .iter()
+            .flat_map(|root| {
+                walkdir::WalkDir::new(root)
+                    .into_iter()
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_type().is_file() && is_photo(entry.path()))
+                    .filter_map(|entry| {
+                        let (size, modified_time) = file_version(entry.path())?;
+                        let key = normalize_path(&entry.path().to_string_lossy());
+                        Some((key, (entry.into_path(), size, modified_time)))
+                    })
+            })
+            .collect();
+
+        let stale: Vec<(String, PathBuf, u64, u64)> = {
+            let store = LOCATION_STORE.lock().map_err(|error| error.to_string())?;
+
+            found
+                .iter()
+                .filter(|(key, (_, size, modified_time))| {
+                    store.files.get(*key).map_or(true, |entry| {
+                        entry.size != *size || entry.modified_time != *modified_time
+                    })
+                })
+                .map(|(key, (path, size, modified_time))| {
+                    (key.clone(), path.clone(), *size, *modified_time)
+                })
+                .collect()
+        };
+
+        // Read without holding the lock, this is the slow part
+        let refreshed: Vec<(String, LocationEntry)> = stale
+            .into_par_iter()
+            .map(|(key, path, size, modified_time)| {
+                let entry = LocationEntry {
+                    size,
+                    modified_time,
+                    location: read_location(&path),
+                };
+                (key, entry)
+            })
+            .collect();
+
+        let mut store = LOCATION_STORE.lock().map_err(|error| error.to_string())?;
+
+        let seen: HashSet<&String> = found.keys().collect();
+        let count_before = store.files.len();
+        store.files.retain(|key, _| {
+            seen.contains(key) || !roots.iter().any(|root| is_same_or_descendant(key, root))
+        });
+        let changed = !refreshed.is_empty() || store.files.len() != count_before;
+        store.files.extend(refreshed);
+
+        if changed {
+            let _ = json_store::save(STORE_NAME, &*store);
+        }
+
+        let photos: Vec<GeotaggedPhoto> = found
+            .keys()
+            .filter_map(|key| {
+                let location = store.files.get(key)?.location?;
+                Some(GeotaggedPhoto {
+                    path: key.clone(),
+                    latitude: location.latitude,
+                    longitude: location.longitude,
+                    taken_time: location.taken_time,
+                })
+            })
+            .collect();
+
+        Ok(cluster_photos(photos, distance_km))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
@@ -0,0 +1,146 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Git status of directory listings through libgit2. Only the part of the
+//! work tree below the listed directory is checked, and a folder takes the
+//! most pressing status of anything inside it, so a folder holding a
+//! conflicted file shows as conflicted.
+
+use crate::utils::normalize_path;
+use git2::{BranchType, Repository, Status, StatusOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Ordered from least to most pressing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitFileStatus {
+    Untracked,
+    /// Changes are staged and the work tree matches the index
+    Staged,
+    /// The work tree has unstaged changes
+    Modified,
+    Conflicted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitRepoSummary {
+    pub root: String,
+    /// `None` when HEAD is detached
+    pub branch: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    /// Short id of the checked out commit, `None` before the first commit
+    pub head_commit: Option<String>,
+}
+
+fn file_status(status: Status) -> Option<GitFileStatus> {
+    if status.is_conflicted() {
+        Some(GitFileStatus::Conflicted)
+    } else if status.intersects(
+        Status::WT_MODIFIED | Status::WT_DELETED | Status::WT_RENAMED | Status::WT_TYPECHANGE,
+    ) {
+        Some(GitFileStatus::Modified)
+    } else if status.intersects(
+        Status::INDEX_NEW
+            | Status::INDEX_MODIFIED
+            | Status::INDEX_DELETED
+            | Status::INDEX_RENAMED
+            | Status::INDEX_TYPECHANGE,
+    ) {
+        Some(GitFileStatus::Staged)
+    } else if status.contains(Status::WT_NEW) {
+        Some(GitFileStatus::Untracked)
+    } else {
+        None
+    }
+}
+
+fn summary(repository: &Repository, root: &Path) -> GitRepoSummary {
+    let head = repository.head().ok();
+    let head_commit = head
+        .as_ref()
+        .and_then(|head| head.peel_to_commit().ok())
+        .map(|commit| commit.id().to_string()[..7].to_string());
+
+    let branch_name = head
+        .as_ref()
+        .filter(|head| head.is_branch())
+        .and_then(|head| head.shorthand().map(str::to_string));
+
+    let mut upstream = None;
+    let (mut ahead, mut behind) = (0, 0);
+
+    if let Some(branch_name) = &branch_name {
+        let upstream_branch = repository
+            .find_branch(branch_name, BranchType::Local)
+            .and_then(|branch| branch.upstream());
+
+        if let Ok(upstream_branch) = upstream_branch {
+            upstream = upstream_branch.name().ok().flatten().map(str::to_string);
+
+            let local_oid = head.as_ref().and_then(|head| head.target());
+            let upstream_oid = upstream_branch.get().target();
+            if let (Some(local_oid), Some(upstream_oid)) = (local_oid, upstream_oid) {
+                (ahead, behind) = repository
+                    .graph_ahead_behind(local_oid, upstream_oid)
+                    .unwrap_or((0, 0));
+            }
+        }
+    }
+
+    GitRepoSummary {
+        root: normalize_path(&root.to_string_lossy()),
+        branch: branch_name,
+        upstream,
+        ahead,
+        behind,
+        head_commit,
+    }
+}
+
+/// The repository `dir` belongs to and the status of each of its children
+/// that has one, by name. `None` outside a work tree.
+pub fn dir_status(dir: &Path) -> Option<(GitRepoSummary, HashMap<String, GitFileStatus>)> {
+    let repository = Repository::discover(dir).ok()?;
+    let root = repository.workdir()?.to_path_buf();
+    let dir = dir.canonicalize().ok()?;
+    let canonical_root = root.canonicalize().ok()?;
+    let relative_dir = dir.strip_prefix(&canonical_root).ok()?.to_path_buf();
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(false)
+        .exclude_submodules(true)
+        .include_ignored(false);
+    if !relative_dir.as_os_str().is_empty() {
+        // Pathspecs use forward slashes on every platform
+        options.pathspec(normalize_path(&relative_dir.to_string_lossy()));
+    }
+
+    let mut statuses: HashMap<String, GitFileStatus> = HashMap::new();
+
+    for entry in repository.statuses(Some(&mut options)).ok()?.iter() {
+        let (Some(path), Some(status)) = (entry.path(), file_status(entry.status())) else {
+            continue;
+        };
+        // Untracked folders are reported with a trailing slash
+        let Ok(below_dir) = Path::new(path.trim_end_matches('/')).strip_prefix(&relative_dir)
+        else {
+            continue;
+        };
+        let Some(child_name) = below_dir.components().next() else {
+            continue;
+        };
+
+        let child_name = child_name.as_os_str().to_string_lossy().to_string();
+        let child_status = statuses.entry(child_name).or_insert(status);
+        *child_status = (*child_status).max(status);
+    }
+
+    Some((summary(&repository, &root), statuses))
+}
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Git ignore rules for listings: `.gitignore` files of the directory and
+//! its parents up to the repository root, `.git/info/exclude` and the
+//! global excludes file. Outside a repository nothing is ignored, as with
+//! git itself.
+
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitIgnoreMode {
+    #[default]
+    Off,
+    /// Lists ignored entries with `git_ignored` set
+    Mark,
+    /// Leaves ignored entries out
+    Exclude,
+}
+
+/// A walker that applies git's rules and nothing else, so hidden files and
+/// `.ignore` files don't change what is listed
+pub fn git_walker(root: &Path) -> ignore::WalkBuilder {
+    let mut builder = ignore::WalkBuilder::new(root);
+    builder
+        .hidden(false)
+        .ignore(false)
+        .parents(true)
+        .git_ignore(true)
+        .git_global(true)
+        .git_exclude(true)
+        .require_git(true);
+    builder
+}
+
+/// Entries directly in `dir` that git ignores
+pub fn ignored_children(dir: &Path) -> HashSet<PathBuf> {
+    let kept: HashSet<PathBuf> = git_walker(dir)
+        .max_depth(Some(1))
+        .build()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .collect();
+
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| !kept.contains(path))
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -10,13 +10,17 @@ use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tantivy::collector::TopDocs;
-use tantivy::query::{AllQuery, BooleanQuery, FuzzyTermQuery, Query, TermQuery};
+use tantivy::query::{AllQuery, BooleanQuery, FuzzyTermQuery, Query, RangeQuery, TermQuery};
 use tantivy::schema::{
     Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, FAST, STORED, STRING,
 };
-use tantivy::{doc, Index, IndexReader, IndexWriter, Term};
+use tantivy::{doc, Index, IndexReader, IndexWriter, Order, Term};
 use tauri::Manager;
 use walkdir::WalkDir;
+use crate::faults::FaultPoint;
+use crate::folder_sizes::{self, FolderSizesBuilder};
+use crate::power::{self, BackgroundWork};
+use crate::skipped_dirs::SkippedDirs;
 use crate::utils::normalize_path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,6 +75,8 @@ pub struct GlobalSearchStatus {
     pub index_size_bytes: u64,
     pub current_drive_root: Option<String>,
     pub drive_scan_errors: Vec<GlobalSearchDriveScanError>,
+    /// Folders the last scan couldn't enter, their content isn't searchable
+    pub skipped_dirs: SkippedDirs,
     pub is_index_valid: bool,
     pub scanned_drives_count: u32,
     pub total_drives_count: u32,
@@ -106,6 +112,7 @@ static GLOBAL_SEARCH_STATE: Lazy<Arc<RwLock<GlobalSearchState>>> = Lazy::new(||
             index_size_bytes: 0,
             current_drive_root: None,
             drive_scan_errors: vec![],
+            skipped_dirs: SkippedDirs::default(),
             is_index_valid: false,
             scanned_drives_count: 0,
             total_drives_count: 0,
@@ -125,7 +132,7 @@ fn now_millis() -> u64 {
 }
 
 fn normalize_case(value: &str) -> String {
-    value.trim().to_lowercase()
+    crate::text::fold_for_search(value.trim())
 }
 
 fn builtin_ignored_paths() -> &'static [&'static str] {
@@ -203,10 +210,25 @@ fn build_schema() -> (Schema, GlobalSearchIndexFields) {
     )
 }
 
-fn index_dir(base_dir: &Path) -> PathBuf {
+pub(crate) fn index_dir(base_dir: &Path) -> PathBuf {
     base_dir.join("global-search").join("index")
 }
 
+/// Why an existing index can't be opened. A missing index isn't an error,
+/// it is built on the next scan.
+pub(crate) fn index_open_error(base_dir: &Path) -> Option<String> {
+    let index_path = index_dir(base_dir);
+
+    if !index_path.join("meta.json").exists() {
+        return None;
+    }
+
+    Index::open_in_dir(&index_path)
+        .and_then(|index| index.reader().map(|_| ()))
+        .err()
+        .map(|error| error.to_string())
+}
+
 fn calculate_dir_size(path: &Path) -> u64 {
     if !path.exists() {
         return 0;
@@ -230,7 +252,7 @@ fn calculate_dir_size(path: &Path) -> u64 {
     total_size
 }
 
-fn meta_file(base_dir: &Path) -> PathBuf {
+pub(crate) fn meta_file(base_dir: &Path) -> PathBuf {
     base_dir.join("global-search").join("status.json")
 }
 
@@ -239,9 +261,12 @@ struct GlobalSearchMeta {
     last_scan_time: Option<u64>,
     indexed_item_count: u64,
     schema_version: u32,
+    #[serde(default)]
+    skipped_dirs: SkippedDirs,
 }
 
-const SCHEMA_VERSION: u32 = 1;
+// Bumped when indexed values change; mismatched indexes are rebuilt
+const SCHEMA_VERSION: u32 = 2;
 
 fn read_meta(base_dir: &Path) -> Option<GlobalSearchMeta> {
     let path = meta_file(base_dir);
@@ -339,6 +364,7 @@ pub fn global_search_init(app: tauri::AppHandle) -> Result<GlobalSearchStatus, S
     }
 
     let (_index, reader, _fields) = open_or_create_index(&index_path)?;
+    folder_sizes::load(&base_dir.join("global-search"), is_valid);
 
     let indexed_item_count = reader.searcher().num_docs();
     let index_size = calculate_dir_size(&index_path);
@@ -351,6 +377,7 @@ pub fn global_search_init(app: tauri::AppHandle) -> Result<GlobalSearchStatus, S
     state.status.indexed_item_count = indexed_item_count;
     state.status.index_size_bytes = index_size;
     state.status.last_scan_time = meta.as_ref().and_then(|m| m.last_scan_time);
+    state.status.skipped_dirs = meta.map(|m| m.skipped_dirs).unwrap_or_default();
     state.status.is_index_valid = is_valid && indexed_item_count > 0;
 
     Ok(state.status.clone())
@@ -397,8 +424,8 @@ fn add_path_doc(writer: &mut IndexWriter, fields: &GlobalSearchIndexFields, path
 }
 
 fn calculate_similarity_score(query: &str, name: &str) -> f32 {
-    let query_lower = query.to_lowercase();
-    let name_lower = name.to_lowercase();
+    let query_lower = crate::text::fold_for_search(query);
+    let name_lower = crate::text::fold_for_search(name);
 
     if name_lower == query_lower {
         return 1.0;
@@ -601,7 +628,7 @@ fn scan_drive(
     writer: &Mutex<IndexWriter>,
     indexed_count: &AtomicU64,
     cancel_flag: &AtomicBool,
-) -> Result<(), GlobalSearchDriveScanError> {
+) -> Result<(FolderSizesBuilder, SkippedDirs), GlobalSearchDriveScanError> {
     let root_path = PathBuf::from(root);
     let root_string = normalize_path(root);
 
@@ -613,15 +640,23 @@ fn scan_drive(
     }
 
     let mut items_since_last_update: u64 = 0;
+    let max_depth = scan_depth.max(1);
+    let mut sizes_builder = FolderSizesBuilder::new(&root_path);
+    let mut skipped_paths: Vec<PathBuf> = Vec::new();
+    let mut skipped_dirs = SkippedDirs::default();
 
     for entry_result in WalkDir::new(&root_path)
         .follow_links(false)
-        .max_depth(scan_depth.max(1))
+        .max_depth(max_depth)
         .into_iter()
         .filter_entry(|entry| {
             let path_string = entry.path().to_string_lossy().to_string();
             let normalized = normalize_path(&path_string);
-            !is_ignored_path(&normalized, ignored_paths)
+            let is_ignored = is_ignored_path(&normalized, ignored_paths);
+            if is_ignored {
+                skipped_paths.push(entry.path().to_path_buf());
+            }
+            !is_ignored
         })
     {
         if cancel_flag.load(Ordering::SeqCst) {
@@ -630,16 +665,27 @@ fn scan_drive(
 
         let entry = match entry_result {
             Ok(e) => e,
-            Err(_) => continue,
+            Err(error) => {
+                sizes_builder.mark_incomplete(error.path().unwrap_or(&root_path));
+                skipped_dirs.record(&error);
+                continue;
+            }
         };
 
         let path = entry.path();
+
+        if crate::faults::check(FaultPoint::SearchScan, path).is_err() {
+            sizes_builder.mark_incomplete(path);
+            continue;
+        }
+
         let path_string = match path.to_str() {
             Some(p) => normalize_path(p),
             None => continue,
         };
 
         if is_ignored_path(&path_string, ignored_paths) {
+            sizes_builder.mark_incomplete(path);
             continue;
         }
 
@@ -647,6 +693,14 @@ fn scan_drive(
             continue;
         }
 
+        // Folders at the depth limit are indexed but not walked
+        if entry.depth() == max_depth && entry.file_type().is_dir() {
+            sizes_builder.mark_incomplete(path);
+        }
+        if let Ok(metadata) = entry.metadata() {
+            sizes_builder.add_entry(path, &metadata);
+        }
+
         if let Ok(mut w) = writer.lock() {
             add_path_doc(&mut w, fields, path);
         }
@@ -659,10 +713,19 @@ fn scan_drive(
                 state.status.indexed_item_count = indexed_count.load(Ordering::Relaxed);
             }
             items_since_last_update = 0;
+
+            // Waits out battery power per the power policy
+            if !power::wait_for_allowance(BackgroundWork::Indexing, cancel_flag) {
+                break;
+            }
         }
     }
 
-    Ok(())
+    for skipped_path in skipped_paths {
+        sizes_builder.mark_incomplete(&skipped_path);
+    }
+
+    Ok((sizes_builder, skipped_dirs))
 }
 
 #[tauri::command]
@@ -682,6 +745,7 @@ pub async fn global_search_start_scan(
         state.status.current_drive_root = None;
         state.status.indexed_item_count = 0;
         state.status.drive_scan_errors = vec![];
+        state.status.skipped_dirs = SkippedDirs::default();
         state.status.scanned_drives_count = 0;
         state.status.total_drives_count = settings.drive_roots.len() as u32;
         state.cancel_flag.store(false, Ordering::SeqCst);
@@ -747,6 +811,8 @@ pub async fn global_search_start_scan(
             let indexed_count = AtomicU64::new(0);
             let mut errors: Vec<GlobalSearchDriveScanError> = Vec::new();
             let mut scanned_count: u32 = 0;
+            let mut folder_size_builders: Vec<FolderSizesBuilder> = Vec::new();
+            let mut skipped_dirs = SkippedDirs::default();
 
             if settings.parallel_scan && valid_drive_roots.len() > 1 {
                 use std::thread;
@@ -789,10 +855,13 @@ pub async fn global_search_start_scan(
                         .collect();
 
                     for handle in results {
-                        if let Ok(result) = handle.join() {
-                            if let Err(error) = result {
-                                errors.push(error);
+                        match handle.join() {
+                            Ok(Ok((builder, drive_skipped_dirs))) => {
+                                folder_size_builders.push(builder);
+                                skipped_dirs.merge(drive_skipped_dirs);
                             }
+                            Ok(Err(error)) => errors.push(error),
+                            Err(_) => {}
                         }
                     }
                 });
@@ -824,14 +893,19 @@ pub async fn global_search_start_scan(
                         state.status.indexed_item_count = indexed_count.load(Ordering::Relaxed);
                     }
 
-                    if let Err(error) = result {
-                        errors.push(error);
+                    match result {
+                        Ok((builder, drive_skipped_dirs)) => {
+                            folder_size_builders.push(builder);
+                            skipped_dirs.merge(drive_skipped_dirs);
+                        }
+                        Err(error) => errors.push(error),
                     }
                 }
             }
 
             if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
                 state.status.drive_scan_errors = errors;
+                state.status.skipped_dirs = skipped_dirs;
                 state.status.current_drive_root = None;
                 state.status.is_committing = true;
             }
@@ -842,6 +916,12 @@ pub async fn global_search_start_scan(
 
             reader.reload().map_err(|error| error.to_string())?;
 
+            // A cancelled walk leaves sizes short without marking them
+            if cancel_flag.load(Ordering::SeqCst) {
+                folder_size_builders.clear();
+            }
+            folder_sizes::replace_all(folder_size_builders);
+
             if let Ok(mut state) = GLOBAL_SEARCH_STATE.write() {
                 state.status.is_committing = false;
             }
@@ -879,6 +959,7 @@ pub async fn global_search_start_scan(
                         last_scan_time: state.status.last_scan_time,
                         indexed_item_count: count,
                         schema_version: SCHEMA_VERSION,
+                        skipped_dirs: state.status.skipped_dirs.clone(),
                     },
                 );
             }
@@ -954,6 +1035,9 @@ pub async fn global_search_index_paths(
         let exact_term = Term::from_field_text(fields.path, &normalized_dir);
         writer.delete_term(exact_term);
 
+        let mut sizes_builder = FolderSizesBuilder::new(path);
+        let mut skipped_paths: Vec<PathBuf> = Vec::new();
+
         for entry_result in WalkDir::new(path)
             .follow_links(false)
             .max_depth(scan_depth)
@@ -961,12 +1045,19 @@ pub async fn global_search_index_paths(
             .filter_entry(|entry| {
                 let path_string = entry.path().to_string_lossy().to_string();
                 let normalized = normalize_path(&path_string);
-                !is_ignored_path(&normalized, &ignored_paths)
+                let is_ignored = is_ignored_path(&normalized, &ignored_paths);
+                if is_ignored {
+                    skipped_paths.push(entry.path().to_path_buf());
+                }
+                !is_ignored
             })
         {
             let entry = match entry_result {
                 Ok(entry) => entry,
-                Err(_) => continue,
+                Err(error) => {
+                    sizes_builder.mark_incomplete(error.path().unwrap_or(path));
+                    continue;
+                }
             };
 
             if entry.depth() == 0 {
@@ -980,12 +1071,25 @@ pub async fn global_search_index_paths(
             };
 
             if is_ignored_path(&path_string, &ignored_paths) {
+                sizes_builder.mark_incomplete(entry_path);
                 continue;
             }
 
+            if entry.depth() == scan_depth && entry.file_type().is_dir() {
+                sizes_builder.mark_incomplete(entry_path);
+            }
+            if let Ok(metadata) = entry.metadata() {
+                sizes_builder.add_entry(entry_path, &metadata);
+            }
+
             add_path_doc(&mut writer, &fields, entry_path);
             indexed_count += 1;
         }
+
+        for skipped_path in skipped_paths {
+            sizes_builder.mark_incomplete(&skipped_path);
+        }
+        folder_sizes::replace_subtree(sizes_builder);
     }
 
     if indexed_count > 0 {
@@ -1013,6 +1117,7 @@ pub async fn global_search_query(
     query: String,
     options: GlobalSearchQueryOptions,
 ) -> Result<Vec<GlobalSearchResultEntry>, String> {
+    let _timer = crate::metrics::time_command("global_search_query");
     let base_dir = app
         .path()
         .app_data_dir()
@@ -1282,6 +1387,274 @@ pub async fn global_search_query_paths(
     Ok(sorted_results)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LargestItemsSource {
+    Index,
+    /// The scope isn't indexed and was walked
+    Live,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LargestItem {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    pub modified_time: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LargestItems {
+    pub scope: String,
+    pub files: Vec<LargestItem>,
+    /// Folders with a known total size, see `folder_sizes`
+    pub folders: Vec<LargestItem>,
+    pub source: LargestItemsSource,
+    /// False when part of the scope wasn't walked, because it lies below the
+    /// scan depth, is ignored or couldn't be read
+    pub is_complete: bool,
+    /// Folders in the scope that couldn't be entered
+    pub skipped_dirs: SkippedDirs,
+    pub indexed_at: Option<u64>,
+    pub cancelled: bool,
+}
+
+static LARGEST_ITEMS_REQUESTS: Lazy<Mutex<std::collections::HashMap<String, Arc<AtomicBool>>>> =
+    Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+
+fn to_largest_item(path: &Path, size: u64, modified_time: u64) -> LargestItem {
+    LargestItem {
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: normalize_path(&path.to_string_lossy()),
+        size,
+        modified_time,
+    }
+}
+
+fn modified_millis(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn largest_folders(scope: &str, limit: usize) -> Vec<LargestItem> {
+    folder_sizes::largest_in(scope, limit)
+        .into_iter()
+        .map(|(path, totals)| to_largest_item(Path::new(&path), totals.size, 0))
+        .collect()
+}
+
+/// The largest indexed files inside `scope`, checked against the disk.
+/// `None` when nothing inside `scope` is indexed.
+fn largest_indexed_files(
+    base_dir: &Path,
+    scope: &str,
+    limit: usize,
+) -> Result<Option<Vec<LargestItem>>, String> {
+    let (reader, fields) = {
+        let mut state = GLOBAL_SEARCH_STATE
+            .write()
+            .map_err(|error| error.to_string())?;
+        if !state.status.is_index_valid {
+            return Ok(None);
+        }
+        if state.reader.is_none() || state.fields.is_none() {
+            let (index, reader, fields) = open_or_create_index(&index_dir(base_dir))?;
+            state.index = Some(index);
+            state.reader = Some(reader);
+            state.fields = Some(fields);
+        }
+        (
+            state.reader.as_ref().unwrap().clone(),
+            *state.fields.as_ref().unwrap(),
+        )
+    };
+
+    // Everything inside the scope sorts between "scope/" and "scope0"
+    let prefix = folder_sizes::child_prefix(scope);
+    let upper_bound = format!("{}0", &prefix[..prefix.len() - 1]);
+    let scope_query =
+        RangeQuery::new_str("path".to_string(), prefix.as_str()..upper_bound.as_str());
+
+    // Some of the indexed files may be gone by now
+    let searcher = reader.searcher();
+    let top_docs = searcher
+        .search(
+            &scope_query,
+            &TopDocs::with_limit(limit * 2).order_by_fast_field::<u64>("size", Order::Desc),
+        )
+        .map_err(|error| error.to_string())?;
+
+    if top_docs.is_empty() {
+        return Ok(None);
+    }
+
+    let mut files: Vec<LargestItem> = top_docs
+        .par_iter()
+        .filter_map(|(_size, doc_address)| {
+            let retrieved: tantivy::TantivyDocument = searcher.doc(*doc_address).ok()?;
+            let path = retrieved.get_first(fields.path).and_then(|value| value.as_str())?;
+            let metadata = std::fs::symlink_metadata(path).ok()?;
+
+            metadata.is_file().then(|| {
+                to_largest_item(Path::new(path), metadata.len(), modified_millis(&metadata))
+            })
+        })
+        .collect();
+
+    files.par_sort_by(|first, second| second.size.cmp(&first.size));
+    files.truncate(limit);
+    Ok(Some(files))
+}
+
+/// Walks `scope` for its largest files, adding up folder sizes on the way
+fn largest_files_live(
+    scope: &Path,
+    limit: usize,
+    cancel_flag: &AtomicBool,
+) -> (Vec<LargestItem>, SkippedDirs, bool) {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    let mut largest: BinaryHeap<Reverse<(u64, PathBuf, u64)>> = BinaryHeap::new();
+    let mut sizes_builder = FolderSizesBuilder::new(scope);
+    let mut skipped_dirs = SkippedDirs::default();
+    let mut is_complete = true;
+
+    for entry_result in WalkDir::new(scope).follow_links(false).min_depth(1) {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return (Vec::new(), skipped_dirs, false);
+        }
+
+        let entry = match entry_result {
+            Ok(entry) => entry,
+            Err(error) => {
+                sizes_builder.mark_incomplete(error.path().unwrap_or(scope));
+                skipped_dirs.record(&error);
+                is_complete = false;
+                continue;
+            }
+        };
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        sizes_builder.add_entry(entry.path(), &metadata);
+
+        if metadata.is_file() {
+            largest.push(Reverse((
+                metadata.len(),
+                entry.into_path(),
+                modified_millis(&metadata),
+            )));
+            if largest.len() > limit {
+                largest.pop();
+            }
+        }
+    }
+
+    // Listing the folders next time takes no walk
+    folder_sizes::replace_subtree(sizes_builder);
+
+    let files = largest
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((size, path, modified_time))| to_largest_item(&path, size, modified_time))
+        .collect();
+    (files, skipped_dirs, is_complete)
+}
+
+/// The largest files and folders inside `scope`, from the index when it
+/// covers the scope, otherwise from a walk of it
+#[tauri::command]
+pub async fn largest_items(
+    app: tauri::AppHandle,
+    scope: String,
+    limit: usize,
+    request_id: Option<String>,
+) -> Result<LargestItems, String> {
+    let base_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|error: tauri::Error| error.to_string())?;
+    let scope = normalize_path(&scope);
+    let limit = limit.max(1);
+
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    if let (Some(request_id), Ok(mut active_requests)) =
+        (&request_id, LARGEST_ITEMS_REQUESTS.lock())
+    {
+        active_requests.insert(request_id.clone(), cancel_flag.clone());
+    }
+
+    let result = tokio::task::spawn_blocking(move || -> Result<LargestItems, String> {
+        let (indexed_at, indexed_skipped_dirs) = GLOBAL_SEARCH_STATE
+            .read()
+            .map(|state| {
+                (
+                    state.status.last_scan_time,
+                    state.status.skipped_dirs.within(&scope),
+                )
+            })
+            .unwrap_or_default();
+
+        if let Some(files) = largest_indexed_files(&base_dir, &scope, limit)? {
+            return Ok(LargestItems {
+                files,
+                folders: largest_folders(&scope, limit),
+                source: LargestItemsSource::Index,
+                is_complete: folder_sizes::lookup(&scope).is_some(),
+                skipped_dirs: indexed_skipped_dirs,
+                indexed_at,
+                cancelled: false,
+                scope,
+            });
+        }
+
+        let (files, skipped_dirs, is_complete) =
+            largest_files_live(Path::new(&scope), limit, &cancel_flag);
+        let cancelled = cancel_flag.load(Ordering::Relaxed);
+        Ok(LargestItems {
+            files,
+            folders: if cancelled {
+                Vec::new()
+            } else {
+                largest_folders(&scope, limit)
+            },
+            source: LargestItemsSource::Live,
+            is_complete: is_complete && !cancelled,
+            skipped_dirs,
+            indexed_at: None,
+            cancelled,
+            scope,
+        })
+    })
+    .await
+    .map_err(|error| error.to_string());
+
+    if let (Some(request_id), Ok(mut active_requests)) =
+        (&request_id, LARGEST_ITEMS_REQUESTS.lock())
+    {
+        active_requests.remove(request_id);
+    }
+
+    result?
+}
+
+#[tauri::command]
+pub fn cancel_largest_items(request_id: String) {
+    if let Ok(active_requests) = LARGEST_ITEMS_REQUESTS.lock() {
+        if let Some(cancel_flag) = active_requests.get(&request_id) {
+            cancel_flag.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
 fn is_hidden_path(path: &Path) -> bool {
     #[cfg(windows)]
     {
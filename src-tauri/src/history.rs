@@ -0,0 +1,382 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use crate::app_windows::notify_store_changed;
+use crate::json_store;
+use crate::utils::normalize_path;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const STORE_NAME: &str = "history";
+const MAX_DESTINATION_RECORDS: usize = 500;
+const MAX_VISIT_RECORDS: usize = 2000;
+const DEFAULT_SUGGESTION_LIMIT: usize = 10;
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+const OPEN_TAB_SCORE: f64 = 50.0;
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DestinationRecord {
+    path: String,
+    /// Lowercase extension of the items sent there, `None` for folders
+    extension: Option<String>,
+    count: u32,
+    last_used: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VisitKind {
+    Dir,
+    File,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VisitRecord {
+    pub path: String,
+    pub kind: VisitKind,
+    pub count: u32,
+    pub last_visited: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    #[serde(flatten)]
+    pub record: VisitRecord,
+    pub score: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryStore {
+    #[serde(default)]
+    destinations: Vec<DestinationRecord>,
+    #[serde(default)]
+    visits: Vec<VisitRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DestinationSuggestion {
+    pub path: String,
+    pub score: f64,
+    /// Why the folder was suggested: "recent", "file_type", "open_tab"
+    pub reasons: Vec<String>,
+}
+
+static HISTORY_STORE: Lazy<Mutex<HistoryStore>> =
+    Lazy::new(|| Mutex::new(json_store::load(STORE_NAME)));
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// Frecency weighting: frequent use counts, but recent use counts more
+fn frecency(count: u32, last_used: u64, now: u64) -> f64 {
+    let age_days = now.saturating_sub(last_used) / DAY_MS;
+
+    let recency_weight = match age_days {
+        0..=3 => 100.0,
+        4..=13 => 70.0,
+        14..=30 => 50.0,
+        31..=90 => 30.0,
+        _ => 10.0,
+    };
+
+    count as f64 * recency_weight
+}
+
+fn item_extension(path: &Path) -> Option<String> {
+    if path.is_dir() {
+        return None;
+    }
+
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+/// Records a visited directory or opened file
+pub fn record_path_visit(path: &Path) {
+    let kind = match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => VisitKind::Dir,
+        Ok(_) => VisitKind::File,
+        Err(_) => return,
+    };
+
+    let path = normalize_path(&path.to_string_lossy());
+    let now = now_ms();
+
+    let mut store = match HISTORY_STORE.lock() {
+        Ok(store) => store,
+        Err(_) => return,
+    };
+
+    match store.visits.iter_mut().find(|record| record.path == path) {
+        Some(record) => {
+            record.count += 1;
+            record.last_visited = now;
+            record.kind = kind;
+        }
+        None => store.visits.push(VisitRecord {
+            path,
+            kind,
+            count: 1,
+            last_visited: now,
+        }),
+    }
+
+    if store.visits.len() > MAX_VISIT_RECORDS {
+        // Evict the entries with the lowest frecency
+        store.visits.sort_by(|first, second| {
+            frecency(second.count, second.last_visited, now)
+                .partial_cmp(&frecency(first.count, first.last_visited, now))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        store.visits.truncate(MAX_VISIT_RECORDS);
+    }
+
+    let _ = json_store::save(STORE_NAME, &*store);
+}
+
+// Takes up to `limit` ranked records whose paths still exist, dropping the
+// missing ones from the store along the way
+fn take_existing(
+    store: &mut HistoryStore,
+    ranked: Vec<HistoryEntry>,
+    limit: usize,
+) -> Vec<HistoryEntry> {
+    let mut results: Vec<HistoryEntry> = Vec::new();
+    let mut missing_paths: HashSet<String> = HashSet::new();
+
+    for entry in ranked {
+        if results.len() >= limit {
+            break;
+        }
+
+        if Path::new(&entry.record.path).exists() {
+            results.push(entry);
+        } else {
+            missing_paths.insert(entry.record.path);
+        }
+    }
+
+    if !missing_paths.is_empty() {
+        store
+            .visits
+            .retain(|record| !missing_paths.contains(&record.path));
+        let _ = json_store::save(STORE_NAME, &*store);
+    }
+
+    results
+}
+
+/// Records where items were copied or moved to. Called by file operations.
+pub fn record_destination(destination: &Path, source_paths: &[String]) {
+    let destination = normalize_path(&destination.to_string_lossy());
+    let now = now_ms();
+
+    let extensions: HashSet<Option<String>> = source_paths
+        .iter()
+        .map(|source_path| item_extension(Path::new(source_path)))
+        .collect();
+
+    let mut store = match HISTORY_STORE.lock() {
+        Ok(store) => store,
+        Err(_) => return,
+    };
+
+    for extension in extensions {
+        match store
+            .destinations
+            .iter_mut()
+            .find(|record| record.path == destination && record.extension == extension)
+        {
+            Some(record) => {
+                record.count += 1;
+                record.last_used = now;
+            }
+            None => store.destinations.push(DestinationRecord {
+                path: destination.clone(),
+                extension,
+                count: 1,
+                last_used: now,
+            }),
+        }
+    }
+
+    if store.destinations.len() > MAX_DESTINATION_RECORDS {
+        store
+            .destinations
+            .sort_by(|first, second| second.last_used.cmp(&first.last_used));
+        store.destinations.truncate(MAX_DESTINATION_RECORDS);
+    }
+
+    let _ = json_store::save(STORE_NAME, &*store);
+}
+
+/// Ranks likely target folders for moving or copying `paths`, based on
+/// recent destinations, destinations used for the same file types, and
+/// the folders open in tabs.
+#[tauri::command]
+pub fn suggest_destinations(
+    paths: Vec<String>,
+    open_tabs: Option<Vec<String>>,
+    limit: Option<usize>,
+) -> Result<Vec<DestinationSuggestion>, String> {
+    let now = now_ms();
+
+    let extensions: HashSet<Option<String>> = paths
+        .iter()
+        .map(|path| item_extension(Path::new(path)))
+        .collect();
+
+    // Suggesting the folders the items are already in is pointless
+    let source_parents: HashSet<String> = paths
+        .iter()
+        .filter_map(|path| Path::new(path).parent())
+        .map(|parent| normalize_path(&parent.to_string_lossy()))
+        .collect();
+
+    let mut suggestions: HashMap<String, DestinationSuggestion> = HashMap::new();
+    let mut add_score = |path: &str, score: f64, reason: &str| {
+        let suggestion =
+            suggestions
+                .entry(path.to_string())
+                .or_insert_with(|| DestinationSuggestion {
+                    path: path.to_string(),
+                    score: 0.0,
+                    reasons: Vec::new(),
+                });
+
+        suggestion.score += score;
+
+        if !suggestion.reasons.iter().any(|existing| existing == reason) {
+            suggestion.reasons.push(reason.to_string());
+        }
+    };
+
+    {
+        let store = HISTORY_STORE.lock().map_err(|error| error.to_string())?;
+
+        for record in &store.destinations {
+            let score = frecency(record.count, record.last_used, now);
+
+            if extensions.contains(&record.extension) {
+                add_score(&record.path, score, "file_type");
+            } else {
+                // Destinations used for other file types still count, less so
+                add_score(&record.path, score * 0.25, "recent");
+            }
+        }
+    }
+
+    for tab_path in open_tabs.unwrap_or_default() {
+        add_score(&normalize_path(&tab_path), OPEN_TAB_SCORE, "open_tab");
+    }
+
+    let mut ranked: Vec<DestinationSuggestion> = suggestions
+        .into_values()
+        .filter(|suggestion| !source_parents.contains(&suggestion.path))
+        .filter(|suggestion| Path::new(&suggestion.path).is_dir())
+        .collect();
+
+    ranked.sort_by(|first, second| {
+        second
+            .score
+            .partial_cmp(&first.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    ranked.truncate(limit.unwrap_or(DEFAULT_SUGGESTION_LIMIT));
+
+    Ok(ranked)
+}
+
+/// Records a navigation to a directory or an opened file
+#[tauri::command]
+pub fn record_visit(window: tauri::WebviewWindow, path: String) {
+    record_path_visit(Path::new(&path));
+    notify_store_changed(STORE_NAME, Some(window.label()));
+}
+
+/// Most recently visited directories and/or opened files
+#[tauri::command]
+pub fn get_recent(
+    kind: Option<VisitKind>,
+    limit: Option<usize>,
+) -> Result<Vec<HistoryEntry>, String> {
+    let now = now_ms();
+    let mut store = HISTORY_STORE.lock().map_err(|error| error.to_string())?;
+
+    let mut ranked: Vec<HistoryEntry> = store
+        .visits
+        .iter()
+        .filter(|record| kind.map(|kind| record.kind == kind).unwrap_or(true))
+        .map(|record| HistoryEntry {
+            score: frecency(record.count, record.last_visited, now),
+            record: record.clone(),
+        })
+        .collect();
+
+    ranked.sort_by(|first, second| second.record.last_visited.cmp(&first.record.last_visited));
+
+    Ok(take_existing(
+        &mut store,
+        ranked,
+        limit.unwrap_or(DEFAULT_HISTORY_LIMIT),
+    ))
+}
+
+/// Frecency-ranked directories for a "jump to" palette. `prefix` matches
+/// the start of the full path or of the folder name, case-insensitively.
+#[tauri::command]
+pub fn get_frecent_dirs(
+    prefix: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<HistoryEntry>, String> {
+    let now = now_ms();
+    let prefix = prefix
+        .map(|prefix| normalize_path(&prefix).to_lowercase())
+        .filter(|prefix| !prefix.is_empty());
+    let mut store = HISTORY_STORE.lock().map_err(|error| error.to_string())?;
+
+    let matches_prefix = |path: &str| match &prefix {
+        Some(prefix) => {
+            let path = path.to_lowercase();
+            let name = path
+                .rsplit('/')
+                .find(|segment| !segment.is_empty())
+                .unwrap_or("");
+            path.starts_with(prefix.as_str()) || name.starts_with(prefix.as_str())
+        }
+        None => true,
+    };
+
+    let mut ranked: Vec<HistoryEntry> = store
+        .visits
+        .iter()
+        .filter(|record| record.kind == VisitKind::Dir && matches_prefix(&record.path))
+        .map(|record| HistoryEntry {
+            score: frecency(record.count, record.last_visited, now),
+            record: record.clone(),
+        })
+        .collect();
+
+    ranked.sort_by(|first, second| {
+        second
+            .score
+            .partial_cmp(&first.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    Ok(take_existing(
+        &mut store,
+        ranked,
+        limit.unwrap_or(DEFAULT_HISTORY_LIMIT),
+    ))
+}
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Internet shortcuts in the format the desktop opens with a browser: a
+//! `.url` file on Windows, a `.desktop` file of type Link on Linux and a
+//! `.webloc` property list on macOS. Shortcuts are named after the title of
+//! the page they point to.
+
+use crate::file_operations::get_unique_destination_path;
+use crate::filename_rules::sanitize_windows_name;
+use crate::utils::normalize_path;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+use tauri::Url;
+
+const SUPPORTED_SCHEMES: [&str; 3] = ["http", "https", "ftp"];
+const TITLE_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+/// The title is in the head, the rest of the page isn't needed
+const MAX_PAGE_BYTES: u64 = 256 * 1024;
+const MAX_TITLE_LENGTH: usize = 120;
+
+/// Parses `text` as a URL a shortcut can point to
+fn parse_url(text: &str) -> Option<Url> {
+    let text = text.trim();
+    if text.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Url::parse(text)
+        .ok()
+        .filter(|url| SUPPORTED_SCHEMES.contains(&url.scheme()) && url.host_str().is_some())
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| (&rest[1..end], end));
+        let character = entity.and_then(|(name, _)| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => name
+                        .strip_prefix('#')
+                        .and_then(|digits| digits.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+
+        match (character, entity) {
+            (Some(character), Some((_, end))) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+fn extract_title(html: &str) -> Option<String> {
+    // ASCII lowercasing keeps byte offsets valid for the original
+    let lowercase_html = html.to_ascii_lowercase();
+    let tag_start = lowercase_html.find("<title")?;
+    let content_start = tag_start + lowercase_html[tag_start..].find('>')? + 1;
+    let content_end = content_start + lowercase_html[content_start..].find("</title")?;
+
+    let title = decode_entities(&html[content_start..content_end])
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    (!title.is_empty()).then_some(title)
+}
+
+fn fetch_title(url: &Url) -> Option<String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(TITLE_FETCH_TIMEOUT)
+        .build()
+        .ok()?;
+    let response = client
+        .get(url.as_str())
+        .header("User-Agent", "sigma-file-manager")
+        .send()
+        .ok()?
+        .error_for_status()
+        .ok()?;
+
+    let mut page = Vec::new();
+    response.take(MAX_PAGE_BYTES).read_to_end(&mut page).ok()?;
+
+    extract_title(&String::from_utf8_lossy(&page))
+}
+
+/// A file name for the shortcut, valid on every platform the shortcut may
+/// be copied to
+fn shortcut_name(url: &Url, title: Option<&str>) -> String {
+    let title = title
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .or(url.host_str())
+        .unwrap_or("Shortcut");
+    let title: String = title.chars().take(MAX_TITLE_LENGTH).collect();
+
+    format!(
+        "{}.{}",
+        sanitize_windows_name(title.trim()),
+        SHORTCUT_EXTENSION
+    )
+}
+
+#[cfg(windows)]
+const SHORTCUT_EXTENSION: &str = "url";
+
+#[cfg(windows)]
+fn shortcut_contents(url: &Url, _title: &str) -> Result<Vec<u8>, String> {
+    Ok(format!("[InternetShortcut]\r\nURL={}\r\n", url).into_bytes())
+}
+
+#[cfg(target_os = "macos")]
+const SHORTCUT_EXTENSION: &str = "webloc";
+
+#[cfg(target_os = "macos")]
+fn shortcut_contents(url: &Url, _title: &str) -> Result<Vec<u8>, String> {
+    let mut dictionary = plist::Dictionary::new();
+    dictionary.insert("URL".to_string(), plist::Value::String(url.to_string()));
+
+    let mut contents = Vec::new();
+    plist::to_writer_xml(&mut contents, &plist::Value::Dictionary(dictionary))
+        .map_err(|error| error.to_string())?;
+    Ok(contents)
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+const SHORTCUT_EXTENSION: &str = "desktop";
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn shortcut_contents(url: &Url, title: &str) -> Result<Vec<u8>, String> {
+    Ok(format!(
+        "[Desktop Entry]\nVersion=1.0\nType=Link\nName={}\nURL={}\nIcon=text-html\n",
+        title, url
+    )
+    .into_bytes())
+}
+
+fn create_shortcut(directory: &Path, url: &Url, title: Option<String>) -> Result<String, String> {
+    if !directory.is_dir() {
+        return Err(format!("Not a directory: {}", directory.display()));
+    }
+
+    let title = title.or_else(|| fetch_title(url));
+    let name = shortcut_name(url, title.as_deref());
+    let display_name = name
+        .strip_suffix(&format!(".{}", SHORTCUT_EXTENSION))
+        .unwrap_or(&name)
+        .to_string();
+    let path = get_unique_destination_path(directory, &name);
+
+    std::fs::write(&path, shortcut_contents(url, &display_name)?)
+        .map_err(|error| error.to_string())?;
+    Ok(normalize_path(&path.to_string_lossy()))
+}
+
+/// The URL on the clipboard, if it holds one
+#[tauri::command]
+pub async fn get_clipboard_url(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    let text = tokio::task::spawn_blocking(move || crate::clipboard::get_text(&app))
+        .await
+        .map_err(|error| error.to_string())??;
+
+    Ok(text.as_deref().and_then(parse_url).map(String::from))
+}
+
+/// Creates a shortcut to `url` in `directory` and returns its path. Without
+/// a `title`, the shortcut is named after the page title, or the host when
+/// the page can't be fetched.
+#[tauri::command]
+pub async fn create_internet_shortcut(
+    directory: String,
+    url: String,
+    title: Option<String>,
+) -> Result<String, String> {
+    let url = parse_url(&url).ok_or_else(|| format!("Not a web address: {}", url))?;
+
+    tokio::task::spawn_blocking(move || create_shortcut(Path::new(&directory), &url, title))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Creates a shortcut to the URL on the clipboard in `directory`. Returns
+/// `None` when the clipboard holds no URL.
+#[tauri::command]
+pub async fn create_shortcut_from_clipboard(
+    app: tauri::AppHandle,
+    directory: String,
+) -> Result<Option<String>, String> {
+    tokio::task::spawn_blocking(move || {
+        let text = crate::clipboard::get_text(&app)?;
+        let Some(url) = text.as_deref().and_then(parse_url) else {
+            return Ok(None);
+        };
+
+        create_shortcut(Path::new(&directory), &url, None).map(Some)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
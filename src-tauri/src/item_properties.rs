@@ -0,0 +1,323 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use crate::dir_reader::{get_ownership_info, is_hidden};
+use crate::dir_size::{is_excluded, ExclusionPreset};
+use crate::requests::{self, ActiveRequest, ProgressEmitter};
+use crate::skipped_dirs::SkippedDirs;
+use crate::utils::normalize_path;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+const PROGRESS_EVENT: &str = "item-properties-progress";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ItemPropertiesProgress {
+    pub request_id: String,
+    pub total_size: u64,
+    pub nested_file_count: u64,
+    pub nested_dir_count: u64,
+}
+
+/// Attributes shared by every selected item. A field is `None` when the
+/// items disagree or the platform doesn't have that attribute.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommonAttributes {
+    pub is_hidden: Option<bool>,
+    pub is_readonly: Option<bool>,
+    pub mime: Option<String>,
+    pub mode: Option<u32>,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    /// Raw FILE_ATTRIBUTE_* flags on Windows
+    pub windows_attributes: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkTarget {
+    pub path: String,
+    pub target: String,
+    pub is_broken: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ItemProperties {
+    pub item_count: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+    pub symlink_count: u64,
+    pub missing_count: u64,
+    /// Size of all files, including everything inside selected folders
+    pub total_size: u64,
+    /// Space those files take on disk, less than `total_size` when some of
+    /// them are sparse or compressed
+    pub total_allocated_size: u64,
+    pub nested_file_count: u64,
+    pub nested_dir_count: u64,
+    /// Whether the size walk finished, it stops early when cancelled
+    pub is_size_complete: bool,
+    pub inaccessible_count: u64,
+    /// Folders whose content isn't in the totals for lack of permission
+    pub skipped_dirs: SkippedDirs,
+    pub earliest_created_time: Option<u64>,
+    pub latest_created_time: Option<u64>,
+    pub earliest_modified_time: Option<u64>,
+    pub latest_modified_time: Option<u64>,
+    pub common: CommonAttributes,
+    pub link_targets: Vec<LinkTarget>,
+}
+
+impl ItemProperties {
+    fn add_file(&mut self, path: &Path, metadata: &fs::Metadata) {
+        self.total_size += metadata.len();
+        self.total_allocated_size +=
+            crate::sparse_files::allocated_size(path, metadata).unwrap_or(metadata.len());
+    }
+}
+
+fn to_millis(time: std::io::Result<SystemTime>) -> Option<u64> {
+    time.ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as u64)
+}
+
+// Keeps the value while every item agrees on it. `is_first` seeds it.
+fn merge_common<T: PartialEq>(common: &mut Option<T>, value: Option<T>, is_first: bool) {
+    if is_first {
+        *common = value;
+    } else if *common != value {
+        *common = None;
+    }
+}
+
+fn merge_range(earliest: &mut Option<u64>, latest: &mut Option<u64>, value: Option<u64>) {
+    if let Some(value) = value {
+        *earliest = Some(earliest.map_or(value, |current| current.min(value)));
+        *latest = Some(latest.map_or(value, |current| current.max(value)));
+    }
+}
+
+#[cfg(windows)]
+fn windows_attributes(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::windows::fs::MetadataExt;
+    Some(metadata.file_attributes())
+}
+
+#[cfg(not(windows))]
+fn windows_attributes(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+struct SizeWalk<'a> {
+    progress: ProgressEmitter<'a>,
+    cancel_token: &'a AtomicBool,
+    presets: &'a [ExclusionPreset],
+}
+
+impl SizeWalk<'_> {
+    fn emit_progress(&self, properties: &ItemProperties, force: bool) {
+        self.progress
+            .emit(force, |request_id| ItemPropertiesProgress {
+                request_id: request_id.to_string(),
+                total_size: properties.total_size,
+                nested_file_count: properties.nested_file_count,
+                nested_dir_count: properties.nested_dir_count,
+            });
+    }
+
+    // Returns false when cancelled
+    fn add_dir(&mut self, dir_path: &Path, properties: &mut ItemProperties) -> bool {
+        // Copied out so the filter doesn't keep `self` borrowed
+        let presets = self.presets;
+
+        for entry in WalkDir::new(dir_path)
+            .follow_links(false)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| !is_excluded(entry, presets))
+        {
+            if self.cancel_token.load(Ordering::Relaxed) {
+                return false;
+            }
+
+            let (entry, metadata) = match entry.and_then(|entry| {
+                let metadata = entry.metadata()?;
+                Ok((entry, metadata))
+            }) {
+                Ok(entry_and_metadata) => entry_and_metadata,
+                Err(error) => {
+                    properties.skipped_dirs.record(&error);
+                    properties.inaccessible_count += 1;
+                    continue;
+                }
+            };
+
+            if metadata.is_dir() {
+                properties.nested_dir_count += 1;
+            } else {
+                properties.nested_file_count += 1;
+                properties.add_file(entry.path(), &metadata);
+            }
+
+            self.emit_progress(properties, false);
+        }
+
+        true
+    }
+}
+
+fn collect_properties(
+    app: &tauri::AppHandle,
+    owner_window: Option<&str>,
+    paths: &[String],
+    request_id: Option<&str>,
+    cancel_token: &AtomicBool,
+    presets: &[ExclusionPreset],
+) -> ItemProperties {
+    let mut properties = ItemProperties {
+        item_count: paths.len() as u64,
+        is_size_complete: true,
+        ..Default::default()
+    };
+    let mut common = CommonAttributes::default();
+    let mut dir_paths: Vec<&Path> = Vec::new();
+    let mut is_first = true;
+
+    for path_string in paths {
+        let path = Path::new(path_string);
+
+        let Ok(symlink_metadata) = fs::symlink_metadata(path) else {
+            properties.missing_count += 1;
+            continue;
+        };
+
+        let is_symlink = symlink_metadata.is_symlink();
+
+        if is_symlink {
+            properties.symlink_count += 1;
+
+            if let Ok(target) = fs::read_link(path) {
+                properties.link_targets.push(LinkTarget {
+                    path: normalize_path(path_string),
+                    target: normalize_path(&target.to_string_lossy()),
+                    is_broken: fs::metadata(path).is_err(),
+                });
+            }
+        }
+
+        // Links are described by their target, like in the file list
+        let metadata = fs::metadata(path).unwrap_or(symlink_metadata);
+
+        if metadata.is_dir() {
+            properties.dir_count += 1;
+            // Linked folders aren't walked so their content isn't counted twice
+            if !is_symlink {
+                dir_paths.push(path);
+            }
+        } else {
+            properties.file_count += 1;
+            properties.add_file(path, &metadata);
+        }
+
+        merge_range(
+            &mut properties.earliest_created_time,
+            &mut properties.latest_created_time,
+            to_millis(metadata.created()),
+        );
+        merge_range(
+            &mut properties.earliest_modified_time,
+            &mut properties.latest_modified_time,
+            to_millis(metadata.modified()),
+        );
+
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let mime = if metadata.is_file() {
+            crate::dir_reader::get_mime_type(&extension)
+        } else {
+            None
+        };
+        let (mode, owner, group) = get_ownership_info(&metadata);
+
+        merge_common(&mut common.is_hidden, Some(is_hidden(path)), is_first);
+        merge_common(
+            &mut common.is_readonly,
+            Some(metadata.permissions().readonly()),
+            is_first,
+        );
+        merge_common(&mut common.mime, mime, is_first);
+        merge_common(&mut common.mode, mode, is_first);
+        merge_common(&mut common.owner, owner, is_first);
+        merge_common(&mut common.group, group, is_first);
+        merge_common(
+            &mut common.windows_attributes,
+            windows_attributes(&metadata),
+            is_first,
+        );
+        is_first = false;
+    }
+
+    properties.common = common;
+
+    let mut size_walk = SizeWalk {
+        progress: ProgressEmitter::new(app, owner_window, PROGRESS_EVENT, request_id),
+        cancel_token,
+        presets,
+    };
+
+    for dir_path in dir_paths {
+        if !size_walk.add_dir(dir_path, &mut properties) {
+            properties.is_size_complete = false;
+            break;
+        }
+    }
+
+    size_walk.emit_progress(&properties, true);
+    properties
+}
+
+/// Aggregated info for the properties dialog of one or more items. The
+/// recursive size is computed in the background; when `request_id` is given,
+/// partial totals are emitted as `item-properties-progress` events and the
+/// walk can be stopped with `cancel_item_properties`. `exclude` leaves
+/// derived folders such as `node_modules` out of the recursive totals.
+/// Progress goes to `window_id`, or to the calling window when omitted.
+#[tauri::command]
+pub async fn get_item_properties(
+    app: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    paths: Vec<String>,
+    request_id: Option<String>,
+    exclude: Option<Vec<ExclusionPreset>>,
+    window_id: Option<String>,
+) -> Result<ItemProperties, String> {
+    let presets = exclude.unwrap_or_default();
+    let active_request = ActiveRequest::register(request_id.as_deref());
+    let cancel_token = active_request.cancel_token();
+    let owner_window = window_id.unwrap_or_else(|| window.label().to_string());
+
+    tokio::task::spawn_blocking(move || {
+        collect_properties(
+            &app,
+            Some(&owner_window),
+            &paths,
+            request_id.as_deref(),
+            &cancel_token,
+            &presets,
+        )
+    })
+    .await
+    .map_err(|error| error.to_string())
+}
+
+/// Stops the size walk of a running `get_item_properties` call
+#[tauri::command]
+pub fn cancel_item_properties(request_id: String) {
+    requests::cancel(&request_id);
+}
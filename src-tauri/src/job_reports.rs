@@ -0,0 +1,393 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Manifests of finished jobs: every item, what was done with it, its size,
+//! how long it took and why it failed or was skipped. Kept in
+//! `<app data>/job-reports/<job id>.json`, the latest `MAX_REPORTS` of them,
+//! with their summaries in the `job-reports` store for listing them.
+
+use crate::json_store;
+use crate::utils::normalize_path;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+const REPORTS_DIR: &str = "job-reports";
+const STORE_NAME: &str = "job-reports";
+const MAX_REPORTS: usize = 200;
+
+/// Summaries of the saved reports, newest first
+static INDEX: Lazy<Mutex<Vec<JobReportSummary>>> = Lazy::new(|| Mutex::new(load_index()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Copy,
+    Move,
+    Delete,
+    Trash,
+    Sync,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemAction {
+    Copied,
+    Moved,
+    Deleted,
+    Trashed,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The destination had an item of that name and the conflict
+    /// resolution was to skip
+    AlreadyExists,
+    /// The item was in the destination folder already
+    SameFolder,
+    /// Changed on both sides of a sync since the last one
+    Conflict,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobReportItem {
+    pub path: String,
+    pub destination: Option<String>,
+    pub action: ItemAction,
+    /// Size of the item, folders included, when it was known
+    pub bytes: Option<u64>,
+    pub duration_ms: Option<u64>,
+    pub error: Option<String>,
+    pub skip_reason: Option<SkipReason>,
+}
+
+impl JobReportItem {
+    pub fn new(path: &Path, action: ItemAction) -> Self {
+        Self {
+            path: normalize_path(&path.to_string_lossy()),
+            destination: None,
+            action,
+            bytes: None,
+            duration_ms: None,
+            error: None,
+            skip_reason: None,
+        }
+    }
+
+    pub fn failed(path: &Path, error: impl Into<String>) -> Self {
+        Self {
+            error: Some(error.into()),
+            ..Self::new(path, ItemAction::Failed)
+        }
+    }
+
+    pub fn skipped(path: &Path, reason: SkipReason) -> Self {
+        Self {
+            skip_reason: Some(reason),
+            ..Self::new(path, ItemAction::Skipped)
+        }
+    }
+
+    pub fn destination(mut self, destination: &Path) -> Self {
+        self.destination = Some(normalize_path(&destination.to_string_lossy()));
+        self
+    }
+
+    pub fn bytes(mut self, bytes: u64) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    pub fn started_at(mut self, started_at: Instant) -> Self {
+        self.duration_ms = Some(started_at.elapsed().as_millis() as u64);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobReportSummary {
+    pub job_id: String,
+    pub kind: JobKind,
+    /// Milliseconds since the Unix epoch
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub processed_count: u64,
+    pub failed_count: u64,
+    pub skipped_count: u64,
+    pub total_bytes: u64,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobReport {
+    #[serde(flatten)]
+    pub summary: JobReportSummary,
+    pub items: Vec<JobReportItem>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+/// Items recorded from several threads at once, such as the files and
+/// folders of a folder copy, for `JobRecorder::record_all`
+#[derive(Debug, Default)]
+pub struct ItemLog(Mutex<Vec<JobReportItem>>);
+
+impl ItemLog {
+    pub fn record(&self, item: JobReportItem) {
+        if let Ok(mut items) = self.0.lock() {
+            items.push(item);
+        }
+    }
+
+    /// The items recorded so far, leaving the log empty
+    pub fn take(&self) -> Vec<JobReportItem> {
+        self.0
+            .lock()
+            .map(|mut items| std::mem::take(&mut *items))
+            .unwrap_or_default()
+    }
+}
+
+/// Collects the items of a running job, see `finish`
+pub struct JobRecorder {
+    report: JobReport,
+    started: Instant,
+}
+
+impl JobRecorder {
+    pub fn new(kind: JobKind) -> Self {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+
+        Self {
+            report: JobReport {
+                summary: JobReportSummary {
+                    job_id: uuid::Uuid::new_v4().to_string(),
+                    kind,
+                    started_at,
+                    duration_ms: 0,
+                    processed_count: 0,
+                    failed_count: 0,
+                    skipped_count: 0,
+                    total_bytes: 0,
+                    cancelled: false,
+                },
+                items: Vec::new(),
+            },
+            started: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, item: JobReportItem) {
+        let summary = &mut self.report.summary;
+        match item.action {
+            ItemAction::Failed => summary.failed_count += 1,
+            ItemAction::Skipped => summary.skipped_count += 1,
+            _ => {
+                summary.processed_count += 1;
+                summary.total_bytes += item.bytes.unwrap_or(0);
+            }
+        }
+        self.report.items.push(item);
+    }
+
+    pub fn record_all(&mut self, items: impl IntoIterator<Item = JobReportItem>) {
+        for item in items {
+            self.record(item);
+        }
+    }
+
+    pub fn set_cancelled(&mut self, cancelled: bool) {
+        self.report.summary.cancelled = cancelled;
+    }
+
+    /// Saves the report and returns its job id. A report that can't be
+    /// saved is logged, the job itself is done either way.
+    pub fn finish(mut self) -> Option<String> {
+        self.report.summary.duration_ms = self.started.elapsed().as_millis() as u64;
+
+        match save(&self.report) {
+            Ok(()) => Some(self.report.summary.job_id),
+            Err(error) => {
+                tracing::warn!("Failed to save job report: {}", error);
+                None
+            }
+        }
+    }
+}
+
+/// The total size of a file or folder, links not followed, for items that
+/// are renamed or deleted rather than copied
+pub fn item_bytes(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn reports_dir() -> Result<PathBuf, String> {
+    Ok(crate::json_store::data_dir()?.join(REPORTS_DIR))
+}
+
+fn report_path(job_id: &str) -> Result<PathBuf, String> {
+    // Ids come from the frontend, they must not point outside the folder
+    if uuid::Uuid::parse_str(job_id).is_err() {
+        return Err(format!("Invalid job id: {}", job_id));
+    }
+    Ok(reports_dir()?.join(format!("{}.json", job_id)))
+}
+
+// Reports saved before the index existed are read once to build it
+fn load_index() -> Vec<JobReportSummary> {
+    if json_store::store_path(STORE_NAME).is_ok_and(|path| path.exists()) {
+        return json_store::load(STORE_NAME);
+    }
+
+    let Some(entries) = reports_dir().ok().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut summaries: Vec<JobReportSummary> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let text = fs::read_to_string(entry.path()).ok()?;
+            let report: JobReport = serde_json::from_str(&text).ok()?;
+            Some(report.summary)
+        })
+        .collect();
+
+    summaries.sort_by(|first, second| second.started_at.cmp(&first.started_at));
+    let _ = json_store::save(STORE_NAME, &summaries);
+    summaries
+}
+
+fn save(report: &JobReport) -> Result<(), String> {
+    fs::create_dir_all(reports_dir()?).map_err(|error| error.to_string())?;
+
+    let json = serde_json::to_string(report).map_err(|error| error.to_string())?;
+    fs::write(report_path(&report.summary.job_id)?, json).map_err(|error| error.to_string())?;
+
+    let mut index = INDEX.lock().map_err(|error| error.to_string())?;
+    index.insert(0, report.summary.clone());
+
+    // Drops the oldest reports past MAX_REPORTS
+    if index.len() > MAX_REPORTS {
+        for summary in index.split_off(MAX_REPORTS) {
+            if let Ok(path) = report_path(&summary.job_id) {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    json_store::save(STORE_NAME, &*index)
+}
+
+fn load(job_id: &str) -> Result<JobReport, String> {
+    let text = fs::read_to_string(report_path(job_id)?)
+        .map_err(|_| format!("No report for job {}", job_id))?;
+    serde_json::from_str(&text).map_err(|error| error.to_string())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(report: &JobReport) -> String {
+    let mut csv = String::from("path,destination,action,bytes,duration_ms,error,skip_reason\n");
+
+    for item in &report.items {
+        let fields = [
+            csv_field(&item.path),
+            csv_field(item.destination.as_deref().unwrap_or("")),
+            serde_name(&item.action),
+            item.bytes
+                .map(|bytes| bytes.to_string())
+                .unwrap_or_default(),
+            item.duration_ms
+                .map(|duration| duration.to_string())
+                .unwrap_or_default(),
+            csv_field(item.error.as_deref().unwrap_or("")),
+            item.skip_reason
+                .as_ref()
+                .map(serde_name)
+                .unwrap_or_default(),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+// The snake_case name serde gives a unit variant
+fn serde_name<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+/// Reports of finished jobs, newest first, without their items
+#[tauri::command]
+pub async fn list_job_reports() -> Result<Vec<JobReportSummary>, String> {
+    tokio::task::spawn_blocking(|| {
+        INDEX
+            .lock()
+            .map(|index| index.clone())
+            .map_err(|error| error.to_string())
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub async fn get_job_report(job_id: String) -> Result<JobReport, String> {
+    tokio::task::spawn_blocking(move || load(&job_id))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Writes the report of a job to `destination_path`, as pretty printed JSON
+/// or as CSV with a row per item
+#[tauri::command]
+pub async fn export_job_report(
+    job_id: String,
+    destination_path: String,
+    format: ReportFormat,
+) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        let report = load(&job_id)?;
+        let contents = match format {
+            ReportFormat::Json => {
+                serde_json::to_string_pretty(&report).map_err(|error| error.to_string())?
+            }
+            ReportFormat::Csv => to_csv(&report),
+        };
+
+        fs::write(crate::utils::to_os_path(&destination_path), contents)
+            .map_err(|error| error.to_string())
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
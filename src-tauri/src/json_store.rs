@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Small JSON file stores for backend-owned state (tags, bookmarks, history).
+//! Each store lives in `<app data>/stores/<name>.json`.
+
+use once_cell::sync::OnceCell;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use tauri::Manager;
+
+static DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+pub fn init(app: &tauri::AppHandle) -> Result<(), String> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|error: tauri::Error| error.to_string())?;
+
+    let _ = DATA_DIR.set(data_dir);
+    Ok(())
+}
+
+pub fn data_dir() -> Result<PathBuf, String> {
+    DATA_DIR
+        .get()
+        .cloned()
+        .ok_or_else(|| "App data directory is not initialized".to_string())
+}
+
+pub fn stores_dir() -> Result<PathBuf, String> {
+    Ok(data_dir()?.join("stores"))
+}
+
+pub fn store_path(name: &str) -> Result<PathBuf, String> {
+    Ok(stores_dir()?.join(format!("{}.json", name)))
+}
+
+/// Loads a store, falling back to the default value when it doesn't exist.
+/// Unreadable stores are quarantined by `safe_mode` instead of being
+/// overwritten by the next save.
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    let path = match store_path(name) {
+        Ok(path) => path,
+        Err(_) => return T::default(),
+    };
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(_) => return T::default(),
+    };
+
+    match serde_json::from_str(&text) {
+        Ok(value) => value,
+        Err(error) => {
+            let backup_path = crate::safe_mode::quarantine(&path).ok();
+            crate::safe_mode::record_issue(name, &path, &error.to_string(), backup_path.as_deref());
+            T::default()
+        }
+    }
+}
+
+/// Writes a store atomically so a crash mid-write never leaves a truncated file
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<(), String> {
+    let path = store_path(name)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+
+    let json = serde_json::to_string_pretty(value).map_err(|error| error.to_string())?;
+    let temp_path = path.with_extension("json.tmp");
+
+    fs::write(&temp_path, json).map_err(|error| error.to_string())?;
+    fs::rename(&temp_path, &path).map_err(|error| error.to_string())
+}
@@ -4,17 +4,74 @@
 
 use tauri::Manager;
 
+mod app_data;
 mod app_updater;
+mod app_windows;
+mod batch_rename;
+mod bookmarks;
+mod capabilities;
+mod cleanup;
+mod clipboard;
+mod code_stats;
+mod credentials;
+mod dir_compare;
 mod dir_reader;
 mod dir_size;
+mod dir_sync;
 mod dir_watcher;
+mod disk_layout;
+mod drive_monitor;
+mod encryption;
+mod faults;
+mod file_attributes;
+mod file_compare;
+mod file_metadata;
 mod file_operations;
+mod filename_rules;
+mod folder_sizes;
+mod geotags;
+mod git;
+mod git_ignore;
 mod global_search;
+mod history;
+mod internet_shortcut;
+mod item_properties;
+mod job_reports;
+mod json_store;
+mod logging;
+mod media_library;
+mod messages;
+mod metrics;
+mod mirror;
+mod mtp;
+mod network_shares;
+mod open_handles;
 mod open_with;
+mod permissions;
+mod power;
+mod remote_fs;
+mod requests;
+mod safe_mode;
+mod scheduler;
+mod secure_delete;
+mod shell_links;
+mod shortcuts;
+mod skipped_dirs;
+mod sparse_files;
 mod system_icons;
 mod system_tray;
+mod tags;
 mod terminal;
+#[cfg(feature = "test-fixtures")]
+pub mod testing;
+mod text;
+mod thumbnails;
+mod trash_bin;
 pub mod utils;
+mod vfs;
+mod view_context;
+mod volume_paths;
+mod xattrs;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -39,12 +96,20 @@ pub fn run() {
             system_tray::reload_webview,
             system_tray::update_tray_shortcut,
             dir_reader::read_dir,
+            dir_reader::get_item_counts,
             dir_reader::get_system_drives,
             dir_reader::get_parent_dir,
             dir_reader::path_exists,
             dir_reader::get_mountable_devices,
+            disk_layout::get_disk_layout,
             dir_reader::mount_drive,
+            dir_reader::unlock_device,
             dir_reader::unmount_drive,
+            dir_reader::eject_drive,
+            dir_reader::set_volume_label,
+            dir_reader::mount_image,
+            dir_reader::unmount_image,
+            open_handles::find_blocking_processes,
             dir_reader::mount_network_share,
             dir_size::get_dir_size,
             dir_size::get_dir_sizes_batch,
@@ -53,13 +118,105 @@ pub fn run() {
             dir_size::invalidate_dir_size_cache,
             dir_size::clear_dir_size_cache,
             dir_size::cancel_dir_size,
+            code_stats::analyze_code_dir,
             file_operations::check_conflicts,
             file_operations::copy_items,
             file_operations::ensure_directory,
             file_operations::move_items,
+            file_operations::get_interrupted_moves,
+            file_operations::resolve_interrupted_move,
+            file_operations::get_resumable_operations,
+            file_operations::resume_operation,
+            file_operations::discard_operation,
             file_operations::rename_item,
             file_operations::delete_items,
+            trash_bin::list_trash,
+            trash_bin::get_trash_settings,
+            trash_bin::set_trash_settings,
+            trash_bin::preview_trash_purge,
+            trash_bin::purge_trash,
+            trash_bin::check_trash_restore,
+            trash_bin::restore_trash_items,
             file_operations::create_item,
+            file_operations::create_hardlink,
+            file_operations::duplicate_items,
+            file_operations::preflight_operation,
+            file_operations::salvage_copy,
+            job_reports::list_job_reports,
+            job_reports::get_job_report,
+            job_reports::export_job_report,
+            batch_rename::preview_batch_rename,
+            batch_rename::batch_rename,
+            filename_rules::scan_problematic_names,
+            filename_rules::fix_problematic_names,
+            filename_rules::validate_filename,
+            file_metadata::copy_attributes,
+            file_metadata::set_file_times,
+            mirror::verify_mirror,
+            dir_compare::compare_dirs,
+            file_compare::compare_files,
+            dir_compare::cancel_compare_dirs,
+            dir_sync::sync_dirs,
+            dir_sync::cancel_sync_dirs,
+            capabilities::get_capabilities,
+            cleanup::find_empty,
+            cleanup::find_large_files,
+            cleanup::find_broken_links,
+            cleanup::cancel_cleanup_scan,
+            cleanup::delete_found,
+            secure_delete::secure_delete,
+            secure_delete::cancel_secure_delete,
+            secure_delete::get_secure_delete_caveats,
+            encryption::encrypt_files,
+            encryption::decrypt_file,
+            encryption::cancel_file_encryption,
+            scheduler::list_tasks,
+            scheduler::add_task,
+            scheduler::remove_task,
+            scheduler::set_task_enabled,
+            scheduler::run_task_now,
+            scheduler::get_task_history,
+            mtp::list_mtp_devices,
+            mtp::get_mtp_storages,
+            safe_mode::get_safe_mode_status,
+            safe_mode::repair_store,
+            clipboard::clipboard_set_files,
+            clipboard::clipboard_get_files,
+            internet_shortcut::get_clipboard_url,
+            internet_shortcut::create_internet_shortcut,
+            internet_shortcut::create_shortcut_from_clipboard,
+            item_properties::get_item_properties,
+            item_properties::cancel_item_properties,
+            network_shares::list_network_shares,
+            network_shares::unmount_network_share,
+            network_shares::save_network_share,
+            network_shares::list_saved_shares,
+            network_shares::remove_saved_share,
+            network_shares::reconnect_share,
+            credentials::save_share_credentials,
+            credentials::load_share_credentials,
+            credentials::delete_share_credentials,
+            app_windows::list_app_windows,
+            logging::get_recent_logs,
+            logging::get_log_settings,
+            logging::set_log_settings,
+            metrics::get_performance_metrics,
+            metrics::reset_performance_metrics,
+            power::get_power_status,
+            power::set_power_policy,
+            app_data::export_app_data,
+            app_data::import_app_data,
+            remote_fs::remote_connect,
+            remote_fs::remote_disconnect,
+            remote_fs::remote_read_dir,
+            remote_fs::remote_download,
+            remote_fs::remote_upload,
+            remote_fs::remote_delete,
+            remote_fs::remote_rename,
+            remote_fs::remote_copy,
+            vfs::vfs_read_dir,
+            vfs::vfs_capabilities,
+            vfs::vfs_transfer,
             global_search::global_search_init,
             global_search::global_search_get_status,
             global_search::global_search_start_scan,
@@ -67,16 +224,69 @@ pub fn run() {
             global_search::global_search_index_paths,
             global_search::global_search_query,
             global_search::global_search_query_paths,
+            global_search::largest_items,
+            global_search::cancel_largest_items,
+            permissions::set_permissions,
+            permissions::set_owner,
+            permissions::get_acl,
+            permissions::set_acl,
+            xattrs::list_xattrs,
+            xattrs::get_xattr,
+            xattrs::set_xattr,
+            xattrs::remove_xattr,
+            xattrs::list_streams,
+            xattrs::read_stream,
+            xattrs::delete_stream,
+            xattrs::get_download_origin,
+            xattrs::clear_download_origin,
+            file_attributes::set_attributes,
+            tags::set_tags,
+            tags::get_tags,
+            tags::query_by_tag,
+            bookmarks::add_bookmark,
+            bookmarks::remove_bookmark,
+            bookmarks::list_bookmarks,
+            bookmarks::reorder_bookmarks,
+            history::suggest_destinations,
+            history::record_visit,
+            view_context::set_view_context,
+            view_context::get_view_context,
+            view_context::remove_view_context,
+            history::get_recent,
+            history::get_frecent_dirs,
+            text::set_collation_locale,
             open_with::get_associated_programs,
             open_with::open_with_program,
             open_with::open_with_default,
+            open_with::get_apps_for_file,
+            open_with::open_with,
+            open_with::get_default_app,
+            open_with::set_default_app,
             open_with::open_native_open_with_dialog,
             open_with::get_shell_context_menu,
             open_with::invoke_shell_context_menu_item,
+            shortcuts::resolve_shortcut,
+            shortcuts::create_shortcut,
             system_icons::get_system_icon,
+            system_icons::get_file_icon,
+            system_icons::get_drag_preview,
             terminal::get_available_terminals,
             terminal::get_terminal_icons,
             terminal::open_terminal,
+            thumbnails::get_video_filmstrip,
+            thumbnails::get_audio_waveform,
+            thumbnails::get_media_tracks,
+            thumbnails::extract_subtitles,
+            thumbnails::get_image_thumbnail,
+            thumbnails::get_animated_preview,
+            thumbnails::get_image_metadata,
+            thumbnails::get_image_palette,
+            thumbnails::find_similar_images,
+            geotags::get_geotagged_photos,
+            media_library::get_media_library_folders,
+            media_library::set_media_library_folders,
+            thumbnails::set_visible_thumbnails,
+            thumbnails::clear_thumbnail_cache,
             dir_watcher::watch_directory,
             dir_watcher::unwatch_directory,
             dir_watcher::get_watched_directories,
@@ -89,6 +299,9 @@ pub fn run() {
                     api.prevent_close();
                 }
             }
+            if let tauri::WindowEvent::Destroyed = event {
+                view_context::forget_window(window.label());
+            }
         })
         .on_menu_event(system_tray::handle_menu_event)
         .run(tauri::generate_context!())
@@ -96,14 +309,15 @@ pub fn run() {
 }
 
 fn setup_handler(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
-    if cfg!(debug_assertions) {
-        app.handle().plugin(
-            tauri_plugin_log::Builder::default()
-                .level(log::LevelFilter::Info)
-                .build(),
-        )?;
-    }
-
+    json_store::init(app.handle())?;
+    logging::init(app.handle())?;
+    safe_mode::init();
+    app_windows::init(app.handle());
+    metrics::init();
+    drive_monitor::start(app.handle().clone());
+    trash_bin::start_purging();
+    scheduler::start(app.handle().clone());
+    capabilities::start();
     system_tray::setup_system_tray(&app.handle())?;
 
     // Open devtools in production for debugging (TODO: remove after debugging)
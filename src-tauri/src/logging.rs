@@ -0,0 +1,297 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Backend logging built on `tracing`. Events go to a daily rotating file in
+//! the app log directory and to an in-memory ring buffer that backs the
+//! diagnostics panel. Levels can be changed per module at runtime.
+
+use crate::json_store;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+const STORE_NAME: &str = "log_settings";
+const LOG_FILE_PREFIX: &str = "sigma-file-manager";
+const MAX_LOG_FILES: usize = 7;
+const RECENT_LOGS_CAPACITY: usize = 5000;
+const DEFAULT_QUERY_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    /// Level for everything without a module override: error, warn, info,
+    /// debug or trace
+    pub default_level: String,
+    /// Per-module overrides keyed by target, e.g.
+    /// "sigma_file_manager::thumbnails" or a dependency like "tantivy"
+    pub module_levels: BTreeMap<String, String>,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            default_level: "info".to_string(),
+            module_levels: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogStatus {
+    pub settings: LogSettings,
+    pub log_dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: u64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogFilter {
+    /// Least severe level to include
+    pub min_level: Option<String>,
+    /// Only entries whose target starts with this prefix
+    pub target: Option<String>,
+    /// Case-insensitive match against the message and field values
+    pub contains: Option<String>,
+    /// Only entries at or after this timestamp (ms since epoch)
+    pub since: Option<u64>,
+    /// Newest entries to return, 500 by default
+    pub limit: Option<usize>,
+}
+
+static RECENT_LOGS: Lazy<Mutex<VecDeque<LogEntry>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(RECENT_LOGS_CAPACITY)));
+static FILTER_HANDLE: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+static FILE_GUARD: OnceCell<WorkerGuard> = OnceCell::new();
+static LOG_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+#[derive(Default)]
+struct FieldCollector {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldCollector {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+}
+
+/// Keeps the latest events in memory for `get_recent_logs`
+struct RecentLogsLayer;
+
+impl<S: Subscriber> Layer<S> for RecentLogsLayer {
+    fn on_event(&self, event: &Event<'_>, _context: Context<'_, S>) {
+        let mut collector = FieldCollector::default();
+        event.record(&mut collector);
+
+        // Records bridged from the `log` crate carry their origin as fields
+        let target = collector
+            .fields
+            .remove("log.target")
+            .unwrap_or_else(|| event.metadata().target().to_string());
+        collector.fields.retain(|name, _| !name.starts_with("log."));
+
+        let entry = LogEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or(0),
+            level: event.metadata().level().to_string().to_lowercase(),
+            target,
+            message: collector.message,
+            fields: collector.fields,
+        };
+
+        if let Ok(mut recent_logs) = RECENT_LOGS.lock() {
+            if recent_logs.len() == RECENT_LOGS_CAPACITY {
+                recent_logs.pop_front();
+            }
+            recent_logs.push_back(entry);
+        }
+    }
+}
+
+fn parse_level(level: &str) -> Result<Level, String> {
+    level
+        .parse::<Level>()
+        .map_err(|_| format!("Unknown log level: {}", level))
+}
+
+fn build_filter(settings: &LogSettings) -> Result<EnvFilter, String> {
+    let mut directives = vec![parse_level(&settings.default_level)?
+        .to_string()
+        .to_lowercase()];
+
+    for (module, level) in &settings.module_levels {
+        directives.push(format!(
+            "{}={}",
+            module,
+            parse_level(level)?.to_string().to_lowercase()
+        ));
+    }
+
+    EnvFilter::try_new(directives.join(",")).map_err(|error| error.to_string())
+}
+
+pub fn init(app: &tauri::AppHandle) -> Result<(), String> {
+    // Problems found before the subscriber exists are logged once it does
+    let mut startup_warnings: Vec<String> = Vec::new();
+
+    let settings: LogSettings = json_store::load(STORE_NAME);
+    let filter = build_filter(&settings).unwrap_or_else(|error| {
+        startup_warnings.push(format!("Invalid log settings, using defaults: {}", error));
+        EnvFilter::new("info")
+    });
+    let (filter_layer, filter_handle) = reload::Layer::new(filter);
+
+    let file_layer = app.path().app_log_dir().ok().and_then(|log_dir| {
+        let appender = RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(LOG_FILE_PREFIX)
+            .filename_suffix("log")
+            .max_log_files(MAX_LOG_FILES)
+            .build(&log_dir)
+            .map_err(|error| {
+                startup_warnings.push(format!("Failed to open the log file: {}", error))
+            })
+            .ok()?;
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+        let _ = FILE_GUARD.set(guard);
+        let _ = LOG_DIR.set(log_dir);
+
+        Some(
+            tracing_subscriber::fmt::layer()
+                .with_writer(writer)
+                .with_ansi(false),
+        )
+    });
+
+    let stdout_layer = cfg!(debug_assertions).then(tracing_subscriber::fmt::layer);
+    // Without a log file, release builds log to stderr instead
+    let stderr_layer = (file_layer.is_none() && !cfg!(debug_assertions))
+        .then(|| tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
+
+    tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(file_layer)
+        .with(stdout_layer)
+        .with(stderr_layer)
+        .with(RecentLogsLayer)
+        .try_init()
+        .map_err(|error| error.to_string())?;
+
+    let _ = FILTER_HANDLE.set(filter_handle);
+    for warning in startup_warnings {
+        tracing::warn!("{}", warning);
+    }
+    Ok(())
+}
+
+fn level_rank(level: &str) -> u8 {
+    match level {
+        "error" => 1,
+        "warn" => 2,
+        "info" => 3,
+        "debug" => 4,
+        _ => 5,
+    }
+}
+
+#[tauri::command]
+pub fn get_recent_logs(filter: Option<LogFilter>) -> Result<Vec<LogEntry>, String> {
+    let filter = filter.unwrap_or_default();
+    let max_rank = filter
+        .min_level
+        .as_deref()
+        .map(|level| level_rank(&level.to_lowercase()))
+        .unwrap_or(u8::MAX);
+    let needle = filter.contains.as_deref().map(str::to_lowercase);
+    let recent_logs = RECENT_LOGS.lock().map_err(|error| error.to_string())?;
+
+    let mut entries: Vec<LogEntry> = recent_logs
+        .iter()
+        .rev()
+        .filter(|entry| level_rank(&entry.level) <= max_rank)
+        .filter(|entry| filter.since.is_none_or(|since| entry.timestamp >= since))
+        .filter(|entry| {
+            filter
+                .target
+                .as_deref()
+                .is_none_or(|target| entry.target.starts_with(target))
+        })
+        .filter(|entry| {
+            needle.as_deref().is_none_or(|needle| {
+                entry.message.to_lowercase().contains(needle)
+                    || entry
+                        .fields
+                        .values()
+                        .any(|value| value.to_lowercase().contains(needle))
+            })
+        })
+        .take(filter.limit.unwrap_or(DEFAULT_QUERY_LIMIT))
+        .cloned()
+        .collect();
+
+    // Oldest first, the order a log is read in
+    entries.reverse();
+    Ok(entries)
+}
+
+#[tauri::command]
+pub fn get_log_settings() -> LogStatus {
+    LogStatus {
+        settings: json_store::load(STORE_NAME),
+        log_dir: LOG_DIR
+            .get()
+            .map(|log_dir| log_dir.to_string_lossy().to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn set_log_settings(settings: LogSettings) -> Result<LogStatus, String> {
+    let filter = build_filter(&settings)?;
+
+    if let Some(handle) = FILTER_HANDLE.get() {
+        handle.reload(filter).map_err(|error| error.to_string())?;
+    }
+
+    json_store::save(STORE_NAME, &settings)?;
+    tracing::info!(?settings, "Log levels changed");
+
+    Ok(get_log_settings())
+}
@@ -0,0 +1,327 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Metadata behind the media library views: album, artist and year of
+//! audio files from their tags, capture year and camera model of photos
+//! from EXIF. It is read for the library folders the user picks and kept
+//! in a store keyed by path, size and modification time, so only new and
+//! changed files are read again. The views themselves are served by
+//! `vfs` under `media://`.
+
+use crate::json_store;
+use crate::utils::normalize_path;
+use exif::Tag;
+use lofty::file::TaggedFileExt;
+use lofty::tag::Accessor;
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+const STORE_NAME: &str = "media-library";
+/// Listings within this interval reuse the last scan of the folders
+const RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+const AUDIO_EXTENSIONS: [&str; 11] = [
+    "mp3", "flac", "ogg", "opus", "m4a", "aac", "wav", "wma", "aiff", "ape", "wv",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MediaMetadata {
+    pub album: Option<String>,
+    pub artist: Option<String>,
+    /// Release year of audio, capture year of photos
+    pub year: Option<u32>,
+    pub camera: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MediaEntry {
+    size: u64,
+    modified_time: u64,
+    #[serde(default)]
+    metadata: MediaMetadata,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MediaLibraryStore {
+    #[serde(default)]
+    folders: Vec<String>,
+    #[serde(default)]
+    files: HashMap<String, MediaEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LibraryFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified_time: u64,
+    pub metadata: MediaMetadata,
+}
+
+/// The ways the library can be browsed, each a top level folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaView {
+    Albums,
+    Artists,
+    Years,
+    Cameras,
+}
+
+impl MediaView {
+    pub const ALL: [MediaView; 4] = [
+        MediaView::Albums,
+        MediaView::Artists,
+        MediaView::Years,
+        MediaView::Cameras,
+    ];
+
+    pub fn folder_name(self) -> &'static str {
+        match self {
+            MediaView::Albums => "Albums",
+            MediaView::Artists => "Artists",
+            MediaView::Years => "Years",
+            MediaView::Cameras => "Cameras",
+        }
+    }
+
+    pub fn from_folder_name(name: &str) -> Option<Self> {
+        MediaView::ALL
+            .into_iter()
+            .find(|view| view.folder_name() == name)
+    }
+
+    /// Folder the file is grouped under, with characters that can't be in
+    /// a folder name replaced
+    pub fn group_of(self, metadata: &MediaMetadata) -> Option<String> {
+        let group = match self {
+            MediaView::Albums => metadata.album.clone(),
+            MediaView::Artists => metadata.artist.clone(),
+            MediaView::Years => metadata.year.map(|year| year.to_string()),
+            MediaView::Cameras => metadata.camera.clone(),
+        }?;
+
+        Some(group.replace(['/', '\\'], "-"))
+    }
+}
+
+static MEDIA_STORE: Lazy<Mutex<MediaLibraryStore>> =
+    Lazy::new(|| Mutex::new(json_store::load(STORE_NAME)));
+
+static LAST_SCAN: Lazy<Mutex<Option<(Instant, Vec<LibraryFile>)>>> = Lazy::new(|| Mutex::new(None));
+
+fn is_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+fn read_audio_metadata(path: &Path) -> MediaMetadata {
+    let Ok(tagged_file) = lofty::read_from_path(path) else {
+        return MediaMetadata::default();
+    };
+    let Some(tag) = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag())
+    else {
+        return MediaMetadata::default();
+    };
+
+    MediaMetadata {
+        album: tag.album().and_then(|album| non_empty(&album)),
+        artist: tag.artist().and_then(|artist| non_empty(&artist)),
+        year: tag.year().filter(|year| *year > 0),
+        camera: None,
+    }
+}
+
+fn read_photo_metadata(path: &Path, modified_time: u64) -> MediaMetadata {
+    let exif = File::open(path).ok().and_then(|file| {
+        exif::Reader::new()
+            .read_from_container(&mut BufReader::new(file))
+            .ok()
+    });
+
+    let ascii = |tag: Tag| {
+        exif.as_ref()
+            .and_then(|exif| crate::geotags::ascii_value(exif, tag))
+            .and_then(|value| non_empty(&String::from_utf8_lossy(&value)))
+    };
+
+    let year = ascii(Tag::DateTimeOriginal)
+        .and_then(|value| exif::DateTime::from_ascii(value.as_bytes()).ok())
+        .map(|date_time| date_time.year as u32)
+        .filter(|year| *year > 0)
+        // Scans and edited exports often lose their EXIF date
+        .or_else(|| Some(crate::utils::unix_millis_to_civil_date(modified_time).0 as u32));
+
+    // Most models already start with the make, such as "Canon EOS R5"
+    let camera = match (ascii(Tag::Make), ascii(Tag::Model)) {
+        (Some(make), Some(model)) if !model.to_lowercase().starts_with(&make.to_lowercase()) => {
+            Some(format!("{} {}", make, model))
+        }
+        (make, model) => model.or(make),
+    };
+
+    MediaMetadata {
+        album: None,
+        artist: None,
+        year,
+        camera,
+    }
+}
+
+fn file_version(path: &Path) -> Option<(u64, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified_time = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0);
+
+    Some((metadata.len(), modified_time))
+}
+
+fn scan(folders: &[String]) -> Result<Vec<LibraryFile>, String> {
+    let found: HashMap<String, (PathBuf, u64, u64)> = folders
+        .iter()
+        .flat_map(|folder| {
+            walkdir::WalkDir::new(folder)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    entry.file_type().is_file()
+                        && (is_audio(entry.path()) || crate::geotags::is_photo(entry.path()))
+                })
+                .filter_map(|entry| {
+                    let (size, modified_time) = file_version(entry.path())?;
+                    let key = normalize_path(&entry.path().to_string_lossy());
+                    Some((key, (entry.into_path(), size, modified_time)))
+                })
+        })
+        .collect();
+
+    let stale: Vec<(String, PathBuf, u64, u64)> = {
+        let store = MEDIA_STORE.lock().map_err(|error| error.to_string())?;
+
+        found
+            .iter()
+            .filter(|(key, (_, size, modified_time))| {
+                store.files.get(*key).map_or(true, |entry| {
+                    entry.size != *size || entry.modified_time != *modified_time
+                })
+            })
+            .map(|(key, (path, size, modified_time))| {
+                (key.clone(), path.clone(), *size, *modified_time)
+            })
+            .collect()
+    };
+
+    // Read without holding the lock, this is the slow part
+    let refreshed: Vec<(String, MediaEntry)> = stale
+        .into_par_iter()
+        .map(|(key, path, size, modified_time)| {
+            let metadata = if is_audio(&path) {
+                read_audio_metadata(&path)
+            } else {
+                read_photo_metadata(&path, modified_time)
+            };
+
+            (
+                key,
+                MediaEntry {
+                    size,
+                    modified_time,
+                    metadata,
+                },
+            )
+        })
+        .collect();
+
+    let mut store = MEDIA_STORE.lock().map_err(|error| error.to_string())?;
+    let count_before = store.files.len();
+    store.files.retain(|key, _| found.contains_key(key));
+    let changed = !refreshed.is_empty() || store.files.len() != count_before;
+    store.files.extend(refreshed);
+
+    if changed {
+        let _ = json_store::save(STORE_NAME, &*store);
+    }
+
+    Ok(found
+        .into_iter()
+        .filter_map(|(key, (path, size, modified_time))| {
+            Some(LibraryFile {
+                path,
+                size,
+                modified_time,
+                metadata: store.files.get(&key)?.metadata.clone(),
+            })
+        })
+        .collect())
+}
+
+/// Media files in the library folders with their metadata. Folders are
+/// rescanned when the last scan is older than `RESCAN_INTERVAL`.
+pub fn library_files() -> Result<Vec<LibraryFile>, String> {
+    let mut last_scan = LAST_SCAN.lock().map_err(|error| error.to_string())?;
+
+    if let Some((scanned_at, files)) = last_scan.as_ref() {
+        if scanned_at.elapsed() < RESCAN_INTERVAL {
+            return Ok(files.clone());
+        }
+    }
+
+    let folders = MEDIA_STORE
+        .lock()
+        .map_err(|error| error.to_string())?
+        .folders
+        .clone();
+    let files = scan(&folders)?;
+    *last_scan = Some((Instant::now(), files.clone()));
+
+    Ok(files)
+}
+
+#[tauri::command]
+pub fn get_media_library_folders() -> Result<Vec<String>, String> {
+    Ok(MEDIA_STORE
+        .lock()
+        .map_err(|error| error.to_string())?
+        .folders
+        .clone())
+}
+
+/// Sets the folders the media views are built from
+#[tauri::command]
+pub fn set_media_library_folders(folders: Vec<String>) -> Result<(), String> {
+    let mut folders: Vec<String> = folders
+        .iter()
+        .map(|folder| normalize_path(folder))
+        .filter(|folder| !folder.is_empty())
+        .collect();
+    folders.sort();
+    folders.dedup();
+
+    let mut store = MEDIA_STORE.lock().map_err(|error| error.to_string())?;
+    store.folders = folders;
+    json_store::save(STORE_NAME, &*store)?;
+    drop(store);
+
+    *LAST_SCAN.lock().map_err(|error| error.to_string())? = None;
+
+    Ok(())
+}
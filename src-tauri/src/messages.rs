@@ -0,0 +1,205 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Catalog of user-facing messages produced by the backend. Instead of a
+//! finished English sentence, errors carry a message key and parameters
+//! serialized as JSON; the frontend looks the key up under `backendMessages`
+//! in its locale files and falls back to the English `message`.
+//!
+//! Templates use the same `{name}` placeholders as vue-i18n, so the English
+//! text here and in `en.json` stay identical.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Message {
+    key: &'static str,
+    template: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalizedMessage {
+    pub key: String,
+    pub params: BTreeMap<String, String>,
+    /// English text with the parameters filled in
+    pub message: String,
+}
+
+impl Message {
+    const fn new(key: &'static str, template: &'static str) -> Self {
+        Message { key, template }
+    }
+
+    pub fn text(self) -> LocalizedMessage {
+        LocalizedMessage {
+            key: self.key.to_string(),
+            params: BTreeMap::new(),
+            message: self.template.to_string(),
+        }
+    }
+
+    pub fn with(self, name: &str, value: impl Display) -> LocalizedMessage {
+        self.text().with(name, value)
+    }
+}
+
+impl LocalizedMessage {
+    pub fn with(mut self, name: &str, value: impl Display) -> Self {
+        let value = value.to_string();
+        self.message = self.message.replace(&format!("{{{}}}", name), &value);
+        self.params.insert(name.to_string(), value);
+        self
+    }
+}
+
+// Commands keep returning `Result<_, String>`, the message travels as JSON
+impl From<LocalizedMessage> for String {
+    fn from(message: LocalizedMessage) -> Self {
+        serde_json::to_string(&message).unwrap_or(message.message)
+    }
+}
+
+// Mounting
+
+pub const MOUNT_INSTALL_UDISKS: Message = Message::new(
+    "mount.installUdisks",
+    "Could not mount {device}. Install udisks2 for automatic mounting.",
+);
+pub const UNMOUNT_INSTALL_UDISKS: Message = Message::new(
+    "mount.unmountInstallUdisks",
+    "Could not unmount. Install udisks2 or use 'umount {mountPoint}'.",
+);
+pub const MOUNT_NOT_SUPPORTED_WINDOWS: Message = Message::new(
+    "mount.notSupportedOnWindows",
+    "Mount not supported on Windows - drives are auto-mounted",
+);
+pub const UNMOUNT_NOT_SUPPORTED_WINDOWS: Message = Message::new(
+    "mount.unmountNotSupportedOnWindows",
+    "Unmount not supported on Windows - use system tray eject",
+);
+pub const MOUNT_COMMAND_FAILED: Message =
+    Message::new("mount.commandFailed", "Failed to run {command}: {error}");
+pub const MOUNT_FAILED: Message = Message::new("mount.failed", "{protocol} mount failed: {error}");
+pub const MOUNT_DEVICE_LOCKED: Message = Message::new(
+    "mount.deviceLocked",
+    "{device} is encrypted. Unlock it with its passphrase first.",
+);
+pub const MOUNT_POINT_CREATE_FAILED: Message = Message::new(
+    "mount.mountPointCreateFailed",
+    "Failed to create mount point: {error}",
+);
+pub const MOUNT_UNKNOWN_PROTOCOL: Message =
+    Message::new("mount.unknownProtocol", "Unknown protocol: {protocol}");
+pub const MOUNT_SSHFS_NOT_INSTALLED: Message = Message::new(
+    "mount.sshfsNotInstalled",
+    "Failed to run sshfs: {error}. Is sshfs installed?",
+);
+pub const MOUNT_SSHFS_WINDOWS: Message = Message::new(
+    "mount.sshfsWindowsRequirements",
+    "SSHFS on Windows requires WinFSP and sshfs-win. Install from https://github.com/winfsp/sshfs-win",
+);
+pub const MOUNT_NFS_WINDOWS: Message = Message::new(
+    "mount.nfsWindowsRequirements",
+    "NFS on Windows requires 'Services for NFS' Windows feature to be enabled",
+);
+
+// File operations and conflicts
+
+pub const SOURCE_NOT_FOUND: Message = Message::new(
+    "fileOperations.sourceNotFound",
+    "Source path does not exist: {path}",
+);
+pub const SOURCE_INVALID: Message = Message::new(
+    "fileOperations.sourceInvalid",
+    "Invalid source path: {path}",
+);
+pub const SOURCE_NOT_FILE: Message = Message::new(
+    "fileOperations.sourceNotFile",
+    "Source is not a file: {path}",
+);
+pub const DESTINATION_NOT_FOUND: Message = Message::new(
+    "fileOperations.destinationNotFound",
+    "Destination path does not exist: {path}",
+);
+pub const DESTINATION_NOT_DIRECTORY: Message = Message::new(
+    "fileOperations.destinationNotDirectory",
+    "Destination is not a directory: {path}",
+);
+pub const PATH_NOT_FOUND: Message =
+    Message::new("fileOperations.pathNotFound", "Path does not exist: {path}");
+pub const PATH_NOT_DIRECTORY: Message = Message::new(
+    "fileOperations.pathNotDirectory",
+    "Path is not a directory: {path}",
+);
+pub const PATH_ALREADY_EXISTS: Message = Message::new(
+    "fileOperations.pathAlreadyExists",
+    "Path already exists: {path}",
+);
+pub const DIRECTORY_NOT_FOUND: Message = Message::new(
+    "fileOperations.directoryNotFound",
+    "Directory does not exist: {path}",
+);
+pub const NAME_ALREADY_EXISTS: Message = Message::new(
+    "fileOperations.nameAlreadyExists",
+    "A file or folder with the name '{name}' already exists",
+);
+pub const NAME_EMPTY: Message = Message::new("fileOperations.nameEmpty", "Name cannot be empty");
+pub const NAME_HAS_SEPARATORS: Message = Message::new(
+    "fileOperations.nameHasSeparators",
+    "Name contains invalid path separators",
+);
+pub const NO_PARENT_DIRECTORY: Message = Message::new(
+    "fileOperations.noParentDirectory",
+    "Cannot determine parent directory",
+);
+pub const INSUFFICIENT_SPACE: Message = Message::new(
+    "fileOperations.insufficientSpace",
+    "Not enough free space on {volume}: {required} bytes required, {available} bytes available",
+);
+pub const INSUFFICIENT_SPACE_DESTINATION: Message = Message::new(
+    "fileOperations.insufficientSpaceOnDestination",
+    "Not enough free space on the destination volume: {required} bytes required, {available} bytes available",
+);
+pub const CHECKSUM_MISMATCH: Message = Message::new(
+    "fileOperations.checksumMismatch",
+    "The copy of {path} does not match the original, the source was kept",
+);
+pub const COPY_MISMATCH: Message = Message::new(
+    "fileOperations.copyMismatch",
+    "The copy of {path} does not match the original",
+);
+
+pub const SECURE_DELETE_SOLID_STATE: Message = Message::new(
+    "secureDelete.solidState",
+    "{volume} is a solid-state drive, which writes to new cells and keeps old ones until it reclaims them, so overwriting can't guarantee the data is gone. Full-disk encryption protects it reliably.",
+);
+pub const SECURE_DELETE_COPY_ON_WRITE: Message = Message::new(
+    "secureDelete.copyOnWrite",
+    "{volume} uses {fileSystem}, which writes changes to new blocks and may keep snapshots, so the original data can survive overwriting. Full-disk encryption protects it reliably.",
+);
+pub const SECURE_DELETE_NETWORK: Message = Message::new(
+    "secureDelete.network",
+    "{volume} is a network share. The server may keep snapshots or backups that overwriting from here can't reach.",
+);
+pub const SECURE_DELETE_UNKNOWN_DRIVE: Message = Message::new(
+    "secureDelete.unknownDrive",
+    "The kind of drive behind {volume} can't be determined. If it is a solid-state drive, overwriting can't guarantee the data is gone.",
+);
+pub const SECURE_DELETE_HARD_LINKS: Message = Message::new(
+    "secureDelete.hardLinks",
+    "{path} has other hard links, overwriting it would destroy their content too",
+);
+
+// Remote connections
+
+pub const REMOTE_UNKNOWN_HOST_KEY: Message = Message::new(
+    "remote.unknownHostKey",
+    "The authenticity of {host} can't be established. Its {keyType} key fingerprint is {fingerprint}. Connect only if it matches the one of the server.",
+);
+pub const REMOTE_HOST_KEY_MISMATCH: Message = Message::new(
+    "remote.hostKeyMismatch",
+    "The host key of {host} does not match the known one. The server may have been reinstalled, or the connection is being intercepted.",
+);
@@ -0,0 +1,287 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! In-process performance counters for diagnosing slow directories and
+//! drives. Nothing is persisted or sent anywhere; the numbers live until the
+//! app exits or `reset_performance_metrics` is called.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the latency buckets in milliseconds; slower calls land in
+/// a final overflow bucket
+const LATENCY_BUCKETS_MS: [u64; 12] = [1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    counts: [u64; LATENCY_BUCKETS_MS.len() + 1],
+    total_ms: f64,
+    max_ms: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct CacheCounter {
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct JobCounter {
+    jobs: u64,
+    items: u64,
+    failed_items: u64,
+    busy_time: Duration,
+}
+
+#[derive(Default)]
+struct Metrics {
+    commands: HashMap<&'static str, Histogram>,
+    caches: HashMap<&'static str, CacheCounter>,
+    jobs: HashMap<&'static str, JobCounter>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyBucket {
+    /// `None` for the overflow bucket
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandMetrics {
+    pub command: String,
+    pub calls: u64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+    /// Approximated from the histogram buckets
+    pub p95_ms: Option<u64>,
+    pub buckets: Vec<LatencyBucket>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheMetrics {
+    pub cache: String,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobMetrics {
+    pub kind: String,
+    pub jobs: u64,
+    pub items: u64,
+    pub failed_items: u64,
+    pub items_per_second: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatcherMetrics {
+    /// Filesystem events received but not yet processed
+    pub queue_depth: usize,
+    pub max_queue_depth: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerformanceMetrics {
+    pub uptime_seconds: u64,
+    pub commands: Vec<CommandMetrics>,
+    pub caches: Vec<CacheMetrics>,
+    pub jobs: Vec<JobMetrics>,
+    pub watcher: WatcherMetrics,
+}
+
+static METRICS: Lazy<Mutex<Metrics>> = Lazy::new(|| Mutex::new(Metrics::default()));
+static STARTED_AT: Lazy<Instant> = Lazy::new(Instant::now);
+static WATCHER_QUEUE_DEPTH: AtomicUsize = AtomicUsize::new(0);
+static WATCHER_MAX_QUEUE_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Records the command's latency when dropped
+pub struct CommandTimer {
+    command: &'static str,
+    started_at: Instant,
+}
+
+impl Drop for CommandTimer {
+    fn drop(&mut self) {
+        record_latency(self.command, self.started_at.elapsed());
+    }
+}
+
+pub fn init() {
+    Lazy::force(&STARTED_AT);
+}
+
+pub fn time_command(command: &'static str) -> CommandTimer {
+    CommandTimer {
+        command,
+        started_at: Instant::now(),
+    }
+}
+
+fn record_latency(command: &'static str, elapsed: Duration) {
+    let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+    let bucket = LATENCY_BUCKETS_MS
+        .iter()
+        .position(|bound| elapsed_ms <= *bound as f64)
+        .unwrap_or(LATENCY_BUCKETS_MS.len());
+
+    if let Ok(mut metrics) = METRICS.lock() {
+        let histogram = metrics.commands.entry(command).or_default();
+        histogram.counts[bucket] += 1;
+        histogram.total_ms += elapsed_ms;
+        histogram.max_ms = histogram.max_ms.max(elapsed_ms);
+    }
+}
+
+pub fn record_cache_lookup(cache: &'static str, is_hit: bool) {
+    if let Ok(mut metrics) = METRICS.lock() {
+        let counter = metrics.caches.entry(cache).or_default();
+
+        if is_hit {
+            counter.hits += 1;
+        } else {
+            counter.misses += 1;
+        }
+    }
+}
+
+pub fn record_job(kind: &'static str, items: u64, failed_items: u64, elapsed: Duration) {
+    if let Ok(mut metrics) = METRICS.lock() {
+        let counter = metrics.jobs.entry(kind).or_default();
+        counter.jobs += 1;
+        counter.items += items;
+        counter.failed_items += failed_items;
+        counter.busy_time += elapsed;
+    }
+}
+
+pub fn watcher_event_queued() {
+    let depth = WATCHER_QUEUE_DEPTH.fetch_add(1, Ordering::Relaxed) + 1;
+    WATCHER_MAX_QUEUE_DEPTH.fetch_max(depth, Ordering::Relaxed);
+}
+
+pub fn watcher_event_processed() {
+    let _ = WATCHER_QUEUE_DEPTH.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |depth| {
+        Some(depth.saturating_sub(1))
+    });
+}
+
+fn percentile_bound(histogram: &Histogram, calls: u64, percentile: f64) -> Option<u64> {
+    let target = (calls as f64 * percentile).ceil() as u64;
+    let mut seen = 0;
+
+    for (index, count) in histogram.counts.iter().enumerate() {
+        seen += count;
+
+        if seen >= target {
+            // The overflow bucket has no upper bound, the max is the best guess
+            return Some(
+                LATENCY_BUCKETS_MS
+                    .get(index)
+                    .copied()
+                    .unwrap_or(histogram.max_ms.ceil() as u64),
+            );
+        }
+    }
+
+    None
+}
+
+fn to_command_metrics(command: &str, histogram: &Histogram) -> CommandMetrics {
+    let calls = histogram.counts.iter().sum::<u64>();
+
+    CommandMetrics {
+        command: command.to_string(),
+        calls,
+        mean_ms: if calls > 0 {
+            histogram.total_ms / calls as f64
+        } else {
+            0.0
+        },
+        max_ms: histogram.max_ms,
+        p95_ms: percentile_bound(histogram, calls, 0.95),
+        buckets: histogram
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(index, count)| LatencyBucket {
+                le_ms: LATENCY_BUCKETS_MS.get(index).copied(),
+                count: *count,
+            })
+            .collect(),
+    }
+}
+
+#[tauri::command]
+pub fn get_performance_metrics() -> Result<PerformanceMetrics, String> {
+    let metrics = METRICS.lock().map_err(|error| error.to_string())?;
+
+    let mut commands: Vec<CommandMetrics> = metrics
+        .commands
+        .iter()
+        .map(|(command, histogram)| to_command_metrics(command, histogram))
+        .collect();
+    // Where the time goes is what matters, so the costliest commands come first
+    commands.sort_by(|first, second| {
+        (second.mean_ms * second.calls as f64).total_cmp(&(first.mean_ms * first.calls as f64))
+    });
+
+    let mut caches: Vec<CacheMetrics> = metrics
+        .caches
+        .iter()
+        .map(|(cache, counter)| {
+            let lookups = counter.hits + counter.misses;
+            CacheMetrics {
+                cache: cache.to_string(),
+                hits: counter.hits,
+                misses: counter.misses,
+                hit_rate: (lookups > 0).then(|| counter.hits as f64 / lookups as f64),
+            }
+        })
+        .collect();
+    caches.sort_by(|first, second| first.cache.cmp(&second.cache));
+
+    let mut jobs: Vec<JobMetrics> = metrics
+        .jobs
+        .iter()
+        .map(|(kind, counter)| {
+            let busy_seconds = counter.busy_time.as_secs_f64();
+            JobMetrics {
+                kind: kind.to_string(),
+                jobs: counter.jobs,
+                items: counter.items,
+                failed_items: counter.failed_items,
+                items_per_second: (busy_seconds > 0.0).then(|| counter.items as f64 / busy_seconds),
+            }
+        })
+        .collect();
+    jobs.sort_by(|first, second| first.kind.cmp(&second.kind));
+
+    Ok(PerformanceMetrics {
+        uptime_seconds: STARTED_AT.elapsed().as_secs(),
+        commands,
+        caches,
+        jobs,
+        watcher: WatcherMetrics {
+            queue_depth: WATCHER_QUEUE_DEPTH.load(Ordering::Relaxed),
+            max_queue_depth: WATCHER_MAX_QUEUE_DEPTH.load(Ordering::Relaxed),
+        },
+    })
+}
+
+#[tauri::command]
+pub fn reset_performance_metrics() -> Result<(), String> {
+    let mut metrics = METRICS.lock().map_err(|error| error.to_string())?;
+    *metrics = Metrics::default();
+    WATCHER_MAX_QUEUE_DEPTH.store(
+        WATCHER_QUEUE_DEPTH.load(Ordering::Relaxed),
+        Ordering::Relaxed,
+    );
+    Ok(())
+}
@@ -0,0 +1,242 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use crate::file_operations::{Retrier, RetryPolicy};
+use crate::utils::{normalize_path, to_os_path};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+// FAT and exFAT store modification times with 2 second precision
+pub(crate) const MTIME_TOLERANCE_MS: u64 = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyLevel {
+    Size,
+    Mtime,
+    Content,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorDifference {
+    pub relative_path: String,
+    /// "type", "size", "mtime" or "content"
+    pub reason: String,
+    pub source_size: u64,
+    pub target_size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MirrorReport {
+    pub source: String,
+    pub target: String,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    pub differing: Vec<MirrorDifference>,
+    pub matched_count: u64,
+    /// Files that hit transient read errors and needed more than one attempt
+    pub retried: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct TreeEntry {
+    is_dir: bool,
+    size: u64,
+    modified_ms: u64,
+}
+
+fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn collect_tree(root: &Path, errors: &mut Vec<String>) -> BTreeMap<String, TreeEntry> {
+    let mut entries = BTreeMap::new();
+
+    for entry in WalkDir::new(root).follow_links(false).min_depth(1) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                errors.push(error.to_string());
+                continue;
+            }
+        };
+
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                errors.push(format!("{}: {}", entry.path().display(), error));
+                continue;
+            }
+        };
+
+        let relative_path = match entry.path().strip_prefix(root) {
+            Ok(relative_path) => normalize_path(&relative_path.to_string_lossy()),
+            Err(_) => continue,
+        };
+
+        entries.insert(
+            relative_path,
+            TreeEntry {
+                is_dir: metadata.is_dir(),
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                modified_ms: metadata.modified().map(to_millis).unwrap_or(0),
+            },
+        );
+    }
+
+    entries
+}
+
+fn compare_metadata(
+    source: &TreeEntry,
+    target: &TreeEntry,
+    level: VerifyLevel,
+) -> Option<&'static str> {
+    if source.is_dir != target.is_dir {
+        return Some("type");
+    }
+
+    if source.is_dir {
+        return None;
+    }
+
+    if source.size != target.size {
+        return Some("size");
+    }
+
+    if level == VerifyLevel::Mtime
+        && source.modified_ms.abs_diff(target.modified_ms) > MTIME_TOLERANCE_MS
+    {
+        return Some("mtime");
+    }
+
+    None
+}
+
+fn verify_mirror_blocking(
+    source: &Path,
+    target: &Path,
+    level: VerifyLevel,
+) -> Result<MirrorReport, String> {
+    if !source.is_dir() {
+        return Err(format!("Source is not a directory: {}", source.display()));
+    }
+
+    if !target.is_dir() {
+        return Err(format!("Target is not a directory: {}", target.display()));
+    }
+
+    let mut report = MirrorReport {
+        source: normalize_path(&source.to_string_lossy()),
+        target: normalize_path(&target.to_string_lossy()),
+        ..Default::default()
+    };
+
+    let source_tree = collect_tree(source, &mut report.errors);
+    let target_tree = collect_tree(target, &mut report.errors);
+    let mut content_candidates: Vec<(&String, &TreeEntry, &TreeEntry)> = Vec::new();
+
+    for (relative_path, source_entry) in &source_tree {
+        let Some(target_entry) = target_tree.get(relative_path) else {
+            report.missing.push(relative_path.clone());
+            continue;
+        };
+
+        if let Some(reason) = compare_metadata(source_entry, target_entry, level) {
+            report.differing.push(MirrorDifference {
+                relative_path: relative_path.clone(),
+                reason: reason.to_string(),
+                source_size: source_entry.size,
+                target_size: target_entry.size,
+            });
+        } else if level == VerifyLevel::Content && !source_entry.is_dir {
+            content_candidates.push((relative_path, source_entry, target_entry));
+        } else {
+            report.matched_count += 1;
+        }
+    }
+
+    report.extra = target_tree
+        .keys()
+        .filter(|relative_path| !source_tree.contains_key(*relative_path))
+        .cloned()
+        .collect();
+
+    let content_results: Vec<(String, u64, Result<bool, String>, Option<Vec<String>>)> =
+        content_candidates
+            .par_iter()
+            .map(|(relative_path, source_entry, _)| {
+                let source_file = source.join(relative_path.as_str());
+                let target_file = target.join(relative_path.as_str());
+                let mut retrier = Retrier::new(retry_policy.clone());
+                let is_equal = retrier
+                    .run(&source_file, || {
+                        crate::file_compare::files_equal(&source_file, &target_file)
+                    })
+                    .map_err(|error| format!("{}: {}", relative_path, error));
+                (
+                    (*relative_path).clone(),
+                    source_entry.size,
+                    is_equal,
+                    retrier.into_retried_paths(),
+                )
+            })
+            .collect();
+
+    for (relative_path, size, is_equal, retried_paths) in content_results {
+        report.retried.extend(retried_paths.unwrap_or_default());
+
+        match is_equal {
+            Ok(true) => report.matched_count += 1,
+            Ok(false) => {
+                report.differing.push(MirrorDifference {
+                    relative_path,
+                    reason: "content".to_string(),
+                    source_size: size,
+                    target_size: size,
+                });
+            }
+            Err(error) => report.errors.push(error),
+        }
+    }
+
+    report
+        .differing
+        .sort_by(|first, second| first.relative_path.cmp(&second.relative_path));
+
+    Ok(report)
+}
+
+/// Compares a backup or sync target against its source without modifying
+/// anything, reporting missing, extra and differing files. `level` picks how
+/// thoroughly files are compared: size only, size and modification time, or
+/// size and content. Content checks stop at the first differing block.
+#[tauri::command]
+pub async fn verify_mirror(
+    source: String,
+    target: String,
+    level: Option<VerifyLevel>,
+    retry_policy: Option<RetryPolicy>,
+) -> Result<MirrorReport, String> {
+    let level = level.unwrap_or(VerifyLevel::Mtime);
+    let retry_policy = retry_policy.unwrap_or_default();
+
+    tokio::task::spawn_blocking(move || {
+        verify_mirror_blocking(
+            &to_os_path(&source),
+            &to_os_path(&target),
+            level,
+            retry_policy,
+        )
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
@@ -0,0 +1,287 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Phones, cameras and players that share their storage over MTP. Devices
+//! are driven over USB with a built-in PTP/MTP client, except on Windows
+//! where the WPD driver owns them and the Windows Portable Devices API is
+//! used instead.
+//!
+//! Items are addressed as `mtp://<device key>/<storage name>/<path>` and
+//! browsed through the VFS like any other location.
+//!
+//! Listing devices doesn't touch them. A session, which claims the device
+//! from other programs such as gvfs, KIO or Image Capture, is only opened
+//! once one of its storages is browsed, and is closed again after a minute
+//! without use.
+
+#[cfg(not(windows))]
+mod usb;
+#[cfg(windows)]
+mod wpd;
+
+#[cfg(not(windows))]
+use usb as platform;
+#[cfg(windows)]
+use wpd as platform;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const SCHEME: &str = "mtp://";
+
+/// Sessions unused for this long are closed, giving the device back
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MtpStorage {
+    pub id: String,
+    /// Path segment of the storage in `mtp://` URIs, unique per device
+    pub name: String,
+    pub total_space: u64,
+    pub free_space: u64,
+    pub uri: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MtpDeviceInfo {
+    pub key: String,
+    /// Root of the device, which lists its storages
+    pub uri: String,
+    pub name: String,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    /// Filled in only while a session is open, see `get_mtp_storages`
+    pub storages: Vec<MtpStorage>,
+    /// Set when the device is connected but its storage can't be read,
+    /// usually because the phone is locked or in charging-only mode
+    pub error: Option<String>,
+}
+
+/// A device found during enumeration, before a session is opened
+#[derive(Debug, Clone)]
+pub(crate) struct DetectedDevice {
+    pub key: String,
+    pub name: String,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+/// A file or folder on a device
+#[derive(Debug, Clone)]
+pub(crate) struct MtpObject {
+    pub id: String,
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Milliseconds since the Unix epoch, 0 when unknown
+    pub modified_time: u64,
+}
+
+/// An open session with a device. Object and storage IDs are opaque to
+/// callers; `parent_id` of `None` means the root of a storage.
+pub(crate) trait MtpDevice: Send {
+    /// Storages with `id`, `name` and space filled in
+    fn storages(&mut self) -> Result<Vec<MtpStorage>, String>;
+    fn list(&mut self, storage_id: &str, parent_id: Option<&str>)
+        -> Result<Vec<MtpObject>, String>;
+    fn download(&mut self, object: &MtpObject, destination: &Path) -> Result<u64, String>;
+    fn upload(
+        &mut self,
+        storage_id: &str,
+        parent_id: Option<&str>,
+        name: &str,
+        source: &Path,
+    ) -> Result<u64, String>;
+    fn create_folder(
+        &mut self,
+        storage_id: &str,
+        parent_id: Option<&str>,
+        name: &str,
+    ) -> Result<String, String>;
+    /// Deletes a file, or a folder with everything in it
+    fn delete(&mut self, object_id: &str) -> Result<(), String>;
+}
+
+pub(crate) type SharedDevice = Arc<Mutex<Box<dyn MtpDevice>>>;
+
+struct OpenDevice {
+    device: SharedDevice,
+    last_used: Instant,
+}
+
+/// Sessions are kept open between calls while the device is browsed, as
+/// opening one takes a while on some phones
+static OPEN_DEVICES: Lazy<Mutex<HashMap<String, OpenDevice>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static IS_REAPER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Closes sessions that went unused for `SESSION_IDLE_TIMEOUT` and aren't
+/// in the middle of an operation. Stops once no session is left.
+fn start_idle_reaper() {
+    if IS_REAPER_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    std::thread::spawn(|| loop {
+        std::thread::sleep(IDLE_CHECK_INTERVAL);
+
+        let Ok(mut devices) = OPEN_DEVICES.lock() else {
+            IS_REAPER_RUNNING.store(false, Ordering::SeqCst);
+            return;
+        };
+        devices.retain(|key, open| {
+            let is_idle = open.last_used.elapsed() >= SESSION_IDLE_TIMEOUT
+                && Arc::strong_count(&open.device) == 1;
+            if is_idle {
+                tracing::info!(device = %key, "Closed idle MTP session");
+            }
+            !is_idle
+        });
+        if devices.is_empty() {
+            IS_REAPER_RUNNING.store(false, Ordering::SeqCst);
+            return;
+        }
+    });
+}
+
+pub(crate) fn device_for(key: &str) -> Result<SharedDevice, String> {
+    let mut devices = OPEN_DEVICES.lock().map_err(|error| error.to_string())?;
+
+    if let Some(open) = devices.get_mut(key) {
+        open.last_used = Instant::now();
+        return Ok(open.device.clone());
+    }
+
+    let device: SharedDevice = Arc::new(Mutex::new(platform::open(key)?));
+    devices.insert(
+        key.to_string(),
+        OpenDevice {
+            device: device.clone(),
+            last_used: Instant::now(),
+        },
+    );
+    tracing::info!(device = key, "Opened MTP session");
+    start_idle_reaper();
+
+    Ok(device)
+}
+
+/// Drops a cached session, so the next access reconnects to the device
+pub(crate) fn forget_device(key: &str) {
+    if let Ok(mut devices) = OPEN_DEVICES.lock() {
+        devices.remove(key);
+    }
+}
+
+/// Splits `mtp://<key>/<path>` into the device key and the path on it
+pub(crate) fn parse_uri(uri: &str) -> Option<(String, String)> {
+    let rest = uri.strip_prefix(SCHEME)?;
+    let (key, path) = rest.split_once('/').unwrap_or((rest, ""));
+
+    if key.is_empty() {
+        return None;
+    }
+
+    Some((key.to_string(), format!("/{}", path.trim_matches('/'))))
+}
+
+/// Names storages after their descriptions. Phones with a card often call
+/// both storages the same, so duplicates get their ID appended.
+pub(crate) fn name_storages(storages: &mut [MtpStorage]) {
+    let names: Vec<String> = storages
+        .iter()
+        .map(|storage| storage.name.replace('/', "_"))
+        .collect();
+
+    for (index, storage) in storages.iter_mut().enumerate() {
+        let name = if names[index].is_empty() {
+            storage.id.clone()
+        } else if names.iter().filter(|name| **name == names[index]).count() > 1 {
+            format!("{} ({})", names[index], storage.id)
+        } else {
+            names[index].clone()
+        };
+
+        storage.name = name;
+    }
+}
+
+/// Storages of a device, opening a session with it
+fn read_storages(key: &str) -> Result<Vec<MtpStorage>, String> {
+    let storages = device_for(key).and_then(|device| {
+        let mut device = device.lock().map_err(|error| error.to_string())?;
+        device.storages()
+    });
+
+    let mut storages = storages.inspect_err(|error| {
+        tracing::warn!(device = %key, %error, "Failed to read MTP storages");
+        forget_device(key);
+    })?;
+    name_storages(&mut storages);
+
+    for storage in &mut storages {
+        storage.uri = format!("{}{}/{}", SCHEME, key, storage.name);
+    }
+    Ok(storages)
+}
+
+fn device_info(detected: DetectedDevice) -> MtpDeviceInfo {
+    let is_open = OPEN_DEVICES
+        .lock()
+        .is_ok_and(|devices| devices.contains_key(&detected.key));
+    // Devices without a session are left alone until they are browsed
+    let (storages, error) = if is_open {
+        match read_storages(&detected.key) {
+            Ok(storages) => (storages, None),
+            Err(error) => (Vec::new(), Some(error)),
+        }
+    } else {
+        (Vec::new(), None)
+    };
+
+    MtpDeviceInfo {
+        uri: format!("{}{}/", SCHEME, detected.key),
+        key: detected.key,
+        name: detected.name,
+        manufacturer: detected.manufacturer,
+        model: detected.model,
+        serial_number: detected.serial_number,
+        storages,
+        error,
+    }
+}
+
+#[tauri::command]
+pub async fn list_mtp_devices() -> Result<Vec<MtpDeviceInfo>, String> {
+    tokio::task::spawn_blocking(|| {
+        let detected = platform::list_devices()?;
+
+        // Sessions of unplugged devices are dead, a replugged one needs a new one
+        if let Ok(mut devices) = OPEN_DEVICES.lock() {
+            devices.retain(|key, _| detected.iter().any(|device| &device.key == key));
+        }
+
+        Ok(detected.into_iter().map(device_info).collect())
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Storages of the device with `key`. Opens a session, which takes the
+/// device from other programs until it goes unused for a minute.
+#[tauri::command]
+pub async fn get_mtp_storages(key: String) -> Result<Vec<MtpStorage>, String> {
+    tokio::task::spawn_blocking(move || read_storages(&key))
+        .await
+        .map_err(|error| error.to_string())?
+}
@@ -0,0 +1,713 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! A minimal PTP/MTP initiator over USB bulk transfers, covering what
+//! browsing and copying need: storages, object listing, download, upload,
+//! folder creation and deletion.
+
+use super::{DetectedDevice, MtpDevice, MtpObject, MtpStorage};
+use crate::utils::civil_to_unix_millis;
+use futures_lite::future::block_on;
+use nusb::transfer::{Direction, EndpointType, RequestBuffer, TransferError};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+const CONTAINER_COMMAND: u16 = 1;
+const CONTAINER_DATA: u16 = 2;
+const CONTAINER_RESPONSE: u16 = 3;
+const HEADER_SIZE: usize = 12;
+
+const OPERATION_OPEN_SESSION: u16 = 0x1002;
+const OPERATION_CLOSE_SESSION: u16 = 0x1003;
+const OPERATION_GET_STORAGE_IDS: u16 = 0x1004;
+const OPERATION_GET_STORAGE_INFO: u16 = 0x1005;
+const OPERATION_GET_OBJECT_HANDLES: u16 = 0x1007;
+const OPERATION_GET_OBJECT_INFO: u16 = 0x1008;
+const OPERATION_GET_OBJECT: u16 = 0x1009;
+const OPERATION_DELETE_OBJECT: u16 = 0x100B;
+const OPERATION_SEND_OBJECT_INFO: u16 = 0x100C;
+const OPERATION_SEND_OBJECT: u16 = 0x100D;
+const OPERATION_GET_OBJECT_PROP_VALUE: u16 = 0x9803;
+
+const RESPONSE_OK: u16 = 0x2001;
+const RESPONSE_SESSION_ALREADY_OPEN: u16 = 0x201E;
+
+const FORMAT_UNDEFINED: u16 = 0x3000;
+const FORMAT_ASSOCIATION: u16 = 0x3001;
+const ASSOCIATION_GENERIC_FOLDER: u16 = 0x0001;
+const PROPERTY_OBJECT_SIZE: u32 = 0xDC04;
+
+/// Parent handle of objects at the root of a storage
+const ROOT_PARENT: u32 = 0xFFFF_FFFF;
+/// Marks sizes that don't fit the 32-bit dataset fields
+const SIZE_UNKNOWN: u32 = 0xFFFF_FFFF;
+const TRANSFER_SIZE: usize = 256 * 1024;
+
+struct Response {
+    code: u16,
+    params: Vec<u32>,
+}
+
+pub struct UsbMtpDevice {
+    interface: nusb::Interface,
+    bulk_in: u8,
+    bulk_out: u8,
+    max_packet_size: usize,
+    transaction_id: u32,
+}
+
+fn transfer_error(error: TransferError) -> String {
+    format!("USB transfer failed: {}", error)
+}
+
+/// Reads the little-endian fields of PTP datasets
+struct DatasetReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> DatasetReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        DatasetReader { data, offset: 0 }
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + N)
+            .ok_or_else(|| "Truncated MTP dataset".to_string())?;
+        self.offset += N;
+        Ok(bytes.try_into().expect("slice has the requested length"))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.bytes()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn u32_array(&mut self) -> Result<Vec<u32>, String> {
+        let count = self.u32()?;
+        (0..count).map(|_| self.u32()).collect()
+    }
+
+    /// A length-prefixed, null-terminated UTF-16 string
+    fn string(&mut self) -> Result<String, String> {
+        let length = self.u8()? as usize;
+        let units = (0..length)
+            .map(|_| self.u16())
+            .collect::<Result<Vec<u16>, String>>()?;
+
+        Ok(String::from_utf16_lossy(&units)
+            .trim_end_matches('\0')
+            .to_string())
+    }
+}
+
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    if value.is_empty() {
+        buffer.push(0);
+        return;
+    }
+
+    let units: Vec<u16> = value.encode_utf16().take(254).chain([0]).collect();
+    buffer.push(units.len() as u8);
+
+    for unit in units {
+        buffer.extend_from_slice(&unit.to_le_bytes());
+    }
+}
+
+/// Parses the `YYYYMMDDThhmmss` dates of object datasets, ignoring
+/// fractions and zone suffixes
+fn parse_date(value: &str) -> u64 {
+    let field = |range: std::ops::Range<usize>| {
+        value
+            .get(range)
+            .and_then(|digits| digits.parse::<i64>().ok())
+    };
+
+    match (
+        field(0..4),
+        field(4..6),
+        field(6..8),
+        field(9..11),
+        field(11..13),
+        field(13..15),
+    ) {
+        (Some(year), Some(month), Some(day), Some(hour), Some(minute), Some(second)) => {
+            civil_to_unix_millis(year, month, day, hour, minute, second)
+        }
+        _ => 0,
+    }
+}
+
+fn is_mtp_interface(interface: &nusb::InterfaceInfo) -> bool {
+    // Cameras use the still image class, Android a vendor class named "MTP"
+    (interface.class() == 6 && interface.subclass() == 1 && interface.protocol() == 1)
+        || interface
+            .interface_string()
+            .is_some_and(|name| name.contains("MTP"))
+}
+
+fn device_key(info: &nusb::DeviceInfo) -> String {
+    let suffix = match info.serial_number() {
+        Some(serial) if !serial.trim().is_empty() => serial
+            .chars()
+            .filter(|character| {
+                character.is_ascii_alphanumeric() || *character == '-' || *character == '_'
+            })
+            .collect(),
+        _ => format!("{}-{}", info.bus_number(), info.device_address()),
+    };
+
+    format!(
+        "{:04x}-{:04x}-{}",
+        info.vendor_id(),
+        info.product_id(),
+        suffix
+    )
+}
+
+fn mtp_devices() -> Result<Vec<nusb::DeviceInfo>, String> {
+    Ok(nusb::list_devices()
+        .map_err(|error| error.to_string())?
+        .filter(|info| info.interfaces().any(is_mtp_interface))
+        .collect())
+}
+
+pub fn list_devices() -> Result<Vec<DetectedDevice>, String> {
+    Ok(mtp_devices()?
+        .iter()
+        .map(|info| DetectedDevice {
+            key: device_key(info),
+            name: info
+                .product_string()
+                .map(str::to_string)
+                .unwrap_or_else(|| "MTP device".to_string()),
+            manufacturer: info.manufacturer_string().map(str::to_string),
+            model: info.product_string().map(str::to_string),
+            serial_number: info.serial_number().map(str::to_string),
+        })
+        .collect())
+}
+
+pub fn open(key: &str) -> Result<Box<dyn MtpDevice>, String> {
+    let info = mtp_devices()?
+        .into_iter()
+        .find(|info| device_key(info) == key)
+        .ok_or_else(|| format!("MTP device is not connected: {}", key))?;
+    let interface_number = info
+        .interfaces()
+        .find(|interface| is_mtp_interface(interface))
+        .map(|interface| interface.interface_number())
+        .ok_or_else(|| "Device has no MTP interface".to_string())?;
+
+    let device = info.open().map_err(|error| error.to_string())?;
+    let configuration = device
+        .active_configuration()
+        .map_err(|error| error.to_string())?;
+    let alt_setting = configuration
+        .interface_alt_settings()
+        .find(|alt_setting| {
+            alt_setting.interface_number() == interface_number
+                && alt_setting.alternate_setting() == 0
+        })
+        .ok_or_else(|| "Device has no MTP interface".to_string())?;
+
+    let bulk_endpoint = |direction: Direction| {
+        alt_setting.endpoints().find(|endpoint| {
+            endpoint.transfer_type() == EndpointType::Bulk && endpoint.direction() == direction
+        })
+    };
+    let (Some(bulk_in), Some(bulk_out)) =
+        (bulk_endpoint(Direction::In), bulk_endpoint(Direction::Out))
+    else {
+        return Err("Device has no bulk endpoints".to_string());
+    };
+
+    // Fails when another program, e.g. a desktop's MTP daemon, holds the device
+    let interface = device
+        .claim_interface(interface_number)
+        .map_err(|error| format!("The device is in use by another program: {}", error))?;
+
+    let mut mtp_device = UsbMtpDevice {
+        interface,
+        bulk_in: bulk_in.address(),
+        bulk_out: bulk_out.address(),
+        max_packet_size: bulk_out.max_packet_size(),
+        transaction_id: 0,
+    };
+    mtp_device.open_session()?;
+
+    Ok(Box::new(mtp_device))
+}
+
+impl UsbMtpDevice {
+    fn next_transaction_id(&mut self) -> u32 {
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        self.transaction_id
+    }
+
+    fn container(container_type: u16, code: u16, transaction_id: u32, length: u32) -> Vec<u8> {
+        let mut container = Vec::with_capacity(HEADER_SIZE);
+        container.extend_from_slice(&length.to_le_bytes());
+        container.extend_from_slice(&container_type.to_le_bytes());
+        container.extend_from_slice(&code.to_le_bytes());
+        container.extend_from_slice(&transaction_id.to_le_bytes());
+        container
+    }
+
+    fn send(&self, data: Vec<u8>) -> Result<(), String> {
+        block_on(self.interface.bulk_out(self.bulk_out, data))
+            .into_result()
+            .map(|_| ())
+            .map_err(transfer_error)
+    }
+
+    fn receive(&self) -> Result<Vec<u8>, String> {
+        block_on(
+            self.interface
+                .bulk_in(self.bulk_in, RequestBuffer::new(TRANSFER_SIZE)),
+        )
+        .into_result()
+        .map_err(transfer_error)
+    }
+
+    /// Next non-empty transfer, skipping the zero-length packets that end
+    /// data phases of an exact multiple of the packet size
+    fn receive_container(&self) -> Result<Vec<u8>, String> {
+        loop {
+            let data = self.receive()?;
+
+            if !data.is_empty() {
+                return Ok(data);
+            }
+        }
+    }
+
+    fn send_command(&mut self, code: u16, params: &[u32]) -> Result<u32, String> {
+        let transaction_id = self.next_transaction_id();
+        let length = (HEADER_SIZE + params.len() * 4) as u32;
+        let mut command = Self::container(CONTAINER_COMMAND, code, transaction_id, length);
+
+        for param in params {
+            command.extend_from_slice(&param.to_le_bytes());
+        }
+
+        self.send(command)?;
+        Ok(transaction_id)
+    }
+
+    fn parse_response(data: &[u8]) -> Result<Response, String> {
+        let mut reader = DatasetReader::new(data);
+        let length = reader.u32()? as usize;
+        let container_type = reader.u16()?;
+        let code = reader.u16()?;
+        let _transaction_id = reader.u32()?;
+
+        if container_type != CONTAINER_RESPONSE {
+            return Err(format!("Unexpected MTP container type {}", container_type));
+        }
+
+        let param_count = length
+            .saturating_sub(HEADER_SIZE)
+            .min(data.len() - HEADER_SIZE)
+            / 4;
+        let params = (0..param_count)
+            .map(|_| reader.u32())
+            .collect::<Result<Vec<u32>, String>>()?;
+
+        Ok(Response { code, params })
+    }
+
+    fn check(operation: u16, response: Response) -> Result<Vec<u32>, String> {
+        if response.code == RESPONSE_OK {
+            Ok(response.params)
+        } else {
+            Err(format!(
+                "MTP operation 0x{:04X} failed with response 0x{:04X}",
+                operation, response.code
+            ))
+        }
+    }
+
+    /// An operation without a data phase
+    fn request(&mut self, code: u16, params: &[u32]) -> Result<Vec<u32>, String> {
+        self.send_command(code, params)?;
+        let response = Self::parse_response(&self.receive_container()?)?;
+        Self::check(code, response)
+    }
+
+    /// An operation whose data phase comes from the device, streamed into
+    /// `sink`
+    fn request_data(
+        &mut self,
+        code: u16,
+        params: &[u32],
+        sink: &mut dyn FnMut(&[u8]) -> Result<(), String>,
+    ) -> Result<Vec<u32>, String> {
+        self.send_command(code, params)?;
+        let first = self.receive_container()?;
+
+        if first.len() < HEADER_SIZE {
+            return Err("Truncated MTP container".to_string());
+        }
+
+        let container_type = u16::from_le_bytes([first[4], first[5]]);
+
+        if container_type == CONTAINER_RESPONSE {
+            return Self::check(code, Self::parse_response(&first)?);
+        }
+
+        if container_type != CONTAINER_DATA {
+            return Err(format!("Unexpected MTP container type {}", container_type));
+        }
+
+        // Objects over 4 GB don't fit the length field, their data phase
+        // ends with a short transfer instead
+        let length = u32::from_le_bytes([first[0], first[1], first[2], first[3]]);
+        let expected = match length {
+            u32::MAX => None,
+            length => Some(
+                (length as usize)
+                    .checked_sub(HEADER_SIZE)
+                    .ok_or_else(|| "Truncated MTP container".to_string())?,
+            ),
+        };
+        let mut received = first.len() - HEADER_SIZE;
+        let mut is_short = first.len() < TRANSFER_SIZE;
+        sink(&first[HEADER_SIZE..])?;
+
+        while expected.map_or(!is_short, |expected| received < expected) {
+            let data = self.receive()?;
+            received += data.len();
+            is_short = data.len() < TRANSFER_SIZE;
+            sink(&data)?;
+
+            if data.is_empty() {
+                break;
+            }
+        }
+
+        let response = Self::parse_response(&self.receive_container()?)?;
+        Self::check(code, response)
+    }
+
+    fn read_data(&mut self, code: u16, params: &[u32]) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        self.request_data(code, params, &mut |chunk| {
+            data.extend_from_slice(chunk);
+            Ok(())
+        })?;
+        Ok(data)
+    }
+
+    /// An operation whose data phase goes to the device
+    fn request_send(
+        &mut self,
+        code: u16,
+        params: &[u32],
+        size: u64,
+        source: &mut dyn Read,
+    ) -> Result<Vec<u32>, String> {
+        let transaction_id = self.send_command(code, params)?;
+        let total = HEADER_SIZE as u64 + size;
+        let length = u32::try_from(total).unwrap_or(u32::MAX);
+        let mut chunk = Self::container(CONTAINER_DATA, code, transaction_id, length);
+        let mut sent = 0u64;
+
+        loop {
+            let header_size = chunk.len();
+            chunk.resize(TRANSFER_SIZE, 0);
+            let read = fill(source, &mut chunk[header_size..])?;
+            chunk.truncate(header_size + read);
+
+            if chunk.is_empty() {
+                break;
+            }
+
+            sent += chunk.len() as u64;
+            self.send(std::mem::take(&mut chunk))?;
+
+            if sent >= total {
+                break;
+            }
+        }
+
+        if sent % self.max_packet_size as u64 == 0 {
+            self.send(Vec::new())?;
+        }
+
+        let response = Self::parse_response(&self.receive_container()?)?;
+        Self::check(code, response)
+    }
+
+    fn open_session(&mut self) -> Result<(), String> {
+        // Session IDs start at 1 and the OpenSession itself uses transaction 0
+        self.transaction_id = u32::MAX;
+        self.send_command(OPERATION_OPEN_SESSION, &[1])?;
+        let response = Self::parse_response(&self.receive_container()?)?;
+
+        match response.code {
+            RESPONSE_OK | RESPONSE_SESSION_ALREADY_OPEN => Ok(()),
+            code => Err(format!("Failed to open an MTP session: 0x{:04X}", code)),
+        }
+    }
+
+    fn object(&mut self, handle: u32) -> Result<MtpObject, String> {
+        let data = self.read_data(OPERATION_GET_OBJECT_INFO, &[handle])?;
+        let mut reader = DatasetReader::new(&data);
+        let _storage_id = reader.u32()?;
+        let format = reader.u16()?;
+        let _protection_status = reader.u16()?;
+        let compressed_size = reader.u32()?;
+        let _thumb_format = reader.u16()?;
+        // Thumbnail size and dimensions, image dimensions and bit depth
+        for _ in 0..6 {
+            reader.u32()?;
+        }
+        let _parent = reader.u32()?;
+        let _association_type = reader.u16()?;
+        let _association_description = reader.u32()?;
+        let _sequence_number = reader.u32()?;
+        let name = reader.string()?;
+        let created = reader.string()?;
+        let modified = reader.string().unwrap_or_default();
+
+        let is_dir = format == FORMAT_ASSOCIATION;
+        let size = if is_dir {
+            0
+        } else if compressed_size == SIZE_UNKNOWN {
+            let value = self.read_data(
+                OPERATION_GET_OBJECT_PROP_VALUE,
+                &[handle, PROPERTY_OBJECT_SIZE],
+            )?;
+            DatasetReader::new(&value).u64()?
+        } else {
+            compressed_size as u64
+        };
+
+        Ok(MtpObject {
+            id: handle.to_string(),
+            name,
+            is_dir,
+            size,
+            modified_time: match parse_date(&modified) {
+                0 => parse_date(&created),
+                modified_time => modified_time,
+            },
+        })
+    }
+
+    fn send_object_info(
+        &mut self,
+        storage_id: u32,
+        parent: u32,
+        name: &str,
+        size: u64,
+        is_dir: bool,
+    ) -> Result<u32, String> {
+        let mut dataset = Vec::new();
+        dataset.extend_from_slice(&0u32.to_le_bytes());
+        dataset.extend_from_slice(
+            &if is_dir {
+                FORMAT_ASSOCIATION
+            } else {
+                FORMAT_UNDEFINED
+            }
+            .to_le_bytes(),
+        );
+        dataset.extend_from_slice(&0u16.to_le_bytes());
+        dataset.extend_from_slice(&u32::try_from(size).unwrap_or(SIZE_UNKNOWN).to_le_bytes());
+        dataset.extend_from_slice(&0u16.to_le_bytes());
+        dataset.extend_from_slice(&[0; 24]);
+        dataset.extend_from_slice(&0u32.to_le_bytes());
+        dataset.extend_from_slice(
+            &if is_dir {
+                ASSOCIATION_GENERIC_FOLDER
+            } else {
+                0
+            }
+            .to_le_bytes(),
+        );
+        dataset.extend_from_slice(&0u32.to_le_bytes());
+        dataset.extend_from_slice(&0u32.to_le_bytes());
+        write_string(&mut dataset, name);
+        // Creation and modification dates, keywords
+        write_string(&mut dataset, "");
+        write_string(&mut dataset, "");
+        write_string(&mut dataset, "");
+
+        let dataset_size = dataset.len() as u64;
+        let params = self.request_send(
+            OPERATION_SEND_OBJECT_INFO,
+            &[storage_id, parent],
+            dataset_size,
+            &mut dataset.as_slice(),
+        )?;
+
+        params
+            .get(2)
+            .copied()
+            .ok_or_else(|| "The device didn't return a handle for the new object".to_string())
+    }
+}
+
+/// Reads until `buffer` is full or the source ends. Every transfer but the
+/// last must be full, as a short one ends the data phase.
+fn fill(source: &mut dyn Read, buffer: &mut [u8]) -> Result<usize, String> {
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        match source.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error.to_string()),
+        }
+    }
+
+    Ok(filled)
+}
+
+fn parse_id(id: &str) -> Result<u32, String> {
+    id.parse::<u32>()
+        .map_err(|_| format!("Invalid MTP object ID: {}", id))
+}
+
+fn parent_handle(parent_id: Option<&str>) -> Result<u32, String> {
+    parent_id.map(parse_id).unwrap_or(Ok(ROOT_PARENT))
+}
+
+impl MtpDevice for UsbMtpDevice {
+    fn storages(&mut self) -> Result<Vec<MtpStorage>, String> {
+        let data = self.read_data(OPERATION_GET_STORAGE_IDS, &[])?;
+        let storage_ids = DatasetReader::new(&data).u32_array()?;
+        let mut storages = Vec::new();
+
+        // A zero low word marks a removable storage with no media inserted
+        for storage_id in storage_ids.into_iter().filter(|id| id & 0xFFFF != 0) {
+            let info = self.read_data(OPERATION_GET_STORAGE_INFO, &[storage_id])?;
+            let mut reader = DatasetReader::new(&info);
+            let _storage_type = reader.u16()?;
+            let _filesystem_type = reader.u16()?;
+            let _access_capability = reader.u16()?;
+            let total_space = reader.u64()?;
+            let free_space = reader.u64()?;
+            let _free_objects = reader.u32()?;
+            let description = reader.string()?;
+            let volume_label = reader.string().unwrap_or_default();
+
+            storages.push(MtpStorage {
+                id: storage_id.to_string(),
+                name: if description.is_empty() {
+                    volume_label
+                } else {
+                    description
+                },
+                total_space,
+                free_space,
+                uri: String::new(),
+            });
+        }
+
+        Ok(storages)
+    }
+
+    fn list(
+        &mut self,
+        storage_id: &str,
+        parent_id: Option<&str>,
+    ) -> Result<Vec<MtpObject>, String> {
+        let data = self.read_data(
+            OPERATION_GET_OBJECT_HANDLES,
+            &[parse_id(storage_id)?, 0, parent_handle(parent_id)?],
+        )?;
+
+        DatasetReader::new(&data)
+            .u32_array()?
+            .into_iter()
+            .map(|handle| self.object(handle))
+            .collect()
+    }
+
+    fn download(&mut self, object: &MtpObject, destination: &Path) -> Result<u64, String> {
+        let file = File::create(destination).map_err(|error| error.to_string())?;
+        let mut writer = BufWriter::new(file);
+        let mut written = 0u64;
+
+        self.request_data(
+            OPERATION_GET_OBJECT,
+            &[parse_id(&object.id)?],
+            &mut |chunk| {
+                written += chunk.len() as u64;
+                writer.write_all(chunk).map_err(|error| error.to_string())
+            },
+        )?;
+        writer.flush().map_err(|error| error.to_string())?;
+
+        Ok(written)
+    }
+
+    fn upload(
+        &mut self,
+        storage_id: &str,
+        parent_id: Option<&str>,
+        name: &str,
+        source: &Path,
+    ) -> Result<u64, String> {
+        let mut file = File::open(source).map_err(|error| error.to_string())?;
+        let size = file.metadata().map_err(|error| error.to_string())?.len();
+
+        self.send_object_info(
+            parse_id(storage_id)?,
+            parent_handle(parent_id)?,
+            name,
+            size,
+            false,
+        )?;
+        self.request_send(OPERATION_SEND_OBJECT, &[], size, &mut file)?;
+
+        Ok(size)
+    }
+
+    fn create_folder(
+        &mut self,
+        storage_id: &str,
+        parent_id: Option<&str>,
+        name: &str,
+    ) -> Result<String, String> {
+        self.send_object_info(
+            parse_id(storage_id)?,
+            parent_handle(parent_id)?,
+            name,
+            0,
+            true,
+        )
+        .map(|handle| handle.to_string())
+    }
+
+    fn delete(&mut self, object_id: &str) -> Result<(), String> {
+        self.request(OPERATION_DELETE_OBJECT, &[parse_id(object_id)?, 0])
+            .map(|_| ())
+    }
+}
+
+impl Drop for UsbMtpDevice {
+    fn drop(&mut self) {
+        let _ = self.request(OPERATION_CLOSE_SESSION, &[]);
+    }
+}
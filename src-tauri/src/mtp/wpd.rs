@@ -0,0 +1,481 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! MTP devices through Windows Portable Devices. Windows binds its own
+//! driver to them, so the USB interface can't be claimed directly.
+
+use super::{DetectedDevice, MtpDevice, MtpObject, MtpStorage};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use windows::core::{GUID, HSTRING, PROPVARIANT, PWSTR};
+use windows::Win32::Devices::PortableDevices::*;
+use windows::Win32::Foundation::PROPERTYKEY;
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoTaskMemFree, IStream, CLSCTX_INPROC_SERVER,
+    COINIT_MULTITHREADED, STGC_DEFAULT, STGM_READ,
+};
+use windows::Win32::System::Variant::VT_LPWSTR;
+
+const DEVICE_OBJECT_ID: &str = "DEVICE";
+const ENUM_BATCH_SIZE: usize = 32;
+const BUFFER_SIZE: u32 = 256 * 1024;
+
+pub struct WpdDevice {
+    device: IPortableDevice,
+}
+
+// Objects come from the free-threaded PortableDeviceFTM class
+unsafe impl Send for WpdDevice {}
+
+fn com_error(error: windows::core::Error) -> String {
+    error.message().to_string()
+}
+
+/// Joins the multithreaded apartment, as calls arrive on blocking pool
+/// threads. Repeated calls on one thread are harmless.
+fn init_com() {
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+    }
+}
+
+unsafe fn take_pwstr(value: PWSTR) -> String {
+    let text = value.to_string().unwrap_or_default();
+    CoTaskMemFree(Some(value.0 as *const _));
+    text
+}
+
+/// FNV-1a of the PnP ID, which is too long and punctuated for a URI
+fn device_key(pnp_id: &str) -> String {
+    let hash = pnp_id
+        .to_lowercase()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+
+    format!("wpd-{:016x}", hash)
+}
+
+/// Reads one of the manager's device strings, which take a sized buffer
+unsafe fn device_string(
+    getter: impl Fn(PWSTR, *mut u32) -> windows::core::Result<()>,
+) -> Option<String> {
+    let mut length = 0u32;
+    let _ = getter(PWSTR::null(), &mut length);
+
+    if length == 0 {
+        return None;
+    }
+
+    let mut buffer = vec![0u16; length as usize];
+    getter(PWSTR(buffer.as_mut_ptr()), &mut length).ok()?;
+
+    Some(
+        String::from_utf16_lossy(&buffer)
+            .trim_end_matches('\0')
+            .to_string(),
+    )
+    .filter(|value| !value.is_empty())
+}
+
+unsafe fn pnp_device_ids(manager: &IPortableDeviceManager) -> Result<Vec<String>, String> {
+    manager.RefreshDeviceList().map_err(com_error)?;
+
+    let mut count = 0u32;
+    manager
+        .GetDevices(std::ptr::null_mut(), &mut count)
+        .map_err(com_error)?;
+
+    let mut ids = vec![PWSTR::null(); count as usize];
+    manager
+        .GetDevices(ids.as_mut_ptr(), &mut count)
+        .map_err(com_error)?;
+
+    Ok(ids
+        .into_iter()
+        .take(count as usize)
+        .map(|id| take_pwstr(id))
+        .collect())
+}
+
+pub fn list_devices() -> Result<Vec<DetectedDevice>, String> {
+    init_com();
+
+    unsafe {
+        let manager: IPortableDeviceManager =
+            CoCreateInstance(&PortableDeviceManager, None, CLSCTX_INPROC_SERVER)
+                .map_err(com_error)?;
+
+        Ok(pnp_device_ids(&manager)?
+            .into_iter()
+            .map(|pnp_id| {
+                let id = HSTRING::from(&pnp_id);
+                let name = device_string(|buffer, length| {
+                    manager.GetDeviceFriendlyName(&id, buffer, length)
+                });
+                let manufacturer = device_string(|buffer, length| {
+                    manager.GetDeviceManufacturer(&id, buffer, length)
+                });
+                let model = device_string(|buffer, length| {
+                    manager.GetDeviceDescription(&id, buffer, length)
+                });
+
+                DetectedDevice {
+                    key: device_key(&pnp_id),
+                    name: name
+                        .or_else(|| model.clone())
+                        .unwrap_or_else(|| "Portable device".to_string()),
+                    manufacturer,
+                    model,
+                    serial_number: None,
+                }
+            })
+            .collect())
+    }
+}
+
+pub fn open(key: &str) -> Result<Box<dyn MtpDevice>, String> {
+    init_com();
+
+    unsafe {
+        let manager: IPortableDeviceManager =
+            CoCreateInstance(&PortableDeviceManager, None, CLSCTX_INPROC_SERVER)
+                .map_err(com_error)?;
+        let pnp_id = pnp_device_ids(&manager)?
+            .into_iter()
+            .find(|pnp_id| device_key(pnp_id) == key)
+            .ok_or_else(|| format!("MTP device is not connected: {}", key))?;
+
+        let client_info: IPortableDeviceValues =
+            CoCreateInstance(&PortableDeviceValues, None, CLSCTX_INPROC_SERVER)
+                .map_err(com_error)?;
+        client_info
+            .SetStringValue(&WPD_CLIENT_NAME, &HSTRING::from("Sigma File Manager"))
+            .map_err(com_error)?;
+
+        let device: IPortableDevice =
+            CoCreateInstance(&PortableDeviceFTM, None, CLSCTX_INPROC_SERVER).map_err(com_error)?;
+        device
+            .Open(&HSTRING::from(&pnp_id), &client_info)
+            .map_err(com_error)?;
+
+        Ok(Box::new(WpdDevice { device }))
+    }
+}
+
+impl WpdDevice {
+    fn content(&self) -> Result<IPortableDeviceContent, String> {
+        init_com();
+        unsafe { self.device.Content().map_err(com_error) }
+    }
+
+    unsafe fn children(
+        &self,
+        content: &IPortableDeviceContent,
+        parent_id: &str,
+    ) -> Result<Vec<String>, String> {
+        let objects = content
+            .EnumObjects(0, &HSTRING::from(parent_id), None)
+            .map_err(com_error)?;
+        let mut ids = Vec::new();
+
+        loop {
+            let mut batch = [PWSTR::null(); ENUM_BATCH_SIZE];
+            let mut fetched = 0u32;
+            let _ = objects.Next(&mut batch, &mut fetched);
+
+            if fetched == 0 {
+                break;
+            }
+
+            ids.extend(batch[..fetched as usize].iter().map(|id| take_pwstr(*id)));
+        }
+
+        Ok(ids)
+    }
+
+    unsafe fn values(
+        &self,
+        content: &IPortableDeviceContent,
+        object_id: &str,
+    ) -> Result<IPortableDeviceValues, String> {
+        content
+            .Properties()
+            .and_then(|properties| properties.GetValues(&HSTRING::from(object_id), None))
+            .map_err(com_error)
+    }
+
+    unsafe fn new_values(
+        &self,
+        parent_id: &str,
+        name: &str,
+    ) -> Result<IPortableDeviceValues, String> {
+        let values: IPortableDeviceValues =
+            CoCreateInstance(&PortableDeviceValues, None, CLSCTX_INPROC_SERVER)
+                .map_err(com_error)?;
+        let name = HSTRING::from(name);
+
+        values
+            .SetStringValue(&WPD_OBJECT_PARENT_ID, &HSTRING::from(parent_id))
+            .and_then(|_| values.SetStringValue(&WPD_OBJECT_NAME, &name))
+            .and_then(|_| values.SetStringValue(&WPD_OBJECT_ORIGINAL_FILE_NAME, &name))
+            .map_err(com_error)?;
+
+        Ok(values)
+    }
+}
+
+fn string_value(values: &IPortableDeviceValues, key: &PROPERTYKEY) -> Option<String> {
+    unsafe {
+        values
+            .GetStringValue(key)
+            .ok()
+            .map(|value| take_pwstr(value))
+    }
+}
+
+fn guid_value(values: &IPortableDeviceValues, key: &PROPERTYKEY) -> Option<GUID> {
+    unsafe { values.GetGuidValue(key).ok() }
+}
+
+fn size_value(values: &IPortableDeviceValues, key: &PROPERTYKEY) -> u64 {
+    unsafe { values.GetUnsignedLargeIntegerValue(key).unwrap_or(0) }
+}
+
+impl MtpDevice for WpdDevice {
+    fn storages(&mut self) -> Result<Vec<MtpStorage>, String> {
+        let content = self.content()?;
+
+        unsafe {
+            let mut storages = Vec::new();
+
+            for object_id in self.children(&content, DEVICE_OBJECT_ID)? {
+                let values = self.values(&content, &object_id)?;
+
+                if guid_value(&values, &WPD_FUNCTIONAL_OBJECT_CATEGORY)
+                    != Some(WPD_FUNCTIONAL_CATEGORY_STORAGE)
+                {
+                    continue;
+                }
+
+                storages.push(MtpStorage {
+                    name: string_value(&values, &WPD_STORAGE_DESCRIPTION)
+                        .or_else(|| string_value(&values, &WPD_OBJECT_NAME))
+                        .unwrap_or_default(),
+                    id: object_id,
+                    total_space: size_value(&values, &WPD_STORAGE_CAPACITY),
+                    free_space: size_value(&values, &WPD_STORAGE_FREE_SPACE_IN_BYTES),
+                    uri: String::new(),
+                });
+            }
+
+            Ok(storages)
+        }
+    }
+
+    fn list(
+        &mut self,
+        storage_id: &str,
+        parent_id: Option<&str>,
+    ) -> Result<Vec<MtpObject>, String> {
+        let content = self.content()?;
+
+        unsafe {
+            self.children(&content, parent_id.unwrap_or(storage_id))?
+                .into_iter()
+                .map(|object_id| {
+                    let values = self.values(&content, &object_id)?;
+                    let content_type = guid_value(&values, &WPD_OBJECT_CONTENT_TYPE);
+                    let is_dir = content_type == Some(WPD_CONTENT_TYPE_FOLDER)
+                        || content_type == Some(WPD_CONTENT_TYPE_FUNCTIONAL_OBJECT);
+
+                    Ok(MtpObject {
+                        name: string_value(&values, &WPD_OBJECT_ORIGINAL_FILE_NAME)
+                            .or_else(|| string_value(&values, &WPD_OBJECT_NAME))
+                            .unwrap_or_else(|| object_id.clone()),
+                        id: object_id,
+                        is_dir,
+                        size: if is_dir {
+                            0
+                        } else {
+                            size_value(&values, &WPD_OBJECT_SIZE)
+                        },
+                        // Dates are VT_DATE values, which the values API has no getter for
+                        modified_time: 0,
+                    })
+                })
+                .collect()
+        }
+    }
+
+    fn download(&mut self, object: &MtpObject, destination: &Path) -> Result<u64, String> {
+        let content = self.content()?;
+        let mut output = File::create(destination).map_err(|error| error.to_string())?;
+
+        unsafe {
+            let resources = content.Transfer().map_err(com_error)?;
+            let mut optimal_size = BUFFER_SIZE;
+            let mut stream: Option<IStream> = None;
+            resources
+                .GetStream(
+                    &HSTRING::from(&object.id),
+                    &WPD_RESOURCE_DEFAULT,
+                    STGM_READ.0,
+                    &mut optimal_size,
+                    &mut stream,
+                )
+                .map_err(com_error)?;
+            let stream = stream.ok_or_else(|| "The device returned no data".to_string())?;
+
+            let mut buffer = vec![0u8; optimal_size.max(BUFFER_SIZE) as usize];
+            let mut written = 0u64;
+
+            loop {
+                let mut read = 0u32;
+                stream
+                    .Read(
+                        buffer.as_mut_ptr() as *mut _,
+                        buffer.len() as u32,
+                        Some(&mut read as *mut u32),
+                    )
+                    .ok()
+                    .map_err(com_error)?;
+
+                if read == 0 {
+                    break;
+                }
+
+                output
+                    .write_all(&buffer[..read as usize])
+                    .map_err(|error| error.to_string())?;
+                written += read as u64;
+            }
+
+            Ok(written)
+        }
+    }
+
+    fn upload(
+        &mut self,
+        storage_id: &str,
+        parent_id: Option<&str>,
+        name: &str,
+        source: &Path,
+    ) -> Result<u64, String> {
+        let content = self.content()?;
+        let mut input = File::open(source).map_err(|error| error.to_string())?;
+        let size = input.metadata().map_err(|error| error.to_string())?.len();
+
+        unsafe {
+            let values = self.new_values(parent_id.unwrap_or(storage_id), name)?;
+            values
+                .SetUnsignedLargeIntegerValue(&WPD_OBJECT_SIZE, size)
+                .and_then(|_| {
+                    values.SetGuidValue(&WPD_OBJECT_CONTENT_TYPE, &WPD_CONTENT_TYPE_GENERIC_FILE)
+                })
+                .and_then(|_| {
+                    values.SetGuidValue(&WPD_OBJECT_FORMAT, &WPD_OBJECT_FORMAT_UNSPECIFIED)
+                })
+                .map_err(com_error)?;
+
+            let mut stream: Option<IStream> = None;
+            let mut optimal_size = BUFFER_SIZE;
+            content
+                .CreateObjectWithPropertiesAndData(
+                    &values,
+                    &mut stream,
+                    &mut optimal_size,
+                    std::ptr::null_mut(),
+                )
+                .map_err(com_error)?;
+            let stream = stream.ok_or_else(|| "The device refused the file".to_string())?;
+
+            let mut buffer = vec![0u8; optimal_size.max(BUFFER_SIZE) as usize];
+
+            loop {
+                let read = input.read(&mut buffer).map_err(|error| error.to_string())?;
+
+                if read == 0 {
+                    break;
+                }
+
+                let mut written = 0u32;
+                stream
+                    .Write(
+                        buffer.as_ptr() as *const _,
+                        read as u32,
+                        Some(&mut written as *mut u32),
+                    )
+                    .ok()
+                    .map_err(com_error)?;
+            }
+
+            stream.Commit(STGC_DEFAULT).map_err(com_error)?;
+        }
+
+        Ok(size)
+    }
+
+    fn create_folder(
+        &mut self,
+        storage_id: &str,
+        parent_id: Option<&str>,
+        name: &str,
+    ) -> Result<String, String> {
+        let content = self.content()?;
+
+        unsafe {
+            let values = self.new_values(parent_id.unwrap_or(storage_id), name)?;
+            values
+                .SetGuidValue(&WPD_OBJECT_CONTENT_TYPE, &WPD_CONTENT_TYPE_FOLDER)
+                .and_then(|_| {
+                    values.SetGuidValue(&WPD_OBJECT_FORMAT, &WPD_OBJECT_FORMAT_PROPERTIES_ONLY)
+                })
+                .map_err(com_error)?;
+
+            let mut object_id = PWSTR::null();
+            content
+                .CreateObjectWithPropertiesOnly(&values, &mut object_id)
+                .map_err(com_error)?;
+
+            Ok(take_pwstr(object_id))
+        }
+    }
+
+    fn delete(&mut self, object_id: &str) -> Result<(), String> {
+        let content = self.content()?;
+
+        unsafe {
+            let object_ids: IPortableDevicePropVariantCollection = CoCreateInstance(
+                &PortableDevicePropVariantCollection,
+                None,
+                CLSCTX_INPROC_SERVER,
+            )
+            .map_err(com_error)?;
+            object_ids
+                .Add(&PROPVARIANT::from(object_id))
+                .map_err(com_error)?;
+            // Variants from strings are BSTRs, WPD expects wide strings
+            object_ids.ChangeType(VT_LPWSTR.0).map_err(com_error)?;
+
+            content
+                .Delete(
+                    PORTABLE_DEVICE_DELETE_WITH_RECURSION.0 as u32,
+                    &object_ids,
+                    std::ptr::null_mut(),
+                )
+                .map_err(com_error)
+        }
+    }
+}
+
+impl Drop for WpdDevice {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.Close();
+        }
+    }
+}
@@ -0,0 +1,357 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use crate::app_windows::notify_store_changed;
+use crate::credentials::{self, ShareCredentials};
+use crate::dir_reader::{mount_network_share, NetworkShareParams};
+use crate::json_store;
+use crate::utils::normalize_path;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const STORE_NAME: &str = "network_shares";
+
+#[cfg(not(windows))]
+const NETWORK_FILE_SYSTEMS: [&str; 11] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smbfs",
+    "smb3",
+    "afpfs",
+    "webdav",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "fuse.gvfsd-fuse",
+    "macfuse",
+];
+
+/// A server connection the user can re-mount with one click. Passwords are
+/// never stored here, only an optional reference to a stored credential.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedShare {
+    pub id: String,
+    pub name: String,
+    pub protocol: String,
+    pub host: String,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub remote_path: String,
+    #[serde(default)]
+    pub credential_ref: Option<String>,
+    #[serde(default)]
+    pub last_mount_point: Option<String>,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MountedShare {
+    pub mount_point: String,
+    pub source: String,
+    pub file_system: String,
+    /// Id of the saved connection this mount came from, if any
+    pub saved_share_id: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ShareStore {
+    #[serde(default)]
+    shares: Vec<SavedShare>,
+}
+
+static SHARE_STORE: Lazy<Mutex<ShareStore>> =
+    Lazy::new(|| Mutex::new(json_store::load(STORE_NAME)));
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(windows)]
+fn run_net_use(args: &[&str]) -> Result<std::process::Output, String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    std::process::Command::new("net")
+        .arg("use")
+        .args(args)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map_err(|error| format!("Failed to run 'net use': {}", error))
+}
+
+// `net use` lists one connection per line: status, local drive, remote path
+#[cfg(windows)]
+pub(crate) fn list_mounted() -> Vec<MountedShare> {
+    let Ok(output) = run_net_use(&[]) else {
+        return Vec::new();
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let remote_index = columns
+                .iter()
+                .position(|column| column.starts_with(r"\\"))?;
+            let local_drive = columns[..remote_index]
+                .iter()
+                .find(|column| column.len() == 2 && column.ends_with(':'))?;
+
+            Some(MountedShare {
+                mount_point: format!("{}/", local_drive),
+                source: normalize_path(columns[remote_index]),
+                file_system: "smb".to_string(),
+                saved_share_id: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(windows))]
+pub(crate) fn list_mounted() -> Vec<MountedShare> {
+    sysinfo::Disks::new_with_refreshed_list()
+        .iter()
+        .filter_map(|disk| {
+            let file_system = disk.file_system().to_string_lossy().to_lowercase();
+
+            NETWORK_FILE_SYSTEMS
+                .contains(&file_system.as_str())
+                .then(|| MountedShare {
+                    mount_point: normalize_path(&disk.mount_point().to_string_lossy()),
+                    source: disk.name().to_string_lossy().to_string(),
+                    file_system,
+                    saved_share_id: None,
+                })
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn unmount(mount_point: &str) -> Result<(), String> {
+    let target = mount_point.trim_end_matches(['/', '\\']);
+    let output = run_net_use(&[target, "/delete", "/y"])?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        Err(format!("net use failed: {}", stderr.trim()))
+    }
+}
+
+#[cfg(not(windows))]
+fn unmount(mount_point: &str) -> Result<(), String> {
+    let mut attempts: Vec<(&str, Vec<&str>)> = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    {
+        // Shares mounted through gio live under the gvfs FUSE directory
+        if mount_point.contains("/gvfs/") {
+            attempts.push(("gio", vec!["mount", "-u", mount_point]));
+        }
+        attempts.push(("fusermount", vec!["-u", mount_point]));
+    }
+
+    #[cfg(target_os = "macos")]
+    attempts.push(("diskutil", vec!["unmount", mount_point]));
+
+    attempts.push(("umount", vec![mount_point]));
+
+    let mut last_error = String::from("No unmount tool available");
+
+    for (program, args) in attempts {
+        match std::process::Command::new(program).args(&args).output() {
+            Ok(output) if output.status.success() => {
+                // mount_network_share creates the mount directory itself
+                let _ = std::fs::remove_dir(mount_point);
+                return Ok(());
+            }
+            Ok(output) => {
+                last_error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            }
+            Err(error) => last_error = format!("Failed to run {}: {}", program, error),
+        }
+    }
+
+    Err(last_error)
+}
+
+/// Lists currently mounted network shares
+#[tauri::command]
+pub fn list_network_shares() -> Vec<MountedShare> {
+    let mut mounted = list_mounted();
+
+    if let Ok(store) = SHARE_STORE.lock() {
+        for share in &mut mounted {
+            let mount_point = share.mount_point.trim_end_matches('/');
+            share.saved_share_id = store
+                .shares
+                .iter()
+                .find(|saved| {
+                    saved
+                        .last_mount_point
+                        .as_deref()
+                        .is_some_and(|last_mount_point| {
+                            last_mount_point.trim_end_matches('/') == mount_point
+                        })
+                })
+                .map(|saved| saved.id.clone());
+        }
+    }
+
+    mounted
+}
+
+/// Disconnects a network share mounted with `mount_network_share`
+#[tauri::command]
+pub async fn unmount_network_share(mount_point: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || unmount(&mount_point))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Saves a connection for later `reconnect_share` calls. A password in
+/// `params` goes to the OS keychain and is linked through `credential_ref`,
+/// an explicit `credential_ref` links credentials stored earlier.
+#[tauri::command]
+pub async fn save_network_share(
+    window: tauri::WebviewWindow,
+    params: NetworkShareParams,
+    credential_ref: Option<String>,
+) -> Result<SavedShare, String> {
+    let credential_ref = match params.password.clone() {
+        Some(password) if !password.is_empty() => {
+            let credential_ref = credential_ref.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            let credentials = ShareCredentials {
+                username: params.username.clone(),
+                password,
+            };
+
+            let saved_ref = tokio::task::spawn_blocking(move || {
+                credentials::save(&credential_ref, &credentials).map(|_| credential_ref)
+            })
+            .await
+            .map_err(|error| error.to_string())??;
+
+            Some(saved_ref)
+        }
+        _ => credential_ref,
+    };
+
+    let share = SavedShare {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: params.mount_name,
+        protocol: params.protocol,
+        host: params.host,
+        port: params.port,
+        username: params.username,
+        remote_path: params.remote_path,
+        credential_ref,
+        last_mount_point: None,
+        created_at: now_ms(),
+    };
+
+    let mut store = SHARE_STORE.lock().map_err(|error| error.to_string())?;
+    store.shares.push(share.clone());
+    json_store::save(STORE_NAME, &*store)?;
+    notify_store_changed(STORE_NAME, Some(window.label()));
+
+    Ok(share)
+}
+
+#[tauri::command]
+pub fn list_saved_shares() -> Result<Vec<SavedShare>, String> {
+    let store = SHARE_STORE.lock().map_err(|error| error.to_string())?;
+    Ok(store.shares.clone())
+}
+
+/// Removes a saved connection along with its keychain entry, unless another
+/// saved connection still links the same credential
+#[tauri::command]
+pub async fn remove_saved_share(window: tauri::WebviewWindow, id: String) -> Result<(), String> {
+    let orphaned_ref = {
+        let mut store = SHARE_STORE.lock().map_err(|error| error.to_string())?;
+        let credential_ref = store
+            .shares
+            .iter()
+            .find(|share| share.id == id)
+            .and_then(|share| share.credential_ref.clone());
+        store.shares.retain(|share| share.id != id);
+        json_store::save(STORE_NAME, &*store)?;
+
+        credential_ref.filter(|credential_ref| {
+            !store
+                .shares
+                .iter()
+                .any(|share| share.credential_ref.as_ref() == Some(credential_ref))
+        })
+    };
+    notify_store_changed(STORE_NAME, Some(window.label()));
+
+    if let Some(credential_ref) = orphaned_ref {
+        tokio::task::spawn_blocking(move || credentials::delete(&credential_ref))
+            .await
+            .map_err(|error| error.to_string())??;
+    }
+
+    Ok(())
+}
+
+/// Mounts a saved connection again. `password` is only needed when the
+/// share requires one and no credential is linked, it takes precedence over
+/// the linked one otherwise.
+#[tauri::command]
+pub async fn reconnect_share(
+    window: tauri::WebviewWindow,
+    id: String,
+    password: Option<String>,
+) -> Result<String, String> {
+    let share = {
+        let store = SHARE_STORE.lock().map_err(|error| error.to_string())?;
+        store
+            .shares
+            .iter()
+            .find(|share| share.id == id)
+            .cloned()
+            .ok_or_else(|| format!("Saved share not found: {}", id))?
+    };
+
+    let mount_point = tokio::task::spawn_blocking(move || {
+        let stored = match (&password, &share.credential_ref) {
+            (None, Some(credential_ref)) => credentials::load(credential_ref)?,
+            _ => None,
+        };
+
+        let (username, password) = match stored {
+            Some(stored) => (stored.username.or(share.username), Some(stored.password)),
+            None => (share.username, password),
+        };
+
+        mount_network_share(NetworkShareParams {
+            protocol: share.protocol,
+            host: share.host,
+            port: share.port,
+            username,
+            password,
+            remote_path: share.remote_path,
+            mount_name: share.name,
+        })
+    })
+    .await
+    .map_err(|error| error.to_string())??;
+
+    let mut store = SHARE_STORE.lock().map_err(|error| error.to_string())?;
+    if let Some(saved) = store.shares.iter_mut().find(|saved| saved.id == id) {
+        saved.last_mount_point = Some(normalize_path(&mount_point));
+    }
+    json_store::save(STORE_NAME, &*store)?;
+    notify_store_changed(STORE_NAME, Some(window.label()));
+
+    Ok(mount_point)
+}
@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Finds the processes holding files open under a path, for when unmount
+//! or delete fails because the target is busy. Only processes the user may
+//! inspect are found: on Unix that is the user's own unless running as root.
+
+use crate::utils::to_os_path;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Paths reported per process; the UI only needs a hint of what is open
+const MAX_PATHS_PER_PROCESS: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockingProcess {
+    pub pid: u32,
+    pub name: String,
+    /// Open files, working directories and mapped files under the target.
+    /// Empty on Windows, where the Restart Manager doesn't say which file.
+    pub paths: Vec<String>,
+}
+
+/// Lists processes with open handles on `path` or anything below it.
+/// `path` can be a file, a folder or a mount point.
+#[tauri::command]
+pub async fn find_blocking_processes(path: String) -> Result<Vec<BlockingProcess>, String> {
+    tokio::task::spawn_blocking(move || {
+        let target = to_os_path(&path);
+
+        if !target.exists() {
+            return Err(format!("Path does not exist: {}", path));
+        }
+
+        let target = target.canonicalize().unwrap_or(target);
+        let mut processes = platform_find(&target)?;
+        processes.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+
+        Ok(processes)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[cfg(target_os = "linux")]
+fn platform_find(target: &Path) -> Result<Vec<BlockingProcess>, String> {
+    use crate::utils::normalize_path;
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::PathBuf;
+
+    let own_pid = std::process::id();
+    let mut processes = Vec::new();
+
+    for proc_entry in fs::read_dir("/proc").map_err(|error| error.to_string())? {
+        let Ok(proc_entry) = proc_entry else {
+            continue;
+        };
+        let Some(pid) = proc_entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u32>().ok())
+        else {
+            continue;
+        };
+
+        if pid == own_pid {
+            continue;
+        }
+
+        let proc_dir = proc_entry.path();
+        let mut paths = BTreeSet::new();
+        let mut note = |candidate: PathBuf| {
+            if candidate.starts_with(target) && paths.len() < MAX_PATHS_PER_PROCESS {
+                paths.insert(normalize_path(&candidate.to_string_lossy()));
+            }
+        };
+
+        // Unreadable for other users' processes, which are skipped
+        if let Ok(descriptors) = fs::read_dir(proc_dir.join("fd")) {
+            for descriptor in descriptors.flatten() {
+                if let Ok(link_target) = fs::read_link(descriptor.path()) {
+                    note(link_target);
+                }
+            }
+        }
+
+        for link in ["cwd", "root", "exe"] {
+            if let Ok(link_target) = fs::read_link(proc_dir.join(link)) {
+                note(link_target);
+            }
+        }
+
+        // Memory mapped files keep a mount busy without an open descriptor
+        if let Ok(maps) = fs::read_to_string(proc_dir.join("maps")) {
+            for line in maps.lines() {
+                if let Some(mapped) = line.split_whitespace().nth(5) {
+                    if mapped.starts_with('/') {
+                        note(PathBuf::from(mapped));
+                    }
+                }
+            }
+        }
+
+        if paths.is_empty() {
+            continue;
+        }
+
+        let name = fs::read_to_string(proc_dir.join("comm"))
+            .map(|comm| comm.trim().to_string())
+            .unwrap_or_else(|_| pid.to_string());
+
+        processes.push(BlockingProcess {
+            pid,
+            name,
+            paths: paths.into_iter().collect(),
+        });
+    }
+
+    Ok(processes)
+}
+
+// No /proc on macOS, lsof reads the same information from the kernel
+#[cfg(target_os = "macos")]
+fn platform_find(target: &Path) -> Result<Vec<BlockingProcess>, String> {
+    use crate::utils::normalize_path;
+
+    let target_str = target.to_string_lossy();
+    let mut command = std::process::Command::new("lsof");
+    command.arg("-F").arg("pcn");
+
+    if target.is_dir() {
+        // +D scans the folder recursively instead of matching it alone
+        command.arg("+D");
+    }
+
+    let output = command
+        .arg(target_str.as_ref())
+        .output()
+        .map_err(|error| format!("Failed to run lsof: {}", error))?;
+
+    // lsof exits with 1 both on errors and when nothing is open
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let own_pid = std::process::id();
+    let mut processes: Vec<BlockingProcess> = Vec::new();
+
+    for line in stdout.lines() {
+        let (field, value) = line.split_at(line.len().min(1));
+
+        match field {
+            "p" => {
+                if let Ok(pid) = value.parse::<u32>() {
+                    processes.push(BlockingProcess {
+                        pid,
+                        name: pid.to_string(),
+                        paths: Vec::new(),
+                    });
+                }
+            }
+            "c" => {
+                if let Some(process) = processes.last_mut() {
+                    process.name = value.to_string();
+                }
+            }
+            "n" => {
+                if let Some(process) = processes.last_mut() {
+                    let path = normalize_path(value);
+                    if process.paths.len() < MAX_PATHS_PER_PROCESS && !process.paths.contains(&path)
+                    {
+                        process.paths.push(path);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    processes.retain(|process| process.pid != own_pid);
+    Ok(processes)
+}
+
+/// The Restart Manager reports which processes use a set of files, so the
+/// files under the target are registered with a session and listed.
+#[cfg(windows)]
+fn platform_find(target: &Path) -> Result<Vec<BlockingProcess>, String> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{ERROR_MORE_DATA, ERROR_SUCCESS};
+    use windows_sys::Win32::System::RestartManager::{
+        RmEndSession, RmGetList, RmRegisterResources, RmStartSession, CCH_RM_SESSION_KEY,
+        RM_PROCESS_INFO,
+    };
+
+    // Registering every file of a large drive would take minutes; files
+    // past the limit are not checked
+    const MAX_REGISTERED_FILES: usize = 10_000;
+
+    let files: Vec<Vec<u16>> = walkdir::WalkDir::new(target)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .take(MAX_REGISTERED_FILES)
+        .map(|entry| entry.path().as_os_str().encode_wide().chain([0]).collect())
+        .collect();
+
+    if files.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let file_pointers: Vec<*const u16> = files.iter().map(|file| file.as_ptr()).collect();
+    let mut session = 0u32;
+    let mut session_key = [0u16; CCH_RM_SESSION_KEY as usize + 1];
+
+    let status = unsafe { RmStartSession(&mut session, 0, session_key.as_mut_ptr()) };
+    if status != ERROR_SUCCESS {
+        return Err(std::io::Error::from_raw_os_error(status as i32).to_string());
+    }
+
+    let result = (|| {
+        let status = unsafe {
+            RmRegisterResources(
+                session,
+                file_pointers.len() as u32,
+                file_pointers.as_ptr(),
+                0,
+                std::ptr::null(),
+                0,
+                std::ptr::null(),
+            )
+        };
+        if status != ERROR_SUCCESS {
+            return Err(std::io::Error::from_raw_os_error(status as i32).to_string());
+        }
+
+        let mut process_infos: Vec<RM_PROCESS_INFO> = Vec::new();
+
+        // The list can grow between the sizing call and the real one
+        loop {
+            let mut needed = 0u32;
+            let mut count = process_infos.len() as u32;
+            let mut reboot_reasons = 0u32;
+
+            let status = unsafe {
+                RmGetList(
+                    session,
+                    &mut needed,
+                    &mut count,
+                    process_infos.as_mut_ptr(),
+                    &mut reboot_reasons,
+                )
+            };
+
+            match status {
+                ERROR_SUCCESS => {
+                    process_infos.truncate(count as usize);
+                    break;
+                }
+                ERROR_MORE_DATA => {
+                    process_infos = vec![unsafe { std::mem::zeroed() }; needed as usize];
+                }
+                _ => return Err(std::io::Error::from_raw_os_error(status as i32).to_string()),
+            }
+        }
+
+        let own_pid = std::process::id();
+
+        Ok(process_infos
+            .iter()
+            .filter(|info| info.Process.dwProcessId != own_pid)
+            .map(|info| {
+                let name_length = info
+                    .strAppName
+                    .iter()
+                    .position(|char| *char == 0)
+                    .unwrap_or(info.strAppName.len());
+
+                BlockingProcess {
+                    pid: info.Process.dwProcessId,
+                    name: String::from_utf16_lossy(&info.strAppName[..name_length]),
+                    paths: Vec::new(),
+                }
+            })
+            .collect())
+    })();
+
+    unsafe {
+        RmEndSession(session);
+    }
+
+    result
+}
@@ -40,12 +40,12 @@ pub fn get_associated_programs_impl(file_path: &str) -> GetAssociatedProgramsRes
         };
     }
 
-    log::info!("Open With Linux: resolving apps for {}", file_path);
+    tracing::info!("Open With Linux: resolving apps for {}", file_path);
 
     let mime_type = match get_mime_type(path) {
         Ok(value) => value,
         Err(message) => {
-            log::warn!(
+            tracing::warn!(
                 "Open With Linux: failed to resolve mime type for {}: {}",
                 file_path,
                 message
@@ -60,7 +60,7 @@ pub fn get_associated_programs_impl(file_path: &str) -> GetAssociatedProgramsRes
         }
     };
 
-    log::info!("Open With Linux: mime type {}", mime_type);
+    tracing::info!("Open With Linux: mime type {}", mime_type);
 
     let gio_info = get_gio_mime_info(&mime_type).unwrap_or(GioMimeInfo {
         default_app: None,
@@ -77,9 +77,9 @@ pub fn get_associated_programs_impl(file_path: &str) -> GetAssociatedProgramsRes
         .or(xdg_default_app);
 
     if let Some(default_id) = default_desktop_id.as_ref() {
-        log::info!("Open With Linux: default desktop id {}", default_id);
+        tracing::info!("Open With Linux: default desktop id {}", default_id);
     } else {
-        log::info!("Open With Linux: default desktop id not found");
+        tracing::info!("Open With Linux: default desktop id not found");
     }
 
     let mut default_program: Option<AssociatedProgram> = None;
@@ -87,7 +87,7 @@ pub fn get_associated_programs_impl(file_path: &str) -> GetAssociatedProgramsRes
         if let Some(program) = desktop_id_to_program(desktop_id, true) {
             default_program = Some(program);
         } else {
-            log::warn!(
+            tracing::warn!(
                 "Open With Linux: default desktop id not resolved {}",
                 desktop_id
             );
@@ -111,7 +111,7 @@ pub fn get_associated_programs_impl(file_path: &str) -> GetAssociatedProgramsRes
     merge_desktop_ids(&mut other_ids, &gio_info.registered_apps);
     merge_desktop_ids(&mut other_ids, &mimeapps_entries.other_apps);
 
-    log::info!(
+    tracing::info!(
         "Open With Linux: candidates default={} recommended={} other={}",
         default_desktop_id.is_some(),
         recommended_ids.len(),
@@ -126,7 +126,7 @@ pub fn get_associated_programs_impl(file_path: &str) -> GetAssociatedProgramsRes
             seen_ids.insert(desktop_id.to_lowercase());
             recommended_programs.push(program);
         } else {
-            log::warn!(
+            tracing::warn!(
                 "Open With Linux: recommended desktop id not resolved {}",
                 desktop_id
             );
@@ -141,14 +141,14 @@ pub fn get_associated_programs_impl(file_path: &str) -> GetAssociatedProgramsRes
             seen_ids.insert(desktop_id.to_lowercase());
             other_programs.push(program);
         } else {
-            log::warn!(
+            tracing::warn!(
                 "Open With Linux: other desktop id not resolved {}",
                 desktop_id
             );
         }
     }
 
-    log::info!(
+    tracing::info!(
         "Open With Linux: resolved default={} recommended={} other={}",
         default_program.is_some(),
         recommended_programs.len(),
@@ -165,7 +165,7 @@ pub fn get_associated_programs_impl(file_path: &str) -> GetAssociatedProgramsRes
 }
 
 pub fn open_with_desktop_id(program_id: &str, file_path: &str) -> Option<OpenWithResult> {
-    log::info!(
+    tracing::info!(
         "Open With Linux: launch request program={} file={}",
         program_id,
         file_path
@@ -188,7 +188,7 @@ pub fn open_with_desktop_id(program_id: &str, file_path: &str) -> Option<OpenWit
     }
 
     if desktop_path.is_none() && !program_id.ends_with(".desktop") {
-        log::warn!("Open With Linux: no desktop file found for {}", program_id);
+        tracing::warn!("Open With Linux: no desktop file found for {}", program_id);
         return None;
     }
 
@@ -199,14 +199,14 @@ pub fn open_with_desktop_id(program_id: &str, file_path: &str) -> Option<OpenWit
         .spawn()
     {
         Ok(_) => {
-            log::info!("Open With Linux: launched via gio {}", launch_target);
+            tracing::info!("Open With Linux: launched via gio {}", launch_target);
             return Some(OpenWithResult {
                 success: true,
                 error: None,
             });
         }
         Err(command_error) => {
-            log::warn!(
+            tracing::warn!(
                 "Open With Linux: gio launch failed for {}: {}",
                 launch_target,
                 command_error
@@ -221,14 +221,14 @@ pub fn open_with_desktop_id(program_id: &str, file_path: &str) -> Option<OpenWit
         .spawn()
     {
         Ok(_) => {
-            log::info!("Open With Linux: launched via gtk-launch {}", launch_id);
+            tracing::info!("Open With Linux: launched via gtk-launch {}", launch_id);
             return Some(OpenWithResult {
                 success: true,
                 error: None,
             });
         }
         Err(command_error) => {
-            log::warn!(
+            tracing::warn!(
                 "Open With Linux: gtk-launch failed for {}: {}",
                 launch_id,
                 command_error
@@ -262,7 +262,7 @@ fn get_mime_type(path: &Path) -> Result<String, String> {
     }
 
     if let Some(mime_type) = get_mime_type_from_extension(path) {
-        log::info!(
+        tracing::info!(
             "Open With Linux: using extension-based mime type for {}",
             file_path
         );
@@ -290,7 +290,7 @@ fn get_mime_type_via_xdg_mime(file_path: &str) -> Option<String> {
         return None;
     }
 
-    log::info!("Open With Linux: xdg-mime resolved {}", mime_type);
+    tracing::info!("Open With Linux: xdg-mime resolved {}", mime_type);
     Some(mime_type)
 }
 
@@ -313,7 +313,7 @@ fn get_mime_type_via_gio(file_path: &str) -> Option<String> {
                 .trim()
                 .to_string();
             if !mime_type.is_empty() {
-                log::info!("Open With Linux: gio info resolved {}", mime_type);
+                tracing::info!("Open With Linux: gio info resolved {}", mime_type);
                 return Some(mime_type);
             }
         }
@@ -337,7 +337,7 @@ fn get_mime_type_via_file_command(file_path: &str) -> Option<String> {
         return None;
     }
 
-    log::info!("Open With Linux: file command resolved {}", mime_type);
+    tracing::info!("Open With Linux: file command resolved {}", mime_type);
     Some(mime_type)
 }
 
@@ -421,7 +421,7 @@ fn get_gio_mime_info(mime_type: &str) -> Option<GioMimeInfo> {
         .env("LANG", "C")
         .output()
         .map_err(|command_error| {
-            log::warn!(
+            tracing::warn!(
                 "Open With Linux: gio mime failed for {}: {}",
                 mime_type,
                 command_error
@@ -430,7 +430,7 @@ fn get_gio_mime_info(mime_type: &str) -> Option<GioMimeInfo> {
         .ok()?;
 
     if !output.status.success() {
-        log::warn!(
+        tracing::warn!(
             "Open With Linux: gio mime exited non-zero for {}",
             mime_type
         );
@@ -439,7 +439,7 @@ fn get_gio_mime_info(mime_type: &str) -> Option<GioMimeInfo> {
 
     let content = String::from_utf8_lossy(&output.stdout).to_string();
     let parsed = parse_gio_mime_output(&content);
-    log::info!(
+    tracing::info!(
         "Open With Linux: gio parsed default={} recommended={} registered={}",
         parsed.default_app.is_some(),
         parsed.recommended_apps.len(),
@@ -580,7 +580,7 @@ fn get_mimeapps_entries(mime_type: &str) -> MimeappsEntries {
 
     merge_desktop_ids(&mut entries.other_apps, &get_mimeinfo_cache_apps(mime_type));
 
-    log::info!(
+    tracing::info!(
         "Open With Linux: mimeapps entries default={} recommended={} other={}",
         entries.default_app.is_some(),
         entries.recommended_apps.len(),
@@ -590,12 +590,47 @@ fn get_mimeapps_entries(mime_type: &str) -> MimeappsEntries {
     entries
 }
 
+pub fn get_default_app_impl(mime_type: &str) -> Result<Option<AssociatedProgram>, String> {
+    Ok(get_xdg_default_app(mime_type)
+        .and_then(|desktop_id| desktop_id_to_program(&desktop_id, true)))
+}
+
+pub fn set_default_app_impl(mime_type: &str, desktop_id: &str) -> OpenWithResult {
+    if find_desktop_file(desktop_id).is_none() {
+        return OpenWithResult {
+            success: false,
+            error: Some(format!("Application not found: {}", desktop_id)),
+        };
+    }
+
+    match Command::new("xdg-mime")
+        .args(["default", desktop_id, mime_type])
+        .output()
+    {
+        Ok(output) if output.status.success() => OpenWithResult {
+            success: true,
+            error: None,
+        },
+        Ok(output) => OpenWithResult {
+            success: false,
+            error: Some(format!(
+                "xdg-mime failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+        },
+        Err(command_error) => OpenWithResult {
+            success: false,
+            error: Some(format!("Failed to run xdg-mime: {}", command_error)),
+        },
+    }
+}
+
 fn get_xdg_default_app(mime_type: &str) -> Option<String> {
     let output = Command::new("xdg-mime")
         .args(["query", "default", mime_type])
         .output()
         .map_err(|command_error| {
-            log::warn!(
+            tracing::warn!(
                 "Open With Linux: xdg-mime default failed for {}: {}",
                 mime_type,
                 command_error
@@ -605,7 +640,7 @@ fn get_xdg_default_app(mime_type: &str) -> Option<String> {
 
     if !output.status.success() {
         let stderr_value = String::from_utf8_lossy(&output.stderr);
-        log::warn!(
+        tracing::warn!(
             "Open With Linux: xdg-mime default exited non-zero for {}: {}",
             mime_type,
             stderr_value.trim()
@@ -781,7 +816,7 @@ fn desktop_id_to_program(desktop_id: &str, is_default: bool) -> Option<Associate
         .or_else(|| exec_path.as_ref().and_then(|value| get_program_icon(value)));
 
     if desktop_file_path.is_none() {
-        log::warn!("Open With Linux: desktop file not found {}", desktop_id);
+        tracing::warn!("Open With Linux: desktop file not found {}", desktop_id);
     }
 
     Some(AssociatedProgram {
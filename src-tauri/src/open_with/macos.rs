@@ -0,0 +1,248 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::types::{AssociatedProgram, GetAssociatedProgramsResult, OpenWithResult};
+use super::utils::get_program_icon;
+use core_foundation::array::{CFArray, CFArrayRef};
+use core_foundation::base::TCFType;
+use core_foundation::string::{CFString, CFStringRef};
+use core_foundation::url::{CFURLRef, CFURL};
+use std::ffi::c_void;
+use std::path::{Path, PathBuf};
+
+const LS_ROLES_ALL: u32 = 0xFFFF_FFFF;
+// Value of kUTTagClassMIMEType
+const UT_TAG_CLASS_MIME_TYPE: &str = "public.mime-type";
+
+#[link(name = "CoreServices", kind = "framework")]
+extern "C" {
+    fn LSCopyApplicationURLsForURL(in_url: CFURLRef, in_role_mask: u32) -> CFArrayRef;
+    fn LSCopyDefaultApplicationURLForURL(
+        in_url: CFURLRef,
+        in_role_mask: u32,
+        out_error: *mut *mut c_void,
+    ) -> CFURLRef;
+    fn LSCopyApplicationURLsForBundleIdentifier(
+        in_bundle_identifier: CFStringRef,
+        out_error: *mut *mut c_void,
+    ) -> CFArrayRef;
+    fn LSCopyDefaultRoleHandlerForContentType(
+        in_content_type: CFStringRef,
+        in_role: u32,
+    ) -> CFStringRef;
+    fn LSSetDefaultRoleHandlerForContentType(
+        in_content_type: CFStringRef,
+        in_role: u32,
+        in_handler_bundle_id: CFStringRef,
+    ) -> i32;
+    fn UTTypeCreatePreferredIdentifierForTag(
+        in_tag_class: CFStringRef,
+        in_tag: CFStringRef,
+        in_conforming_to_uti: CFStringRef,
+    ) -> CFStringRef;
+}
+
+fn error_result(message: String) -> GetAssociatedProgramsResult {
+    GetAssociatedProgramsResult {
+        success: false,
+        recommended_programs: vec![],
+        other_programs: vec![],
+        default_program: None,
+        error: Some(message),
+    }
+}
+
+fn to_program(app_path: &Path, is_default: bool) -> AssociatedProgram {
+    let path = app_path.to_string_lossy().to_string();
+
+    AssociatedProgram {
+        name: app_path
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.clone()),
+        icon: get_program_icon(&path),
+        path,
+        is_default,
+    }
+}
+
+fn default_app_path(url: &CFURL) -> Option<PathBuf> {
+    let app_url = unsafe {
+        LSCopyDefaultApplicationURLForURL(
+            url.as_concrete_TypeRef(),
+            LS_ROLES_ALL,
+            std::ptr::null_mut(),
+        )
+    };
+
+    if app_url.is_null() {
+        return None;
+    }
+
+    unsafe { CFURL::wrap_under_create_rule(app_url) }.to_path()
+}
+
+fn app_paths(url: &CFURL) -> Vec<PathBuf> {
+    let app_urls = unsafe { LSCopyApplicationURLsForURL(url.as_concrete_TypeRef(), LS_ROLES_ALL) };
+
+    if app_urls.is_null() {
+        return vec![];
+    }
+
+    let app_urls: CFArray<CFURL> = unsafe { CFArray::wrap_under_create_rule(app_urls) };
+    app_urls
+        .iter()
+        .filter_map(|app_url| app_url.to_path())
+        .collect()
+}
+
+pub fn get_associated_programs_impl(file_path: &str) -> GetAssociatedProgramsResult {
+    let path = Path::new(file_path);
+    if !path.exists() {
+        return error_result(format!("Path not found: {}", file_path));
+    }
+
+    let url = match CFURL::from_path(path, path.is_dir()) {
+        Some(url) => url,
+        None => return error_result(format!("Invalid path: {}", file_path)),
+    };
+
+    let default_path = default_app_path(&url);
+    let default_program = default_path
+        .as_ref()
+        .map(|app_path| to_program(app_path, true));
+
+    let mut recommended_programs: Vec<AssociatedProgram> = Vec::new();
+
+    for app_path in app_paths(&url) {
+        let is_duplicate = default_path.as_ref() == Some(&app_path)
+            || recommended_programs
+                .iter()
+                .any(|program| Path::new(&program.path) == app_path);
+
+        if !is_duplicate {
+            recommended_programs.push(to_program(&app_path, false));
+        }
+    }
+
+    recommended_programs
+        .sort_by(|first, second| first.name.to_lowercase().cmp(&second.name.to_lowercase()));
+
+    GetAssociatedProgramsResult {
+        success: true,
+        recommended_programs,
+        other_programs: vec![],
+        default_program,
+        error: None,
+    }
+}
+
+fn mime_to_uti(mime: &str) -> Option<CFString> {
+    let tag_class = CFString::new(UT_TAG_CLASS_MIME_TYPE);
+    let tag = CFString::new(mime);
+
+    let uti = unsafe {
+        UTTypeCreatePreferredIdentifierForTag(
+            tag_class.as_concrete_TypeRef(),
+            tag.as_concrete_TypeRef(),
+            std::ptr::null(),
+        )
+    };
+
+    if uti.is_null() {
+        return None;
+    }
+
+    let uti = unsafe { CFString::wrap_under_create_rule(uti) };
+
+    // Unknown MIME types get a dynamic "dyn.*" identifier nothing handles
+    if uti.to_string().starts_with("dyn.") {
+        return None;
+    }
+
+    Some(uti)
+}
+
+fn bundle_identifier(app_path: &Path) -> Option<String> {
+    let info = plist::Value::from_file(app_path.join("Contents").join("Info.plist")).ok()?;
+
+    info.as_dictionary()?
+        .get("CFBundleIdentifier")?
+        .as_string()
+        .map(|identifier| identifier.to_string())
+}
+
+fn bundle_app_path(bundle_id: &CFString) -> Option<PathBuf> {
+    let app_urls = unsafe {
+        LSCopyApplicationURLsForBundleIdentifier(
+            bundle_id.as_concrete_TypeRef(),
+            std::ptr::null_mut(),
+        )
+    };
+
+    if app_urls.is_null() {
+        return None;
+    }
+
+    let app_urls: CFArray<CFURL> = unsafe { CFArray::wrap_under_create_rule(app_urls) };
+    let first_path = app_urls.iter().find_map(|app_url| app_url.to_path());
+    first_path
+}
+
+pub fn get_default_app_impl(mime: &str) -> Result<Option<AssociatedProgram>, String> {
+    let uti = mime_to_uti(mime).ok_or_else(|| format!("Unknown MIME type: {}", mime))?;
+
+    let bundle_id =
+        unsafe { LSCopyDefaultRoleHandlerForContentType(uti.as_concrete_TypeRef(), LS_ROLES_ALL) };
+
+    if bundle_id.is_null() {
+        return Ok(None);
+    }
+
+    let bundle_id = unsafe { CFString::wrap_under_create_rule(bundle_id) };
+
+    Ok(bundle_app_path(&bundle_id).map(|app_path| to_program(&app_path, true)))
+}
+
+pub fn set_default_app_impl(mime: &str, app_path: &str) -> OpenWithResult {
+    let uti = match mime_to_uti(mime) {
+        Some(uti) => uti,
+        None => {
+            return OpenWithResult {
+                success: false,
+                error: Some(format!("Unknown MIME type: {}", mime)),
+            }
+        }
+    };
+
+    let bundle_id = match bundle_identifier(Path::new(app_path)) {
+        Some(bundle_id) => CFString::new(&bundle_id),
+        None => {
+            return OpenWithResult {
+                success: false,
+                error: Some(format!("Not an application bundle: {}", app_path)),
+            }
+        }
+    };
+
+    let status = unsafe {
+        LSSetDefaultRoleHandlerForContentType(
+            uti.as_concrete_TypeRef(),
+            LS_ROLES_ALL,
+            bundle_id.as_concrete_TypeRef(),
+        )
+    };
+
+    if status == 0 {
+        OpenWithResult {
+            success: true,
+            error: None,
+        }
+    } else {
+        OpenWithResult {
+            success: false,
+            error: Some(format!("LaunchServices error {}", status)),
+        }
+    }
+}
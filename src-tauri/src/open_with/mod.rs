@@ -11,7 +11,12 @@ mod windows;
 #[cfg(target_os = "linux")]
 mod linux;
 
-pub use types::{GetAssociatedProgramsResult, GetShellContextMenuResult, OpenWithResult};
+#[cfg(target_os = "macos")]
+mod macos;
+
+pub use types::{
+    AssociatedProgram, GetAssociatedProgramsResult, GetShellContextMenuResult, OpenWithResult,
+};
 
 use std::path::Path;
 use std::process::Command;
@@ -27,7 +32,11 @@ pub fn get_associated_programs(file_path: String) -> GetAssociatedProgramsResult
     {
         linux::get_associated_programs_impl(&file_path)
     }
-    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    #[cfg(target_os = "macos")]
+    {
+        macos::get_associated_programs_impl(&file_path)
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
     {
         let _ = file_path;
         GetAssociatedProgramsResult {
@@ -36,13 +45,93 @@ pub fn get_associated_programs(file_path: String) -> GetAssociatedProgramsResult
             other_programs: vec![],
             default_program: None,
             error: Some(
-                "Open With functionality is only supported on Windows and Linux currently"
-                    .to_string(),
+                "Open With functionality is not supported on this platform".to_string(),
             ),
         }
     }
 }
 
+/// Returns the system default application for a MIME type
+#[tauri::command]
+pub fn get_default_app(mime: String) -> Result<Option<AssociatedProgram>, String> {
+    #[cfg(target_os = "windows")]
+    {
+        windows::get_default_app_impl(&mime)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        linux::get_default_app_impl(&mime)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos::get_default_app_impl(&mime)
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+    {
+        let _ = mime;
+        Err("Default applications are not supported on this platform".to_string())
+    }
+}
+
+/// Makes `app_id` (an application from `get_apps_for_file`) the system
+/// default for a MIME type
+#[tauri::command]
+pub fn set_default_app(mime: String, app_id: String) -> OpenWithResult {
+    #[cfg(target_os = "windows")]
+    {
+        windows::set_default_app_impl(&mime, &app_id)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        linux::set_default_app_impl(&mime, &app_id)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos::set_default_app_impl(&mime, &app_id)
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+    {
+        let _ = (mime, app_id);
+        OpenWithResult {
+            success: false,
+            error: Some("Default applications are not supported on this platform".to_string()),
+        }
+    }
+}
+
+/// Lists applications able to open the file, default application first
+#[tauri::command]
+pub fn get_apps_for_file(path: String) -> Result<Vec<AssociatedProgram>, String> {
+    let result = get_associated_programs(path);
+
+    if !result.success {
+        return Err(result
+            .error
+            .unwrap_or_else(|| "Failed to get applications".to_string()));
+    }
+
+    let mut apps: Vec<AssociatedProgram> = Vec::new();
+
+    for program in result
+        .default_program
+        .into_iter()
+        .chain(result.recommended_programs)
+        .chain(result.other_programs)
+    {
+        if !apps.iter().any(|existing| existing.path == program.path) {
+            apps.push(program);
+        }
+    }
+
+    Ok(apps)
+}
+
+/// Opens the file with an application returned by `get_apps_for_file`
+#[tauri::command]
+pub fn open_with(path: String, app_id: String) -> OpenWithResult {
+    open_with_program(path, app_id, Vec::new())
+}
+
 #[tauri::command]
 pub fn open_with_program(
     file_path: String,
@@ -58,6 +147,7 @@ pub fn open_with_program(
     }
 
     let absolute_file_path = canonicalize_path(file);
+    crate::history::record_path_visit(file);
 
     #[cfg(target_os = "windows")]
     {
@@ -82,6 +172,26 @@ pub fn open_with_program(
         }
     }
 
+    // App bundles are directories and have to be launched through `open`
+    #[cfg(target_os = "macos")]
+    {
+        if arguments.is_empty() && program_path.ends_with(".app") {
+            return match Command::new("open")
+                .args(["-a", &program_path, &absolute_file_path])
+                .spawn()
+            {
+                Ok(_) => OpenWithResult {
+                    success: true,
+                    error: None,
+                },
+                Err(spawn_error) => OpenWithResult {
+                    success: false,
+                    error: Some(format!("Failed to start program: {}", spawn_error)),
+                },
+            };
+        }
+    }
+
     let program = Path::new(&program_path);
     if !program.exists() {
         return OpenWithResult {
@@ -133,6 +243,8 @@ pub fn open_with_program(
 
 #[tauri::command]
 pub fn open_with_default(file_path: String) -> OpenWithResult {
+    crate::history::record_path_visit(Path::new(&file_path));
+
     #[cfg(target_os = "windows")]
     {
         use std::os::windows::process::CommandExt;
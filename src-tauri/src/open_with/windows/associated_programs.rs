@@ -278,7 +278,7 @@ unsafe fn pwstr_to_string(pwstr: PWSTR) -> Option<String> {
     pwstr_to_string_and_free(pwstr)
 }
 
-unsafe fn get_default_program(
+pub(super) unsafe fn get_default_program(
     extension: &str,
     seen_paths: &HashSet<String>,
 ) -> Option<AssociatedProgram> {
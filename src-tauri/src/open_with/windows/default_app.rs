@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::associated_programs::get_default_program;
+use crate::open_with::types::{AssociatedProgram, OpenWithResult};
+use std::collections::HashSet;
+use std::os::windows::process::CommandExt;
+use std::process::Command;
+use winreg::enums::HKEY_CLASSES_ROOT;
+use winreg::RegKey;
+
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+fn mime_to_extension(mime: &str) -> Option<String> {
+    RegKey::predef(HKEY_CLASSES_ROOT)
+        .open_subkey(format!("MIME\\Database\\Content Type\\{}", mime))
+        .ok()?
+        .get_value::<String, _>("Extension")
+        .ok()
+        .filter(|extension| !extension.is_empty())
+}
+
+pub fn get_default_app_impl(mime: &str) -> Result<Option<AssociatedProgram>, String> {
+    let extension = mime_to_extension(mime)
+        .ok_or_else(|| format!("No file extension is registered for {}", mime))?;
+
+    let program = unsafe { get_default_program(&extension, &HashSet::new()) };
+
+    Ok(program.map(|mut program| {
+        program.is_default = true;
+        program
+    }))
+}
+
+// Windows guards per-user default choices with a hash only the Settings
+// app can produce, so the user is sent there to confirm the change
+pub fn set_default_app_impl(mime: &str, _app_id: &str) -> OpenWithResult {
+    if mime_to_extension(mime).is_none() {
+        return OpenWithResult {
+            success: false,
+            error: Some(format!("No file extension is registered for {}", mime)),
+        };
+    }
+
+    let settings_result = Command::new("cmd")
+        .args(["/C", "start", "", "ms-settings:defaultapps"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .spawn();
+
+    OpenWithResult {
+        success: false,
+        error: Some(match settings_result {
+            Ok(_) => "Windows requires default apps to be changed in Settings. Default apps settings were opened.".to_string(),
+            Err(spawn_error) => format!("Failed to open Default apps settings: {}", spawn_error),
+        }),
+    }
+}
@@ -3,11 +3,13 @@
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
 mod associated_programs;
+mod default_app;
 mod shell_menu;
 mod utils;
 
 pub use associated_programs::get_associated_programs_impl;
 pub use associated_programs::invoke_handler_for_file;
+pub use default_app::{get_default_app_impl, set_default_app_impl};
 pub use shell_menu::get_shell_context_menu_impl;
 pub use shell_menu::invoke_shell_command;
 
@@ -0,0 +1,358 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::{AclEntry, AclInfo};
+use std::ffi::c_void;
+use std::os::windows::ffi::OsStrExt;
+use windows_sys::Win32::Foundation::{CloseHandle, LocalFree, HANDLE};
+use windows_sys::Win32::Security::Authorization::{
+    ConvertSidToStringSidW, ConvertStringSidToSidW, GetNamedSecurityInfoW, SetEntriesInAclW,
+    SetNamedSecurityInfoW, EXPLICIT_ACCESS_W, TRUSTEE_W,
+};
+use windows_sys::Win32::Security::{
+    GetAce, GetAclInformation, GetSecurityDescriptorControl, GetTokenInformation,
+    LookupAccountSidW, TokenUser, ACCESS_ALLOWED_ACE, ACE_HEADER, ACL, ACL_SIZE_INFORMATION,
+    TOKEN_QUERY, TOKEN_USER,
+};
+use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+const SE_FILE_OBJECT: i32 = 1;
+const OWNER_SECURITY_INFORMATION: u32 = 0x00000001;
+const DACL_SECURITY_INFORMATION: u32 = 0x00000004;
+const PROTECTED_DACL_SECURITY_INFORMATION: u32 = 0x80000000;
+const UNPROTECTED_DACL_SECURITY_INFORMATION: u32 = 0x20000000;
+const ACL_SIZE_INFORMATION_CLASS: i32 = 2;
+const SE_DACL_PROTECTED: u16 = 0x1000;
+
+const ACCESS_ALLOWED_ACE_TYPE: u8 = 0x0;
+const ACCESS_DENIED_ACE_TYPE: u8 = 0x1;
+
+const OBJECT_INHERIT_ACE: u8 = 0x1;
+const CONTAINER_INHERIT_ACE: u8 = 0x2;
+const NO_PROPAGATE_INHERIT_ACE: u8 = 0x4;
+const INHERIT_ONLY_ACE: u8 = 0x8;
+const INHERITED_ACE: u8 = 0x10;
+
+const GRANT_ACCESS: i32 = 1;
+const DENY_ACCESS: i32 = 3;
+const NO_MULTIPLE_TRUSTEE: i32 = 0;
+const TRUSTEE_IS_SID: i32 = 0;
+const TRUSTEE_IS_NAME: i32 = 1;
+const TRUSTEE_IS_UNKNOWN: i32 = 0;
+
+fn to_wide(value: &str) -> Vec<u16> {
+    std::ffi::OsStr::new(value)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect()
+}
+
+fn wide_ptr_to_string(pointer: *const u16) -> String {
+    if pointer.is_null() {
+        return String::new();
+    }
+    unsafe {
+        let length = (0..).take_while(|&index| *pointer.add(index) != 0).count();
+        String::from_utf16_lossy(std::slice::from_raw_parts(pointer, length))
+    }
+}
+
+fn sid_to_string(sid: *mut c_void) -> String {
+    let mut string_sid: *mut u16 = std::ptr::null_mut();
+    unsafe {
+        if ConvertSidToStringSidW(sid, &mut string_sid) == 0 {
+            return String::new();
+        }
+        let value = wide_ptr_to_string(string_sid);
+        LocalFree(string_sid as *mut c_void);
+        value
+    }
+}
+
+/// SID string of the user running Sigma, such as "S-1-5-21-...-1001"
+pub fn current_user_sid() -> Option<String> {
+    let mut token: HANDLE = std::ptr::null_mut();
+    unsafe {
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return None;
+        }
+
+        let mut length = 0u32;
+        GetTokenInformation(token, TokenUser, std::ptr::null_mut(), 0, &mut length);
+        // u64 elements keep the buffer aligned for TOKEN_USER
+        let mut buffer = vec![0u64; (length as usize).div_ceil(8)];
+        let succeeded = GetTokenInformation(
+            token,
+            TokenUser,
+            buffer.as_mut_ptr() as *mut c_void,
+            length,
+            &mut length,
+        ) != 0;
+        CloseHandle(token);
+
+        if !succeeded {
+            return None;
+        }
+
+        let token_user = &*(buffer.as_ptr() as *const TOKEN_USER);
+        Some(sid_to_string(token_user.User.Sid)).filter(|sid| !sid.is_empty())
+    }
+}
+
+// Resolves a SID to "DOMAIN\name"; falls back to the SID string for
+// orphaned accounts (e.g. users deleted from the machine).
+fn sid_to_account_name(sid: *mut c_void) -> Option<String> {
+    let mut name = vec![0u16; 256];
+    let mut domain = vec![0u16; 256];
+    let mut name_length = name.len() as u32;
+    let mut domain_length = domain.len() as u32;
+    let mut sid_use: i32 = 0;
+
+    let succeeded = unsafe {
+        LookupAccountSidW(
+            std::ptr::null(),
+            sid,
+            name.as_mut_ptr(),
+            &mut name_length,
+            domain.as_mut_ptr(),
+            &mut domain_length,
+            &mut sid_use,
+        ) != 0
+    };
+
+    if !succeeded {
+        return None;
+    }
+
+    let name = String::from_utf16_lossy(&name[..name_length as usize]);
+    let domain = String::from_utf16_lossy(&domain[..domain_length as usize]);
+
+    if domain.is_empty() {
+        Some(name)
+    } else {
+        Some(format!("{}\\{}", domain, name))
+    }
+}
+
+pub fn read_acl(path: &str) -> Result<AclInfo, String> {
+    let wide_path = to_wide(path);
+    let mut owner: *mut c_void = std::ptr::null_mut();
+    let mut dacl: *mut ACL = std::ptr::null_mut();
+    let mut descriptor: *mut c_void = std::ptr::null_mut();
+
+    let status = unsafe {
+        GetNamedSecurityInfoW(
+            wide_path.as_ptr(),
+            SE_FILE_OBJECT,
+            OWNER_SECURITY_INFORMATION | DACL_SECURITY_INFORMATION,
+            &mut owner,
+            std::ptr::null_mut(),
+            &mut dacl,
+            std::ptr::null_mut(),
+            &mut descriptor,
+        )
+    };
+
+    if status != 0 {
+        return Err(format!(
+            "Failed to read security info: {}",
+            std::io::Error::from_raw_os_error(status as i32)
+        ));
+    }
+
+    let mut control: u16 = 0;
+    let mut revision: u32 = 0;
+    unsafe {
+        GetSecurityDescriptorControl(descriptor, &mut control, &mut revision);
+    }
+
+    let owner_name = if owner.is_null() {
+        None
+    } else {
+        sid_to_account_name(owner).or_else(|| Some(sid_to_string(owner)))
+    };
+
+    let mut entries: Vec<AclEntry> = Vec::new();
+
+    // A null DACL grants everyone full access; it is reported as no entries
+    if !dacl.is_null() {
+        let mut size_info: ACL_SIZE_INFORMATION = unsafe { std::mem::zeroed() };
+        let got_info = unsafe {
+            GetAclInformation(
+                dacl,
+                &mut size_info as *mut ACL_SIZE_INFORMATION as *mut c_void,
+                std::mem::size_of::<ACL_SIZE_INFORMATION>() as u32,
+                ACL_SIZE_INFORMATION_CLASS,
+            ) != 0
+        };
+
+        if got_info {
+            for ace_index in 0..size_info.AceCount {
+                let mut ace_pointer: *mut c_void = std::ptr::null_mut();
+                if unsafe { GetAce(dacl, ace_index, &mut ace_pointer) } == 0 {
+                    continue;
+                }
+
+                let header = unsafe { &*(ace_pointer as *const ACE_HEADER) };
+                let access_type = match header.AceType {
+                    ACCESS_ALLOWED_ACE_TYPE => "allow",
+                    ACCESS_DENIED_ACE_TYPE => "deny",
+                    _ => continue,
+                };
+
+                // Allowed and denied ACEs share the same layout
+                let ace = unsafe { &*(ace_pointer as *const ACCESS_ALLOWED_ACE) };
+                let sid = &ace.SidStart as *const u32 as *mut c_void;
+                let sid_string = sid_to_string(sid);
+
+                entries.push(AclEntry {
+                    trustee: sid_to_account_name(sid).unwrap_or_else(|| sid_string.clone()),
+                    sid: sid_string,
+                    access_type: access_type.to_string(),
+                    access_mask: ace.Mask,
+                    is_inherited: header.AceFlags & INHERITED_ACE != 0,
+                    object_inherit: header.AceFlags & OBJECT_INHERIT_ACE != 0,
+                    container_inherit: header.AceFlags & CONTAINER_INHERIT_ACE != 0,
+                    inherit_only: header.AceFlags & INHERIT_ONLY_ACE != 0,
+                    no_propagate_inherit: header.AceFlags & NO_PROPAGATE_INHERIT_ACE != 0,
+                });
+            }
+        }
+    }
+
+    unsafe {
+        LocalFree(descriptor);
+    }
+
+    Ok(AclInfo {
+        path: crate::utils::normalize_path(path),
+        owner: owner_name,
+        is_protected: control & SE_DACL_PROTECTED != 0,
+        entries,
+    })
+}
+
+/// Replaces the explicit entries of the DACL. Inherited entries are ignored
+/// in the input; Windows re-applies them from the parent unless
+/// `is_protected` disables inheritance.
+pub fn write_acl(path: &str, entries: &[AclEntry], is_protected: bool) -> Result<(), String> {
+    let explicit_entries: Vec<&AclEntry> = entries.iter().filter(|entry| !entry.is_inherited).collect();
+
+    // Keep SIDs and names alive until SetEntriesInAclW has copied them
+    let mut sids: Vec<*mut c_void> = Vec::new();
+    let mut names: Vec<Vec<u16>> = Vec::new();
+    let mut explicit_access: Vec<EXPLICIT_ACCESS_W> = Vec::new();
+
+    let mut result: Result<(), String> = Ok(());
+
+    for entry in &explicit_entries {
+        let access_mode = match entry.access_type.as_str() {
+            "allow" => GRANT_ACCESS,
+            "deny" => DENY_ACCESS,
+            other => {
+                result = Err(format!("Unknown access type: {}", other));
+                break;
+            }
+        };
+
+        let mut inheritance: u32 = 0;
+        if entry.object_inherit {
+            inheritance |= OBJECT_INHERIT_ACE as u32;
+        }
+        if entry.container_inherit {
+            inheritance |= CONTAINER_INHERIT_ACE as u32;
+        }
+        if entry.inherit_only {
+            inheritance |= INHERIT_ONLY_ACE as u32;
+        }
+        if entry.no_propagate_inherit {
+            inheritance |= NO_PROPAGATE_INHERIT_ACE as u32;
+        }
+
+        let (trustee_form, trustee_name) = if !entry.sid.is_empty() {
+            let wide_sid = to_wide(&entry.sid);
+            let mut sid: *mut c_void = std::ptr::null_mut();
+            if unsafe { ConvertStringSidToSidW(wide_sid.as_ptr(), &mut sid) } == 0 {
+                result = Err(format!("Invalid SID: {}", entry.sid));
+                break;
+            }
+            sids.push(sid);
+            (TRUSTEE_IS_SID, sid as *mut u16)
+        } else {
+            names.push(to_wide(&entry.trustee));
+            let name = names.last_mut().map(|name| name.as_mut_ptr()).unwrap_or(std::ptr::null_mut());
+            (TRUSTEE_IS_NAME, name)
+        };
+
+        explicit_access.push(EXPLICIT_ACCESS_W {
+            grfAccessPermissions: entry.access_mask,
+            grfAccessMode: access_mode,
+            grfInheritance: inheritance,
+            Trustee: TRUSTEE_W {
+                pMultipleTrustee: std::ptr::null_mut(),
+                MultipleTrusteeOperation: NO_MULTIPLE_TRUSTEE,
+                TrusteeForm: trustee_form,
+                TrusteeType: TRUSTEE_IS_UNKNOWN,
+                ptstrName: trustee_name,
+            },
+        });
+    }
+
+    if result.is_ok() {
+        let mut new_acl: *mut ACL = std::ptr::null_mut();
+        let status = unsafe {
+            SetEntriesInAclW(
+                explicit_access.len() as u32,
+                explicit_access.as_ptr(),
+                std::ptr::null(),
+                &mut new_acl,
+            )
+        };
+
+        if status != 0 {
+            result = Err(format!(
+                "Failed to build ACL: {}",
+                std::io::Error::from_raw_os_error(status as i32)
+            ));
+        } else {
+            let wide_path = to_wide(path);
+            let protection_flag = if is_protected {
+                PROTECTED_DACL_SECURITY_INFORMATION
+            } else {
+                UNPROTECTED_DACL_SECURITY_INFORMATION
+            };
+
+            let status = unsafe {
+                SetNamedSecurityInfoW(
+                    wide_path.as_ptr(),
+                    SE_FILE_OBJECT,
+                    DACL_SECURITY_INFORMATION | protection_flag,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    new_acl,
+                    std::ptr::null(),
+                )
+            };
+
+            if status != 0 {
+                result = Err(format!(
+                    "Failed to apply ACL: {}",
+                    std::io::Error::from_raw_os_error(status as i32)
+                ));
+            }
+
+            unsafe {
+                LocalFree(new_acl as *mut c_void);
+            }
+        }
+    }
+
+    for sid in sids {
+        unsafe {
+            LocalFree(sid);
+        }
+    }
+
+    result
+}
@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+#[cfg(windows)]
+pub mod acl;
+#[cfg(unix)]
+pub mod unix;
+
+use crate::file_operations::FileOperationResult;
+use crate::utils::to_os_path;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AclEntry {
+    pub trustee: String,
+    pub sid: String,
+    pub access_type: String,
+    pub access_mask: u32,
+    pub is_inherited: bool,
+    pub object_inherit: bool,
+    pub container_inherit: bool,
+    pub inherit_only: bool,
+    pub no_propagate_inherit: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AclInfo {
+    pub path: String,
+    pub owner: Option<String>,
+    pub is_protected: bool,
+    pub entries: Vec<AclEntry>,
+}
+
+
+// Applies `apply` to the path and, when recursive, to everything below it.
+// Symlinks inside the tree are skipped so changes never escape the tree.
+// Folders come after their contents, so a mode that takes away access to a
+// folder doesn't stop the walk from reaching what is inside it.
+#[cfg(unix)]
+fn apply_to_tree<F>(path: &Path, recursive: bool, apply: F) -> FileOperationResult
+where
+    F: Fn(&Path, bool) -> std::io::Result<()>,
+{
+    let mut changed_count: u32 = 0;
+    let mut failed_count: u32 = 0;
+    let mut last_error: Option<String> = None;
+
+    let max_depth = if recursive { usize::MAX } else { 0 };
+
+    for entry in walkdir::WalkDir::new(path)
+        .follow_links(false)
+        .contents_first(true)
+        .max_depth(max_depth)
+    {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                failed_count += 1;
+                last_error = Some(error.to_string());
+                continue;
+            }
+        };
+
+        if entry.depth() > 0 && entry.path_is_symlink() {
+            continue;
+        }
+
+        match apply(entry.path(), entry.file_type().is_dir()) {
+            Ok(()) => changed_count += 1,
+            Err(error) => {
+                failed_count += 1;
+                last_error = Some(format!("{}: {}", entry.path().display(), error));
+            }
+        }
+    }
+
+    FileOperationResult {
+        error: last_error,
+        ..FileOperationResult::counts(changed_count, failed_count, 0)
+    }
+}
+
+// The mode for folders when none is given: `mode` with search permission
+// added for whoever may read, like chmod's `X`, so 0o644 gives 0o755
+#[cfg(unix)]
+fn searchable_mode(mode: u32) -> u32 {
+    mode | ((mode & 0o444) >> 2)
+}
+
+/// Sets Unix permission bits (e.g. 0o755) on a path, optionally recursively.
+/// Folders get `directory_mode`, or `mode` with `x` added wherever `r` is set,
+/// so that a file mode like 0o644 doesn't lock the user out of the folders.
+#[tauri::command]
+pub async fn set_permissions(
+    path: String,
+    mode: u32,
+    directory_mode: Option<u32>,
+    recursive: bool,
+) -> Result<FileOperationResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let target = to_os_path(&path);
+
+        if !target.exists() {
+            return FileOperationResult::failure(format!("Path does not exist: {}", path));
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = mode & 0o7777;
+            let directory_mode = directory_mode.unwrap_or_else(|| searchable_mode(mode)) & 0o7777;
+            apply_to_tree(&target, recursive, |entry_path, is_dir| {
+                let mode = if is_dir { directory_mode } else { mode };
+                std::fs::set_permissions(entry_path, std::fs::Permissions::from_mode(mode))
+            })
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = (mode, directory_mode, recursive);
+            FileOperationResult::failure(
+                "Unix permissions are not supported on this platform".to_string(),
+            )
+        }
+    })
+    .await
+    .map_err(|error| error.to_string())
+}
+
+/// Changes the owner and/or group of a path. Names or numeric ids are accepted.
+#[tauri::command]
+pub async fn set_owner(
+    path: String,
+    user: Option<String>,
+    group: Option<String>,
+    recursive: Option<bool>,
+) -> Result<FileOperationResult, String> {
+    tokio::task::spawn_blocking(move || change_owner(&path, user, group, recursive))
+        .await
+        .map_err(|error| error.to_string())
+}
+
+fn change_owner(
+    path: &str,
+    user: Option<String>,
+    group: Option<String>,
+    recursive: Option<bool>,
+) -> FileOperationResult {
+    let target = to_os_path(path);
+
+    if !target.exists() {
+        return FileOperationResult::failure(format!("Path does not exist: {}", path));
+    }
+
+    #[cfg(unix)]
+    {
+        let uid = match user.as_deref().filter(|value| !value.is_empty()) {
+            Some(user_value) => match unix::resolve_uid(user_value) {
+                Ok(uid) => Some(uid),
+                Err(error) => return FileOperationResult::failure(error),
+            },
+            None => None,
+        };
+
+        let gid = match group.as_deref().filter(|value| !value.is_empty()) {
+            Some(group_value) => match unix::resolve_gid(group_value) {
+                Ok(gid) => Some(gid),
+                Err(error) => return FileOperationResult::failure(error),
+            },
+            None => None,
+        };
+
+        if uid.is_none() && gid.is_none() {
+            return FileOperationResult::failure(
+                "Either user or group must be specified".to_string(),
+            );
+        }
+
+        apply_to_tree(&target, recursive.unwrap_or(false), |entry_path, _| {
+            std::os::unix::fs::chown(entry_path, uid, gid)
+        })
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (user, group, recursive);
+        FileOperationResult::failure(
+            "Changing ownership is not supported on this platform".to_string(),
+        )
+    }
+}
+
+/// Reads the DACL of a file or directory (Windows only).
+#[tauri::command]
+pub fn get_acl(path: String) -> Result<AclInfo, String> {
+    let target = to_os_path(&path);
+    if !target.exists() {
+        return Err(format!("Path does not exist: {}", path));
+    }
+
+    #[cfg(windows)]
+    {
+        acl::read_acl(&target.to_string_lossy())
+    }
+
+    #[cfg(not(windows))]
+    {
+        Err("ACLs are only supported on Windows".to_string())
+    }
+}
+
+/// Replaces the explicit DACL entries of a file or directory (Windows only).
+/// `is_protected` disables inheritance from the parent when true.
+#[tauri::command]
+pub fn set_acl(path: String, entries: Vec<AclEntry>, is_protected: Option<bool>) -> Result<(), String> {
+    let target = to_os_path(&path);
+    if !target.exists() {
+        return Err(format!("Path does not exist: {}", path));
+    }
+
+    #[cfg(windows)]
+    {
+        acl::write_acl(&target.to_string_lossy(), &entries, is_protected.unwrap_or(false))
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = (entries, is_protected);
+        Err("ACLs are only supported on Windows".to_string())
+    }
+}
@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::sync::Mutex;
+
+// Entries in one directory usually share a handful of owners, so names are
+// cached instead of hitting NSS for every row of a listing.
+static USER_NAME_CACHE: Lazy<Mutex<HashMap<u32, Option<String>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+static GROUP_NAME_CACHE: Lazy<Mutex<HashMap<u32, Option<String>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+const INITIAL_BUFFER_SIZE: usize = 4096;
+const MAX_BUFFER_SIZE: usize = 1024 * 1024;
+
+fn lookup_with_buffer<T, F>(mut lookup: F) -> Option<T>
+where
+    F: FnMut(&mut [libc::c_char]) -> Result<Option<T>, libc::c_int>,
+{
+    let mut buffer_size = INITIAL_BUFFER_SIZE;
+
+    while buffer_size <= MAX_BUFFER_SIZE {
+        let mut buffer = vec![0 as libc::c_char; buffer_size];
+        match lookup(&mut buffer) {
+            Ok(value) => return value,
+            Err(libc::ERANGE) => buffer_size *= 2,
+            Err(_) => return None,
+        }
+    }
+
+    None
+}
+
+fn lookup_user_name(uid: u32) -> Option<String> {
+    lookup_with_buffer(|buffer| {
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::passwd = std::ptr::null_mut();
+        let status = unsafe {
+            libc::getpwuid_r(uid, &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut result)
+        };
+        if status != 0 {
+            return Err(status);
+        }
+        if result.is_null() {
+            return Ok(None);
+        }
+        let name = unsafe { CStr::from_ptr(passwd.pw_name) };
+        Ok(Some(name.to_string_lossy().into_owned()))
+    })
+}
+
+fn lookup_group_name(gid: u32) -> Option<String> {
+    lookup_with_buffer(|buffer| {
+        let mut group: libc::group = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::group = std::ptr::null_mut();
+        let status = unsafe {
+            libc::getgrgid_r(gid, &mut group, buffer.as_mut_ptr(), buffer.len(), &mut result)
+        };
+        if status != 0 {
+            return Err(status);
+        }
+        if result.is_null() {
+            return Ok(None);
+        }
+        let name = unsafe { CStr::from_ptr(group.gr_name) };
+        Ok(Some(name.to_string_lossy().into_owned()))
+    })
+}
+
+/// Returns the user name for `uid`, or the numeric id if it has no entry.
+pub fn user_name(uid: u32) -> String {
+    let cached = USER_NAME_CACHE
+        .lock()
+        .ok()
+        .and_then(|cache| cache.get(&uid).cloned());
+
+    let name = match cached {
+        Some(name) => name,
+        None => {
+            let name = lookup_user_name(uid);
+            if let Ok(mut cache) = USER_NAME_CACHE.lock() {
+                cache.insert(uid, name.clone());
+            }
+            name
+        }
+    };
+
+    name.unwrap_or_else(|| uid.to_string())
+}
+
+/// Returns the group name for `gid`, or the numeric id if it has no entry.
+pub fn group_name(gid: u32) -> String {
+    let cached = GROUP_NAME_CACHE
+        .lock()
+        .ok()
+        .and_then(|cache| cache.get(&gid).cloned());
+
+    let name = match cached {
+        Some(name) => name,
+        None => {
+            let name = lookup_group_name(gid);
+            if let Ok(mut cache) = GROUP_NAME_CACHE.lock() {
+                cache.insert(gid, name.clone());
+            }
+            name
+        }
+    };
+
+    name.unwrap_or_else(|| gid.to_string())
+}
+
+/// Resolves a user name (or numeric uid) to a uid.
+pub fn resolve_uid(user: &str) -> Result<u32, String> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Ok(uid);
+    }
+
+    let c_name = CString::new(user).map_err(|_| format!("Invalid user name: {}", user))?;
+    let uid = lookup_with_buffer(|buffer| {
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::passwd = std::ptr::null_mut();
+        let status = unsafe {
+            libc::getpwnam_r(
+                c_name.as_ptr(),
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        if status != 0 {
+            return Err(status);
+        }
+        if result.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(passwd.pw_uid))
+    });
+
+    uid.ok_or_else(|| format!("User not found: {}", user))
+}
+
+/// Resolves a group name (or numeric gid) to a gid.
+pub fn resolve_gid(group: &str) -> Result<u32, String> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Ok(gid);
+    }
+
+    let c_name = CString::new(group).map_err(|_| format!("Invalid group name: {}", group))?;
+    let gid = lookup_with_buffer(|buffer| {
+        let mut group_entry: libc::group = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::group = std::ptr::null_mut();
+        let status = unsafe {
+            libc::getgrnam_r(
+                c_name.as_ptr(),
+                &mut group_entry,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        if status != 0 {
+            return Err(status);
+        }
+        if result.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(group_entry.gr_gid))
+    });
+
+    gid.ok_or_else(|| format!("Group not found: {}", group))
+}
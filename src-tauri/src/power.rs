@@ -0,0 +1,332 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Battery and metered-connection awareness for background work. Indexing,
+//! thumbnail generation and scheduled backups ask `allowance` before doing
+//! work the user didn't explicitly request.
+
+use crate::json_store;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const STORE_NAME: &str = "power_policy";
+// Detection spawns processes on some platforms, so results are reused
+const STATE_CACHE_TTL: Duration = Duration::from_secs(30);
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const THROTTLE_DELAY: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PowerState {
+    /// `None` on machines without a battery or when it can't be determined
+    pub on_battery: Option<bool>,
+    pub battery_percent: Option<u8>,
+    /// `None` when the platform doesn't report connection cost
+    pub is_metered: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerPolicyMode {
+    /// Pause or throttle based on the detected power and network state
+    Auto,
+    /// Always run background work at full speed
+    Unrestricted,
+    /// Always behave as if on battery and a metered connection
+    Conserve,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerPolicy {
+    pub mode: PowerPolicyMode,
+    pub pause_on_battery: bool,
+    pub pause_on_metered: bool,
+    /// Below this charge, all background work pauses
+    pub low_battery_percent: u8,
+}
+
+impl Default for PowerPolicy {
+    fn default() -> Self {
+        PowerPolicy {
+            mode: PowerPolicyMode::Auto,
+            pause_on_battery: true,
+            pause_on_metered: true,
+            low_battery_percent: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundWork {
+    Indexing,
+    Thumbnails,
+    Backups,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Allowance {
+    Run,
+    Throttle,
+    Pause,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerStatus {
+    pub state: PowerState,
+    pub policy: PowerPolicy,
+    pub indexing: Allowance,
+    pub thumbnails: Allowance,
+    pub backups: Allowance,
+}
+
+static POLICY: Lazy<Mutex<PowerPolicy>> = Lazy::new(|| Mutex::new(json_store::load(STORE_NAME)));
+static STATE_CACHE: Lazy<Mutex<Option<(Instant, PowerState)>>> = Lazy::new(|| Mutex::new(None));
+
+#[cfg(target_os = "linux")]
+fn detect_state() -> PowerState {
+    use std::fs;
+
+    let mut state = PowerState::default();
+
+    if let Ok(supplies) = fs::read_dir("/sys/class/power_supply") {
+        for supply in supplies.filter_map(|supply| supply.ok()) {
+            let path = supply.path();
+            let read = |name: &str| {
+                fs::read_to_string(path.join(name))
+                    .map(|value| value.trim().to_string())
+                    .ok()
+            };
+
+            match read("type").as_deref() {
+                Some("Mains") => {
+                    if let Some(online) = read("online") {
+                        state.on_battery = Some(online != "1");
+                    }
+                }
+                // Peripheral batteries (mice, headsets) report scope "Device"
+                Some("Battery") if read("scope").as_deref() != Some("Device") => {
+                    state.battery_percent = read("capacity").and_then(|value| value.parse().ok());
+
+                    if state.on_battery.is_none() {
+                        state.on_battery = read("status").map(|status| status == "Discharging");
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if state.battery_percent.is_none() {
+        state.on_battery = None;
+    }
+
+    // NetworkManager's Metered property: 1 yes, 3 guessed yes, 2 / 4 no
+    state.is_metered = std::process::Command::new("busctl")
+        .args([
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            let output = String::from_utf8_lossy(&output.stdout);
+            match output.trim().strip_prefix("u ")? {
+                "1" | "3" => Some(true),
+                "2" | "4" => Some(false),
+                _ => None,
+            }
+        });
+
+    state
+}
+
+#[cfg(target_os = "macos")]
+fn detect_state() -> PowerState {
+    let mut state = PowerState::default();
+
+    let Some(output) = std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+    else {
+        return state;
+    };
+
+    let output = String::from_utf8_lossy(&output.stdout);
+
+    // "Now drawing from 'Battery Power'" followed by "... 85%; discharging; ..."
+    state.battery_percent = output
+        .split(|character: char| character.is_whitespace() || character == ';')
+        .find_map(|word| word.strip_suffix('%')?.parse().ok());
+
+    if state.battery_percent.is_some() {
+        state.on_battery = Some(output.contains("'Battery Power'"));
+    }
+
+    state
+}
+
+#[cfg(windows)]
+fn detect_state() -> PowerState {
+    use windows::Networking::Connectivity::{NetworkCostType, NetworkInformation};
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    // BatteryFlag 128 means there is no system battery, 255 unknown
+    const NO_SYSTEM_BATTERY: u8 = 128;
+    const UNKNOWN: u8 = 255;
+
+    let mut state = PowerState::default();
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+
+    if unsafe { GetSystemPowerStatus(&mut status) } != 0
+        && status.BatteryFlag != NO_SYSTEM_BATTERY
+        && status.BatteryFlag != UNKNOWN
+    {
+        state.on_battery = match status.ACLineStatus {
+            0 => Some(true),
+            1 => Some(false),
+            _ => None,
+        };
+        state.battery_percent =
+            (status.BatteryLifePercent != UNKNOWN).then_some(status.BatteryLifePercent);
+    }
+
+    state.is_metered = NetworkInformation::GetInternetConnectionProfile()
+        .and_then(|profile| profile.GetConnectionCost())
+        .and_then(|cost| {
+            Ok(cost.NetworkCostType()? != NetworkCostType::Unrestricted
+                || cost.Roaming()?
+                || cost.OverDataLimit()?)
+        })
+        .ok();
+
+    state
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn detect_state() -> PowerState {
+    PowerState::default()
+}
+
+pub fn power_state() -> PowerState {
+    if let Ok(cache) = STATE_CACHE.lock() {
+        if let Some((detected_at, state)) = cache.as_ref() {
+            if detected_at.elapsed() < STATE_CACHE_TTL {
+                return state.clone();
+            }
+        }
+    }
+
+    let state = detect_state();
+
+    if let Ok(mut cache) = STATE_CACHE.lock() {
+        *cache = Some((Instant::now(), state.clone()));
+    }
+
+    state
+}
+
+fn decide(work: BackgroundWork, state: &PowerState, policy: &PowerPolicy) -> Allowance {
+    let (on_battery, is_metered, is_low_battery) = match policy.mode {
+        PowerPolicyMode::Unrestricted => return Allowance::Run,
+        PowerPolicyMode::Conserve => (true, true, false),
+        PowerPolicyMode::Auto => (
+            state.on_battery == Some(true),
+            state.is_metered == Some(true),
+            state.on_battery == Some(true)
+                && state
+                    .battery_percent
+                    .is_some_and(|percent| percent < policy.low_battery_percent),
+        ),
+    };
+
+    if is_low_battery {
+        return Allowance::Pause;
+    }
+
+    let on_battery = on_battery && policy.pause_on_battery;
+    let is_metered = is_metered && policy.pause_on_metered;
+
+    match work {
+        BackgroundWork::Indexing if on_battery => Allowance::Pause,
+        // Thumbnails are what the user is looking at, so they only slow down
+        BackgroundWork::Thumbnails if on_battery => Allowance::Throttle,
+        BackgroundWork::Backups if on_battery || is_metered => Allowance::Pause,
+        _ => Allowance::Run,
+    }
+}
+
+/// Whether background work of this kind should run now
+pub fn allowance(work: BackgroundWork) -> Allowance {
+    let policy = POLICY
+        .lock()
+        .map(|policy| policy.clone())
+        .unwrap_or_default();
+
+    decide(work, &power_state(), &policy)
+}
+
+/// Blocks while `work` is paused, sleeps briefly when throttled. Returns
+/// false when `cancel_flag` was set while waiting.
+pub fn wait_for_allowance(work: BackgroundWork, cancel_flag: &AtomicBool) -> bool {
+    loop {
+        if cancel_flag.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        match allowance(work) {
+            Allowance::Run => return true,
+            Allowance::Throttle => {
+                std::thread::sleep(THROTTLE_DELAY);
+                return true;
+            }
+            Allowance::Pause => std::thread::sleep(PAUSE_POLL_INTERVAL),
+        }
+    }
+}
+
+fn status(policy: PowerPolicy) -> PowerStatus {
+    let state = power_state();
+
+    PowerStatus {
+        indexing: decide(BackgroundWork::Indexing, &state, &policy),
+        thumbnails: decide(BackgroundWork::Thumbnails, &state, &policy),
+        backups: decide(BackgroundWork::Backups, &state, &policy),
+        state,
+        policy,
+    }
+}
+
+/// Current power and network state with the resulting allowances
+#[tauri::command]
+pub async fn get_power_status() -> Result<PowerStatus, String> {
+    let policy = POLICY.lock().map_err(|error| error.to_string())?.clone();
+
+    tokio::task::spawn_blocking(move || status(policy))
+        .await
+        .map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+pub async fn set_power_policy(policy: PowerPolicy) -> Result<PowerStatus, String> {
+    {
+        let mut current = POLICY.lock().map_err(|error| error.to_string())?;
+        *current = policy.clone();
+        json_store::save(STORE_NAME, &*current)?;
+    }
+
+    tokio::task::spawn_blocking(move || status(policy))
+        .await
+        .map_err(|error| error.to_string())
+}
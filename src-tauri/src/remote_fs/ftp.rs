@@ -0,0 +1,239 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::{ConnectOptions, FtpMode, RemoteBackend, RemoteCredentials, RemoteEntry, RemoteUrl};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Seek, SeekFrom};
+use std::net::ToSocketAddrs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use suppaftp::list::File as ListedFile;
+use suppaftp::types::FileType;
+use suppaftp::{Mode, RustlsConnector, RustlsFtpStream};
+
+const DEFAULT_PORT: u16 = 21;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub struct FtpBackend {
+    stream: RustlsFtpStream,
+    // Cleared after the first MLSD failure, old servers only know LIST
+    supports_mlsd: bool,
+}
+
+fn tls_connector() -> RustlsConnector {
+    let mut root_store = rustls::RootCertStore::empty();
+    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+
+    RustlsConnector::from(Arc::new(config))
+}
+
+fn to_remote_entry(file: &ListedFile) -> RemoteEntry {
+    RemoteEntry {
+        name: file.name().to_string(),
+        is_dir: file.is_directory(),
+        is_symlink: file.is_symlink(),
+        size: file.size() as u64,
+        modified_time: file
+            .modified()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0),
+        accessed_time: 0,
+        mode: None,
+    }
+}
+
+fn parent_and_name(path: &str) -> (&str, &str) {
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None => ("/", path),
+    }
+}
+
+impl FtpBackend {
+    pub fn connect(
+        url: &RemoteUrl,
+        credentials: &RemoteCredentials,
+        options: &ConnectOptions,
+    ) -> Result<Self, String> {
+        let port = url.port.unwrap_or(DEFAULT_PORT);
+        let address = (url.host.as_str(), port)
+            .to_socket_addrs()
+            .map_err(|error| error.to_string())?
+            .next()
+            .ok_or_else(|| format!("Could not resolve host: {}", url.host))?;
+
+        let mut stream = RustlsFtpStream::connect_timeout(address, CONNECT_TIMEOUT)
+            .map_err(|error| error.to_string())?;
+
+        // Explicit FTPS: the control connection is upgraded with AUTH TLS
+        if url.scheme == "ftps" {
+            stream = stream
+                .into_secure(tls_connector(), &url.host)
+                .map_err(|error| error.to_string())?;
+        }
+
+        let username = credentials.username.as_deref().unwrap_or("anonymous");
+        let password = credentials.password.as_deref().unwrap_or("");
+        stream
+            .login(username, password)
+            .map_err(|error| error.to_string())?;
+
+        stream.set_mode(match options.ftp_mode.unwrap_or(FtpMode::Passive) {
+            FtpMode::Passive => Mode::Passive,
+            FtpMode::ExtendedPassive => Mode::ExtendedPassive,
+            FtpMode::Active => Mode::Active,
+        });
+        stream
+            .transfer_type(FileType::Binary)
+            .map_err(|error| error.to_string())?;
+
+        Ok(FtpBackend {
+            stream,
+            supports_mlsd: true,
+        })
+    }
+
+    fn list(&mut self, path: &str) -> Result<Vec<ListedFile>, String> {
+        if self.supports_mlsd {
+            match self.stream.mlsd(Some(path)) {
+                Ok(lines) => {
+                    return Ok(lines
+                        .iter()
+                        .filter_map(|line| ListedFile::from_mlsx_line(line).ok())
+                        .collect())
+                }
+                Err(error) => {
+                    tracing::debug!("MLSD failed, falling back to LIST: {}", error);
+                    self.supports_mlsd = false;
+                }
+            }
+        }
+
+        // LIST output is free-form, the parser handles Unix and DOS styles
+        let lines = self
+            .stream
+            .list(Some(path))
+            .map_err(|error| error.to_string())?;
+
+        Ok(lines
+            .iter()
+            .filter_map(|line| ListedFile::from_str(line).ok())
+            .collect())
+    }
+}
+
+impl RemoteBackend for FtpBackend {
+    fn read_dir(&mut self, path: &str) -> Result<Vec<RemoteEntry>, String> {
+        Ok(self
+            .list(path)?
+            .iter()
+            .filter(|file| file.name() != "." && file.name() != "..")
+            .map(to_remote_entry)
+            .collect())
+    }
+
+    // FTP has no portable stat, so the item is looked up in its parent
+    fn stat(&mut self, path: &str) -> Result<RemoteEntry, String> {
+        let (parent, name) = parent_and_name(path);
+
+        if name.is_empty() {
+            return Ok(RemoteEntry {
+                name: "/".to_string(),
+                is_dir: true,
+                is_symlink: false,
+                size: 0,
+                modified_time: 0,
+                accessed_time: 0,
+                mode: None,
+            });
+        }
+
+        self.read_dir(parent)?
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| "Not found".to_string())
+    }
+
+    fn download_file(
+        &mut self,
+        path: &str,
+        destination: &Path,
+        offset: u64,
+    ) -> Result<u64, String> {
+        let mut local_file = if offset > 0 {
+            self.stream
+                .resume_transfer(offset as usize)
+                .map_err(|error| error.to_string())?;
+            OpenOptions::new().append(true).open(destination)
+        } else {
+            File::create(destination)
+        }
+        .map_err(|error| error.to_string())?;
+
+        let mut data_stream = self
+            .stream
+            .retr_as_stream(path)
+            .map_err(|error| error.to_string())?;
+        let copied = io::copy(&mut data_stream, &mut local_file).map_err(|error| error.to_string());
+
+        self.stream
+            .finalize_retr_stream(data_stream)
+            .map_err(|error| error.to_string())?;
+
+        copied
+    }
+
+    fn upload_file(&mut self, source: &Path, path: &str, offset: u64) -> Result<u64, String> {
+        let mut local_file = File::open(source).map_err(|error| error.to_string())?;
+
+        if offset > 0 {
+            local_file
+                .seek(SeekFrom::Start(offset))
+                .map_err(|error| error.to_string())?;
+            self.stream
+                .append_file(path, &mut local_file)
+                .map_err(|error| error.to_string())
+        } else {
+            self.stream
+                .put_file(path, &mut local_file)
+                .map_err(|error| error.to_string())
+        }
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), String> {
+        match self.stream.mkdir(path) {
+            Ok(()) => Ok(()),
+            Err(_) if self.stat(path).is_ok_and(|entry| entry.is_dir) => Ok(()),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), String> {
+        self.stream.rm(path).map_err(|error| error.to_string())
+    }
+
+    fn remove_dir(&mut self, path: &str) -> Result<(), String> {
+        self.stream.rmdir(path).map_err(|error| error.to_string())
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        self.stream
+            .rename(from, to)
+            .map_err(|error| error.to_string())
+    }
+}
+
+impl Drop for FtpBackend {
+    fn drop(&mut self) {
+        let _ = self.stream.quit();
+    }
+}
@@ -0,0 +1,724 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Browsing and transferring files on servers without mounting them. Remote
+//! items are addressed with virtual paths like `sftp://user@host:22/home/user`
+//! or `davs://cloud.example.com/remote.php/dav/files/user`, which the
+//! frontend passes around the same way as local paths. S3 buckets are
+//! browsed as `s3://<access key>@s3.eu-central-1.amazonaws.com/bucket`.
+
+mod ftp;
+mod s3;
+mod sftp;
+mod webdav;
+
+use crate::dir_reader::{get_mime_type, DirContents, DirEntry};
+use crate::file_operations::FileOperationResult;
+use crate::utils::to_os_path;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A file or directory as reported by a remote server
+#[derive(Debug, Clone)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    pub size: u64,
+    /// Milliseconds since the Unix epoch, 0 when unknown
+    pub modified_time: u64,
+    pub accessed_time: u64,
+    pub mode: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RemoteCredentials {
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub private_key_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FtpMode {
+    Passive,
+    ExtendedPassive,
+    /// The server connects back to this machine, rarely works behind NAT
+    Active,
+}
+
+/// Protocol-specific connection settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectOptions {
+    /// Data connection mode for FTP, passive by default
+    pub ftp_mode: Option<FtpMode>,
+    /// Fingerprint of an unknown SFTP host key the user confirmed, as given
+    /// in the `remote.unknownHostKey` error
+    pub accept_host_key: Option<String>,
+    /// Signing region of S3 endpoints whose host name doesn't tell it,
+    /// `us-east-1` by default
+    pub s3_region: Option<String>,
+}
+
+/// Operations every remote protocol provides. Paths are absolute remote
+/// paths with forward slashes.
+pub(crate) trait RemoteBackend: Send {
+    fn read_dir(&mut self, path: &str) -> Result<Vec<RemoteEntry>, String>;
+    fn stat(&mut self, path: &str) -> Result<RemoteEntry, String>;
+    /// Transfers continue at `offset` when it's non-zero, appending to what
+    /// was transferred before. Returns the number of bytes transferred.
+    fn download_file(&mut self, path: &str, destination: &Path, offset: u64)
+        -> Result<u64, String>;
+    fn upload_file(&mut self, source: &Path, path: &str, offset: u64) -> Result<u64, String>;
+    fn create_dir(&mut self, path: &str) -> Result<(), String>;
+    fn remove_file(&mut self, path: &str) -> Result<(), String>;
+    fn remove_dir(&mut self, path: &str) -> Result<(), String>;
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), String>;
+
+    fn supports_copy(&self) -> bool {
+        false
+    }
+
+    /// Copies within the server without a round trip through this machine
+    fn copy(&mut self, _from: &str, _to: &str) -> Result<(), String> {
+        Err("Copying on the server is not supported by this protocol".to_string())
+    }
+}
+
+pub(crate) type SharedBackend = Arc<Mutex<Box<dyn RemoteBackend>>>;
+
+// Open connections by origin (`scheme://user@host:port`)
+static CONNECTIONS: Lazy<Mutex<HashMap<String, SharedBackend>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A parsed virtual path: `scheme://[user@]host[:port][/path]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteUrl {
+    pub scheme: String,
+    pub username: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+    pub path: String,
+}
+
+impl RemoteUrl {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid remote path: {}", url);
+        let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+
+        let (username, host_port) = match authority.rsplit_once('@') {
+            Some((username, host_port)) => (Some(username.to_string()), host_port),
+            None => (None, authority),
+        };
+
+        // IPv6 hosts are bracketed: [::1]:22
+        let (host, port) = if let Some(bracketed) = host_port.strip_prefix('[') {
+            let (host, after) = bracketed.split_once(']').ok_or_else(invalid)?;
+            (host, after.strip_prefix(':'))
+        } else {
+            match host_port.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (host_port, None),
+            }
+        };
+
+        if host.is_empty() {
+            return Err(invalid());
+        }
+
+        let port = port
+            .filter(|port| !port.is_empty())
+            .map(|port| port.parse::<u16>().map_err(|_| invalid()))
+            .transpose()?;
+
+        let path = path.trim_end_matches('/');
+
+        Ok(RemoteUrl {
+            scheme: scheme.to_lowercase(),
+            username: username.filter(|username| !username.is_empty()),
+            host: host.to_string(),
+            port,
+            path: if path.is_empty() {
+                "/".to_string()
+            } else {
+                path.to_string()
+            },
+        })
+    }
+
+    pub fn origin(&self) -> String {
+        let user = self
+            .username
+            .as_ref()
+            .map(|username| format!("{}@", username))
+            .unwrap_or_default();
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        let port = self
+            .port
+            .map(|port| format!(":{}", port))
+            .unwrap_or_default();
+
+        format!("{}://{}{}{}", self.scheme, user, host, port)
+    }
+
+    /// Virtual path of another remote path on the same server
+    pub fn with_path(&self, path: &str) -> String {
+        format!("{}{}", self.origin(), path)
+    }
+}
+
+pub(crate) fn join_remote_path(parent: &str, name: &str) -> String {
+    format!("{}/{}", parent.trim_end_matches('/'), name)
+}
+
+fn remote_file_name(path: &str) -> Option<&str> {
+    path.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+const REMOTE_SCHEMES: [&str; 9] = [
+    "sftp", "ssh", "webdav", "webdavs", "dav", "davs", "ftp", "ftps", "s3",
+];
+
+/// Whether a path is a virtual path of one of the supported protocols
+pub fn is_remote_path(path: &str) -> bool {
+    path.split_once("://")
+        .is_some_and(|(scheme, _)| REMOTE_SCHEMES.contains(&scheme.to_lowercase().as_str()))
+}
+
+fn connect_backend(
+    url: &RemoteUrl,
+    credentials: &RemoteCredentials,
+    options: &ConnectOptions,
+) -> Result<Box<dyn RemoteBackend>, String> {
+    match url.scheme.as_str() {
+        "ftp" | "ftps" => Ok(Box::new(ftp::FtpBackend::connect(
+            url,
+            credentials,
+            options,
+        )?)),
+        "sftp" | "ssh" => Ok(Box::new(sftp::SftpBackend::connect(
+            url,
+            credentials,
+            options,
+        )?)),
+        "webdav" | "webdavs" | "dav" | "davs" => {
+            Ok(Box::new(webdav::WebDavBackend::connect(url, credentials)?))
+        }
+        "s3" => Ok(Box::new(s3::S3Backend::connect(url, credentials, options)?)),
+        scheme => Err(format!("Unsupported remote protocol: {}", scheme)),
+    }
+}
+
+// Connects with default credentials (SSH agent, default keys, anonymous FTP)
+// when there is no open connection yet
+pub(crate) fn backend_for(url: &RemoteUrl) -> Result<SharedBackend, String> {
+    let origin = url.origin();
+
+    if let Some(backend) = CONNECTIONS
+        .lock()
+        .map_err(|error| error.to_string())?
+        .get(&origin)
+    {
+        return Ok(backend.clone());
+    }
+
+    let credentials = RemoteCredentials {
+        username: url.username.clone(),
+        ..Default::default()
+    };
+    let backend: SharedBackend = Arc::new(Mutex::new(connect_backend(
+        url,
+        &credentials,
+        &ConnectOptions::default(),
+    )?));
+
+    CONNECTIONS
+        .lock()
+        .map_err(|error| error.to_string())?
+        .insert(origin, backend.clone());
+
+    Ok(backend)
+}
+
+fn with_backend<T>(
+    url: &str,
+    operation: impl FnOnce(&mut dyn RemoteBackend, &RemoteUrl) -> Result<T, String>,
+) -> Result<T, String> {
+    let url = RemoteUrl::parse(url)?;
+    let backend = backend_for(&url)?;
+    let mut backend = backend.lock().map_err(|error| error.to_string())?;
+    operation(backend.as_mut(), &url)
+}
+
+fn to_dir_entry(url: &RemoteUrl, parent: &str, entry: RemoteEntry) -> DirEntry {
+    let extension = if entry.is_dir {
+        None
+    } else {
+        Path::new(&entry.name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+    };
+    let is_file = !entry.is_dir;
+
+    DirEntry {
+        path: url.with_path(&join_remote_path(parent, &entry.name)),
+        is_hidden: entry.name.starts_with('.'),
+        mime: if is_file {
+            get_mime_type(&extension)
+        } else {
+            None
+        },
+        ext: extension,
+        name: entry.name,
+        size: if is_file { entry.size } else { 0 },
+        item_count: None,
+        modified_time: entry.modified_time,
+        accessed_time: entry.accessed_time,
+        created_time: 0,
+        is_file,
+        is_dir: entry.is_dir,
+        is_symlink: entry.is_symlink,
+        is_shortcut: false,
+        is_readonly: entry.mode.is_some_and(|mode| mode & 0o200 == 0),
+        allocated_size: None,
+        hard_link_count: None,
+        file_id: None,
+        mode: entry.mode,
+        owner: None,
+        group: None,
+        tags: None,
+        git_ignored: false,
+        git_status: None,
+        indexed_size: None,
+    }
+}
+
+fn download_recursive(
+    backend: &mut dyn RemoteBackend,
+    path: &str,
+    entry: &RemoteEntry,
+    destination: &Path,
+    resume: bool,
+    result: &mut FileOperationResult,
+) {
+    let outcome = if entry.is_dir {
+        fs::create_dir_all(destination)
+            .map_err(|error| error.to_string())
+            .and_then(|_| backend.read_dir(path))
+            .map(|children| {
+                for child in children {
+                    let child_path = join_remote_path(path, &child.name);
+                    let child_destination = destination.join(&child.name);
+                    download_recursive(
+                        backend,
+                        &child_path,
+                        &child,
+                        &child_destination,
+                        resume,
+                        result,
+                    );
+                }
+            })
+    } else {
+        let local_size = fs::metadata(destination)
+            .map(|metadata| metadata.len())
+            .ok();
+
+        match local_size {
+            Some(local_size) if resume && local_size == entry.size => {
+                result.skipped_count = Some(result.skipped_count.unwrap_or(0) + 1);
+                Ok(())
+            }
+            _ => {
+                let offset = local_size
+                    .filter(|local_size| resume && *local_size < entry.size)
+                    .unwrap_or(0);
+
+                backend
+                    .download_file(path, destination, offset)
+                    .map(|_| result.copied_count = Some(result.copied_count.unwrap_or(0) + 1))
+            }
+        }
+    };
+
+    if let Err(error) = outcome {
+        result.failed_count = Some(result.failed_count.unwrap_or(0) + 1);
+        result.error = Some(format!("{}: {}", path, error));
+    }
+}
+
+fn upload_recursive(
+    backend: &mut dyn RemoteBackend,
+    source: &Path,
+    path: &str,
+    resume: bool,
+    result: &mut FileOperationResult,
+) {
+    let outcome = if source.is_dir() {
+        backend.create_dir(path).and_then(|_| {
+            let children = fs::read_dir(source).map_err(|error| error.to_string())?;
+
+            for child in children.filter_map(|child| child.ok()) {
+                let child_path = join_remote_path(path, &child.file_name().to_string_lossy());
+                upload_recursive(backend, &child.path(), &child_path, resume, result);
+            }
+
+            Ok(())
+        })
+    } else {
+        let local_size = fs::metadata(source)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        let remote_size = resume
+            .then(|| backend.stat(path).ok())
+            .flatten()
+            .filter(|entry| !entry.is_dir)
+            .map(|entry| entry.size);
+
+        match remote_size {
+            Some(remote_size) if remote_size == local_size => {
+                result.skipped_count = Some(result.skipped_count.unwrap_or(0) + 1);
+                Ok(())
+            }
+            _ => {
+                let offset = remote_size
+                    .filter(|remote_size| *remote_size < local_size)
+                    .unwrap_or(0);
+
+                backend
+                    .upload_file(source, path, offset)
+                    .map(|_| result.copied_count = Some(result.copied_count.unwrap_or(0) + 1))
+            }
+        }
+    };
+
+    if let Err(error) = outcome {
+        result.failed_count = Some(result.failed_count.unwrap_or(0) + 1);
+        result.error = Some(format!("{}: {}", source.display(), error));
+    }
+}
+
+fn delete_recursive(
+    backend: &mut dyn RemoteBackend,
+    path: &str,
+    entry: &RemoteEntry,
+) -> Result<(), String> {
+    if entry.is_dir && !entry.is_symlink {
+        for child in backend.read_dir(path)? {
+            delete_recursive(backend, &join_remote_path(path, &child.name), &child)?;
+        }
+        backend.remove_dir(path)
+    } else {
+        backend.remove_file(path)
+    }
+}
+
+fn finish_result(mut result: FileOperationResult) -> FileOperationResult {
+    result.success = result.failed_count.unwrap_or(0) == 0;
+    result
+}
+
+fn error_result(error: String) -> FileOperationResult {
+    FileOperationResult {
+        success: false,
+        error: Some(error),
+        ..FileOperationResult::counts(0, 0, 0)
+    }
+}
+
+/// Opens a connection to the server of `url` and keeps it for later calls.
+/// `credential_ref` names credentials stored with `save_share_credentials`.
+/// SFTP servers not known yet fail with `remote.unknownHostKey` until the
+/// user confirms the fingerprint through `options.accept_host_key`.
+/// Returns the origin the connection is registered under.
+#[tauri::command]
+pub async fn remote_connect(
+    url: String,
+    password: Option<String>,
+    credential_ref: Option<String>,
+    private_key_path: Option<String>,
+    options: Option<ConnectOptions>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let url = RemoteUrl::parse(&url)?;
+        let stored = match (&password, &credential_ref) {
+            (None, Some(credential_ref)) => crate::credentials::load(credential_ref)?,
+            _ => None,
+        };
+
+        let credentials = match stored {
+            Some(stored) => RemoteCredentials {
+                username: stored.username.or_else(|| url.username.clone()),
+                password: Some(stored.password),
+                private_key_path,
+            },
+            None => RemoteCredentials {
+                username: url.username.clone(),
+                password,
+                private_key_path,
+            },
+        };
+
+        let backend = connect_backend(&url, &credentials, &options.unwrap_or_default())?;
+        let origin = url.origin();
+
+        CONNECTIONS
+            .lock()
+            .map_err(|error| error.to_string())?
+            .insert(origin.clone(), Arc::new(Mutex::new(backend)));
+
+        Ok(origin)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub fn remote_disconnect(url: String) -> Result<(), String> {
+    let origin = RemoteUrl::parse(&url)?.origin();
+    CONNECTIONS
+        .lock()
+        .map_err(|error| error.to_string())?
+        .remove(&origin);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remote_read_dir(url: String) -> Result<DirContents, String> {
+    tokio::task::spawn_blocking(move || {
+        with_backend(&url, |backend, url| {
+            let mut entries: Vec<DirEntry> = backend
+                .read_dir(&url.path)?
+                .into_iter()
+                .map(|entry| to_dir_entry(url, &url.path, entry))
+                .collect();
+
+            entries.sort_by(|first, second| match (first.is_dir, second.is_dir) {
+                (true, false) => std::cmp::Ordering::Less,
+                (false, true) => std::cmp::Ordering::Greater,
+                _ => crate::text::compare_names(&first.name, &second.name),
+            });
+
+            let dir_count = entries.iter().filter(|entry| entry.is_dir).count();
+            let file_count = entries.len() - dir_count;
+
+            Ok(DirContents {
+                path: url.with_path(&url.path),
+                entries,
+                total_count: dir_count + file_count,
+                dir_count,
+                file_count,
+                git_repository: None,
+            })
+        })
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Downloads remote files and folders into a local directory. Existing
+/// items are skipped unless `overwrite` is set. With `resume`, partially
+/// downloaded files continue where they stopped and complete ones are skipped.
+#[tauri::command]
+pub async fn remote_download(
+    urls: Vec<String>,
+    destination_path: String,
+    overwrite: Option<bool>,
+    resume: Option<bool>,
+) -> Result<FileOperationResult, String> {
+    let overwrite = overwrite.unwrap_or(false);
+    let resume = resume.unwrap_or(false);
+
+    tokio::task::spawn_blocking(move || {
+        let destination = to_os_path(&destination_path);
+
+        if !destination.is_dir() {
+            return Ok(error_result(format!(
+                "Destination is not a directory: {}",
+                destination_path
+            )));
+        }
+
+        let mut result = FileOperationResult::counts(0, 0, 0);
+
+        for url in &urls {
+            let outcome = with_backend(url, |backend, url| {
+                let name = remote_file_name(&url.path)
+                    .ok_or_else(|| format!("Invalid remote path: {}", url.path))?;
+                let target = destination.join(name);
+
+                if target.exists() && !overwrite && !resume {
+                    result.skipped_count = Some(result.skipped_count.unwrap_or(0) + 1);
+                    return Ok(());
+                }
+
+                let entry = backend.stat(&url.path)?;
+                download_recursive(backend, &url.path, &entry, &target, resume, &mut result);
+                Ok(())
+            });
+
+            if let Err(error) = outcome {
+                result.failed_count = Some(result.failed_count.unwrap_or(0) + 1);
+                result.error = Some(format!("{}: {}", url, error));
+            }
+        }
+
+        Ok(finish_result(result))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Uploads local files and folders into a remote directory. Existing items
+/// are skipped unless `overwrite` is set. With `resume`, partially uploaded
+/// files continue where they stopped where the protocol allows it.
+#[tauri::command]
+pub async fn remote_upload(
+    source_paths: Vec<String>,
+    destination_url: String,
+    overwrite: Option<bool>,
+    resume: Option<bool>,
+) -> Result<FileOperationResult, String> {
+    let overwrite = overwrite.unwrap_or(false);
+    let resume = resume.unwrap_or(false);
+
+    tokio::task::spawn_blocking(move || {
+        with_backend(&destination_url, |backend, url| {
+            let mut result = FileOperationResult::counts(0, 0, 0);
+
+            for source_path in &source_paths {
+                let source = to_os_path(source_path);
+                let Some(name) = source.file_name() else {
+                    result.failed_count = Some(result.failed_count.unwrap_or(0) + 1);
+                    result.error = Some(format!("Invalid source path: {}", source_path));
+                    continue;
+                };
+
+                let target = join_remote_path(&url.path, &name.to_string_lossy());
+
+                if !overwrite && !resume && backend.stat(&target).is_ok() {
+                    result.skipped_count = Some(result.skipped_count.unwrap_or(0) + 1);
+                    continue;
+                }
+
+                upload_recursive(backend, &source, &target, resume, &mut result);
+            }
+
+            Ok(finish_result(result))
+        })
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Permanently deletes remote files and folders, remote servers have no trash
+#[tauri::command]
+pub async fn remote_delete(urls: Vec<String>) -> Result<FileOperationResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut result = FileOperationResult::counts(0, 0, 0);
+
+        for url in &urls {
+            let outcome = with_backend(url, |backend, url| {
+                let entry = backend.stat(&url.path)?;
+                delete_recursive(backend, &url.path, &entry)
+            });
+
+            match outcome {
+                Ok(()) => result.copied_count = Some(result.copied_count.unwrap_or(0) + 1),
+                Err(error) => {
+                    result.failed_count = Some(result.failed_count.unwrap_or(0) + 1);
+                    result.error = Some(format!("{}: {}", url, error));
+                }
+            }
+        }
+
+        Ok(finish_result(result))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub async fn remote_rename(url: String, new_name: String) -> Result<FileOperationResult, String> {
+    if new_name.is_empty() || new_name.contains('/') {
+        return Ok(error_result(format!("Invalid name: {}", new_name)));
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let outcome = with_backend(&url, |backend, url| {
+            let parent = url
+                .path
+                .rsplit_once('/')
+                .map(|(parent, _)| parent)
+                .unwrap_or("");
+            let target = join_remote_path(parent, &new_name);
+
+            if backend.stat(&target).is_ok() {
+                return Err(format!("An item named '{}' already exists", new_name));
+            }
+
+            backend.rename(&url.path, &target)
+        });
+
+        Ok(match outcome {
+            Ok(()) => FileOperationResult::counts(1, 0, 0),
+            Err(error) => error_result(error),
+        })
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Copies items to another folder on the same server, without downloading
+/// them. Only protocols with server-side copy (WebDAV) support this.
+#[tauri::command]
+pub async fn remote_copy(
+    urls: Vec<String>,
+    destination_url: String,
+) -> Result<FileOperationResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let destination = RemoteUrl::parse(&destination_url)?;
+        let mut result = FileOperationResult::counts(0, 0, 0);
+
+        for url in &urls {
+            let outcome = with_backend(url, |backend, url| {
+                if url.origin() != destination.origin() {
+                    return Err("Source and destination are on different servers".to_string());
+                }
+
+                let name = remote_file_name(&url.path)
+                    .ok_or_else(|| format!("Invalid remote path: {}", url.path))?;
+                backend.copy(&url.path, &join_remote_path(&destination.path, name))
+            });
+
+            match outcome {
+                Ok(()) => result.copied_count = Some(result.copied_count.unwrap_or(0) + 1),
+                Err(error) => {
+                    result.failed_count = Some(result.failed_count.unwrap_or(0) + 1);
+                    result.error = Some(format!("{}: {}", url, error));
+                }
+            }
+        }
+
+        Ok(finish_result(result))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
@@ -0,0 +1,732 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Amazon S3 and compatible storage, such as MinIO, Backblaze B2, Wasabi
+//! and Cloudflare R2, addressed as `s3://<access key>@<endpoint>/<bucket>/<key>`.
+//! The password is the secret key. The root lists the buckets, folders are
+//! the key prefixes up to a `/`. Requests go over HTTPS with path-style
+//! addressing and are signed with AWS Signature Version 4.
+
+use super::{ConnectOptions, RemoteBackend, RemoteCredentials, RemoteEntry, RemoteUrl};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::blocking::{Body, Client, RequestBuilder, Response};
+use reqwest::header::{HeaderName, CONTENT_LENGTH, LAST_MODIFIED};
+use reqwest::{Method, StatusCode};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_REGION: &str = "us-east-1";
+/// Largest object a single PUT or server-side copy can write
+const MAX_OBJECT_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+// Bodies are sent over TLS without hashing them first
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+// Signature Version 4 encodes everything but the unreserved characters
+const URI_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+pub struct S3Backend {
+    client: Client,
+    endpoint: String,
+    host: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, URI_ENCODE).to_string()
+}
+
+// Slashes separate the segments of a key and are kept
+fn encode_key(key: &str) -> String {
+    key.split('/').map(encode).collect::<Vec<_>>().join("/")
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The bucket and the key inside it of a remote path. The root has no
+/// bucket and a bucket itself has an empty key.
+fn split_path(path: &str) -> (Option<&str>, &str) {
+    let path = path.trim_start_matches('/');
+
+    match path.split_once('/') {
+        Some((bucket, key)) => (Some(bucket), key),
+        None if path.is_empty() => (None, ""),
+        None => (Some(path), ""),
+    }
+}
+
+// `s3.<region>.amazonaws.com` and the same naming of most compatible
+// services, such as Wasabi and Backblaze B2
+fn region_for_host(host: &str) -> String {
+    if host.ends_with(".r2.cloudflarestorage.com") {
+        return "auto".to_string();
+    }
+
+    match host.split('.').collect::<Vec<_>>().as_slice() {
+        ["s3", region, _, _] => region.to_string(),
+        [legacy, "amazonaws", "com"] => legacy
+            .strip_prefix("s3-")
+            .unwrap_or(DEFAULT_REGION)
+            .to_string(),
+        _ => DEFAULT_REGION.to_string(),
+    }
+}
+
+fn status_error(status: StatusCode) -> String {
+    match status.as_u16() {
+        301 => "The bucket is in another region".to_string(),
+        403 => "Access denied".to_string(),
+        404 => "Not found".to_string(),
+        409 => "The bucket already exists or is not empty".to_string(),
+        _ => format!("Server responded with {}", status),
+    }
+}
+
+// Errors come with an XML body explaining them, except for HEAD requests
+fn error_message(xml: &str) -> Option<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut in_message = false;
+
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(element) => in_message = element.local_name().as_ref() == b"Message",
+            Event::Text(text) if in_message => {
+                return text
+                    .unescape()
+                    .ok()
+                    .map(|message| message.trim().to_string())
+            }
+            Event::End(_) => in_message = false,
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+fn check(response: Response) -> Result<Response, String> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().unwrap_or_default();
+    Err(error_message(&body).unwrap_or_else(|| status_error(status)))
+}
+
+fn send(request: RequestBuilder) -> Result<Response, String> {
+    check(request.send().map_err(|error| error.to_string())?)
+}
+
+fn parse_time(value: &str) -> u64 {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|time| time.timestamp_millis().max(0) as u64)
+        .unwrap_or(0)
+}
+
+fn dir_entry(name: &str, modified_time: u64) -> RemoteEntry {
+    RemoteEntry {
+        name: name.to_string(),
+        is_dir: true,
+        is_symlink: false,
+        size: 0,
+        modified_time,
+        accessed_time: 0,
+        mode: None,
+    }
+}
+
+/// An object, or a bucket when listing buckets
+#[derive(Default)]
+struct ListedObject {
+    key: String,
+    size: u64,
+    modified_time: u64,
+}
+
+#[derive(Default)]
+struct Listing {
+    objects: Vec<ListedObject>,
+    /// Folders directly inside the listed prefix, with a trailing `/`
+    prefixes: Vec<String>,
+    next_token: Option<String>,
+}
+
+enum Section {
+    Other,
+    Object,
+    CommonPrefix,
+}
+
+// Reads both ListObjectsV2 and ListBuckets responses
+fn parse_listing(xml: &str) -> Result<Listing, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut listing = Listing::default();
+    let mut section = Section::Other;
+    let mut current_element = String::new();
+
+    loop {
+        match reader.read_event().map_err(|error| error.to_string())? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+
+                match name.as_str() {
+                    "Contents" | "Bucket" => {
+                        section = Section::Object;
+                        listing.objects.push(ListedObject::default());
+                    }
+                    "CommonPrefixes" => section = Section::CommonPrefix,
+                    _ => {}
+                }
+
+                current_element = name;
+            }
+            Event::Text(text) => {
+                let value = text.unescape().map_err(|error| error.to_string())?;
+                let value = value.trim();
+
+                match (&section, current_element.as_str()) {
+                    (Section::CommonPrefix, "Prefix") => listing.prefixes.push(value.to_string()),
+                    (Section::Other, "NextContinuationToken") => {
+                        listing.next_token = Some(value.to_string())
+                    }
+                    (Section::Object, element) => {
+                        let Some(object) = listing.objects.last_mut() else {
+                            continue;
+                        };
+
+                        match element {
+                            "Key" | "Name" => object.key = value.to_string(),
+                            "Size" => object.size = value.parse().unwrap_or(0),
+                            "LastModified" | "CreationDate" => {
+                                object.modified_time = parse_time(value)
+                            }
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(element) => {
+                if matches!(
+                    element.local_name().as_ref(),
+                    b"Contents" | b"Bucket" | b"CommonPrefixes"
+                ) {
+                    section = Section::Other;
+                }
+                current_element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(listing)
+}
+
+impl S3Backend {
+    pub fn connect(
+        url: &RemoteUrl,
+        credentials: &RemoteCredentials,
+        options: &ConnectOptions,
+    ) -> Result<Self, String> {
+        let access_key = credentials
+            .username
+            .clone()
+            .ok_or("S3 needs the access key as the user name")?;
+        let secret_key = credentials
+            .password
+            .clone()
+            .ok_or("S3 needs the secret key as the password")?;
+
+        let host = if url.host.contains(':') {
+            format!("[{}]", url.host)
+        } else {
+            url.host.clone()
+        };
+        // The Host header leaves out the default port, and so must the signature
+        let host = match url.port {
+            Some(port) if port != 443 => format!("{}:{}", host, port),
+            _ => host,
+        };
+
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|error| error.to_string())?;
+
+        let backend = S3Backend {
+            client,
+            endpoint: format!("https://{}", host),
+            host,
+            region: options
+                .s3_region
+                .clone()
+                .unwrap_or_else(|| region_for_host(&url.host)),
+            access_key,
+            secret_key,
+        };
+
+        // Fails early on wrong keys. Listing all buckets needs a permission
+        // keys limited to one bucket don't have.
+        match split_path(&url.path) {
+            (Some(bucket), _) => send(backend.request(Method::HEAD, Some(bucket), "", &[], &[]))?,
+            (None, _) => send(backend.request(Method::GET, None, "", &[], &[]))?,
+        };
+
+        Ok(backend)
+    }
+
+    /// A request signed with Signature Version 4. `headers` are signed
+    /// along with the ones every request has.
+    fn request(
+        &self,
+        method: Method,
+        bucket: Option<&str>,
+        key: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+    ) -> RequestBuilder {
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+
+        let mut canonical_uri = "/".to_string();
+        if let Some(bucket) = bucket {
+            canonical_uri.push_str(&encode(bucket));
+
+            if !key.is_empty() {
+                canonical_uri.push('/');
+                canonical_uri.push_str(&encode_key(key));
+            }
+        }
+
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .map(|(name, value)| (encode(name), encode(value)))
+            .collect();
+        query.sort();
+        let canonical_query = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let mut signed_headers: Vec<(String, String)> = vec![
+            ("host".to_string(), self.host.clone()),
+            (
+                "x-amz-content-sha256".to_string(),
+                UNSIGNED_PAYLOAD.to_string(),
+            ),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+        signed_headers.extend(
+            headers
+                .iter()
+                .map(|(name, value)| (name.to_lowercase(), value.trim().to_string())),
+        );
+        signed_headers.sort();
+
+        let canonical_headers: String = signed_headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect();
+        let signed_header_names = signed_headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method,
+            canonical_uri,
+            canonical_query,
+            canonical_headers,
+            signed_header_names,
+            UNSIGNED_PAYLOAD
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let date_key = hmac(format!("AWS4{}", self.secret_key).as_bytes(), &date);
+        let region_key = hmac(&date_key, &self.region);
+        let signing_key = hmac(&hmac(&region_key, "s3"), "aws4_request");
+        let signature = hex(&hmac(&signing_key, &string_to_sign));
+
+        let mut url = format!("{}{}", self.endpoint, canonical_uri);
+        if !canonical_query.is_empty() {
+            url.push('?');
+            url.push_str(&canonical_query);
+        }
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header(
+                "Authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    self.access_key, scope, signed_header_names, signature
+                ),
+            )
+            .header("x-amz-content-sha256", UNSIGNED_PAYLOAD)
+            .header("x-amz-date", amz_date);
+
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        request
+    }
+
+    fn list_buckets(&self) -> Result<Vec<RemoteEntry>, String> {
+        let xml = send(self.request(Method::GET, None, "", &[], &[]))?
+            .text()
+            .map_err(|error| error.to_string())?;
+
+        Ok(parse_listing(&xml)?
+            .objects
+            .iter()
+            .map(|bucket| dir_entry(&bucket.key, bucket.modified_time))
+            .collect())
+    }
+
+    /// Every key starting with `prefix`, or with `shallow` only the ones
+    /// directly in that folder and the folders next to them
+    fn list(&self, bucket: &str, prefix: &str, shallow: bool) -> Result<Listing, String> {
+        let mut listing = Listing::default();
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if shallow {
+                query.push(("delimiter", "/"));
+            }
+            if let Some(token) = &continuation_token {
+                query.push(("continuation-token", token));
+            }
+
+            let xml = send(self.request(Method::GET, Some(bucket), "", &query, &[]))?
+                .text()
+                .map_err(|error| error.to_string())?;
+            let page = parse_listing(&xml)?;
+
+            listing.objects.extend(page.objects);
+            listing.prefixes.extend(page.prefixes);
+
+            match page.next_token {
+                Some(token) => continuation_token = Some(token),
+                None => return Ok(listing),
+            }
+        }
+    }
+
+    fn stat_entry(&self, path: &str) -> Result<RemoteEntry, String> {
+        let name = super::remote_file_name(path).unwrap_or("/");
+        let (bucket, key) = split_path(path);
+
+        let Some(bucket) = bucket else {
+            return Ok(dir_entry(name, 0));
+        };
+
+        if key.is_empty() {
+            send(self.request(Method::HEAD, Some(bucket), "", &[], &[]))?;
+            return Ok(dir_entry(name, 0));
+        }
+
+        let response = self
+            .request(Method::HEAD, Some(bucket), key, &[], &[])
+            .send()
+            .map_err(|error| error.to_string())?;
+
+        if response.status().is_success() {
+            let header = |name: HeaderName| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+            };
+
+            return Ok(RemoteEntry {
+                name: name.to_string(),
+                is_dir: false,
+                is_symlink: false,
+                size: header(CONTENT_LENGTH)
+                    .and_then(|length| length.parse().ok())
+                    .unwrap_or(0),
+                modified_time: header(LAST_MODIFIED)
+                    .and_then(|time| httpdate::parse_http_date(time).ok())
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|duration| duration.as_millis() as u64)
+                    .unwrap_or(0),
+                accessed_time: 0,
+                mode: None,
+            });
+        }
+
+        if response.status() != StatusCode::NOT_FOUND {
+            return Err(status_error(response.status()));
+        }
+
+        // Folders exist as long as a key starts with their prefix
+        let listing = self.list(bucket, &format!("{}/", key), true)?;
+        if listing.objects.is_empty() && listing.prefixes.is_empty() {
+            return Err("Not found".to_string());
+        }
+
+        Ok(dir_entry(name, 0))
+    }
+
+    /// Source and destination paths of every object `from` stands for, the
+    /// object itself or everything in a folder, folder markers included
+    fn objects_to_copy(&self, from: &str, to: &str) -> Result<Vec<(String, String)>, String> {
+        if !self.stat_entry(from)?.is_dir {
+            return Ok(vec![(from.to_string(), to.to_string())]);
+        }
+
+        let (Some(bucket), key) = split_path(from) else {
+            return Err("The bucket list can't be copied".to_string());
+        };
+        if key.is_empty() {
+            return Err("Buckets can't be copied, only their contents".to_string());
+        }
+
+        let prefix = format!("{}/", key);
+        Ok(self
+            .list(bucket, &prefix, false)?
+            .objects
+            .into_iter()
+            .map(|object| {
+                let relative_key = &object.key[prefix.len()..];
+                (
+                    format!("/{}/{}", bucket, object.key),
+                    format!("{}/{}", to.trim_end_matches('/'), relative_key),
+                )
+            })
+            .collect())
+    }
+
+    fn copy_object(&self, from: &str, to: &str) -> Result<(), String> {
+        let (Some(source_bucket), source_key) = split_path(from) else {
+            return Err(format!("Invalid path: {}", from));
+        };
+        let (Some(bucket), key) = split_path(to) else {
+            return Err(format!("Invalid path: {}", to));
+        };
+
+        let source = format!("/{}/{}", encode(source_bucket), encode_key(source_key));
+        let response = send(self.request(
+            Method::PUT,
+            Some(bucket),
+            key,
+            &[],
+            &[("x-amz-copy-source", &source)],
+        ))?;
+
+        // Copies failing after they started still answer 200
+        let body = response.text().map_err(|error| error.to_string())?;
+        match body.contains("<Error>").then(|| error_message(&body)) {
+            Some(message) => Err(message.unwrap_or_else(|| "Copying failed".to_string())),
+            None => Ok(()),
+        }
+    }
+
+    fn delete_object(&self, path: &str) -> Result<(), String> {
+        let (bucket, key) = split_path(path);
+        send(self.request(Method::DELETE, bucket, key, &[], &[])).map(|_| ())
+    }
+}
+
+impl RemoteBackend for S3Backend {
+    fn read_dir(&mut self, path: &str) -> Result<Vec<RemoteEntry>, String> {
+        let (bucket, key) = split_path(path);
+
+        let Some(bucket) = bucket else {
+            return self.list_buckets();
+        };
+
+        let prefix = if key.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", key.trim_end_matches('/'))
+        };
+        let listing = self.list(bucket, &prefix, true)?;
+
+        let folders = listing.prefixes.iter().filter_map(|folder| {
+            let name = folder.strip_prefix(&prefix)?.trim_end_matches('/');
+            (!name.is_empty()).then(|| dir_entry(name, 0))
+        });
+
+        // The marker object of the folder itself has an empty name
+        let files = listing.objects.iter().filter_map(|object| {
+            let name = object.key.strip_prefix(&prefix)?;
+
+            (!name.is_empty()).then(|| RemoteEntry {
+                name: name.to_string(),
+                is_dir: false,
+                is_symlink: false,
+                size: object.size,
+                modified_time: object.modified_time,
+                accessed_time: 0,
+                mode: None,
+            })
+        });
+
+        Ok(folders.chain(files).collect())
+    }
+
+    fn stat(&mut self, path: &str) -> Result<RemoteEntry, String> {
+        self.stat_entry(path)
+    }
+
+    fn download_file(
+        &mut self,
+        path: &str,
+        destination: &Path,
+        offset: u64,
+    ) -> Result<u64, String> {
+        let (bucket, key) = split_path(path);
+        let mut request = self.request(Method::GET, bucket, key, &[], &[]);
+
+        if offset > 0 {
+            request = request.header("Range", format!("bytes={}-", offset));
+        }
+
+        let mut response = send(request)?;
+
+        let mut local_file = if response.status() == StatusCode::PARTIAL_CONTENT {
+            OpenOptions::new().append(true).open(destination)
+        } else {
+            File::create(destination)
+        }
+        .map_err(|error| error.to_string())?;
+
+        response
+            .copy_to(&mut local_file)
+            .map_err(|error| error.to_string())
+    }
+
+    // Objects are written whole, so uploads always start over. Larger
+    // files would need a multipart upload.
+    fn upload_file(&mut self, source: &Path, path: &str, _offset: u64) -> Result<u64, String> {
+        let local_file = File::open(source).map_err(|error| error.to_string())?;
+        let size = local_file
+            .metadata()
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+
+        if size > MAX_OBJECT_SIZE {
+            return Err("Files over 5 GB can't be uploaded to S3".to_string());
+        }
+
+        let (bucket, key) = split_path(path);
+        send(
+            self.request(Method::PUT, bucket, key, &[], &[])
+                .body(Body::from(local_file)),
+        )?;
+
+        Ok(size)
+    }
+
+    // Folders are kept as empty marker objects named after their prefix
+    fn create_dir(&mut self, path: &str) -> Result<(), String> {
+        let (Some(bucket), key) = split_path(path) else {
+            return Err("Invalid path: /".to_string());
+        };
+
+        if !key.is_empty() {
+            let marker = format!("{}/", key.trim_end_matches('/'));
+            return send(
+                self.request(Method::PUT, Some(bucket), &marker, &[], &[])
+                    .body(""),
+            )
+            .map(|_| ());
+        }
+
+        // Buckets outside the default region have to name theirs
+        let body = if self.region == DEFAULT_REGION {
+            String::new()
+        } else {
+            format!(
+                "<CreateBucketConfiguration><LocationConstraint>{}</LocationConstraint>\
+                 </CreateBucketConfiguration>",
+                self.region
+            )
+        };
+        let response = self
+            .request(Method::PUT, Some(bucket), "", &[], &[])
+            .body(body)
+            .send()
+            .map_err(|error| error.to_string())?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::CONFLICT if self.stat_entry(path).is_ok() => Ok(()),
+            _ => check(response).map(|_| ()),
+        }
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), String> {
+        self.delete_object(path)
+    }
+
+    // Deleting a marker that doesn't exist succeeds too
+    fn remove_dir(&mut self, path: &str) -> Result<(), String> {
+        match split_path(path) {
+            (Some(_), "") => self.delete_object(path),
+            _ => self.delete_object(&format!("{}/", path.trim_end_matches('/'))),
+        }
+    }
+
+    // S3 has no renames, objects are copied and the originals deleted
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        let objects = self.objects_to_copy(from, to)?;
+
+        for (source, destination) in &objects {
+            self.copy_object(source, destination)?;
+        }
+
+        for (source, _) in &objects {
+            self.delete_object(source)?;
+        }
+
+        Ok(())
+    }
+
+    fn supports_copy(&self) -> bool {
+        true
+    }
+
+    fn copy(&mut self, from: &str, to: &str) -> Result<(), String> {
+        for (source, destination) in self.objects_to_copy(from, to)? {
+            self.copy_object(&source, &destination)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,386 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::{ConnectOptions, RemoteBackend, RemoteCredentials, RemoteEntry, RemoteUrl};
+use crate::messages;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use ssh2::{
+    CheckResult, FileStat, HashType, HostKeyType, KnownHostFileKind, KnownHosts, OpenFlags,
+    OpenType, Session, Sftp,
+};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Seek, SeekFrom};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 22;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const SESSION_TIMEOUT_MS: u32 = 30_000;
+
+pub struct SftpBackend {
+    // The session must outlive the SFTP channel
+    _session: Session,
+    sftp: Sftp,
+}
+
+fn default_username() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+}
+
+fn ssh_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".ssh"))
+}
+
+// Keys of hosts the user accepted in the app, kept apart from
+// ~/.ssh/known_hosts so the app never rewrites the user's own file
+fn app_known_hosts_path() -> Result<PathBuf, String> {
+    Ok(crate::json_store::data_dir()?.join("known_hosts"))
+}
+
+fn key_type_name(key_type: HostKeyType) -> &'static str {
+    match key_type {
+        HostKeyType::Rsa => "RSA",
+        HostKeyType::Dss => "DSA",
+        HostKeyType::Ecdsa256 | HostKeyType::Ecdsa384 | HostKeyType::Ecdsa521 => "ECDSA",
+        HostKeyType::Ed25519 => "ED25519",
+        _ => "unknown",
+    }
+}
+
+// The fingerprint as OpenSSH prints it, `SHA256:` and unpadded base64
+fn fingerprint(session: &Session) -> Result<String, String> {
+    let hash = session
+        .host_key_hash(HashType::Sha256)
+        .ok_or_else(|| "Server did not provide a host key".to_string())?;
+    Ok(format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)))
+}
+
+fn known_hosts_entry(host: &str, port: u16) -> String {
+    if port == DEFAULT_PORT {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+fn remember_host_key(
+    session: &Session,
+    host: &str,
+    port: u16,
+    key: &[u8],
+    key_type: HostKeyType,
+) -> Result<(), String> {
+    let path = app_known_hosts_path()?;
+    let mut known_hosts = session.known_hosts().map_err(|error| error.to_string())?;
+
+    if path.exists() {
+        known_hosts
+            .read_file(&path, KnownHostFileKind::OpenSSH)
+            .map_err(|error| error.to_string())?;
+    } else if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+
+    known_hosts
+        .add(&known_hosts_entry(host, port), key, "", key_type.into())
+        .map_err(|error| error.to_string())?;
+    known_hosts
+        .write_file(&path, KnownHostFileKind::OpenSSH)
+        .map_err(|error| error.to_string())
+}
+
+fn read_known_hosts(known_hosts: &mut KnownHosts, path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Ok(());
+    }
+    known_hosts
+        .read_file(path, KnownHostFileKind::OpenSSH)
+        .map(|_| ())
+        .map_err(|error| format!("Failed to read {}: {}", path.display(), error))
+}
+
+// Checks the host key against ~/.ssh/known_hosts and the keys accepted in
+// the app. A host found in neither is refused with its fingerprint, which
+// the user confirms by connecting again with `accept_host_key`; the key is
+// then remembered. Keys that differ from the known one are always refused.
+fn verify_host_key(
+    session: &Session,
+    host: &str,
+    port: u16,
+    accepted_fingerprint: Option<&str>,
+) -> Result<(), String> {
+    let (key, key_type) = session
+        .host_key()
+        .ok_or_else(|| "Server did not provide a host key".to_string())?;
+    let mut known_hosts = session.known_hosts().map_err(|error| error.to_string())?;
+
+    // libssh2 stops at the first line it doesn't understand, such as
+    // `@cert-authority` markers or `sk-*` keys, which OpenSSH files can
+    // have. Hosts listed before that line are still known.
+    if let Some(ssh_dir) = ssh_dir() {
+        if let Err(error) = read_known_hosts(&mut known_hosts, &ssh_dir.join("known_hosts")) {
+            tracing::warn!("Skipping the user's known hosts: {}", error);
+        }
+    }
+    read_known_hosts(&mut known_hosts, &app_known_hosts_path()?)?;
+
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(messages::REMOTE_HOST_KEY_MISMATCH.with("host", host).into()),
+        CheckResult::Failure => Err(format!("Failed to verify the host key of {}", host)),
+        CheckResult::NotFound => {
+            let fingerprint = fingerprint(session)?;
+            if accepted_fingerprint != Some(fingerprint.as_str()) {
+                return Err(messages::REMOTE_UNKNOWN_HOST_KEY
+                    .with("host", host)
+                    .with("keyType", key_type_name(key_type))
+                    .with("fingerprint", fingerprint)
+                    .into());
+            }
+            remember_host_key(session, host, port, key, key_type)
+        }
+    }
+}
+
+fn authenticate(
+    session: &Session,
+    username: &str,
+    credentials: &RemoteCredentials,
+) -> Result<(), String> {
+    if let Some(private_key_path) = &credentials.private_key_path {
+        // The password doubles as the key passphrase
+        return session
+            .userauth_pubkey_file(
+                username,
+                None,
+                Path::new(private_key_path),
+                credentials.password.as_deref(),
+            )
+            .map_err(|error| error.to_string());
+    }
+
+    if let Some(password) = &credentials.password {
+        return session
+            .userauth_password(username, password)
+            .map_err(|error| error.to_string());
+    }
+
+    if session.userauth_agent(username).is_ok() {
+        return Ok(());
+    }
+
+    let default_keys = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+    for key_name in default_keys {
+        let Some(key_path) = ssh_dir().map(|ssh_dir| ssh_dir.join(key_name)) else {
+            break;
+        };
+
+        if key_path.exists()
+            && session
+                .userauth_pubkey_file(username, None, &key_path, None)
+                .is_ok()
+        {
+            return Ok(());
+        }
+    }
+
+    Err("Authentication required: no SSH agent identity or default key was accepted".to_string())
+}
+
+fn to_remote_entry(name: String, stat: &FileStat, is_symlink: bool) -> RemoteEntry {
+    RemoteEntry {
+        name,
+        is_dir: stat.is_dir(),
+        is_symlink,
+        size: stat.size.unwrap_or(0),
+        modified_time: stat.mtime.unwrap_or(0) * 1000,
+        accessed_time: stat.atime.unwrap_or(0) * 1000,
+        mode: stat.perm,
+    }
+}
+
+impl SftpBackend {
+    pub fn connect(
+        url: &RemoteUrl,
+        credentials: &RemoteCredentials,
+        options: &ConnectOptions,
+    ) -> Result<Self, String> {
+        let port = url.port.unwrap_or(DEFAULT_PORT);
+        let username = credentials
+            .username
+            .clone()
+            .or_else(default_username)
+            .ok_or_else(|| "A username is required for SFTP".to_string())?;
+
+        let address = (url.host.as_str(), port)
+            .to_socket_addrs()
+            .map_err(|error| error.to_string())?
+            .next()
+            .ok_or_else(|| format!("Could not resolve host: {}", url.host))?;
+        let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+            .map_err(|error| error.to_string())?;
+
+        let mut session = Session::new().map_err(|error| error.to_string())?;
+        session.set_tcp_stream(stream);
+        session.set_timeout(SESSION_TIMEOUT_MS);
+        session.handshake().map_err(|error| error.to_string())?;
+
+        verify_host_key(
+            &session,
+            &url.host,
+            port,
+            options.accept_host_key.as_deref(),
+        )?;
+        authenticate(&session, &username, credentials)?;
+
+        if !session.authenticated() {
+            return Err("Authentication failed".to_string());
+        }
+
+        let sftp = session.sftp().map_err(|error| error.to_string())?;
+
+        Ok(SftpBackend {
+            _session: session,
+            sftp,
+        })
+    }
+
+    // Symlinks are reported with the type of their target, falling back to
+    // the link itself when it's dangling
+    fn stat_entry(&self, path: &Path, name: String) -> Result<RemoteEntry, String> {
+        let link_stat = self.sftp.lstat(path).map_err(|error| error.to_string())?;
+        let is_symlink = link_stat.file_type().is_symlink();
+        let stat = if is_symlink {
+            self.sftp.stat(path).unwrap_or(link_stat)
+        } else {
+            link_stat
+        };
+
+        Ok(to_remote_entry(name, &stat, is_symlink))
+    }
+}
+
+impl RemoteBackend for SftpBackend {
+    fn read_dir(&mut self, path: &str) -> Result<Vec<RemoteEntry>, String> {
+        let entries = self
+            .sftp
+            .readdir(Path::new(path))
+            .map_err(|error| error.to_string())?;
+
+        Ok(entries
+            .into_iter()
+            .filter_map(|(entry_path, stat)| {
+                let name = entry_path.file_name()?.to_string_lossy().to_string();
+
+                if stat.file_type().is_symlink() {
+                    self.stat_entry(&entry_path, name).ok()
+                } else {
+                    Some(to_remote_entry(name, &stat, false))
+                }
+            })
+            .collect())
+    }
+
+    fn stat(&mut self, path: &str) -> Result<RemoteEntry, String> {
+        let name = super::remote_file_name(path).unwrap_or("/").to_string();
+        self.stat_entry(Path::new(path), name)
+    }
+
+    fn download_file(
+        &mut self,
+        path: &str,
+        destination: &Path,
+        offset: u64,
+    ) -> Result<u64, String> {
+        let mut remote_file = self
+            .sftp
+            .open(Path::new(path))
+            .map_err(|error| error.to_string())?;
+        let mut local_file = if offset > 0 {
+            remote_file
+                .seek(SeekFrom::Start(offset))
+                .map_err(|error| error.to_string())?;
+            OpenOptions::new().append(true).open(destination)
+        } else {
+            File::create(destination)
+        }
+        .map_err(|error| error.to_string())?;
+
+        io::copy(&mut remote_file, &mut local_file).map_err(|error| error.to_string())
+    }
+
+    fn upload_file(&mut self, source: &Path, path: &str, offset: u64) -> Result<u64, String> {
+        let mut local_file = File::open(source).map_err(|error| error.to_string())?;
+        let mode = local_file
+            .metadata()
+            .ok()
+            .and_then(|metadata| {
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    Some(metadata.permissions().mode() as i32 & 0o777)
+                }
+                #[cfg(not(unix))]
+                {
+                    let _ = metadata;
+                    None
+                }
+            })
+            .unwrap_or(0o644);
+
+        let flags = if offset > 0 {
+            OpenFlags::WRITE
+        } else {
+            OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE
+        };
+        let mut remote_file = self
+            .sftp
+            .open_mode(Path::new(path), flags, mode, OpenType::File)
+            .map_err(|error| error.to_string())?;
+
+        if offset > 0 {
+            local_file
+                .seek(SeekFrom::Start(offset))
+                .map_err(|error| error.to_string())?;
+            remote_file
+                .seek(SeekFrom::Start(offset))
+                .map_err(|error| error.to_string())?;
+        }
+
+        io::copy(&mut local_file, &mut remote_file).map_err(|error| error.to_string())
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), String> {
+        match self.sftp.mkdir(Path::new(path), 0o755) {
+            Ok(()) => Ok(()),
+            Err(_) if self.stat(path).is_ok_and(|entry| entry.is_dir) => Ok(()),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), String> {
+        self.sftp
+            .unlink(Path::new(path))
+            .map_err(|error| error.to_string())
+    }
+
+    fn remove_dir(&mut self, path: &str) -> Result<(), String> {
+        self.sftp
+            .rmdir(Path::new(path))
+            .map_err(|error| error.to_string())
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        self.sftp
+            .rename(Path::new(from), Path::new(to), None)
+            .map_err(|error| error.to_string())
+    }
+}
@@ -0,0 +1,424 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::{RemoteBackend, RemoteCredentials, RemoteEntry, RemoteUrl};
+use percent_encoding::percent_decode_str;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::blocking::{Body, Client, RequestBuilder, Response};
+use reqwest::{Method, StatusCode};
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::Url;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+const LOCK_TIMEOUT_SECONDS: u32 = 300;
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+    <d:resourcetype/>
+    <d:getcontentlength/>
+    <d:getlastmodified/>
+  </d:prop>
+</d:propfind>"#;
+
+const LOCK_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:lockinfo xmlns:d="DAV:">
+  <d:lockscope><d:exclusive/></d:lockscope>
+  <d:locktype><d:write/></d:locktype>
+</d:lockinfo>"#;
+
+pub struct WebDavBackend {
+    client: Client,
+    base_url: Url,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+fn method(name: &str) -> Method {
+    Method::from_bytes(name.as_bytes()).unwrap_or(Method::GET)
+}
+
+fn status_error(status: StatusCode) -> String {
+    match status.as_u16() {
+        401 => "Authentication failed".to_string(),
+        403 => "Access denied".to_string(),
+        404 => "Not found".to_string(),
+        409 => "Parent folder does not exist".to_string(),
+        412 => "Destination already exists".to_string(),
+        423 => "The item is locked by another client".to_string(),
+        507 => "Not enough storage space on the server".to_string(),
+        _ => format!("Server responded with {}", status),
+    }
+}
+
+fn check(response: Response) -> Result<Response, String> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(status_error(response.status()))
+    }
+}
+
+struct PropfindEntry {
+    href: String,
+    is_dir: bool,
+    size: u64,
+    modified_time: u64,
+}
+
+// Elements are matched by local name, servers use different prefixes for
+// the DAV: namespace
+fn parse_multistatus(xml: &str) -> Result<Vec<PropfindEntry>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut entries = Vec::new();
+    let mut current: Option<PropfindEntry> = None;
+    let mut current_element = String::new();
+
+    loop {
+        match reader.read_event().map_err(|error| error.to_string())? {
+            Event::Start(element) | Event::Empty(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_lowercase();
+
+                match name.as_str() {
+                    "response" => {
+                        current = Some(PropfindEntry {
+                            href: String::new(),
+                            is_dir: false,
+                            size: 0,
+                            modified_time: 0,
+                        })
+                    }
+                    "collection" => {
+                        if let Some(entry) = current.as_mut() {
+                            entry.is_dir = true;
+                        }
+                    }
+                    _ => {}
+                }
+
+                current_element = name;
+            }
+            Event::Text(text) => {
+                let Some(entry) = current.as_mut() else {
+                    continue;
+                };
+                let value = text.unescape().map_err(|error| error.to_string())?;
+                let value = value.trim();
+
+                match current_element.as_str() {
+                    "href" => entry.href = value.to_string(),
+                    "getcontentlength" => entry.size = value.parse().unwrap_or(0),
+                    "getlastmodified" => {
+                        entry.modified_time = httpdate::parse_http_date(value)
+                            .ok()
+                            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                            .map(|duration| duration.as_millis() as u64)
+                            .unwrap_or(0)
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(element) => {
+                if element
+                    .local_name()
+                    .as_ref()
+                    .eq_ignore_ascii_case(b"response")
+                {
+                    if let Some(entry) = current.take() {
+                        entries.push(entry);
+                    }
+                }
+                current_element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(entries)
+}
+
+// Hrefs are either absolute URLs or absolute paths, always percent-encoded
+fn href_path(href: &str) -> String {
+    let path = Url::parse(href)
+        .map(|url| url.path().to_string())
+        .unwrap_or_else(|_| href.to_string());
+
+    percent_decode_str(&path).decode_utf8_lossy().to_string()
+}
+
+impl WebDavBackend {
+    pub fn connect(url: &RemoteUrl, credentials: &RemoteCredentials) -> Result<Self, String> {
+        let http_scheme = match url.scheme.as_str() {
+            "webdav" | "dav" => "http",
+            _ => "https",
+        };
+        let host = if url.host.contains(':') {
+            format!("[{}]", url.host)
+        } else {
+            url.host.clone()
+        };
+        let port = url
+            .port
+            .map(|port| format!(":{}", port))
+            .unwrap_or_default();
+        let base_url = Url::parse(&format!("{}://{}{}/", http_scheme, host, port))
+            .map_err(|error| error.to_string())?;
+
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|error| error.to_string())?;
+
+        let backend = WebDavBackend {
+            client,
+            base_url,
+            username: credentials.username.clone(),
+            password: credentials.password.clone(),
+        };
+
+        // Fails early on wrong credentials or a path that isn't WebDAV
+        backend.stat_entry(&url.path)?;
+
+        Ok(backend)
+    }
+
+    fn url_for(&self, path: &str, is_collection: bool) -> Url {
+        let mut url = self.base_url.clone();
+
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.clear();
+            segments.extend(path.split('/').filter(|segment| !segment.is_empty()));
+
+            if is_collection {
+                segments.push("");
+            }
+        }
+
+        url
+    }
+
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        let request = self.client.request(method, url);
+
+        match &self.username {
+            Some(username) => request.basic_auth(username, self.password.as_deref()),
+            None => request,
+        }
+    }
+
+    fn propfind(&self, path: &str, depth: &str) -> Result<Vec<PropfindEntry>, String> {
+        let response = self
+            .request(method("PROPFIND"), self.url_for(path, depth != "0"))
+            .header("Depth", depth)
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY)
+            .send()
+            .map_err(|error| error.to_string())?;
+        let xml = check(response)?.text().map_err(|error| error.to_string())?;
+
+        parse_multistatus(&xml)
+    }
+
+    // Servers without lock support (class 1) answer 405 or 501, writes then
+    // go ahead unlocked
+    fn lock(&self, path: &str) -> Result<Option<String>, String> {
+        let response = self
+            .request(method("LOCK"), self.url_for(path, false))
+            .header("Timeout", format!("Second-{}", LOCK_TIMEOUT_SECONDS))
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(LOCK_BODY)
+            .send()
+            .map_err(|error| error.to_string())?;
+
+        match response.status().as_u16() {
+            200 | 201 => Ok(response
+                .headers()
+                .get("Lock-Token")
+                .and_then(|token| token.to_str().ok())
+                .map(|token| token.trim().to_string())),
+            405 | 501 => Ok(None),
+            _ => Err(status_error(response.status())),
+        }
+    }
+
+    fn unlock(&self, path: &str, lock_token: &str) {
+        let result = self
+            .request(method("UNLOCK"), self.url_for(path, false))
+            .header("Lock-Token", lock_token)
+            .send();
+
+        if let Err(error) = result {
+            tracing::warn!("Failed to unlock {}: {}", path, error);
+        }
+    }
+
+    fn transfer(&self, method_name: &str, from: &str, to: &str) -> Result<(), String> {
+        let is_dir = self.stat_entry(from)?.is_dir;
+        let response = self
+            .request(method(method_name), self.url_for(from, is_dir))
+            .header("Destination", self.url_for(to, is_dir).as_str())
+            .header("Overwrite", "F")
+            .header("Depth", "infinity")
+            .send()
+            .map_err(|error| error.to_string())?;
+
+        check(response).map(|_| ())
+    }
+
+    fn stat_entry(&self, path: &str) -> Result<RemoteEntry, String> {
+        let entry = self
+            .propfind(path, "0")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Not found".to_string())?;
+
+        Ok(RemoteEntry {
+            name: super::remote_file_name(path).unwrap_or("/").to_string(),
+            is_dir: entry.is_dir,
+            is_symlink: false,
+            size: entry.size,
+            modified_time: entry.modified_time,
+            accessed_time: 0,
+            mode: None,
+        })
+    }
+}
+
+impl RemoteBackend for WebDavBackend {
+    fn read_dir(&mut self, path: &str) -> Result<Vec<RemoteEntry>, String> {
+        let own_path = path.trim_end_matches('/');
+
+        Ok(self
+            .propfind(path, "1")?
+            .into_iter()
+            .filter_map(|entry| {
+                let entry_path = href_path(&entry.href);
+                let entry_path = entry_path.trim_end_matches('/');
+
+                // The collection itself is part of the response
+                if entry_path == own_path {
+                    return None;
+                }
+
+                Some(RemoteEntry {
+                    name: super::remote_file_name(entry_path)?.to_string(),
+                    is_dir: entry.is_dir,
+                    is_symlink: false,
+                    size: entry.size,
+                    modified_time: entry.modified_time,
+                    accessed_time: 0,
+                    mode: None,
+                })
+            })
+            .collect())
+    }
+
+    fn stat(&mut self, path: &str) -> Result<RemoteEntry, String> {
+        self.stat_entry(path)
+    }
+
+    fn download_file(
+        &mut self,
+        path: &str,
+        destination: &Path,
+        offset: u64,
+    ) -> Result<u64, String> {
+        let mut request = self.request(Method::GET, self.url_for(path, false));
+
+        if offset > 0 {
+            request = request.header("Range", format!("bytes={}-", offset));
+        }
+
+        let mut response = check(request.send().map_err(|error| error.to_string())?)?;
+
+        // Servers ignoring the range send the whole file
+        let mut local_file = if response.status() == StatusCode::PARTIAL_CONTENT {
+            OpenOptions::new().append(true).open(destination)
+        } else {
+            File::create(destination)
+        }
+        .map_err(|error| error.to_string())?;
+
+        response
+            .copy_to(&mut local_file)
+            .map_err(|error| error.to_string())
+    }
+
+    // PUT has no standard way to continue an upload, so it always starts over
+    fn upload_file(&mut self, source: &Path, path: &str, _offset: u64) -> Result<u64, String> {
+        let local_file = File::open(source).map_err(|error| error.to_string())?;
+        let size = local_file
+            .metadata()
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+
+        let lock_token = self.lock(path)?;
+        let mut request = self
+            .request(Method::PUT, self.url_for(path, false))
+            .body(Body::from(local_file));
+
+        if let Some(lock_token) = &lock_token {
+            request = request.header("If", format!("({})", lock_token));
+        }
+
+        let result = request
+            .send()
+            .map_err(|error| error.to_string())
+            .and_then(check);
+
+        if let Some(lock_token) = &lock_token {
+            self.unlock(path, lock_token);
+        }
+
+        result.map(|_| size)
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), String> {
+        let response = self
+            .request(method("MKCOL"), self.url_for(path, true))
+            .send()
+            .map_err(|error| error.to_string())?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            // 405 means something already exists at the path
+            StatusCode::METHOD_NOT_ALLOWED if self.stat_entry(path)?.is_dir => Ok(()),
+            status => Err(status_error(status)),
+        }
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), String> {
+        let response = self
+            .request(Method::DELETE, self.url_for(path, false))
+            .send()
+            .map_err(|error| error.to_string())?;
+
+        check(response).map(|_| ())
+    }
+
+    fn remove_dir(&mut self, path: &str) -> Result<(), String> {
+        let response = self
+            .request(Method::DELETE, self.url_for(path, true))
+            .send()
+            .map_err(|error| error.to_string())?;
+
+        check(response).map(|_| ())
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        self.transfer("MOVE", from, to)
+    }
+
+    fn supports_copy(&self) -> bool {
+        true
+    }
+
+    fn copy(&mut self, from: &str, to: &str) -> Result<(), String> {
+        self.transfer("COPY", from, to)
+    }
+}
@@ -0,0 +1,146 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Cancellation and progress events of long-running commands. A command
+//! registers the `request_id` the frontend gave it for as long as it runs,
+//! which lets the matching `cancel_*` command stop it, and sends progress
+//! through a `ProgressEmitter`, which leaves out events that come too soon
+//! after the previous one.
+
+use crate::app_windows::emit_to_owner;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+static ACTIVE_REQUESTS: Lazy<Mutex<HashMap<String, Arc<AtomicBool>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The cancel token of a running command, registered under its request id
+/// until dropped. Commands without a request id get a token nothing sets.
+pub struct ActiveRequest {
+    request_id: Option<String>,
+    cancel_token: Arc<AtomicBool>,
+}
+
+impl ActiveRequest {
+    pub fn register(request_id: Option<&str>) -> Self {
+        let cancel_token = Arc::new(AtomicBool::new(false));
+
+        if let (Some(request_id), Ok(mut active_requests)) = (request_id, ACTIVE_REQUESTS.lock()) {
+            active_requests.insert(request_id.to_string(), cancel_token.clone());
+        }
+
+        ActiveRequest {
+            request_id: request_id.map(str::to_string),
+            cancel_token,
+        }
+    }
+
+    /// The token to hand to the blocking task
+    pub fn cancel_token(&self) -> Arc<AtomicBool> {
+        self.cancel_token.clone()
+    }
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        if let (Some(request_id), Ok(mut active_requests)) =
+            (&self.request_id, ACTIVE_REQUESTS.lock())
+        {
+            active_requests.remove(request_id);
+        }
+    }
+}
+
+/// Stops the command running under `request_id`, if there is one
+pub fn cancel(request_id: &str) {
+    if let Ok(active_requests) = ACTIVE_REQUESTS.lock() {
+        if let Some(cancel_token) = active_requests.get(request_id) {
+            cancel_token.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Lets through one call per interval. Usable from several threads, a call
+/// while another thread holds it counts as too soon.
+pub struct Throttle {
+    interval: Duration,
+    last: Mutex<Instant>,
+}
+
+impl Throttle {
+    pub fn new(interval: Duration) -> Self {
+        Throttle {
+            interval,
+            last: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Whether the interval has passed since the last time this returned
+    /// true, or `force` is set
+    pub fn ready(&self, force: bool) -> bool {
+        let Ok(mut last) = self.last.try_lock() else {
+            return force;
+        };
+
+        if !force && last.elapsed() < self.interval {
+            return false;
+        }
+
+        *last = Instant::now();
+        true
+    }
+}
+
+/// Sends the progress events of one request to the window that started it.
+/// Nothing is sent without a request id, as nobody would be listening.
+pub struct ProgressEmitter<'a> {
+    app: &'a tauri::AppHandle,
+    owner_window: Option<&'a str>,
+    event: &'static str,
+    request_id: Option<&'a str>,
+    throttle: Throttle,
+}
+
+impl<'a> ProgressEmitter<'a> {
+    pub fn new(
+        app: &'a tauri::AppHandle,
+        owner_window: Option<&'a str>,
+        event: &'static str,
+        request_id: Option<&'a str>,
+    ) -> Self {
+        ProgressEmitter {
+            app,
+            owner_window,
+            event,
+            request_id,
+            throttle: Throttle::new(PROGRESS_INTERVAL),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.throttle = Throttle::new(interval);
+        self
+    }
+
+    /// Emits the payload built from the request id, unless the previous
+    /// event went out less than the interval ago. `force` is for events
+    /// that must not be dropped, such as final totals or found items.
+    pub fn emit<S: Serialize + Clone>(&self, force: bool, payload: impl FnOnce(&str) -> S) {
+        let Some(request_id) = self.request_id else {
+            return;
+        };
+
+        if !self.throttle.ready(force) {
+            return;
+        }
+
+        emit_to_owner(self.app, self.owner_window, self.event, payload(request_id));
+    }
+}
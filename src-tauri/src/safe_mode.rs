@@ -0,0 +1,266 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Startup checks of everything the app persists. A store that can't be
+//! read is moved aside under a timestamped name and the app starts with its
+//! defaults in safe mode, so a damaged file never keeps the app from
+//! launching and is never overwritten by the next save.
+//!
+//! Store IDs are the backend store names (`tags`, `bookmarks`, ...), the
+//! frontend user data files (`user-settings`, `workspaces`, `user-stats`)
+//! and `search-index`.
+
+use crate::{global_search, json_store};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const USER_DATA_DIR: &str = "user-data";
+const USER_DATA_STORES: [(&str, &str); 3] = [
+    ("user-settings", "user-settings.json"),
+    ("workspaces", "workspaces.json"),
+    ("user-stats", "user-stats.json"),
+];
+const SEARCH_INDEX_STORE: &str = "search-index";
+// Backups skip files with this suffix, see `app_data`
+const QUARANTINE_SUFFIX: &str = "corrupt";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreIssue {
+    pub store_id: String,
+    pub path: String,
+    pub error: String,
+    /// Where the damaged file was moved, when it could be moved
+    pub backup_path: Option<String>,
+    pub detected_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafeModeStatus {
+    /// Set when any store was reset during this session
+    pub is_active: bool,
+    pub issues: Vec<StoreIssue>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreRepairResult {
+    pub store_id: String,
+    pub was_damaged: bool,
+    pub backup_path: Option<String>,
+}
+
+enum StoreLocation {
+    JsonFile(PathBuf),
+    SearchIndex(PathBuf),
+}
+
+static ISSUES: Lazy<Mutex<Vec<StoreIssue>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn path_string(path: &Path) -> String {
+    crate::utils::normalize_path(&path.to_string_lossy())
+}
+
+/// Moves a damaged file or folder aside as `<name>.<timestamp>.corrupt`
+pub fn quarantine(path: &Path) -> Result<PathBuf, String> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Invalid store path: {}", path.display()))?;
+    let backup_path = path.with_file_name(format!("{}.{}.{}", name, now_ms(), QUARANTINE_SUFFIX));
+
+    fs::rename(path, &backup_path).map_err(|error| error.to_string())?;
+    Ok(backup_path)
+}
+
+/// Records a store that was found damaged, replacing an earlier record of it
+pub fn record_issue(store_id: &str, path: &Path, error: &str, backup_path: Option<&Path>) {
+    tracing::warn!(
+        store = store_id,
+        path = %path.display(),
+        backup = ?backup_path,
+        "Store is damaged, starting it from defaults: {}",
+        error
+    );
+
+    if let Ok(mut issues) = ISSUES.lock() {
+        issues.retain(|issue| issue.store_id != store_id);
+        issues.push(StoreIssue {
+            store_id: store_id.to_string(),
+            path: path_string(path),
+            error: error.to_string(),
+            backup_path: backup_path.map(path_string),
+            detected_at: now_ms(),
+        });
+    }
+}
+
+fn store_ids() -> Vec<String> {
+    let mut store_ids: Vec<String> = json_store::stores_dir()
+        .ok()
+        .and_then(|stores_dir| fs::read_dir(stores_dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_suffix(".json").map(str::to_string)
+        })
+        .collect();
+    store_ids.sort();
+
+    store_ids.extend(USER_DATA_STORES.iter().map(|(id, _)| id.to_string()));
+    store_ids.push(SEARCH_INDEX_STORE.to_string());
+    store_ids
+}
+
+fn locate(store_id: &str) -> Result<StoreLocation, String> {
+    let data_dir = json_store::data_dir()?;
+
+    if store_id == SEARCH_INDEX_STORE {
+        return Ok(StoreLocation::SearchIndex(data_dir));
+    }
+
+    if let Some((_, file_name)) = USER_DATA_STORES.iter().find(|(id, _)| *id == store_id) {
+        return Ok(StoreLocation::JsonFile(
+            data_dir.join(USER_DATA_DIR).join(file_name),
+        ));
+    }
+
+    // Store names map to file names, so they can't be allowed to leave the folder
+    let is_store_name = !store_id.is_empty()
+        && store_id
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_');
+
+    if !is_store_name {
+        return Err(format!("Unknown store: {}", store_id));
+    }
+
+    Ok(StoreLocation::JsonFile(json_store::store_path(store_id)?))
+}
+
+fn location_path(location: &StoreLocation) -> PathBuf {
+    match location {
+        StoreLocation::JsonFile(path) => path.clone(),
+        StoreLocation::SearchIndex(data_dir) => global_search::index_dir(data_dir),
+    }
+}
+
+/// Why a store can't be read, or `None` when it is fine or doesn't exist yet
+fn check(location: &StoreLocation) -> Option<String> {
+    match location {
+        StoreLocation::JsonFile(path) => {
+            let bytes = match fs::read(path) {
+                Ok(bytes) => bytes,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => return None,
+                Err(error) => return Some(error.to_string()),
+            };
+
+            serde_json::from_slice::<serde_json::Value>(&bytes)
+                .err()
+                .map(|error| error.to_string())
+        }
+        StoreLocation::SearchIndex(data_dir) => global_search::index_open_error(data_dir),
+    }
+}
+
+/// Moves a damaged store aside, leaving the app to recreate it
+fn set_aside(location: &StoreLocation) -> Result<Option<PathBuf>, String> {
+    let path = location_path(location);
+
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let backup_path = quarantine(&path)?;
+
+    // The index status describes the index that was just moved
+    if let StoreLocation::SearchIndex(data_dir) = location {
+        let _ = fs::remove_file(global_search::meta_file(data_dir));
+    }
+
+    Ok(Some(backup_path))
+}
+
+/// Checks every store before anything loads them
+pub fn init() {
+    for store_id in store_ids() {
+        let Ok(location) = locate(&store_id) else {
+            continue;
+        };
+        let Some(error) = check(&location) else {
+            continue;
+        };
+
+        let path = location_path(&location);
+        let backup_path = set_aside(&location).unwrap_or_else(|quarantine_error| {
+            tracing::error!(
+                store = %store_id,
+                "Failed to move the damaged store aside: {}",
+                quarantine_error
+            );
+            None
+        });
+
+        record_issue(&store_id, &path, &error, backup_path.as_deref());
+    }
+
+    if ISSUES.lock().is_ok_and(|issues| !issues.is_empty()) {
+        tracing::warn!("Starting in safe mode, damaged stores were reset");
+    }
+}
+
+#[tauri::command]
+pub fn get_safe_mode_status() -> Result<SafeModeStatus, String> {
+    let issues = ISSUES.lock().map_err(|error| error.to_string())?.clone();
+
+    Ok(SafeModeStatus {
+        is_active: !issues.is_empty(),
+        issues,
+    })
+}
+
+/// Checks a store again and resets it if it is damaged. Stores already
+/// reset at startup are marked as dealt with, which ends safe mode once
+/// none are left.
+#[tauri::command]
+pub fn repair_store(store_id: String) -> Result<StoreRepairResult, String> {
+    let location = locate(&store_id)?;
+    let previous_issue = {
+        let mut issues = ISSUES.lock().map_err(|error| error.to_string())?;
+        let previous_issue = issues
+            .iter()
+            .find(|issue| issue.store_id == store_id)
+            .cloned();
+        issues.retain(|issue| issue.store_id != store_id);
+        previous_issue
+    };
+
+    let backup_path = match check(&location) {
+        Some(error) => {
+            let backup_path = set_aside(&location)?;
+            tracing::info!(store = %store_id, "Repaired damaged store: {}", error);
+            backup_path.map(|path| path_string(&path))
+        }
+        None => previous_issue
+            .as_ref()
+            .and_then(|issue| issue.backup_path.clone()),
+    };
+
+    Ok(StoreRepairResult {
+        was_damaged: previous_issue.is_some() || backup_path.is_some(),
+        store_id,
+        backup_path,
+    })
+}
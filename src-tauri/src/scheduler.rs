@@ -0,0 +1,415 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Recurring tasks the user sets up, such as syncing a folder every night
+//! or purging old items from the trash every week. Tasks are persisted and
+//! run on a timer while Sigma is open; a run missed while it was closed
+//! happens once after the next start. Each run is kept in a short history
+//! and announced with `scheduled-task-started` and `scheduled-task-finished`
+//! events.
+
+use crate::app_windows::emit_to_owner;
+use crate::dir_sync::{self, SyncMode};
+use crate::json_store;
+use crate::power::{self, Allowance, BackgroundWork};
+use crate::utils::to_os_path;
+use chrono::{DateTime, Datelike, Local, NaiveTime, TimeZone};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+const STORE_NAME: &str = "scheduled-tasks";
+const TASK_STARTED_EVENT: &str = "scheduled-task-started";
+const TASK_FINISHED_EVENT: &str = "scheduled-task-finished";
+const TICK_INTERVAL: Duration = Duration::from_secs(30);
+const HISTORY_LIMIT: usize = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TaskSchedule {
+    Interval {
+        minutes: u32,
+    },
+    /// Every day at a local time
+    Daily {
+        hour: u32,
+        minute: u32,
+    },
+    /// `weekday` counts from 0 for Monday
+    Weekly {
+        weekday: u32,
+        hour: u32,
+        minute: u32,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TaskAction {
+    SyncDirs {
+        source: String,
+        destination: String,
+        mode: SyncMode,
+        #[serde(default)]
+        exclude: Vec<String>,
+    },
+    /// Purges items trashed more than `older_than_days` ago, then the
+    /// oldest items until the trash fits `max_total_size`
+    PurgeTrash {
+        older_than_days: Option<u32>,
+        max_total_size: Option<u64>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledTask {
+    pub id: String,
+    pub name: String,
+    pub schedule: TaskSchedule,
+    pub action: TaskAction,
+    pub enabled: bool,
+    pub created_at: u64,
+    pub next_run_at: u64,
+    pub last_run_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskTrigger {
+    Schedule,
+    Manual,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskRun {
+    pub task_id: String,
+    pub task_name: String,
+    pub trigger: TaskTrigger,
+    pub started_at: u64,
+    /// `None` while the task runs
+    pub finished_at: Option<u64>,
+    pub success: bool,
+    /// Files synced or trash items purged
+    pub item_count: u64,
+    pub byte_count: u64,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SchedulerStore {
+    #[serde(default)]
+    tasks: Vec<ScheduledTask>,
+    /// Oldest run first
+    #[serde(default)]
+    history: Vec<TaskRun>,
+}
+
+static STORE: Lazy<Mutex<SchedulerStore>> = Lazy::new(|| Mutex::new(json_store::load(STORE_NAME)));
+
+static RUNNING_TASKS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn next_local_time(
+    after: DateTime<Local>,
+    hour: u32,
+    minute: u32,
+    weekday: Option<u32>,
+) -> DateTime<Local> {
+    let time = NaiveTime::from_hms_opt(hour, minute, 0).unwrap_or_default();
+    let mut date = after.date_naive();
+
+    // The matching day is always within the next eight
+    for _ in 0..9 {
+        let is_matching_day =
+            weekday.is_none_or(|weekday| date.weekday().num_days_from_monday() == weekday);
+
+        // A time skipped by a daylight saving change yields nothing
+        let candidate = date
+            .and_time(time)
+            .and_local_timezone(Local)
+            .earliest()
+            .filter(|candidate| *candidate > after);
+
+        if let (true, Some(candidate)) = (is_matching_day, candidate) {
+            return candidate;
+        }
+        date = date.succ_opt().unwrap_or(date);
+    }
+
+    after + chrono::Duration::days(1)
+}
+
+fn next_run_at(schedule: &TaskSchedule, after_ms: u64) -> u64 {
+    let after = Local
+        .timestamp_millis_opt(after_ms as i64)
+        .single()
+        .unwrap_or_else(Local::now);
+
+    let next = match *schedule {
+        TaskSchedule::Interval { minutes } => after + chrono::Duration::minutes(minutes as i64),
+        TaskSchedule::Daily { hour, minute } => next_local_time(after, hour, minute, None),
+        TaskSchedule::Weekly {
+            weekday,
+            hour,
+            minute,
+        } => next_local_time(after, hour, minute, Some(weekday)),
+    };
+
+    next.timestamp_millis().max(0) as u64
+}
+
+fn validate_schedule(schedule: &TaskSchedule) -> Result<(), String> {
+    let is_valid = match *schedule {
+        TaskSchedule::Interval { minutes } => minutes > 0,
+        TaskSchedule::Daily { hour, minute } => hour < 24 && minute < 60,
+        TaskSchedule::Weekly {
+            weekday,
+            hour,
+            minute,
+        } => weekday < 7 && hour < 24 && minute < 60,
+    };
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid schedule: {:?}", schedule))
+    }
+}
+
+/// Items handled, bytes handled and the errors of a task run
+fn execute(app: &AppHandle, action: &TaskAction) -> Result<(u64, u64, Vec<String>), String> {
+    match action {
+        TaskAction::SyncDirs {
+            source,
+            destination,
+            mode,
+            exclude,
+        } => {
+            let report = dir_sync::run_sync(
+                app,
+                &to_os_path(source),
+                &to_os_path(destination),
+                *mode,
+                exclude.clone(),
+            )?;
+            Ok((report.completed_count, report.bytes_to_copy, report.errors))
+        }
+        TaskAction::PurgeTrash {
+            older_than_days,
+            max_total_size,
+        } => {
+            let purge = crate::trash_bin::purge_with(*older_than_days, *max_total_size)?;
+            Ok((purge.items.len() as u64, purge.total_size, purge.errors))
+        }
+    }
+}
+
+fn run_task(app: &AppHandle, task: ScheduledTask, trigger: TaskTrigger) -> Result<TaskRun, String> {
+    if !RUNNING_TASKS
+        .lock()
+        .map_err(|error| error.to_string())?
+        .insert(task.id.clone())
+    {
+        return Err(format!("{} is already running", task.name));
+    }
+
+    let mut run = TaskRun {
+        task_id: task.id.clone(),
+        task_name: task.name.clone(),
+        trigger,
+        started_at: now_ms(),
+        finished_at: None,
+        success: false,
+        item_count: 0,
+        byte_count: 0,
+        errors: Vec::new(),
+    };
+    emit_to_owner(app, None, TASK_STARTED_EVENT, run.clone());
+
+    match execute(app, &task.action) {
+        Ok((item_count, byte_count, errors)) => {
+            run.item_count = item_count;
+            run.byte_count = byte_count;
+            run.errors = errors;
+        }
+        Err(error) => run.errors.push(error),
+    }
+    run.finished_at = Some(now_ms());
+    run.success = run.errors.is_empty();
+
+    if let Ok(mut running_tasks) = RUNNING_TASKS.lock() {
+        running_tasks.remove(&task.id);
+    }
+
+    if let Ok(mut store) = STORE.lock() {
+        if let Some(stored_task) = store.tasks.iter_mut().find(|stored| stored.id == task.id) {
+            stored_task.last_run_at = Some(run.started_at);
+        }
+        store.history.push(run.clone());
+        let overflow = store.history.len().saturating_sub(HISTORY_LIMIT);
+        store.history.drain(..overflow);
+        let _ = json_store::save(STORE_NAME, &*store);
+    }
+
+    emit_to_owner(app, None, TASK_FINISHED_EVENT, run.clone());
+    Ok(run)
+}
+
+/// Takes the tasks that are due and moves them to their next run
+fn due_tasks() -> Vec<ScheduledTask> {
+    let now = now_ms();
+    // Syncs are backups as far as the power policy goes
+    let syncs_paused = power::allowance(BackgroundWork::Backups) == Allowance::Pause;
+
+    let Ok(mut store) = STORE.lock() else {
+        return Vec::new();
+    };
+    let mut due = Vec::new();
+
+    for task in store
+        .tasks
+        .iter_mut()
+        .filter(|task| task.enabled && task.next_run_at <= now)
+    {
+        if syncs_paused && matches!(task.action, TaskAction::SyncDirs { .. }) {
+            continue;
+        }
+        task.next_run_at = next_run_at(&task.schedule, now);
+        due.push(task.clone());
+    }
+
+    if !due.is_empty() {
+        let _ = json_store::save(STORE_NAME, &*store);
+    }
+
+    due
+}
+
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            for task in due_tasks() {
+                let app = app.clone();
+                tauri::async_runtime::spawn_blocking(move || {
+                    let task_name = task.name.clone();
+                    if let Err(error) = run_task(&app, task, TaskTrigger::Schedule) {
+                        tracing::warn!("Scheduled task {} didn't run: {}", task_name, error);
+                    }
+                });
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn list_tasks() -> Result<Vec<ScheduledTask>, String> {
+    Ok(STORE
+        .lock()
+        .map_err(|error| error.to_string())?
+        .tasks
+        .clone())
+}
+
+#[tauri::command]
+pub fn add_task(
+    name: String,
+    schedule: TaskSchedule,
+    action: TaskAction,
+) -> Result<ScheduledTask, String> {
+    validate_schedule(&schedule)?;
+
+    let now = now_ms();
+    let task = ScheduledTask {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        next_run_at: next_run_at(&schedule, now),
+        schedule,
+        action,
+        enabled: true,
+        created_at: now,
+        last_run_at: None,
+    };
+
+    let mut store = STORE.lock().map_err(|error| error.to_string())?;
+    store.tasks.push(task.clone());
+    json_store::save(STORE_NAME, &*store)?;
+
+    Ok(task)
+}
+
+#[tauri::command]
+pub fn remove_task(id: String) -> Result<(), String> {
+    let mut store = STORE.lock().map_err(|error| error.to_string())?;
+    store.tasks.retain(|task| task.id != id);
+    json_store::save(STORE_NAME, &*store)
+}
+
+/// Pauses or resumes a task. A resumed task runs at its next scheduled
+/// time rather than catching up.
+#[tauri::command]
+pub fn set_task_enabled(id: String, enabled: bool) -> Result<ScheduledTask, String> {
+    let mut store = STORE.lock().map_err(|error| error.to_string())?;
+    let task = store
+        .tasks
+        .iter_mut()
+        .find(|task| task.id == id)
+        .ok_or_else(|| format!("No scheduled task with id {}", id))?;
+
+    if enabled && !task.enabled {
+        task.next_run_at = next_run_at(&task.schedule, now_ms());
+    }
+    task.enabled = enabled;
+    let task = task.clone();
+
+    json_store::save(STORE_NAME, &*store)?;
+    Ok(task)
+}
+
+/// Runs a task right away, leaving its schedule as it is
+#[tauri::command]
+pub async fn run_task_now(app: AppHandle, id: String) -> Result<TaskRun, String> {
+    let task = STORE
+        .lock()
+        .map_err(|error| error.to_string())?
+        .tasks
+        .iter()
+        .find(|task| task.id == id)
+        .cloned()
+        .ok_or_else(|| format!("No scheduled task with id {}", id))?;
+
+    tokio::task::spawn_blocking(move || run_task(&app, task, TaskTrigger::Manual))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Past runs, most recent first, of one task or of all of them
+#[tauri::command]
+pub fn get_task_history(task_id: Option<String>) -> Result<Vec<TaskRun>, String> {
+    let store = STORE.lock().map_err(|error| error.to_string())?;
+
+    Ok(store
+        .history
+        .iter()
+        .rev()
+        .filter(|run| {
+            task_id
+                .as_ref()
+                .is_none_or(|task_id| &run.task_id == task_id)
+        })
+        .cloned()
+        .collect())
+}
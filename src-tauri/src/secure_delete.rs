@@ -0,0 +1,319 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Deleting files after overwriting their contents with random data, so
+//! they can't be recovered from the disk by undelete tools.
+//!
+//! Overwriting in place only reaches the old data when the file system
+//! writes to the same blocks and the drive to the same cells. Solid-state
+//! drives remap writes to fresh cells, copy-on-write file systems write
+//! changes to new blocks and keep snapshots, and network shares can have
+//! backups on the server. Each volume involved is checked for these and a
+//! caveat is returned with the result rather than claiming a guarantee
+//! that doesn't hold.
+
+use crate::messages::{self, LocalizedMessage};
+use crate::requests::{self, ActiveRequest, ProgressEmitter};
+use crate::utils::{normalize_path, volume_space, VolumeSpace};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::Manager;
+use walkdir::WalkDir;
+
+const PROGRESS_EVENT: &str = "secure-delete-progress";
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// The Gutmann method, the most passes anyone has seriously proposed
+const MAX_PASSES: u32 = 35;
+const COPY_ON_WRITE_FILE_SYSTEMS: [&str; 7] =
+    ["btrfs", "zfs", "apfs", "refs", "bcachefs", "f2fs", "nilfs2"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SecureDeleteProgress {
+    pub request_id: String,
+    pub processed_bytes: u64,
+    /// File sizes times the number of passes
+    pub total_bytes: u64,
+    pub pass: u32,
+    pub passes: u32,
+    pub current_path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SecureDeleteResult {
+    /// Requested paths that were overwritten and deleted completely
+    pub deleted_paths: Vec<String>,
+    pub errors: Vec<String>,
+    /// Why overwriting may not have reached the original data
+    pub caveats: Vec<LocalizedMessage>,
+    pub cancelled: bool,
+}
+
+fn volume_caveat(volume: &VolumeSpace) -> Option<LocalizedMessage> {
+    let file_system = volume.file_system.to_lowercase();
+
+    if volume.is_network {
+        Some(messages::SECURE_DELETE_NETWORK.with("volume", &volume.mount_point))
+    } else if COPY_ON_WRITE_FILE_SYSTEMS.contains(&file_system.as_str()) {
+        Some(
+            messages::SECURE_DELETE_COPY_ON_WRITE
+                .with("volume", &volume.mount_point)
+                .with("fileSystem", &volume.file_system),
+        )
+    } else {
+        match volume.is_solid_state {
+            Some(true) => {
+                Some(messages::SECURE_DELETE_SOLID_STATE.with("volume", &volume.mount_point))
+            }
+            Some(false) => None,
+            None => Some(messages::SECURE_DELETE_UNKNOWN_DRIVE.with("volume", &volume.mount_point)),
+        }
+    }
+}
+
+/// Caveats of the volumes holding `paths`, one per volume
+pub fn caveats(paths: &[PathBuf]) -> Vec<LocalizedMessage> {
+    let mut seen_mount_points = HashSet::new();
+
+    paths
+        .iter()
+        .filter_map(|path| volume_space(path))
+        .filter(|volume| seen_mount_points.insert(volume.mount_point.clone()))
+        .filter_map(|volume| volume_caveat(&volume))
+        .collect()
+}
+
+fn total_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// A keyed BLAKE3 output stream; fast, and unpredictable without the key
+fn random_stream() -> blake3::OutputReader {
+    let mut key = [0u8; 32];
+    key[..16].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+    key[16..].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+    blake3::Hasher::new_keyed(&key).finalize_xof()
+}
+
+#[cfg(unix)]
+fn has_other_links(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn has_other_links(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+struct SecureDeleteRun<'a> {
+    progress: ProgressEmitter<'a>,
+    cancel_token: &'a AtomicBool,
+    passes: u32,
+    processed_bytes: u64,
+    total_bytes: u64,
+}
+
+impl SecureDeleteRun<'_> {
+    fn emit_progress(&self, current_path: &Path, pass: u32, force: bool) {
+        self.progress
+            .emit(force, |request_id| SecureDeleteProgress {
+                request_id: request_id.to_string(),
+                processed_bytes: self.processed_bytes,
+                total_bytes: self.total_bytes,
+                pass,
+                passes: self.passes,
+                current_path: normalize_path(&current_path.to_string_lossy()),
+            });
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_token.load(Ordering::Relaxed)
+    }
+
+    fn overwrite(&mut self, path: &Path, size: u64) -> std::io::Result<()> {
+        let mut file = fs::OpenOptions::new().write(true).open(path)?;
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+
+        for pass in 1..=self.passes {
+            let mut stream = random_stream();
+            let mut remaining = size;
+            file.seek(SeekFrom::Start(0))?;
+
+            while remaining > 0 {
+                if self.is_cancelled() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Interrupted,
+                        "Cancelled",
+                    ));
+                }
+
+                let length = remaining.min(CHUNK_SIZE as u64) as usize;
+                stream.fill(&mut buffer[..length]);
+                file.write_all(&buffer[..length])?;
+                remaining -= length as u64;
+                self.processed_bytes += length as u64;
+                self.emit_progress(path, pass, false);
+            }
+
+            // Each pass has to reach the disk, not just the page cache
+            file.sync_all()?;
+        }
+
+        file.set_len(0)?;
+        file.sync_all()
+    }
+
+    /// Overwrites and deletes one file. The file is renamed first, so its
+    /// name doesn't linger in the directory entry either.
+    fn delete_file(&mut self, path: &Path) -> Result<(), String> {
+        let metadata = fs::symlink_metadata(path).map_err(|error| error.to_string())?;
+
+        // Only the link goes, whatever it points to is left alone
+        if metadata.file_type().is_symlink() {
+            return fs::remove_file(path).map_err(|error| error.to_string());
+        }
+        if has_other_links(&metadata) {
+            return Err(messages::SECURE_DELETE_HARD_LINKS
+                .with("path", path.display())
+                .into());
+        }
+
+        self.overwrite(path, metadata.len())
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+
+        let renamed_path = path.with_file_name(uuid::Uuid::new_v4().simple().to_string());
+        let removed_path = match fs::rename(path, &renamed_path) {
+            Ok(()) => renamed_path,
+            Err(_) => path.to_path_buf(),
+        };
+        fs::remove_file(&removed_path).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    fn delete_path(&mut self, path: &Path, errors: &mut Vec<String>) -> bool {
+        let is_dir = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir());
+        if !is_dir {
+            return match self.delete_file(path) {
+                Ok(()) => true,
+                Err(error) => {
+                    if !self.is_cancelled() {
+                        errors.push(error);
+                    }
+                    false
+                }
+            };
+        }
+
+        let mut is_complete = true;
+        for entry in WalkDir::new(path).contents_first(true) {
+            if self.is_cancelled() {
+                return false;
+            }
+
+            let result = match entry {
+                Ok(entry) if entry.file_type().is_dir() => fs::remove_dir(entry.path())
+                    .map_err(|error| format!("{}: {}", entry.path().display(), error)),
+                Ok(entry) => self.delete_file(entry.path()),
+                Err(error) => Err(error.to_string()),
+            };
+
+            if let Err(error) = result {
+                // Folders still holding a failed file fail as a consequence
+                if is_complete && !self.is_cancelled() {
+                    errors.push(error);
+                }
+                is_complete = false;
+            }
+        }
+        is_complete
+    }
+}
+
+fn secure_delete_blocking(run: &mut SecureDeleteRun, paths: &[PathBuf]) -> SecureDeleteResult {
+    run.total_bytes =
+        paths.iter().map(|path| total_size(path)).sum::<u64>() * u64::from(run.passes);
+
+    let caveats = caveats(paths);
+    let mut deleted_paths = Vec::new();
+    let mut errors = Vec::new();
+
+    for path in paths {
+        if run.is_cancelled() {
+            break;
+        }
+        if run.delete_path(path, &mut errors) {
+            deleted_paths.push(normalize_path(&path.to_string_lossy()));
+        }
+        run.emit_progress(path, run.passes, true);
+    }
+
+    SecureDeleteResult {
+        deleted_paths,
+        errors,
+        caveats,
+        cancelled: run.is_cancelled(),
+    }
+}
+
+/// Overwrites files `passes` times with random data, then deletes them.
+/// Folders are processed recursively; symbolic links are removed without
+/// touching their targets.
+#[tauri::command]
+pub async fn secure_delete(
+    window: tauri::WebviewWindow,
+    paths: Vec<String>,
+    passes: Option<u32>,
+    request_id: Option<String>,
+) -> Result<SecureDeleteResult, String> {
+    let active_request = ActiveRequest::register(request_id.as_deref());
+    let cancel_token = active_request.cancel_token();
+    let app = window.app_handle().clone();
+    let owner_window = window.label().to_string();
+
+    tokio::task::spawn_blocking(move || {
+        let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+        let mut run = SecureDeleteRun {
+            progress: ProgressEmitter::new(
+                &app,
+                Some(&owner_window),
+                PROGRESS_EVENT,
+                request_id.as_deref(),
+            ),
+            cancel_token: &cancel_token,
+            passes: passes.unwrap_or(1).clamp(1, MAX_PASSES),
+            processed_bytes: 0,
+            total_bytes: 0,
+        };
+        secure_delete_blocking(&mut run, &paths)
+    })
+    .await
+    .map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+pub fn cancel_secure_delete(request_id: String) {
+    requests::cancel(&request_id);
+}
+
+/// Caveats to show before the user confirms a secure delete
+#[tauri::command]
+pub async fn get_secure_delete_caveats(
+    paths: Vec<String>,
+) -> Result<Vec<LocalizedMessage>, String> {
+    tokio::task::spawn_blocking(move || {
+        let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+        caveats(&paths)
+    })
+    .await
+    .map_err(|error| error.to_string())
+}
@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Targets of Windows shell links (`.lnk` files), read as laid out in the
+//! MS-SHLLINK specification. Only the parts that lead to the target path
+//! are parsed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HEADER_SIZE: u32 = 0x4C;
+const LINK_CLSID: [u8; 16] = [
+    0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+/// Shell links are a few kilobytes at most, anything larger isn't one
+const MAX_LINK_SIZE: u64 = 1024 * 1024;
+
+// Link flags
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x1;
+const HAS_LINK_INFO: u32 = 0x2;
+const HAS_NAME: u32 = 0x4;
+const HAS_RELATIVE_PATH: u32 = 0x8;
+const IS_UNICODE: u32 = 0x80;
+
+// Link info flags
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+const COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX: u32 = 0x2;
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// Strings in the system code page, which isn't known here. Paths are
+// mostly ASCII, and links with other paths carry Unicode ones as well.
+fn ansi_string_at(data: &[u8], offset: usize) -> Option<String> {
+    let bytes = data.get(offset..)?;
+    let end = bytes.iter().position(|byte| *byte == 0)?;
+    Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+fn unicode_string_at(data: &[u8], offset: usize) -> Option<String> {
+    let units: Vec<u16> = data
+        .get(offset..)?
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+/// A string of the link info, Unicode when the link info has it
+fn link_info_string(
+    info: &[u8],
+    ansi_offset: usize,
+    unicode_offset: Option<usize>,
+) -> Option<String> {
+    match unicode_offset {
+        Some(offset) if offset > 0 => unicode_string_at(info, offset),
+        _ if ansi_offset > 0 => ansi_string_at(info, ansi_offset),
+        _ => None,
+    }
+}
+
+/// The absolute target path kept in the link info structure
+fn link_info_path(info: &[u8]) -> Option<String> {
+    let header_size = u32_at(info, 4)?;
+    let flags = u32_at(info, 8)?;
+    let has_unicode_offsets = header_size >= 0x24;
+    let unicode_offset = |offset: usize| {
+        has_unicode_offsets
+            .then(|| u32_at(info, offset))
+            .flatten()
+            .map(|value| value as usize)
+    };
+
+    let suffix = link_info_string(info, u32_at(info, 0x18)? as usize, unicode_offset(0x20))
+        .unwrap_or_default();
+
+    if flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0 {
+        let base = link_info_string(info, u32_at(info, 0x10)? as usize, unicode_offset(0x1C))?;
+        return Some(format!("{}{}", base, suffix));
+    }
+
+    if flags & COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX != 0 {
+        let network_link = info.get(u32_at(info, 0x14)? as usize..)?;
+        let net_name_offset = u32_at(network_link, 8)? as usize;
+        // The Unicode name is there when the name comes after its offset
+        let unicode_name_offset = (net_name_offset > 0x14)
+            .then(|| u32_at(network_link, 0x14))
+            .flatten()
+            .map(|value| value as usize);
+        let net_name = link_info_string(network_link, net_name_offset, unicode_name_offset)?;
+
+        if suffix.is_empty() {
+            return Some(net_name);
+        }
+        return Some(format!("{}\\{}", net_name.trim_end_matches('\\'), suffix));
+    }
+
+    None
+}
+
+/// The relative path from the string data, which follows the link info
+fn relative_path(data: &[u8], mut offset: usize, flags: u32) -> Option<String> {
+    if flags & HAS_RELATIVE_PATH == 0 {
+        return None;
+    }
+    let is_unicode = flags & IS_UNICODE != 0;
+    let char_size = if is_unicode { 2 } else { 1 };
+
+    // Strings are counted, not terminated. The name comes first.
+    if flags & HAS_NAME != 0 {
+        let count = u16_at(data, offset)? as usize;
+        offset += 2 + count * char_size;
+    }
+    let count = u16_at(data, offset)? as usize;
+    let bytes = data.get(offset + 2..offset + 2 + count * char_size)?;
+
+    if is_unicode {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        Some(String::from_utf16_lossy(&units))
+    } else {
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+struct LinkPaths {
+    absolute: Option<String>,
+    relative: Option<String>,
+}
+
+fn parse(data: &[u8]) -> Option<LinkPaths> {
+    if u32_at(data, 0)? != HEADER_SIZE || data.get(4..20)? != LINK_CLSID {
+        return None;
+    }
+    let flags = u32_at(data, 0x14)?;
+    let mut offset = HEADER_SIZE as usize;
+
+    if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+        offset += 2 + u16_at(data, offset)? as usize;
+    }
+
+    let mut absolute = None;
+    if flags & HAS_LINK_INFO != 0 {
+        let info_size = u32_at(data, offset)? as usize;
+        let info = data.get(offset..offset.checked_add(info_size)?)?;
+        absolute = link_info_path(info);
+        offset += info_size;
+    }
+
+    Some(LinkPaths {
+        absolute,
+        relative: relative_path(data, offset, flags),
+    })
+}
+
+/// The target of the shell link at `path`. `None` for links to things
+/// other than files and folders, like Control Panel items, whose target is
+/// only kept as a shell item list.
+pub fn read_target(path: &Path) -> Result<Option<PathBuf>, String> {
+    let size = fs::metadata(path).map_err(|error| error.to_string())?.len();
+    if size > MAX_LINK_SIZE {
+        return Err(format!("Not a shell link: {}", path.display()));
+    }
+
+    let data = fs::read(path).map_err(|error| error.to_string())?;
+    let paths = parse(&data).ok_or_else(|| format!("Not a shell link: {}", path.display()))?;
+
+    if let Some(absolute) = paths.absolute.filter(|absolute| !absolute.is_empty()) {
+        return Ok(Some(PathBuf::from(absolute)));
+    }
+    Ok(paths
+        .relative
+        .filter(|relative| !relative.is_empty())
+        .and_then(|relative| Some(path.parent()?.join(relative))))
+}
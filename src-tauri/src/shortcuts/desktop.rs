@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! `.desktop` launchers, as described by the freedesktop.org Desktop Entry
+//! Specification. `Type=Application` launchers run a program,
+//! `Type=Link` ones open a URL, which for shortcuts is a `file://` one.
+
+use super::{Shortcut, ShortcutKind};
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tauri::Url;
+
+const DESKTOP_ENTRY_GROUP: &str = "[Desktop Entry]";
+
+/// Keys of the `[Desktop Entry]` group. Localized keys like `Name[de]`
+/// are kept under their full name and not used.
+fn read_entry_keys(path: &Path) -> Result<HashMap<String, String>, String> {
+    let content = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let mut keys = HashMap::new();
+    let mut in_desktop_entry = false;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            in_desktop_entry = trimmed == DESKTOP_ENTRY_GROUP;
+            continue;
+        }
+        if !in_desktop_entry || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = trimmed.split_once('=') {
+            keys.insert(key.trim().to_string(), unescape_value(value.trim()));
+        }
+    }
+
+    Ok(keys)
+}
+
+fn unescape_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(char_value) = chars.next() {
+        if char_value != '\\' {
+            result.push(char_value);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => result.push(' '),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+fn escape_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+/// The arguments of an `Exec` value, without field codes like `%f`, which
+/// stand for the files a launcher is opened with
+fn split_exec(value: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut has_token = false;
+    let mut in_quotes = false;
+    let mut chars = value.chars();
+
+    while let Some(char_value) = chars.next() {
+        match char_value {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_token = true;
+            }
+            '\\' if in_quotes => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            '%' => match chars.next() {
+                Some('%') => current.push('%'),
+                // Deprecated and file codes alike expand to nothing here
+                Some(_) => {}
+                None => current.push('%'),
+            },
+            _ if char_value.is_whitespace() && !in_quotes => {
+                if has_token || !current.is_empty() {
+                    arguments.push(std::mem::take(&mut current));
+                }
+                has_token = false;
+            }
+            _ => current.push(char_value),
+        }
+    }
+    if has_token || !current.is_empty() {
+        arguments.push(current);
+    }
+
+    arguments.retain(|argument| !argument.is_empty());
+    arguments
+}
+
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        return Some(PathBuf::from(program));
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn read(path: &Path) -> Result<Shortcut, String> {
+    let mut keys = read_entry_keys(path)?;
+    let mut shortcut = Shortcut::new(ShortcutKind::DesktopEntry);
+
+    match keys.get("Type").map(String::as_str) {
+        Some("Link") => {
+            let url = keys.remove("URL").unwrap_or_default();
+            let file_path = Url::parse(&url)
+                .ok()
+                .filter(|parsed| parsed.scheme() == "file")
+                .and_then(|parsed| parsed.to_file_path().ok());
+            match file_path {
+                Some(file_path) => shortcut.set_target(&file_path),
+                None if !url.is_empty() => shortcut.target = Some(url),
+                None => {}
+            }
+        }
+        Some("Application") => {
+            let mut arguments = split_exec(&keys.remove("Exec").unwrap_or_default()).into_iter();
+            if let Some(program) = arguments.next() {
+                let program_path = find_in_path(&program).unwrap_or_else(|| PathBuf::from(program));
+                shortcut.set_target(&program_path);
+            }
+            let arguments: Vec<String> = arguments.collect();
+            if !arguments.is_empty() {
+                shortcut.arguments = Some(arguments.join(" "));
+            }
+            shortcut.working_dir = keys.remove("Path");
+        }
+        _ => {}
+    }
+
+    shortcut.name = keys.remove("Name");
+    shortcut.description = keys.remove("Comment");
+    shortcut.icon = keys.remove("Icon");
+    Ok(shortcut)
+}
+
+pub fn create(target: &Path, link_path: &Path) -> Result<(), String> {
+    let url = Url::from_file_path(target)
+        .map_err(|_| format!("Not an absolute path: {}", target.display()))?;
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| target.to_string_lossy().into_owned());
+
+    let mut content = format!(
+        "{}\nType=Link\nName={}\nURL={}\n",
+        DESKTOP_ENTRY_GROUP,
+        escape_value(&name),
+        url
+    );
+    if target.is_dir() {
+        content.push_str("Icon=folder\n");
+    }
+
+    fs::write(link_path, content).map_err(|error| error.to_string())?;
+    // File managers only open launchers that are executable
+    fs::set_permissions(link_path, fs::Permissions::from_mode(0o755))
+        .map_err(|error| error.to_string())
+}
@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Shortcuts to files, folders and programs: shell links (`.lnk`) on
+//! Windows and `.desktop` launchers on Linux. Listings mark them with
+//! `is_shortcut`, and opening one resolves it so navigation can follow it.
+
+#[cfg(target_os = "linux")]
+mod desktop;
+#[cfg(windows)]
+mod windows;
+
+use crate::utils::{normalize_path, to_os_path};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortcutKind {
+    ShellLink,
+    DesktopEntry,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Shortcut {
+    pub kind: ShortcutKind,
+    /// The file, folder or program the shortcut opens. A URL for launchers
+    /// of other than local files.
+    pub target: Option<String>,
+    pub target_is_dir: bool,
+    pub arguments: Option<String>,
+    pub working_dir: Option<String>,
+    /// A path to an icon file, or on Linux an icon theme name
+    pub icon: Option<String>,
+    /// Index of the icon within `icon`, for icons inside executables
+    pub icon_index: Option<i32>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Shortcut {
+    fn new(kind: ShortcutKind) -> Self {
+        Self {
+            kind,
+            target: None,
+            target_is_dir: false,
+            arguments: None,
+            working_dir: None,
+            icon: None,
+            icon_index: None,
+            name: None,
+            description: None,
+        }
+    }
+
+    fn set_target(&mut self, target: &Path) {
+        self.target_is_dir = target.is_dir();
+        self.target = Some(normalize_path(&target.to_string_lossy()));
+    }
+}
+
+/// The shortcut extension of this platform
+fn shortcut_extension() -> Option<&'static str> {
+    if cfg!(windows) {
+        Some("lnk")
+    } else if cfg!(target_os = "linux") {
+        Some("desktop")
+    } else {
+        None
+    }
+}
+
+/// Whether `path` is a shortcut this platform can resolve, by extension
+pub fn is_shortcut(path: &Path) -> bool {
+    match (shortcut_extension(), path.extension()) {
+        (Some(expected), Some(extension)) => extension.eq_ignore_ascii_case(expected),
+        _ => false,
+    }
+}
+
+fn read_shortcut(path: &Path) -> Result<Shortcut, String> {
+    if !is_shortcut(path) {
+        return Err(format!("Not a shortcut: {}", path.display()));
+    }
+
+    #[cfg(windows)]
+    {
+        windows::read(path)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        desktop::read(path)
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        Err("Shortcuts are not supported on this platform".to_string())
+    }
+}
+
+fn write_shortcut(target: &Path, link_path: &Path) -> Result<(), String> {
+    if !target.exists() {
+        return Err(format!("Target does not exist: {}", target.display()));
+    }
+    if link_path.exists() {
+        return Err(format!("Already exists: {}", link_path.display()));
+    }
+    if !is_shortcut(link_path) {
+        let extension = shortcut_extension()
+            .ok_or_else(|| "Shortcuts are not supported on this platform".to_string())?;
+        return Err(format!("Shortcut names must end with .{}", extension));
+    }
+
+    #[cfg(windows)]
+    {
+        windows::create(target, link_path)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        desktop::create(target, link_path)
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        Err("Shortcuts are not supported on this platform".to_string())
+    }
+}
+
+/// What the shortcut at `path` points to
+#[tauri::command]
+pub async fn resolve_shortcut(path: String) -> Result<Shortcut, String> {
+    tokio::task::spawn_blocking(move || read_shortcut(&to_os_path(&path)))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Creates a shortcut at `link_path` that opens `target`. `link_path` must
+/// have this platform's shortcut extension, `.lnk` or `.desktop`.
+#[tauri::command]
+pub async fn create_shortcut(target: String, link_path: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        write_shortcut(&to_os_path(&target), &to_os_path(&link_path))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
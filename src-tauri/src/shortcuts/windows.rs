@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Shell links through `IShellLinkW`, which also resolves targets that the
+//! link stores only as a shell item list
+
+use super::{Shortcut, ShortcutKind};
+use std::path::Path;
+use windows::core::{Interface, HSTRING};
+use windows::Win32::Foundation::{BOOL, MAX_PATH};
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoUninitialize, IPersistFile, CLSCTX_INPROC_SERVER,
+    COINIT_APARTMENTTHREADED, STGM_READ,
+};
+use windows::Win32::UI::Shell::{IShellLinkW, ShellLink};
+
+/// Arguments and descriptions can be longer than a path
+const TEXT_BUFFER_SIZE: usize = 32 * 1024;
+
+fn com_error(error: windows::core::Error) -> String {
+    error.message().to_string()
+}
+
+/// Keeps COM initialized for the thread while in scope
+struct ComGuard {
+    needs_uninit: bool,
+}
+
+impl ComGuard {
+    fn init() -> Self {
+        let result = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) };
+        Self {
+            needs_uninit: result.is_ok(),
+        }
+    }
+}
+
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        if self.needs_uninit {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+fn wide_to_string(buffer: &[u16]) -> Option<String> {
+    let length = buffer
+        .iter()
+        .position(|unit| *unit == 0)
+        .unwrap_or(buffer.len());
+    if length == 0 {
+        return None;
+    }
+    Some(String::from_utf16_lossy(&buffer[..length]))
+}
+
+fn shell_link() -> Result<IShellLinkW, String> {
+    unsafe { CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER) }.map_err(com_error)
+}
+
+pub fn read(path: &Path) -> Result<Shortcut, String> {
+    let _com = ComGuard::init();
+    let link = shell_link()?;
+    let mut shortcut = Shortcut::new(ShortcutKind::ShellLink);
+
+    unsafe {
+        let file: IPersistFile = link.cast().map_err(com_error)?;
+        file.Load(&HSTRING::from(path.as_os_str()), STGM_READ)
+            .map_err(com_error)?;
+
+        let mut path_buffer = vec![0u16; MAX_PATH as usize];
+        if link
+            .GetPath(&mut path_buffer, std::ptr::null_mut(), 0)
+            .is_ok()
+        {
+            if let Some(target) = wide_to_string(&path_buffer) {
+                shortcut.set_target(Path::new(&target));
+            }
+        }
+
+        let mut text_buffer = vec![0u16; TEXT_BUFFER_SIZE];
+        if link.GetArguments(&mut text_buffer).is_ok() {
+            shortcut.arguments = wide_to_string(&text_buffer);
+        }
+
+        text_buffer.fill(0);
+        if link.GetDescription(&mut text_buffer).is_ok() {
+            shortcut.description = wide_to_string(&text_buffer);
+        }
+
+        path_buffer.fill(0);
+        if link.GetWorkingDirectory(&mut path_buffer).is_ok() {
+            shortcut.working_dir =
+                wide_to_string(&path_buffer).map(|dir| crate::utils::normalize_path(&dir));
+        }
+
+        path_buffer.fill(0);
+        let mut icon_index = 0;
+        if link
+            .GetIconLocation(&mut path_buffer, &mut icon_index)
+            .is_ok()
+        {
+            shortcut.icon = wide_to_string(&path_buffer);
+            shortcut.icon_index = shortcut.icon.as_ref().map(|_| icon_index);
+        }
+    }
+
+    shortcut.name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned());
+    Ok(shortcut)
+}
+
+pub fn create(target: &Path, link_path: &Path) -> Result<(), String> {
+    let _com = ComGuard::init();
+    let link = shell_link()?;
+
+    unsafe {
+        link.SetPath(&HSTRING::from(target.as_os_str()))
+            .map_err(com_error)?;
+        if let Some(parent) = target.parent().filter(|_| target.is_file()) {
+            link.SetWorkingDirectory(&HSTRING::from(parent.as_os_str()))
+                .map_err(com_error)?;
+        }
+
+        let file: IPersistFile = link.cast().map_err(com_error)?;
+        file.Save(&HSTRING::from(link_path.as_os_str()), BOOL::from(true))
+            .map_err(com_error)
+    }
+}
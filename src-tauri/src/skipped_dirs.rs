@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Folders a recursive walk couldn't enter for lack of permission. Results
+//! carry them so the UI can say "N folders skipped due to permissions"
+//! rather than showing totals that are silently short.
+
+use crate::utils::normalize_path;
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+/// Enough to show where the gaps are, a whole drive can have thousands
+const MAX_LISTED_PATHS: usize = 100;
+
+fn ignore_error_path(error: &ignore::Error) -> Option<&Path> {
+    match error {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            ignore_error_path(err)
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedDirs {
+    pub count: u64,
+    /// The first `MAX_LISTED_PATHS` of the skipped folders
+    pub paths: Vec<String>,
+}
+
+impl SkippedDirs {
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn add(&mut self, path: &Path) {
+        self.count += 1;
+        if self.paths.len() < MAX_LISTED_PATHS {
+            self.paths.push(normalize_path(&path.to_string_lossy()));
+        }
+    }
+
+    fn record_io(&mut self, path: Option<&Path>, error: Option<&io::Error>) -> bool {
+        let is_permission_denied =
+            error.is_some_and(|error| error.kind() == ErrorKind::PermissionDenied);
+
+        match path {
+            Some(path) if is_permission_denied => {
+                self.add(path);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Counts the folder behind `error` when it was refused for lack of
+    /// permission. Returns whether it was counted.
+    pub fn record(&mut self, error: &walkdir::Error) -> bool {
+        self.record_io(error.path(), error.io_error())
+    }
+
+    /// `record` for the parallel walker of the `ignore` crate
+    pub fn record_ignore_error(&mut self, error: &ignore::Error) -> bool {
+        self.record_io(ignore_error_path(error), error.io_error())
+    }
+
+    pub fn merge(&mut self, other: SkippedDirs) {
+        self.count += other.count;
+        let room = MAX_LISTED_PATHS.saturating_sub(self.paths.len());
+        self.paths.extend(other.paths.into_iter().take(room));
+    }
+
+    /// The listed folders inside `scope`. The count is that of the listed
+    /// folders, so it can fall short when the list is full.
+    pub fn within(&self, scope: &str) -> SkippedDirs {
+        let prefix = crate::folder_sizes::child_prefix(scope);
+        let paths: Vec<String> = self
+            .paths
+            .iter()
+            .filter(|path| path.starts_with(&prefix))
+            .cloned()
+            .collect();
+
+        SkippedDirs {
+            count: paths.len() as u64,
+            paths,
+        }
+    }
+}
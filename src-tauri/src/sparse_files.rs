@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Sparse files, whose unwritten ranges (holes) take no space on disk, as
+//! with virtual machine disk images and database files. Listings report
+//! their allocated size next to their size, and copies keep the holes
+//! instead of writing them out as zeros.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Space the file takes on disk, when that is less than its size because
+/// it is sparse or compressed
+#[cfg(unix)]
+pub fn allocated_size(_path: &Path, metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    // st_blocks counts 512-byte units whatever the block size
+    let allocated = metadata.blocks() * 512;
+    (metadata.is_file() && allocated < metadata.len()).then_some(allocated)
+}
+
+#[cfg(windows)]
+pub fn allocated_size(path: &Path, metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::MetadataExt;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetCompressedFileSizeW, FILE_ATTRIBUTE_COMPRESSED, FILE_ATTRIBUTE_SPARSE_FILE,
+        INVALID_FILE_SIZE,
+    };
+
+    // Other files take their full size, which saves a call per file
+    if !metadata.is_file()
+        || metadata.file_attributes() & (FILE_ATTRIBUTE_SPARSE_FILE | FILE_ATTRIBUTE_COMPRESSED)
+            == 0
+    {
+        return None;
+    }
+
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut high = 0u32;
+    let low = unsafe { GetCompressedFileSizeW(wide_path.as_ptr(), &mut high) };
+    if low == INVALID_FILE_SIZE && io::Error::last_os_error().raw_os_error() != Some(0) {
+        return None;
+    }
+
+    let allocated = ((high as u64) << 32) | low as u64;
+    (allocated < metadata.len()).then_some(allocated)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn allocated_size(_path: &Path, _metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Whether copies of the file should keep its holes
+fn is_sparse(path: &Path, metadata: &fs::Metadata) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        use windows_sys::Win32::Storage::FileSystem::FILE_ATTRIBUTE_SPARSE_FILE;
+        let _ = path;
+        metadata.file_attributes() & FILE_ATTRIBUTE_SPARSE_FILE != 0
+    }
+    #[cfg(not(windows))]
+    {
+        allocated_size(path, metadata).is_some()
+    }
+}
+
+#[cfg(unix)]
+fn copy_range(source: &File, destination: &File, start: u64, end: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+
+    let mut buffer = vec![0u8; COPY_CHUNK_SIZE.min((end - start) as usize)];
+    let mut offset = start;
+    while offset < end {
+        let chunk_length = buffer.len().min((end - offset) as usize);
+        let read_bytes = source.read_at(&mut buffer[..chunk_length], offset)?;
+        if read_bytes == 0 {
+            break;
+        }
+        destination.write_all_at(&buffer[..read_bytes], offset)?;
+        offset += read_bytes as u64;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn copy_range(source: &File, destination: &File, start: u64, end: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    let mut buffer = vec![0u8; COPY_CHUNK_SIZE.min((end - start) as usize)];
+    let mut offset = start;
+    while offset < end {
+        let chunk_length = buffer.len().min((end - offset) as usize);
+        let read_bytes = source.seek_read(&mut buffer[..chunk_length], offset)?;
+        if read_bytes == 0 {
+            break;
+        }
+        let mut written = 0;
+        while written < read_bytes {
+            written +=
+                destination.seek_write(&buffer[written..read_bytes], offset + written as u64)?;
+        }
+        offset += read_bytes as u64;
+    }
+    Ok(())
+}
+
+/// The ranges of `file` that hold data, found with `SEEK_DATA` and
+/// `SEEK_HOLE`
+#[cfg(unix)]
+fn data_ranges(file: &File, length: u64) -> io::Result<Vec<(u64, u64)>> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut ranges = Vec::new();
+    let mut offset: u64 = 0;
+    while offset < length {
+        let data_start = unsafe { libc::lseek(file.as_raw_fd(), offset as _, libc::SEEK_DATA) };
+        if data_start < 0 {
+            let error = io::Error::last_os_error();
+            // Only a hole is left
+            if error.raw_os_error() == Some(libc::ENXIO) {
+                break;
+            }
+            return Err(error);
+        }
+        let data_end = unsafe { libc::lseek(file.as_raw_fd(), data_start, libc::SEEK_HOLE) };
+        if data_end < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        ranges.push((data_start as u64, data_end as u64));
+        offset = data_end as u64;
+    }
+    Ok(ranges)
+}
+
+#[cfg(windows)]
+fn file_control(file: &File, code: u32, input: &[u8], output: &mut [u8]) -> io::Result<u32> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    let mut returned = 0u32;
+    let succeeded = unsafe {
+        DeviceIoControl(
+            file.as_raw_handle() as _,
+            code,
+            input.as_ptr() as _,
+            input.len() as u32,
+            output.as_mut_ptr() as _,
+            output.len() as u32,
+            &mut returned,
+            std::ptr::null_mut(),
+        )
+    };
+
+    if succeeded == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(returned)
+}
+
+/// The allocated ranges of `file`, from `FSCTL_QUERY_ALLOCATED_RANGES`
+#[cfg(windows)]
+fn data_ranges(file: &File, length: u64) -> io::Result<Vec<(u64, u64)>> {
+    use windows_sys::Win32::Foundation::ERROR_MORE_DATA;
+    use windows_sys::Win32::System::Ioctl::{
+        FILE_ALLOCATED_RANGE_BUFFER, FSCTL_QUERY_ALLOCATED_RANGES,
+    };
+
+    const RANGES_PER_CALL: usize = 512;
+    let range_size = std::mem::size_of::<FILE_ALLOCATED_RANGE_BUFFER>();
+
+    let mut ranges = Vec::new();
+    let mut offset: u64 = 0;
+    let mut output = vec![0u8; range_size * RANGES_PER_CALL];
+    while offset < length {
+        let query = FILE_ALLOCATED_RANGE_BUFFER {
+            FileOffset: offset as i64,
+            Length: (length - offset) as i64,
+        };
+        let input = unsafe {
+            std::slice::from_raw_parts(
+                &query as *const FILE_ALLOCATED_RANGE_BUFFER as *const u8,
+                range_size,
+            )
+        };
+
+        let (returned, has_more) =
+            match file_control(file, FSCTL_QUERY_ALLOCATED_RANGES, input, &mut output) {
+                Ok(returned) => (returned, false),
+                Err(error) if error.raw_os_error() == Some(ERROR_MORE_DATA as i32) => {
+                    (output.len() as u32, true)
+                }
+                Err(error) => return Err(error),
+            };
+
+        let found: Vec<(u64, u64)> = output[..returned as usize]
+            .chunks_exact(range_size)
+            .map(|bytes| {
+                let range = unsafe {
+                    std::ptr::read_unaligned(bytes.as_ptr() as *const FILE_ALLOCATED_RANGE_BUFFER)
+                };
+                let start = range.FileOffset as u64;
+                (start, start + range.Length as u64)
+            })
+            .collect();
+        let Some(&(_, last_end)) = found.last() else {
+            break;
+        };
+        ranges.extend(found);
+        if !has_more {
+            break;
+        }
+        offset = last_end;
+    }
+    Ok(ranges)
+}
+
+/// Marks a new file sparse, so ranges left unwritten stay holes
+#[cfg(windows)]
+fn mark_sparse(file: &File) -> io::Result<()> {
+    use windows_sys::Win32::System::Ioctl::FSCTL_SET_SPARSE;
+    file_control(file, FSCTL_SET_SPARSE, &[], &mut []).map(|_| ())
+}
+
+// Unix file systems leave ranges that are never written unallocated
+#[cfg(unix)]
+fn mark_sparse(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// Copies `source` to `destination` with its holes when it is sparse.
+/// Returns `false` without leaving anything behind for other files, and
+/// when the holes can't be read or the destination can't hold them, such
+/// as FAT32 drives and many network shares. Those are copied the usual way.
+#[cfg(any(unix, windows))]
+pub fn copy_if_sparse(source: &Path, destination: &Path) -> io::Result<bool> {
+    let metadata = fs::metadata(source)?;
+    if !is_sparse(source, &metadata) {
+        return Ok(false);
+    }
+
+    let source_file = File::open(source)?;
+    let length = metadata.len();
+    let ranges = match data_ranges(&source_file, length) {
+        Ok(ranges) => ranges,
+        Err(error) => {
+            tracing::debug!("Copying {} without its holes: {}", source.display(), error);
+            return Ok(false);
+        }
+    };
+
+    let destination_file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(destination)?;
+    if let Err(error) = mark_sparse(&destination_file) {
+        tracing::debug!("Copying {} without its holes: {}", source.display(), error);
+        drop(destination_file);
+        let _ = fs::remove_file(destination);
+        return Ok(false);
+    }
+
+    let result = (|| {
+        destination_file.set_len(length)?;
+        for (start, end) in ranges {
+            copy_range(&source_file, &destination_file, start, end.min(length))?;
+        }
+        destination_file.sync_all()?;
+        destination_file.set_permissions(metadata.permissions())
+    })();
+
+    if let Err(error) = result {
+        drop(destination_file);
+        let _ = fs::remove_file(destination);
+        return Err(error);
+    }
+    Ok(true)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn copy_if_sparse(_source: &Path, _destination: &Path) -> io::Result<bool> {
+    Ok(false)
+}
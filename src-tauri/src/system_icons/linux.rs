@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Icon lookup through the freedesktop icon theme specification.
+//! Only PNG icons are used; themes that ship SVG only fall back to hicolor.
+
+use once_cell::sync::Lazy;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const FALLBACK_THEME: &str = "hicolor";
+const ICON_CONTEXTS: [&str; 4] = ["mimetypes", "places", "apps", "devices"];
+
+// Theme search chain: the user's theme, the themes it inherits from, hicolor
+static THEME_CHAIN: Lazy<Vec<String>> = Lazy::new(|| {
+    let mut chain: Vec<String> = Vec::new();
+    let mut pending: Vec<String> = current_theme().into_iter().collect();
+
+    while let Some(theme) = pending.pop() {
+        if chain.contains(&theme) || theme == FALLBACK_THEME {
+            continue;
+        }
+
+        let mut parents = theme_parents(&theme);
+        parents.reverse();
+        pending.extend(parents);
+        chain.push(theme);
+    }
+
+    chain.push(FALLBACK_THEME.to_string());
+    chain
+});
+
+fn icon_base_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+
+    if let Ok(home) = env::var("HOME") {
+        dirs.push(PathBuf::from(&home).join(".icons"));
+    }
+
+    if let Ok(data_home) = env::var("XDG_DATA_HOME") {
+        dirs.push(PathBuf::from(data_home).join("icons"));
+    } else if let Ok(home) = env::var("HOME") {
+        dirs.push(PathBuf::from(home).join(".local/share/icons"));
+    }
+
+    match env::var("XDG_DATA_DIRS") {
+        Ok(data_dirs) => {
+            for entry in data_dirs.split(':').filter(|entry| !entry.is_empty()) {
+                dirs.push(PathBuf::from(entry).join("icons"));
+            }
+        }
+        Err(_) => {
+            dirs.push(PathBuf::from("/usr/local/share/icons"));
+            dirs.push(PathBuf::from("/usr/share/icons"));
+        }
+    }
+
+    dirs
+}
+
+fn current_theme() -> Option<String> {
+    let gsettings_theme = Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.interface", "icon-theme"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .trim_matches('\'')
+                .to_string()
+        })
+        .filter(|theme| !theme.is_empty());
+
+    gsettings_theme.or_else(|| {
+        let home = env::var("HOME").ok()?;
+        let settings =
+            fs::read_to_string(Path::new(&home).join(".config/gtk-3.0/settings.ini")).ok()?;
+
+        settings.lines().find_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "gtk-icon-theme-name").then(|| value.trim().to_string())
+        })
+    })
+}
+
+fn theme_parents(theme: &str) -> Vec<String> {
+    icon_base_dirs()
+        .into_iter()
+        .map(|base| base.join(theme).join("index.theme"))
+        .find_map(|index_path| fs::read_to_string(index_path).ok())
+        .and_then(|index| {
+            index.lines().find_map(|line| {
+                line.strip_prefix("Inherits=").map(|value| {
+                    value
+                        .split(',')
+                        .map(|parent| parent.trim().to_string())
+                        .filter(|parent| !parent.is_empty())
+                        .collect()
+                })
+            })
+        })
+        .unwrap_or_default()
+}
+
+// Candidate icon names from most to least specific, e.g. "image-png",
+// "image-x-generic"
+fn icon_names(path: &Path) -> Vec<String> {
+    if path.is_dir() {
+        return vec!["folder".to_string(), "inode-directory".to_string()];
+    }
+
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+
+    let mut names: Vec<String> = Vec::new();
+
+    if let Some(mime) = crate::dir_reader::get_mime_type(&extension) {
+        names.push(mime.replace('/', "-"));
+
+        if let Some((media_type, _)) = mime.split_once('/') {
+            names.push(format!("{}-x-generic", media_type));
+        }
+    }
+
+    names.push("text-x-generic".to_string());
+    names.push("application-x-generic".to_string());
+    names
+}
+
+fn find_icon_file(name: &str, size: u16) -> Option<PathBuf> {
+    let sizes: Vec<u16> = std::iter::once(size)
+        .chain([48, 64, 32, 128, 256, 24, 16])
+        .collect();
+    let base_dirs = icon_base_dirs();
+
+    for theme in THEME_CHAIN.iter() {
+        for base in &base_dirs {
+            let theme_dir = base.join(theme);
+
+            if !theme_dir.is_dir() {
+                continue;
+            }
+
+            for icon_size in &sizes {
+                let size_dir = format!("{}x{}", icon_size, icon_size);
+
+                for context in ICON_CONTEXTS {
+                    // Both "48x48/mimetypes" and "mimetypes/48" layouts are in use
+                    for candidate in [
+                        theme_dir.join(&size_dir).join(context),
+                        theme_dir.join(context).join(icon_size.to_string()),
+                    ] {
+                        let icon_path = candidate.join(format!("{}.png", name));
+
+                        if icon_path.is_file() {
+                            return Some(icon_path);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+pub fn themed_icon_png(path: &Path, size: u16) -> Option<Vec<u8>> {
+    let icon_path = icon_names(path)
+        .iter()
+        .find_map(|name| find_icon_file(name, size))?;
+
+    let image = image::open(&icon_path).ok()?;
+
+    if image.width() == size as u32 && image.height() == size as u32 {
+        return fs::read(&icon_path).ok();
+    }
+
+    let resized = image.resize(
+        size as u32,
+        size as u32,
+        image::imageops::FilterType::Lanczos3,
+    );
+    let mut png_bytes: Vec<u8> = Vec::new();
+
+    resized
+        .write_to(
+            &mut std::io::Cursor::new(&mut png_bytes),
+            image::ImageFormat::Png,
+        )
+        .ok()?;
+
+    Some(png_bytes)
+}
@@ -0,0 +1,305 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
+use file_icon_provider::get_file_icon as get_provider_icon;
+use image::codecs::png::PngEncoder;
+use image::ImageEncoder;
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tauri::Manager;
+
+const ICON_CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(512).unwrap();
+
+static ICON_DATA_URL_CACHE: Lazy<Mutex<LruCache<String, String>>> =
+    Lazy::new(|| Mutex::new(LruCache::new(ICON_CACHE_CAPACITY)));
+
+fn has_unique_icon(extension: &Option<String>) -> bool {
+    #[cfg(windows)]
+    let unique_icon_extensions = [
+        "exe", "dll", "ico", "lnk", "scr", "cpl", "msi", "appx", "msix",
+    ];
+
+    #[cfg(target_os = "linux")]
+    let unique_icon_extensions = ["desktop", "appimage"];
+
+    #[cfg(target_os = "macos")]
+    let unique_icon_extensions = ["icns"];
+
+    extension
+        .as_ref()
+        .map(|ext| unique_icon_extensions.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn file_icon_cache_key(path: &str, is_dir: bool, extension: &Option<String>, size: u16) -> String {
+    if is_dir {
+        return format!("dir:{}:{size}", path.to_lowercase());
+    }
+
+    let normalized_extension = extension
+        .as_ref()
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default();
+
+    if has_unique_icon(extension) {
+        format!("path:{}:{size}", path.to_lowercase())
+    } else {
+        format!("ext:{normalized_extension}:{size}")
+    }
+}
+
+fn build_dummy_path_for_extension(extension: &Option<String>) -> PathBuf {
+    let file_name = match extension
+        .as_ref()
+        .map(|ext| ext.trim())
+        .filter(|ext| !ext.is_empty())
+    {
+        Some(extension_value) => format!("file.{extension_value}"),
+        None => "file".to_string(),
+    };
+    PathBuf::from(file_name)
+}
+
+fn encode_icon_to_png(width: u32, height: u32, pixels: Vec<u8>) -> Result<Vec<u8>, String> {
+    if width == 0 || height == 0 {
+        return Err("Invalid icon dimensions".to_string());
+    }
+
+    let expected_len = width
+        .checked_mul(height)
+        .and_then(|value| value.checked_mul(4))
+        .ok_or_else(|| "Icon dimensions overflow".to_string())? as usize;
+
+    if pixels.len() != expected_len {
+        return Err("Unexpected icon pixel buffer length".to_string());
+    }
+
+    let mut png_bytes: Vec<u8> = Vec::new();
+    let png_encoder = PngEncoder::new(&mut png_bytes);
+    png_encoder
+        .write_image(&pixels, width, height, image::ExtendedColorType::Rgba8)
+        .map_err(|error| error.to_string())?;
+
+    Ok(png_bytes)
+}
+
+fn encode_icon_to_png_data_url(width: u32, height: u32, pixels: Vec<u8>) -> Result<String, String> {
+    let png_bytes = encode_icon_to_png(width, height, pixels)?;
+    let base64_png = BASE64_STANDARD.encode(png_bytes);
+    Ok(format!("data:image/png;base64,{base64_png}"))
+}
+
+fn get_icon_data_url_uncached(path: &Path, size: u16) -> Result<String, String> {
+    let icon = get_provider_icon(path, size).map_err(|error| error.to_string())?;
+    encode_icon_to_png_data_url(icon.width, icon.height, icon.pixels)
+}
+
+fn get_icon_png_uncached(path: &Path, size: u16) -> Result<Vec<u8>, String> {
+    // Prefer the freedesktop icon theme, the provider has no theme support
+    #[cfg(target_os = "linux")]
+    if let Some(png_bytes) = linux::themed_icon_png(path, size) {
+        return Ok(png_bytes);
+    }
+
+    let icon = get_provider_icon(path, size).map_err(|error| error.to_string())?;
+    encode_icon_to_png(icon.width, icon.height, icon.pixels)
+}
+
+// Icons that depend on the file itself are keyed by its modification time
+// too, so a rebuilt executable or edited shortcut gets a fresh icon
+fn icon_disk_cache_path(cache_dir: &Path, path: &Path, cache_key: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    cache_key.hash(&mut hasher);
+
+    if cache_key.starts_with("path:") {
+        let modified_time = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        modified_time.hash(&mut hasher);
+    }
+
+    cache_dir.join(format!("{:016x}.png", hasher.finish()))
+}
+
+fn cached_icon_png(cache_dir: &Path, path: &str, icon_size: u16) -> Result<Vec<u8>, String> {
+    let icon_path = crate::utils::to_os_path(path);
+    let is_dir = icon_path.is_dir();
+    let extension = if is_dir {
+        None
+    } else {
+        icon_path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_string())
+    };
+
+    let cache_key = file_icon_cache_key(path, is_dir, &extension, icon_size);
+    let cache_path = icon_disk_cache_path(cache_dir, &icon_path, &cache_key);
+
+    let cached_png = std::fs::read(&cache_path).ok();
+    crate::metrics::record_cache_lookup("icons_disk", cached_png.is_some());
+
+    if let Some(png_bytes) = cached_png {
+        return Ok(png_bytes);
+    }
+
+    let source_path = if icon_path.exists() {
+        icon_path
+    } else {
+        build_dummy_path_for_extension(&extension)
+    };
+
+    let png_bytes = get_icon_png_uncached(&source_path, icon_size)?;
+
+    if std::fs::create_dir_all(cache_dir).is_ok() {
+        let _ = std::fs::write(&cache_path, &png_bytes);
+    }
+
+    Ok(png_bytes)
+}
+
+/// Returns the OS-native icon of a file, folder or application as raw PNG
+/// bytes. Icons are cached on disk in the app cache directory.
+#[tauri::command]
+pub async fn get_file_icon(
+    app: tauri::AppHandle,
+    path: String,
+    size: Option<u16>,
+) -> Result<tauri::ipc::Response, String> {
+    let _timer = crate::metrics::time_command("get_file_icon");
+    let icon_size = size.unwrap_or(32).clamp(8, 256);
+    let cache_dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|error: tauri::Error| error.to_string())?
+        .join("icons");
+
+    let png_bytes =
+        tokio::task::spawn_blocking(move || cached_icon_png(&cache_dir, &path, icon_size))
+            .await
+            .map_err(|error| error.to_string())??;
+
+    Ok(tauri::ipc::Response::new(png_bytes))
+}
+
+const DRAG_PREVIEW_ICON_SIZE: u16 = 48;
+const DRAG_PREVIEW_MAX_LAYERS: usize = 3;
+const DRAG_PREVIEW_LAYER_OFFSET: u32 = 6;
+
+/// Renders the image shown under the cursor while dragging items out to
+/// other applications: the icon of the first item, stacked over the icons of
+/// up to two more. Returns the path of the PNG, which the native drag APIs
+/// expect instead of image data.
+#[tauri::command]
+pub async fn get_drag_preview(app: tauri::AppHandle, paths: Vec<String>) -> Result<String, String> {
+    let app_cache_dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|error: tauri::Error| error.to_string())?;
+
+    tokio::task::spawn_blocking(move || {
+        let icon_cache_dir = app_cache_dir.join("icons");
+        let layers: Vec<image::RgbaImage> = paths
+            .iter()
+            .take(DRAG_PREVIEW_MAX_LAYERS)
+            .filter_map(|path| cached_icon_png(&icon_cache_dir, path, DRAG_PREVIEW_ICON_SIZE).ok())
+            .filter_map(|png_bytes| image::load_from_memory(&png_bytes).ok())
+            .map(|icon| {
+                image::imageops::resize(
+                    &icon.to_rgba8(),
+                    DRAG_PREVIEW_ICON_SIZE as u32,
+                    DRAG_PREVIEW_ICON_SIZE as u32,
+                    image::imageops::FilterType::Triangle,
+                )
+            })
+            .collect();
+
+        if layers.is_empty() {
+            return Err("No icons available for the dragged items".to_string());
+        }
+
+        let canvas_size =
+            DRAG_PREVIEW_ICON_SIZE as u32 + DRAG_PREVIEW_LAYER_OFFSET * (layers.len() as u32 - 1);
+        let mut canvas = image::RgbaImage::new(canvas_size, canvas_size);
+
+        // Draw back to front so the first item ends up on top
+        for (index, layer) in layers.iter().enumerate().rev() {
+            let offset = (DRAG_PREVIEW_LAYER_OFFSET * index as u32) as i64;
+            image::imageops::overlay(&mut canvas, layer, offset, offset);
+        }
+
+        let mut hasher = DefaultHasher::new();
+        paths
+            .iter()
+            .take(DRAG_PREVIEW_MAX_LAYERS)
+            .for_each(|path| path.hash(&mut hasher));
+
+        let preview_dir = app_cache_dir.join("drag");
+        std::fs::create_dir_all(&preview_dir).map_err(|error| error.to_string())?;
+        let preview_path = preview_dir.join(format!("{:016x}.png", hasher.finish()));
+
+        let png_bytes = encode_icon_to_png(canvas_size, canvas_size, canvas.into_raw())?;
+        std::fs::write(&preview_path, png_bytes).map_err(|error| error.to_string())?;
+
+        Ok(preview_path.to_string_lossy().to_string())
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub fn get_system_icon(
+    path: String,
+    is_dir: bool,
+    extension: Option<String>,
+    size: Option<u16>,
+) -> Result<Option<String>, String> {
+    let icon_size = size.unwrap_or(32).clamp(8, 256);
+    let cache_key = file_icon_cache_key(&path, is_dir, &extension, icon_size);
+
+    if let Ok(mut cache) = ICON_DATA_URL_CACHE.lock() {
+        let cached_value = cache.get(&cache_key);
+        crate::metrics::record_cache_lookup("icons_memory", cached_value.is_some());
+
+        if let Some(cached_value) = cached_value {
+            return Ok(Some(cached_value.to_string()));
+        }
+    }
+
+    let icon_path = if is_dir {
+        crate::utils::to_os_path(&path)
+    } else {
+        let normalized_path = crate::utils::to_os_path(&path);
+        if normalized_path.exists() {
+            normalized_path
+        } else {
+            build_dummy_path_for_extension(&extension)
+        }
+    };
+
+    let data_url_result = get_icon_data_url_uncached(&icon_path, icon_size);
+
+    match data_url_result {
+        Ok(data_url) => {
+            if let Ok(mut cache) = ICON_DATA_URL_CACHE.lock() {
+                cache.put(cache_key, data_url.clone());
+            }
+            Ok(Some(data_url))
+        }
+        Err(_) => Ok(None),
+    }
+}
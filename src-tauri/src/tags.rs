@@ -0,0 +1,267 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use crate::app_windows::notify_store_changed;
+use crate::dir_reader::DirEntry;
+use crate::json_store;
+use crate::utils::normalize_path;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+const STORE_NAME: &str = "tags";
+
+#[cfg(target_os = "macos")]
+const FINDER_TAGS_ATTRIBUTE: &str = "com.apple.metadata:_kMDItemUserTags";
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const XDG_TAGS_ATTRIBUTE: &str = "user.xdg.tags";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileTag {
+    pub name: String,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaggedItem {
+    pub path: String,
+    pub tags: Vec<FileTag>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TagStore {
+    #[serde(default)]
+    paths: HashMap<String, Vec<FileTag>>,
+}
+
+static TAG_STORE: Lazy<Mutex<TagStore>> = Lazy::new(|| Mutex::new(json_store::load(STORE_NAME)));
+
+fn is_same_or_descendant(path: &str, ancestor: &str) -> bool {
+    path == ancestor
+        || path
+            .strip_prefix(ancestor)
+            .map(|rest| rest.starts_with('/'))
+            .unwrap_or(false)
+}
+
+fn tag_names_match(first: &str, second: &str) -> bool {
+    first.to_lowercase() == second.to_lowercase()
+}
+
+fn clean_tags(tags: Vec<FileTag>) -> Vec<FileTag> {
+    let mut cleaned: Vec<FileTag> = Vec::new();
+
+    for tag in tags {
+        let name = tag.name.trim().to_string();
+
+        if name.is_empty()
+            || cleaned
+                .iter()
+                .any(|existing| tag_names_match(&existing.name, &name))
+        {
+            continue;
+        }
+
+        cleaned.push(FileTag {
+            name,
+            color: tag.color.filter(|color| !color.is_empty()),
+        });
+    }
+
+    cleaned
+}
+
+// Finder tags are stored as "name\n<color index>" in a binary plist
+#[cfg(target_os = "macos")]
+fn finder_color_index(color: &Option<String>) -> u8 {
+    match color.as_deref().map(|color| color.to_lowercase()).as_deref() {
+        Some("gray") | Some("grey") => 1,
+        Some("green") => 2,
+        Some("purple") => 3,
+        Some("blue") => 4,
+        Some("yellow") => 5,
+        Some("red") => 6,
+        Some("orange") => 7,
+        _ => 0,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn mirror_to_attributes(path: &str, tags: &[FileTag]) -> Result<(), String> {
+    if tags.is_empty() {
+        let _ = crate::xattrs::remove_xattr(path.to_string(), FINDER_TAGS_ATTRIBUTE.to_string());
+        return Ok(());
+    }
+
+    let labels: Vec<String> = tags
+        .iter()
+        .map(|tag| format!("{}\n{}", tag.name, finder_color_index(&tag.color)))
+        .collect();
+
+    let mut value: Vec<u8> = Vec::new();
+    plist::to_writer_binary(&mut value, &labels).map_err(|error| error.to_string())?;
+
+    crate::xattrs::set_xattr(path.to_string(), FINDER_TAGS_ATTRIBUTE.to_string(), value)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn mirror_to_attributes(path: &str, tags: &[FileTag]) -> Result<(), String> {
+    if tags.is_empty() {
+        let _ = crate::xattrs::remove_xattr(path.to_string(), XDG_TAGS_ATTRIBUTE.to_string());
+        return Ok(());
+    }
+
+    let value = tags
+        .iter()
+        .map(|tag| tag.name.replace(',', " "))
+        .collect::<Vec<String>>()
+        .join(",");
+
+    crate::xattrs::set_xattr(path.to_string(), XDG_TAGS_ATTRIBUTE.to_string(), value.into_bytes())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "android")))]
+fn mirror_to_attributes(_path: &str, _tags: &[FileTag]) -> Result<(), String> {
+    Ok(())
+}
+
+/// Fills in `tags` for directory listing entries
+pub fn attach_tags(entries: &mut [DirEntry]) {
+    let store = match TAG_STORE.lock() {
+        Ok(store) => store,
+        Err(_) => return,
+    };
+
+    if store.paths.is_empty() {
+        return;
+    }
+
+    for entry in entries.iter_mut() {
+        entry.tags = store.paths.get(&entry.path).cloned();
+    }
+}
+
+/// Moves tags of a renamed or moved item (and anything below it) to its new path
+pub fn handle_path_moved(from: &Path, to: &Path) {
+    let from = normalize_path(&from.to_string_lossy());
+    let to = normalize_path(&to.to_string_lossy());
+
+    let mut store = match TAG_STORE.lock() {
+        Ok(store) => store,
+        Err(_) => return,
+    };
+
+    let moved_keys: Vec<String> = store
+        .paths
+        .keys()
+        .filter(|key| is_same_or_descendant(key, &from))
+        .cloned()
+        .collect();
+
+    if moved_keys.is_empty() {
+        return;
+    }
+
+    for key in moved_keys {
+        if let Some(tags) = store.paths.remove(&key) {
+            let new_key = format!("{}{}", to, &key[from.len()..]);
+            store.paths.insert(new_key, tags);
+        }
+    }
+
+    let _ = json_store::save(STORE_NAME, &*store);
+    notify_store_changed(STORE_NAME, None);
+}
+
+/// Drops tags of a deleted item and anything below it
+pub fn handle_path_removed(path: &Path) {
+    let path = normalize_path(&path.to_string_lossy());
+
+    let mut store = match TAG_STORE.lock() {
+        Ok(store) => store,
+        Err(_) => return,
+    };
+
+    let count_before = store.paths.len();
+    store.paths.retain(|key, _| !is_same_or_descendant(key, &path));
+
+    if store.paths.len() != count_before {
+        let _ = json_store::save(STORE_NAME, &*store);
+        notify_store_changed(STORE_NAME, None);
+    }
+}
+
+/// Replaces the tags of a path. An empty list removes all tags.
+/// With `mirror`, tags are also written as Finder tags (macOS) or
+/// `user.xdg.tags` (Linux) so other apps can see them.
+#[tauri::command]
+pub fn set_tags(
+    window: tauri::WebviewWindow,
+    path: String,
+    tags: Vec<FileTag>,
+    mirror: Option<bool>,
+) -> Result<(), String> {
+    if !Path::new(&path).exists() {
+        return Err(format!("Path does not exist: {}", path));
+    }
+
+    let key = normalize_path(&path);
+    let tags = clean_tags(tags);
+
+    {
+        let mut store = TAG_STORE.lock().map_err(|error| error.to_string())?;
+
+        if tags.is_empty() {
+            store.paths.remove(&key);
+        } else {
+            store.paths.insert(key, tags.clone());
+        }
+
+        json_store::save(STORE_NAME, &*store)?;
+    }
+
+    notify_store_changed(STORE_NAME, Some(window.label()));
+
+    if mirror.unwrap_or(false) {
+        mirror_to_attributes(&path, &tags)
+            .map_err(|error| format!("Tags saved, but mirroring to file attributes failed: {}", error))?;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_tags(path: String) -> Result<Vec<FileTag>, String> {
+    let store = TAG_STORE.lock().map_err(|error| error.to_string())?;
+
+    Ok(store
+        .paths
+        .get(&normalize_path(&path))
+        .cloned()
+        .unwrap_or_default())
+}
+
+/// Returns existing items carrying the tag (matched case-insensitively)
+#[tauri::command]
+pub fn query_by_tag(tag: String) -> Result<Vec<TaggedItem>, String> {
+    let tag = tag.trim().to_string();
+    let store = TAG_STORE.lock().map_err(|error| error.to_string())?;
+
+    let mut items: Vec<TaggedItem> = store
+        .paths
+        .iter()
+        .filter(|(_, tags)| tags.iter().any(|existing| tag_names_match(&existing.name, &tag)))
+        .filter(|(path, _)| Path::new(path.as_str()).exists())
+        .map(|(path, tags)| TaggedItem {
+            path: path.clone(),
+            tags: tags.clone(),
+        })
+        .collect();
+
+    items.sort_by(|first, second| first.path.cmp(&second.path));
+    Ok(items)
+}
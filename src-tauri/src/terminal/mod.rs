@@ -34,12 +34,29 @@ pub fn get_available_terminals() -> GetAvailableTerminalsResult {
     }
 }
 
+/// With a `view_id`, `directory_path` may be relative to the view's current
+/// directory, or empty for the directory itself
 #[tauri::command]
 pub fn open_terminal(
+    window: tauri::WebviewWindow,
     directory_path: String,
     terminal_id: String,
     as_admin: bool,
+    view_id: Option<String>,
 ) -> OpenTerminalResult {
+    let directory_path = match crate::view_context::resolve_path(
+        window.label(),
+        view_id.as_deref(),
+        &directory_path,
+    ) {
+        Ok(directory_path) => directory_path,
+        Err(error) => {
+            return OpenTerminalResult {
+                success: false,
+                error: Some(error),
+            }
+        }
+    };
     let path = Path::new(&directory_path);
     if !path.exists() || !path.is_dir() {
         return OpenTerminalResult {
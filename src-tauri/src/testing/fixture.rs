@@ -0,0 +1,299 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Temporary directory trees for tests. A `Scenario` describes the tree,
+//! `build` creates it in a unique folder under the system temp directory,
+//! and the returned `Fixture` removes it again when dropped.
+//!
+//! Items a platform can't create (symlinks without the privilege on
+//! Windows, names Windows forbids) are skipped and listed in
+//! `Fixture::skipped`, so one scenario can be shared across platforms.
+
+use crate::utils::normalize_path;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+enum Item {
+    Dir(PathBuf),
+    File(PathBuf, Vec<u8>),
+    HiddenFile(PathBuf),
+    Symlink { link: PathBuf, target: PathBuf },
+    SparseFile(PathBuf, u64),
+}
+
+/// Names that trip up path handling, created by `Scenario::weird_names`
+pub fn weird_names() -> Vec<String> {
+    let mut names = vec![
+        "with space.txt".to_string(),
+        "  leading spaces.txt".to_string(),
+        "ünïcödé.txt".to_string(),
+        "日本語のファイル.txt".to_string(),
+        "emoji 😀.txt".to_string(),
+        "#hash %percent &amp.txt".to_string(),
+        "-leading-dash".to_string(),
+        "no_extension".to_string(),
+        "many.dots.in.name.tar.gz".to_string(),
+        // Longest name most file systems allow, in bytes
+        format!("{}.txt", "a".repeat(251)),
+        // Decomposed é, which some file systems normalize
+        "cafe\u{301}.txt".to_string(),
+    ];
+
+    if !cfg!(windows) {
+        names.extend(
+            [
+                "trailing dot.",
+                "trailing space ",
+                "colon:name.txt",
+                "back\\slash.txt",
+                "question?.txt",
+                "new\nline.txt",
+                "CON",
+            ]
+            .map(str::to_string),
+        );
+    }
+
+    names
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Scenario {
+    items: Vec<Item>,
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Scenario::default()
+    }
+
+    pub fn dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.items.push(Item::Dir(path.into()));
+        self
+    }
+
+    pub fn file(mut self, path: impl Into<PathBuf>, contents: impl AsRef<[u8]>) -> Self {
+        self.items
+            .push(Item::File(path.into(), contents.as_ref().to_vec()));
+        self
+    }
+
+    /// A file hidden the platform's way: callers pass a dot-prefixed name,
+    /// and on Windows the hidden attribute is set as well
+    pub fn hidden_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.items.push(Item::HiddenFile(path.into()));
+        self
+    }
+
+    /// A symlink to `target`, relative to the fixture root. The target
+    /// doesn't have to exist, which makes a broken link.
+    pub fn symlink(mut self, link: impl Into<PathBuf>, target: impl Into<PathBuf>) -> Self {
+        self.items.push(Item::Symlink {
+            link: link.into(),
+            target: target.into(),
+        });
+        self
+    }
+
+    /// A file of `size` bytes with no data written, so file systems that
+    /// support it store it sparse
+    pub fn sparse_file(mut self, path: impl Into<PathBuf>, size: u64) -> Self {
+        self.items.push(Item::SparseFile(path.into(), size));
+        self
+    }
+
+    /// `depth` levels of nested folders, each holding `breadth` files and
+    /// the next level
+    pub fn deep_tree(mut self, path: impl Into<PathBuf>, depth: usize, breadth: usize) -> Self {
+        let mut level = path.into();
+
+        for depth_index in 0..depth {
+            level = level.join(format!("level-{}", depth_index));
+            self.items.push(Item::Dir(level.clone()));
+
+            for file_index in 0..breadth {
+                self.items.push(Item::File(
+                    level.join(format!("file-{}.txt", file_index)),
+                    format!("{}:{}", depth_index, file_index).into_bytes(),
+                ));
+            }
+        }
+
+        self
+    }
+
+    /// A folder with one file for each of `weird_names`
+    pub fn weird_names(mut self, path: impl Into<PathBuf>) -> Self {
+        let folder = path.into();
+        self.items.push(Item::Dir(folder.clone()));
+
+        for name in weird_names() {
+            self.items
+                .push(Item::File(folder.join(&name), name.into_bytes()));
+        }
+
+        self
+    }
+
+    pub fn build(self) -> io::Result<Fixture> {
+        let root = std::env::temp_dir().join(format!("sigma-fixture-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&root)?;
+
+        let mut fixture = Fixture {
+            root,
+            skipped: Vec::new(),
+        };
+
+        for item in self.items {
+            if let Err(error) = fixture.create(&item) {
+                let path = match &item {
+                    Item::Dir(path)
+                    | Item::File(path, _)
+                    | Item::HiddenFile(path)
+                    | Item::SparseFile(path, _) => path.clone(),
+                    Item::Symlink { link, .. } => link.clone(),
+                };
+
+                fixture
+                    .skipped
+                    .push((normalize_path(&path.to_string_lossy()), error.to_string()));
+            }
+        }
+
+        Ok(fixture)
+    }
+}
+
+#[derive(Debug)]
+pub struct Fixture {
+    root: PathBuf,
+    /// Items that couldn't be created on this platform, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+impl Fixture {
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn path(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    /// The path the way commands take and return it
+    pub fn uri(&self, relative: impl AsRef<Path>) -> String {
+        normalize_path(&self.path(relative).to_string_lossy())
+    }
+
+    fn create_parent(path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+    }
+
+    fn create(&self, item: &Item) -> io::Result<()> {
+        match item {
+            Item::Dir(path) => fs::create_dir_all(self.path(path)),
+            Item::File(path, contents) => {
+                let path = self.path(path);
+                Self::create_parent(&path)?;
+                fs::write(path, contents)
+            }
+            Item::HiddenFile(path) => {
+                let path = self.path(path);
+                Self::create_parent(&path)?;
+                File::create(&path)?;
+                set_hidden(&path)
+            }
+            Item::Symlink { link, target } => {
+                let link = self.path(link);
+                Self::create_parent(&link)?;
+                create_symlink(&self.path(target), &link)
+            }
+            Item::SparseFile(path, size) => {
+                let path = self.path(path);
+                Self::create_parent(&path)?;
+                let file = File::create(&path)?;
+                mark_sparse(&file)?;
+                file.set_len(*size)
+            }
+        }
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+// Needs Developer Mode or the symlink privilege
+#[cfg(windows)]
+fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    if target.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+#[cfg(not(windows))]
+fn set_hidden(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_hidden(path: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{SetFileAttributesW, FILE_ATTRIBUTE_HIDDEN};
+
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+
+    if unsafe { SetFileAttributesW(wide_path.as_ptr(), FILE_ATTRIBUTE_HIDDEN) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+// Unix file systems leave unwritten ranges unallocated on their own
+#[cfg(not(windows))]
+fn mark_sparse(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(windows)]
+fn mark_sparse(file: &File) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::System::Ioctl::FSCTL_SET_SPARSE;
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    let mut returned = 0u32;
+    let succeeded = unsafe {
+        DeviceIoControl(
+            file.as_raw_handle() as _,
+            FSCTL_SET_SPARSE,
+            std::ptr::null(),
+            0,
+            std::ptr::null_mut(),
+            0,
+            &mut returned,
+            std::ptr::null_mut(),
+        )
+    };
+
+    if succeeded == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
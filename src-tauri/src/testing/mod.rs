@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Support for integration tests, built with the `test-fixtures` feature:
+//! filesystem scenarios, fault injection, and the entry points of the
+//! subsystems under test.
+//!
+//! ```ignore
+//! use sigma_file_manager::testing::*;
+//!
+//! let fixture = Scenario::new()
+//!     .file("docs/readme.txt", "hello")
+//!     .hidden_file(".config")
+//!     .deep_tree("deep", 40, 2)
+//!     .build()?;
+//!
+//! inject_fault(FaultPoint::ReadDir, fixture.path("deep"), Fault::PermissionDenied, Some(1));
+//! assert!(read_dir(fixture.uri("deep"), None, None, None).is_err());
+//! assert!(read_dir(fixture.uri("deep"), None, None, None).is_ok());
+//! ```
+
+mod fixture;
+
+pub use crate::dir_reader::{read_dir_blocking as read_dir, DirContents, DirEntry};
+pub use crate::faults::{clear_faults, inject_fault, Fault, FaultPoint};
+pub use crate::file_operations::{
+    copy_items, delete_items, move_items, CopyOptions, FileOperationResult, RetryPolicy,
+};
+pub use fixture::{weird_names, Fixture, Scenario};
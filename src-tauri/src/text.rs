@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Unicode-aware name comparison. macOS file systems commonly hand out
+//! decomposed (NFD) names while other systems use composed (NFC) ones, so
+//! names are normalized before they're compared, sorted or searched.
+
+use icu_collator::options::{CollatorOptions, Strength};
+use icu_collator::{Collator, CollatorBorrowed};
+use icu_locale_core::Locale;
+use icu_normalizer::{ComposingNormalizerBorrowed, DecomposingNormalizerBorrowed};
+use once_cell::sync::Lazy;
+use std::cmp::Ordering;
+use std::sync::RwLock;
+
+static COLLATOR: Lazy<RwLock<CollatorBorrowed<'static>>> = Lazy::new(|| {
+    let locale = tauri_plugin_os::locale().unwrap_or_default();
+    RwLock::new(build_collator(&locale).unwrap_or_else(|_| root_collator()))
+});
+
+fn collator_options() -> CollatorOptions {
+    let mut options = CollatorOptions::default();
+    options.strength = Some(Strength::Tertiary);
+    options
+}
+
+fn build_collator(locale: &str) -> Result<CollatorBorrowed<'static>, String> {
+    let locale: Locale = locale
+        .replace('_', "-")
+        .parse()
+        .map_err(|_| format!("Invalid locale: {}", locale))?;
+
+    Collator::try_new((&locale).into(), collator_options()).map_err(|error| error.to_string())
+}
+
+fn root_collator() -> CollatorBorrowed<'static> {
+    Collator::try_new(Default::default(), collator_options())
+        .expect("root collation data is compiled in")
+}
+
+// Combining diacritical mark blocks
+fn is_combining_mark(character: char) -> bool {
+    matches!(
+        character,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+// Letters with a stroke don't decompose into base letter + mark
+fn fold_stroked_letter(character: char) -> char {
+    match character {
+        'ø' => 'o',
+        'ł' => 'l',
+        'đ' => 'd',
+        'ħ' => 'h',
+        'Ø' => 'O',
+        'Ł' => 'L',
+        'Đ' => 'D',
+        'Ħ' => 'H',
+        _ => character,
+    }
+}
+
+/// Converts a name to NFC so composed and decomposed forms compare equal
+pub fn normalize_name(value: &str) -> String {
+    ComposingNormalizerBorrowed::new_nfc()
+        .normalize(value)
+        .into_owned()
+}
+
+/// Removes diacritics but keeps case: "Crème Brûlée" becomes "Creme Brulee"
+pub fn strip_diacritics(value: &str) -> String {
+    let stripped: String = DecomposingNormalizerBorrowed::new_nfd()
+        .normalize(value)
+        .chars()
+        .filter(|character| !is_combining_mark(*character))
+        .map(fold_stroked_letter)
+        .collect();
+
+    normalize_name(&stripped)
+}
+
+/// Folds case and diacritics for search matching: "Café" and "cafe\u{301}"
+/// both become "cafe"
+pub fn fold_for_search(value: &str) -> String {
+    strip_diacritics(value).to_lowercase()
+}
+
+/// Compares names using the collation rules of the current locale
+pub fn compare_names(first: &str, second: &str) -> Ordering {
+    match COLLATOR.read() {
+        Ok(collator) => collator
+            .compare(first, second)
+            .then_with(|| first.cmp(second)),
+        Err(_) => normalize_name(first)
+            .to_lowercase()
+            .cmp(&normalize_name(second).to_lowercase()),
+    }
+}
+
+/// Switches name sorting to the collation rules of `locale` (BCP 47 tag)
+#[tauri::command]
+pub fn set_collation_locale(locale: String) -> Result<(), String> {
+    let collator = build_collator(&locale)?;
+    let mut current = COLLATOR.write().map_err(|error| error.to_string())?;
+    *current = collator;
+    Ok(())
+}
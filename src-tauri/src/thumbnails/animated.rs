@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{imageops, AnimationDecoder, Delay, Frame, Frames};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+pub struct AnimatedPreviewInfo {
+    pub frame_count: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+fn open_reader(path: &Path) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|error| error.to_string())
+}
+
+fn open_frames(path: &Path, extension: &str) -> Result<Option<Frames<'static>>, String> {
+    match extension {
+        "gif" => {
+            let decoder = GifDecoder::new(open_reader(path)?).map_err(|error| error.to_string())?;
+            Ok(Some(decoder.into_frames()))
+        }
+        "webp" => {
+            let decoder = WebPDecoder::new(open_reader(path)?).map_err(|error| error.to_string())?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            Ok(Some(decoder.into_frames()))
+        }
+        "png" | "apng" => {
+            let decoder = PngDecoder::new(open_reader(path)?).map_err(|error| error.to_string())?;
+            if !decoder.is_apng().map_err(|error| error.to_string())? {
+                return Ok(None);
+            }
+            let apng = decoder.apng().map_err(|error| error.to_string())?;
+            Ok(Some(apng.into_frames()))
+        }
+        _ => Ok(None),
+    }
+}
+
+pub fn is_animated(path: &Path, extension: &str) -> bool {
+    match open_frames(path, extension) {
+        Ok(Some(frames)) => frames.take(2).filter(|frame| frame.is_ok()).count() > 1,
+        _ => false,
+    }
+}
+
+fn scaled_dimensions(width: u32, height: u32, max_dimension: u32) -> (u32, u32) {
+    if width <= max_dimension && height <= max_dimension {
+        return (width, height);
+    }
+    let scale = max_dimension as f64 / width.max(height) as f64;
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+fn delay_millis(delay: Delay) -> u32 {
+    let (numerator, denominator) = delay.numer_denom_ms();
+    if denominator == 0 {
+        return 100;
+    }
+    (numerator / denominator).max(20)
+}
+
+fn scale_frame(buffer: image::RgbaImage, max_dimension: u32) -> image::RgbaImage {
+    let (width, height) = scaled_dimensions(buffer.width(), buffer.height(), max_dimension);
+    if width == buffer.width() && height == buffer.height() {
+        buffer
+    } else {
+        imageops::resize(&buffer, width, height, imageops::FilterType::Triangle)
+    }
+}
+
+/// Folds each run of `step` frames into its first one, which takes the
+/// delays of the others
+fn fold_frames(frames: Vec<(image::RgbaImage, u32)>, step: usize) -> Vec<(image::RgbaImage, u32)> {
+    let mut folded: Vec<(image::RgbaImage, u32)> = Vec::with_capacity(frames.len().div_ceil(step));
+    for (index, (buffer, delay)) in frames.into_iter().enumerate() {
+        match folded.last_mut() {
+            Some((_, kept_delay)) if index % step != 0 => *kept_delay += delay,
+            _ => folded.push((buffer, delay)),
+        }
+    }
+    folded
+}
+
+/// Re-encodes an animation as a downscaled GIF with at most `max_frames`
+/// frames. Long animations are sampled evenly and the skipped frames'
+/// delays are folded into the kept ones so playback speed is preserved.
+/// Every frame is decoded, but only up to twice `max_frames` are kept at
+/// a time: when that fills up, every other one is folded away and from
+/// then on only every `step`th frame is kept.
+pub fn write_animated_preview(
+    path: &Path,
+    extension: &str,
+    destination: &Path,
+    max_dimension: u32,
+    max_frames: usize,
+) -> Result<AnimatedPreviewInfo, String> {
+    let frames = open_frames(path, extension)?
+        .ok_or_else(|| format!("File is not animated: {}", path.display()))?;
+
+    let max_frames = max_frames.max(1);
+    let mut kept_frames: Vec<(image::RgbaImage, u32)> = Vec::new();
+    let mut step: usize = 1;
+
+    for (index, frame_result) in frames.enumerate() {
+        let frame = frame_result.map_err(|error| error.to_string())?;
+        let delay = delay_millis(frame.delay());
+
+        match kept_frames.last_mut() {
+            Some((_, kept_delay)) if index % step != 0 => *kept_delay += delay,
+            _ => kept_frames.push((scale_frame(frame.into_buffer(), max_dimension), delay)),
+        }
+
+        if kept_frames.len() == max_frames * 2 {
+            kept_frames = fold_frames(kept_frames, 2);
+            step *= 2;
+        }
+    }
+
+    if kept_frames.is_empty() {
+        return Err("Animation has no decodable frames".to_string());
+    }
+
+    let step = kept_frames.len().div_ceil(max_frames);
+    let scaled_frames = fold_frames(kept_frames, step);
+    let (width, height) = scaled_frames[0].0.dimensions();
+
+    let file = File::create(destination).map_err(|error| error.to_string())?;
+    let mut encoder = GifEncoder::new_with_speed(file, 10);
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(|error| error.to_string())?;
+
+    let mut frame_count: u32 = 0;
+
+    for (buffer, total_delay) in scaled_frames {
+        let frame = Frame::from_parts(buffer, 0, 0, Delay::from_numer_denom_ms(total_delay, 1));
+        encoder
+            .encode_frame(frame)
+            .map_err(|error| error.to_string())?;
+        frame_count += 1;
+    }
+
+    Ok(AnimatedPreviewInfo {
+        frame_count,
+        width,
+        height,
+    })
+}
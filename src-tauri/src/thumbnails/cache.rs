@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::Manager;
+use crate::utils::normalize_path;
+
+pub fn thumbnails_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let base_dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|error: tauri::Error| error.to_string())?;
+    let dir = base_dir.join("thumbnails");
+    std::fs::create_dir_all(&dir).map_err(|error| error.to_string())?;
+    Ok(dir)
+}
+
+// Cache files live in a folder named after a hash of the source path, so
+// every variant of a file can be dropped without knowing its old version
+// or listing the whole cache.
+pub fn path_hash(path: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    normalize_path(&path.to_string_lossy()).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn version_hash(path: &Path) -> Option<u32> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified_time = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0);
+
+    let mut hasher = DefaultHasher::new();
+    modified_time.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    Some(hasher.finish() as u32)
+}
+
+fn source_dir(cache_dir: &Path, source: &Path) -> PathBuf {
+    cache_dir.join(format!("{:016x}", path_hash(source)))
+}
+
+/// Path of a cached variant of the current version of `source`. Creates
+/// the source's folder, so the variant can be written there.
+pub fn cache_file_path(
+    cache_dir: &Path,
+    source: &Path,
+    variant: &str,
+    extension: &str,
+) -> Option<PathBuf> {
+    let version = version_hash(source)?;
+    let source_dir = source_dir(cache_dir, source);
+    std::fs::create_dir_all(&source_dir).ok()?;
+    Some(source_dir.join(format!("{:08x}-{}.{}", version, variant, extension)))
+}
+
+fn remove_files_in(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+
+    let mut removed_count: u64 = 0;
+    for entry in entries.flatten() {
+        if entry.path().is_file() && std::fs::remove_file(entry.path()).is_ok() {
+            removed_count += 1;
+        }
+    }
+    removed_count
+}
+
+/// Removes every cached file, including those of the flat layout older
+/// versions used
+pub fn clear_cache_dir(cache_dir: &Path) -> Result<u64, String> {
+    let mut removed_count: u64 = 0;
+
+    for entry in std::fs::read_dir(cache_dir)
+        .map_err(|error| error.to_string())?
+        .flatten()
+    {
+        let path = entry.path();
+        if path.is_dir() {
+            removed_count += remove_files_in(&path);
+            let _ = std::fs::remove_dir(&path);
+        } else if std::fs::remove_file(&path).is_ok() {
+            removed_count += 1;
+        }
+    }
+
+    Ok(removed_count)
+}
+
+/// Removes every cached variant (all sizes and versions) of the given sources.
+pub fn remove_variants(cache_dir: &Path, sources: &[PathBuf]) -> u64 {
+    let mut removed_count: u64 = 0;
+
+    for source in sources {
+        let source_dir = source_dir(cache_dir, source);
+        removed_count += remove_files_in(&source_dir);
+        let _ = std::fs::remove_dir(&source_dir);
+    }
+
+    removed_count
+}
@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Decoding through command-line tools, for when the decoders in `native`
+//! aren't built in or fail.
+
+use image::DynamicImage;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn tool_command(program: &str) -> Command {
+    #[allow(unused_mut)]
+    let mut command = Command::new(program);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    command
+}
+
+fn decoder_commands(extension: &str, input: &Path, output: &Path) -> Vec<Command> {
+    let mut commands: Vec<Command> = Vec::new();
+
+    #[cfg(target_os = "macos")]
+    {
+        let mut sips = tool_command("sips");
+        sips.args(["-s", "format", "png"]).arg(input).arg("--out").arg(output);
+        commands.push(sips);
+    }
+
+    let dedicated_tools: &[&str] = match extension {
+        "heic" | "heif" => &["heif-convert", "heif-dec"],
+        "avif" => &["avifdec", "heif-convert"],
+        "jxl" => &["djxl"],
+        _ => &[],
+    };
+
+    for program in dedicated_tools {
+        let mut command = tool_command(program);
+        command.arg(input).arg(output);
+        commands.push(command);
+    }
+
+    let mut magick = tool_command("magick");
+    magick.arg(input).arg(output);
+    commands.push(magick);
+
+    let mut ffmpeg = tool_command("ffmpeg");
+    ffmpeg
+        .args(["-v", "error", "-y", "-i"])
+        .arg(input)
+        .args(["-frames:v", "1"])
+        .arg(output);
+    commands.push(ffmpeg);
+
+    commands
+}
+
+fn temp_output_path() -> PathBuf {
+    let counter = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!(
+        "sigma-decode-{}-{}.png",
+        std::process::id(),
+        counter
+    ))
+}
+
+pub fn decode_with_external_tool(path: &Path, extension: &str) -> Result<DynamicImage, String> {
+    let output_path = temp_output_path();
+    let mut last_error = format!(
+        "No decoder found for .{} files. Install libheif, libavif, libjxl or ImageMagick.",
+        extension
+    );
+
+    for mut command in decoder_commands(extension, path, &output_path) {
+        match command.output() {
+            Ok(output) if output.status.success() && output_path.exists() => {
+                let result = image::open(&output_path).map_err(|error| error.to_string());
+                let _ = std::fs::remove_file(&output_path);
+                return result;
+            }
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+                if !stderr.trim().is_empty() {
+                    last_error = stderr.trim().to_string();
+                }
+                let _ = std::fs::remove_file(&output_path);
+            }
+            Err(_) => continue,
+        }
+    }
+
+    Err(last_error)
+}
@@ -0,0 +1,774 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+mod animated;
+mod cache;
+mod external;
+mod native;
+mod palette;
+mod raster;
+mod similar;
+mod video;
+mod waveform;
+
+use image::{imageops, ImageFormat, RgbImage};
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Emitter;
+use crate::power::{self, Allowance, BackgroundWork};
+use crate::skipped_dirs::SkippedDirs;
+use crate::utils::normalize_path;
+
+const DEFAULT_FILMSTRIP_FRAME_COUNT: u32 = 10;
+const DEFAULT_FILMSTRIP_FRAME_HEIGHT: u32 = 160;
+const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+// Physical thumbnail sizes are snapped to these buckets so each file has a
+// bounded number of cached variants regardless of zoom level and DPI.
+const THUMBNAIL_SIZE_BUCKETS: [u32; 6] = [64, 128, 256, 384, 512, 1024];
+const VISIBLE_THUMBNAILS_CAPACITY: usize = 1000;
+const THROTTLED_UPGRADE_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+const DEFAULT_ANIMATED_PREVIEW_SIZE: u32 = 320;
+const DEFAULT_ANIMATED_PREVIEW_FRAMES: u32 = 48;
+const DEFAULT_PALETTE_COLOR_COUNT: u32 = 6;
+// Palettes and perceptual hashes are taken from a small thumbnail, which
+// holds enough pixels for both and is usually cached already
+const ANALYSIS_THUMBNAIL_SIZE: u32 = 128;
+const DEFAULT_SIMILARITY_THRESHOLD: u32 = 10;
+const DEFAULT_WAVEFORM_POINTS: u32 = 512;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageThumbnail {
+    pub source_path: String,
+    pub thumbnail_path: String,
+    pub width: u32,
+    pub height: u32,
+    pub is_animated: bool,
+    #[serde(default)]
+    pub size_bucket: u32,
+    #[serde(default)]
+    pub is_upgrading: bool,
+}
+
+// Sources whose higher resolution variant is being generated in the background
+static PENDING_UPGRADES: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+// Sources currently shown by the frontend, mapped to the size bucket they
+// were last requested at. Watcher events regenerate thumbnails for these.
+static VISIBLE_THUMBNAILS: Lazy<Mutex<LruCache<String, u32>>> = Lazy::new(|| {
+    Mutex::new(LruCache::new(
+        NonZeroUsize::new(VISIBLE_THUMBNAILS_CAPACITY).unwrap(),
+    ))
+});
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageMetadata {
+    pub path: String,
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub has_alpha: Option<bool>,
+    pub is_animated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImagePalette {
+    pub source_path: String,
+    pub colors: Vec<palette::PaletteColor>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioWaveform {
+    pub source_path: String,
+    pub duration: f64,
+    /// Peak amplitude of each evenly spaced point, from 0 to 1
+    pub peaks: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarImage {
+    pub path: String,
+    /// Differing hash bits, 0 for images that look the same
+    pub distance: u32,
+    /// From 0 to 1, where 1 is identical
+    pub similarity: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarImages {
+    pub matches: Vec<SimilarImage>,
+    /// Folders in the scope that couldn't be searched
+    pub skipped_dirs: SkippedDirs,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnimatedPreview {
+    pub source_path: String,
+    pub preview_path: String,
+    pub frame_count: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VideoFilmstrip {
+    pub source_path: String,
+    pub strip_path: String,
+    pub frame_count: u32,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub duration: f64,
+}
+
+fn read_cached_metadata<T: serde::de::DeserializeOwned>(metadata_path: &Path, output_path: &Path) -> Option<T> {
+    if !output_path.exists() {
+        return None;
+    }
+    let text = std::fs::read_to_string(metadata_path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn write_cached_metadata<T: Serialize>(metadata_path: &Path, value: &T) {
+    if let Ok(json) = serde_json::to_string(value) {
+        let _ = std::fs::write(metadata_path, json);
+    }
+}
+
+fn file_extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default()
+}
+
+fn build_filmstrip(
+    source: &Path,
+    strip_path: &Path,
+    frame_count: u32,
+    frame_height: u32,
+) -> Result<VideoFilmstrip, String> {
+    let duration = video::probe_duration_seconds(source)?;
+
+    let timestamps: Vec<f64> = (0..frame_count)
+        .map(|index| duration * (index as f64 + 0.5) / frame_count as f64)
+        .collect();
+
+    let frames: Vec<RgbImage> = timestamps
+        .par_iter()
+        .map(|timestamp| {
+            video::extract_frame(source, *timestamp, frame_height).map(|frame| frame.to_rgb8())
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let frame_width = frames
+        .first()
+        .map(|frame| frame.width())
+        .filter(|width| *width > 0)
+        .ok_or_else(|| "Video has no decodable frames".to_string())?;
+
+    let mut strip = RgbImage::new(frame_width * frame_count, frame_height);
+
+    for (index, frame) in frames.iter().enumerate() {
+        let cell = if frame.width() == frame_width && frame.height() == frame_height {
+            frame.clone()
+        } else {
+            imageops::resize(frame, frame_width, frame_height, imageops::FilterType::Triangle)
+        };
+        imageops::replace(&mut strip, &cell, (index as u32 * frame_width) as i64, 0);
+    }
+
+    strip
+        .save_with_format(strip_path, ImageFormat::Jpeg)
+        .map_err(|error| error.to_string())?;
+
+    Ok(VideoFilmstrip {
+        source_path: normalize_path(&source.to_string_lossy()),
+        strip_path: normalize_path(&strip_path.to_string_lossy()),
+        frame_count,
+        frame_width,
+        frame_height,
+        duration,
+    })
+}
+
+/// Returns a horizontal strip of evenly spaced frames for hover scrubbing.
+/// Strips are cached per video version, so repeat hovers are a file lookup.
+#[tauri::command]
+pub async fn get_video_filmstrip(
+    app: tauri::AppHandle,
+    path: String,
+    frame_count: Option<u32>,
+    frame_height: Option<u32>,
+) -> Result<VideoFilmstrip, String> {
+    let cache_dir = cache::thumbnails_dir(&app)?;
+    let frame_count = frame_count.unwrap_or(DEFAULT_FILMSTRIP_FRAME_COUNT).clamp(2, 50);
+    let frame_height = frame_height.unwrap_or(DEFAULT_FILMSTRIP_FRAME_HEIGHT).clamp(32, 720);
+
+    tokio::task::spawn_blocking(move || {
+        let source = PathBuf::from(&path);
+
+        if !source.is_file() {
+            return Err(format!("Path is not a file: {}", path));
+        }
+
+        let variant = format!("filmstrip-{}x{}", frame_count, frame_height);
+        let strip_path = cache::cache_file_path(&cache_dir, &source, &variant, "jpg")
+            .ok_or_else(|| format!("Failed to read file metadata: {}", path))?;
+        let metadata_path = strip_path.with_extension("json");
+
+        if let Some(cached) = read_cached_metadata(&metadata_path, &strip_path) {
+            return Ok(cached);
+        }
+
+        let filmstrip = build_filmstrip(&source, &strip_path, frame_count, frame_height)?;
+        write_cached_metadata(&metadata_path, &filmstrip);
+
+        Ok(filmstrip)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaTracks {
+    pub path: String,
+    pub duration: Option<f64>,
+    pub audio_tracks: Vec<video::MediaTrack>,
+    pub subtitle_tracks: Vec<video::MediaTrack>,
+    pub chapters: Vec<video::MediaChapter>,
+}
+
+/// Lists the embedded audio tracks, subtitle tracks and chapters of a
+/// video or audio file.
+#[tauri::command]
+pub async fn get_media_tracks(path: String) -> Result<MediaTracks, String> {
+    tokio::task::spawn_blocking(move || {
+        let source = PathBuf::from(&path);
+
+        if !source.is_file() {
+            return Err(format!("Path is not a file: {}", path));
+        }
+
+        let probe = video::probe_media(&source)?;
+
+        Ok(MediaTracks {
+            path: normalize_path(&path),
+            duration: probe.duration,
+            audio_tracks: probe.audio_tracks,
+            subtitle_tracks: probe.subtitle_tracks,
+            chapters: probe.chapters,
+        })
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Saves an embedded subtitle track, identified by its stream index, to
+/// `destination`. Returns the path written.
+#[tauri::command]
+pub async fn extract_subtitles(
+    path: String,
+    track: u32,
+    destination: String,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let source = PathBuf::from(&path);
+        let destination_path = PathBuf::from(&destination);
+
+        if !source.is_file() {
+            return Err(format!("Path is not a file: {}", path));
+        }
+
+        if destination_path.exists() {
+            return Err(format!("Destination already exists: {}", destination));
+        }
+
+        let probe = video::probe_media(&source)?;
+        let subtitle = probe
+            .subtitle_tracks
+            .iter()
+            .find(|subtitle| subtitle.index == track)
+            .ok_or_else(|| format!("No subtitle track {} in {}", track, path))?;
+
+        video::extract_subtitle_track(&source, subtitle, &destination_path)?;
+
+        Ok(normalize_path(&destination))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Returns the amplitude envelope of an audio file for waveform previews
+/// and scrubbing. Envelopes are cached per file version like filmstrips.
+#[tauri::command]
+pub async fn get_audio_waveform(
+    app: tauri::AppHandle,
+    path: String,
+    points: Option<u32>,
+) -> Result<AudioWaveform, String> {
+    let cache_dir = cache::thumbnails_dir(&app)?;
+    let points = points.unwrap_or(DEFAULT_WAVEFORM_POINTS).clamp(16, 8192);
+
+    tokio::task::spawn_blocking(move || {
+        let source = PathBuf::from(&path);
+
+        if !source.is_file() {
+            return Err(format!("Path is not a file: {}", path));
+        }
+
+        let variant = format!("waveform-{}", points);
+        let waveform_path = cache::cache_file_path(&cache_dir, &source, &variant, "json")
+            .ok_or_else(|| format!("Failed to read file metadata: {}", path))?;
+
+        let cached = read_cached_metadata::<AudioWaveform>(&waveform_path, &waveform_path);
+        crate::metrics::record_cache_lookup("waveforms", cached.is_some());
+
+        if let Some(cached) = cached {
+            return Ok(cached);
+        }
+
+        let envelope = waveform::build_envelope(&source, points)?;
+        let result = AudioWaveform {
+            source_path: normalize_path(&path),
+            duration: envelope.duration,
+            peaks: envelope.peaks,
+        };
+        write_cached_metadata(&waveform_path, &result);
+
+        Ok(result)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+fn thumbnail_size_bucket(logical_size: u32, scale_factor: f64) -> u32 {
+    let physical_size = (logical_size as f64 * scale_factor.clamp(0.5, 4.0)).ceil() as u32;
+    THUMBNAIL_SIZE_BUCKETS
+        .iter()
+        .copied()
+        .find(|bucket| *bucket >= physical_size)
+        .unwrap_or(THUMBNAIL_SIZE_BUCKETS[THUMBNAIL_SIZE_BUCKETS.len() - 1])
+}
+
+fn thumbnail_metadata_path(source: &Path, cache_dir: &Path, size: u32) -> Result<PathBuf, String> {
+    let variant = format!("thumb-{}", size);
+    cache::cache_file_path(cache_dir, source, &variant, "json")
+        .ok_or_else(|| format!("Failed to read file metadata: {}", source.display()))
+}
+
+fn read_cached_thumbnail(source: &Path, cache_dir: &Path, size: u32) -> Option<ImageThumbnail> {
+    let metadata_path = thumbnail_metadata_path(source, cache_dir, size).ok()?;
+    let cached = read_cached_metadata::<ImageThumbnail>(&metadata_path, &metadata_path)?;
+    if Path::new(&cached.thumbnail_path).exists() {
+        Some(cached)
+    } else {
+        None
+    }
+}
+
+fn build_image_thumbnail(
+    source: &Path,
+    cache_dir: &Path,
+    size: u32,
+) -> Result<ImageThumbnail, String> {
+    let metadata_path = thumbnail_metadata_path(source, cache_dir, size)?;
+
+    let cached = read_cached_thumbnail(source, cache_dir, size);
+    crate::metrics::record_cache_lookup("thumbnails", cached.is_some());
+
+    if let Some(cached) = cached {
+        return Ok(cached);
+    }
+
+    // Decoding through the generic reader yields the first frame of
+    // animated formats, which keeps grid thumbnails cheap.
+    let decoded = raster::decode_image(source)?;
+    let thumbnail = decoded.thumbnail(size, size);
+    let (format, extension) = raster::thumbnail_format(&thumbnail);
+    let thumbnail_path = metadata_path.with_extension(extension);
+    raster::save_thumbnail(&thumbnail, &thumbnail_path, format)?;
+
+    let result = ImageThumbnail {
+        source_path: normalize_path(&source.to_string_lossy()),
+        thumbnail_path: normalize_path(&thumbnail_path.to_string_lossy()),
+        width: thumbnail.width(),
+        height: thumbnail.height(),
+        is_animated: animated::is_animated(source, &file_extension(source)),
+        size_bucket: size,
+        is_upgrading: false,
+    };
+    write_cached_metadata(&metadata_path, &result);
+
+    Ok(result)
+}
+
+fn spawn_thumbnail_upgrade(app: tauri::AppHandle, source: PathBuf, cache_dir: PathBuf, size: u32) {
+    // Upgrades are optional, the lower resolution variant stays on screen
+    let allowance = power::allowance(BackgroundWork::Thumbnails);
+    if allowance == Allowance::Pause {
+        return;
+    }
+
+    let pending_key = format!("{}:{}", normalize_path(&source.to_string_lossy()), size);
+
+    if let Ok(mut pending) = PENDING_UPGRADES.lock() {
+        if !pending.insert(pending_key.clone()) {
+            return;
+        }
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        if allowance == Allowance::Throttle {
+            std::thread::sleep(THROTTLED_UPGRADE_DELAY);
+        }
+
+        match build_image_thumbnail(&source, &cache_dir, size) {
+            Ok(thumbnail) => {
+                if let Err(error) = app.emit("thumbnail-updated", &thumbnail) {
+                    tracing::error!("Failed to emit thumbnail-updated event: {}", error);
+                }
+            }
+            Err(error) => {
+                tracing::warn!("Thumbnail upgrade failed for {}: {}", source.display(), error);
+            }
+        }
+
+        if let Ok(mut pending) = PENDING_UPGRADES.lock() {
+            pending.remove(&pending_key);
+        }
+    });
+}
+
+/// Returns a static thumbnail (the first frame for animated formats).
+///
+/// `size` is in logical pixels and is multiplied by `scale_factor` (the
+/// window's device pixel ratio). If only a smaller variant is cached it is
+/// returned right away with `is_upgrading` set, and a sharper one is
+/// generated in the background and announced via `thumbnail-updated`.
+#[tauri::command]
+pub async fn get_image_thumbnail(
+    app: tauri::AppHandle,
+    path: String,
+    size: Option<u32>,
+    scale_factor: Option<f64>,
+) -> Result<ImageThumbnail, String> {
+    let _timer = crate::metrics::time_command("get_image_thumbnail");
+    let cache_dir = cache::thumbnails_dir(&app)?;
+    let logical_size = size.unwrap_or(DEFAULT_THUMBNAIL_SIZE).clamp(16, 1024);
+    let size_bucket = thumbnail_size_bucket(logical_size, scale_factor.unwrap_or(1.0));
+
+    if let Ok(mut visible) = VISIBLE_THUMBNAILS.lock() {
+        visible.put(normalize_path(&path), size_bucket);
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let source = PathBuf::from(&path);
+
+        if !source.is_file() {
+            return Err(format!("Path is not a file: {}", path));
+        }
+
+        if let Some(cached) = read_cached_thumbnail(&source, &cache_dir, size_bucket) {
+            return Ok(cached);
+        }
+
+        let lower_resolution = THUMBNAIL_SIZE_BUCKETS
+            .iter()
+            .rev()
+            .filter(|bucket| **bucket < size_bucket)
+            .find_map(|bucket| read_cached_thumbnail(&source, &cache_dir, *bucket));
+
+        if let Some(mut cached) = lower_resolution {
+            spawn_thumbnail_upgrade(app, source, cache_dir, size_bucket);
+            cached.is_upgrading = true;
+            return Ok(cached);
+        }
+
+        build_image_thumbnail(&source, &cache_dir, size_bucket)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Drops cached thumbnails of changed files and regenerates the ones the
+/// frontend is displaying. Called by the directory watcher.
+pub fn invalidate_thumbnails(app: &tauri::AppHandle, changed_paths: &[PathBuf]) {
+    let cache_dir = match cache::thumbnails_dir(app) {
+        Ok(cache_dir) => cache_dir,
+        Err(_) => return,
+    };
+
+    cache::remove_variants(&cache_dir, changed_paths);
+
+    let to_regenerate: Vec<(PathBuf, u32)> = match VISIBLE_THUMBNAILS.lock() {
+        Ok(visible) => changed_paths
+            .iter()
+            .filter_map(|changed_path| {
+                let key = normalize_path(&changed_path.to_string_lossy());
+                visible
+                    .peek(&key)
+                    .map(|size_bucket| (changed_path.clone(), *size_bucket))
+            })
+            .collect(),
+        Err(_) => return,
+    };
+
+    for (source, size_bucket) in to_regenerate {
+        if source.is_file() {
+            spawn_thumbnail_upgrade(app.clone(), source, cache_dir.clone(), size_bucket);
+        }
+    }
+}
+
+/// Replaces the set of thumbnails the frontend is currently displaying so
+/// only those are regenerated when their files change.
+#[tauri::command]
+pub fn set_visible_thumbnails(paths: Vec<String>) {
+    let requested: HashSet<String> = paths.iter().map(|path| normalize_path(path)).collect();
+
+    if let Ok(mut visible) = VISIBLE_THUMBNAILS.lock() {
+        let stale_keys: Vec<String> = visible
+            .iter()
+            .filter(|(key, _)| !requested.contains(*key))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in stale_keys {
+            visible.pop(&key);
+        }
+
+        for path in requested {
+            if !visible.contains(&path) {
+                visible.put(path, DEFAULT_THUMBNAIL_SIZE);
+            }
+        }
+    }
+}
+
+/// Returns a downscaled, frame-limited GIF of an animated GIF/WebP/APNG
+/// for hover previews. Only generated on request since it is much more
+/// expensive than the static thumbnail.
+#[tauri::command]
+pub async fn get_animated_preview(
+    app: tauri::AppHandle,
+    path: String,
+    max_dimension: Option<u32>,
+    max_frames: Option<u32>,
+) -> Result<AnimatedPreview, String> {
+    let cache_dir = cache::thumbnails_dir(&app)?;
+    let max_dimension = max_dimension
+        .unwrap_or(DEFAULT_ANIMATED_PREVIEW_SIZE)
+        .clamp(32, 1024);
+    let max_frames = max_frames
+        .unwrap_or(DEFAULT_ANIMATED_PREVIEW_FRAMES)
+        .clamp(2, 200);
+
+    tokio::task::spawn_blocking(move || {
+        let source = PathBuf::from(&path);
+
+        if !source.is_file() {
+            return Err(format!("Path is not a file: {}", path));
+        }
+
+        let variant = format!("animated-{}-{}", max_dimension, max_frames);
+        let preview_path = cache::cache_file_path(&cache_dir, &source, &variant, "gif")
+            .ok_or_else(|| format!("Failed to read file metadata: {}", path))?;
+        let metadata_path = preview_path.with_extension("json");
+
+        if let Some(cached) = read_cached_metadata(&metadata_path, &preview_path) {
+            return Ok(cached);
+        }
+
+        let info = animated::write_animated_preview(
+            &source,
+            &file_extension(&source),
+            &preview_path,
+            max_dimension,
+            max_frames as usize,
+        )
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&preview_path);
+        })?;
+
+        let preview = AnimatedPreview {
+            source_path: normalize_path(&source.to_string_lossy()),
+            preview_path: normalize_path(&preview_path.to_string_lossy()),
+            frame_count: info.frame_count,
+            width: info.width,
+            height: info.height,
+        };
+        write_cached_metadata(&metadata_path, &preview);
+
+        Ok(preview)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub async fn get_image_metadata(path: String) -> Result<ImageMetadata, String> {
+    tokio::task::spawn_blocking(move || {
+        let source = PathBuf::from(&path);
+
+        if !source.is_file() {
+            return Err(format!("Path is not a file: {}", path));
+        }
+
+        let info = raster::read_image_info(&source)?;
+
+        Ok(ImageMetadata {
+            path: normalize_path(&path),
+            is_animated: animated::is_animated(&source, &info.format),
+            format: info.format,
+            width: info.width,
+            height: info.height,
+            has_alpha: info.has_alpha,
+        })
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Returns the dominant colors of an image, most common first, for color
+/// based browsing and the palette strip in the details pane.
+#[tauri::command]
+pub async fn get_image_palette(
+    app: tauri::AppHandle,
+    path: String,
+    color_count: Option<u32>,
+) -> Result<ImagePalette, String> {
+    let cache_dir = cache::thumbnails_dir(&app)?;
+    let color_count = color_count.unwrap_or(DEFAULT_PALETTE_COLOR_COUNT).clamp(1, 16);
+
+    tokio::task::spawn_blocking(move || {
+        let source = PathBuf::from(&path);
+
+        if !source.is_file() {
+            return Err(format!("Path is not a file: {}", path));
+        }
+
+        let variant = format!("palette-{}", color_count);
+        let palette_path = cache::cache_file_path(&cache_dir, &source, &variant, "json")
+            .ok_or_else(|| format!("Failed to read file metadata: {}", path))?;
+
+        let cached = read_cached_metadata::<ImagePalette>(&palette_path, &palette_path);
+        crate::metrics::record_cache_lookup("palettes", cached.is_some());
+
+        if let Some(cached) = cached {
+            return Ok(cached);
+        }
+
+        let thumbnail = build_image_thumbnail(&source, &cache_dir, ANALYSIS_THUMBNAIL_SIZE)?;
+        let image = raster::decode_image(Path::new(&thumbnail.thumbnail_path))?;
+
+        let result = ImagePalette {
+            source_path: normalize_path(&path),
+            colors: palette::extract_palette(&image, color_count as usize),
+        };
+        write_cached_metadata(&palette_path, &result);
+
+        Ok(result)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+fn cached_image_hash(source: &Path, cache_dir: &Path) -> Result<similar::ImageHash, String> {
+    let hash_path = cache::cache_file_path(cache_dir, source, "dhash", "json")
+        .ok_or_else(|| format!("Failed to read file metadata: {}", source.display()))?;
+
+    let cached = read_cached_metadata::<similar::ImageHash>(&hash_path, &hash_path);
+    crate::metrics::record_cache_lookup("image-hashes", cached.is_some());
+
+    if let Some(cached) = cached {
+        return Ok(cached);
+    }
+
+    let thumbnail = build_image_thumbnail(source, cache_dir, ANALYSIS_THUMBNAIL_SIZE)?;
+    let image = raster::decode_image(Path::new(&thumbnail.thumbnail_path))?;
+    let hash = similar::difference_hash(&image);
+    write_cached_metadata(&hash_path, &hash);
+
+    Ok(hash)
+}
+
+/// Finds images under the `scope` folders that look like the reference:
+/// resized copies, re-encodes and lightly edited versions. Hashes are
+/// cached with the thumbnails, so repeat searches only hash new files.
+#[tauri::command]
+pub async fn find_similar_images(
+    app: tauri::AppHandle,
+    reference_path: String,
+    threshold: Option<u32>,
+    scope: Vec<String>,
+) -> Result<SimilarImages, String> {
+    let cache_dir = cache::thumbnails_dir(&app)?;
+    // Past half the bits, unrelated images start to match
+    let threshold = threshold
+        .unwrap_or(DEFAULT_SIMILARITY_THRESHOLD)
+        .min(similar::HASH_BITS / 2);
+
+    tokio::task::spawn_blocking(move || {
+        let reference = PathBuf::from(&reference_path);
+
+        if !reference.is_file() {
+            return Err(format!("Path is not a file: {}", reference_path));
+        }
+
+        let reference_hash = cached_image_hash(&reference, &cache_dir)?;
+
+        let mut skipped_dirs = SkippedDirs::default();
+
+        // A set, as scope folders may overlap
+        let candidates: HashSet<PathBuf> = scope
+            .iter()
+            .flat_map(walkdir::WalkDir::new)
+            .filter_map(|entry| match entry {
+                Ok(entry) => Some(entry),
+                Err(error) => {
+                    skipped_dirs.record(&error);
+                    None
+                }
+            })
+            .filter(|entry| entry.file_type().is_file() && similar::is_image_file(entry.path()))
+            .map(|entry| entry.into_path())
+            .filter(|candidate| *candidate != reference)
+            .collect();
+
+        let mut matches: Vec<SimilarImage> = candidates
+            .par_iter()
+            .filter_map(|candidate| {
+                // Undecodable files are skipped rather than failing the search
+                let hash = cached_image_hash(candidate, &cache_dir).ok()?;
+                let distance = similar::distance(reference_hash, hash);
+
+                (distance <= threshold).then(|| SimilarImage {
+                    path: normalize_path(&candidate.to_string_lossy()),
+                    distance,
+                    similarity: 1.0 - distance as f32 / similar::HASH_BITS as f32,
+                })
+            })
+            .collect();
+
+        matches.sort_by(|a, b| a.distance.cmp(&b.distance).then_with(|| a.path.cmp(&b.path)));
+
+        Ok(SimilarImages {
+            matches,
+            skipped_dirs,
+        })
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub fn clear_thumbnail_cache(app: tauri::AppHandle) -> Result<u64, String> {
+    let cache_dir = cache::thumbnails_dir(&app)?;
+    cache::clear_cache_dir(&cache_dir)
+}
@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! In-process decoders for HEIF, AVIF and JPEG XL. HEIF and AVIF need the
+//! libheif and dav1d system libraries and are only built in with the `heif`
+//! and `avif` features. Without them, or when they fail, `external` tries
+//! the command-line decoders.
+
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder};
+use std::io::Read;
+use std::path::Path;
+
+/// Formats the image crate can't decode on its own
+pub fn handles(extension: &str) -> bool {
+    matches!(extension, "heic" | "heif" | "avif" | "jxl")
+}
+
+pub fn decode(path: &Path, extension: &str) -> Result<DynamicImage, String> {
+    match extension {
+        "jxl" => decode_jxl(path),
+        "avif" => decode_avif(path),
+        "heic" | "heif" => decode_heif(path),
+        _ => Err(format!("Unsupported image format: .{}", extension)),
+    }
+}
+
+fn decode_jxl(path: &Path) -> Result<DynamicImage, String> {
+    let file = std::fs::File::open(path).map_err(|error| error.to_string())?;
+    let mut decoder = jxl_oxide::integration::JxlDecoder::new(std::io::BufReader::new(file))
+        .map_err(|error| error.to_string())?;
+    let orientation = decoder.orientation().map_err(|error| error.to_string())?;
+
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|error| error.to_string())?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+// The AV1 decoder leaves the rotation and mirroring of the container to
+// the caller
+#[cfg(feature = "avif")]
+fn decode_avif(path: &Path) -> Result<DynamicImage, String> {
+    let mut image = image::ImageReader::open(path)
+        .map_err(|error| error.to_string())?
+        .with_guessed_format()
+        .map_err(|error| error.to_string())?
+        .decode()
+        .map_err(|error| error.to_string())?;
+
+    if let Some(header) = read_isobmff_header(path) {
+        for orientation in header.transforms {
+            image.apply_orientation(orientation);
+        }
+    }
+    Ok(image)
+}
+
+// libheif reads AVIF too, when it was built with an AV1 decoder
+#[cfg(not(feature = "avif"))]
+fn decode_avif(path: &Path) -> Result<DynamicImage, String> {
+    decode_heif(path)
+}
+
+#[cfg(not(feature = "heif"))]
+fn decode_heif(_path: &Path) -> Result<DynamicImage, String> {
+    Err("HEIF and AVIF decoding isn't built in".to_string())
+}
+
+#[cfg(feature = "heif")]
+fn decode_heif(path: &Path) -> Result<DynamicImage, String> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let data = std::fs::read(path).map_err(|error| error.to_string())?;
+    let context = HeifContext::read_from_bytes(&data).map_err(|error| error.to_string())?;
+    let handle = context
+        .primary_image_handle()
+        .map_err(|error| error.to_string())?;
+
+    let has_alpha = handle.has_alpha_channel();
+    let chroma = if has_alpha {
+        RgbChroma::Rgba
+    } else {
+        RgbChroma::Rgb
+    };
+    // Applies the rotation, mirroring and cropping of the container
+    let decoded = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(chroma), None)
+        .map_err(|error| error.to_string())?;
+
+    let plane = decoded
+        .planes()
+        .interleaved
+        .ok_or("Decoded image has no pixel data")?;
+    let row_length = plane.width as usize * if has_alpha { 4 } else { 3 };
+    let mut pixels = Vec::with_capacity(row_length * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(row.get(..row_length).ok_or("Decoded row is too short")?);
+    }
+
+    let image = if has_alpha {
+        image::RgbaImage::from_raw(plane.width, plane.height, pixels).map(DynamicImage::from)
+    } else {
+        image::RgbImage::from_raw(plane.width, plane.height, pixels).map(DynamicImage::from)
+    };
+    image.ok_or_else(|| "Decoded image has an unexpected size".to_string())
+}
+
+/// What the boxes of a HEIF/AVIF container say about its primary image
+pub struct IsobmffHeader {
+    /// Width and height as displayed, after `transforms`
+    pub dimensions: Option<(u32, u32)>,
+    /// Rotation and mirroring to apply to the decoded image, in order
+    #[cfg_attr(not(feature = "avif"), allow(dead_code))]
+    pub transforms: Vec<Orientation>,
+}
+
+// Boxes are found by their type, which is preceded by their size. Property
+// boxes are small, so a size check rules out matches in the image data.
+fn find_boxes<'a>(
+    buffer: &'a [u8],
+    box_type: &'a [u8; 4],
+    data_length: usize,
+) -> impl Iterator<Item = &'a [u8]> + 'a {
+    let box_length = 8 + data_length as u32;
+
+    buffer
+        .windows(4)
+        .enumerate()
+        .filter(move |(_, window)| window == box_type)
+        .filter_map(move |(type_offset, _)| {
+            let size_offset = type_offset.checked_sub(4)?;
+            let size = u32::from_be_bytes(buffer[size_offset..type_offset].try_into().ok()?);
+            let data_start = type_offset + 4;
+            (size == box_length).then(|| buffer.get(data_start..data_start + data_length))?
+        })
+}
+
+/// Reads the image size from the `ispe` property boxes of a HEIF/AVIF
+/// container without decoding, and its rotation from `irot` and mirroring
+/// from `imir`. The largest `ispe` is the primary image, smaller ones
+/// belong to embedded thumbnails or grid tiles.
+pub fn read_isobmff_header(path: &Path) -> Option<IsobmffHeader> {
+    let mut buffer: Vec<u8> = Vec::new();
+    std::fs::File::open(path)
+        .ok()?
+        .take(512 * 1024)
+        .read_to_end(&mut buffer)
+        .ok()?;
+
+    // Version and flags, then width and height
+    let stored_dimensions = find_boxes(&buffer, b"ispe", 12)
+        .map(|data| {
+            let width = u32::from_be_bytes(data[4..8].try_into().unwrap_or_default());
+            let height = u32::from_be_bytes(data[8..12].try_into().unwrap_or_default());
+            (width, height)
+        })
+        .filter(|(width, height)| *width > 0 && *height > 0)
+        .max_by_key(|(width, height)| *width as u64 * *height as u64);
+
+    let mut transforms = Vec::new();
+    // Quarter turns anticlockwise. Thumbnails carry the same boxes as the
+    // primary image, only the first of each counts.
+    let quarter_turns = find_boxes(&buffer, b"irot", 1)
+        .next()
+        .map_or(0, |data| data[0] & 0b11);
+    match quarter_turns {
+        1 => transforms.push(Orientation::Rotate270),
+        2 => transforms.push(Orientation::Rotate180),
+        3 => transforms.push(Orientation::Rotate90),
+        _ => {}
+    }
+    // Mirrored across the vertical axis or, with the bit set, the
+    // horizontal one
+    if let Some(data) = find_boxes(&buffer, b"imir", 1).next() {
+        transforms.push(if data[0] & 1 == 0 {
+            Orientation::FlipHorizontal
+        } else {
+            Orientation::FlipVertical
+        });
+    }
+
+    let dimensions = stored_dimensions.map(|(width, height)| {
+        if quarter_turns % 2 == 1 {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    });
+
+    Some(IsobmffHeader {
+        dimensions,
+        transforms,
+    })
+}
@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Dominant colors by median cut: the pixels are split along their widest
+//! channel until there are as many boxes as colors, and each box yields
+//! its average color weighted by how many pixels it holds.
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+// Mostly transparent pixels would pull the palette toward whatever color
+// the encoder left in the invisible area
+const MIN_ALPHA: u8 = 128;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaletteColor {
+    pub hex: String,
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    /// Share of the visible pixels, from 0 to 1
+    pub proportion: f32,
+}
+
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = pixels.iter().fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+                (min.min(pixel[channel]), max.max(pixel[channel]))
+            });
+            (channel, max.saturating_sub(min))
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+fn average_color(pixels: &[[u8; 3]], total: usize) -> PaletteColor {
+    let mut sums = [0u64; 3];
+
+    for pixel in pixels {
+        for channel in 0..3 {
+            sums[channel] += pixel[channel] as u64;
+        }
+    }
+
+    let count = pixels.len().max(1) as u64;
+    let [red, green, blue] = sums.map(|sum| ((sum + count / 2) / count) as u8);
+
+    PaletteColor {
+        hex: format!("#{:02x}{:02x}{:02x}", red, green, blue),
+        red,
+        green,
+        blue,
+        proportion: pixels.len() as f32 / total.max(1) as f32,
+    }
+}
+
+/// Returns up to `color_count` colors, most common first. Fully
+/// transparent images have no palette.
+pub fn extract_palette(image: &DynamicImage, color_count: usize) -> Vec<PaletteColor> {
+    let pixels: Vec<[u8; 3]> = image
+        .to_rgba8()
+        .pixels()
+        .filter(|pixel| pixel[3] >= MIN_ALPHA)
+        .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+    let total = pixels.len();
+
+    if total == 0 {
+        return Vec::new();
+    }
+
+    let mut boxes = vec![pixels];
+
+    while boxes.len() < color_count {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, pixels)| pixels.len() > 1)
+            .map(|(index, pixels)| (index, widest_channel(pixels)))
+            .filter(|(_, (_, range))| *range > 0)
+            .max_by_key(|(index, (_, range))| (*range as usize) * boxes[*index].len());
+
+        let Some((index, (channel, _))) = widest else {
+            break;
+        };
+
+        let mut pixels = boxes.swap_remove(index);
+        pixels.sort_unstable_by_key(|pixel| pixel[channel]);
+        let upper = pixels.split_off(pixels.len() / 2);
+        boxes.push(pixels);
+        boxes.push(upper);
+    }
+
+    let mut colors: Vec<PaletteColor> = boxes
+        .iter()
+        .map(|pixels| average_color(pixels, total))
+        .collect();
+    colors.sort_by(|a, b| b.proportion.total_cmp(&a.proportion));
+    colors
+}
@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::{external, native};
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use std::path::Path;
+
+pub struct ImageInfo {
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub has_alpha: Option<bool>,
+}
+
+fn lowercase_extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default()
+}
+
+// In process where that is built in, else with a command-line tool
+fn decode_with_codec(path: &Path, extension: &str) -> Result<DynamicImage, String> {
+    native::decode(path, extension).or_else(|error| {
+        tracing::debug!("Decoding {} in process failed: {}", path.display(), error);
+        external::decode_with_external_tool(path, extension)
+    })
+}
+
+pub fn decode_image(path: &Path) -> Result<DynamicImage, String> {
+    let extension = lowercase_extension(path);
+    if native::handles(&extension) {
+        return decode_with_codec(path, &extension);
+    }
+
+    ImageReader::open(path)
+        .map_err(|error| error.to_string())?
+        .with_guessed_format()
+        .map_err(|error| error.to_string())?
+        .decode()
+        .map_err(|error| error.to_string())
+}
+
+/// Reads format and dimensions from the file header where possible and
+/// only falls back to a full decode for formats without a native reader.
+pub fn read_image_info(path: &Path) -> Result<ImageInfo, String> {
+    let extension = lowercase_extension(path);
+
+    if native::handles(&extension) {
+        let header = (extension != "jxl").then(|| native::read_isobmff_header(path)).flatten();
+        if let Some((width, height)) = header.and_then(|header| header.dimensions) {
+            return Ok(ImageInfo {
+                format: extension,
+                width,
+                height,
+                has_alpha: None,
+            });
+        }
+
+        let decoded = decode_with_codec(path, &extension)?;
+        return Ok(ImageInfo {
+            format: extension,
+            width: decoded.width(),
+            height: decoded.height(),
+            has_alpha: Some(decoded.color().has_alpha()),
+        });
+    }
+
+    let reader = ImageReader::open(path)
+        .map_err(|error| error.to_string())?
+        .with_guessed_format()
+        .map_err(|error| error.to_string())?;
+
+    let format = reader
+        .format()
+        .and_then(|format| format.extensions_str().first().copied())
+        .map(|format| format.to_string())
+        .unwrap_or(extension);
+
+    let decoder = reader.into_decoder().map_err(|error| error.to_string())?;
+    let (width, height) = decoder.dimensions();
+
+    Ok(ImageInfo {
+        format,
+        width,
+        height,
+        has_alpha: Some(decoder.color_type().has_alpha()),
+    })
+}
+
+/// Saves opaque thumbnails as JPEG and keeps PNG for anything with alpha.
+pub fn thumbnail_format(image: &DynamicImage) -> (ImageFormat, &'static str) {
+    if image.color().has_alpha() {
+        (ImageFormat::Png, "png")
+    } else {
+        (ImageFormat::Jpeg, "jpg")
+    }
+}
+
+pub fn save_thumbnail(image: &DynamicImage, destination: &Path, format: ImageFormat) -> Result<(), String> {
+    let result = if format == ImageFormat::Jpeg {
+        image.to_rgb8().save_with_format(destination, format)
+    } else {
+        image.save_with_format(destination, format)
+    };
+    result.map_err(|error| error.to_string())
+}
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Perceptual hashes for finding visually similar images. A difference
+//! hash records whether brightness rises or falls between neighbouring
+//! cells of a tiny grayscale copy, so it survives resizing, recompression
+//! and small edits, and similar images differ in only a few bits.
+
+use image::{imageops, DynamicImage};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const HASH_WIDTH: u32 = 9;
+const HASH_HEIGHT: u32 = 8;
+
+pub const HASH_BITS: u32 = (HASH_WIDTH - 1) * HASH_HEIGHT;
+
+const IMAGE_EXTENSIONS: [&str; 9] = [
+    "jpg", "jpeg", "png", "gif", "webp", "heic", "heif", "avif", "jxl",
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ImageHash {
+    pub hash: u64,
+}
+
+pub fn difference_hash(image: &DynamicImage) -> ImageHash {
+    let small = imageops::resize(
+        &image.to_luma8(),
+        HASH_WIDTH,
+        HASH_HEIGHT,
+        imageops::FilterType::Triangle,
+    );
+    let mut hash = 0u64;
+
+    for y in 0..HASH_HEIGHT {
+        for x in 0..HASH_WIDTH - 1 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    ImageHash { hash }
+}
+
+pub fn distance(first: ImageHash, second: ImageHash) -> u32 {
+    (first.hash ^ second.hash).count_ones()
+}
+
+pub fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
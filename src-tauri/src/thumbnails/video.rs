@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::process::Command;
+
+pub fn media_tool_command(program: &str) -> Command {
+    #[allow(unused_mut)]
+    let mut command = Command::new(program);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    command
+}
+
+pub fn probe_duration_seconds(path: &Path) -> Result<f64, String> {
+    let output = media_tool_command("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ])
+        .arg(path)
+        .output()
+        .map_err(|error| format!("Failed to run ffprobe: {}. Is ffmpeg installed?", error))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(format!("ffprobe failed: {}", stderr.trim()));
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|duration| duration.is_finite() && *duration > 0.0)
+        .ok_or_else(|| "Could not determine video duration".to_string())
+}
+
+pub fn extract_frame(
+    path: &Path,
+    timestamp: f64,
+    frame_height: u32,
+) -> Result<DynamicImage, String> {
+    let output = media_tool_command("ffmpeg")
+        .args(["-v", "error", "-ss", &format!("{:.3}", timestamp), "-i"])
+        .arg(path)
+        .args([
+            "-frames:v",
+            "1",
+            "-vf",
+            &format!("scale=-2:{}", frame_height),
+            "-f",
+            "image2pipe",
+            "-vcodec",
+            "png",
+            "-",
+        ])
+        .output()
+        .map_err(|error| format!("Failed to run ffmpeg: {}. Is ffmpeg installed?", error))?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(format!("ffmpeg failed: {}", stderr.trim()));
+    }
+
+    image::load_from_memory_with_format(&output.stdout, ImageFormat::Png)
+        .map_err(|error| error.to_string())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaTrack {
+    /// Stream index in the file, as `extract_subtitles` takes it
+    pub index: u32,
+    pub codec: Option<String>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub is_default: bool,
+    pub is_forced: bool,
+    /// Audio only
+    pub channels: Option<u32>,
+    /// Subtitles only: false for image based formats such as PGS and VobSub
+    pub is_text: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaChapter {
+    pub title: Option<String>,
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaProbe {
+    pub duration: Option<f64>,
+    pub audio_tracks: Vec<MediaTrack>,
+    pub subtitle_tracks: Vec<MediaTrack>,
+    pub chapters: Vec<MediaChapter>,
+}
+
+// Subtitle codecs stored as bitmaps, which can't be converted to text
+const IMAGE_SUBTITLE_CODECS: [&str; 4] =
+    ["hdmv_pgs_subtitle", "dvd_subtitle", "dvb_subtitle", "xsub"];
+
+pub fn is_text_subtitle(codec: &str) -> bool {
+    !IMAGE_SUBTITLE_CODECS.contains(&codec)
+}
+
+fn tag(value: &Value, name: &str) -> Option<String> {
+    value
+        .get("tags")?
+        .get(name)?
+        .as_str()
+        .map(str::trim)
+        .filter(|text| !text.is_empty() && *text != "und")
+        .map(str::to_string)
+}
+
+fn disposition(stream: &Value, name: &str) -> bool {
+    stream
+        .get("disposition")
+        .and_then(|disposition| disposition.get(name))
+        .and_then(Value::as_u64)
+        == Some(1)
+}
+
+// ffprobe prints times as strings to keep their precision
+fn seconds(value: &Value, name: &str) -> Option<f64> {
+    value
+        .get(name)?
+        .as_str()?
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite())
+}
+
+/// Lists the audio tracks, subtitle tracks and chapters of a media file
+pub fn probe_media(path: &Path) -> Result<MediaProbe, String> {
+    let output = media_tool_command("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration:stream=index,codec_type,codec_name,channels:stream_tags:stream_disposition",
+            "-show_chapters",
+            "-of",
+            "json",
+        ])
+        .arg(path)
+        .output()
+        .map_err(|error| format!("Failed to run ffprobe: {}. Is ffmpeg installed?", error))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(format!("ffprobe failed: {}", stderr.trim()));
+    }
+
+    let probe: Value = serde_json::from_slice(&output.stdout).map_err(|error| error.to_string())?;
+    let streams = probe
+        .get("streams")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    let track = |stream: &Value| {
+        let codec = stream
+            .get("codec_name")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        MediaTrack {
+            index: stream.get("index").and_then(Value::as_u64).unwrap_or(0) as u32,
+            language: tag(stream, "language"),
+            title: tag(stream, "title"),
+            is_default: disposition(stream, "default"),
+            is_forced: disposition(stream, "forced"),
+            channels: stream
+                .get("channels")
+                .and_then(Value::as_u64)
+                .map(|channels| channels as u32),
+            is_text: None,
+            codec,
+        }
+    };
+
+    let of_type = |codec_type: &'static str| {
+        streams
+            .iter()
+            .filter(move |stream| {
+                stream.get("codec_type").and_then(Value::as_str) == Some(codec_type)
+            })
+            .map(track)
+    };
+
+    let audio_tracks = of_type("audio").collect();
+    let subtitle_tracks = of_type("subtitle")
+        .map(|mut subtitle| {
+            subtitle.channels = None;
+            subtitle.is_text = subtitle.codec.as_deref().map(is_text_subtitle);
+            subtitle
+        })
+        .collect();
+
+    let chapters = probe
+        .get("chapters")
+        .and_then(Value::as_array)
+        .map(|chapters| {
+            chapters
+                .iter()
+                .map(|chapter| MediaChapter {
+                    title: tag(chapter, "title"),
+                    start: seconds(chapter, "start_time").unwrap_or(0.0),
+                    end: seconds(chapter, "end_time").unwrap_or(0.0),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(MediaProbe {
+        duration: probe
+            .get("format")
+            .and_then(|format| seconds(format, "duration")),
+        audio_tracks,
+        subtitle_tracks,
+        chapters,
+    })
+}
+
+/// Writes one subtitle stream to `destination`. Text subtitles are
+/// converted to the format the destination extension names (srt, ass,
+/// vtt); image based ones are copied as they are.
+pub fn extract_subtitle_track(
+    path: &Path,
+    track: &MediaTrack,
+    destination: &Path,
+) -> Result<(), String> {
+    let mut command = media_tool_command("ffmpeg");
+    command
+        .args(["-v", "error", "-n", "-i"])
+        .arg(path)
+        .args(["-map", &format!("0:{}", track.index)]);
+
+    if track.is_text == Some(false) {
+        command.args(["-c:s", "copy"]);
+    }
+
+    let output = command
+        .arg(destination)
+        .output()
+        .map_err(|error| format!("Failed to run ffmpeg: {}. Is ffmpeg installed?", error))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(format!("ffmpeg failed: {}", stderr.trim()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Amplitude envelopes of audio files. ffmpeg decodes the audio to a low
+//! rate mono stream that is read in chunks, so long recordings never have
+//! to fit in memory.
+
+use super::video;
+use std::io::Read;
+use std::path::Path;
+use std::process::Stdio;
+
+// Plenty for an envelope, and keeps decoding an hour of audio quick
+const SAMPLE_RATE: u32 = 8000;
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+pub struct Envelope {
+    pub duration: f64,
+    /// Peak amplitude of each point, from 0 to 1
+    pub peaks: Vec<f32>,
+}
+
+struct PeakAccumulator {
+    samples_per_point: u64,
+    samples_in_point: u64,
+    current_peak: u16,
+    peaks: Vec<f32>,
+}
+
+impl PeakAccumulator {
+    fn add(&mut self, sample: i16) {
+        self.current_peak = self.current_peak.max(sample.unsigned_abs());
+        self.samples_in_point += 1;
+
+        if self.samples_in_point == self.samples_per_point {
+            self.finish_point();
+        }
+    }
+
+    fn finish_point(&mut self) {
+        self.peaks
+            .push((self.current_peak as f32 / i16::MAX as f32).min(1.0));
+        self.current_peak = 0;
+        self.samples_in_point = 0;
+    }
+}
+
+pub fn build_envelope(source: &Path, points: u32) -> Result<Envelope, String> {
+    let duration = video::probe_duration_seconds(source)?;
+    let expected_samples = (duration * SAMPLE_RATE as f64).ceil().max(1.0) as u64;
+    let samples_per_point = expected_samples.div_ceil(points as u64).max(1);
+
+    let mut child = video::media_tool_command("ffmpeg")
+        .args(["-v", "error", "-i"])
+        .arg(source)
+        .args(["-vn", "-ac", "1", "-ar", &SAMPLE_RATE.to_string()])
+        .args(["-f", "s16le", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| format!("Failed to run ffmpeg: {}. Is ffmpeg installed?", error))?;
+
+    let mut stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| "ffmpeg produced no output".to_string())?;
+
+    let mut accumulator = PeakAccumulator {
+        samples_per_point,
+        samples_in_point: 0,
+        current_peak: 0,
+        peaks: Vec::with_capacity(points as usize),
+    };
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+    // A sample can be split across two reads
+    let mut pending_byte: Option<u8> = None;
+
+    loop {
+        let read = match stdout.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(error) => {
+                let _ = child.kill();
+                return Err(error.to_string());
+            }
+        };
+
+        let mut bytes = &buffer[..read];
+
+        if let Some(low) = pending_byte.take() {
+            if let Some((&high, rest)) = bytes.split_first() {
+                accumulator.add(i16::from_le_bytes([low, high]));
+                bytes = rest;
+            }
+        }
+
+        let mut samples = bytes.chunks_exact(2);
+
+        for sample in samples.by_ref() {
+            accumulator.add(i16::from_le_bytes([sample[0], sample[1]]));
+        }
+
+        pending_byte = samples.remainder().first().copied();
+    }
+
+    let status = child.wait().map_err(|error| error.to_string())?;
+
+    if !status.success() && accumulator.peaks.is_empty() {
+        return Err(format!(
+            "ffmpeg failed to decode audio: {}",
+            source.display()
+        ));
+    }
+
+    if accumulator.samples_in_point > 0 {
+        accumulator.finish_point();
+    }
+
+    // The probed duration is an estimate; pad or trim to the requested size
+    let mut peaks = accumulator.peaks;
+    peaks.resize(points as usize, 0.0);
+
+    Ok(Envelope { duration, peaks })
+}
@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! The FreeDesktop.org trash specification as GNOME (gio) and KDE (KIO)
+//! implement it. Files on the home volume go to `$XDG_DATA_HOME/Trash`;
+//! files on any other volume go to that volume's own trash, either
+//! `$topdir/.Trash/$uid` when the admin created a sticky `.Trash`, or
+//! `$topdir/.Trash-$uid`, with paths stored relative to the volume so the
+//! trash still works when it is mounted elsewhere. The `.trashinfo` file is
+//! created before the move, as the spec requires, so a crash never leaves
+//! a trashed file without its original location.
+
+use super::TrashItem;
+use crate::utils::normalize_path;
+use percent_encoding::{percent_decode_str, percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const INFO_EXTENSION: &str = "trashinfo";
+const DIRECTORY_SIZES_FILE: &str = "directorysizes";
+const STICKY_BIT: u32 = 0o1000;
+
+// Everything but unreserved characters and separators, as gio writes it.
+// Names are encoded byte by byte, so those that aren't UTF-8 survive.
+const PATH_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// A trash directory and the volume root its relative paths start from
+pub struct TrashDir {
+    pub path: PathBuf,
+    /// `None` for the home trash, whose paths are absolute
+    pub top_dir: Option<PathBuf>,
+}
+
+impl TrashDir {
+    pub fn info_dir(&self) -> PathBuf {
+        self.path.join("info")
+    }
+
+    pub fn files_dir(&self) -> PathBuf {
+        self.path.join("files")
+    }
+}
+
+fn uid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+pub fn home_trash() -> Option<TrashDir> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(".local/share")))?;
+
+    Some(TrashDir {
+        path: data_home.join("Trash"),
+        top_dir: None,
+    })
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+fn device_of(path: &Path) -> Option<u64> {
+    fs::symlink_metadata(path)
+        .ok()
+        .map(|metadata| metadata.dev())
+}
+
+/// The root of the mount `path` lives on: the last ancestor on its device
+fn top_dir_of(path: &Path) -> Option<PathBuf> {
+    let device = device_of(path)?;
+    let mut top_dir = path.parent()?.to_path_buf();
+
+    while let Some(parent) = top_dir.parent() {
+        if device_of(parent) != Some(device) {
+            break;
+        }
+        top_dir = parent.to_path_buf();
+    }
+
+    Some(top_dir)
+}
+
+fn create_private_dir(path: &Path) -> std::io::Result<()> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(path)
+}
+
+/// `$topdir/.Trash/$uid` is only trusted in a sticky, non-symlink `.Trash`,
+/// otherwise anyone could read what others trash
+fn shared_trash_dir(top_dir: &Path) -> Option<PathBuf> {
+    let shared_trash = top_dir.join(".Trash");
+    let metadata = fs::symlink_metadata(&shared_trash).ok()?;
+
+    if !metadata.is_dir() || metadata.permissions().mode() & STICKY_BIT == 0 {
+        return None;
+    }
+
+    Some(shared_trash.join(uid().to_string()))
+}
+
+/// Trash directories of a volume that exist
+pub fn volume_trash_dirs(top_dir: &Path) -> Vec<TrashDir> {
+    shared_trash_dir(top_dir)
+        .into_iter()
+        .chain(std::iter::once(top_dir.join(format!(".Trash-{}", uid()))))
+        .filter(|path| path.join("info").is_dir())
+        .map(|path| TrashDir {
+            path,
+            top_dir: Some(top_dir.to_path_buf()),
+        })
+        .collect()
+}
+
+/// Picks the trash for a path, creating it when needed
+pub fn trash_dir_for(path: &Path) -> Result<TrashDir, String> {
+    let device =
+        device_of(path).ok_or_else(|| format!("Path does not exist: {}", path.display()))?;
+
+    if let Some(home_trash) = home_trash() {
+        // The data directory may not exist yet on a fresh account
+        let _ = create_private_dir(&home_trash.path);
+        if device_of(&home_trash.path) == Some(device) {
+            return Ok(home_trash);
+        }
+    }
+
+    let top_dir =
+        top_dir_of(path).ok_or_else(|| format!("Cannot find the volume of {}", path.display()))?;
+
+    for trash_path in shared_trash_dir(&top_dir)
+        .into_iter()
+        .chain(std::iter::once(top_dir.join(format!(".Trash-{}", uid()))))
+    {
+        let is_usable = create_private_dir(&trash_path.join("info")).is_ok()
+            && create_private_dir(&trash_path.join("files")).is_ok()
+            && device_of(&trash_path) == Some(device);
+
+        if is_usable {
+            return Ok(TrashDir {
+                path: trash_path,
+                top_dir: Some(top_dir),
+            });
+        }
+    }
+
+    Err(format!("{} has no usable trash folder", top_dir.display()))
+}
+
+fn local_date_time(seconds: i64) -> String {
+    let time = seconds as libc::time_t;
+    let mut local: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&time, &mut local) };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        local.tm_year + 1900,
+        local.tm_mon + 1,
+        local.tm_mday,
+        local.tm_hour,
+        local.tm_min,
+        local.tm_sec
+    )
+}
+
+/// Milliseconds since the Unix epoch of a local `YYYY-MM-DDThh:mm:ss`
+fn parse_local_date_time(text: &str) -> Option<u64> {
+    let (date, time) = text.trim().split_once('T')?;
+    let mut date_parts = date.splitn(3, '-').map(|part| part.parse::<i32>().ok());
+    let mut time_parts = time
+        .get(..8)
+        .unwrap_or(time)
+        .splitn(3, ':')
+        .map(|part| part.parse::<i32>().ok());
+
+    let mut local: libc::tm = unsafe { std::mem::zeroed() };
+    local.tm_year = date_parts.next()?? - 1900;
+    local.tm_mon = date_parts.next()?? - 1;
+    local.tm_mday = date_parts.next()??;
+    local.tm_hour = time_parts.next()??;
+    local.tm_min = time_parts.next()??;
+    local.tm_sec = time_parts.next()??;
+    // Lets mktime work out daylight saving time
+    local.tm_isdst = -1;
+
+    let seconds = unsafe { libc::mktime(&mut local) };
+    (seconds >= 0).then(|| seconds as u64 * 1000)
+}
+
+fn info_file_name(name: &OsStr) -> OsString {
+    let mut info_name = name.to_os_string();
+    info_name.push(".");
+    info_name.push(INFO_EXTENSION);
+    info_name
+}
+
+fn encode(bytes: &[u8]) -> String {
+    percent_encode(bytes, PATH_ENCODE_SET).to_string()
+}
+
+fn decode(text: &str) -> OsString {
+    OsString::from_vec(percent_decode_str(text).collect())
+}
+
+/// Claims a name in `info/` by creating its `.trashinfo` exclusively, so
+/// two processes trashing files with the same name can't collide
+fn create_info_file(
+    trash_dir: &TrashDir,
+    file_name: &OsStr,
+) -> Result<(OsString, fs::File), String> {
+    let path = Path::new(file_name);
+    let stem = path.file_stem().unwrap_or(file_name);
+    let extension = path.extension();
+
+    for counter in 1..10_000 {
+        let mut name = OsString::new();
+        match (counter, extension) {
+            (1, _) => name.push(file_name),
+            (_, Some(extension)) => {
+                name.push(stem);
+                name.push(format!(".{}.", counter));
+                name.push(extension);
+            }
+            (_, None) => {
+                name.push(file_name);
+                name.push(format!(".{}", counter));
+            }
+        }
+        let info_path = trash_dir.info_dir().join(info_file_name(&name));
+
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&info_path)
+        {
+            Ok(file) if !trash_dir.files_dir().join(&name).exists() => return Ok((name, file)),
+            Ok(_) => {
+                let _ = fs::remove_file(&info_path);
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(error) => return Err(error.to_string()),
+        }
+    }
+
+    Err(format!(
+        "No free name in the trash for {}",
+        file_name.to_string_lossy()
+    ))
+}
+
+// Links are counted as themselves, the root one included
+fn directory_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .follow_root_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Moves a file or folder into its volume's trash, returning the trash and
+/// where the item is kept in it
+pub fn trash_path(path: &Path) -> Result<(TrashDir, PathBuf), String> {
+    let path = std::path::absolute(path).map_err(|error| error.to_string())?;
+    let file_name = path
+        .file_name()
+        .map(OsStr::to_os_string)
+        .ok_or_else(|| format!("Cannot trash {}", path.display()))?;
+    let trash_dir = trash_dir_for(&path)?;
+    create_private_dir(&trash_dir.info_dir()).map_err(|error| error.to_string())?;
+    create_private_dir(&trash_dir.files_dir()).map_err(|error| error.to_string())?;
+
+    let stored_path = match &trash_dir.top_dir {
+        Some(top_dir) => path.strip_prefix(top_dir).unwrap_or(&path).to_path_buf(),
+        None => path.clone(),
+    };
+    let encoded_path = encode(stored_path.as_os_str().as_bytes());
+    let now_seconds = std::time::SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0);
+
+    let (name, mut info_file) = create_info_file(&trash_dir, &file_name)?;
+    let info_path = trash_dir.info_dir().join(info_file_name(&name));
+    let contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encoded_path,
+        local_date_time(now_seconds)
+    );
+
+    let moved = info_file
+        .write_all(contents.as_bytes())
+        .and_then(|()| info_file.sync_all())
+        .and_then(|()| fs::rename(&path, trash_dir.files_dir().join(&name)));
+
+    if let Err(error) = moved {
+        let _ = fs::remove_file(&info_path);
+        return Err(error.to_string());
+    }
+
+    // File managers show folder sizes from this cache instead of walking
+    // every trashed folder. Links to folders aren't folders here.
+    let trashed_path = trash_dir.files_dir().join(&name);
+    if fs::symlink_metadata(&trashed_path).is_ok_and(|metadata| metadata.is_dir()) {
+        let info_modified = fs::metadata(&info_path)
+            .map(|metadata| metadata.mtime())
+            .unwrap_or(now_seconds);
+        let line = format!(
+            "{} {} {}\n",
+            directory_size(&trashed_path),
+            info_modified,
+            encode(name.as_bytes())
+        );
+        let _ = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(trash_dir.path.join(DIRECTORY_SIZES_FILE))
+            .and_then(|mut file| file.write_all(line.as_bytes()));
+    }
+
+    Ok((trash_dir, trashed_path))
+}
+
+fn parse_info_file(info_path: &Path) -> Option<(PathBuf, Option<u64>)> {
+    let text = fs::read_to_string(info_path).ok()?;
+    let mut in_section = false;
+    let mut original_path = None;
+    let mut deleted_at = None;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_section = line == "[Trash Info]";
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some(("Path", value)) => {
+                original_path = Some(PathBuf::from(decode(value)));
+            }
+            Some(("DeletionDate", value)) => deleted_at = parse_local_date_time(value),
+            _ => {}
+        }
+    }
+
+    Some((original_path?, deleted_at))
+}
+
+fn cached_directory_sizes(trash_dir: &TrashDir) -> HashMap<OsString, u64> {
+    fs::read_to_string(trash_dir.path.join(DIRECTORY_SIZES_FILE))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let size = fields.next()?.parse().ok()?;
+            let _modified = fields.next()?;
+            Some((decode(fields.next()?), size))
+        })
+        .collect()
+}
+
+/// Items in one trash directory, whoever trashed them
+pub fn list_trash_dir(trash_dir: &TrashDir) -> Vec<TrashItem> {
+    let Ok(entries) = fs::read_dir(trash_dir.info_dir()) else {
+        return Vec::new();
+    };
+    let directory_sizes = cached_directory_sizes(trash_dir);
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let info_path = entry.path();
+            if info_path.extension().and_then(|ext| ext.to_str()) != Some(INFO_EXTENSION) {
+                return None;
+            }
+
+            let name = info_path.file_stem()?.to_os_string();
+            let trashed_path = trash_dir.files_dir().join(&name);
+            let metadata = fs::symlink_metadata(&trashed_path).ok()?;
+            let (stored_path, deleted_at) = parse_info_file(&info_path)?;
+
+            let original_path = match &trash_dir.top_dir {
+                Some(top_dir) if !stored_path.is_absolute() => top_dir.join(&stored_path),
+                _ => stored_path,
+            };
+            let size = if metadata.is_dir() {
+                directory_sizes
+                    .get(&name)
+                    .copied()
+                    .unwrap_or_else(|| directory_size(&trashed_path))
+            } else {
+                metadata.len()
+            };
+
+            Some(TrashItem {
+                id: normalize_path(&info_path.to_string_lossy()),
+                name: original_path
+                    .file_name()
+                    .unwrap_or(name.as_os_str())
+                    .to_string_lossy()
+                    .to_string(),
+                original_path: normalize_path(&original_path.to_string_lossy()),
+                trashed_path: Some(normalize_path(&trashed_path.to_string_lossy())),
+                deleted_at: deleted_at.unwrap_or(0),
+                size,
+                is_dir: metadata.is_dir(),
+                record_os_path: Some(info_path),
+                original_os_path: Some(original_path),
+                trashed_os_path: Some(trashed_path),
+            })
+        })
+        .collect()
+}
+
+/// The home trash and the trash of every mounted volume
+pub fn all_trash_dirs() -> Vec<TrashDir> {
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let mut mount_points: Vec<PathBuf> = disks
+        .iter()
+        .map(|disk| disk.mount_point().to_path_buf())
+        .collect();
+    mount_points.sort();
+    mount_points.dedup();
+
+    home_trash()
+        .into_iter()
+        .chain(
+            mount_points
+                .iter()
+                .flat_map(|mount_point| volume_trash_dirs(mount_point)),
+        )
+        .collect()
+}
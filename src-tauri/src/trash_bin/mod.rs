@@ -0,0 +1,427 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! The system trash. On Linux the FreeDesktop.org specification is
+//! implemented here so items trashed in Sigma show up in Nautilus and
+//! Dolphin and the other way around, with each volume's own trash used for
+//! files on it. Windows and macOS go through the `trash` crate, which uses
+//! the shell's recycle bin and Finder's trash, except on removable drives,
+//! which Windows gives no recycle bin and where Sigma keeps its own. A
+//! trash may be capped in size, with its oldest items purged to make room,
+//! and a retention policy purges old items in the background. Items are
+//! restored from here too, see `restore`.
+
+#[cfg(target_os = "linux")]
+mod freedesktop;
+mod purge;
+#[cfg(windows)]
+mod recycle_bin;
+mod restore;
+
+use crate::json_store;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use restore::{RestoreCheck, RestoreOutcome};
+
+const SETTINGS_STORE: &str = "trash-settings";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashItem {
+    /// Identifies the item for later trash operations
+    pub id: String,
+    pub name: String,
+    pub original_path: String,
+    /// Where the item is kept, when the trash is a plain folder
+    pub trashed_path: Option<String>,
+    /// Milliseconds since the Unix epoch, 0 when unknown
+    pub deleted_at: u64,
+    pub size: u64,
+    pub is_dir: bool,
+    /// Exact paths of the record in `id`, the original location and the
+    /// kept item, which the strings above only show lossily when a name
+    /// isn't valid UTF-8. Set when listed from a plain folder trash.
+    #[serde(skip)]
+    pub(crate) record_os_path: Option<PathBuf>,
+    #[serde(skip)]
+    pub(crate) original_os_path: Option<PathBuf>,
+    #[serde(skip)]
+    pub(crate) trashed_os_path: Option<PathBuf>,
+}
+
+impl TrashItem {
+    pub(crate) fn record_location(&self) -> PathBuf {
+        self.record_os_path
+            .clone()
+            .unwrap_or_else(|| crate::utils::to_os_path(&self.id))
+    }
+
+    pub(crate) fn original_location(&self) -> PathBuf {
+        self.original_os_path
+            .clone()
+            .unwrap_or_else(|| crate::utils::to_os_path(&self.original_path))
+    }
+
+    pub(crate) fn trashed_location(&self) -> Option<PathBuf> {
+        self.trashed_os_path
+            .clone()
+            .or_else(|| self.trashed_path.as_deref().map(crate::utils::to_os_path))
+    }
+}
+
+/// Size caps of the trash on each volume, of which the smaller applies
+/// when both are set, and the retention policy of the whole trash
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrashSettings {
+    pub max_size: Option<u64>,
+    /// Percentage of the volume's capacity
+    pub max_size_percent: Option<u8>,
+    /// Items trashed longer ago are purged
+    pub purge_after_days: Option<u32>,
+    /// The oldest items are purged until the trash of all volumes together
+    /// fits this size
+    pub max_total_size: Option<u64>,
+}
+
+/// Items a purge deletes, or would delete in a preview
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashPurge {
+    pub items: Vec<TrashItem>,
+    pub total_size: u64,
+    pub errors: Vec<String>,
+}
+
+static SETTINGS: Lazy<Mutex<TrashSettings>> =
+    Lazy::new(|| Mutex::new(json_store::load(SETTINGS_STORE)));
+
+#[cfg(not(target_os = "macos"))]
+fn size_cap(volume_path: &Path) -> Option<u64> {
+    let settings = SETTINGS.lock().ok()?.clone();
+    let percent_cap = settings.max_size_percent.and_then(|percent| {
+        let volume = crate::utils::volume_space(volume_path)?;
+        Some(volume.total_bytes / 100 * u64::from(percent.min(100)))
+    });
+
+    [settings.max_size, percent_cap].into_iter().flatten().min()
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Permanently deletes an item kept in a plain folder trash. The item goes
+/// before its record, so an interruption never leaves an untracked item.
+fn delete_item(item: &TrashItem) -> Result<(), String> {
+    let trashed_path = item
+        .trashed_location()
+        .ok_or_else(|| format!("{} can't be deleted from here", item.name))?;
+
+    crate::file_operations::remove_dir_or_file(&trashed_path)?;
+    std::fs::remove_file(item.record_location()).map_err(|error| error.to_string())
+}
+
+/// Purges the oldest items of one trash until it fits under its volume's
+/// cap. The item just trashed, at `kept_path`, is never purged.
+#[cfg(not(target_os = "macos"))]
+fn enforce_size_cap(volume_path: &Path, mut items: Vec<TrashItem>, kept_path: &Path) {
+    let Some(cap) = size_cap(volume_path) else {
+        return;
+    };
+    let mut total_size: u64 = items.iter().map(|item| item.size).sum();
+    if total_size <= cap {
+        return;
+    }
+
+    let kept_path = crate::utils::normalize_path(&kept_path.to_string_lossy());
+    items.sort_by_key(|item| item.deleted_at);
+
+    for item in &items {
+        if total_size <= cap {
+            break;
+        }
+        if item.trashed_path.as_deref() == Some(kept_path.as_str()) {
+            continue;
+        }
+
+        match delete_item(item) {
+            Ok(()) => total_size = total_size.saturating_sub(item.size),
+            Err(error) => tracing::warn!(
+                "Failed to purge {} from the trash: {}",
+                item.original_path,
+                error
+            ),
+        }
+    }
+}
+
+/// Moves a file or folder to the trash
+pub fn trash_path(path: &Path) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    {
+        let (trash_dir, trashed_path) = freedesktop::trash_path(path)?;
+        enforce_size_cap(
+            &trash_dir.path,
+            freedesktop::list_trash_dir(&trash_dir),
+            &trashed_path,
+        );
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    {
+        let removable_volume =
+            crate::utils::volume_space(path).filter(|volume| volume.is_removable);
+
+        if let Some(volume) = removable_volume {
+            let mount_point = crate::utils::to_os_path(&volume.mount_point);
+            let trashed_path = recycle_bin::trash_path(path, &mount_point)?;
+            enforce_size_cap(
+                &mount_point,
+                recycle_bin::list_bin(&mount_point),
+                &trashed_path,
+            );
+            return Ok(());
+        }
+
+        trash::delete(path).map_err(|error| error.to_string())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        trash::delete(path).map_err(|error| error.to_string())
+    }
+}
+
+fn list_items() -> Result<Vec<TrashItem>, String> {
+    #[cfg(target_os = "linux")]
+    {
+        Ok(freedesktop::all_trash_dirs()
+            .iter()
+            .flat_map(freedesktop::list_trash_dir)
+            .collect())
+    }
+
+    #[cfg(windows)]
+    {
+        let items = trash::os_limited::list().map_err(|error| error.to_string())?;
+
+        let mut trash_items: Vec<TrashItem> = items
+            .into_iter()
+            .map(|item| {
+                let (size, is_dir) =
+                    match trash::os_limited::metadata(&item).map(|metadata| metadata.size) {
+                        Ok(trash::TrashItemSize::Bytes(bytes)) => (bytes, false),
+                        Ok(trash::TrashItemSize::Entries(_)) => (0, true),
+                        Err(_) => (0, false),
+                    };
+
+                TrashItem {
+                    id: item.id.to_string_lossy().to_string(),
+                    name: item.name.to_string_lossy().to_string(),
+                    original_path: crate::utils::normalize_path(
+                        &item.original_path().to_string_lossy(),
+                    ),
+                    trashed_path: None,
+                    deleted_at: item.time_deleted.max(0) as u64 * 1000,
+                    size,
+                    is_dir,
+                    record_os_path: None,
+                    original_os_path: None,
+                    trashed_os_path: None,
+                }
+            })
+            .collect();
+
+        trash_items.extend(
+            recycle_bin::removable_mount_points()
+                .iter()
+                .flat_map(|mount_point| recycle_bin::list_bin(mount_point)),
+        );
+
+        Ok(trash_items)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Err("Listing the trash is not supported on macOS".to_string())
+    }
+}
+
+/// Items in the trash, most recently deleted first
+#[tauri::command]
+pub async fn list_trash() -> Result<Vec<TrashItem>, String> {
+    tokio::task::spawn_blocking(|| {
+        let mut items = list_items()?;
+        items.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+        Ok(items)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub fn get_trash_settings() -> Result<TrashSettings, String> {
+    Ok(SETTINGS.lock().map_err(|error| error.to_string())?.clone())
+}
+
+/// Sets the size caps, which apply from the next item trashed, and the
+/// retention policy, which applies from the next purge
+#[tauri::command]
+pub fn set_trash_settings(settings: TrashSettings) -> Result<(), String> {
+    let mut current = SETTINGS.lock().map_err(|error| error.to_string())?;
+    json_store::save(SETTINGS_STORE, &settings)?;
+    *current = settings;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn purge_shell_items(items: &[&TrashItem]) -> Result<(), String> {
+    let ids: std::collections::HashSet<&str> = items.iter().map(|item| item.id.as_str()).collect();
+    let shell_items: Vec<trash::TrashItem> = trash::os_limited::list()
+        .map_err(|error| error.to_string())?
+        .into_iter()
+        .filter(|item| ids.contains(item.id.to_string_lossy().as_ref()))
+        .collect();
+
+    trash::os_limited::purge_all(shell_items).map_err(|error| error.to_string())
+}
+
+#[cfg(not(windows))]
+fn purge_shell_items(_items: &[&TrashItem]) -> Result<(), String> {
+    Err("Deleting from the system trash is not supported on this platform".to_string())
+}
+
+/// Permanently deletes trashed items, returning what failed
+fn delete_items(items: &[TrashItem]) -> Vec<String> {
+    let (plain_items, shell_items): (Vec<&TrashItem>, Vec<&TrashItem>) =
+        items.iter().partition(|item| item.trashed_path.is_some());
+
+    let mut errors: Vec<String> = plain_items
+        .iter()
+        .filter_map(|item| {
+            delete_item(item)
+                .err()
+                .map(|error| format!("{}: {}", item.original_path, error))
+        })
+        .collect();
+
+    if !shell_items.is_empty() {
+        if let Err(error) = purge_shell_items(&shell_items) {
+            errors.push(error);
+        }
+    }
+
+    errors
+}
+
+fn purge_by(settings: &TrashSettings, dry_run: bool) -> Result<TrashPurge, String> {
+    let items = purge::purge_candidates(list_items()?, settings, now_ms());
+    let errors = if dry_run {
+        Vec::new()
+    } else {
+        delete_items(&items)
+    };
+
+    Ok(TrashPurge {
+        total_size: items.iter().map(|item| item.size).sum(),
+        items,
+        errors,
+    })
+}
+
+/// Applies the retention policy, or only reports what it would delete
+fn purge(dry_run: bool) -> Result<TrashPurge, String> {
+    let settings = SETTINGS.lock().map_err(|error| error.to_string())?.clone();
+    purge_by(&settings, dry_run)
+}
+
+/// Purges by limits other than the configured policy, for scheduled tasks
+pub fn purge_with(
+    purge_after_days: Option<u32>,
+    max_total_size: Option<u64>,
+) -> Result<TrashPurge, String> {
+    let settings = TrashSettings {
+        purge_after_days,
+        max_total_size,
+        ..Default::default()
+    };
+    purge_by(&settings, false)
+}
+
+/// Starts applying the retention policy in the background
+pub fn start_purging() {
+    purge::start();
+}
+
+/// What the next purge would delete under the current policy
+#[tauri::command]
+pub async fn preview_trash_purge() -> Result<TrashPurge, String> {
+    tokio::task::spawn_blocking(|| purge(true))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Applies the retention policy now instead of waiting for the next run
+#[tauri::command]
+pub async fn purge_trash() -> Result<TrashPurge, String> {
+    tokio::task::spawn_blocking(|| purge(false))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Whether each item can go back to where it was, so the frontend can offer
+/// another destination for those that can't
+#[tauri::command]
+pub async fn check_trash_restore(ids: Vec<String>) -> Result<Vec<RestoreCheck>, String> {
+    tokio::task::spawn_blocking(move || {
+        let items = list_items()?;
+
+        Ok(items
+            .into_iter()
+            .filter(|item| ids.contains(&item.id))
+            .map(|item| RestoreCheck {
+                state: restore::target_state(&item.original_location()),
+                id: item.id,
+                original_path: item.original_path,
+            })
+            .collect())
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+/// Restores items to their original locations, recreating missing parent
+/// folders, or into `destination` when given
+#[tauri::command]
+pub async fn restore_trash_items(
+    ids: Vec<String>,
+    destination: Option<String>,
+) -> Result<Vec<RestoreOutcome>, String> {
+    tokio::task::spawn_blocking(move || {
+        let items = list_items()?;
+        let destination = destination.map(|destination| crate::utils::to_os_path(&destination));
+
+        Ok(ids
+            .iter()
+            .map(|id| match items.iter().find(|item| &item.id == id) {
+                Some(item) => restore::restore(item, destination.as_deref()),
+                None => RestoreOutcome {
+                    id: id.clone(),
+                    restored_path: None,
+                    error: Some("The item is no longer in the trash".to_string()),
+                    state: None,
+                },
+            })
+            .collect())
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
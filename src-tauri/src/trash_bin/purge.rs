@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Retention policy of the trash: items trashed more than a set number of
+//! days ago are deleted, then the oldest items until the whole trash fits a
+//! total size. The policy is evaluated periodically while Sigma runs.
+
+use super::{TrashItem, TrashSettings};
+use std::thread;
+use std::time::Duration;
+
+/// Lets startup finish before the first purge
+const STARTUP_DELAY: Duration = Duration::from_secs(5 * 60);
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Items the policy would delete, oldest first
+pub fn purge_candidates(
+    mut items: Vec<TrashItem>,
+    settings: &TrashSettings,
+    now_ms: u64,
+) -> Vec<TrashItem> {
+    items.sort_by_key(|item| item.deleted_at);
+
+    let cutoff = settings
+        .purge_after_days
+        .map(|days| now_ms.saturating_sub(u64::from(days) * DAY_MS));
+    let mut remaining_size: u64 = items.iter().map(|item| item.size).sum();
+
+    items
+        .into_iter()
+        .filter(|item| {
+            // An unknown deletion time never counts as expired
+            let is_expired =
+                cutoff.is_some_and(|cutoff| item.deleted_at > 0 && item.deleted_at < cutoff);
+            let is_over_size = settings
+                .max_total_size
+                .is_some_and(|max_total_size| remaining_size > max_total_size);
+
+            if is_expired || is_over_size {
+                remaining_size = remaining_size.saturating_sub(item.size);
+            }
+            is_expired || is_over_size
+        })
+        .collect()
+}
+
+fn run() {
+    thread::sleep(STARTUP_DELAY);
+
+    loop {
+        let has_policy = super::SETTINGS
+            .lock()
+            .map(|settings| {
+                settings.purge_after_days.is_some() || settings.max_total_size.is_some()
+            })
+            .unwrap_or(false);
+
+        if has_policy {
+            match super::purge(false) {
+                Ok(purge) if !purge.items.is_empty() => tracing::info!(
+                    "Purged {} items ({} bytes) from the trash",
+                    purge.items.len(),
+                    purge.total_size
+                ),
+                Ok(_) => {}
+                Err(error) => tracing::warn!("Failed to purge the trash: {}", error),
+            }
+        }
+
+        thread::sleep(PURGE_INTERVAL);
+    }
+}
+
+pub fn start() {
+    let spawn_result = thread::Builder::new()
+        .name("trash-purge".to_string())
+        .spawn(run);
+
+    if let Err(error) = spawn_result {
+        tracing::error!("Failed to start the trash purge: {}", error);
+    }
+}
@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Recycle bins of removable drives. Windows deletes files on USB sticks and
+//! memory cards permanently, so Sigma keeps a `$RECYCLE.BIN` on them laid
+//! out the way Explorer lays out the bins of fixed drives: in a folder named
+//! after the user's SID, each item is a `$R` file or folder next to an `$I`
+//! file recording its original path, size and deletion time.
+
+use super::TrashItem;
+use crate::utils::normalize_path;
+use std::fs;
+use std::io::Write;
+use std::os::windows::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const RECYCLE_BIN_DIR: &str = "$RECYCLE.BIN";
+const INFO_FORMAT_VERSION: u64 = 2;
+const INFO_HEADER_SIZE: usize = 28;
+/// Seconds between the FILETIME epoch (1601) and the Unix epoch
+const FILETIME_EPOCH_OFFSET: u64 = 11_644_473_600;
+const FILETIME_TICKS_PER_MS: u64 = 10_000;
+
+/// The user's folder in the recycle bin of the volume at `mount_point`
+pub fn user_bin_dir(mount_point: &Path) -> Option<PathBuf> {
+    let sid = crate::permissions::acl::current_user_sid()?;
+    Some(mount_point.join(RECYCLE_BIN_DIR).join(sid))
+}
+
+fn create_bin_dir(mount_point: &Path) -> Result<PathBuf, String> {
+    use windows_sys::Win32::Storage::FileSystem::{
+        SetFileAttributesW, FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_SYSTEM,
+    };
+
+    let bin_dir = user_bin_dir(mount_point)
+        .ok_or_else(|| "Cannot determine the current user's SID".to_string())?;
+    fs::create_dir_all(&bin_dir).map_err(|error| error.to_string())?;
+
+    // Explorer hides the bin the same way on fixed drives
+    let root: Vec<u16> = mount_point
+        .join(RECYCLE_BIN_DIR)
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    unsafe {
+        SetFileAttributesW(root.as_ptr(), FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM);
+    }
+
+    Ok(bin_dir)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn item_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// `$I` file contents: version, size, deletion FILETIME, then the original
+/// path as a counted, NUL terminated UTF-16 string
+fn encode_info(original_path: &Path, size: u64, deleted_at: u64) -> Vec<u8> {
+    let wide_path: Vec<u16> = original_path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let file_time = (deleted_at + FILETIME_EPOCH_OFFSET * 1000) * FILETIME_TICKS_PER_MS;
+
+    let mut bytes = Vec::with_capacity(INFO_HEADER_SIZE + wide_path.len() * 2);
+    bytes.extend_from_slice(&INFO_FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&size.to_le_bytes());
+    bytes.extend_from_slice(&file_time.to_le_bytes());
+    bytes.extend_from_slice(&(wide_path.len() as u32).to_le_bytes());
+    for unit in wide_path {
+        bytes.extend_from_slice(&unit.to_le_bytes());
+    }
+    bytes
+}
+
+/// Original path, size and deletion time in Unix milliseconds
+fn decode_info(bytes: &[u8]) -> Option<(String, u64, u64)> {
+    let read_u64 = |offset: usize| -> Option<u64> {
+        Some(u64::from_le_bytes(
+            bytes.get(offset..offset + 8)?.try_into().ok()?,
+        ))
+    };
+
+    // Version 1 (Vista to 8.1) has a fixed 260 character path after the time
+    let (path_start, path_length) = match read_u64(0)? {
+        1 => (24, 260),
+        2 => (
+            INFO_HEADER_SIZE,
+            u32::from_le_bytes(bytes.get(24..28)?.try_into().ok()?) as usize,
+        ),
+        _ => return None,
+    };
+
+    let wide_path: Vec<u16> = bytes
+        .get(path_start..path_start + path_length * 2)?
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    let deleted_at =
+        (read_u64(16)? / FILETIME_TICKS_PER_MS).saturating_sub(FILETIME_EPOCH_OFFSET * 1000);
+
+    Some((
+        String::from_utf16_lossy(&wide_path),
+        read_u64(8)?,
+        deleted_at,
+    ))
+}
+
+/// Moves a file or folder into the recycle bin of its removable volume and
+/// returns where it is kept
+pub fn trash_path(path: &Path, mount_point: &Path) -> Result<PathBuf, String> {
+    let path = std::path::absolute(path).map_err(|error| error.to_string())?;
+    let bin_dir = create_bin_dir(mount_point)?;
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let size = item_size(&path);
+
+    // Explorer names items with six random characters; a collision just
+    // means drawing again
+    for _ in 0..100 {
+        let key: String = uuid::Uuid::new_v4()
+            .simple()
+            .to_string()
+            .to_uppercase()
+            .chars()
+            .take(6)
+            .collect();
+        let info_path = bin_dir.join(format!("$I{}{}", key, extension));
+        let trashed_path = bin_dir.join(format!("$R{}{}", key, extension));
+
+        let mut info_file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error.to_string()),
+        };
+        if trashed_path.exists() {
+            let _ = fs::remove_file(&info_path);
+            continue;
+        }
+
+        let moved = info_file
+            .write_all(&encode_info(&path, size, now_ms()))
+            .and_then(|()| info_file.sync_all())
+            .and_then(|()| fs::rename(&path, &trashed_path));
+
+        if let Err(error) = moved {
+            drop(info_file);
+            let _ = fs::remove_file(&info_path);
+            return Err(error.to_string());
+        }
+
+        return Ok(trashed_path);
+    }
+
+    Err(format!(
+        "No free name in the recycle bin for {}",
+        path.display()
+    ))
+}
+
+/// Items in the user's recycle bin of one volume
+pub fn list_bin(mount_point: &Path) -> Vec<TrashItem> {
+    let Some(bin_dir) = user_bin_dir(mount_point) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(&bin_dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let key = file_name.strip_prefix("$I")?;
+            let info_path = entry.path();
+            let trashed_path = bin_dir.join(format!("$R{}", key));
+            let metadata = fs::symlink_metadata(&trashed_path).ok()?;
+            let (original_path, size, deleted_at) = decode_info(&fs::read(&info_path).ok()?)?;
+
+            Some(TrashItem {
+                id: normalize_path(&info_path.to_string_lossy()),
+                name: Path::new(&original_path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| key.to_string()),
+                original_path: normalize_path(&original_path),
+                trashed_path: Some(normalize_path(&trashed_path.to_string_lossy())),
+                deleted_at,
+                size,
+                is_dir: metadata.is_dir(),
+                record_os_path: Some(info_path),
+                original_os_path: None,
+                trashed_os_path: Some(trashed_path),
+            })
+        })
+        .collect()
+}
+
+/// Mount points of removable volumes, whose bins the shell doesn't list
+pub fn removable_mount_points() -> Vec<PathBuf> {
+    sysinfo::Disks::new_with_refreshed_list()
+        .iter()
+        .filter(|disk| disk.is_removable())
+        .map(|disk| disk.mount_point().to_path_buf())
+        .collect()
+}
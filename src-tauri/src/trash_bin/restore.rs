@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Restoring trashed items, to where they were or to a chosen folder.
+//! Parent folders deleted since are recreated, but a location on a drive
+//! that isn't mounted is reported instead, so the user can pick another
+//! destination rather than have the path recreated on the wrong volume.
+
+use super::TrashItem;
+use crate::file_operations::{
+    copy_item, get_unique_destination_path, remove_dir_or_file, CopyOptions, Retrier, RetryPolicy,
+};
+use crate::utils::normalize_path;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where removable and network drives get mounted; a missing folder below
+/// one of them is usually a drive that isn't mounted, not a deleted folder
+const MOUNT_ROOTS: [&str; 4] = ["/media", "/run/media", "/mnt", "/Volumes"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreTargetState {
+    Available,
+    /// Parent folders were deleted and are recreated on restore
+    MissingParents,
+    /// On a drive that isn't mounted now
+    VolumeUnavailable,
+    /// Something else exists at the path
+    Occupied,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreCheck {
+    pub id: String,
+    pub original_path: String,
+    pub state: RestoreTargetState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreOutcome {
+    pub id: String,
+    pub restored_path: Option<String>,
+    pub error: Option<String>,
+    /// Why the original location couldn't be used, when that was the case
+    pub state: Option<RestoreTargetState>,
+}
+
+impl RestoreOutcome {
+    fn failed(id: &str, error: String, state: Option<RestoreTargetState>) -> Self {
+        RestoreOutcome {
+            id: id.to_string(),
+            restored_path: None,
+            error: Some(error),
+            state,
+        }
+    }
+}
+
+pub fn target_state(path: &Path) -> RestoreTargetState {
+    if fs::symlink_metadata(path).is_ok() {
+        return RestoreTargetState::Occupied;
+    }
+    if path.parent().is_some_and(Path::is_dir) {
+        return RestoreTargetState::Available;
+    }
+
+    // A Windows drive letter that isn't there leaves no ancestor at all
+    let Some(existing_ancestor) = path.ancestors().skip(1).find(|ancestor| ancestor.is_dir())
+    else {
+        return RestoreTargetState::VolumeUnavailable;
+    };
+
+    let is_under_mount_root = MOUNT_ROOTS
+        .iter()
+        .any(|mount_root| existing_ancestor.starts_with(mount_root));
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let is_on_mounted_drive = disks
+        .iter()
+        .map(|disk| disk.mount_point())
+        .filter(|mount_point| existing_ancestor.starts_with(mount_point))
+        .max_by_key(|mount_point| mount_point.as_os_str().len())
+        .is_some_and(|mount_point| {
+            MOUNT_ROOTS
+                .iter()
+                .any(|mount_root| mount_point.starts_with(mount_root))
+        });
+
+    if is_under_mount_root && !is_on_mounted_drive {
+        RestoreTargetState::VolumeUnavailable
+    } else {
+        RestoreTargetState::MissingParents
+    }
+}
+
+fn move_item(from: &Path, to: &Path) -> Result<(), String> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        // Restoring to another volume
+        Err(error) if error.raw_os_error() == Some(17) || error.raw_os_error() == Some(18) => {
+            let mut retrier = Retrier::new(RetryPolicy::default());
+            copy_item(from, to, &mut retrier, &CopyOptions::default()).inspect_err(|_| {
+                let _ = remove_dir_or_file(to);
+            })?;
+            remove_dir_or_file(from)
+        }
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+// Items of plain folder trashes; the record goes last, so a failed
+// restore leaves the item listed
+fn restore_plain_item(item: &TrashItem, trashed_path: &Path, target: &Path) -> Result<(), String> {
+    move_item(trashed_path, target)?;
+    let _ = fs::remove_file(item.record_location());
+    Ok(())
+}
+
+// The shell only restores to the original location, so other destinations
+// are reached with a move from there
+#[cfg(windows)]
+fn restore_shell_item(item: &TrashItem, target: &Path) -> Result<(), String> {
+    let shell_item = trash::os_limited::list()
+        .map_err(|error| error.to_string())?
+        .into_iter()
+        .find(|shell_item| shell_item.id.to_string_lossy() == item.id)
+        .ok_or_else(|| format!("{} is no longer in the trash", item.name))?;
+
+    trash::os_limited::restore_all([shell_item]).map_err(|error| error.to_string())?;
+
+    let original_path = item.original_location();
+    if original_path != target {
+        move_item(&original_path, target)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn restore_shell_item(_item: &TrashItem, _target: &Path) -> Result<(), String> {
+    Err("Restoring from the system trash is not supported on this platform".to_string())
+}
+
+/// Restores an item to its original location, or into `destination` under
+/// a free name
+pub fn restore(item: &TrashItem, destination: Option<&Path>) -> RestoreOutcome {
+    let target: PathBuf = match destination {
+        Some(destination) => {
+            if !destination.is_dir() {
+                return RestoreOutcome::failed(
+                    &item.id,
+                    format!("Not a directory: {}", destination.display()),
+                    None,
+                );
+            }
+            get_unique_destination_path(destination, &item.name)
+        }
+        None => {
+            let original_path = item.original_location();
+
+            match target_state(&original_path) {
+                RestoreTargetState::Available => {}
+                RestoreTargetState::MissingParents => {
+                    let parent = original_path.parent().unwrap_or(&original_path);
+                    if let Err(error) = fs::create_dir_all(parent) {
+                        return RestoreOutcome::failed(&item.id, error.to_string(), None);
+                    }
+                }
+                state => {
+                    let error = match state {
+                        RestoreTargetState::Occupied => "The original location is taken",
+                        _ => "The original location is on a drive that isn't mounted",
+                    };
+                    return RestoreOutcome::failed(&item.id, error.to_string(), Some(state));
+                }
+            }
+            original_path
+        }
+    };
+
+    let result = match item.trashed_location() {
+        Some(trashed_path) => restore_plain_item(item, &trashed_path, &target),
+        None => restore_shell_item(item, &target),
+    };
+
+    match result {
+        Ok(()) => RestoreOutcome {
+            id: item.id.clone(),
+            restored_path: Some(normalize_path(&target.to_string_lossy())),
+            error: None,
+            state: None,
+        },
+        Err(error) => RestoreOutcome::failed(&item.id, error, None),
+    }
+}
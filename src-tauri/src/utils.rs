@@ -2,6 +2,202 @@
 // License: GNU GPLv3 or later. See the license file in the project root for more information.
 // Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
 
+// Paths this long need the verbatim prefix on Windows. Directories are
+// limited to MAX_PATH minus room for an 8.3 file name.
+#[cfg(windows)]
+const MAX_PATH_WITHOUT_PREFIX: usize = 248;
+
+/// Forward-slash path for the frontend. Verbatim prefixes added by
+/// `to_os_path` are removed, so UNC paths come out as `//server/share/...`.
 pub fn normalize_path(path: &str) -> String {
-    path.replace('\\', "/")
+    let path = path.replace('\\', "/");
+
+    if let Some(share_path) = path.strip_prefix("//?/UNC/") {
+        format!("//{}", share_path)
+    } else if let Some(local_path) = path.strip_prefix("//?/") {
+        local_path.to_string()
+    } else {
+        path
+    }
+}
+
+// Resolves "." and ".." lexically, verbatim paths are passed to the file
+// system as they are
+#[cfg(windows)]
+fn clean_components(path: &std::path::Path) -> std::path::PathBuf {
+    use std::path::Component;
+
+    let mut cleaned = std::path::PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !matches!(
+                    cleaned.components().next_back(),
+                    Some(Component::Prefix(_)) | Some(Component::RootDir) | None
+                ) {
+                    cleaned.pop();
+                }
+            }
+            other => cleaned.push(other.as_os_str()),
+        }
+    }
+
+    cleaned
+}
+
+/// Always adds the verbatim prefix on Windows, which also reaches names with
+/// trailing dots or reserved device names. Other platforms get the path as is.
+#[cfg(windows)]
+pub fn to_verbatim_path(path: &str) -> std::path::PathBuf {
+    let path = path.replace('/', "\\");
+
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return std::path::PathBuf::from(path);
+    }
+
+    let cleaned = clean_components(std::path::Path::new(&path));
+    let cleaned = cleaned.to_string_lossy();
+
+    if let Some(share_path) = cleaned.strip_prefix(r"\\") {
+        std::path::PathBuf::from(format!(r"\\?\UNC\{}", share_path))
+    } else if std::path::Path::new(cleaned.as_ref()).is_absolute() {
+        std::path::PathBuf::from(format!(r"\\?\{}", cleaned))
+    } else {
+        std::path::PathBuf::from(path)
+    }
+}
+
+#[cfg(not(windows))]
+pub fn to_verbatim_path(path: &str) -> std::path::PathBuf {
+    std::path::PathBuf::from(path)
+}
+
+/// Converts a frontend path to one every fs call accepts: backslashes and,
+/// for paths over the Windows MAX_PATH limit, the verbatim prefix (`\\?\`
+/// or `\\?\UNC\` for shares). Commands pass every path they get through
+/// this. Paths joined onto it while walking a tree need nothing more, as
+/// std adds the prefix itself to absolute paths that get too long, but
+/// Win32 calls made directly, such as the ACL ones, rely on it.
+#[cfg(windows)]
+pub fn to_os_path(path: &str) -> std::path::PathBuf {
+    if path.len() >= MAX_PATH_WITHOUT_PREFIX {
+        to_verbatim_path(path)
+    } else {
+        std::path::PathBuf::from(path.replace('/', "\\"))
+    }
+}
+
+#[cfg(not(windows))]
+pub fn to_os_path(path: &str) -> std::path::PathBuf {
+    std::path::PathBuf::from(path)
+}
+
+/// The volume a path lives on and its free space
+#[derive(Debug, Clone)]
+pub struct VolumeSpace {
+    pub name: String,
+    pub mount_point: String,
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+    pub is_removable: bool,
+    /// Backed by another machine, such as NFS, SMB or SSHFS mounts
+    pub is_network: bool,
+    pub file_system: String,
+    /// `None` when the kind of drive can't be determined
+    pub is_solid_state: Option<bool>,
+}
+
+const NETWORK_FILE_SYSTEMS: [&str; 10] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smbfs",
+    "smb3",
+    "afpfs",
+    "webdav",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "fuse.gvfsd-fuse",
+];
+
+/// Finds the volume containing `path`, if it can be determined
+pub fn volume_space(path: &std::path::Path) -> Option<VolumeSpace> {
+    let path = std::fs::canonicalize(path).ok()?;
+
+    // Mount points are reported without the verbatim prefix canonicalize adds
+    #[cfg(windows)]
+    let path = {
+        let stripped = path
+            .to_string_lossy()
+            .strip_prefix(r"\\?\")
+            .map(std::path::PathBuf::from);
+        stripped.unwrap_or(path)
+    };
+
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+
+    disks
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| VolumeSpace {
+            name: disk.name().to_string_lossy().to_string(),
+            mount_point: normalize_path(&disk.mount_point().to_string_lossy()),
+            available_bytes: disk.available_space(),
+            total_bytes: disk.total_space(),
+            is_removable: disk.is_removable(),
+            is_network: NETWORK_FILE_SYSTEMS
+                .contains(&disk.file_system().to_string_lossy().to_lowercase().as_str()),
+            file_system: disk.file_system().to_string_lossy().to_string(),
+            is_solid_state: match disk.kind() {
+                sysinfo::DiskKind::SSD => Some(true),
+                sysinfo::DiskKind::HDD => Some(false),
+                sysinfo::DiskKind::Unknown(_) => None,
+            },
+        })
+}
+
+/// Milliseconds since the Unix epoch of a UTC calendar date and time, for
+/// formats that store timestamps as fields (zip entries, MTP objects)
+pub fn civil_to_unix_millis(
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+) -> u64 {
+    // Days since the epoch of a proleptic Gregorian date
+    let shifted_year = if month <= 2 { year - 1 } else { year };
+    let era = shifted_year.div_euclid(400);
+    let year_of_era = shifted_year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second;
+
+    (seconds.max(0) as u64) * 1000
+}
+
+/// UTC calendar date (year, month, day) of milliseconds since the Unix epoch
+pub fn unix_millis_to_civil_date(millis: u64) -> (i64, u32, u32) {
+    let days = (millis / 1000 / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
 }
@@ -0,0 +1,210 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Zip archives as folders. Entries can be listed, extracted and added;
+//! removing or renaming would mean rewriting the whole archive, so those
+//! are left to the archive tools.
+
+use super::{unsupported, Capabilities, VfsEntry, VirtualFileSystem};
+use crate::utils::{civil_to_unix_millis, to_os_path};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub struct ArchiveFs {
+    archive_path: String,
+}
+
+// Zip timestamps are local calendar dates without a zone, treated as UTC
+fn to_millis(date_time: zip::DateTime) -> u64 {
+    civil_to_unix_millis(
+        date_time.year() as i64,
+        date_time.month() as i64,
+        date_time.day() as i64,
+        date_time.hour() as i64,
+        date_time.minute() as i64,
+        date_time.second() as i64,
+    )
+}
+
+fn entry_name(inner_path: &str) -> &str {
+    inner_path.trim_matches('/')
+}
+
+impl ArchiveFs {
+    pub fn new(archive_path: &str) -> Self {
+        ArchiveFs {
+            archive_path: archive_path.to_string(),
+        }
+    }
+
+    fn open_archive(&self) -> Result<ZipArchive<File>, String> {
+        let file = File::open(to_os_path(&self.archive_path)).map_err(|error| error.to_string())?;
+        ZipArchive::new(file).map_err(|error| error.to_string())
+    }
+
+    fn open_writer(&self) -> Result<ZipWriter<File>, String> {
+        let os_path = to_os_path(&self.archive_path);
+
+        if os_path.exists() {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(os_path)
+                .map_err(|error| error.to_string())?;
+            ZipWriter::new_append(file).map_err(|error| error.to_string())
+        } else {
+            let file = File::create(os_path).map_err(|error| error.to_string())?;
+            Ok(ZipWriter::new(file))
+        }
+    }
+
+    /// Direct children of a folder. Archives don't always store folder
+    /// entries, so folders are also inferred from the paths of their files.
+    fn children(&self, inner_path: &str) -> Result<Vec<VfsEntry>, String> {
+        let mut archive = self.open_archive()?;
+        let prefix = match entry_name(inner_path) {
+            "" => String::new(),
+            folder => format!("{}/", folder),
+        };
+        let mut children: BTreeMap<String, VfsEntry> = BTreeMap::new();
+
+        for index in 0..archive.len() {
+            let file = archive.by_index(index).map_err(|error| error.to_string())?;
+            let Some(relative) = file.name().strip_prefix(&prefix) else {
+                continue;
+            };
+            let relative = relative.trim_end_matches('/');
+
+            if relative.is_empty() {
+                continue;
+            }
+
+            let (name, is_nested) = match relative.split_once('/') {
+                Some((name, _)) => (name, true),
+                None => (relative, false),
+            };
+            let is_dir = is_nested || file.is_dir();
+
+            if is_nested && children.contains_key(name) {
+                continue;
+            }
+
+            children.insert(
+                name.to_string(),
+                VfsEntry {
+                    name: name.to_string(),
+                    is_dir,
+                    is_symlink: false,
+                    size: if is_dir { 0 } else { file.size() },
+                    modified_time: if is_nested {
+                        0
+                    } else {
+                        file.last_modified().map(to_millis).unwrap_or(0)
+                    },
+                    accessed_time: 0,
+                    created_time: 0,
+                    mode: file.unix_mode(),
+                },
+            );
+        }
+
+        Ok(children.into_values().collect())
+    }
+}
+
+impl VirtualFileSystem for ArchiveFs {
+    fn id(&self) -> String {
+        format!("zip://{}", self.archive_path)
+    }
+
+    fn uri(&self, path: &str) -> String {
+        format!("zip://{}!{}", self.archive_path, path)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            can_write: true,
+            ..Capabilities::default()
+        }
+    }
+
+    fn read_dir(&mut self, path: &str) -> Result<Vec<VfsEntry>, String> {
+        self.children(path)
+    }
+
+    fn stat(&mut self, path: &str) -> Result<VfsEntry, String> {
+        let name = entry_name(path);
+        let (parent, file_name) = name.rsplit_once('/').unwrap_or(("", name));
+
+        if file_name.is_empty() {
+            return Ok(VfsEntry {
+                name: Path::new(&self.archive_path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                is_dir: true,
+                is_symlink: false,
+                size: 0,
+                modified_time: 0,
+                accessed_time: 0,
+                created_time: 0,
+                mode: None,
+            });
+        }
+
+        self.children(parent)?
+            .into_iter()
+            .find(|entry| entry.name == file_name)
+            .ok_or_else(|| "Not found".to_string())
+    }
+
+    fn read_file(&mut self, path: &str, destination: &Path) -> Result<u64, String> {
+        let mut archive = self.open_archive()?;
+        let mut file = archive
+            .by_name(entry_name(path))
+            .map_err(|error| error.to_string())?;
+        let mut output = File::create(destination).map_err(|error| error.to_string())?;
+
+        io::copy(&mut file, &mut output).map_err(|error| error.to_string())
+    }
+
+    fn write_file(&mut self, source: &Path, path: &str) -> Result<u64, String> {
+        let mut input = File::open(source).map_err(|error| error.to_string())?;
+        let mut writer = self.open_writer()?;
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        writer
+            .start_file(entry_name(path), options)
+            .map_err(|error| error.to_string())?;
+        let written = io::copy(&mut input, &mut writer).map_err(|error| error.to_string())?;
+        writer.finish().map_err(|error| error.to_string())?;
+
+        Ok(written)
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), String> {
+        if self.stat(path).is_ok_and(|entry| entry.is_dir) {
+            return Ok(());
+        }
+
+        let mut writer = self.open_writer()?;
+        writer
+            .add_directory(entry_name(path), SimpleFileOptions::default())
+            .map_err(|error| error.to_string())?;
+        writer.finish().map_err(|error| error.to_string())?;
+        Ok(())
+    }
+
+    fn remove(&mut self, _path: &str, _is_dir: bool) -> Result<(), String> {
+        Err(unsupported("Deleting from an archive"))
+    }
+
+    fn rename(&mut self, _from: &str, _to: &str) -> Result<(), String> {
+        Err(unsupported("Renaming inside an archive"))
+    }
+}
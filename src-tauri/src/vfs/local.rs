@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::{Capabilities, VfsEntry, VirtualFileSystem};
+use crate::utils::to_os_path;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct LocalFs;
+
+fn to_millis(time: std::io::Result<SystemTime>) -> u64 {
+    time.ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn mode(metadata: &Metadata) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+fn to_entry(name: String, path: &Path) -> Result<VfsEntry, String> {
+    let link_metadata = fs::symlink_metadata(path).map_err(|error| error.to_string())?;
+    let is_symlink = link_metadata.file_type().is_symlink();
+    // Symlinks are reported with the type of their target
+    let metadata = if is_symlink {
+        fs::metadata(path).unwrap_or(link_metadata)
+    } else {
+        link_metadata
+    };
+
+    Ok(VfsEntry {
+        name,
+        is_dir: metadata.is_dir(),
+        is_symlink,
+        size: metadata.len(),
+        modified_time: to_millis(metadata.modified()),
+        accessed_time: to_millis(metadata.accessed()),
+        created_time: to_millis(metadata.created()),
+        mode: mode(&metadata),
+    })
+}
+
+impl VirtualFileSystem for LocalFs {
+    fn id(&self) -> String {
+        "local".to_string()
+    }
+
+    fn uri(&self, path: &str) -> String {
+        path.to_string()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            can_write: true,
+            can_delete: true,
+            can_rename: true,
+            server_side_copy: false,
+            has_trash: true,
+        }
+    }
+
+    fn read_dir(&mut self, path: &str) -> Result<Vec<VfsEntry>, String> {
+        let entries = fs::read_dir(to_os_path(path)).map_err(|error| error.to_string())?;
+
+        Ok(entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                to_entry(
+                    entry.file_name().to_string_lossy().to_string(),
+                    &entry.path(),
+                )
+                .ok()
+            })
+            .collect())
+    }
+
+    fn stat(&mut self, path: &str) -> Result<VfsEntry, String> {
+        let os_path = to_os_path(path);
+        let name = os_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string());
+
+        to_entry(name, &os_path)
+    }
+
+    fn read_file(&mut self, path: &str, destination: &Path) -> Result<u64, String> {
+        fs::copy(to_os_path(path), destination).map_err(|error| error.to_string())
+    }
+
+    fn write_file(&mut self, source: &Path, path: &str) -> Result<u64, String> {
+        fs::copy(source, to_os_path(path)).map_err(|error| error.to_string())
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), String> {
+        fs::create_dir_all(to_os_path(path)).map_err(|error| error.to_string())
+    }
+
+    fn remove(&mut self, path: &str, is_dir: bool) -> Result<(), String> {
+        if is_dir {
+            fs::remove_dir(to_os_path(path))
+        } else {
+            fs::remove_file(to_os_path(path))
+        }
+        .map_err(|error| error.to_string())
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        fs::rename(to_os_path(from), to_os_path(to)).map_err(|error| error.to_string())
+    }
+
+    fn local_path(&self, path: &str) -> Option<PathBuf> {
+        Some(to_os_path(path))
+    }
+}
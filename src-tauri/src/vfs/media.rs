@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Media library views as read-only folders: `media://Albums/<album>`,
+//! `media://Artists/<artist>`, `media://Years/<year>` and
+//! `media://Cameras/<model>`. Groups and their files are only worked out
+//! when a folder is listed. Files keep their real paths as URIs, so
+//! opening or copying one goes to the original.
+
+use super::{unsupported, Capabilities, VfsEntry, VirtualFileSystem};
+use crate::media_library::{self, LibraryFile, MediaView};
+use crate::utils::normalize_path;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const SCHEME: &str = "media://";
+
+pub struct MediaFs {
+    /// Real paths of the files listed so far, by virtual path
+    files: HashMap<String, PathBuf>,
+}
+
+fn segments(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+fn folder_entry(name: String) -> VfsEntry {
+    VfsEntry {
+        name,
+        is_dir: true,
+        is_symlink: false,
+        size: 0,
+        modified_time: 0,
+        accessed_time: 0,
+        created_time: 0,
+        mode: None,
+    }
+}
+
+fn file_entry(name: String, file: &LibraryFile) -> VfsEntry {
+    VfsEntry {
+        name,
+        is_dir: false,
+        is_symlink: false,
+        size: file.size,
+        modified_time: file.modified_time,
+        accessed_time: 0,
+        created_time: 0,
+        mode: None,
+    }
+}
+
+fn view_of(name: &str) -> Result<MediaView, String> {
+    MediaView::from_folder_name(name).ok_or_else(|| format!("Not found: {}", name))
+}
+
+impl MediaFs {
+    pub fn open(uri: &str) -> Result<(Self, String), String> {
+        let rest = uri
+            .strip_prefix(SCHEME)
+            .ok_or_else(|| format!("Invalid media path: {}", uri))?;
+
+        Ok((
+            MediaFs {
+                files: HashMap::new(),
+            },
+            format!("/{}", rest.trim_matches('/')),
+        ))
+    }
+
+    fn groups(view: MediaView) -> Result<BTreeSet<String>, String> {
+        Ok(media_library::library_files()?
+            .iter()
+            .filter_map(|file| view.group_of(&file.metadata))
+            .collect())
+    }
+
+    /// Lists a group, naming files apart when two share a name
+    fn group_files(&mut self, view: MediaView, group: &str) -> Result<Vec<VfsEntry>, String> {
+        let mut files: Vec<LibraryFile> = media_library::library_files()?
+            .into_iter()
+            .filter(|file| view.group_of(&file.metadata).as_deref() == Some(group))
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut name_counts: HashMap<String, u32> = HashMap::new();
+        let mut entries = Vec::with_capacity(files.len());
+
+        for file in files {
+            let name = file
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let count = name_counts.entry(name.clone()).or_insert(0);
+            *count += 1;
+
+            let unique_name = if *count == 1 {
+                name
+            } else {
+                let path = Path::new(&name);
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                match path.extension() {
+                    Some(extension) => {
+                        format!("{} ({}).{}", stem, count, extension.to_string_lossy())
+                    }
+                    None => format!("{} ({})", stem, count),
+                }
+            };
+
+            let virtual_path = format!("/{}/{}/{}", view.folder_name(), group, unique_name);
+            self.files.insert(virtual_path, file.path.clone());
+            entries.push(file_entry(unique_name, &file));
+        }
+
+        Ok(entries)
+    }
+
+    fn real_path(&mut self, path: &str) -> Result<PathBuf, String> {
+        if let Some(real_path) = self.files.get(path) {
+            return Ok(real_path.clone());
+        }
+
+        if let [view, group, _] = segments(path)[..] {
+            self.group_files(view_of(view)?, group)?;
+        }
+
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| format!("Not found: {}", path))
+    }
+}
+
+impl VirtualFileSystem for MediaFs {
+    fn id(&self) -> String {
+        SCHEME.to_string()
+    }
+
+    fn uri(&self, path: &str) -> String {
+        match self.files.get(path) {
+            Some(real_path) => normalize_path(&real_path.to_string_lossy()),
+            None => format!("{}{}", SCHEME, path.trim_start_matches('/')),
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn read_dir(&mut self, path: &str) -> Result<Vec<VfsEntry>, String> {
+        match segments(path)[..] {
+            [] => Ok(MediaView::ALL
+                .iter()
+                .map(|view| folder_entry(view.folder_name().to_string()))
+                .collect()),
+            [view] => Ok(Self::groups(view_of(view)?)?
+                .into_iter()
+                .map(folder_entry)
+                .collect()),
+            [view, group] => self.group_files(view_of(view)?, group),
+            _ => Err("Not a directory".to_string()),
+        }
+    }
+
+    fn stat(&mut self, path: &str) -> Result<VfsEntry, String> {
+        match segments(path)[..] {
+            [] => Ok(folder_entry(String::new())),
+            [view] => view_of(view).map(|view| folder_entry(view.folder_name().to_string())),
+            [view, group] => {
+                if !Self::groups(view_of(view)?)?.contains(group) {
+                    return Err(format!("Not found: {}", group));
+                }
+                Ok(folder_entry(group.to_string()))
+            }
+            [_, _, name] => {
+                let metadata =
+                    fs::metadata(self.real_path(path)?).map_err(|error| error.to_string())?;
+                let modified_time = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|duration| duration.as_millis() as u64)
+                    .unwrap_or(0);
+
+                Ok(VfsEntry {
+                    name: name.to_string(),
+                    is_dir: false,
+                    is_symlink: false,
+                    size: metadata.len(),
+                    modified_time,
+                    accessed_time: 0,
+                    created_time: 0,
+                    mode: None,
+                })
+            }
+            _ => Err(format!("Not found: {}", path)),
+        }
+    }
+
+    fn read_file(&mut self, path: &str, destination: &Path) -> Result<u64, String> {
+        fs::copy(self.real_path(path)?, destination).map_err(|error| error.to_string())
+    }
+
+    fn write_file(&mut self, _source: &Path, _path: &str) -> Result<u64, String> {
+        Err(unsupported("Writing to a media view"))
+    }
+
+    fn create_dir(&mut self, _path: &str) -> Result<(), String> {
+        Err(unsupported("Creating folders in a media view"))
+    }
+
+    fn remove(&mut self, _path: &str, _is_dir: bool) -> Result<(), String> {
+        Err(unsupported("Deleting from a media view"))
+    }
+
+    fn rename(&mut self, _from: &str, _to: &str) -> Result<(), String> {
+        Err(unsupported("Renaming in a media view"))
+    }
+
+    fn local_path(&self, path: &str) -> Option<PathBuf> {
+        self.files.get(path).cloned()
+    }
+}
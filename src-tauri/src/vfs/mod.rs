@@ -0,0 +1,464 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! A common interface over the places files can live: local disks, zip
+//! archives and the servers of `remote_fs`. Items are addressed with URIs:
+//!
+//! - local paths as they are: `/home/user/file.txt`, `C:/Users/file.txt`
+//! - archive contents: `zip:///home/user/archive.zip!/folder/file.txt`
+//! - remote items: `sftp://user@host/home/user/file.txt`,
+//!   `s3://<access key>@<endpoint>/bucket/key`
+//! - portable devices: `mtp://<device key>/<storage>/DCIM/photo.jpg`
+//! - media library views: `media://Albums/<album>/track.flac`
+//!
+//! `copy_items`, `move_items` and `delete_items` hand items outside local
+//! disks to `transfer` and `delete`, so copy and move work between any two
+//! backends. Transfers within one backend use its rename or server-side copy
+//! when it has them; otherwise files are streamed through a local staging
+//! file.
+
+mod archive;
+mod local;
+mod media;
+mod mtp;
+mod remote;
+
+use crate::dir_reader::{get_mime_type, DirContents, DirEntry};
+use crate::file_operations::{
+    unique_destination_path_with, ConflictResolution, FileOperationResult,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ARCHIVE_SCHEME: &str = "zip://";
+const ARCHIVE_SEPARATOR: char = '!';
+
+/// A file or directory in any backend
+#[derive(Debug, Clone)]
+pub struct VfsEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    pub size: u64,
+    /// Milliseconds since the Unix epoch, 0 when unknown
+    pub modified_time: u64,
+    pub accessed_time: u64,
+    pub created_time: u64,
+    pub mode: Option<u32>,
+}
+
+/// What a backend can do beyond listing and reading
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub can_write: bool,
+    pub can_delete: bool,
+    pub can_rename: bool,
+    /// Copies within the backend without a round trip through this machine
+    pub server_side_copy: bool,
+    pub has_trash: bool,
+}
+
+/// Operations shared by all backends. Paths are backend-specific absolute
+/// paths with forward slashes.
+pub(crate) trait VirtualFileSystem: Send {
+    /// Identifies the storage the paths belong to, so transfers can tell
+    /// whether source and destination share a backend
+    fn id(&self) -> String;
+    /// URI of a path in this backend
+    fn uri(&self, path: &str) -> String;
+    fn capabilities(&self) -> Capabilities;
+    fn read_dir(&mut self, path: &str) -> Result<Vec<VfsEntry>, String>;
+    fn stat(&mut self, path: &str) -> Result<VfsEntry, String>;
+    /// Copies a file's contents into a local file
+    fn read_file(&mut self, path: &str, destination: &Path) -> Result<u64, String>;
+    /// Creates or replaces a file with the contents of a local file
+    fn write_file(&mut self, source: &Path, path: &str) -> Result<u64, String>;
+    fn create_dir(&mut self, path: &str) -> Result<(), String>;
+    /// Removes a file or an empty directory
+    fn remove(&mut self, path: &str, is_dir: bool) -> Result<(), String>;
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), String>;
+
+    fn copy(&mut self, _from: &str, _to: &str) -> Result<(), String> {
+        Err("Copying within this location is not supported".to_string())
+    }
+
+    /// Local file backing a path, which spares staging a copy
+    fn local_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+pub(crate) fn unsupported(operation: &str) -> String {
+    format!("{} is not supported in this location", operation)
+}
+
+pub(crate) fn join_path(parent: &str, name: &str) -> String {
+    format!("{}/{}", parent.trim_end_matches('/'), name)
+}
+
+/// Whether a URI is a plain path on a local disk
+pub(crate) fn is_local(uri: &str) -> bool {
+    !uri.starts_with(ARCHIVE_SCHEME)
+        && !uri.starts_with(crate::mtp::SCHEME)
+        && !uri.starts_with(media::SCHEME)
+        && !crate::remote_fs::is_remote_path(uri)
+}
+
+fn file_name(path: &str) -> Option<&str> {
+    path.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+/// Opens the backend a URI points into and returns it with the path inside it
+pub(crate) fn open(uri: &str) -> Result<(Box<dyn VirtualFileSystem>, String), String> {
+    if let Some(rest) = uri.strip_prefix(ARCHIVE_SCHEME) {
+        let (archive_path, inner_path) = match rest.split_once(ARCHIVE_SEPARATOR) {
+            Some((archive_path, inner_path)) => (archive_path, inner_path),
+            None => (rest, "/"),
+        };
+        let inner_path = format!("/{}", inner_path.trim_matches('/'));
+
+        return Ok((Box::new(archive::ArchiveFs::new(archive_path)), inner_path));
+    }
+
+    if uri.starts_with(crate::mtp::SCHEME) {
+        let (backend, path) = mtp::MtpFs::open(uri)?;
+        return Ok((Box::new(backend), path));
+    }
+
+    if uri.starts_with(media::SCHEME) {
+        let (backend, path) = media::MediaFs::open(uri)?;
+        return Ok((Box::new(backend), path));
+    }
+
+    if !is_local(uri) {
+        let (backend, path) = remote::RemoteFs::open(uri)?;
+        return Ok((Box::new(backend), path));
+    }
+
+    Ok((Box::new(local::LocalFs), crate::utils::normalize_path(uri)))
+}
+
+fn to_dir_entry(uri: String, entry: VfsEntry) -> DirEntry {
+    let extension = if entry.is_dir {
+        None
+    } else {
+        Path::new(&entry.name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+    };
+    let is_file = !entry.is_dir;
+
+    DirEntry {
+        path: uri,
+        is_hidden: entry.name.starts_with('.'),
+        mime: if is_file {
+            get_mime_type(&extension)
+        } else {
+            None
+        },
+        ext: extension,
+        name: entry.name,
+        size: if is_file { entry.size } else { 0 },
+        item_count: None,
+        modified_time: entry.modified_time,
+        accessed_time: entry.accessed_time,
+        created_time: entry.created_time,
+        is_file,
+        is_dir: entry.is_dir,
+        is_symlink: entry.is_symlink,
+        is_shortcut: false,
+        is_readonly: entry.mode.is_some_and(|mode| mode & 0o200 == 0),
+        allocated_size: None,
+        hard_link_count: None,
+        file_id: None,
+        mode: entry.mode,
+        owner: None,
+        group: None,
+        tags: None,
+        git_ignored: false,
+        git_status: None,
+        indexed_size: None,
+    }
+}
+
+fn remove_recursive(
+    backend: &mut dyn VirtualFileSystem,
+    path: &str,
+    entry: &VfsEntry,
+) -> Result<(), String> {
+    if entry.is_dir && !entry.is_symlink {
+        for child in backend.read_dir(path)? {
+            remove_recursive(backend, &join_path(path, &child.name), &child)?;
+        }
+    }
+
+    backend.remove(path, entry.is_dir && !entry.is_symlink)
+}
+
+fn copy_file(
+    source: &mut dyn VirtualFileSystem,
+    source_path: &str,
+    destination: &mut dyn VirtualFileSystem,
+    destination_path: &str,
+) -> Result<(), String> {
+    if let Some(local_source) = source.local_path(source_path) {
+        return destination
+            .write_file(&local_source, destination_path)
+            .map(|_| ());
+    }
+
+    if let Some(local_destination) = destination.local_path(destination_path) {
+        return source
+            .read_file(source_path, &local_destination)
+            .map(|_| ());
+    }
+
+    // Neither side is on disk, the file passes through a staging copy
+    let staging_path = std::env::temp_dir().join(format!("sigma-vfs-{}", uuid::Uuid::new_v4()));
+    let outcome = source
+        .read_file(source_path, &staging_path)
+        .and_then(|_| destination.write_file(&staging_path, destination_path));
+    let _ = fs::remove_file(&staging_path);
+
+    outcome.map(|_| ())
+}
+
+fn copy_recursive(
+    source: &mut dyn VirtualFileSystem,
+    source_path: &str,
+    entry: &VfsEntry,
+    destination: &mut dyn VirtualFileSystem,
+    destination_path: &str,
+    result: &mut FileOperationResult,
+) -> bool {
+    let outcome = if entry.is_dir {
+        destination
+            .create_dir(destination_path)
+            .and_then(|_| source.read_dir(source_path))
+            .map(|children| {
+                children.iter().fold(true, |all_copied, child| {
+                    copy_recursive(
+                        source,
+                        &join_path(source_path, &child.name),
+                        child,
+                        destination,
+                        &join_path(destination_path, &child.name),
+                        result,
+                    ) && all_copied
+                })
+            })
+    } else {
+        copy_file(source, source_path, destination, destination_path).map(|_| true)
+    };
+
+    match outcome {
+        Ok(all_copied) => all_copied,
+        Err(error) => {
+            result.failed_count = Some(result.failed_count.unwrap_or(0) + 1);
+            result.error = Some(format!("{}: {}", source.uri(source_path), error));
+            false
+        }
+    }
+}
+
+fn transfer_item(
+    source_uri: &str,
+    destination: &mut dyn VirtualFileSystem,
+    destination_dir: &str,
+    is_move: bool,
+    resolution: &ConflictResolution,
+    result: &mut FileOperationResult,
+) -> Result<(), String> {
+    let (mut source, source_path) = open(source_uri)?;
+    let name = file_name(&source_path)
+        .ok_or_else(|| format!("Invalid source path: {}", source_uri))?
+        .to_string();
+    let mut destination_path = join_path(destination_dir, &name);
+    let entry = source.stat(&source_path)?;
+    let same_backend = source.id() == destination.id();
+
+    // Only a copy into its own folder can go ahead, under a new name
+    if same_backend
+        && source_path == destination_path
+        && (is_move || *resolution != ConflictResolution::AutoRename)
+    {
+        result.skipped_count = Some(result.skipped_count.unwrap_or(0) + 1);
+        return Ok(());
+    }
+
+    if let Ok(existing) = destination.stat(&destination_path) {
+        match resolution {
+            ConflictResolution::Skip => {
+                result.skipped_count = Some(result.skipped_count.unwrap_or(0) + 1);
+                return Ok(());
+            }
+            ConflictResolution::Replace => {
+                remove_recursive(destination, &destination_path, &existing)?
+            }
+            ConflictResolution::AutoRename => {
+                let free_name = unique_destination_path_with(Path::new(""), &name, |candidate| {
+                    destination
+                        .stat(&join_path(destination_dir, &candidate.to_string_lossy()))
+                        .is_ok()
+                });
+                destination_path = join_path(destination_dir, &free_name.to_string_lossy());
+            }
+        }
+    }
+
+    let capabilities = source.capabilities();
+
+    if same_backend && is_move && capabilities.can_rename {
+        source.rename(&source_path, &destination_path)?;
+    } else if same_backend && !is_move && capabilities.server_side_copy {
+        source.copy(&source_path, &destination_path)?;
+    } else {
+        if !copy_recursive(
+            source.as_mut(),
+            &source_path,
+            &entry,
+            destination,
+            &destination_path,
+            result,
+        ) {
+            // Keeps the source of a partially copied move intact
+            return Ok(());
+        }
+
+        if is_move {
+            remove_recursive(source.as_mut(), &source_path, &entry)?;
+        }
+    }
+
+    result.copied_count = Some(result.copied_count.unwrap_or(0) + 1);
+    Ok(())
+}
+
+/// Copies or moves items between any two locations, resolving name
+/// conflicts the way `copy_items` does
+pub(crate) fn transfer(
+    source_uris: &[String],
+    destination_uri: &str,
+    is_move: bool,
+    resolution: &ConflictResolution,
+) -> Result<FileOperationResult, String> {
+    let (mut destination, destination_dir) = open(destination_uri)?;
+
+    if !destination.capabilities().can_write {
+        return Err(unsupported("Writing"));
+    }
+
+    let mut result = FileOperationResult::counts(0, 0, 0);
+
+    for source_uri in source_uris {
+        if let Err(error) = transfer_item(
+            source_uri,
+            destination.as_mut(),
+            &destination_dir,
+            is_move,
+            resolution,
+            &mut result,
+        ) {
+            result.failed_count = Some(result.failed_count.unwrap_or(0) + 1);
+            result.error = Some(format!("{}: {}", source_uri, error));
+        }
+    }
+
+    result.success = result.failed_count.unwrap_or(0) == 0;
+    Ok(result)
+}
+
+/// Deletes a file or folder with everything inside it, skipping the trash
+pub(crate) fn delete(uri: &str) -> Result<(), String> {
+    let (mut backend, path) = open(uri)?;
+
+    if !backend.capabilities().can_delete {
+        return Err(unsupported("Deleting"));
+    }
+
+    let entry = backend.stat(&path)?;
+    remove_recursive(backend.as_mut(), &path, &entry)
+}
+
+#[tauri::command]
+pub async fn vfs_read_dir(uri: String) -> Result<DirContents, String> {
+    tokio::task::spawn_blocking(move || {
+        let (mut backend, path) = open(&uri)?;
+        let mut entries: Vec<DirEntry> = backend
+            .read_dir(&path)?
+            .into_iter()
+            .map(|entry| to_dir_entry(backend.uri(&join_path(&path, &entry.name)), entry))
+            .collect();
+
+        entries.sort_by(|first, second| match (first.is_dir, second.is_dir) {
+            (true, false) => std::cmp::Ordering::Less,
+            (false, true) => std::cmp::Ordering::Greater,
+            _ => crate::text::compare_names(&first.name, &second.name),
+        });
+
+        let dir_count = entries.iter().filter(|entry| entry.is_dir).count();
+        let file_count = entries.len() - dir_count;
+
+        Ok(DirContents {
+            path: backend.uri(&path),
+            entries,
+            total_count: dir_count + file_count,
+            dir_count,
+            file_count,
+            git_repository: None,
+        })
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command]
+pub async fn vfs_capabilities(uri: String) -> Result<Capabilities, String> {
+    tokio::task::spawn_blocking(move || Ok(open(&uri)?.0.capabilities()))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+/// Copies or moves items between any two locations. Existing items at the
+/// destination are skipped unless `overwrite` is set.
+#[tauri::command]
+pub async fn vfs_transfer(
+    source_uris: Vec<String>,
+    destination_uri: String,
+    is_move: bool,
+    overwrite: Option<bool>,
+) -> Result<FileOperationResult, String> {
+    let overwrite = overwrite.unwrap_or(false);
+    let conflict_resolution = Some(if overwrite { "replace" } else { "skip" }.to_string());
+
+    tokio::task::spawn_blocking(move || {
+        if is_move {
+            crate::file_operations::move_items(
+                source_uris,
+                destination_uri,
+                conflict_resolution,
+                None,
+                None,
+                None,
+                None,
+            )
+        } else {
+            crate::file_operations::copy_items(
+                source_uris,
+                destination_uri,
+                conflict_resolution,
+                None,
+                None,
+                None,
+                None,
+            )
+        }
+    })
+    .await
+    .map_err(|error| error.to_string())
+}
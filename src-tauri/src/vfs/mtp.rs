@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! MTP devices as folders. The device root lists its storages; below them
+//! paths are resolved to object IDs one level at a time, as MTP addresses
+//! objects by ID rather than by path.
+
+use super::{file_name, unsupported, Capabilities, VfsEntry, VirtualFileSystem};
+use crate::mtp::{self, MtpDevice, MtpObject, SharedDevice, SCHEME};
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+enum Location {
+    Root,
+    Storage {
+        id: String,
+    },
+    Object {
+        storage_id: String,
+        object: MtpObject,
+    },
+}
+
+pub struct MtpFs {
+    key: String,
+    device: SharedDevice,
+    /// Resolved paths, kept for the lifetime of one listing or transfer
+    locations: HashMap<String, Location>,
+}
+
+fn parent_path(path: &str) -> &str {
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) | None => "/",
+        Some((parent, _)) => parent,
+    }
+}
+
+fn object_entry(object: &MtpObject) -> VfsEntry {
+    VfsEntry {
+        name: object.name.clone(),
+        is_dir: object.is_dir,
+        is_symlink: false,
+        size: object.size,
+        modified_time: object.modified_time,
+        accessed_time: 0,
+        created_time: 0,
+        mode: None,
+    }
+}
+
+fn folder_entry(name: String) -> VfsEntry {
+    VfsEntry {
+        name,
+        is_dir: true,
+        is_symlink: false,
+        size: 0,
+        modified_time: 0,
+        accessed_time: 0,
+        created_time: 0,
+        mode: None,
+    }
+}
+
+impl MtpFs {
+    pub fn open(uri: &str) -> Result<(Self, String), String> {
+        let (key, path) =
+            mtp::parse_uri(uri).ok_or_else(|| format!("Invalid MTP path: {}", uri))?;
+        let device = mtp::device_for(&key)?;
+
+        Ok((
+            MtpFs {
+                key,
+                device,
+                locations: HashMap::new(),
+            },
+            path,
+        ))
+    }
+
+    /// Runs a device call, dropping the session when it fails so an
+    /// unplugged and replugged device gets a fresh one
+    fn with_device<T>(
+        &self,
+        operation: impl FnOnce(&mut dyn MtpDevice) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut device = self.device.lock().map_err(|error| error.to_string())?;
+        let outcome = operation(device.as_mut());
+
+        if outcome.is_err() {
+            mtp::forget_device(&self.key);
+        }
+
+        outcome
+    }
+
+    fn storages(&self) -> Result<Vec<mtp::MtpStorage>, String> {
+        let mut storages = self.with_device(|device| device.storages())?;
+        mtp::name_storages(&mut storages);
+        Ok(storages)
+    }
+
+    fn children(&self, location: &Location) -> Result<Vec<(String, Location)>, String> {
+        match location {
+            Location::Root => Ok(self
+                .storages()?
+                .into_iter()
+                .map(|storage| (storage.name, Location::Storage { id: storage.id }))
+                .collect()),
+            Location::Storage { id } => self.objects(id, None),
+            Location::Object { storage_id, object } if object.is_dir => {
+                self.objects(storage_id, Some(&object.id))
+            }
+            Location::Object { .. } => Err("Not a directory".to_string()),
+        }
+    }
+
+    fn objects(
+        &self,
+        storage_id: &str,
+        parent_id: Option<&str>,
+    ) -> Result<Vec<(String, Location)>, String> {
+        Ok(self
+            .with_device(|device| device.list(storage_id, parent_id))?
+            .into_iter()
+            .map(|object| {
+                (
+                    object.name.clone(),
+                    Location::Object {
+                        storage_id: storage_id.to_string(),
+                        object,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    fn resolve(&mut self, path: &str) -> Result<Location, String> {
+        let path = format!("/{}", path.trim_matches('/'));
+
+        if path == "/" {
+            return Ok(Location::Root);
+        }
+
+        if let Some(location) = self.locations.get(&path) {
+            return Ok(location.clone());
+        }
+
+        let parent = self.resolve(parent_path(&path))?;
+        let name = file_name(&path).unwrap_or_default();
+
+        for (child_name, child) in self.children(&parent)? {
+            let child_path = super::join_path(parent_path(&path), &child_name);
+            self.locations.insert(child_path, child);
+        }
+
+        self.locations
+            .get(&path)
+            .cloned()
+            .ok_or_else(|| format!("Not found: {}", name))
+    }
+
+    /// Storage and parent object of a path about to be created
+    fn parent_of(&mut self, path: &str) -> Result<(String, Option<String>), String> {
+        match self.resolve(parent_path(path))? {
+            Location::Root => Err(unsupported("Writing to the device root")),
+            Location::Storage { id } => Ok((id, None)),
+            Location::Object { storage_id, object } if object.is_dir => {
+                Ok((storage_id, Some(object.id)))
+            }
+            Location::Object { .. } => Err("Not a directory".to_string()),
+        }
+    }
+
+    fn forget_path(&mut self, path: &str) {
+        let path = format!("/{}", path.trim_matches('/'));
+        let prefix = format!("{}/", path);
+        self.locations
+            .retain(|cached, _| *cached != path && !cached.starts_with(&prefix));
+    }
+}
+
+impl VirtualFileSystem for MtpFs {
+    fn id(&self) -> String {
+        format!("{}{}", SCHEME, self.key)
+    }
+
+    fn uri(&self, path: &str) -> String {
+        format!("{}{}{}", SCHEME, self.key, path)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            can_write: true,
+            can_delete: true,
+            ..Capabilities::default()
+        }
+    }
+
+    fn read_dir(&mut self, path: &str) -> Result<Vec<VfsEntry>, String> {
+        let location = self.resolve(path)?;
+        let children = self.children(&location)?;
+        let mut entries = Vec::with_capacity(children.len());
+
+        for (name, child) in children {
+            entries.push(match &child {
+                Location::Object { object, .. } => object_entry(object),
+                _ => folder_entry(name.clone()),
+            });
+            self.locations.insert(super::join_path(path, &name), child);
+        }
+
+        Ok(entries)
+    }
+
+    fn stat(&mut self, path: &str) -> Result<VfsEntry, String> {
+        Ok(match self.resolve(path)? {
+            Location::Root => folder_entry(self.key.clone()),
+            Location::Storage { .. } => {
+                folder_entry(file_name(path).unwrap_or_default().to_string())
+            }
+            Location::Object { object, .. } => object_entry(&object),
+        })
+    }
+
+    fn read_file(&mut self, path: &str, destination: &Path) -> Result<u64, String> {
+        match self.resolve(path)? {
+            Location::Object { object, .. } if !object.is_dir => {
+                self.with_device(|device| device.download(&object, destination))
+            }
+            _ => Err("Not a file".to_string()),
+        }
+    }
+
+    fn write_file(&mut self, source: &Path, path: &str) -> Result<u64, String> {
+        // Objects can't be overwritten in place, only replaced
+        if let Ok(Location::Object { object, .. }) = self.resolve(path) {
+            self.with_device(|device| device.delete(&object.id))?;
+        }
+
+        let (storage_id, parent_id) = self.parent_of(path)?;
+        let name = file_name(path).unwrap_or_default().to_string();
+        self.forget_path(path);
+
+        self.with_device(|device| device.upload(&storage_id, parent_id.as_deref(), &name, source))
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), String> {
+        if self.stat(path).is_ok_and(|entry| entry.is_dir) {
+            return Ok(());
+        }
+
+        let (storage_id, parent_id) = self.parent_of(path)?;
+        let name = file_name(path).unwrap_or_default().to_string();
+        self.forget_path(path);
+
+        self.with_device(|device| device.create_folder(&storage_id, parent_id.as_deref(), &name))
+            .map(|_| ())
+    }
+
+    fn remove(&mut self, path: &str, _is_dir: bool) -> Result<(), String> {
+        match self.resolve(path)? {
+            Location::Object { object, .. } => {
+                self.with_device(|device| device.delete(&object.id))?;
+                self.forget_path(path);
+                Ok(())
+            }
+            _ => Err(unsupported("Deleting a storage")),
+        }
+    }
+
+    fn rename(&mut self, _from: &str, _to: &str) -> Result<(), String> {
+        Err(unsupported("Renaming on a portable device"))
+    }
+}
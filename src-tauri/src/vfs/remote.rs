@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use super::{Capabilities, VfsEntry, VirtualFileSystem};
+use crate::remote_fs::{backend_for, RemoteBackend, RemoteEntry, RemoteUrl, SharedBackend};
+use std::path::Path;
+
+/// Exposes a `remote_fs` connection, shared with the `remote_*` commands
+pub struct RemoteFs {
+    url: RemoteUrl,
+    backend: SharedBackend,
+}
+
+impl From<RemoteEntry> for VfsEntry {
+    fn from(entry: RemoteEntry) -> Self {
+        VfsEntry {
+            name: entry.name,
+            is_dir: entry.is_dir,
+            is_symlink: entry.is_symlink,
+            size: entry.size,
+            modified_time: entry.modified_time,
+            accessed_time: entry.accessed_time,
+            created_time: 0,
+            mode: entry.mode,
+        }
+    }
+}
+
+impl RemoteFs {
+    pub fn open(uri: &str) -> Result<(Self, String), String> {
+        let url = RemoteUrl::parse(uri)?;
+        let backend = backend_for(&url)?;
+        let path = url.path.clone();
+
+        Ok((RemoteFs { url, backend }, path))
+    }
+
+    // Locked per call, so a transfer between two folders of one server can
+    // hold both ends without deadlocking
+    fn with_backend<T>(
+        &self,
+        operation: impl FnOnce(&mut dyn RemoteBackend) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut backend = self.backend.lock().map_err(|error| error.to_string())?;
+        operation(backend.as_mut())
+    }
+}
+
+impl VirtualFileSystem for RemoteFs {
+    fn id(&self) -> String {
+        self.url.origin()
+    }
+
+    fn uri(&self, path: &str) -> String {
+        self.url.with_path(path)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            can_write: true,
+            can_delete: true,
+            can_rename: true,
+            server_side_copy: self
+                .backend
+                .lock()
+                .is_ok_and(|backend| backend.supports_copy()),
+            has_trash: false,
+        }
+    }
+
+    fn read_dir(&mut self, path: &str) -> Result<Vec<VfsEntry>, String> {
+        self.with_backend(|backend| {
+            Ok(backend
+                .read_dir(path)?
+                .into_iter()
+                .map(VfsEntry::from)
+                .collect())
+        })
+    }
+
+    fn stat(&mut self, path: &str) -> Result<VfsEntry, String> {
+        self.with_backend(|backend| backend.stat(path).map(VfsEntry::from))
+    }
+
+    fn read_file(&mut self, path: &str, destination: &Path) -> Result<u64, String> {
+        self.with_backend(|backend| backend.download_file(path, destination, 0))
+    }
+
+    fn write_file(&mut self, source: &Path, path: &str) -> Result<u64, String> {
+        self.with_backend(|backend| backend.upload_file(source, path, 0))
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), String> {
+        self.with_backend(|backend| backend.create_dir(path))
+    }
+
+    fn remove(&mut self, path: &str, is_dir: bool) -> Result<(), String> {
+        self.with_backend(|backend| {
+            if is_dir {
+                backend.remove_dir(path)
+            } else {
+                backend.remove_file(path)
+            }
+        })
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        self.with_backend(|backend| backend.rename(from, to))
+    }
+
+    fn copy(&mut self, from: &str, to: &str) -> Result<(), String> {
+        self.with_backend(|backend| backend.copy(from, to))
+    }
+}
@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! What each file view (a tab or pane of a window) is showing: its current
+//! directory, its filter and its selection. Views report changes as they
+//! happen, and commands given a view id resolve relative targets against
+//! this state instead of paths the frontend may have captured before a
+//! navigation finished.
+
+use crate::utils::{normalize_path, to_os_path};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewContext {
+    pub view_id: String,
+    pub current_dir: String,
+    pub filter: Option<String>,
+    pub selected_paths: Vec<String>,
+    /// Changes whenever the selection does, so commands can tell whether
+    /// the selection they were given is still the current one
+    pub selection_id: u64,
+}
+
+/// Contexts by window label and view id
+static VIEW_CONTEXTS: Lazy<Mutex<HashMap<(String, String), ViewContext>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The view's current directory, or `path` resolved against it when
+/// relative. Without a known view, `path` is returned as is.
+pub fn resolve_path(
+    window_label: &str,
+    view_id: Option<&str>,
+    path: &str,
+) -> Result<String, String> {
+    let Some(view_id) = view_id else {
+        return Ok(path.to_string());
+    };
+
+    if !path.is_empty() && to_os_path(path).is_absolute() {
+        return Ok(path.to_string());
+    }
+
+    let contexts = VIEW_CONTEXTS.lock().map_err(|error| error.to_string())?;
+    let context = contexts
+        .get(&(window_label.to_string(), view_id.to_string()))
+        .ok_or_else(|| format!("Unknown view: {}", view_id))?;
+
+    if path.is_empty() {
+        Ok(context.current_dir.clone())
+    } else {
+        Ok(normalize_path(
+            &to_os_path(&context.current_dir)
+                .join(to_os_path(path))
+                .to_string_lossy(),
+        ))
+    }
+}
+
+/// The selected paths of a view, provided the selection hasn't changed
+/// since `selection_id` was handed out
+pub fn selected_paths(
+    window_label: &str,
+    view_id: &str,
+    selection_id: u64,
+) -> Result<Vec<String>, String> {
+    let contexts = VIEW_CONTEXTS.lock().map_err(|error| error.to_string())?;
+    let context = contexts
+        .get(&(window_label.to_string(), view_id.to_string()))
+        .ok_or_else(|| format!("Unknown view: {}", view_id))?;
+
+    if context.selection_id != selection_id {
+        return Err("The selection changed before the command ran".to_string());
+    }
+
+    Ok(context.selected_paths.clone())
+}
+
+/// Drops the contexts of a closed window
+pub fn forget_window(window_label: &str) {
+    if let Ok(mut contexts) = VIEW_CONTEXTS.lock() {
+        contexts.retain(|(label, _), _| label != window_label);
+    }
+}
+
+/// Records what a view shows. A `None` selection keeps the current one.
+#[tauri::command]
+pub fn set_view_context(
+    window: tauri::WebviewWindow,
+    view_id: String,
+    current_dir: String,
+    filter: Option<String>,
+    selected_paths: Option<Vec<String>>,
+) -> Result<ViewContext, String> {
+    let mut contexts = VIEW_CONTEXTS.lock().map_err(|error| error.to_string())?;
+    let context = contexts
+        .entry((window.label().to_string(), view_id.clone()))
+        .or_insert_with(|| ViewContext {
+            view_id,
+            current_dir: String::new(),
+            filter: None,
+            selected_paths: Vec::new(),
+            selection_id: 0,
+        });
+
+    context.current_dir = normalize_path(&current_dir);
+    context.filter = filter.filter(|filter| !filter.is_empty());
+
+    if let Some(selected_paths) = selected_paths {
+        let selected_paths: Vec<String> = selected_paths
+            .iter()
+            .map(|path| normalize_path(path))
+            .collect();
+
+        if selected_paths != context.selected_paths {
+            context.selected_paths = selected_paths;
+            context.selection_id += 1;
+        }
+    }
+
+    Ok(context.clone())
+}
+
+#[tauri::command]
+pub fn get_view_context(
+    window: tauri::WebviewWindow,
+    view_id: String,
+) -> Result<Option<ViewContext>, String> {
+    let contexts = VIEW_CONTEXTS.lock().map_err(|error| error.to_string())?;
+    Ok(contexts
+        .get(&(window.label().to_string(), view_id))
+        .cloned())
+}
+
+/// Forgets a view, e.g. when its tab is closed
+#[tauri::command]
+pub fn remove_view_context(window: tauri::WebviewWindow, view_id: String) -> Result<(), String> {
+    let mut contexts = VIEW_CONTEXTS.lock().map_err(|error| error.to_string())?;
+    contexts.remove(&(window.label().to_string(), view_id));
+    Ok(())
+}
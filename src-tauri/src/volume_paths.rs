@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Paths kept relative to the volume they live on, so they can be found
+//! again when the volume comes back at another drive letter or mount point.
+//! Local volumes are told apart by file system UUID (serial number on
+//! Windows), network shares by their source, such as `//server/share`.
+
+use crate::utils::normalize_path;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VolumeId {
+    Uuid { uuid: String },
+    Share { source: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumePath {
+    pub volume: VolumeId,
+    /// Relative to the volume's mount point, empty for its root
+    pub relative_path: String,
+}
+
+struct MountedVolume {
+    id: VolumeId,
+    /// Normalized, with a trailing slash
+    mount_point: String,
+}
+
+fn with_trailing_slash(path: String) -> String {
+    if path.ends_with('/') {
+        path
+    } else {
+        format!("{}/", path)
+    }
+}
+
+// Share sources are compared case-insensitively, as server names are
+fn share_id(source: &str) -> VolumeId {
+    VolumeId::Share {
+        source: normalize_path(source).trim_end_matches('/').to_lowercase(),
+    }
+}
+
+/// The volumes mounted when it was listed. Listing takes a moment, so one
+/// listing serves many lookups.
+pub struct MountedVolumes(Vec<MountedVolume>);
+
+impl MountedVolumes {
+    pub fn list() -> Self {
+        Self(mounted_volumes())
+    }
+
+    /// The volume `path` lives on and the path relative to it. `None` when
+    /// the volume has no UUID, like some FAT volumes on Linux.
+    pub fn locate(&self, path: &Path) -> Option<VolumePath> {
+        let path = with_trailing_slash(normalize_path(&path.to_string_lossy()));
+
+        self.0
+            .iter()
+            .filter(|volume| path.starts_with(&volume.mount_point))
+            .max_by_key(|volume| volume.mount_point.len())
+            .map(|volume| VolumePath {
+                volume: volume.id.clone(),
+                relative_path: path[volume.mount_point.len()..]
+                    .trim_end_matches('/')
+                    .to_string(),
+            })
+    }
+
+    /// Where `volume_path` is now, if its volume is mounted and the path
+    /// exists there
+    pub fn resolve(&self, volume_path: &VolumePath) -> Option<PathBuf> {
+        self.0
+            .iter()
+            .filter(|volume| volume.id == volume_path.volume)
+            .map(|volume| Path::new(&volume.mount_point).join(&volume_path.relative_path))
+            .find(|candidate| candidate.exists())
+    }
+}
+
+fn mounted_volumes() -> Vec<MountedVolume> {
+    let local_volumes =
+        crate::disk_layout::mounted_volume_ids()
+            .into_iter()
+            .map(|(uuid, mount_point)| MountedVolume {
+                id: VolumeId::Uuid {
+                    uuid: uuid.to_lowercase(),
+                },
+                mount_point: with_trailing_slash(normalize_path(&mount_point)),
+            });
+    let shares = crate::network_shares::list_mounted()
+        .into_iter()
+        .map(|share| MountedVolume {
+            id: share_id(&share.source),
+            mount_point: with_trailing_slash(normalize_path(&share.mount_point)),
+        });
+
+    local_volumes.chain(shares).collect()
+}
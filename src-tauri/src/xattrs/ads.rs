@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use std::ffi::OsString;
+use std::io;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use windows_sys::Win32::Foundation::{GetLastError, ERROR_HANDLE_EOF, INVALID_HANDLE_VALUE};
+use windows_sys::Win32::Storage::FileSystem::{
+    FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard, WIN32_FIND_STREAM_DATA,
+};
+
+// Unnamed main data stream, present on every file
+const DEFAULT_STREAM_NAME: &str = "::$DATA";
+const DATA_STREAM_SUFFIX: &str = ":$DATA";
+
+fn stream_path(path: &Path, name: &str) -> PathBuf {
+    let mut stream = path.as_os_str().to_os_string();
+    stream.push(":");
+    stream.push(name);
+    PathBuf::from(stream)
+}
+
+// Stream names are reported as ":name:$DATA"
+fn parse_stream_name(raw_name: &[u16]) -> Option<String> {
+    let length = raw_name
+        .iter()
+        .position(|character| *character == 0)
+        .unwrap_or(raw_name.len());
+    let full_name = OsString::from_wide(&raw_name[..length])
+        .to_string_lossy()
+        .to_string();
+
+    if full_name == DEFAULT_STREAM_NAME {
+        return None;
+    }
+
+    let name = full_name.strip_prefix(':')?.strip_suffix(DATA_STREAM_SUFFIX)?;
+    Some(name.to_string())
+}
+
+pub fn list(path: &Path) -> io::Result<Vec<(String, u64)>> {
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut stream_data: WIN32_FIND_STREAM_DATA = unsafe { std::mem::zeroed() };
+
+    let handle = unsafe {
+        FindFirstStreamW(
+            wide_path.as_ptr(),
+            FindStreamInfoStandard,
+            &mut stream_data as *mut _ as *mut _,
+            0,
+        )
+    };
+
+    if handle == INVALID_HANDLE_VALUE {
+        // Directories without named streams report EOF straight away
+        if unsafe { GetLastError() } == ERROR_HANDLE_EOF {
+            return Ok(Vec::new());
+        }
+
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut streams = Vec::new();
+
+    loop {
+        if let Some(name) = parse_stream_name(&stream_data.cStreamName) {
+            streams.push((name, stream_data.StreamSize.max(0) as u64));
+        }
+
+        if unsafe { FindNextStreamW(handle, &mut stream_data as *mut _ as *mut _) } == 0 {
+            break;
+        }
+    }
+
+    unsafe {
+        FindClose(handle);
+    }
+
+    Ok(streams)
+}
+
+pub fn open(path: &Path, name: &str) -> io::Result<std::fs::File> {
+    std::fs::File::open(stream_path(path, name))
+}
+
+pub fn get(path: &Path, name: &str) -> io::Result<Vec<u8>> {
+    std::fs::read(stream_path(path, name))
+}
+
+pub fn set(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    std::fs::write(stream_path(path, name), value)
+}
+
+pub fn remove(path: &Path, name: &str) -> io::Result<()> {
+    std::fs::remove_file(stream_path(path, name))
+}
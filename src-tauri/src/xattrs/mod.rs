@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Extended attributes on Linux/macOS. On Windows the same API is backed by
+//! NTFS alternate data streams.
+
+#[cfg(windows)]
+mod ads;
+mod origin;
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+mod unix;
+
+#[cfg(windows)]
+use ads as platform;
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+use unix as platform;
+
+#[cfg(not(any(
+    windows,
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos"
+)))]
+mod platform {
+    use std::io;
+    use std::path::Path;
+
+    fn unsupported<T>() -> io::Result<T> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Extended attributes are not supported on this platform",
+        ))
+    }
+
+    pub fn list(_path: &Path) -> io::Result<Vec<(String, u64)>> {
+        unsupported()
+    }
+
+    pub fn get(_path: &Path, _name: &str) -> io::Result<Vec<u8>> {
+        unsupported()
+    }
+
+    pub fn set(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+        unsupported()
+    }
+
+    pub fn remove(_path: &Path, _name: &str) -> io::Result<()> {
+        unsupported()
+    }
+}
+
+pub use origin::DownloadOrigin;
+use crate::utils::to_os_path;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[cfg(windows)]
+const MAX_STREAM_PREVIEW: u64 = 64 * 1024;
+#[cfg(not(windows))]
+const STREAMS_UNSUPPORTED: &str = "Alternate data streams are only available on Windows";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtendedAttribute {
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamContent {
+    pub size: u64,
+    /// The content when it is text, like that of `Zone.Identifier`
+    pub text: Option<String>,
+    /// The content when it isn't text
+    pub data: Vec<u8>,
+    /// Only the first `MAX_STREAM_PREVIEW` bytes were read
+    pub is_truncated: bool,
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Attribute name cannot be empty".to_string());
+    }
+
+    if name.contains('\0') {
+        return Err("Attribute name cannot contain null bytes".to_string());
+    }
+
+    // Stream names can't contain path or stream separators
+    #[cfg(windows)]
+    if name.contains([':', '\\', '/']) {
+        return Err(format!("Invalid stream name: {}", name));
+    }
+
+    Ok(())
+}
+
+fn ensure_exists(path: &Path) -> Result<(), String> {
+    if path.symlink_metadata().is_err() {
+        return Err(format!("Path does not exist: {}", path.display()));
+    }
+
+    Ok(())
+}
+
+/// Lists extended attribute names and value sizes of a file or directory
+#[tauri::command]
+pub fn list_xattrs(path: String) -> Result<Vec<ExtendedAttribute>, String> {
+    let path = &to_os_path(&path);
+    ensure_exists(path)?;
+
+    let mut attributes: Vec<ExtendedAttribute> = platform::list(path)
+        .map_err(|error| format!("Failed to list attributes: {}", error))?
+        .into_iter()
+        .map(|(name, size)| ExtendedAttribute { name, size })
+        .collect();
+
+    attributes.sort_by(|first, second| first.name.cmp(&second.name));
+    Ok(attributes)
+}
+
+/// Reads the raw value of an extended attribute
+#[tauri::command]
+pub fn get_xattr(path: String, name: String) -> Result<Vec<u8>, String> {
+    let path = &to_os_path(&path);
+    ensure_exists(path)?;
+    validate_name(&name)?;
+
+    platform::get(path, &name)
+        .map_err(|error| format!("Failed to read attribute '{}': {}", name, error))
+}
+
+/// Creates or replaces an extended attribute
+#[tauri::command]
+pub fn set_xattr(path: String, name: String, value: Vec<u8>) -> Result<(), String> {
+    let path = &to_os_path(&path);
+    ensure_exists(path)?;
+    validate_name(&name)?;
+
+    platform::set(path, &name, &value)
+        .map_err(|error| format!("Failed to write attribute '{}': {}", name, error))
+}
+
+/// Removes an extended attribute
+#[tauri::command]
+pub fn remove_xattr(path: String, name: String) -> Result<(), String> {
+    let path = &to_os_path(&path);
+    ensure_exists(path)?;
+    validate_name(&name)?;
+
+    platform::remove(path, &name)
+        .map_err(|error| format!("Failed to remove attribute '{}': {}", name, error))
+}
+
+/// Alternate data streams of a file or folder on NTFS, like the
+/// `Zone.Identifier` stream Windows marks downloads with
+#[tauri::command]
+pub fn list_streams(path: String) -> Result<Vec<ExtendedAttribute>, String> {
+    #[cfg(windows)]
+    {
+        list_xattrs(path)
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = path;
+        Err(STREAMS_UNSUPPORTED.to_string())
+    }
+}
+
+/// Reads an alternate data stream for display. Streams can be large, only
+/// the start of them is read.
+#[tauri::command]
+pub fn read_stream(path: String, name: String) -> Result<StreamContent, String> {
+    #[cfg(windows)]
+    {
+        use std::io::Read;
+
+        let path = &to_os_path(&path);
+        ensure_exists(path)?;
+        validate_name(&name)?;
+
+        let read_error =
+            |error: std::io::Error| format!("Failed to read stream '{}': {}", name, error);
+        let file = ads::open(path, &name).map_err(read_error)?;
+        let size = file.metadata().map_err(read_error)?.len();
+
+        let mut data = Vec::new();
+        file.take(MAX_STREAM_PREVIEW)
+            .read_to_end(&mut data)
+            .map_err(read_error)?;
+
+        let is_truncated = size > data.len() as u64;
+        let text = std::str::from_utf8(&data)
+            .ok()
+            .filter(|text| !text.contains('\0'))
+            .map(String::from);
+        if text.is_some() {
+            data.clear();
+        }
+
+        Ok(StreamContent {
+            size,
+            text,
+            data,
+            is_truncated,
+        })
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = (path, name);
+        Err(STREAMS_UNSUPPORTED.to_string())
+    }
+}
+
+/// Deletes an alternate data stream, like `Zone.Identifier` to unblock a
+/// downloaded file
+#[tauri::command]
+pub fn delete_stream(path: String, name: String) -> Result<(), String> {
+    #[cfg(windows)]
+    {
+        remove_xattr(path, name)
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = (path, name);
+        Err(STREAMS_UNSUPPORTED.to_string())
+    }
+}
+
+/// Where a downloaded file came from, `None` when it has no download marks
+#[tauri::command]
+pub fn get_download_origin(path: String) -> Result<Option<DownloadOrigin>, String> {
+    let path = &to_os_path(&path);
+    ensure_exists(path)?;
+
+    Ok(origin::read_origin(path))
+}
+
+/// Removes the download marks of a file, so the system opens it without
+/// warning
+#[tauri::command]
+pub fn clear_download_origin(path: String) -> Result<(), String> {
+    let path = &to_os_path(&path);
+    ensure_exists(path)?;
+
+    origin::clear(path)
+}
+
+/// Copies every extended attribute of `source` onto `destination`,
+/// returning how many were copied
+pub(crate) fn copy_all(source: &Path, destination: &Path) -> std::io::Result<u32> {
+    let mut copied_count: u32 = 0;
+
+    for (name, _) in platform::list(source)? {
+        let value = platform::get(source, &name)?;
+        platform::set(destination, &name, &value)?;
+        copied_count += 1;
+    }
+
+    Ok(copied_count)
+}
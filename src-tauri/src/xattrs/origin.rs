@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Where downloaded files came from, as browsers record it: the
+//! `Zone.Identifier` stream on Windows, the `com.apple.quarantine` and
+//! `kMDItemWhereFroms` attributes on macOS and the `user.xdg.*.url`
+//! attributes on Linux. Windows and macOS warn before opening files that
+//! carry these marks, clearing them unblocks the file.
+
+use super::platform;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[cfg(windows)]
+const ORIGIN_ATTRIBUTES: &[&str] = &["Zone.Identifier"];
+#[cfg(target_os = "macos")]
+const ORIGIN_ATTRIBUTES: &[&str] = &[
+    "com.apple.quarantine",
+    "com.apple.metadata:kMDItemWhereFroms",
+];
+#[cfg(not(any(windows, target_os = "macos")))]
+const ORIGIN_ATTRIBUTES: &[&str] = &["user.xdg.origin.url", "user.xdg.referrer.url"];
+
+/// Zones from the internet zone up are the ones Windows warns about
+#[cfg(windows)]
+const INTERNET_ZONE_ID: u32 = 3;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DownloadOrigin {
+    /// Where the file was downloaded from
+    pub url: Option<String>,
+    /// The page the download was started from
+    pub referrer_url: Option<String>,
+    /// Windows security zone, 3 for the internet and 4 for restricted sites
+    pub zone_id: Option<u32>,
+    /// The app that downloaded the file, on macOS
+    pub agent: Option<String>,
+    /// Milliseconds since the Unix epoch, on macOS
+    pub downloaded_at: Option<u64>,
+    /// The system warns before opening the file
+    pub is_blocked: bool,
+}
+
+fn read_text(path: &Path, name: &str) -> Option<String> {
+    let value = platform::get(path, name).ok()?;
+    let text = String::from_utf8_lossy(&value).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// `[ZoneTransfer]` keys, such as `ZoneId=3` and `HostUrl=...`
+#[cfg(windows)]
+pub fn read_origin(path: &Path) -> Option<DownloadOrigin> {
+    let content = read_text(path, "Zone.Identifier")?;
+    let mut origin = DownloadOrigin::default();
+
+    for line in content.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match key.trim() {
+            "ZoneId" => origin.zone_id = value.parse().ok(),
+            "HostUrl" => origin.url = Some(value),
+            "ReferrerUrl" => origin.referrer_url = Some(value),
+            _ => {}
+        }
+    }
+
+    origin.is_blocked = origin
+        .zone_id
+        .is_some_and(|zone_id| zone_id >= INTERNET_ZONE_ID);
+    Some(origin)
+}
+
+/// The quarantine value is `flags;hex seconds;agent;event id`. The URLs are
+/// in `kMDItemWhereFroms`, a binary plist array of the download URL and
+/// the referrer.
+#[cfg(target_os = "macos")]
+pub fn read_origin(path: &Path) -> Option<DownloadOrigin> {
+    let quarantine = read_text(path, "com.apple.quarantine");
+    let where_froms: Vec<String> = platform::get(path, "com.apple.metadata:kMDItemWhereFroms")
+        .ok()
+        .and_then(|value| plist::from_bytes(&value).ok())
+        .unwrap_or_default();
+
+    if quarantine.is_none() && where_froms.is_empty() {
+        return None;
+    }
+
+    let mut origin = DownloadOrigin::default();
+    let mut where_froms = where_froms.into_iter();
+    origin.url = where_froms.next();
+    origin.referrer_url = where_froms.next();
+
+    if let Some(quarantine) = quarantine {
+        let fields: Vec<&str> = quarantine.split(';').collect();
+        origin.downloaded_at = fields
+            .get(1)
+            .and_then(|seconds| u64::from_str_radix(seconds, 16).ok())
+            .map(|seconds| seconds * 1000);
+        origin.agent = fields
+            .get(2)
+            .filter(|agent| !agent.is_empty())
+            .map(|agent| agent.to_string());
+        origin.is_blocked = true;
+    }
+
+    Some(origin)
+}
+
+/// Set by Chromium and Firefox. Linux doesn't block anything on them.
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn read_origin(path: &Path) -> Option<DownloadOrigin> {
+    let url = read_text(path, "user.xdg.origin.url");
+    let referrer_url = read_text(path, "user.xdg.referrer.url");
+
+    if url.is_none() && referrer_url.is_none() {
+        return None;
+    }
+
+    Some(DownloadOrigin {
+        url,
+        referrer_url,
+        ..DownloadOrigin::default()
+    })
+}
+
+/// Removes whichever of the download marks `path` has
+pub fn clear(path: &Path) -> Result<(), String> {
+    let present: Vec<String> = platform::list(path)
+        .map_err(|error| format!("Failed to list attributes: {}", error))?
+        .into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| ORIGIN_ATTRIBUTES.contains(&name.as_str()))
+        .collect();
+
+    for name in present {
+        platform::remove(path, &name)
+            .map_err(|error| format!("Failed to remove attribute '{}': {}", name, error))?;
+    }
+
+    Ok(())
+}
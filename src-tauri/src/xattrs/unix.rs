@@ -0,0 +1,177 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use libc::{c_char, c_void, ssize_t};
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+fn to_cstring(bytes: &[u8]) -> io::Result<CString> {
+    CString::new(bytes)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Value contains a null byte"))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn sys_list(path: *const c_char, buffer: *mut c_char, size: usize) -> ssize_t {
+    libc::listxattr(path, buffer, size)
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn sys_list(path: *const c_char, buffer: *mut c_char, size: usize) -> ssize_t {
+    libc::listxattr(path, buffer, size, 0)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn sys_get(
+    path: *const c_char,
+    name: *const c_char,
+    buffer: *mut c_void,
+    size: usize,
+) -> ssize_t {
+    libc::getxattr(path, name, buffer, size)
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn sys_get(
+    path: *const c_char,
+    name: *const c_char,
+    buffer: *mut c_void,
+    size: usize,
+) -> ssize_t {
+    libc::getxattr(path, name, buffer, size, 0, 0)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn sys_set(
+    path: *const c_char,
+    name: *const c_char,
+    value: *const c_void,
+    size: usize,
+) -> libc::c_int {
+    libc::setxattr(path, name, value, size, 0)
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn sys_set(
+    path: *const c_char,
+    name: *const c_char,
+    value: *const c_void,
+    size: usize,
+) -> libc::c_int {
+    libc::setxattr(path, name, value, size, 0, 0)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn sys_remove(path: *const c_char, name: *const c_char) -> libc::c_int {
+    libc::removexattr(path, name)
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn sys_remove(path: *const c_char, name: *const c_char) -> libc::c_int {
+    libc::removexattr(path, name, 0)
+}
+
+// Queries the required size first, then reads. Retries when the value grows
+// between the two calls.
+fn read_sized<F>(call: F) -> io::Result<Vec<u8>>
+where
+    F: Fn(*mut c_void, usize) -> ssize_t,
+{
+    loop {
+        let size = call(std::ptr::null_mut(), 0);
+
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut buffer = vec![0u8; size as usize];
+        let read = call(buffer.as_mut_ptr() as *mut c_void, buffer.len());
+
+        if read < 0 {
+            let error = io::Error::last_os_error();
+
+            if error.raw_os_error() == Some(libc::ERANGE) {
+                continue;
+            }
+
+            return Err(error);
+        }
+
+        buffer.truncate(read as usize);
+        return Ok(buffer);
+    }
+}
+
+fn value_size(path: &CString, name: &CString) -> io::Result<u64> {
+    let size = unsafe { sys_get(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(size as u64)
+}
+
+pub fn list(path: &Path) -> io::Result<Vec<(String, u64)>> {
+    let c_path = to_cstring(path.as_os_str().as_bytes())?;
+
+    let names = read_sized(|buffer, size| unsafe {
+        sys_list(c_path.as_ptr(), buffer as *mut c_char, size)
+    })?;
+
+    let mut attributes = Vec::new();
+
+    for raw_name in names.split(|byte| *byte == 0).filter(|name| !name.is_empty()) {
+        let c_name = to_cstring(raw_name)?;
+        // Attributes removed since listing are skipped
+        if let Ok(size) = value_size(&c_path, &c_name) {
+            attributes.push((String::from_utf8_lossy(raw_name).to_string(), size));
+        }
+    }
+
+    Ok(attributes)
+}
+
+pub fn get(path: &Path, name: &str) -> io::Result<Vec<u8>> {
+    let c_path = to_cstring(path.as_os_str().as_bytes())?;
+    let c_name = to_cstring(name.as_bytes())?;
+
+    read_sized(|buffer, size| unsafe { sys_get(c_path.as_ptr(), c_name.as_ptr(), buffer, size) })
+}
+
+pub fn set(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    let c_path = to_cstring(path.as_os_str().as_bytes())?;
+    let c_name = to_cstring(name.as_bytes())?;
+
+    let result = unsafe {
+        sys_set(
+            c_path.as_ptr(),
+            c_name.as_ptr(),
+            value.as_ptr() as *const c_void,
+            value.len(),
+        )
+    };
+
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+pub fn remove(path: &Path, name: &str) -> io::Result<()> {
+    let c_path = to_cstring(path.as_os_str().as_bytes())?;
+    let c_name = to_cstring(name.as_bytes())?;
+
+    if unsafe { sys_remove(c_path.as_ptr(), c_name.as_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use sigma_file_manager::testing::*;
+use std::fs;
+use std::io;
+
+// Short delays keep the retries from slowing the tests down
+fn retries(max_attempts: u32) -> Option<RetryPolicy> {
+    Some(RetryPolicy {
+        max_attempts,
+        initial_delay_ms: 1,
+        max_delay_ms: 1,
+    })
+}
+
+fn copy(sources: Vec<String>, destination: String, max_attempts: u32) -> FileOperationResult {
+    copy_items(
+        sources,
+        destination,
+        None,
+        None,
+        retries(max_attempts),
+        None,
+        None,
+    )
+}
+
+#[test]
+fn transient_errors_are_retried() {
+    let fixture = Scenario::new()
+        .file("source/flaky.txt", "contents")
+        .dir("destination")
+        .build()
+        .expect("fixture");
+
+    inject_fault(
+        FaultPoint::CopyFile,
+        fixture.path("source/flaky.txt"),
+        Fault::Error(io::ErrorKind::TimedOut),
+        Some(2),
+    );
+
+    let result = copy(
+        vec![fixture.uri("source/flaky.txt")],
+        fixture.uri("destination"),
+        3,
+    );
+
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.copied_count, Some(1));
+    assert_eq!(result.failed_count, Some(0));
+    assert_eq!(
+        result.retried_paths,
+        Some(vec![fixture.uri("source/flaky.txt")])
+    );
+    assert_eq!(
+        fs::read(fixture.path("destination/flaky.txt")).expect("copy"),
+        b"contents"
+    );
+}
+
+#[test]
+fn files_inside_folders_are_retried() {
+    let fixture = Scenario::new()
+        .file("source/folder/flaky.txt", "flaky")
+        .file("source/folder/steady.txt", "steady")
+        .dir("destination")
+        .build()
+        .expect("fixture");
+
+    inject_fault(
+        FaultPoint::CopyFile,
+        fixture.path("source/folder/flaky.txt"),
+        Fault::Error(io::ErrorKind::TimedOut),
+        Some(1),
+    );
+
+    let result = copy(
+        vec![fixture.uri("source/folder")],
+        fixture.uri("destination"),
+        2,
+    );
+
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.copied_count, Some(1));
+    assert_eq!(
+        result.retried_paths,
+        Some(vec![fixture.uri("source/folder/flaky.txt")])
+    );
+    assert!(fixture.path("destination/folder/flaky.txt").is_file());
+    assert!(fixture.path("destination/folder/steady.txt").is_file());
+}
+
+#[test]
+fn transient_errors_fail_once_attempts_run_out() {
+    let fixture = Scenario::new()
+        .file("source/flaky.txt", "contents")
+        .dir("destination")
+        .build()
+        .expect("fixture");
+
+    inject_fault(
+        FaultPoint::CopyFile,
+        fixture.path("source/flaky.txt"),
+        Fault::Error(io::ErrorKind::TimedOut),
+        Some(3),
+    );
+
+    let result = copy(
+        vec![fixture.uri("source/flaky.txt")],
+        fixture.uri("destination"),
+        3,
+    );
+
+    assert!(!result.success);
+    assert!(result.error.is_some());
+    assert_eq!(result.copied_count, Some(0));
+    assert_eq!(result.failed_count, Some(1));
+    assert_eq!(
+        result.retried_paths,
+        Some(vec![fixture.uri("source/flaky.txt")])
+    );
+}
+
+#[test]
+fn other_errors_fail_without_retrying() {
+    let fixture = Scenario::new()
+        .file("source/denied.txt", "denied")
+        .file("source/allowed.txt", "allowed")
+        .dir("destination")
+        .build()
+        .expect("fixture");
+
+    inject_fault(
+        FaultPoint::CopyFile,
+        fixture.path("source/denied.txt"),
+        Fault::PermissionDenied,
+        Some(1),
+    );
+
+    let result = copy(
+        vec![
+            fixture.uri("source/denied.txt"),
+            fixture.uri("source/allowed.txt"),
+        ],
+        fixture.uri("destination"),
+        3,
+    );
+
+    assert!(!result.success);
+    assert_eq!(result.copied_count, Some(1));
+    assert_eq!(result.failed_count, Some(1));
+    assert_eq!(result.retried_paths, None);
+    assert!(!fixture.path("destination/denied.txt").exists());
+    assert!(fixture.path("destination/allowed.txt").is_file());
+}
@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+//! Times folder copies with the worker count picked for the drives against
+//! fixed counts, to check the defaults of `workers_for_drive`. Ignored by
+//! default, as it is slow and its numbers depend on the machine:
+//!
+//! ```text
+//! cargo test --release --features test-fixtures --test copy_workers -- --ignored --nocapture
+//! ```
+//!
+//! The source tree is created in the temp folder. Set
+//! `SIGMA_BENCH_DESTINATION` to a folder on another drive, such as a USB
+//! stick, a hard disk or a network share, to time copies onto it.
+
+use sigma_file_manager::testing::*;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const FILE_COUNT: usize = 4000;
+const FILE_SIZE: usize = 16 * 1024;
+const FILES_PER_FOLDER: usize = 100;
+const RUNS: usize = 3;
+const WORKER_COUNTS: [usize; 6] = [1, 2, 4, 8, 16, 32];
+
+fn source_tree() -> Fixture {
+    let contents = vec![b'x'; FILE_SIZE];
+    let mut scenario = Scenario::new().dir("destination");
+
+    for file_index in 0..FILE_COUNT {
+        scenario = scenario.file(
+            format!(
+                "source/folder-{}/file-{}.bin",
+                file_index / FILES_PER_FOLDER,
+                file_index
+            ),
+            &contents,
+        );
+    }
+
+    scenario.build().expect("fixture")
+}
+
+fn destination_root(fixture: &Fixture) -> PathBuf {
+    match std::env::var_os("SIGMA_BENCH_DESTINATION") {
+        Some(destination) => PathBuf::from(destination),
+        None => fixture.path("destination"),
+    }
+}
+
+/// The fastest of `RUNS` copies into fresh folders
+fn time_copy(fixture: &Fixture, workers: Option<usize>) -> Duration {
+    let mut fastest = Duration::MAX;
+
+    for _ in 0..RUNS {
+        let destination = destination_root(fixture).join(format!(
+            "sigma-bench-{}",
+            workers.map_or("default".to_string(), |workers| workers.to_string())
+        ));
+        let _ = fs::remove_dir_all(&destination);
+        fs::create_dir_all(&destination).expect("destination");
+
+        let started = Instant::now();
+        let result = copy_items(
+            vec![fixture.uri("source")],
+            destination.to_string_lossy().to_string(),
+            None,
+            None,
+            None,
+            None,
+            Some(CopyOptions {
+                workers,
+                ..CopyOptions::default()
+            }),
+        );
+        fastest = fastest.min(started.elapsed());
+
+        assert!(result.success, "{:?}", result.error);
+        fs::remove_dir_all(&destination).expect("cleanup");
+    }
+
+    fastest
+}
+
+#[test]
+#[ignore]
+fn default_workers_against_fixed_counts() {
+    let fixture = source_tree();
+    println!(
+        "{} files of {} KiB into {}",
+        FILE_COUNT,
+        FILE_SIZE / 1024,
+        destination_root(&fixture).display()
+    );
+
+    let default_time = time_copy(&fixture, None);
+    println!("default: {:>8.0?}", default_time);
+
+    let mut fastest = (1, Duration::MAX);
+    for workers in WORKER_COUNTS {
+        let time = time_copy(&fixture, Some(workers));
+        println!("{:>7}: {:>8.0?}", workers, time);
+
+        if time < fastest.1 {
+            fastest = (workers, time);
+        }
+    }
+
+    println!(
+        "fastest: {} workers, the default took {:.0}% longer",
+        fastest.0,
+        (default_time.as_secs_f64() / fastest.1.as_secs_f64() - 1.0) * 100.0
+    );
+}
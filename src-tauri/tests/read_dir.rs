@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+use sigma_file_manager::testing::*;
+use std::collections::BTreeSet;
+
+fn is_skipped(fixture: &Fixture, relative: &str) -> bool {
+    fixture.skipped.iter().any(|(path, _)| path == relative)
+}
+
+#[test]
+fn lists_symlinks_and_hidden_files() {
+    let fixture = Scenario::new()
+        .file("listing/target.txt", "target")
+        .dir("listing/folder")
+        .hidden_file("listing/.hidden")
+        .symlink("listing/link.txt", "listing/target.txt")
+        .symlink("listing/folder-link", "listing/folder")
+        .build()
+        .expect("fixture");
+
+    let contents = read_dir(fixture.uri("listing"), None, None, None).expect("listing");
+    let entry = |name: &str| contents.entries.iter().find(|entry| entry.name == name);
+
+    let hidden = entry(".hidden").expect("hidden file is listed");
+    assert!(hidden.is_hidden);
+    assert!(!entry("target.txt").expect("target is listed").is_hidden);
+
+    if !is_skipped(&fixture, "listing/link.txt") {
+        let link = entry("link.txt").expect("file link is listed");
+        assert!(link.is_symlink);
+        assert!(link.is_file);
+        assert_eq!(link.size, "target".len() as u64);
+    }
+
+    if !is_skipped(&fixture, "listing/folder-link") {
+        let link = entry("folder-link").expect("folder link is listed");
+        assert!(link.is_symlink);
+        assert!(link.is_dir);
+    }
+
+    // Folders come first
+    let first_file = contents.entries.iter().position(|entry| !entry.is_dir);
+    let last_dir = contents.entries.iter().rposition(|entry| entry.is_dir);
+    if let (Some(first_file), Some(last_dir)) = (first_file, last_dir) {
+        assert!(last_dir < first_file);
+    }
+    assert_eq!(contents.total_count, contents.entries.len());
+}
+
+#[test]
+fn lists_odd_names_unchanged() {
+    let fixture = Scenario::new().weird_names("odd").build().expect("fixture");
+
+    let expected: BTreeSet<String> = weird_names()
+        .into_iter()
+        .filter(|name| !is_skipped(&fixture, &format!("odd/{}", name)))
+        .collect();
+
+    let contents = read_dir(fixture.uri("odd"), None, None, None).expect("listing");
+    let listed: BTreeSet<String> = contents
+        .entries
+        .iter()
+        .map(|entry| entry.name.clone())
+        .collect();
+
+    assert_eq!(listed, expected);
+
+    for entry in &contents.entries {
+        assert_eq!(entry.path, fixture.uri(format!("odd/{}", entry.name)));
+    }
+}
+
+#[test]
+fn item_counts_are_only_read_on_request() {
+    let fixture = Scenario::new()
+        .file("counts/folder/one.txt", "1")
+        .file("counts/folder/two.txt", "2")
+        .build()
+        .expect("fixture");
+
+    let without = read_dir(fixture.uri("counts"), None, None, None).expect("listing");
+    assert_eq!(without.entries[0].item_count, None);
+
+    let with = read_dir(fixture.uri("counts"), None, None, Some(true)).expect("listing");
+    assert_eq!(with.entries[0].item_count, Some(2));
+}
+
+#[test]
+fn injected_read_dir_fault_fails_the_listing_once() {
+    let fixture = Scenario::new()
+        .deep_tree("deep", 3, 2)
+        .build()
+        .expect("fixture");
+
+    inject_fault(
+        FaultPoint::ReadDir,
+        fixture.path("deep"),
+        Fault::PermissionDenied,
+        Some(1),
+    );
+
+    assert!(read_dir(fixture.uri("deep"), None, None, None).is_err());
+
+    let contents = read_dir(fixture.uri("deep"), None, None, None).expect("listing");
+    assert_eq!(contents.dir_count, 1);
+    assert_eq!(contents.file_count, 0);
+}
@@ -4,17 +4,19 @@
 
 import { ref, onMounted, onUnmounted } from 'vue';
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import type { DriveInfo } from '@/types/drive-info';
 import { useUserSettingsStore } from '@/stores/storage/user-settings';
 
-const DRIVE_POLL_INTERVAL_MS = 1000;
+const DRIVE_EVENTS = ['drive-added', 'drive-removed', 'drive-changed'];
 
 const drives = ref<DriveInfo[]>([]);
 const isLoading = ref(false);
 const error = ref<string | null>(null);
 
-let pollIntervalId: ReturnType<typeof setInterval> | null = null;
+let driveEventUnlisteners: UnlistenFn[] = [];
+let isListening = false;
 let activeSubscribers = 0;
 let previousDriveCount = 0;
 let isInitialFetch = true;
@@ -62,21 +64,30 @@ async function initialFetch() {
   isLoading.value = false;
 }
 
-function startPolling() {
-  if (pollIntervalId !== null) {
+async function startListening() {
+  if (isListening) {
     return;
   }
 
-  pollIntervalId = setInterval(fetchDrives, DRIVE_POLL_INTERVAL_MS);
-}
+  isListening = true;
+  const unlisteners = await Promise.all(
+    DRIVE_EVENTS.map((eventName) => listen(eventName, fetchDrives)),
+  );
 
-function stopPolling() {
-  if (pollIntervalId !== null) {
-    clearInterval(pollIntervalId);
-    pollIntervalId = null;
+  if (isListening) {
+    driveEventUnlisteners = unlisteners;
+  }
+  else {
+    unlisteners.forEach((unlisten) => unlisten());
   }
 }
 
+function stopListening() {
+  isListening = false;
+  driveEventUnlisteners.forEach((unlisten) => unlisten());
+  driveEventUnlisteners = [];
+}
+
 async function refresh() {
   await fetchDrives();
 }
@@ -102,7 +113,7 @@ export function useDrives() {
 
     if (activeSubscribers === 1) {
       initialFetch();
-      startPolling();
+      startListening();
     }
   });
 
@@ -110,7 +121,7 @@ export function useDrives() {
     activeSubscribers--;
 
     if (activeSubscribers === 0) {
-      stopPolling();
+      stopListening();
     }
   });
 
@@ -4,6 +4,7 @@
 
 import { ref, computed, onUnmounted, type Ref } from 'vue';
 import { startDrag as startOutboundDrag } from '@crabnebula/tauri-plugin-drag';
+import { invoke } from '@tauri-apps/api/core';
 import { resolveResource } from '@tauri-apps/api/path';
 import type { DirEntry } from '@/types/dir-entry';
 import { UI_CONSTANTS } from '@/constants';
@@ -159,7 +160,14 @@ export function useFileBrowserDrag(options: {
     }
   }
 
-  async function getDragIconPath(): Promise<string> {
+  async function getDragIconPath(filePaths: string[]): Promise<string> {
+    try {
+      return await invoke<string>('get_drag_preview', { paths: filePaths });
+    }
+    catch {
+      // Fall back to the app icon when no native icons are available
+    }
+
     if (!cachedDragIconPath) {
       cachedDragIconPath = await resolveResource('icons/32x32.png');
     }
@@ -213,7 +221,7 @@ export function useFileBrowserDrag(options: {
     isOutboundDragActive = true;
     const filePaths = dragItems.value.map(item => item.path);
     const dragMode = operationType.value;
-    const iconPath = await getDragIconPath();
+    const iconPath = await getDragIconPath(filePaths);
 
     cleanup();
 
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+import {
+  watch, onUnmounted, type Ref, type ComputedRef,
+} from 'vue';
+import type { DirEntry } from '@/types/dir-entry';
+
+const LOAD_DELAY_MS = 100;
+const FOLDER_ROW_SELECTOR = '[data-entry-path][data-drop-target]';
+
+interface UseFileBrowserItemCountsOptions {
+  entries: ComputedRef<DirEntry[]>;
+  entriesContainerRef: Ref<HTMLElement | null>;
+  loadAll: ComputedRef<boolean>;
+  loadItemCounts: (paths: string[]) => Promise<void>;
+}
+
+// Counts the items of the folders whose rows are on screen, once scrolling
+// settles. Sorting by item count needs every count, so then all folders
+// are counted and the list re-sorts as they come in.
+export function useFileBrowserItemCounts(options: UseFileBrowserItemCountsOptions) {
+  const visiblePaths = new Set<string>();
+  let loadTimer: ReturnType<typeof setTimeout> | null = null;
+
+  function scheduleLoad() {
+    if (loadTimer) {
+      clearTimeout(loadTimer);
+    }
+
+    loadTimer = setTimeout(() => {
+      loadTimer = null;
+      const paths = options.loadAll.value
+        ? options.entries.value.filter(entry => entry.is_dir).map(entry => entry.path)
+        : [...visiblePaths];
+
+      if (paths.length > 0) {
+        void options.loadItemCounts(paths);
+      }
+    }, LOAD_DELAY_MS);
+  }
+
+  const observer = new IntersectionObserver((observedRows) => {
+    for (const observedRow of observedRows) {
+      const path = (observedRow.target as HTMLElement).dataset.entryPath;
+
+      if (!path) {
+        continue;
+      }
+
+      if (observedRow.isIntersecting) {
+        visiblePaths.add(path);
+      }
+      else {
+        visiblePaths.delete(path);
+      }
+    }
+
+    scheduleLoad();
+  });
+
+  // Rows are observed anew after each render, which reports the visible
+  // ones again
+  function observeRows() {
+    observer.disconnect();
+    visiblePaths.clear();
+
+    const container = options.entriesContainerRef.value;
+
+    if (!container) {
+      return;
+    }
+
+    container.querySelectorAll(FOLDER_ROW_SELECTOR).forEach(row => observer.observe(row));
+  }
+
+  watch([options.entries, options.entriesContainerRef], observeRows, { flush: 'post' });
+
+  watch(options.loadAll, (loadAll) => {
+    if (loadAll) {
+      scheduleLoad();
+    }
+  });
+
+  onUnmounted(() => {
+    observer.disconnect();
+
+    if (loadTimer) {
+      clearTimeout(loadTimer);
+      loadTimer = null;
+    }
+  });
+}
@@ -30,6 +30,7 @@ interface DirChangePayload {
 
 const DIRECTORY_DWELL_TIME_MS = 3000;
 const WATCHER_DEBOUNCE_MS = 500;
+const ITEM_COUNT_BATCH_SIZE = 200;
 
 function getParentOfPath(path: string): string | null {
   const parts = path.split('/').filter(Boolean);
@@ -69,6 +70,7 @@ export function useFileBrowserNavigation(
   let dirChangeUnlisten: UnlistenFn | null = null;
   let watcherRefreshTimer: ReturnType<typeof setTimeout> | null = null;
   let watcherValidationTimer: ReturnType<typeof setTimeout> | null = null;
+  const pendingItemCounts = new Set<string>();
 
   function cancelPendingDirectoryRecord() {
     if (pendingDirectoryRecordTimer) {
@@ -181,8 +183,12 @@ export function useFileBrowserNavigation(
     isRefreshing.value = true;
 
     try {
-      const result = await invoke<DirContents>('read_dir', { path: currentPath.value });
+      const result = await invoke<DirContents>('read_dir', {
+        path: currentPath.value,
+        includeItemCounts: false,
+      });
 
+      keepItemCounts(dirContents.value, result);
       dirContents.value = result;
 
       const currentTab = tab();
@@ -208,12 +214,78 @@ export function useFileBrowserNavigation(
     }
   }
 
+  // A refresh keeps the counts of folders that haven't changed since
+  function keepItemCounts(previous: DirContents | null, next: DirContents): void {
+    if (!previous) {
+      return;
+    }
+
+    const knownEntries = new Map(
+      previous.entries
+        .filter(entry => entry.is_dir && entry.item_count !== null)
+        .map(entry => [entry.path, entry]),
+    );
+
+    for (const entry of next.entries) {
+      const knownEntry = knownEntries.get(entry.path);
+
+      if (knownEntry && knownEntry.modified_time === entry.modified_time) {
+        entry.item_count = knownEntry.item_count;
+      }
+    }
+  }
+
+  // Listings come without the item counts of their folders, which take a
+  // read per folder, so they are loaded for the rows on screen. Folders
+  // already counted or being counted are skipped.
+  async function loadItemCounts(paths: string[]): Promise<void> {
+    const contents = dirContents.value;
+
+    if (!contents) {
+      return;
+    }
+
+    const requestedPaths = new Set(paths);
+    const dirEntries = contents.entries.filter(entry => entry.is_dir
+      && entry.item_count === null
+      && requestedPaths.has(entry.path)
+      && !pendingItemCounts.has(entry.path));
+
+    for (let start = 0; start < dirEntries.length; start += ITEM_COUNT_BATCH_SIZE) {
+      const batch = dirEntries.slice(start, start + ITEM_COUNT_BATCH_SIZE);
+      batch.forEach(entry => pendingItemCounts.add(entry.path));
+
+      try {
+        const counts = await invoke<Record<string, number>>('get_item_counts', {
+          paths: batch.map(entry => entry.path),
+        });
+
+        if (dirContents.value !== contents) {
+          return;
+        }
+
+        for (const entry of batch) {
+          entry.item_count = counts[entry.path] ?? null;
+        }
+      }
+      catch {
+        return;
+      }
+      finally {
+        batch.forEach(entry => pendingItemCounts.delete(entry.path));
+      }
+    }
+  }
+
   async function navigateToNearestExistingAncestor(): Promise<void> {
     let pathToTry = getParentOfPath(currentPath.value);
 
     while (pathToTry) {
       try {
-        await invoke<DirContents>('read_dir', { path: pathToTry });
+        await invoke<DirContents>('read_dir', {
+          path: pathToTry,
+          includeItemCounts: false,
+        });
         await readDir(pathToTry);
         return;
       }
@@ -229,7 +301,10 @@ export function useFileBrowserNavigation(
     if (!currentPath.value) return;
 
     try {
-      await invoke<DirContents>('read_dir', { path: currentPath.value });
+      await invoke<DirContents>('read_dir', {
+        path: currentPath.value,
+        includeItemCounts: false,
+      });
     }
     catch {
       await navigateToNearestExistingAncestor();
@@ -276,7 +351,10 @@ export function useFileBrowserNavigation(
     }
 
     try {
-      const result = await invoke<DirContents>('read_dir', { path });
+      const result = await invoke<DirContents>('read_dir', {
+        path,
+        includeItemCounts: false,
+      });
 
       dirContents.value = result;
       currentPath.value = result.path;
@@ -304,6 +382,7 @@ export function useFileBrowserNavigation(
 
         history.value.push(result.path);
         historyIndex.value = history.value.length - 1;
+        invoke('record_visit', { path: result.path }).catch(() => {});
       }
 
       nextTick(() => {
@@ -460,6 +539,7 @@ export function useFileBrowserNavigation(
     currentDirEntry,
     readDir,
     silentRefresh,
+    loadItemCounts,
     navigateToPath,
     navigateToEntry,
     navigateToParent,
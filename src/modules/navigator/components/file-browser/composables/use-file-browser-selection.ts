@@ -13,6 +13,7 @@ import { useClipboardStore, type FileOperationResult, type ConflictItem, type Co
 import { useDirSizesStore } from '@/stores/runtime/dir-sizes';
 import { toast, CustomProgress, CustomSimple } from '@/components/ui/toaster';
 import { UI_CONSTANTS } from '@/constants';
+import { resolveBackendMessage } from '@/utils/backend-message';
 
 export function useFileBrowserSelection(
   entriesRef: Ref<DirEntry[]>,
@@ -417,7 +418,7 @@ export function useFileBrowserSelection(
       }, 2500);
     }
     else {
-      let errorMessage = result.error || '';
+      let errorMessage = resolveBackendMessage(result.error);
 
       if (errorMessage.includes('same directory')) {
         errorMessage = t('fileBrowser.cannotMoveToSameDirectory');
@@ -575,7 +576,7 @@ export function useFileBrowserSelection(
         toastData.value.title = isCopy
           ? t('fileBrowser.copyFailed')
           : t('fileBrowser.moveFailed');
-        toastData.value.description = result.error || '';
+        toastData.value.description = resolveBackendMessage(result.error);
         toastData.value.actionText = t('close');
         toastData.value.progress = 0;
         toastData.value.itemCount = 0;
@@ -665,7 +666,7 @@ export function useFileBrowserSelection(
         toast.custom(markRaw(CustomSimple), {
           componentProps: {
             title: t('notifications.failedToRenameItem'),
-            description: result.error || '',
+            description: resolveBackendMessage(result.error),
           },
         });
         return false;
@@ -731,7 +732,7 @@ export function useFileBrowserSelection(
             title: itemType === 'directory'
               ? t('dialogs.newDirItemDialog.failedToCreateNewDirectory')
               : t('dialogs.newDirItemDialog.failedToCreateNewFile'),
-            description: result.error || '',
+            description: resolveBackendMessage(result.error),
           },
         });
         return false;
@@ -936,7 +937,7 @@ export function useFileBrowserSelection(
         toastData.value.title = useTrash
           ? t('notifications.errorTrashItems')
           : t('notifications.errorDeleteItems');
-        toastData.value.description = result.error || '';
+        toastData.value.description = resolveBackendMessage(result.error);
         toastData.value.actionText = t('close');
         toastData.value.progress = 0;
         toastData.value.itemCount = 0;
@@ -24,6 +24,7 @@ import { useFileBrowserLifecycle } from './use-file-browser-lifecycle';
 import { useFileBrowserDrag } from './use-file-browser-drag';
 import { useFileBrowserExternalDrop } from './use-file-browser-external-drop';
 import { useVideoThumbnails } from './use-video-thumbnails';
+import { useFileBrowserItemCounts } from './use-file-browser-item-counts';
 import { sortFileBrowserEntries } from '@/modules/navigator/components/file-browser/utils/file-browser-sort';
 
 export interface UseFileBrowserOptions {
@@ -60,6 +61,7 @@ interface DataSource {
   handlePathSubmit: () => Promise<void>;
   openFile: (path: string) => Promise<void>;
   silentRefresh: () => void;
+  loadItemCounts: (paths: string[]) => Promise<void>;
   filterQuery: Ref<string>;
   isFilterOpen: Ref<boolean>;
   toggleFilter: () => void;
@@ -136,6 +138,7 @@ function setupNavigationDataSource(
     handlePathSubmit: navigation.handlePathSubmit,
     openFile: navigation.openFile,
     silentRefresh: navigation.silentRefresh,
+    loadItemCounts: navigation.loadItemCounts,
     filterQuery: filter.filterQuery,
     isFilterOpen: filter.isFilterOpen,
     toggleFilter: filter.toggleFilter,
@@ -189,6 +192,7 @@ function setupExternalDataSource(options: UseFileBrowserOptions): DataSource {
     silentRefresh: () => {
       globalSearchStore.search();
     },
+    loadItemCounts: async () => {},
     filterQuery: ref(''),
     isFilterOpen: ref(false),
     toggleFilter: () => {},
@@ -201,6 +205,7 @@ export function useFileBrowser(options: UseFileBrowserOptions) {
   const isExternalMode = !!options.externalEntries;
   const quickViewStore = useQuickViewStore();
   const clipboardStore = useClipboardStore();
+  const userSettingsStore = useUserSettingsStore();
 
   const dataSource = isExternalMode
     ? setupExternalDataSource(options)
@@ -237,6 +242,16 @@ export function useFileBrowser(options: UseFileBrowserOptions) {
     clearPendingFocusRequest: selection.clearPendingFocusRequest,
   });
 
+  if (!isExternalMode) {
+    useFileBrowserItemCounts({
+      entries: dataSource.entries,
+      entriesContainerRef,
+      loadAll: computed(() => options.layout() === 'list'
+        && userSettingsStore.userSettings.navigator.listSortColumn === 'items'),
+      loadItemCounts: dataSource.loadItemCounts,
+    });
+  }
+
   const videoThumbnails = !isExternalMode
     ? useVideoThumbnails()
     : { getVideoThumbnail: () => undefined };
@@ -15,12 +15,40 @@ export interface ClipboardState {
   items: DirEntry[];
 }
 
+export interface PlannedAction {
+  action: 'copy' | 'move' | 'replace' | 'rename' | 'skip' | 'delete' | 'trash' | 'error';
+  source_path: string;
+  destination_path: string | null;
+  is_dir: boolean;
+  size: number;
+  file_count: number;
+  has_conflict: boolean;
+  error: string | null;
+}
+
+export interface OperationPlan {
+  operation: string;
+  actions: PlannedAction[];
+  item_count: number;
+  file_count: number;
+  total_bytes: number;
+  conflict_count: number;
+  skipped_count: number;
+  error_count: number;
+  required_bytes: number;
+  available_bytes: number | null;
+  has_enough_space: boolean | null;
+  destination_volume?: string | null;
+}
+
 export interface FileOperationResult {
   success: boolean;
   error?: string;
   copied_count?: number;
   failed_count?: number;
   skipped_count?: number;
+  plan?: OperationPlan;
+  retried_paths?: string[];
 }
 
 export interface ConflictItem {
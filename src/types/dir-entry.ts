@@ -8,6 +8,18 @@ export interface DirContents {
   total_count: number;
   dir_count: number;
   file_count: number;
+  git_repository?: GitRepoSummary;
+}
+
+export type GitFileStatus = 'untracked' | 'staged' | 'modified' | 'conflicted';
+
+export interface GitRepoSummary {
+  root: string;
+  branch: string | null;
+  upstream: string | null;
+  ahead: number;
+  behind: number;
+  head_commit: string | null;
 }
 
 export type ExtendedVirtualEntry = {
@@ -30,6 +42,19 @@ export type DirEntry = {
   is_dir: boolean;
   is_symlink: boolean;
   is_hidden: boolean;
+  hard_link_count?: number | null;
+  file_id?: string | null;
+  mode?: number | null;
+  owner?: string | null;
+  group?: string | null;
+  tags?: FileTag[] | null;
+  git_ignored?: boolean;
+  git_status?: GitFileStatus;
+};
+
+export type FileTag = {
+  name: string;
+  color: string | null;
 };
 
 export type Divider = {
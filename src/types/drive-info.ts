@@ -23,4 +23,6 @@ export type MountableDevice = {
   device_path: string;
   file_system: string;
   size: number;
+  is_encrypted: boolean;
+  is_locked: boolean;
 };
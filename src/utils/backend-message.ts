@@ -0,0 +1,45 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// License: GNU GPLv3 or later. See the license file in the project root for more information.
+// Copyright © 2021 - present Aleksey Hoffman. All rights reserved.
+
+import { i18n } from '@/localization';
+
+interface BackendMessage {
+  key: string;
+  params: Record<string, string>;
+  message: string;
+}
+
+function parseBackendMessage(text: string): BackendMessage | null {
+  if (!text.startsWith('{')) {
+    return null;
+  }
+
+  try {
+    const parsed = JSON.parse(text);
+    return typeof parsed?.key === 'string' && typeof parsed?.message === 'string'
+      ? parsed
+      : null;
+  }
+  catch {
+    return null;
+  }
+}
+
+/**
+ * Translates a message produced by the backend (see src-tauri/src/messages.rs).
+ * Plain strings are returned as is.
+ */
+export function resolveBackendMessage(value: unknown): string {
+  const text = value instanceof Error ? value.message : String(value ?? '');
+  const backendMessage = parseBackendMessage(text);
+
+  if (!backendMessage) {
+    return text;
+  }
+
+  const { t, te } = i18n.global;
+  const key = `backendMessages.${backendMessage.key}`;
+
+  return te(key) ? t(key, backendMessage.params ?? {}) : backendMessage.message;
+}